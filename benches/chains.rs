@@ -0,0 +1,295 @@
+//!
+//! Benchmark of building chains.
+//! Run with `cargo bench`. Reports the fastest and median time over several iterations.
+//!
+//! Frame of ~100 passes and ~500 resources is built, and lookups of chains by id are compared
+//! to lookups in a hash map. Then layered graph is generated and collection on single queue,
+//! scheduling on several queues and synchronization are measured separately.
+//! Size of the layered graph can be given as `cargo bench -- PASSES RESOURCES FAN_OUT QUEUES`,
+//! 300 passes, 800 resources, fan-out of 4 and 3 queues by default.
+//!
+
+extern crate fnv;
+extern crate gfx_chain;
+extern crate gfx_hal as hal;
+
+use fnv::FnvHashMap;
+use hal::pso::PipelineStage;
+use hal::queue::QueueFamilyId;
+use std::env;
+use std::time::{Duration, Instant};
+
+use gfx_chain::build;
+use gfx_chain::chain::Chain;
+use gfx_chain::collect::{collect, Chains};
+use gfx_chain::pass::{Pass, PassBuilder, PassId};
+use gfx_chain::resource::{Buffer, Id, Image, ResourceInfo, Resources};
+use gfx_chain::sync::{sync, SyncData};
+use gfx_chain::schedule::Schedule;
+
+const PASSES: u32 = 100;
+const BUFFERS: u32 = 250;
+const IMAGES: u32 = 250;
+const RESOURCES_PER_PASS: u32 = 12;
+const ITERATIONS: usize = 50;
+
+/// Deterministic generator so every run measures the same graph.
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self, bound: u32) -> u32 {
+        self.0 = self
+            .0
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        ((self.0 >> 33) % bound as u64) as u32
+    }
+}
+
+fn passes() -> Vec<Pass> {
+    let mut rng = Lcg(0x5eed);
+    let stages = PipelineStage::VERTEX_SHADER | PipelineStage::FRAGMENT_SHADER;
+    (0..PASSES)
+        .map(|index| {
+            let mut builder = PassBuilder::new(PassId(index as usize), QueueFamilyId(0));
+            if index > 0 {
+                builder = builder.depends_on(PassId(rng.next(index) as usize));
+            }
+            // Every resource is declared at most once by the pass.
+            let first = rng.next(BUFFERS + IMAGES);
+            for offset in 0..RESOURCES_PER_PASS {
+                let resource = (first + offset * 41) % (BUFFERS + IMAGES);
+                let write = rng.next(4) == 0;
+                builder = if resource < BUFFERS {
+                    let id = Id::new(resource);
+                    if write {
+                        builder.storage_buffer_write(id, stages)
+                    } else {
+                        builder.storage_buffer_read(id, stages)
+                    }
+                } else {
+                    let id = Id::new(resource - BUFFERS);
+                    if write {
+                        builder.color_attachment(id)
+                    } else {
+                        builder.sample_image(id, stages)
+                    }
+                };
+            }
+            builder.build().unwrap()
+        })
+        .collect()
+}
+
+fn resources() -> Resources {
+    let mut resources = Resources::new();
+    for index in 0..BUFFERS {
+        resources.add_buffer(Id::new(index), ResourceInfo::default());
+    }
+    for index in 0..IMAGES {
+        resources.add_image(Id::new(index), ResourceInfo::default());
+    }
+    resources
+}
+
+fn micros(duration: Duration) -> f64 {
+    duration.as_secs() as f64 * 1e6 + f64::from(duration.subsec_nanos()) / 1e3
+}
+
+/// Run the closure several times and print the fastest and median time.
+fn bench<T, F>(name: &str, mut f: F) -> T
+where
+    F: FnMut() -> T,
+{
+    let mut times = Vec::with_capacity(ITERATIONS);
+    let mut result = None;
+    for _ in 0..ITERATIONS {
+        let start = Instant::now();
+        result = Some(f());
+        times.push(start.elapsed());
+    }
+    times.sort();
+    println!(
+        "{:<24} min {:>10.1} us   median {:>10.1} us",
+        name,
+        micros(times[0]),
+        micros(times[ITERATIONS / 2])
+    );
+    result.unwrap()
+}
+
+/// Look up the link of every access of every submission. Returns number of links found.
+fn lookup_dense(chains: &Chains<SyncData<u32, u32>>) -> usize {
+    let mut found = 0;
+    for queue in chains.schedule.iter().flat_map(|family| family.iter()) {
+        for (_, submission) in queue.iter() {
+            for (id, &link) in submission.buffers() {
+                found += chains.buffers[id].links().get(link).is_some() as usize;
+            }
+            for (id, &link) in submission.images() {
+                found += chains.images[id].links().get(link).is_some() as usize;
+            }
+        }
+    }
+    found
+}
+
+/// Same as `lookup_dense` but with chains in hash maps.
+fn lookup_hashed(
+    chains: &Chains<SyncData<u32, u32>>,
+    buffers: &FnvHashMap<Id<Buffer>, &Chain<Buffer>>,
+    images: &FnvHashMap<Id<Image>, &Chain<Image>>,
+) -> usize {
+    let mut found = 0;
+    for queue in chains.schedule.iter().flat_map(|family| family.iter()) {
+        for (_, submission) in queue.iter() {
+            for (id, &link) in submission.buffers() {
+                found += buffers[id].links().get(link).is_some() as usize;
+            }
+            for (id, &link) in submission.images() {
+                found += images[id].links().get(link).is_some() as usize;
+            }
+        }
+    }
+    found
+}
+
+fn main() {
+    let passes = passes();
+    let resources = resources();
+    println!("{} passes, {} buffers, {} images", PASSES, BUFFERS, IMAGES);
+
+    let chains = bench("build", || {
+        let mut semaphores = 0;
+        build(
+            passes.clone(),
+            &resources,
+            |_| 1,
+            || {
+                semaphores += 1;
+                (semaphores, semaphores)
+            },
+        )
+        .unwrap()
+    });
+
+    let buffers: FnvHashMap<_, _> = chains
+        .buffers
+        .iter()
+        .map(|(&id, chain)| (id, chain))
+        .collect();
+    let images: FnvHashMap<_, _> = chains
+        .images
+        .iter()
+        .map(|(&id, chain)| (id, chain))
+        .collect();
+    let dense = bench("lookup chains by id", || {
+        (0..100).map(|_| lookup_dense(&chains)).sum::<usize>()
+    });
+    let hashed = bench("lookup chains hashed", || {
+        (0..100)
+            .map(|_| lookup_hashed(&chains, &buffers, &images))
+            .sum::<usize>()
+    });
+    assert_eq!(dense, hashed);
+
+    let mut args = env::args()
+        .skip(1)
+        .filter(|arg| !arg.starts_with("--"))
+        .map(|arg| arg.parse().expect("Size of layered graph must be a number"));
+    let params = LayeredParams {
+        passes: args.next().unwrap_or(300),
+        resources: args.next().unwrap_or(800),
+        fan_out: args.next().unwrap_or(4),
+        queues: args.next().unwrap_or(3),
+    };
+    bench_layered(&params);
+}
+
+/// Size of the layered graph.
+struct LayeredParams {
+    passes: u32,
+    resources: u32,
+    fan_out: u32,
+    queues: u32,
+}
+
+/// Generate layers of `fan_out` passes. Each pass depends on up to `fan_out` passes
+/// of the previous layer and reads one resource written by each of them.
+/// Every resource is written by one pass, half of them are buffers and half are images.
+fn layered_passes(params: &LayeredParams) -> Vec<Pass> {
+    let mut rng = Lcg(0x1a7e);
+    let width = params.fan_out.max(1);
+    let stages = PipelineStage::VERTEX_SHADER | PipelineStage::FRAGMENT_SHADER;
+    let written = |pass: u32| (pass..params.resources).step_by(params.passes as usize);
+    let declare = |builder: PassBuilder, resource: u32, write: bool| {
+        let id = resource / 2;
+        match (resource.is_multiple_of(2), write) {
+            (true, true) => builder.storage_buffer_write(Id::new(id), stages),
+            (true, false) => builder.storage_buffer_read(Id::new(id), stages),
+            (false, true) => builder.color_attachment(Id::new(id)),
+            (false, false) => builder.sample_image(Id::new(id), stages),
+        }
+    };
+    (0..params.passes)
+        .map(|index| {
+            let mut builder = PassBuilder::new(PassId(index as usize), QueueFamilyId(0));
+            let layer = index / width;
+            let mut dependencies = Vec::new();
+            if layer > 0 {
+                for _ in 0..params.fan_out {
+                    let dependency = (layer - 1) * width + rng.next(width);
+                    if !dependencies.contains(&dependency) {
+                        dependencies.push(dependency);
+                    }
+                }
+            }
+            for &dependency in &dependencies {
+                builder = builder.depends_on(PassId(dependency as usize));
+                if let Some(resource) = written(dependency).next() {
+                    builder = declare(builder, resource, false);
+                }
+            }
+            for resource in written(index) {
+                builder = declare(builder, resource, true);
+            }
+            builder.build().unwrap()
+        })
+        .collect()
+}
+
+fn bench_layered(params: &LayeredParams) {
+    let passes = layered_passes(params);
+    let mut resources = Resources::new();
+    for index in 0..params.resources {
+        if index % 2 == 0 {
+            resources.add_buffer(Id::new(index / 2), ResourceInfo::default());
+        } else {
+            resources.add_image(Id::new(index / 2), ResourceInfo::default());
+        }
+    }
+    let declarations: usize = passes
+        .iter()
+        .map(|pass| pass.buffers.len() + pass.images.len())
+        .sum();
+    println!(
+        "{} layered passes, {} resources, fan-out {}, {} queues, {} declarations",
+        params.passes, params.resources, params.fan_out, params.queues, declarations
+    );
+
+    bench("collect on one queue", || {
+        collect(passes.clone(), &resources, |_| 1).unwrap()
+    });
+    let queues = params.queues as usize;
+    let chains = bench("schedule on queues", || {
+        collect(passes.clone(), &resources, |_| queues).unwrap()
+    });
+    let _: Schedule<SyncData<u32, u32>> = bench("sync", || {
+        let mut semaphores = 0;
+        sync(&chains, || {
+            semaphores += 1;
+            (semaphores, semaphores)
+        })
+        .unwrap()
+    });
+}
@@ -0,0 +1,24 @@
+//!
+//! `no_std` crate that builds and synchronizes schedule with `gfx-chain`
+//! without its `std` feature.
+//!
+
+#![no_std]
+
+extern crate alloc;
+extern crate gfx_chain;
+
+use alloc::vec::Vec;
+use gfx_chain::collect::Chains;
+use gfx_chain::pass::Pass;
+use gfx_chain::resource::Resources;
+use gfx_chain::sync::SyncData;
+use gfx_chain::{build, ChainError};
+
+/// Build schedule with single queue per family and unit semaphores.
+pub fn build_schedule(
+    passes: Vec<Pass>,
+    resources: &Resources,
+) -> Result<Chains<SyncData<(), ()>>, ChainError> {
+    build(passes, resources, |_| 1, || ((), ()))
+}
@@ -1,25 +1,66 @@
 extern crate clap;
-extern crate fnv;
 extern crate gfx_chain;
 extern crate gfx_hal as hal;
 extern crate rand;
+#[cfg(feature = "serde-1")]
+extern crate serde_json;
 
 use clap::{App, Arg, SubCommand};
-use fnv::FnvHashMap;
+use gfx_chain::alias::{Lifetime, Lifetimes};
+#[cfg(feature = "capture")]
+use gfx_chain::capture::{Capture, CaptureError, CAPTURE_VERSION};
 use gfx_chain::chain::Chain;
-use gfx_chain::collect::{collect, Chains};
-use gfx_chain::pass::{Pass, PassId, StateUsage};
-use gfx_chain::resource::{Buffer, BufferLayout, Id, Image, Layout, Resource, State, Usage};
-use gfx_chain::schedule::{QueueId, SubmissionId};
-use gfx_chain::sync::{sync, Barrier, SyncData};
-use hal::buffer::Access as BufferAccess;
-use hal::image::{Access as ImageAccess, Layout as ImageLayout};
+use gfx_chain::collect::{
+    collect, collect_with_capabilities, collect_with_families, collect_with_options,
+    collect_with_warnings, Chains, CollectError, FamilyInfo, SchedulerOptions,
+};
+use gfx_chain::compiled::{BindError, Binding, CompiledGraph};
+use gfx_chain::cull::retain_roots;
+use gfx_chain::device::{build_group, DeviceError, DeviceIndex, DevicePass, DeviceResource};
+use gfx_chain::diff::{diff, Change, GuardSide};
+use gfx_chain::dot::write_dot;
+use gfx_chain::frame::FrameRing;
+use gfx_chain::incremental::IncrementalChains;
+use gfx_chain::offload::{offload_suggestions, OffloadSuggestion};
+use gfx_chain::pass::{
+    generate_mipmaps, Capability, ImageUse, MipmapError, OrderingHint, Pass, PassBuildError,
+    PassBuilder, PassId, PassKind, PassMeta, StateUsage, TransferRegion,
+};
+use gfx_chain::resource::{
+    Access, Buffer, BufferAlignments, BufferLayout, External, Hazard as AccessHazard, Id, IdMap,
+    Image, Layout, LayoutPolicy, MergeError, Range as ResourceRange, Resource, ResourceGroup,
+    ResourceInfo, Resources, SharingMode, State, Usage,
+};
+use gfx_chain::schedule::{QueueId, Schedule, SubmissionId};
+#[cfg(feature = "test-utils")]
+use gfx_chain::sim::{self, GraphParams, Hazard, HazardKind, RandomGraph};
+use gfx_chain::stats::SyncCounts;
+use gfx_chain::subchain::{SubChainBuilder, SubChainError};
+use gfx_chain::validate::{validate, ValidationError};
+use gfx_chain::walk::{BarrierRecord, ScheduleWalker, SubpassDependency};
+use gfx_chain::warning::{chain_warnings, declaration_warnings};
+use gfx_chain::{build, ChainError, ChainWarning};
+use gfx_chain::sync::{
+    event_count, fence_count, sync, sync_chains, sync_with_options, Barrier, FeedbackLoop,
+    FenceStrategy, Guard, SyncData, SyncMode, SyncOptions,
+};
+use gfx_chain::truncate::ParkingStates;
+use hal::buffer::{Access as BufferAccess, Usage as BufferUsage};
+use hal::format::Aspects;
+use hal::image::{
+    Access as ImageAccess, Extent, Layout as ImageLayout, Offset, SubresourceRange,
+    Usage as ImageUsage,
+};
+use hal::pass::{AttachmentLoadOp, AttachmentOps, AttachmentStoreOp};
 use hal::pso::PipelineStage;
 use hal::queue::QueueFamilyId;
 use rand::{Isaac64Rng, OsRng, Rng, SeedableRng};
+use std::cell::Cell;
 use std::cmp::min;
 use std::collections::{HashMap, HashSet};
-use std::panic::{catch_unwind, set_hook, AssertUnwindSafe};
+use std::ops::Range;
+use std::panic::{catch_unwind, set_hook, take_hook, AssertUnwindSafe};
+use std::ptr;
 use std::time::{Duration, Instant};
 
 type DefaultRng = Isaac64Rng;
@@ -127,18 +168,24 @@ fn create_pipeline_stage(rng: &mut DefaultRng) -> PipelineStage {
     stage
 }
 
+// Access types not performed at generated stages are dropped, as collection rejects those.
 fn create_buffer_state(rng: &mut DefaultRng) -> State<Buffer> {
+    let access = create_buffer_access(rng);
+    let stages = create_pipeline_stage(rng);
     State {
-        access: create_buffer_access(rng),
+        access: access - access.unsupported_by(stages),
         layout: BufferLayout,
-        stages: create_pipeline_stage(rng),
+        stages,
     }
 }
 fn create_image_state(rng: &mut DefaultRng) -> State<Image> {
+    let access = create_image_access(rng);
+    let layout = create_image_layout(rng);
+    let stages = create_pipeline_stage(rng);
     State {
-        access: create_image_access(rng),
-        layout: create_image_layout(rng),
-        stages: create_pipeline_stage(rng),
+        access: access - access.unsupported_by(stages),
+        layout,
+        stages,
     }
 }
 
@@ -154,22 +201,179 @@ fn create_deps(rng: &mut DefaultRng, i: usize) -> Vec<PassId> {
     deps
 }
 
-fn create_resc_deps<R: Resource, F: Fn(&mut DefaultRng) -> State<R>>(
+/// Every third buffer after the first is accessed only within one of two disjoint halves.
+fn is_split_buffer(id: Id<Buffer>) -> bool {
+    id.index() % 3 == 1
+}
+fn create_buffer_range(rng: &mut DefaultRng, id: Id<Buffer>) -> Option<Range<u64>> {
+    if is_split_buffer(id) {
+        let ranges = [0..1, 1..2, 0..2, 2..4, 3..4];
+        Some(ranges[rng.gen_range(0, ranges.len())].clone())
+    } else if gen_bool(rng) {
+        let start = rng.gen_range(0, 4);
+        Some(start..rng.gen_range(start + 1, 5))
+    } else {
+        None
+    }
+}
+/// Every third image is a depth-stencil image.
+fn is_depth_stencil(id: Id<Image>) -> bool {
+    id.index() % 3 == 2
+}
+/// Every third image after the first is accessed in disjoint ranges by some passes.
+fn is_split(id: Id<Image>) -> bool {
+    id.index() % 3 == 1
+}
+/// Levels are split at the same level by all passes of the image.
+fn create_image_parts(rng: &mut DefaultRng, id: Id<Image>) -> Vec<StateUsage<Image>> {
+    let split = (1 + id.index() % 4) as u8;
+    vec![0..split, split..5]
+        .into_iter()
+        .map(|levels| {
+            let state = create_image_state(rng);
+            let discard = state.access.is_write() && rng.gen_range(0, 4) == 0;
+            StateUsage {
+                state,
+                usage: Usage::none(),
+                range: Some(SubresourceRange {
+                    aspects: Aspects::COLOR,
+                    levels,
+                    layers: 0..1,
+                }),
+                discard,
+                ordering: OrderingHint::None,
+            }
+        })
+        .collect()
+}
+fn create_ordering_hint(rng: &mut DefaultRng) -> OrderingHint {
+    match rng.gen_range(0, 8) {
+        0 => OrderingHint::AsEarlyAsPossible,
+        1 => OrderingHint::AsLateAsPossible,
+        _ => OrderingHint::None,
+    }
+}
+fn create_image_range(rng: &mut DefaultRng, id: Id<Image>) -> Option<SubresourceRange> {
+    if is_depth_stencil(id) {
+        // Mostly separate aspects, sometimes both to keep a single chain.
+        let roll = rng.gen_range(0, 8);
+        let aspects = if roll == 0 {
+            Aspects::DEPTH | Aspects::STENCIL
+        } else if roll < 5 {
+            Aspects::DEPTH
+        } else {
+            Aspects::STENCIL
+        };
+        Some(SubresourceRange {
+            aspects,
+            levels: 0..1,
+            layers: 0..1,
+        })
+    } else if gen_bool(rng) {
+        let start = rng.gen_range(0, 4);
+        Some(SubresourceRange {
+            aspects: Aspects::COLOR,
+            levels: start..rng.gen_range(start + 1, 5),
+            layers: 0..1,
+        })
+    } else {
+        None
+    }
+}
+
+fn create_resource_info<R, F>(
+    rng: &mut DefaultRng,
+    allow_present: bool,
+    new_state: F,
+) -> ResourceInfo<R>
+where
+    R: Resource,
+    F: Fn(&mut DefaultRng) -> State<R>,
+{
+    let sharing = if gen_bool(rng) {
+        SharingMode::Exclusive
+    } else {
+        SharingMode::Concurrent
+    };
+    let external = gen_bool(rng);
+    if allow_present && rng.gen_range(0, 4) == 0 {
+        ResourceInfo {
+            sharing,
+            external,
+            host_write_before: false,
+            host_read_after: false,
+            present: true,
+            initial_state: None,
+            per_frame: false,
+            import: None,
+            export: None,
+            alignments: None,
+        }
+    } else {
+        ResourceInfo {
+            sharing,
+            external,
+            host_write_before: gen_bool(rng),
+            host_read_after: gen_bool(rng),
+            present: false,
+            initial_state: if rng.gen_range(0, 4) == 0 {
+                Some(new_state(rng))
+            } else {
+                None
+            },
+            per_frame: gen_bool(rng),
+            import: create_external(rng, &new_state),
+            export: create_external(rng, &new_state),
+            alignments: None,
+        }
+    }
+}
+
+/// Family that is not part of generated schedule.
+const EXTERNAL_FAMILY: QueueFamilyId = QueueFamilyId(100);
+
+fn create_external<R, F>(rng: &mut DefaultRng, new_state: &F) -> Option<External<R>>
+where
+    R: Resource,
+    F: Fn(&mut DefaultRng) -> State<R>,
+{
+    if rng.gen_range(0, 6) == 0 {
+        Some(External {
+            family: EXTERNAL_FAMILY,
+            state: new_state(rng),
+        })
+    } else {
+        None
+    }
+}
+
+fn create_resc_deps<R, F, G>(
     rng: &mut DefaultRng,
     count: u32,
     used: &mut HashSet<Id<R>>,
     new_state: F,
-) -> HashMap<Id<R>, StateUsage<R>> {
+    new_range: G,
+) -> HashMap<Id<R>, StateUsage<R>>
+where
+    R: Resource,
+    F: Fn(&mut DefaultRng) -> State<R>,
+    G: Fn(&mut DefaultRng, Id<R>) -> Option<R::Range>,
+{
     let mut map = HashMap::new();
     if count != 0 {
         for _ in 0..gen_inclusive_u32(rng, 1, min(count, 4)) {
             let id = Id::new(rng.gen_range(0, count));
             used.insert(id);
+            let state = new_state(rng);
+            let discard = state.access.is_write() && rng.gen_range(0, 4) == 0;
             map.insert(
                 id,
                 StateUsage {
-                    state: new_state(rng),
+                    state,
                     usage: R::Usage::none(),
+                    range: new_range(rng, id),
+                    discard,
+                    ordering: create_ordering_hint(rng),
                 },
             );
         }
@@ -201,6 +405,25 @@ fn fill<T: Default>(num: usize) -> Vec<T> {
     vec
 }
 
+/// Register resources declared by passes that aren't registered yet with default info.
+fn register_declared(resources: &mut Resources, passes: &[Pass]) {
+    for pass in passes {
+        for &id in pass.buffers.keys() {
+            resources.buffers.entry(id).or_default();
+        }
+        for &id in pass.images.keys().chain(pass.image_ranges.keys()) {
+            resources.images.entry(id).or_default();
+        }
+    }
+}
+
+/// Resources with every resource declared by passes registered with default info.
+fn declared(passes: &[Pass]) -> Resources {
+    let mut resources = Resources::new();
+    register_declared(&mut resources, passes);
+    resources
+}
+
 #[derive(Copy, Clone, Debug)]
 enum ExecuteTarget {
     Acquire(usize),
@@ -229,15 +452,30 @@ struct ResourceState<R: Resource> {
     owner: ResourceOwner,
 }
 
+#[derive(Copy, Clone, Debug, Default)]
+struct ResourceUses {
+    started: usize,
+    finished: usize,
+    total: usize,
+}
+
 struct ExecuteStatus<'a, 'b> {
     chains: &'a Chains<SyncData<usize, usize>>,
     passes: &'b Vec<Pass>,
     log: bool,
     queue_state: HashMap<QueueId, QueueStage>,
     image_state: HashMap<Id<Image>, ResourceState<Image>>,
+    stencil_state: HashMap<Id<Image>, ResourceState<Image>>,
+    part_state: HashMap<(Id<Image>, usize), ResourceState<Image>>,
     buffer_state: HashMap<Id<Buffer>, ResourceState<Buffer>>,
+    buffer_part_state: HashMap<(Id<Buffer>, usize), ResourceState<Buffer>>,
     completed_passes: Vec<bool>,
-    signaled_semaphores: Vec<bool>,
+    signaled_semaphores: Vec<Option<u64>>,
+    set_events: Vec<bool>,
+    acquired: HashSet<Id<Image>>,
+    presented: HashSet<Id<Image>>,
+    lifetimes: Lifetimes,
+    image_uses: HashMap<Id<Image>, ResourceUses>,
 }
 impl<'a, 'b> ExecuteStatus<'a, 'b> {
     pub fn new(
@@ -263,30 +501,29 @@ impl<'a, 'b> ExecuteStatus<'a, 'b> {
             );
         }
 
-        let mut buffer_state = HashMap::new();
-        for (&id, buffer) in &chains.buffers {
-            let link = buffer.link(0);
-            buffer_state.insert(
-                id,
-                ResourceState {
-                    access: link.state().access,
-                    layout: link.state().layout,
-                    owner: ResourceOwner::OnQueue(link.family()),
-                },
-            );
-        }
+        let buffer_state = Self::initial_state(&chains.buffers);
+        let image_state = Self::initial_state(&chains.images);
+        let stencil_state = Self::initial_state(&chains.stencils);
+        let part_state = Self::parts_initial_state(&chains.parts);
+        let buffer_part_state = Self::parts_initial_state(&chains.buffer_parts);
 
-        let mut image_state = HashMap::new();
-        for (&id, image) in &chains.images {
-            let link = image.link(0);
-            image_state.insert(
-                id,
-                ResourceState {
-                    access: link.state().access,
-                    layout: link.state().layout,
-                    owner: ResourceOwner::OnQueue(link.family()),
-                },
-            );
+        let mut image_uses = HashMap::new();
+        for queue in chains.schedule.iter().flat_map(|family| family.iter()) {
+            for (sid, submission) in queue.iter() {
+                let images = submission.images().chain(submission.stencils());
+                for id in images.map(|(&id, _)| id).chain(submission.parts().map(|(&id, _)| id)) {
+                    image_uses
+                        .entry(id)
+                        .or_insert_with(ResourceUses::default)
+                        .total += 1;
+                }
+                for id in bound_images(chains, sid) {
+                    image_uses
+                        .entry(id)
+                        .or_insert_with(ResourceUses::default)
+                        .total += 1;
+                }
+            }
         }
 
         let completed_passes = fill(passes.len());
@@ -298,9 +535,101 @@ impl<'a, 'b> ExecuteStatus<'a, 'b> {
             log,
             queue_state,
             image_state,
+            stencil_state,
+            part_state,
             buffer_state,
+            buffer_part_state,
             completed_passes,
             signaled_semaphores,
+            set_events: fill(event_count(&chains.schedule)),
+            acquired: HashSet::new(),
+            presented: HashSet::new(),
+            lifetimes: Lifetimes::new(chains),
+            image_uses,
+        }
+    }
+
+    fn initial_state<R: Resource>(
+        chains: &IdMap<R, Chain<R>>,
+    ) -> HashMap<Id<R>, ResourceState<R>> {
+        chains
+            .iter()
+            .map(|(&id, chain)| (id, Self::chain_initial_state(chain)))
+            .collect()
+    }
+    fn parts_initial_state<R: Resource>(
+        parts: &IdMap<R, Vec<Chain<R>>>,
+    ) -> HashMap<(Id<R>, usize), ResourceState<R>> {
+        parts
+            .iter()
+            .flat_map(|(&id, parts)| {
+                parts
+                    .iter()
+                    .enumerate()
+                    .map(move |(index, chain)| ((id, index), Self::chain_initial_state(chain)))
+            })
+            .collect()
+    }
+    fn chain_initial_state<R: Resource>(chain: &Chain<R>) -> ResourceState<R> {
+        let link = chain.link(0);
+        let mut state = ResourceState {
+            access: link.state().access,
+            layout: link.state().layout,
+            owner: ResourceOwner::OnQueue(link.family()),
+        };
+        if let Some(initial) = chain.info().initial_state {
+            state.access = initial.access;
+            state.layout = initial.layout;
+        } else if chain.info().host_write_before {
+            state.access = R::Access::host_write();
+        }
+        if chain.info().present {
+            state.access = R::Access::none();
+            state.layout = R::Layout::discard_content();
+        }
+        if let Some(import) = chain.info().import {
+            state.access = R::Access::none();
+            state.layout = import.state.layout;
+            state.owner = ResourceOwner::TransferringTo(link.family());
+        }
+        state
+    }
+    fn check_final_state<R: Resource>(
+        map: &HashMap<Id<R>, ResourceState<R>>,
+        chains: &IdMap<R, Chain<R>>,
+    ) {
+        for (id, chain) in chains {
+            Self::check_chain_final_state(&map[id], chain);
+        }
+    }
+    fn check_chain_final_state<R: Resource>(state: &ResourceState<R>, chain: &Chain<R>) {
+        let export = chain.info().export;
+        if chain.info().host_read_after && export.is_none() {
+            assert_eq!(
+                state.access,
+                R::Access::host_read(),
+                "Resource is not visible to host after execution."
+            );
+        }
+        let boundary = chain.final_state();
+        // Barriers between read-only links may be omitted.
+        if state.access.is_write() || boundary.state.access.is_write() {
+            assert_eq!(
+                state.access, boundary.state.access,
+                "Final access doesn't match reported one."
+            );
+        }
+        assert_eq!(
+            state.layout, boundary.state.layout,
+            "Final layout doesn't match reported one."
+        );
+        if let Some(owner) = boundary.owner {
+            let expected = if export.is_some() {
+                ResourceOwner::TransferringTo(owner)
+            } else {
+                ResourceOwner::OnQueue(owner)
+            };
+            assert_eq!(state.owner, expected, "Final owner doesn't match reported one.");
         }
     }
 
@@ -334,21 +663,30 @@ impl<'a, 'b> ExecuteStatus<'a, 'b> {
             }
         }
 
+        // Barrier from undefined state discards the content instead of matching the state.
+        let discarding = barrier.queues.is_none()
+            && barrier.states.start.access == R::Access::none()
+            && barrier.states.start.layout == R::Layout::discard_content();
+
         if let ResourceOwner::OnQueue(_) = old_state.owner {
-            // TODO: Handle source = Undefined for images
-            assert_eq!(
-                barrier.states.start.layout, old_state.layout,
-                "Resource source layout does not match actual resource layout."
-            );
-            new_state.layout = barrier.states.end.layout;
+            if !discarding {
+                assert_eq!(
+                    barrier.states.start.layout, old_state.layout,
+                    "Resource source layout does not match actual resource layout."
+                );
+            }
         }
+        new_state.layout = barrier.states.end.layout;
 
         // TODO: Check that the transition done by the transfer out matches the transfer in
 
-        assert_eq!(
-            barrier.states.start.access, old_state.access,
-            "Resource source access flags do not match actual resource access."
-        );
+        // Barriers between read-only links may be omitted.
+        if !discarding && (barrier.states.start.access.is_write() || old_state.access.is_write()) {
+            assert_eq!(
+                barrier.states.start.access, old_state.access,
+                "Resource source access flags do not match actual resource access."
+            );
+        }
         new_state.access = barrier.states.end.access;
 
         new_state
@@ -363,6 +701,38 @@ impl<'a, 'b> ExecuteStatus<'a, 'b> {
         let new_state = Self::barrier_new_state(current_family, barrier, old_state);
         map.insert(id, new_state);
     }
+    fn execute_image_barrier(
+        images: &mut HashMap<Id<Image>, ResourceState<Image>>,
+        stencils: &mut HashMap<Id<Image>, ResourceState<Image>>,
+        current_family: QueueFamilyId,
+        id: Id<Image>,
+        barrier: &Barrier<Image>,
+    ) {
+        let map = if is_stencil_barrier(stencils, id, barrier) {
+            stencils
+        } else {
+            images
+        };
+        Self::execute_barrier(map, current_family, id, barrier);
+    }
+    fn execute_part_barrier<R: Resource>(
+        parts: &mut HashMap<(Id<R>, usize), ResourceState<R>>,
+        chains: &IdMap<R, Vec<Chain<R>>>,
+        current_family: QueueFamilyId,
+        id: Id<R>,
+        barrier: &Barrier<R>,
+    ) {
+        let range = barrier.range.as_ref().expect("Barrier of the part has no range!");
+        let index = chains[&id]
+            .iter()
+            .position(|chain| chain.range().unwrap().overlaps(range))
+            .expect("Barrier doesn't apply to any part!");
+        let part = chains[&id][index].range().unwrap();
+        assert_eq!(part.union(range), *part, "Barrier applies to other parts.");
+        let old_state = parts[&(id, index)];
+        let new_state = Self::barrier_new_state(current_family, barrier, old_state);
+        parts.insert((id, index), new_state);
+    }
     fn can_execute_guard(&self, sid: SubmissionId, is_release: bool) -> bool {
         let sync = self
             .chains
@@ -377,7 +747,43 @@ impl<'a, 'b> ExecuteStatus<'a, 'b> {
         guard
             .wait
             .iter()
-            .all(|wait| self.signaled_semaphores[*wait.semaphore()])
+            .all(|wait| {
+                self.signaled_semaphores[*wait.semaphore()]
+                    .is_some_and(|value| value >= wait.value())
+            })
+    }
+    /// Execute barriers of the guard. Events and semaphores are not touched.
+    fn execute_guard_barriers(&mut self, family: QueueFamilyId, guard: &Guard<usize, usize>) {
+        for (&id, barrier) in &guard.buffers {
+            Self::execute_barrier(&mut self.buffer_state, family, id, barrier);
+        }
+        for &(id, ref barrier) in &guard.buffer_parts {
+            Self::execute_part_barrier(
+                &mut self.buffer_part_state,
+                &self.chains.buffer_parts,
+                family,
+                id,
+                barrier,
+            );
+        }
+        for (&id, barrier) in &guard.images {
+            Self::execute_image_barrier(
+                &mut self.image_state,
+                &mut self.stencil_state,
+                family,
+                id,
+                barrier,
+            );
+        }
+        for &(id, ref barrier) in &guard.parts {
+            Self::execute_part_barrier(
+                &mut self.part_state,
+                &self.chains.parts,
+                family,
+                id,
+                barrier,
+            );
+        }
     }
     fn execute_guard(&mut self, sid: SubmissionId, is_release: bool) {
         assert!(self.can_execute_guard(sid, is_release));
@@ -403,39 +809,143 @@ impl<'a, 'b> ExecuteStatus<'a, 'b> {
             );
         }
 
-        for (&id, barrier) in &guard.buffers {
-            Self::execute_barrier(&mut self.buffer_state, sid.family(), id, barrier);
+        for wait in &guard.wait_acquired {
+            assert!(
+                self.acquired.insert(*wait.semaphore()),
+                "Present image acquired twice."
+            );
         }
-        for (&id, barrier) in &guard.images {
-            Self::execute_barrier(&mut self.image_state, sid.family(), id, barrier);
+        self.execute_guard_barriers(sid.family(), guard);
+
+        for wait in &guard.wait_events {
+            assert!(self.set_events[wait.event], "Event waited before it was set.");
+            for (&id, barrier) in &wait.buffers {
+                Self::execute_barrier(&mut self.buffer_state, sid.family(), id, barrier);
+            }
+            for &(id, ref barrier) in &wait.buffer_parts {
+                Self::execute_part_barrier(
+                    &mut self.buffer_part_state,
+                    &self.chains.buffer_parts,
+                    sid.family(),
+                    id,
+                    barrier,
+                );
+            }
+            for (&id, barrier) in &wait.images {
+                Self::execute_image_barrier(
+                    &mut self.image_state,
+                    &mut self.stencil_state,
+                    sid.family(),
+                    id,
+                    barrier,
+                );
+            }
+            for &(id, ref barrier) in &wait.parts {
+                Self::execute_part_barrier(
+                    &mut self.part_state,
+                    &self.chains.parts,
+                    sid.family(),
+                    id,
+                    barrier,
+                );
+            }
+        }
+        for set in &guard.set_events {
+            assert!(!self.set_events[set.event], "Event already set.");
+            self.set_events[set.event] = true;
+        }
+
+        for signal in &guard.signal_present {
+            assert!(
+                self.presented.insert(*signal.semaphore()),
+                "Present image presented twice."
+            );
         }
 
         for signal in &guard.signal {
             let id = *signal.semaphore();
-            assert!(!self.signaled_semaphores[id], "Semaphore already signaled.");
-            self.signaled_semaphores[id] = true;
+            assert!(
+                self.signaled_semaphores[id].is_none_or(|value| value < signal.value()),
+                "Semaphore already signaled."
+            );
+            self.signaled_semaphores[id] = Some(signal.value());
+        }
+
+        let images: Vec<_> = if sub.is_transition() {
+            // Images are used by the submission of the pass that follows the node.
+            Vec::new()
+        } else if sub.kind() == PassKind::SparseBind {
+            bound_images(self.chains, sid)
+        } else {
+            let mut images: Vec<_> = pass_data
+                .images
+                .keys()
+                .chain(pass_data.image_ranges.keys())
+                .cloned()
+                .collect();
+            images.sort();
+            images.dedup();
+            images
+        };
+        for id in images {
+            if is_release {
+                self.image_uses.get_mut(&id).unwrap().finished += 1;
+            } else {
+                self.start_image_use(id);
+            }
+        }
+    }
+    fn start_image_use(&mut self, id: Id<Image>) {
+        for (&other, uses) in &self.image_uses {
+            if self.lifetimes.aliasable(id, other) {
+                assert_eq!(
+                    uses.started, uses.finished,
+                    "Aliasable image is in use by another submission."
+                );
+                assert!(
+                    uses.started == 0 || uses.finished == uses.total,
+                    "Aliasable image is used before and after this submission."
+                );
+            }
         }
+        self.image_uses.get_mut(&id).unwrap().started += 1;
     }
 
     fn check_pass_state<R: Resource>(
-        map: &HashMap<Id<R>, ResourceState<R>>,
-        chains: &FnvHashMap<Id<R>, Chain<R>>,
+        map: &mut HashMap<Id<R>, ResourceState<R>>,
+        chains: &IdMap<R, Chain<R>>,
         current_family: QueueFamilyId,
         id: Id<R>,
-        expected_state: StateUsage<R>,
+        expected_state: &StateUsage<R>,
+        link_id: usize,
+    ) {
+        let state = map.get_mut(&id).expect("Resource not in chain!");
+        let chain = chains.get(&id).expect("Resource not in chain!");
+        Self::check_state(state, chain, current_family, expected_state, link_id);
+    }
+    fn check_state<R: Resource>(
+        current: &mut ResourceState<R>,
+        chain: &Chain<R>,
+        current_family: QueueFamilyId,
+        expected_state: &StateUsage<R>,
         link_id: usize,
     ) {
-        let state = *map.get(&id).expect("Resource not in chain!");
-        let link = chains
-            .get(&id)
-            .expect("Resource not in chain!")
-            .link(link_id);
+        let state = *current;
+        let link = chain.link(link_id);
 
-        assert_eq!(
-            state.owner,
-            ResourceOwner::OnQueue(current_family),
-            "Resource is not currently owned by the queue executing this pass."
-        );
+        if chain.sharing() == SharingMode::Exclusive {
+            assert_eq!(
+                state.owner,
+                ResourceOwner::OnQueue(current_family),
+                "Resource is not currently owned by the queue executing this pass."
+            );
+        } else {
+            if let ResourceOwner::TransferringTo(_) = state.owner {
+                panic!("Concurrent resource is accessed while owned by external family.");
+            }
+            // The family that accessed the resource last releases it to external family.
+            current.owner = ResourceOwner::OnQueue(current_family);
+        }
         assert_eq!(
             state.layout,
             link.state().layout,
@@ -455,6 +965,12 @@ impl<'a, 'b> ExecuteStatus<'a, 'b> {
             expected_state.state.access,
             "Current access flags do not contain all expected access flags."
         );
+        let usage = expected_state.state.usage();
+        assert_eq!(
+            chain.total_usage() | usage,
+            chain.total_usage(),
+            "Total usage does not contain usage derived from expected state."
+        );
     }
     fn execute_pass(&mut self, sid: SubmissionId) {
         let sub = self
@@ -464,10 +980,21 @@ impl<'a, 'b> ExecuteStatus<'a, 'b> {
             .expect("Submission does not exist?");
         let pass_data = &self.passes[sub.pass().0];
 
+        if sub.is_transition() {
+            // Transition node only records barriers of the next submission.
+            return;
+        }
+
         if self.log {
             println!(" - Executing main pass for {:?} as {:?}", pass_data.id, sid);
         }
 
+        if sub.kind() == PassKind::SparseBind {
+            // Binding doesn't access content of resources.
+            self.completed_passes[sub.pass().0] = true;
+            return;
+        }
+
         // TODO: Figure out what's reasonable to do with multiple queues.
         // (This is too strong a restriction, chains doesn't guarantee this much.)
         if self.queue_state.len() == 1 {
@@ -478,9 +1005,18 @@ impl<'a, 'b> ExecuteStatus<'a, 'b> {
                 );
             }
         }
-        for (&id, &state) in &pass_data.buffers {
+        let chains = self.chains;
+        for (&id, &(part, link)) in sub.buffer_parts() {
+            let chain = &chains.buffer_parts[&id][part];
+            let state = self.buffer_part_state.get_mut(&(id, part)).expect("Part not in chain!");
+            Self::check_state(state, chain, sid.family(), &pass_data.buffers[&id], link);
+        }
+        for (&id, state) in &pass_data.buffers {
+            if chains.buffer_parts.contains_key(&id) {
+                continue;
+            }
             Self::check_pass_state(
-                &self.buffer_state,
+                &mut self.buffer_state,
                 &self.chains.buffers,
                 sid.family(),
                 id,
@@ -488,15 +1024,51 @@ impl<'a, 'b> ExecuteStatus<'a, 'b> {
                 sub.buffer(id),
             );
         }
-        for (&id, &state) in &pass_data.images {
-            Self::check_pass_state(
-                &self.image_state,
-                &self.chains.images,
-                sid.family(),
-                id,
-                state,
-                sub.image(id),
-            );
+        for (&id, state) in &pass_data.images {
+            if self.chains.parts.contains_key(&id) {
+                continue;
+            }
+            if self.chains.images[&id].info().present {
+                assert!(self.acquired.contains(&id), "Present image used before acquisition.");
+                assert!(!self.presented.contains(&id), "Present image used after presentation.");
+            }
+            match sub.stencils().find(|&(&stencil, _)| stencil == id) {
+                Some((_, &index)) => Self::check_pass_state(
+                    &mut self.stencil_state,
+                    &self.chains.stencils,
+                    sid.family(),
+                    id,
+                    state,
+                    index,
+                ),
+                None => Self::check_pass_state(
+                    &mut self.image_state,
+                    &self.chains.images,
+                    sid.family(),
+                    id,
+                    state,
+                    sub.image(id),
+                ),
+            }
+        }
+        for (&id, links) in sub.parts() {
+            let declared: Vec<_> = pass_data
+                .images
+                .get(&id)
+                .into_iter()
+                .chain(pass_data.image_ranges.get(&id).into_iter().flatten())
+                .collect();
+            for &(part, link) in links {
+                let chain = &chains.parts[&id][part];
+                let range = chain.range().unwrap();
+                let state = self.part_state.get_mut(&(id, part)).expect("Part not in chain!");
+                let overlapping = declared
+                    .iter()
+                    .filter(|declared| declared.range.as_ref().is_none_or(|r| r.overlaps(range)));
+                for declared in overlapping {
+                    Self::check_state(state, chain, sid.family(), declared, link);
+                }
+            }
         }
         self.completed_passes[sub.pass().0] = true;
     }
@@ -583,11 +1155,82 @@ impl<'a, 'b> ExecuteStatus<'a, 'b> {
         }
         panic!("No queue could be executed.")
     }
-    fn execute_all(mut self, rng: &mut DefaultRng) {
+    fn run(&mut self, rng: &mut DefaultRng) {
+        let chains = self.chains;
+        let mut queues: Vec<_> = self.queue_state.keys().cloned().collect();
+        queues.sort();
+        for qid in queues {
+            let first = chains.schedule.submission(SubmissionId::new(qid, 0));
+            if let Some(first) = first {
+                let frame_begin = &first.sync().frame_begin;
+                if self.log && frame_begin.pipeline_barrier().is_some() {
+                    println!(" - Executing frame begin barriers of {:?}", qid);
+                }
+                self.execute_guard_barriers(qid.family(), frame_begin);
+            }
+        }
         while !self.is_finished() {
             self.execute_random(rng)
         }
     }
+    fn execute_all(mut self, rng: &mut DefaultRng) {
+        self.run(rng);
+        Self::check_final_state(&self.buffer_state, &self.chains.buffers);
+        for (&id, parts) in &self.chains.buffer_parts {
+            for (index, chain) in parts.iter().enumerate() {
+                Self::check_chain_final_state(&self.buffer_part_state[&(id, index)], chain);
+            }
+        }
+        Self::check_final_state(&self.image_state, &self.chains.images);
+        Self::check_final_state(&self.stencil_state, &self.chains.stencils);
+        for (&id, parts) in &self.chains.parts {
+            for (index, chain) in parts.iter().enumerate() {
+                Self::check_chain_final_state(&self.part_state[&(id, index)], chain);
+            }
+        }
+        for (id, chain) in &self.chains.images {
+            if chain.info().present {
+                assert_eq!(
+                    self.image_state[id].layout,
+                    ImageLayout::Present,
+                    "Present image is not in present layout after execution."
+                );
+                assert!(self.presented.contains(id), "Present image is not presented.");
+            }
+        }
+    }
+}
+
+/// Get images bound by the sparse binding submission.
+fn bound_images(chains: &Chains<SyncData<usize, usize>>, sid: SubmissionId) -> Vec<Id<Image>> {
+    let parts = chains
+        .parts
+        .iter()
+        .flat_map(|(id, parts)| parts.iter().map(move |chain| (id, chain)));
+    let mut images: Vec<_> = chains
+        .images
+        .iter()
+        .chain(&chains.stencils)
+        .chain(parts)
+        .filter(|&(_, chain)| chain.binds().iter().any(|bind| bind.sid == sid))
+        .map(|(&id, _)| id)
+        .collect();
+    images.sort();
+    images.dedup();
+    images
+}
+
+/// Check if barrier applies to separately tracked stencil aspect of the image.
+fn is_stencil_barrier<T>(
+    stencils: &HashMap<Id<Image>, T>,
+    id: Id<Image>,
+    barrier: &Barrier<Image>,
+) -> bool {
+    stencils.contains_key(&id)
+        && barrier
+            .range
+            .as_ref()
+            .is_some_and(|range| range.aspects == Aspects::STENCIL)
 }
 
 fn sanity_check(
@@ -600,51 +1243,355 @@ fn sanity_check(
     ExecuteStatus::new(chains, passes, semaphore_count, log).execute_all(rng)
 }
 
-#[derive(Copy, Clone)]
-struct BenchParams {
-    family_count: usize,
-    queues_per_family: usize,
-    buffer_count: u32,
-    image_count: u32,
-    submit_count: usize,
+/// Check that ids of queues and submissions are contiguous and ordered as submissions are.
+fn check_ids<S>(schedule: &Schedule<S>) {
+    for family in schedule.iter() {
+        for (index, queue) in family.iter().enumerate() {
+            let qid = QueueId::new(family.id(), index);
+            assert_eq!(queue.id(), qid);
+            assert!(ptr::eq(&schedule[qid], queue));
+            let mut prev: Option<(SubmissionId, usize)> = None;
+            for (position, (sid, submission)) in queue.iter().enumerate() {
+                assert_eq!(sid, SubmissionId::new(qid, position));
+                assert!(ptr::eq(&schedule[sid], submission));
+                if let Some((prev_sid, prev_order)) = prev {
+                    assert!(prev_sid < sid && prev_order < submission.submit_order());
+                }
+                prev = Some((sid, submission.submit_order()));
+            }
+        }
+    }
 }
 
-fn test_run(seed: u64, is_test: bool, bench: Option<BenchParams>) -> (usize, Duration) {
-    if is_test {
-        println!("Seed: {}", seed);
+/// Check that barriers perform access types only at stages other than
+/// `TOP_OF_PIPE` and `BOTTOM_OF_PIPE`, including ones performed with event waits.
+fn check_legal_barriers<S, W>(schedule: &Schedule<SyncData<S, W>>) {
+    fn check<R: Resource>(sid: SubmissionId, barrier: &Barrier<R>) {
+        let no_access = PipelineStage::TOP_OF_PIPE | PipelineStage::BOTTOM_OF_PIPE;
+        for state in &[barrier.states.start, barrier.states.end] {
+            assert!(
+                state.access == R::Access::none() || !no_access.contains(state.stages),
+                "Barrier {:?} at {:?} performs access types at no stages.",
+                barrier,
+                sid
+            );
+        }
+        assert!(barrier.is_legal(), "Barrier {:?} at {:?} is illegal.", barrier, sid);
     }
 
-    let mut rng = Isaac64Rng::new_unseeded();
-    rng.reseed(&[seed]);
-    let rng = &mut rng;
-
-    let (family_count, buffer_count, image_count, submit_count) = if let Some(bench) = bench {
-        (
-            bench.family_count,
-            bench.buffer_count,
-            bench.image_count,
-            bench.submit_count,
-        )
-    } else {
-        (
-            gen_inclusive_prefer_low(rng, 1, 4),
-            gen_inclusive_u32(rng, 0, 10),
-            gen_inclusive_u32(rng, 0, 10),
-            gen_inclusive(rng, 1, 25),
-        )
-    };
-
-    if is_test {
-        println!(
-            "Creating test case with {} families, {} buffers, {} images, and {} submissions.",
-            family_count, buffer_count, image_count, submit_count
-        );
+    for queue in schedule.iter().flat_map(|family| family.iter()) {
+        for (sid, submission) in queue.iter() {
+            let sync = submission.sync();
+            for guard in &[&sync.frame_begin, &sync.acquire, &sync.release] {
+                if let Some(barrier) = guard.pipeline_barrier() {
+                    for &(_, barrier) in &barrier.buffers {
+                        check(sid, barrier);
+                    }
+                    for &(_, barrier) in &barrier.images {
+                        check(sid, barrier);
+                    }
+                }
+                for event in &guard.wait_events {
+                    for barrier in event.buffers.values() {
+                        check(sid, barrier);
+                    }
+                    for barrier in event.images.values() {
+                        check(sid, barrier);
+                    }
+                    for (_, barrier) in &event.parts {
+                        check(sid, barrier);
+                    }
+                    for (_, barrier) in &event.buffer_parts {
+                        check(sid, barrier);
+                    }
+                }
+            }
+        }
     }
+}
 
-    let mut max_queues = Vec::new();
-    for _ in 0..family_count {
-        if let Some(bench) = bench {
-            max_queues.push(bench.queues_per_family)
+/// Check that each submission lies within the link it is associated with
+/// and spans of links ordered on each queue.
+fn check_link_views<S>(chains: &Chains<S>) {
+    for queue in chains.schedule.iter().flat_map(|family| family.iter()) {
+        for (sid, submission) in queue.iter() {
+            for (id, &index) in submission.buffers() {
+                let view = chains.buffers[id].link_of(sid).unwrap();
+                assert_eq!(view.index(), index);
+            }
+            for (id, &index) in submission.images() {
+                let chain = &chains.images[id];
+                assert_eq!(chain.link_of(sid).unwrap().index(), index);
+                let before = chain.link_before(sid).map(|view| view.index());
+                assert_eq!(before, index.checked_sub(1));
+            }
+        }
+    }
+    for chain in chains.images.values() {
+        for (prev, next) in chain.link_views().zip(chain.link_views().skip(1)) {
+            for prev_span in prev.spans() {
+                for next_span in next.spans() {
+                    if prev_span.start.queue() == next_span.start.queue() {
+                        assert!(prev_span.end <= next_span.start);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Execute several frames one after another with per-slot synchronization.
+/// Each frame starts with final states of resources from the frames it depends on.
+fn test_frames(
+    rng: &mut DefaultRng,
+    chains: &Chains<SyncData<usize, usize>>,
+    passes: &Vec<Pass>,
+    log: bool,
+) {
+    let ring = FrameRing::new(chains, rng.gen_range(2, 4));
+    if log {
+        println!("Testing {} frames in flight", ring.in_flight());
+    }
+    let mut semaphores = fill(ring.semaphore_count());
+    let mut buffer_states = Vec::new();
+    let mut image_states = Vec::new();
+    let mut stencil_states = Vec::new();
+    let mut part_states = Vec::new();
+    let mut buffer_part_states = Vec::new();
+    for n in 0..ring.in_flight() * 2 + 1 {
+        let frame = ring.frame(n);
+        let frame_chains = Chains {
+            schedule: frame.schedule(&chains.schedule),
+            buffers: chains.buffers.clone(),
+            images: chains.images.clone(),
+            stencils: chains.stencils.clone(),
+            parts: chains.parts.clone(),
+            buffer_parts: chains.buffer_parts.clone(),
+        };
+        for index in frame.semaphores() {
+            semaphores[index] = None;
+        }
+
+        let mut status = ExecuteStatus::new(&frame_chains, passes, 0, log);
+        status.signaled_semaphores = semaphores;
+        status.set_events = fill(ring.event_count());
+        for transition in ring.buffer_transitions() {
+            if let Some(previous) = frame.previous(transition.per_frame) {
+                if let Some(part) = transition.part {
+                    let states: &HashMap<_, _> = &buffer_part_states[previous];
+                    let key = (transition.id, part);
+                    status.buffer_part_state.insert(key, states[&key]);
+                    continue;
+                }
+                let state: &HashMap<_, _> = &buffer_states[previous];
+                status.buffer_state.insert(transition.id, state[&transition.id]);
+            }
+        }
+        for transition in ring.image_transitions() {
+            if let Some(previous) = frame.previous(transition.per_frame) {
+                if let Some(part) = transition.part {
+                    let states: &HashMap<_, _> = &part_states[previous];
+                    let key = (transition.id, part);
+                    status.part_state.insert(key, states[&key]);
+                    continue;
+                }
+                let stencil =
+                    is_stencil_barrier(&status.stencil_state, transition.id, &transition.barrier);
+                let (states, current): (&HashMap<_, _>, _) = if stencil {
+                    (&stencil_states[previous], &mut status.stencil_state)
+                } else {
+                    (&image_states[previous], &mut status.image_state)
+                };
+                current.insert(transition.id, states[&transition.id]);
+            }
+        }
+        status.run(rng);
+
+        semaphores = status.signaled_semaphores;
+        buffer_states.push(status.buffer_state);
+        image_states.push(status.image_state);
+        stencil_states.push(status.stencil_state);
+        part_states.push(status.part_state);
+        buffer_part_states.push(status.buffer_part_state);
+    }
+}
+
+/// Change states declared by random passes and check updated chains.
+fn test_incremental(
+    rng: &mut DefaultRng,
+    passes: &[Pass],
+    shuffled_passes: Vec<Pass>,
+    resources: &Resources,
+    max_queues: &[usize],
+    options: SyncOptions,
+    log: bool,
+) {
+    let semaphore_id = Cell::new(0);
+    let mut incremental = IncrementalChains::new(
+        shuffled_passes,
+        resources.clone(),
+        |QueueFamilyId(id)| max_queues[id],
+        options,
+        || {
+            let id = semaphore_id.get();
+            semaphore_id.set(id + 1);
+            (id, id)
+        },
+    ).unwrap();
+    let mut passes = passes.to_vec();
+
+    for _ in 0..5 {
+        let index = rng.gen_range(0, passes.len());
+        let mut images: Vec<_> = passes[index].images.keys().cloned().collect();
+        if images.is_empty() {
+            continue;
+        }
+        images.sort();
+        let id = images[rng.gen_range(0, images.len())];
+        let old = passes[index].images[&id].state;
+        // Keeping stages allows links to keep their structure.
+        let state = if gen_bool(rng) {
+            State {
+                stages: old.stages,
+                ..create_image_state(rng)
+            }
+        } else {
+            create_image_state(rng)
+        };
+
+        let pass = passes[index].id;
+        match incremental.update_pass_states(pass, &[(id, state)]) {
+            Ok(result) => {
+                if log {
+                    println!("Updated {:?} of {:?} to {:?}: {:?}", id, pass, state, result);
+                }
+                passes[index].images.get_mut(&id).unwrap().state = state;
+            }
+            Err(error) => {
+                if log {
+                    println!("Failed to update {:?} of {:?} to {:?}: {}", id, pass, state, error);
+                }
+            }
+        }
+        if let Err(errors) = validate(incremental.chains(), &passes) {
+            panic!("Validation failed: {:#?}", errors);
+        }
+        for _ in 0..3 {
+            sanity_check(rng, incremental.chains(), &passes, semaphore_id.get(), log);
+        }
+    }
+}
+
+/// Cull passes for random roots and check that the rest still forms valid schedule.
+fn test_culling(
+    rng: &mut DefaultRng,
+    passes: &[Pass],
+    resources: &Resources,
+    max_queues: &[usize],
+    options: SyncOptions,
+    log: bool,
+) {
+    let mut passes = passes.to_vec();
+    for pass in &mut passes {
+        pass.side_effects = rng.gen_range(0, 8) == 0;
+    }
+    let mut buffers: Vec<_> = passes.iter().flat_map(|pass| pass.buffers.keys().cloned()).collect();
+    let mut images: Vec<_> = passes
+        .iter()
+        .flat_map(|pass| pass.images.keys().chain(pass.image_ranges.keys()).cloned())
+        .collect();
+    buffers.sort();
+    buffers.dedup();
+    buffers.retain(|_| rng.gen_range(0, 3) == 0);
+    images.sort();
+    images.dedup();
+    images.retain(|_| rng.gen_range(0, 3) == 0);
+
+    let culled = retain_roots(passes.clone(), &images, &buffers);
+    if log {
+        println!("Culled for roots {:?} and {:?}: {:?}", images, buffers, culled.culled);
+    }
+    assert_eq!(culled.passes.len() + culled.culled.len(), passes.len());
+    for pass in &culled.passes {
+        for dependency in &pass.dependencies {
+            assert!(
+                culled.culled.binary_search(dependency).is_err(),
+                "Retained pass depends on culled one."
+            );
+        }
+    }
+    for pass in passes.iter().filter(|pass| culled.culled.contains(&pass.id)) {
+        assert!(!pass.side_effects, "Pass with side effects is culled.");
+        let writes_root = pass.kind != PassKind::SparseBind
+            && (pass.buffers.iter().any(|(id, state_usage)| {
+                buffers.contains(id) && state_usage.state.access.is_write()
+            }) || pass.images.iter().any(|(id, state_usage)| {
+                images.contains(id) && state_usage.state.access.is_write()
+            }));
+        assert!(!writes_root, "Pass that writes root is culled.");
+    }
+    if culled.passes.is_empty() {
+        return;
+    }
+
+    let chains = collect(culled.passes.clone(), resources, |QueueFamilyId(id)| max_queues[id])
+        .unwrap();
+    let mut semaphore_id = 0;
+    let synched_chains = sync_chains(chains, options, || {
+        let id = semaphore_id;
+        semaphore_id += 1;
+        (id, id)
+    }).unwrap();
+    if let Err(errors) = validate(&synched_chains, &culled.passes) {
+        panic!("Validation failed: {:#?}", errors);
+    }
+}
+
+#[derive(Copy, Clone)]
+struct BenchParams {
+    family_count: usize,
+    queues_per_family: usize,
+    buffer_count: u32,
+    image_count: u32,
+    submit_count: usize,
+}
+
+fn test_run(seed: u64, is_test: bool, bench: Option<BenchParams>) -> (usize, Duration) {
+    if is_test {
+        println!("Seed: {}", seed);
+    }
+
+    let mut rng = Isaac64Rng::new_unseeded();
+    rng.reseed(&[seed]);
+    let rng = &mut rng;
+
+    let (family_count, buffer_count, image_count, submit_count) = if let Some(bench) = bench {
+        (
+            bench.family_count,
+            bench.buffer_count,
+            bench.image_count,
+            bench.submit_count,
+        )
+    } else {
+        (
+            gen_inclusive_prefer_low(rng, 1, 4),
+            gen_inclusive_u32(rng, 0, 10),
+            gen_inclusive_u32(rng, 0, 10),
+            gen_inclusive(rng, 1, 25),
+        )
+    };
+
+    if is_test {
+        println!(
+            "Creating test case with {} families, {} buffers, {} images, and {} submissions.",
+            family_count, buffer_count, image_count, submit_count
+        );
+    }
+
+    let mut max_queues = Vec::new();
+    for _ in 0..family_count {
+        if let Some(bench) = bench {
+            max_queues.push(bench.queues_per_family)
         } else {
             max_queues.push(gen_inclusive_prefer_low(rng, 1, 5));
         }
@@ -666,59 +1613,223 @@ fn test_run(seed: u64, is_test: bool, bench: Option<BenchParams>) -> (usize, Dur
         } else {
             None
         };
+        let mut families = Vec::new();
+        if queue.is_none() && gen_bool(rng) {
+            for _ in 0..gen_inclusive(rng, 1, 2) {
+                let other = QueueFamilyId(rng.gen_range(0, family_count));
+                if other != family && !families.contains(&other) {
+                    families.push(other);
+                }
+            }
+        }
+        let preferred_family = if !families.is_empty() && gen_bool(rng) {
+            Some(families[rng.gen_range(0, families.len())])
+        } else {
+            None
+        };
         let dependencies = create_deps(rng, i);
-        let buffers = create_resc_deps(rng, buffer_count, &mut used_buffers, create_buffer_state);
-        let images = create_resc_deps(rng, image_count, &mut used_images, create_image_state);
+        let buffers = create_resc_deps(
+            rng,
+            buffer_count,
+            &mut used_buffers,
+            create_buffer_state,
+            create_buffer_range,
+        );
+        let mut images = create_resc_deps(
+            rng,
+            image_count,
+            &mut used_images,
+            create_image_state,
+            create_image_range,
+        );
 
         used_families.insert(family);
+        used_families.extend(families.iter().cloned());
         if queue.is_some() {
             pass_complexity += 1;
         }
         pass_complexity += dependencies.len() + buffers.len() + images.len();
+        let kind = match rng.gen_range(0, 8) {
+            0 => PassKind::SparseBind,
+            1 => PassKind::RenderPass,
+            _ => PassKind::Commands,
+        };
+        let mut image_ranges = HashMap::new();
+        if kind != PassKind::SparseBind {
+            let mut ids: Vec<_> = images.keys().cloned().filter(|&id| is_split(id)).collect();
+            ids.sort();
+            for id in ids {
+                if rng.gen_range(0, 3) == 0 {
+                    images.remove(&id);
+                    image_ranges.insert(id, create_image_parts(rng, id));
+                }
+            }
+        }
 
         passes.push(Pass {
             id: PassId(i),
+            kind,
             family,
+            families,
+            preferred_family,
             queue,
             dependencies,
             buffers,
             images,
+            image_ranges,
+            side_effects: false,
+            conditional: rng.gen_range(0, 8) == 0,
+            cost: if rng.gen_range(0, 4) == 0 {
+                rng.gen_range(1, 5)
+            } else {
+                1
+            },
+            capability: if rng.gen_range(0, 4) == 0 {
+                // Every family is capable of graphics in this test.
+                Some(Capability::Graphics)
+            } else {
+                None
+            },
+            transfer_regions: HashMap::new(),
+            image_uses: HashMap::new(),
+            resolves: HashMap::new(),
+            meta: PassMeta {
+                name: None,
+                tag: i as u64,
+            },
         })
     }
     if is_test {
         println!("Submissions: {:#?}", passes);
     }
 
+    let mut resources = Resources::new();
+    let mut buffer_ids: Vec<_> = used_buffers.iter().cloned().collect();
+    buffer_ids.sort();
+    for id in buffer_ids {
+        resources.add_buffer(id, create_resource_info(rng, false, create_buffer_state));
+    }
+    let mut image_ids: Vec<_> = used_images.iter().cloned().collect();
+    image_ids.sort();
+    for id in image_ids {
+        let allow_present = !is_depth_stencil(id) && !is_split(id);
+        resources.add_image(
+            id,
+            create_resource_info(rng, allow_present, create_image_state),
+        );
+    }
+    if gen_bool(rng) {
+        resources.layout_policy = LayoutPolicy::PreferReadOnly;
+    }
+
     let mut shuffled_passes = passes.clone();
     rng.shuffle(&mut shuffled_passes);
+    let shuffled_passes_copy = shuffled_passes.clone();
 
     let now = Instant::now();
     catch_unwind(AssertUnwindSafe(|| {
-        let chains = collect(shuffled_passes, |QueueFamilyId(id)| max_queues[id]);
+        let chains = collect(shuffled_passes, &resources, |QueueFamilyId(id)| {
+            max_queues[id]
+        }).unwrap();
         if is_test {
             println!("Unsynched chains: {:#?}", chains);
         }
 
+        let options = SyncOptions {
+            mode: if gen_bool(rng) {
+                SyncMode::BinarySemaphores
+            } else {
+                SyncMode::Timelines
+            },
+            split_barriers: if gen_bool(rng) {
+                Some(rng.gen_range(0, 3))
+            } else {
+                None
+            },
+            hoist_initial_transitions: gen_bool(rng),
+            fences: match rng.gen_range(0, 3) {
+                0 => FenceStrategy::AllQueues,
+                1 => FenceStrategy::None,
+                _ => FenceStrategy::Explicit(
+                    chains
+                        .schedule
+                        .iter()
+                        .flat_map(|family| family.iter())
+                        .map(|queue| queue.id())
+                        .filter(|_| gen_bool(rng))
+                        .collect(),
+                ),
+            },
+            standalone_transition_nodes: gen_bool(rng),
+            ..SyncOptions::default()
+        };
+        if is_test {
+            println!("Sync options: {:?}", options);
+        }
+
         let mut semaphore_id = 0;
-        let schedule = sync(&chains, || {
+        let synched_chains = sync_chains(chains, options.clone(), || {
             let id = semaphore_id;
             semaphore_id += 1;
             (id, id)
-        });
+        }).unwrap();
 
         if is_test {
-            println!("Schedule: {:#?}", schedule);
+            println!("Schedule: {:#?}", synched_chains.schedule);
             println!("Semaphore count: {}", semaphore_id);
         }
 
-        let synched_chains = Chains {
-            schedule,
-            buffers: chains.buffers,
-            images: chains.images,
-        };
+        check_ids(&synched_chains.schedule);
+        check_link_views(&synched_chains);
+        check_legal_barriers(&synched_chains.schedule);
+        if let Err(errors) = validate(&synched_chains, &passes) {
+            panic!("Validation failed: {:#?}", errors);
+        }
+        #[cfg(feature = "test-utils")]
+        {
+            if let Err(hazard) = sim::verify(&synched_chains) {
+                panic!("Hazard found: {}", hazard);
+            }
+            let skipped: Vec<PassId> = passes
+                .iter()
+                .filter(|pass| pass.is_conditional() && rng.gen_range(0, 2) == 0)
+                .map(|pass| pass.id)
+                .collect();
+            let skip = |sid: SubmissionId| skipped.contains(&synched_chains.schedule[sid].pass());
+            if let Err(hazard) = sim::verify_skipping(&synched_chains, skip) {
+                panic!("Hazard found with {:?} skipped: {}", skipped, hazard);
+            }
+        }
         for _ in 0..10 {
             sanity_check(rng, &synched_chains, &passes, semaphore_id, is_test);
         }
+        if options.mode == SyncMode::BinarySemaphores {
+            test_frames(rng, &synched_chains, &passes, is_test);
+        }
+        test_walker(&synched_chains);
+        test_fences(&synched_chains, &options.fences);
+        test_truncate(rng, &synched_chains, semaphore_id);
+        test_stats(&synched_chains, options.clone(), is_test);
+        // Check random chains for unread writes, layout bounces and family round trips.
+        chain_warnings(&synched_chains);
+        let spare = FamilyInfo::new(QueueFamilyId(!0), Capability::Transfer, 1);
+        for suggestion in offload_suggestions(&synched_chains, &passes, &[spare]) {
+            assert_eq!(suggestion.target_family, spare.id);
+        }
+
+        if bench.is_none() {
+            test_deterministic(&passes, &resources, &max_queues, options.clone(), &synched_chains);
+            test_incremental(
+                rng,
+                &passes,
+                shuffled_passes_copy,
+                &resources,
+                &max_queues,
+                options.clone().standalone_transition_nodes(false),
+                is_test,
+            );
+            test_culling(rng, &passes, &resources, &max_queues, options, is_test);
+        }
     })).ok();
     let duration = Instant::now().duration_since(now);
 
@@ -762,31 +1873,4763 @@ fn run_bench(os_rng: &mut OsRng, bench: &str, params: BenchParams) {
     );
 }
 
-fn main() {
-    let mut app = App::new("gfx-chains random tester")
-        .subcommand(
-            SubCommand::with_name("test")
-                .about("Tests a random chain, printing all results.")
-                .arg(Arg::with_name("SEED").help("optional fixed seed").index(1)),
-        )
-        .subcommand(SubCommand::with_name("bench").about("Benchmarks large random chains."))
-        .subcommand(
-            SubCommand::with_name("fuzz").about("Tests random chains, to find panicking cases."),
-        );
-    let matches = app.clone().get_matches();
+/// Collect and synchronize the same passes declared in reverse order.
+/// Output must be identical.
+fn test_deterministic(
+    passes: &[Pass],
+    resources: &Resources,
+    max_queues: &[usize],
+    options: SyncOptions,
+    expected: &Chains<SyncData<usize, usize>>,
+) {
+    // Rebuilding hash maps changes their iteration order too.
+    let reordered: Vec<_> = passes
+        .iter()
+        .rev()
+        .map(|pass| Pass {
+            buffers: pass.buffers.iter().map(|(&id, state)| (id, state.clone())).collect(),
+            images: pass.images.iter().map(|(&id, state)| (id, state.clone())).collect(),
+            image_ranges: pass
+                .image_ranges
+                .iter()
+                .map(|(&id, ranges)| (id, ranges.iter().rev().cloned().collect()))
+                .collect(),
+            ..pass.clone()
+        })
+        .collect();
+    let chains = collect(reordered, resources, |QueueFamilyId(id)| max_queues[id]).unwrap();
 
-    let mut os_rng = OsRng::new().unwrap();
+    let mut semaphore_id = 0;
+    let synched_chains = sync_chains(chains, options, || {
+        let id = semaphore_id;
+        semaphore_id += 1;
+        (id, id)
+    }).unwrap();
+    assert_eq!(
+        format!("{:?}", synched_chains),
+        format!("{:?}", expected),
+        "Output depends on order of declarations."
+    );
+    assert!(
+        diff(&expected.schedule, &synched_chains.schedule).is_empty(),
+        "Identical schedules differ."
+    );
+}
 
-    if let Some(matches) = matches.subcommand_matches("test") {
-        match matches.value_of("SEED") {
-            Some(seed) => {
-                let seed = seed.parse::<u64>().expect("seed is not a number!");
-                test_run(seed, true, None);
+/// Walker must attribute every synchronization command of the schedule to its submission.
+fn test_walker(chains: &Chains<SyncData<usize, usize>>) {
+    let walker = ScheduleWalker::new(&chains.schedule);
+    let mut count = 0;
+    let mut fences = HashSet::new();
+    for queue in walker.queues() {
+        let submissions = chains
+            .schedule
+            .queue(queue.id())
+            .expect("Walker yields queue not in the schedule");
+        assert_eq!(queue.submissions().len(), submissions.len());
+        if let Some(barrier) = queue.frame_begin() {
+            count += barrier.buffers.len() + barrier.images.len();
+        }
+        for (info, (sid, submission)) in queue.iter().zip(submissions.iter()) {
+            assert_eq!(info.sid(), sid, "Submissions are walked out of order.");
+            assert_eq!(info.pass(), submission.pass());
+            assert_eq!(info.meta(), submission.meta());
+            assert_eq!(info.meta().tag, info.pass().0 as u64);
+            assert_eq!(info.waits(), &submission.sync().acquire.wait[..]);
+            assert_eq!(info.signals(), &submission.sync().release.signal[..]);
+            for record in info.before().iter().chain(info.after()) {
+                count += match *record {
+                    BarrierRecord::WaitEvent(wait) => {
+                        wait.buffers.len()
+                            + wait.images.len()
+                            + wait.parts.len()
+                            + wait.buffer_parts.len()
+                    }
+                    BarrierRecord::Pipeline(ref barrier) => {
+                        barrier.buffers.len() + barrier.images.len()
+                    }
+                    BarrierRecord::SetEvent(_) => 0,
+                };
             }
-            None => {
-                test_run(os_rng.next_u64(), true, None);
+            if let Some(dependency) = info.as_external_dependency(true) {
+                assert_eq!(info.kind(), PassKind::RenderPass);
+                let pipeline = |records: &[BarrierRecord]| {
+                    records.iter().any(|record| match *record {
+                        BarrierRecord::Pipeline(ref barrier) => barrier.buffers.is_empty(),
+                        _ => false,
+                    })
+                };
+                assert!(dependency.begin.is_none() || pipeline(info.before()));
+                assert!(dependency.end.is_none() || pipeline(info.after()));
+                if let Some(strict) = info.as_external_dependency(false) {
+                    let begin = (&strict.begin, &dependency.begin);
+                    for &(strict, any) in [begin, (&strict.end, &dependency.end)].iter() {
+                        if let Some(ref strict) = *strict {
+                            assert!(strict.by_region, "Global dependency is not allowed.");
+                            assert_eq!(Some(strict), any.as_ref());
+                        }
+                    }
+                }
+            }
+            assert_eq!(info.fence(), submission.sync().fence);
+            if let Some(fence) = info.fence() {
+                assert!(fence < walker.fence_count() && fences.insert(fence));
             }
         }
+    }
+    assert_eq!(walker.fence_count(), fence_count(&chains.schedule));
+    assert_eq!(fences.len(), walker.fence_count(), "Fence ids are not dense.");
+    assert_eq!(count, barrier_count(chains), "Walker lost barriers.");
+}
+
+/// Check that fences are signaled where strategy requests them and where host reads resources.
+fn test_fences(chains: &Chains<SyncData<usize, usize>>, strategy: &FenceStrategy) {
+    fn host_read<R, S>(chain: &Chain<R>, schedule: &Schedule<S>) -> Option<SubmissionId>
+    where
+        R: Resource,
+    {
+        if !chain.info().host_read_after || chain.info().export.is_some() {
+            return None;
+        }
+        let view = chain.link_views().last().unwrap();
+        view.spans()
+            .into_iter()
+            .map(|span| SubmissionId::new(span.end.queue(), span.end.index() - 1))
+            .max_by_key(|&sid| (schedule[sid].submit_order(), sid.queue().index()))
+    }
+
+    let schedule = &chains.schedule;
+    let buffers = chains
+        .buffers
+        .values()
+        .chain(chains.buffer_parts.values().flat_map(|parts| parts.iter()))
+        .filter_map(|chain| host_read(chain, schedule));
+    let images = chains
+        .images
+        .values()
+        .chain(chains.stencils.values())
+        .chain(chains.parts.values().flat_map(|parts| parts.iter()))
+        .filter_map(|chain| host_read(chain, schedule));
+    let host_reads: HashSet<_> = buffers.chain(images).collect();
+
+    for queue in schedule.iter().flat_map(|family| family.iter()) {
+        let fenced = match *strategy {
+            FenceStrategy::AllQueues => true,
+            FenceStrategy::None => false,
+            FenceStrategy::Explicit(ref queues) => queues.contains(&queue.id()),
+        };
+        for (sid, submission) in queue.iter() {
+            let last = sid.index() + 1 == queue.len();
+            assert_eq!(
+                submission.sync().fence.is_some(),
+                (fenced && last) || host_reads.contains(&sid),
+                "Fence of {:?} doesn't match {:?}",
+                sid,
+                strategy
+            );
+        }
+    }
+}
+
+/// Truncate the schedule at random submission and check that the prefix is self-contained
+/// and buffers it accesses are parked.
+fn test_truncate(
+    rng: &mut DefaultRng,
+    chains: &Chains<SyncData<usize, usize>>,
+    semaphore_count: usize,
+) {
+    let sids: Vec<SubmissionId> = chains
+        .schedule
+        .iter()
+        .flat_map(|family| family.iter())
+        .flat_map(|queue| queue.iter().map(|(sid, _)| sid))
+        .collect();
+    let upto = sids[rng.gen_range(0, sids.len())];
+    let cut = chains.schedule[upto].submit_order();
+    let parking = ParkingStates::default();
+    let mut semaphore_id = semaphore_count;
+    let truncated = chains.truncate_sync(upto, parking, || {
+        semaphore_id += 1;
+        semaphore_id - 1
+    });
+    check_ids(&truncated);
+    check_legal_barriers(&truncated);
+
+    let mut signals = HashSet::new();
+    let mut waits = HashSet::new();
+    let mut set_events = Vec::new();
+    let mut wait_events = Vec::new();
+    let mut fences = Vec::new();
+    for queue in truncated.iter().flat_map(|family| family.iter()) {
+        let expected = chains.schedule[queue.id()]
+            .iter()
+            .filter(|&(_, submission)| submission.submit_order() <= cut)
+            .count();
+        assert_eq!(queue.len(), expected, "Prefix of {:?} is wrong.", queue.id());
+        for (sid, submission) in queue.iter() {
+            let sync = submission.sync();
+            for guard in &[&sync.acquire, &sync.release] {
+                signals.extend(guard.signal.iter().map(|s| (*s.semaphore(), s.value())));
+                waits.extend(guard.wait.iter().map(|w| (*w.semaphore(), w.value())));
+                set_events.extend(guard.set_events.iter().map(|set| set.event));
+                wait_events.extend(guard.wait_events.iter().map(|wait| wait.event));
+            }
+            fences.extend(sync.fence);
+            if sid.index() + 1 == queue.len() {
+                assert!(sync.fence.is_some(), "Last submission of {:?} isn't fenced.", sid);
+            }
+        }
+    }
+    assert_eq!(signals, waits, "Semaphores of the prefix are not paired.");
+    set_events.sort();
+    wait_events.sort();
+    assert_eq!(set_events, (0..set_events.len()).collect::<Vec<_>>());
+    assert_eq!(set_events, wait_events, "Events of the prefix are not paired.");
+    fences.sort();
+    assert_eq!(fences, (0..fence_count(&truncated)).collect::<Vec<_>>());
+
+    for (&id, chain) in chains.buffers.iter() {
+        let touched = chain.link_views().next().is_some_and(|view| {
+            view.spans()
+                .iter()
+                .any(|span| truncated[span.start.queue()].len() > span.start.index())
+        });
+        let barriers: Vec<&Barrier<Buffer>> = truncated
+            .iter()
+            .flat_map(|family| family.iter())
+            .flat_map(|queue| queue.iter())
+            .flat_map(|(_, submission)| {
+                let sync = submission.sync();
+                vec![&sync.frame_begin, &sync.acquire, &sync.release]
+            })
+            .filter_map(|guard| guard.buffers.get(&id))
+            .collect();
+        if touched {
+            assert!(
+                barriers.iter().any(|barrier| {
+                    barrier.queues.is_none() && barrier.states.end == parking.buffers
+                }),
+                "Buffer {:?} isn't parked.",
+                id
+            );
+        } else {
+            assert!(barriers.is_empty(), "Buffer {:?} isn't accessed by the prefix.", id);
+        }
+    }
+}
+
+/// Count barriers recorded in synchronized schedule.
+fn test_stats(chains: &Chains<SyncData<usize, usize>>, options: SyncOptions, is_test: bool) {
+    let stats = chains.stats();
+    if is_test {
+        println!("Sync stats:\n{}", stats);
+    }
+    assert_eq!(stats.total.barriers, barrier_count(chains));
+    let submissions = chains
+        .schedule
+        .iter()
+        .flat_map(|family| family.iter())
+        .map(|queue| queue.len())
+        .sum::<usize>();
+    assert_eq!(stats.passes.len(), submissions);
+    let mut sum = SyncCounts::default();
+    for pass in &stats.passes {
+        sum += pass.counts;
+    }
+    assert_eq!(sum, stats.total, "Pass counts don't sum up to total.");
+    if options.mode == SyncMode::BinarySemaphores {
+        assert_eq!(stats.total.semaphore_signals, stats.total.semaphore_waits);
+    }
+    assert!(stats.total.layout_transitions <= stats.total.barriers);
+    assert!(stats.longest_chain <= stats.passes.len());
+    assert!(stats.passes.is_empty() || stats.longest_chain > 0);
+
+    let dependencies = chains.semaphore_dependencies();
+    assert_eq!(dependencies.len(), stats.total.semaphore_waits, "Wait has no signal.");
+    let critical = chains.critical_path();
+    let longest_queue = chains
+        .schedule
+        .iter()
+        .flat_map(|family| family.iter())
+        .map(|queue| queue.len())
+        .max()
+        .unwrap_or(0);
+    assert_eq!(critical.total_work, submissions as u64);
+    assert_eq!(critical.length, critical.path.len() as u64);
+    assert!(longest_queue as u64 <= critical.length && critical.length <= critical.total_work);
+    for pair in critical.path.windows(2) {
+        let order = |sid: SubmissionId| chains.schedule[sid].submit_order();
+        assert!(order(pair[0]) < order(pair[1]), "Critical path goes back in time.");
+    }
+}
+
+fn barrier_count(chains: &Chains<SyncData<usize, usize>>) -> usize {
+    let mut count = 0;
+    for queue in chains.schedule.iter().flat_map(|family| family.iter()) {
+        for (_, submission) in queue.iter() {
+            let sync = submission.sync();
+            for guard in &[&sync.acquire, &sync.release, &sync.frame_begin] {
+                count += guard.buffers.len() + guard.images.len();
+                count += guard.parts.len() + guard.buffer_parts.len();
+                for wait in &guard.wait_events {
+                    count += wait.buffers.len() + wait.images.len();
+                    count += wait.parts.len() + wait.buffer_parts.len();
+                }
+            }
+        }
+    }
+    count
+}
+
+/// Consecutive read-only passes must not be separated by barriers.
+/// Only transition into the read layout is expected.
+fn test_read_only_readers() {
+    let id = Id::new(0);
+    for families in &[[0, 0, 0], [0, 1, 2]] {
+        let stages = [
+            PipelineStage::VERTEX_SHADER,
+            PipelineStage::FRAGMENT_SHADER,
+            PipelineStage::COMPUTE_SHADER,
+        ];
+        let passes: Vec<_> = (0..3)
+            .map(|i| {
+                let mut images = HashMap::new();
+                images.insert(
+                    id,
+                    StateUsage {
+                        state: State {
+                            access: ImageAccess::SHADER_READ,
+                            layout: ImageLayout::ShaderReadOnlyOptimal,
+                            stages: stages[i],
+                        },
+                        usage: Usage::none(),
+                        range: None,
+                        discard: false,
+                        ordering: OrderingHint::None,
+                    },
+                );
+                Pass {
+                    id: PassId(i),
+                    kind: PassKind::Commands,
+                    family: QueueFamilyId(families[i]),
+                    families: Vec::new(),
+                    preferred_family: None,
+                    queue: None,
+                    dependencies: (0..i).map(PassId).collect(),
+                    buffers: HashMap::new(),
+                    images,
+                    image_ranges: HashMap::new(),
+                    side_effects: false,
+                    conditional: false,
+                    cost: 1,
+                    capability: None,
+                    transfer_regions: HashMap::new(),
+                    image_uses: HashMap::new(),
+                    resolves: HashMap::new(),
+                    meta: PassMeta::default(),
+                }
+            })
+            .collect();
+
+        let mut resources = Resources::new();
+        resources.add_image(
+            id,
+            ResourceInfo {
+                sharing: SharingMode::Concurrent,
+                initial_state: Some(State {
+                    access: ImageAccess::TRANSFER_WRITE,
+                    layout: ImageLayout::TransferDstOptimal,
+                    stages: PipelineStage::TRANSFER,
+                }),
+                ..ResourceInfo::default()
+            },
+        );
+
+        let chains = collect(passes.clone(), &resources, |_| 1).unwrap();
+        let mut semaphore_id = 0;
+        let schedule = sync_with_options(&chains, SyncOptions::default(), || {
+            let id = semaphore_id;
+            semaphore_id += 1;
+            (id, id)
+        }).unwrap();
+        let synched_chains = Chains {
+            schedule,
+            buffers: chains.buffers,
+            images: chains.images,
+            stencils: chains.stencils,
+            parts: chains.parts,
+            buffer_parts: chains.buffer_parts,
+        };
+        if let Err(errors) = validate(&synched_chains, &passes) {
+            panic!("Validation failed: {:#?}", errors);
+        }
+        assert_eq!(
+            barrier_count(&synched_chains),
+            1,
+            "Barriers between read-only passes on families {:?}: {:#?}",
+            families,
+            synched_chains.schedule
+        );
+    }
+}
+
+/// Pass on one queue writing buffers read by passes on another queue
+/// needs one semaphore per reader, unless waits covered by earlier ones are removed.
+fn test_semaphore_fan_out() {
+    const READERS: usize = 5;
+    let mut writer = PassBuilder::new(PassId(0), QueueFamilyId(1));
+    for index in 0..READERS {
+        writer = writer.raw_buffer_state(Id::new(index as u32), State::<Buffer>::transfer_dst());
+    }
+    let mut passes = vec![writer.build().unwrap()];
+    for index in 0..READERS {
+        passes.push(
+            PassBuilder::new(PassId(index + 1), QueueFamilyId(0))
+                .raw_buffer_state(Id::new(index as u32), State::<Buffer>::transfer_src())
+                .build()
+                .unwrap(),
+        );
+    }
+    let mut resources = Resources::new();
+    for index in 0..READERS {
+        resources.add_buffer(
+            Id::new(index as u32),
+            ResourceInfo {
+                sharing: SharingMode::Concurrent,
+                ..ResourceInfo::default()
+            },
+        );
+    }
+    let chains = collect(passes.clone(), &resources, |_| 1).unwrap();
+
+    for &(optimize_semaphores, expected) in &[(false, READERS), (true, 1)] {
+        let options = SyncOptions {
+            optimize_semaphores,
+            ..SyncOptions::default()
+        };
+        let mut semaphore_id = 0;
+        let schedule = sync_with_options(&chains, options, || {
+            let id = semaphore_id;
+            semaphore_id += 1;
+            (id, id)
+        }).unwrap();
+        let synched_chains = Chains {
+            schedule,
+            buffers: chains.buffers.clone(),
+            images: chains.images.clone(),
+            stencils: chains.stencils.clone(),
+            parts: chains.parts.clone(),
+            buffer_parts: chains.buffer_parts.clone(),
+        };
+        if let Err(errors) = validate(&synched_chains, &passes) {
+            panic!("Validation failed: {:#?}", errors);
+        }
+        let total = synched_chains.stats().total;
+        assert_eq!(semaphore_id, expected, "Semaphores with optimization {}", optimize_semaphores);
+        assert_eq!(total.semaphore_signals, expected);
+        assert_eq!(total.semaphore_waits, expected);
+    }
+}
+
+/// Depth reads interleaved with stencil writes must not transition layout of the other aspect.
+/// Each aspect is tracked by its own chain and barriers carry ranges of single aspect.
+fn test_separate_aspects() {
+    let id = Id::new(0);
+    let depth_read = State {
+        access: ImageAccess::DEPTH_STENCIL_ATTACHMENT_READ,
+        layout: ImageLayout::DepthStencilReadOnlyOptimal,
+        stages: PipelineStage::EARLY_FRAGMENT_TESTS,
+    };
+    let stencil_write = State {
+        access: ImageAccess::DEPTH_STENCIL_ATTACHMENT_READ
+            | ImageAccess::DEPTH_STENCIL_ATTACHMENT_WRITE,
+        layout: ImageLayout::DepthStencilAttachmentOptimal,
+        stages: PipelineStage::LATE_FRAGMENT_TESTS,
+    };
+    let passes: Vec<_> = (0..4)
+        .map(|i| {
+            let (state, aspects) = if i % 2 == 0 {
+                (stencil_write, Aspects::STENCIL)
+            } else {
+                (depth_read, Aspects::DEPTH)
+            };
+            let mut images = HashMap::new();
+            images.insert(
+                id,
+                StateUsage {
+                    state,
+                    usage: Usage::none(),
+                    range: Some(SubresourceRange {
+                        aspects,
+                        levels: 0..1,
+                        layers: 0..1,
+                    }),
+                    discard: false,
+                    ordering: OrderingHint::None,
+                },
+            );
+            Pass {
+                id: PassId(i),
+                kind: PassKind::Commands,
+                family: QueueFamilyId(0),
+                families: Vec::new(),
+                preferred_family: None,
+                queue: None,
+                dependencies: (0..i).map(PassId).collect(),
+                buffers: HashMap::new(),
+                images,
+                image_ranges: HashMap::new(),
+                side_effects: false,
+                conditional: false,
+                cost: 1,
+                capability: None,
+                transfer_regions: HashMap::new(),
+                image_uses: HashMap::new(),
+                resolves: HashMap::new(),
+                meta: PassMeta::default(),
+            }
+        })
+        .collect();
+
+    let mut resources = Resources::new();
+    resources.add_image(
+        id,
+        ResourceInfo {
+            initial_state: Some(State {
+                access: ImageAccess::empty(),
+                layout: ImageLayout::Undefined,
+                stages: PipelineStage::TOP_OF_PIPE,
+            }),
+            ..ResourceInfo::default()
+        },
+    );
+
+    let chains = collect(passes.clone(), &resources, |_| 1).unwrap();
+    assert_eq!(chains.images[&id].links().len(), 1, "Depth reads are split.");
+    assert!(chains.stencils.contains_key(&id), "Stencil aspect is not tracked separately.");
+
+    let mut semaphore_id = 0;
+    let schedule = sync_with_options(&chains, SyncOptions::default(), || {
+        let id = semaphore_id;
+        semaphore_id += 1;
+        (id, id)
+    }).unwrap();
+    let synched_chains = Chains {
+        schedule,
+        buffers: chains.buffers,
+        images: chains.images,
+        stencils: chains.stencils,
+        parts: chains.parts,
+        buffer_parts: chains.buffer_parts,
+    };
+    if let Err(errors) = validate(&synched_chains, &passes) {
+        panic!("Validation failed: {:#?}", errors);
+    }
+    for queue in synched_chains.schedule.iter().flat_map(|family| family.iter()) {
+        for (_, submission) in queue.iter() {
+            for guard in &[&submission.sync().acquire, &submission.sync().release] {
+                for barrier in guard.images.values() {
+                    let aspects = barrier.range.as_ref().map(|range| range.aspects);
+                    assert!(
+                        aspects == Some(Aspects::DEPTH) || aspects == Some(Aspects::STENCIL),
+                        "Barrier applies to both aspects: {:#?}",
+                        barrier
+                    );
+                }
+            }
+        }
+    }
+
+    let mut rng = Isaac64Rng::new_unseeded();
+    sanity_check(&mut rng, &synched_chains, &passes, semaphore_id, false);
+}
+
+/// Declarations of the same resource in builder must be merged or rejected.
+fn test_pass_builder() {
+    let albedo = Id::new(0);
+    let target = Id::new(1);
+    let lights = Id::new(0);
+    let pass = PassBuilder::new(PassId(0), QueueFamilyId(0))
+        .sample_image(albedo, PipelineStage::VERTEX_SHADER)
+        .sample_image(albedo, PipelineStage::FRAGMENT_SHADER)
+        .color_attachment(target)
+        .storage_buffer_write(lights, PipelineStage::COMPUTE_SHADER)
+        .uniform_buffer(lights, PipelineStage::FRAGMENT_SHADER)
+        .build()
+        .unwrap();
+
+    let albedo = &pass.images[&albedo];
+    assert_eq!(albedo.state.access, ImageAccess::SHADER_READ);
+    assert_eq!(albedo.state.layout, ImageLayout::ShaderReadOnlyOptimal);
+    assert_eq!(
+        albedo.state.stages,
+        PipelineStage::VERTEX_SHADER | PipelineStage::FRAGMENT_SHADER
+    );
+    assert_eq!(albedo.usage, ImageUsage::SAMPLED);
+    assert_eq!(
+        pass.images[&target].state.layout,
+        ImageLayout::ColorAttachmentOptimal
+    );
+    let lights = &pass.buffers[&lights];
+    assert_eq!(
+        lights.state.access,
+        BufferAccess::SHADER_WRITE | BufferAccess::CONSTANT_BUFFER_READ
+    );
+    assert_eq!(lights.usage, BufferUsage::STORAGE | BufferUsage::UNIFORM);
+
+    let conflict = PassBuilder::new(PassId(1), QueueFamilyId(0))
+        .sample_image(target, PipelineStage::FRAGMENT_SHADER)
+        .raw_image_state(
+            target,
+            State {
+                access: ImageAccess::empty(),
+                layout: ImageLayout::Present,
+                stages: PipelineStage::BOTTOM_OF_PIPE,
+            },
+        )
+        .build();
+    match conflict {
+        Err(PassBuildError::IncompatibleImageStates { id, pass, .. }) => {
+            assert_eq!((id, pass), (target, PassId(1)))
+        }
+        other => panic!("Conflicting declarations are not rejected: {:?}", other),
+    }
+}
+
+/// Barriers before the pass are combined into one pipeline barrier command
+/// with union of their stages. Parts of the image keep separate entries.
+fn test_combined_pipeline_barrier() {
+    let (buffer, image) = (Id::new(0), Id::new(0));
+    let level = |level, state| StateUsage {
+        state,
+        usage: Usage::none(),
+        range: Some(SubresourceRange {
+            aspects: Aspects::COLOR,
+            levels: level..level + 1,
+            layers: 0..1,
+        }),
+        discard: false,
+        ordering: OrderingHint::None,
+    };
+    let passes = vec![
+        PassBuilder::new(PassId(0), QueueFamilyId(0))
+            .transfer_buffer_dst(buffer)
+            .raw_image(image, level(0, State::<Image>::transfer_dst()))
+            .raw_image(image, level(1, State::<Image>::transfer_dst()))
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(1), QueueFamilyId(0))
+            .depends_on(PassId(0))
+            .vertex_buffer(buffer)
+            .raw_image(image, level(0, State::sampled(PipelineStage::FRAGMENT_SHADER)))
+            .raw_image(image, level(1, State::<Image>::transfer_src()))
+            .build()
+            .unwrap(),
+    ];
+    let mut resources = Resources::new();
+    resources.add_buffer(buffer, ResourceInfo::default());
+    resources.add_image(image, ResourceInfo::default());
+    let chains = build(passes, &resources, |_| 1, || (0, 0)).unwrap();
+
+    let sid = SubmissionId::new(QueueId::new(QueueFamilyId(0), 0), 1);
+    let barrier = chains.schedule[sid].sync().acquire.pipeline_barrier().unwrap();
+    assert_eq!(
+        barrier.stages,
+        PipelineStage::TRANSFER
+            ..PipelineStage::VERTEX_INPUT | PipelineStage::FRAGMENT_SHADER | PipelineStage::TRANSFER
+    );
+    assert_eq!(barrier.buffers.len(), 1);
+    assert_eq!(barrier.buffers[0].0, buffer);
+    let mut layouts: Vec<_> = barrier
+        .images
+        .iter()
+        .map(|&(id, image_barrier)| {
+            assert_eq!(id, image);
+            let levels = image_barrier.range.as_ref().map(|range| range.levels.clone());
+            (levels, image_barrier.states.end.layout)
+        })
+        .collect();
+    layouts.sort_by_key(|(levels, _)| levels.as_ref().map(|levels| levels.start));
+    assert_eq!(
+        layouts,
+        vec![
+            (Some(0..1), ImageLayout::ShaderReadOnlyOptimal),
+            (Some(1..2), ImageLayout::TransferSrcOptimal),
+        ]
+    );
+}
+
+/// Write after read needs only execution dependency, so barrier has no source access.
+/// Read after write and layout transitions keep source access.
+fn test_write_after_read_barriers() {
+    let buffer = Id::new(0);
+    let image = Id::new(0);
+    let passes = vec![
+        PassBuilder::new(PassId(0), QueueFamilyId(0))
+            .storage_buffer_write(buffer, PipelineStage::COMPUTE_SHADER)
+            .sample_image(image, PipelineStage::COMPUTE_SHADER)
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(1), QueueFamilyId(0))
+            .depends_on(PassId(0))
+            .uniform_buffer(buffer, PipelineStage::COMPUTE_SHADER)
+            .storage_image_write(image, PipelineStage::COMPUTE_SHADER)
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(2), QueueFamilyId(0))
+            .depends_on(PassId(1))
+            .storage_buffer_write(buffer, PipelineStage::COMPUTE_SHADER)
+            .build()
+            .unwrap(),
+    ];
+
+    let chains = collect(passes.clone(), &declared(&passes), |_| 1).unwrap();
+    let mut semaphore_id = 0;
+    let schedule = sync_with_options(&chains, SyncOptions::default(), || {
+        let id = semaphore_id;
+        semaphore_id += 1;
+        (id, id)
+    }).unwrap();
+    let synched_chains = Chains {
+        schedule,
+        buffers: chains.buffers,
+        images: chains.images,
+        stencils: chains.stencils,
+        parts: chains.parts,
+        buffer_parts: chains.buffer_parts,
+    };
+    if let Err(errors) = validate(&synched_chains, &passes) {
+        panic!("Validation failed: {:#?}", errors);
+    }
+
+    let acquire = |pass| {
+        synched_chains
+            .schedule
+            .iter()
+            .flat_map(|family| family.iter())
+            .flat_map(|queue| queue.iter())
+            .find(|&(_, submission)| submission.pass() == pass)
+            .map(|(_, submission)| &submission.sync().acquire)
+            .unwrap()
+    };
+    let read = &acquire(PassId(1)).buffers[&buffer];
+    assert_eq!(read.states.start.hazard(&read.states.end), AccessHazard::ReadAfterWrite);
+    assert_eq!(read.states.start.access, BufferAccess::SHADER_WRITE);
+    let write = &acquire(PassId(2)).buffers[&buffer];
+    assert_eq!(write.states.start.hazard(&write.states.end), AccessHazard::WriteAfterRead);
+    assert_eq!(
+        write.states.start.access,
+        BufferAccess::empty(),
+        "Write after read flushes caches."
+    );
+    assert_eq!(write.states.start.stages, PipelineStage::COMPUTE_SHADER);
+    let transition = &acquire(PassId(1)).images[&image];
+    assert_ne!(transition.states.start.layout, transition.states.end.layout);
+    assert_eq!(transition.states.start.access, ImageAccess::SHADER_READ);
+
+    let mut rng = Isaac64Rng::new_unseeded();
+    sanity_check(&mut rng, &synched_chains, &passes, semaphore_id, false);
+}
+
+/// G-buffer images declared as a group are transitioned by one barrier record.
+/// Declaring a member on its own or tracking it differently is rejected.
+fn test_image_groups() {
+    let gbuffer = ResourceGroup::new((0..4).map(Id::new));
+    let passes = vec![
+        PassBuilder::new(PassId(0), QueueFamilyId(0))
+            .image_group(&gbuffer, PassBuilder::clear_color_attachment)
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(1), QueueFamilyId(0))
+            .depends_on(PassId(0))
+            .image_group(&gbuffer, |builder, id| {
+                builder.sample_image(id, PipelineStage::FRAGMENT_SHADER)
+            })
+            .build()
+            .unwrap(),
+    ];
+    let mut resources = Resources::new();
+    resources.add_image_group(gbuffer.clone());
+    register_declared(&mut resources, &passes);
+
+    let chains = collect(passes.clone(), &resources, |_| 1).unwrap();
+    let mut semaphore_id = 0;
+    let schedule = sync_with_options(&chains, SyncOptions::default(), || {
+        let id = semaphore_id;
+        semaphore_id += 1;
+        (id, id)
+    }).unwrap();
+    let synched_chains = Chains {
+        schedule,
+        buffers: chains.buffers,
+        images: chains.images,
+        stencils: chains.stencils,
+        parts: chains.parts,
+        buffer_parts: chains.buffer_parts,
+    };
+    if let Err(errors) = validate(&synched_chains, &passes) {
+        panic!("Validation failed: {:#?}", errors);
+    }
+    let walker = ScheduleWalker::new(&synched_chains.schedule);
+    let info = walker
+        .queues()
+        .flat_map(|queue| queue.iter())
+        .find(|info| info.pass() == PassId(1))
+        .unwrap();
+    match info.before() {
+        [BarrierRecord::Pipeline(ref barrier)] => {
+            let ids: Vec<_> = barrier.images.iter().map(|&(id, _)| id).collect();
+            assert_eq!(&ids[..], gbuffer.ids(), "Group is not transitioned together.");
+        }
+        records => panic!("Group is transitioned by several records: {:?}", records),
+    }
+    let mut rng = Isaac64Rng::new_unseeded();
+    sanity_check(&mut rng, &synched_chains, &passes, semaphore_id, false);
+
+    let mut split = passes.clone();
+    split.push(
+        PassBuilder::new(PassId(2), QueueFamilyId(0))
+            .depends_on(PassId(1))
+            .storage_image_write(Id::new(2), PipelineStage::COMPUTE_SHADER)
+            .build()
+            .unwrap(),
+    );
+    match collect(split, &resources, |_| 1) {
+        Err(CollectError::SplitImageGroup { id, group, pass }) => {
+            assert_eq!((id, group, pass), (Id::new(2), Id::new(0), PassId(2)))
+        }
+        other => panic!("Split group is not rejected: {:?}", other.map(|_| ())),
+    }
+
+    let mut diverged = resources.clone();
+    diverged.images.get_mut(&Id::new(3)).unwrap().present = true;
+    match collect(passes.clone(), &diverged, |_| 1) {
+        Err(CollectError::DivergedImageGroup { id, group }) => {
+            assert_eq!((id, group), (Id::new(3), Id::new(0)))
+        }
+        other => panic!("Diverged group is not rejected: {:?}", other.map(|_| ())),
+    }
+
+    let mut incremental =
+        IncrementalChains::new(passes, resources, |_| 1, SyncOptions::default(), || (0, 0))
+            .unwrap();
+    let state = State {
+        access: ImageAccess::SHADER_READ,
+        layout: ImageLayout::General,
+        stages: PipelineStage::FRAGMENT_SHADER,
+    };
+    match incremental.update_pass_states(PassId(1), &[(Id::new(1), state)]) {
+        Err(CollectError::SplitImageGroup { id, .. }) => assert_eq!(id, Id::new(1)),
+        other => panic!("Update splitting group is not rejected: {:?}", other),
+    }
+}
+
+/// Camera update hinted to go late is placed right before the pass that reads it.
+/// Hint that dependencies don't allow to honor is reported.
+fn test_ordering_hints() {
+    let camera = Id::new(0);
+    let passes = |update_first: bool| {
+        let mut shadows = PassBuilder::new(PassId(0), QueueFamilyId(0))
+            .storage_image_write(Id::new(0), PipelineStage::COMPUTE_SHADER)
+            .image_ordering(Id::new(0), OrderingHint::AsEarlyAsPossible);
+        if update_first {
+            shadows = shadows.depends_on(PassId(1));
+        }
+        vec![
+            shadows.build().unwrap(),
+            PassBuilder::new(PassId(1), QueueFamilyId(0))
+                .transfer_buffer_dst(camera)
+                .buffer_ordering(camera, OrderingHint::AsLateAsPossible)
+                .build()
+                .unwrap(),
+            PassBuilder::new(PassId(2), QueueFamilyId(0))
+                .depends_on(PassId(0))
+                .depends_on(PassId(1))
+                .uniform_buffer(camera, PipelineStage::FRAGMENT_SHADER)
+                .sample_image(Id::new(0), PipelineStage::FRAGMENT_SHADER)
+                .build()
+                .unwrap(),
+            PassBuilder::new(PassId(3), QueueFamilyId(0))
+                .storage_image_write(Id::new(1), PipelineStage::COMPUTE_SHADER)
+                .build()
+                .unwrap(),
+        ]
+    };
+
+    for &update_first in &[false, true] {
+        let passes = passes(update_first);
+        let chains = collect(passes.clone(), &declared(&passes), |_| 1).unwrap();
+        let queue = chains.schedule.queue(QueueId::new(QueueFamilyId(0), 0)).unwrap();
+        let order: Vec<_> = queue.iter().map(|(_, submission)| submission.pass()).collect();
+        let (sid, update) = queue
+            .iter()
+            .find(|&(_, submission)| submission.pass() == PassId(1))
+            .unwrap();
+        if update_first {
+            assert_eq!(order, [PassId(3), PassId(1), PassId(0), PassId(2)]);
+            assert_eq!(update.unhonored_buffer_hints(), [camera]);
+        } else {
+            assert_eq!(order, [PassId(0), PassId(3), PassId(1), PassId(2)]);
+            assert_eq!(queue[SubmissionId::new(sid.queue(), sid.index() + 1)].pass(), PassId(2));
+            assert!(update.unhonored_buffer_hints().is_empty(), "Late hint isn't honored.");
+        }
+        let (_, shadows) = queue
+            .iter()
+            .find(|&(_, submission)| submission.pass() == PassId(0))
+            .unwrap();
+        assert_eq!(shadows.unhonored_image_hints().is_empty(), !update_first);
+    }
+}
+
+/// Declarations of the image by one pass in layouts that can't be merged
+/// fail collection with error naming the image and the pass instead of panicking.
+fn test_incompatible_states() {
+    let (other, image) = (Id::new(0), Id::new(1));
+    let mut pass = PassBuilder::new(PassId(3), QueueFamilyId(0))
+        .sample_image(other, PipelineStage::FRAGMENT_SHADER)
+        .sample_image(image, PipelineStage::FRAGMENT_SHADER)
+        .build()
+        .unwrap();
+    pass.image_ranges.insert(
+        image,
+        vec![StateUsage {
+            state: State {
+                access: ImageAccess::empty(),
+                layout: ImageLayout::Present,
+                stages: PipelineStage::BOTTOM_OF_PIPE,
+            },
+            usage: Usage::none(),
+            range: Some(SubresourceRange {
+                aspects: Aspects::COLOR,
+                levels: 0..1,
+                layers: 0..1,
+            }),
+            discard: false,
+            ordering: OrderingHint::None,
+        }],
+    );
+    let passes = vec![pass];
+    match collect(passes.clone(), &declared(&passes), |_| 1) {
+        Err(CollectError::IncompatibleImageStates { id, pass, error }) => {
+            assert_eq!((id, pass), (image, PassId(3)));
+            let layouts = [error.left.layout, error.right.layout];
+            assert!(
+                layouts.contains(&ImageLayout::Present)
+                    && layouts.contains(&ImageLayout::ShaderReadOnlyOptimal)
+            );
+        }
+        other => panic!("Incompatible states are merged: {:?}", other.map(|_| ())),
+    }
+
+    // Layouts of buffer states always merge, but links report them the same way as of images.
+    let buffer = Id::new(2);
+    let (left, right) = (State::<Buffer>::indirect_read(), State::<Buffer>::transfer_dst());
+    match ChainError::from((buffer, PassId(2), MergeError { left, right })) {
+        ChainError::IncompatibleBufferStates { id, pass, error } => {
+            assert_eq!((id, pass), (buffer, PassId(2)));
+            assert_eq!((error.left, error.right), (left, right));
+        }
+        other => panic!("Unexpected error for buffer states: {:?}", other),
+    }
+}
+
+/// Registering the same resource twice is caught in debug builds.
+fn test_duplicate_registration() {
+    if !cfg!(debug_assertions) {
+        return;
+    }
+    let mut resources = Resources::new();
+    resources.add_buffer(Id::new(0), ResourceInfo::default());
+    resources.add_image(Id::new(0), ResourceInfo::default());
+    resources.add_image(Id::new(1), ResourceInfo::default());
+
+    let hook = take_hook();
+    set_hook(Box::new(|_| {}));
+    let buffer = catch_unwind(AssertUnwindSafe(|| {
+        resources.clone().add_buffer(Id::new(0), ResourceInfo::default())
+    }));
+    let image = catch_unwind(AssertUnwindSafe(|| {
+        resources.clone().add_image(Id::new(1), ResourceInfo::default())
+    }));
+    set_hook(hook);
+    assert!(buffer.is_err(), "Buffer registered twice is accepted.");
+    assert!(image.is_err(), "Image registered twice is accepted.");
+}
+
+/// Malformed pass graphs are rejected with errors naming offending passes.
+fn test_collect_errors() {
+    let pass = |id| PassBuilder::new(PassId(id), QueueFamilyId(0)).transfer_buffer_dst(Id::new(0));
+    let sampling = |id| pass(id).sample_image(Id::new(1), PipelineStage::FRAGMENT_SHADER);
+    let passes = vec![
+        sampling(1).build().unwrap(),
+        sampling(0).uniform_buffer(Id::new(2), PipelineStage::VERTEX_SHADER).build().unwrap(),
+    ];
+    let mut resources = Resources::new();
+    match collect(passes.clone(), &resources, |_| 1) {
+        Err(ChainError::UnknownBuffer { id, pass }) => {
+            assert_eq!((id, pass), (Id::new(0), PassId(0)))
+        }
+        other => panic!("Unknown buffer is accepted: {:?}", other.map(|_| ())),
+    }
+    resources.add_buffer(Id::new(0), ResourceInfo::default());
+    resources.add_buffer(Id::new(2), ResourceInfo::default());
+    match collect(passes.clone(), &resources, |_| 1) {
+        Err(ChainError::UnknownImage { id, pass }) => {
+            assert_eq!((id, pass), (Id::new(1), PassId(0)))
+        }
+        other => panic!("Unknown image is accepted: {:?}", other.map(|_| ())),
+    }
+    resources.add_image(Id::new(1), ResourceInfo::default());
+    let chains = collect(passes.clone(), &resources, |_| 1).unwrap();
+    let truncated = collect(passes[1..].to_vec(), &resources, |_| 1).unwrap();
+    let chains = Chains {
+        schedule: truncated.schedule,
+        ..chains
+    };
+    let sid = SubmissionId::new(QueueId::new(QueueFamilyId(0), 0), 1);
+    match sync(&chains, || (0, 0)) {
+        Err(ChainError::UnknownSubmission { sid: unknown }) => assert_eq!(unknown, sid),
+        other => panic!("Unscheduled submission is accepted: {:?}", other.map(|_| ())),
+    }
+
+    let collect = |passes: Vec<PassBuilder>, queues| {
+        let passes: Vec<_> = passes.into_iter().map(|pass| pass.build().unwrap()).collect();
+        let result = collect(passes.clone(), &declared(&passes), |_| queues);
+        if let Err(ref error) = result {
+            assert!(!error.to_string().is_empty());
+        }
+        result.err()
+    };
+
+    match collect(vec![pass(0), pass(1), pass(0)], 1) {
+        Some(ChainError::DuplicatePass { pass: PassId(0) }) => {}
+        other => panic!("Duplicate pass is accepted: {:?}", other),
+    }
+    match collect(vec![pass(0), pass(1).depends_on(PassId(5))], 1) {
+        Some(ChainError::UnknownDependency {
+            pass: PassId(1),
+            dependency: PassId(5),
+        }) => {}
+        other => panic!("Unknown dependency is accepted: {:?}", other),
+    }
+    let cyclic = vec![
+        pass(3),
+        pass(0).depends_on(PassId(3)).depends_on(PassId(2)),
+        pass(1).depends_on(PassId(0)),
+        pass(2).depends_on(PassId(1)),
+    ];
+    match collect(cyclic, 1) {
+        Some(ChainError::DependencyCycle { passes }) => {
+            assert_eq!(passes, [PassId(0), PassId(2), PassId(1)]);
+        }
+        other => panic!("Dependency cycle is accepted: {:?}", other),
+    }
+    match collect(vec![pass(0).depends_on(PassId(0))], 1) {
+        Some(ChainError::DependencyCycle { passes }) => assert_eq!(passes, [PassId(0)]),
+        other => panic!("Pass depending on itself is accepted: {:?}", other),
+    }
+    match collect(vec![pass(0)], 0) {
+        Some(ChainError::NoQueues {
+            pass: PassId(0),
+            family: QueueFamilyId(0),
+        }) => {}
+        other => panic!("Family without queues is accepted: {:?}", other),
+    }
+    match collect(vec![pass(0).queue(1)], 1) {
+        Some(ChainError::QueueOutOfRange {
+            pass: PassId(0),
+            queue: 1,
+            ..
+        }) => {}
+        other => panic!("Missing queue is accepted: {:?}", other),
+    }
+    assert!(collect(Vec::new(), 0).is_none(), "Empty graph is rejected.");
+}
+
+/// Generated blits transition single levels and are ordered between producer and readers.
+fn test_generate_mipmaps() {
+    let id = Id::new(0);
+    let range = SubresourceRange {
+        aspects: Aspects::COLOR,
+        levels: 0..4,
+        layers: 0..1,
+    };
+    let producers = vec![
+        PassBuilder::new(PassId(0), QueueFamilyId(0)).color_attachment(id),
+        PassBuilder::new(PassId(0), QueueFamilyId(0)).raw_image(
+            id,
+            StateUsage {
+                state: State {
+                    access: ImageAccess::COLOR_ATTACHMENT_WRITE,
+                    layout: ImageLayout::ColorAttachmentOptimal,
+                    stages: PipelineStage::COLOR_ATTACHMENT_OUTPUT,
+                },
+                usage: Usage::none(),
+                range: Some(SubresourceRange {
+                    levels: 0..1,
+                    ..range.clone()
+                }),
+                discard: false,
+                ordering: OrderingHint::None,
+            },
+        ),
+    ];
+    for producer in producers {
+        let mut passes = vec![
+            producer.build().unwrap(),
+            PassBuilder::new(PassId(1), QueueFamilyId(0))
+                .depends_on(PassId(0))
+                .sample_image(id, PipelineStage::FRAGMENT_SHADER)
+                .build()
+                .unwrap(),
+        ];
+        let blits =
+            generate_mipmaps(&mut passes, PassId(0), id, range.clone(), QueueFamilyId(0)).unwrap();
+        assert_eq!(blits, [PassId(2), PassId(3), PassId(4)]);
+        assert!(passes[1].dependencies.contains(&PassId(4)));
+
+        let chains = collect(passes.clone(), &declared(&passes), |_| 1).unwrap();
+        let schedule = sync_with_options(&chains, SyncOptions::default(), || (0, 0)).unwrap();
+        let queue = schedule.queue(QueueId::new(QueueFamilyId(0), 0)).unwrap();
+        let order: Vec<_> = queue.iter().map(|(_, submission)| submission.pass()).collect();
+        assert_eq!(order, [0, 2, 3, 4, 1].iter().cloned().map(PassId).collect::<Vec<_>>());
+        let layout = |level: u8, sid| {
+            let chain = chains.parts[&id]
+                .iter()
+                .find(|chain| chain.range().unwrap().levels == (level..level + 1))
+                .unwrap();
+            chain.link_of(sid).unwrap().state().layout
+        };
+        for (level, &blit) in blits.iter().enumerate() {
+            let (sid, _) = queue.iter().find(|&(_, s)| s.pass() == blit).unwrap();
+            let level = level as u8;
+            // Source level may share the link with later sampling in general layout.
+            let src = layout(level, sid);
+            assert!(src == ImageLayout::TransferSrcOptimal || src == ImageLayout::General);
+            assert_eq!(layout(level + 1, sid), ImageLayout::TransferDstOptimal);
+        }
+
+        let synched_chains = Chains {
+            schedule,
+            buffers: chains.buffers,
+            images: chains.images,
+            stencils: chains.stencils,
+            parts: chains.parts,
+            buffer_parts: chains.buffer_parts,
+        };
+        if let Err(errors) = validate(&synched_chains, &passes) {
+            panic!("Validation failed: {:#?}", errors);
+        }
+        #[cfg(feature = "test-utils")]
+        {
+            if let Err(hazard) = sim::verify(&synched_chains) {
+                panic!("Hazard found: {}", hazard);
+            }
+        }
+    }
+
+    match generate_mipmaps(&mut Vec::new(), PassId(0), id, range, QueueFamilyId(0)) {
+        Err(MipmapError::ProducerNotFound { pass: PassId(0) }) => {}
+        other => panic!("Missing producer is not reported: {:?}", other),
+    }
+}
+
+/// Passes accessing disjoint levels of the image share a link only in the same layout.
+/// Copy from one level and into another can't use one merged `General` layout.
+fn test_disjoint_layouts() {
+    let id = Id::new(0);
+    let level = |level, state| StateUsage {
+        state,
+        usage: Usage::none(),
+        range: Some(SubresourceRange {
+            aspects: Aspects::COLOR,
+            levels: level..level + 1,
+            layers: 0..1,
+        }),
+        discard: false,
+        ordering: OrderingHint::None,
+    };
+    let build = |states: [State<Image>; 2]| {
+        let passes: Vec<_> = (0..2)
+            .map(|index| {
+                PassBuilder::new(PassId(index), QueueFamilyId(0))
+                    .raw_image(id, level(index as u8, states[index]))
+                    .build()
+                    .unwrap()
+            })
+            .collect();
+        let mut resources = Resources::new();
+        resources.add_image(id, ResourceInfo::default());
+        let chains = collect(passes.clone(), &resources, |_| 1).unwrap();
+        let schedule = sync_with_options(&chains, SyncOptions::default(), || (0, 0)).unwrap();
+        let synched_chains = Chains {
+            schedule,
+            buffers: chains.buffers,
+            images: chains.images,
+            stencils: chains.stencils,
+            parts: chains.parts,
+            buffer_parts: chains.buffer_parts,
+        };
+        if let Err(errors) = validate(&synched_chains, &passes) {
+            panic!("Validation failed: {:#?}", errors);
+        }
+        let links = synched_chains.images[&id].links();
+        links.iter().map(|link| link.state().layout).collect::<Vec<_>>()
+    };
+
+    let (src, dst) = (State::<Image>::transfer_src(), State::<Image>::transfer_dst());
+    let layouts = build([src, dst]);
+    assert_eq!(layouts.len(), 2, "Disjoint levels in different layouts share a link.");
+    assert!(layouts.contains(&ImageLayout::TransferSrcOptimal));
+    assert!(layouts.contains(&ImageLayout::TransferDstOptimal));
+    assert_eq!(build([dst, dst]), vec![ImageLayout::TransferDstOptimal]);
+}
+
+/// Links are queried by submissions they span.
+/// Submission between links doesn't belong to any.
+fn test_link_views() {
+    let image = Id::new(0);
+    let passes = vec![
+        PassBuilder::new(PassId(0), QueueFamilyId(0))
+            .transfer_image_dst(image)
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(1), QueueFamilyId(0))
+            .depends_on(PassId(0))
+            .transfer_image_dst(Id::new(1))
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(2), QueueFamilyId(0))
+            .depends_on(PassId(1))
+            .sample_image(image, PipelineStage::FRAGMENT_SHADER)
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(3), QueueFamilyId(0))
+            .depends_on(PassId(2))
+            .sample_image(image, PipelineStage::FRAGMENT_SHADER)
+            .build()
+            .unwrap(),
+    ];
+    let resources = declared(&passes);
+    let chains = collect(passes, &resources, |_| 1).unwrap();
+    let qid = QueueId::new(QueueFamilyId(0), 0);
+    let sid = |index| SubmissionId::new(qid, index);
+    let chain = &chains.images[&image];
+
+    let views: Vec<_> = chain.link_views().collect();
+    assert_eq!(views.len(), 2);
+    assert_eq!(views[0].spans(), [sid(0)..sid(1)]);
+    assert_eq!(views[1].spans(), [sid(2)..sid(4)]);
+    assert_eq!(views[1].queues(), [qid]);
+    assert_eq!(views[1].state().layout, ImageLayout::ShaderReadOnlyOptimal);
+
+    assert_eq!(chain.link_of(sid(3)).map(|view| view.index()), Some(1));
+    let before = chain.link_before(sid(3)).unwrap();
+    assert_eq!(before.index(), 0);
+    assert_eq!(before.state().layout, ImageLayout::TransferDstOptimal);
+    assert!(before.state().access.is_write());
+    assert!(chain.link_of(sid(1)).is_none());
+    assert!(chain.link_before(sid(1)).is_none());
+    assert!(chain.link_before(sid(0)).is_none());
+}
+
+/// Passes are scheduled only to families capable of stages they declare.
+/// Capability specified for the pass must support declared stages.
+fn test_capabilities() {
+    assert_eq!(Capability::required(PipelineStage::COMPUTE_SHADER), Some(Capability::Compute));
+    assert_eq!(
+        Capability::required(PipelineStage::FRAGMENT_SHADER | PipelineStage::TRANSFER),
+        Some(Capability::Graphics)
+    );
+    assert_eq!(
+        Capability::required(PipelineStage::COLOR_ATTACHMENT_OUTPUT),
+        Some(Capability::Graphics)
+    );
+    assert_eq!(Capability::required(PipelineStage::TRANSFER), Some(Capability::Transfer));
+    assert_eq!(Capability::required(PipelineStage::HOST), None);
+    assert!(Capability::Graphics.supports(Capability::Compute));
+    assert!(Capability::Compute.supports(Capability::Transfer));
+    assert!(!Capability::Transfer.supports(Capability::Compute));
+
+    // Family 0 is capable of graphics and family 1 of transfers only.
+    let capability = |family: QueueFamilyId| {
+        if family.0 == 0 {
+            Capability::Graphics
+        } else {
+            Capability::Transfer
+        }
+    };
+    let collect = |passes: Vec<Pass>| {
+        collect_with_capabilities(passes.clone(), &declared(&passes), |_| 1, capability)
+    };
+
+    let compute = |id| {
+        PassBuilder::new(PassId(id), QueueFamilyId(1))
+            .allow_family(QueueFamilyId(0))
+            .storage_buffer_write(Id::new(id as u32), PipelineStage::COMPUTE_SHADER)
+    };
+    let chains = collect(vec![
+        compute(0).build().unwrap(),
+        PassBuilder::new(PassId(1), QueueFamilyId(1))
+            .transfer_buffer_dst(Id::new(1))
+            .build()
+            .unwrap(),
+    ]).unwrap();
+    for &(pass, family) in &[(PassId(0), 0), (PassId(1), 1)] {
+        let queue = chains.schedule.queue(QueueId::new(QueueFamilyId(family), 0)).unwrap();
+        assert!(queue.iter().any(|(_, submission)| submission.pass() == pass));
+    }
+
+    match collect(vec![compute(0).capability(Capability::Transfer).build().unwrap()]) {
+        Err(CollectError::InsufficientCapability {
+            pass: PassId(0),
+            capability: Capability::Transfer,
+            stage,
+        }) => assert_eq!(stage, PipelineStage::COMPUTE_SHADER),
+        result => panic!("Insufficient capability is accepted: {:?}", result.err()),
+    }
+    let incapable = vec![
+        PassBuilder::new(PassId(0), QueueFamilyId(1))
+            .transfer_buffer_dst(Id::new(0))
+            .capability(Capability::Compute)
+            .build()
+            .unwrap(),
+    ];
+    match collect(incapable) {
+        Err(CollectError::IncapableFamilies {
+            pass: PassId(0),
+            family: QueueFamilyId(1),
+            capability: Capability::Compute,
+        }) => {}
+        result => panic!("Pass is scheduled to incapable family: {:?}", result.err()),
+    }
+}
+
+/// Dependency between passes pinned to different families is synchronized by single semaphore.
+fn test_pinned_families() {
+    let (occlusion, target) = (Id::new(0), Id::new(1));
+    let families = [
+        FamilyInfo::new(QueueFamilyId(0), Capability::Graphics, 1),
+        FamilyInfo::new(QueueFamilyId(1), Capability::Compute, 1),
+    ];
+    let passes = vec![
+        PassBuilder::new(PassId(0), QueueFamilyId(1))
+            .storage_image_write(occlusion, PipelineStage::COMPUTE_SHADER)
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(1), QueueFamilyId(0))
+            .depends_on(PassId(0))
+            .sample_image(occlusion, PipelineStage::FRAGMENT_SHADER)
+            .color_attachment(target)
+            .build()
+            .unwrap(),
+    ];
+    let chains = collect_with_families(passes.clone(), &declared(&passes), &families).unwrap();
+    let mut semaphore_id = 0;
+    let synched_chains = sync_chains(chains, SyncOptions::default(), || {
+        let id = semaphore_id;
+        semaphore_id += 1;
+        (id, id)
+    }).unwrap();
+    if let Err(errors) = validate(&synched_chains, &passes) {
+        panic!("Validation failed: {:#?}", errors);
+    }
+
+    let compute = SubmissionId::new(QueueId::new(QueueFamilyId(1), 0), 0);
+    let graphics = SubmissionId::new(QueueId::new(QueueFamilyId(0), 0), 0);
+    assert_eq!(synched_chains.schedule[compute].pass(), PassId(0));
+    assert_eq!(synched_chains.schedule[graphics].pass(), PassId(1));
+    let total = synched_chains.stats().total;
+    assert_eq!((total.semaphore_signals, total.semaphore_waits), (1, 1));
+    assert_eq!(semaphore_id, 1);
+    let (signal, wait) = (
+        &synched_chains.schedule[compute].sync().release.signal,
+        &synched_chains.schedule[graphics].sync().acquire.wait,
+    );
+    assert_eq!((signal.len(), wait.len()), (1, 1));
+    assert_eq!(signal[0].semaphore(), &0);
+    assert_eq!(wait[0].semaphore(), &0);
+}
+
+/// Passes transferring regions finer than granularity of transfer family fall back to others.
+fn test_transfer_granularity() {
+    let extent = |width, height| Extent {
+        width,
+        height,
+        depth: 1,
+    };
+    let region = |x, y, width, height| TransferRegion {
+        offset: Offset { x, y, z: 0 },
+        extent: extent(width, height),
+        level_extent: extent(60, 64),
+    };
+    let coarse = extent(8, 8);
+    assert!(region(8, 16, 16, 8).fits(coarse));
+    assert!(region(56, 0, 4, 64).fits(coarse), "Region at the edge of the level doesn't fit.");
+    assert!(!region(4, 0, 8, 8).fits(coarse));
+    assert!(!region(0, 0, 8, 4).fits(coarse));
+    assert!(!region(-8, 0, 8, 8).fits(coarse));
+    assert!(region(4, 0, 3, 5).fits(extent(1, 1)));
+    assert!(TransferRegion::whole(extent(60, 64)).fits(Extent {
+        width: 0,
+        height: 0,
+        depth: 0,
+    }));
+    assert!(!region(0, 0, 8, 8).fits(extent(0, 0)), "Partial level fits zero granularity.");
+
+    // Family 0 is capable of graphics and family 1 transfers only aligned regions.
+    let families = [
+        FamilyInfo::new(QueueFamilyId(0), Capability::Graphics, 1),
+        FamilyInfo {
+            image_transfer_granularity: coarse,
+            ..FamilyInfo::new(QueueFamilyId(1), Capability::Transfer, 1)
+        },
+    ];
+    let collect =
+        |passes: Vec<Pass>| collect_with_families(passes.clone(), &declared(&passes), &families);
+    let copy = |id, region| {
+        PassBuilder::new(PassId(id), QueueFamilyId(1))
+            .transfer_image_dst(Id::new(id as u32))
+            .transfer_region(Id::new(id as u32), region)
+    };
+
+    let chains = collect(vec![
+        copy(0, region(4, 0, 8, 8))
+            .allow_family(QueueFamilyId(0))
+            .build()
+            .unwrap(),
+        copy(1, region(8, 8, 8, 8)).build().unwrap(),
+    ]).unwrap();
+    for &(pass, family) in &[(PassId(0), 0), (PassId(1), 1)] {
+        let queue = chains.schedule.queue(QueueId::new(QueueFamilyId(family), 0)).unwrap();
+        assert!(
+            queue.iter().any(|(_, submission)| submission.pass() == pass),
+            "Pass {:?} is not scheduled to family {}",
+            pass,
+            family
+        );
+    }
+
+    let misaligned = vec![
+        copy(0, region(8, 0, 8, 8)).build().unwrap(),
+        copy(1, region(0, 4, 8, 8)).build().unwrap(),
+    ];
+    match collect(misaligned) {
+        Err(error @ ChainError::MisalignedImageTransfer { .. }) => {
+            assert!(!error.to_string().is_empty());
+            match error {
+                ChainError::MisalignedImageTransfer {
+                    id,
+                    pass: PassId(1),
+                    family: QueueFamilyId(1),
+                } => assert_eq!(id, Id::new(1)),
+                error => panic!("Wrong pass or family is reported: {}", error),
+            }
+        }
+        result => panic!("Misaligned transfer is accepted: {:?}", result.err()),
+    }
+    // Pinned queue forbids other families.
+    let pinned = copy(0, region(4, 0, 8, 8))
+        .allow_family(QueueFamilyId(0))
+        .queue(0)
+        .build()
+        .unwrap();
+    match collect(vec![pinned]) {
+        Err(ChainError::MisalignedImageTransfer {
+            pass: PassId(0),
+            family: QueueFamilyId(1),
+            ..
+        }) => {}
+        result => panic!("Misaligned transfer on pinned queue: {:?}", result.err()),
+    }
+}
+
+/// Clear of the image sampled by earlier pass must not wait for its content.
+/// Barrier discards the content but still orders the clear after the reads.
+fn test_discard_after_sample() {
+    let id = Id::new(0);
+    let passes = vec![
+        PassBuilder::new(PassId(0), QueueFamilyId(0))
+            .sample_image(id, PipelineStage::FRAGMENT_SHADER)
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(1), QueueFamilyId(0))
+            .depends_on(PassId(0))
+            .clear_image(id)
+            .build()
+            .unwrap(),
+    ];
+
+    let mut resources = Resources::new();
+    resources.add_image(
+        id,
+        ResourceInfo {
+            initial_state: Some(State {
+                access: ImageAccess::SHADER_READ,
+                layout: ImageLayout::ShaderReadOnlyOptimal,
+                stages: PipelineStage::FRAGMENT_SHADER,
+            }),
+            ..ResourceInfo::default()
+        },
+    );
+
+    let chains = collect(passes.clone(), &resources, |_| 1).unwrap();
+    let chain = &chains.images[&id];
+    assert_eq!(chain.links().len(), 2);
+    assert!(chain.discards(1), "Clear doesn't discard the content.");
+
+    let mut semaphore_id = 0;
+    let schedule = sync_with_options(&chains, SyncOptions::default(), || {
+        let id = semaphore_id;
+        semaphore_id += 1;
+        (id, id)
+    }).unwrap();
+    let synched_chains = Chains {
+        schedule,
+        buffers: chains.buffers,
+        images: chains.images,
+        stencils: chains.stencils,
+        parts: chains.parts,
+        buffer_parts: chains.buffer_parts,
+    };
+    if let Err(errors) = validate(&synched_chains, &passes) {
+        panic!("Validation failed: {:#?}", errors);
+    }
+
+    let barrier = synched_chains
+        .schedule
+        .iter()
+        .flat_map(|family| family.iter())
+        .flat_map(|queue| queue.iter())
+        .filter(|&(_, submission)| submission.pass() == PassId(1))
+        .filter_map(|(_, submission)| submission.sync().acquire.images.get(&id))
+        .next()
+        .expect("Barrier before the clear is missing.");
+    assert_eq!(barrier.states.start.layout, ImageLayout::Undefined);
+    assert_eq!(barrier.states.start.access, ImageAccess::empty());
+    assert_eq!(barrier.states.start.stages, PipelineStage::FRAGMENT_SHADER);
+    assert_eq!(barrier.states.end.layout, ImageLayout::TransferDstOptimal);
+
+    let mut rng = Isaac64Rng::new_unseeded();
+    sanity_check(&mut rng, &synched_chains, &passes, semaphore_id, false);
+}
+
+/// Sparse binding between passes on the same queue is ordered with semaphores.
+fn test_sparse_bind_between_passes() {
+    let id = Id::new(0);
+    let passes = vec![
+        PassBuilder::new(PassId(0), QueueFamilyId(0))
+            .storage_image_write(id, PipelineStage::COMPUTE_SHADER)
+            .build()
+            .unwrap(),
+        PassBuilder::sparse_bind(PassId(1), QueueFamilyId(0))
+            .depends_on(PassId(0))
+            .bind_image(id, None)
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(2), QueueFamilyId(0))
+            .depends_on(PassId(1))
+            .sample_image(id, PipelineStage::FRAGMENT_SHADER)
+            .build()
+            .unwrap(),
+    ];
+
+    let mut resources = Resources::new();
+    resources.add_image(id, ResourceInfo::default());
+
+    let chains = collect(passes.clone(), &resources, |_| 1).unwrap();
+    let chain = &chains.images[&id];
+    assert_eq!(chain.links().len(), 2);
+    assert_eq!(chain.binds().len(), 1);
+    assert_eq!(chain.binds()[0].link, 1);
+
+    let mut semaphore_id = 0;
+    let schedule = sync_with_options(&chains, SyncOptions::default(), || {
+        let id = semaphore_id;
+        semaphore_id += 1;
+        (id, id)
+    }).unwrap();
+    let synched_chains = Chains {
+        schedule,
+        buffers: chains.buffers,
+        images: chains.images,
+        stencils: chains.stencils,
+        parts: chains.parts,
+        buffer_parts: chains.buffer_parts,
+    };
+    if let Err(errors) = validate(&synched_chains, &passes) {
+        panic!("Validation failed: {:#?}", errors);
+    }
+
+    let submissions: Vec<_> = synched_chains
+        .schedule
+        .iter()
+        .flat_map(|family| family.iter())
+        .flat_map(|queue| queue.iter())
+        .map(|(_, submission)| submission)
+        .collect();
+    assert_eq!(submissions.len(), 3);
+    let bind = submissions[1].sync();
+    assert_eq!(submissions[1].kind(), PassKind::SparseBind);
+    assert!(bind.acquire.pipeline_barrier().is_none(), "Barrier in binding submission.");
+    assert!(bind.release.pipeline_barrier().is_none(), "Barrier in binding submission.");
+
+    let signaled = |guard: &Guard<usize, usize>, wait: &Guard<usize, usize>| {
+        guard.signal.iter().any(|signal| {
+            wait.wait
+                .iter()
+                .any(|wait| wait.semaphore() == signal.semaphore())
+        })
+    };
+    assert!(
+        signaled(&submissions[0].sync().release, &bind.acquire),
+        "Binding doesn't wait for the previous pass."
+    );
+    assert!(
+        signaled(&bind.release, &submissions[2].sync().acquire),
+        "Next pass doesn't wait for the binding."
+    );
+
+    let mut rng = Isaac64Rng::new_unseeded();
+    sanity_check(&mut rng, &synched_chains, &passes, semaphore_id, false);
+}
+
+/// Indirect arguments written on compute queue are read at `DRAW_INDIRECT` stage.
+fn test_indirect_across_queues() {
+    let id = Id::new(0);
+    let passes = vec![
+        PassBuilder::new(PassId(0), QueueFamilyId(0))
+            .storage_buffer_write(id, PipelineStage::COMPUTE_SHADER)
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(1), QueueFamilyId(1))
+            .depends_on(PassId(0))
+            .indirect_buffer(id)
+            .build()
+            .unwrap(),
+    ];
+
+    let mut resources = Resources::new();
+    resources.add_buffer(
+        id,
+        ResourceInfo {
+            sharing: SharingMode::Exclusive,
+            ..ResourceInfo::default()
+        },
+    );
+
+    let chains = collect(passes.clone(), &resources, |_| 1).unwrap();
+    let indirect = State::<Buffer>::indirect_read();
+    assert_eq!(chains.buffers[&id].link(1).state().stages, indirect.stages);
+
+    let mut semaphore_id = 0;
+    let schedule = sync_with_options(&chains, SyncOptions::default(), || {
+        let id = semaphore_id;
+        semaphore_id += 1;
+        (id, id)
+    }).unwrap();
+    let synched_chains = Chains {
+        schedule,
+        buffers: chains.buffers,
+        images: chains.images,
+        stencils: chains.stencils,
+        parts: chains.parts,
+        buffer_parts: chains.buffer_parts,
+    };
+    if let Err(errors) = validate(&synched_chains, &passes) {
+        panic!("Validation failed: {:#?}", errors);
+    }
+
+    let acquire = &synched_chains
+        .schedule
+        .submission(SubmissionId::new(QueueId::new(QueueFamilyId(1), 0), 0))
+        .expect("Indirect pass is not on graphics queue.")
+        .sync()
+        .acquire;
+    assert!(
+        acquire
+            .wait
+            .iter()
+            .any(|wait| wait.stage().contains(PipelineStage::DRAW_INDIRECT)),
+        "Semaphore isn't waited at DRAW_INDIRECT stage."
+    );
+    let barrier = &acquire.buffers[&id];
+    assert!(barrier.queues.is_some(), "Ownership isn't acquired.");
+    assert_eq!(barrier.states.end.access, indirect.access);
+    assert_eq!(barrier.states.end.stages, indirect.stages);
+
+    let mut rng = Isaac64Rng::new_unseeded();
+    sanity_check(&mut rng, &synched_chains, &passes, semaphore_id, false);
+}
+
+fn test_external_import_export() {
+    let id = Id::new(0);
+    let family = QueueFamilyId(0);
+    let passes = vec![
+        PassBuilder::new(PassId(0), family)
+            .storage_image_write(id, PipelineStage::COMPUTE_SHADER)
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(1), family)
+            .depends_on(PassId(0))
+            .sample_image(id, PipelineStage::FRAGMENT_SHADER)
+            .build()
+            .unwrap(),
+    ];
+    let external = |layout| External {
+        family: EXTERNAL_FAMILY,
+        state: State {
+            access: ImageAccess::empty(),
+            layout,
+            stages: PipelineStage::empty(),
+        },
+    };
+
+    for &export in &[true, false] {
+        let mut resources = Resources::new();
+        resources.add_image(
+            id,
+            ResourceInfo {
+                sharing: SharingMode::Exclusive,
+                import: Some(external(ImageLayout::General)),
+                export: if export {
+                    Some(external(ImageLayout::ShaderReadOnlyOptimal))
+                } else {
+                    None
+                },
+                ..ResourceInfo::default()
+            },
+        );
+
+        let chains = collect(passes.clone(), &resources, |_| 1).unwrap();
+        let mut semaphore_id = 0;
+        let schedule = sync_with_options(&chains, SyncOptions::default(), || {
+            let id = semaphore_id;
+            semaphore_id += 1;
+            (id, id)
+        }).unwrap();
+        let synched_chains = Chains {
+            schedule,
+            buffers: chains.buffers,
+            images: chains.images,
+            stencils: chains.stencils,
+            parts: chains.parts,
+            buffer_parts: chains.buffer_parts,
+        };
+        if let Err(errors) = validate(&synched_chains, &passes) {
+            panic!("Validation failed: {:#?}", errors);
+        }
+
+        let first = SubmissionId::new(QueueId::new(family, 0), 0);
+        let last = SubmissionId::new(QueueId::new(family, 0), 1);
+        let acquire = &synched_chains.schedule[first].sync().acquire.images[&id];
+        let queues = acquire.queues.clone().expect("Ownership isn't acquired.");
+        assert_eq!(queues.start.family(), EXTERNAL_FAMILY);
+        assert_eq!(acquire.states.start.layout, ImageLayout::General);
+
+        let release = synched_chains.schedule[last].sync().release.images.get(&id);
+        let transfers = synched_chains.external_transfers();
+        assert_eq!(transfers.image_imports.len(), 1);
+        assert_eq!(transfers.image_imports[0].sid, first);
+        if export {
+            let release = release.expect("Ownership isn't released.");
+            let queues = release.queues.clone().expect("Ownership isn't released.");
+            assert_eq!(queues.end.family(), EXTERNAL_FAMILY);
+            assert_eq!(release.states.end.layout, ImageLayout::ShaderReadOnlyOptimal);
+            assert_eq!(transfers.image_exports.len(), 1);
+            assert_eq!(transfers.image_exports[0].sid, last);
+        } else {
+            assert!(release.is_none(), "Ownership is released without export.");
+            assert!(transfers.image_exports.is_empty());
+        }
+        assert_eq!(synched_chains.stats().total.ownership_transfers, 1 + export as usize);
+
+        let mut rng = Isaac64Rng::new_unseeded();
+        sanity_check(&mut rng, &synched_chains, &passes, semaphore_id, false);
+    }
+}
+
+/// Pass that samples one mip level and writes the next one gets a chain for each level.
+/// Barriers into the pass transition the levels to different layouts.
+fn test_disjoint_ranges() {
+    let id = Id::new(0);
+    let sampled = State {
+        access: ImageAccess::SHADER_READ,
+        layout: ImageLayout::ShaderReadOnlyOptimal,
+        stages: PipelineStage::COMPUTE_SHADER,
+    };
+    let written = State {
+        access: ImageAccess::SHADER_WRITE,
+        layout: ImageLayout::General,
+        stages: PipelineStage::COMPUTE_SHADER,
+    };
+    // The only layout that can't be merged with others.
+    let presented = State {
+        access: ImageAccess::empty(),
+        layout: ImageLayout::Present,
+        stages: PipelineStage::BOTTOM_OF_PIPE,
+    };
+    let levels = |levels: Range<u8>| SubresourceRange {
+        aspects: Aspects::COLOR,
+        levels,
+        layers: 0..1,
+    };
+    let declare = |state, range| StateUsage {
+        state,
+        usage: Usage::none(),
+        range: Some(range),
+        discard: false,
+        ordering: OrderingHint::None,
+    };
+    let downsample = |pass, mip: u8| {
+        PassBuilder::new(PassId(pass), QueueFamilyId(0))
+            .raw_image(id, declare(sampled, levels(mip..mip + 1)))
+            .raw_image(id, declare(written, levels(mip + 1..mip + 2)))
+    };
+    let passes = vec![
+        PassBuilder::new(PassId(0), QueueFamilyId(0))
+            .clear_image(id)
+            .build()
+            .unwrap(),
+        downsample(1, 0).depends_on(PassId(0)).build().unwrap(),
+        downsample(2, 1).depends_on(PassId(1)).build().unwrap(),
+        PassBuilder::new(PassId(3), QueueFamilyId(0))
+            .depends_on(PassId(2))
+            .sample_image(id, PipelineStage::FRAGMENT_SHADER)
+            .build()
+            .unwrap(),
+    ];
+    assert_eq!(passes[1].image_ranges[&id].len(), 2, "Disjoint ranges are merged.");
+
+    match downsample(4, 0)
+        .raw_image(id, declare(presented, levels(0..2)))
+        .build()
+    {
+        Err(PassBuildError::IncompatibleImageRanges { ranges, .. }) => {
+            assert_eq!((ranges.0.levels, ranges.1.levels), (0..1, 0..2));
+        }
+        other => panic!("Overlapping ranges are not rejected: {:?}", other),
+    }
+
+    let mut resources = Resources::new();
+    resources.add_image(id, ResourceInfo::default());
+
+    let chains = collect(passes.clone(), &resources, |_| 1).unwrap();
+    assert!(!chains.images.contains_key(&id), "Image tracked in parts has whole chain.");
+    let parts: Vec<_> = chains.parts[&id]
+        .iter()
+        .map(|chain| chain.range().unwrap().levels.clone())
+        .collect();
+    assert_eq!(parts, vec![0..1, 1..2, 2..3]);
+
+    let mut semaphore_id = 0;
+    let schedule = sync_with_options(&chains, SyncOptions::default(), || {
+        let id = semaphore_id;
+        semaphore_id += 1;
+        (id, id)
+    }).unwrap();
+    let synched_chains = Chains {
+        schedule,
+        buffers: chains.buffers,
+        images: chains.images,
+        stencils: chains.stencils,
+        parts: chains.parts,
+        buffer_parts: chains.buffer_parts,
+    };
+    if let Err(errors) = validate(&synched_chains, &passes) {
+        panic!("Validation failed: {:#?}", errors);
+    }
+
+    let (_, submission) = synched_chains
+        .schedule
+        .iter()
+        .flat_map(|family| family.iter())
+        .flat_map(|queue| queue.iter())
+        .find(|&(_, submission)| submission.pass() == PassId(1))
+        .unwrap();
+    let layouts: Vec<_> = submission
+        .sync()
+        .acquire
+        .parts
+        .iter()
+        .map(|(_, barrier)| {
+            let range = barrier.range.as_ref().unwrap();
+            (range.levels.clone(), barrier.states.end.layout)
+        })
+        .collect();
+    assert_eq!(
+        layouts,
+        vec![
+            (0..1, ImageLayout::ShaderReadOnlyOptimal),
+            (1..2, ImageLayout::General),
+        ],
+        "Levels are not transitioned to their own layouts."
+    );
+
+    let mut rng = Isaac64Rng::new_unseeded();
+    sanity_check(&mut rng, &synched_chains, &passes, semaphore_id, false);
+
+    // Parts track only ranges of passes accessing disjoint ranges of the image.
+    let mut uncovered = passes.clone();
+    uncovered.push(
+        PassBuilder::new(PassId(4), QueueFamilyId(0))
+            .depends_on(PassId(3))
+            .raw_image(id, declare(sampled, levels(2..4)))
+            .build()
+            .unwrap(),
+    );
+    match collect(uncovered, &resources, |_| 1) {
+        Err(CollectError::UncoveredImageRange {
+            id: image,
+            pass,
+            range,
+        }) => {
+            assert_eq!((image, pass, range), (id, PassId(4), levels(2..4)));
+        }
+        other => panic!("Uncovered range is accepted: {:?}", other.map(|_| ())),
+    }
+
+    // Ranges tracked by the same part must have compatible states.
+    let mut merged = passes[1].clone();
+    merged.id = PassId(4);
+    merged.dependencies = vec![PassId(3)];
+    merged.image_ranges.insert(
+        id,
+        vec![
+            declare(sampled, levels(0..2)),
+            declare(written, levels(2..3)),
+        ],
+    );
+    let mut passes = passes;
+    passes.push(merged);
+    passes[1].image_ranges.get_mut(&id).unwrap()[1].state = presented;
+    match collect(passes, &resources, |_| 1) {
+        Err(CollectError::IncompatibleImageRanges { ranges, .. }) => {
+            assert_eq!((ranges.0.levels, ranges.1.levels), (0..1, 1..2));
+        }
+        other => panic!("Incompatible ranges of the part are not rejected: {:?}", other),
+    }
+}
+
+/// Passes accessing disjoint ranges of the buffer don't depend on each other.
+/// Ranges that violate alignment of their usage are rejected.
+fn test_disjoint_buffer_ranges() {
+    let id = Id::new(0);
+    let state = |access, stages| State {
+        access,
+        layout: BufferLayout,
+        stages,
+    };
+    let written = state(BufferAccess::TRANSFER_WRITE, PipelineStage::TRANSFER);
+    let declare = |state, range| StateUsage {
+        state,
+        usage: Usage::none(),
+        range: Some(range),
+        discard: false,
+        ordering: OrderingHint::None,
+    };
+    let passes = vec![
+        PassBuilder::new(PassId(0), QueueFamilyId(0))
+            .raw_buffer(id, declare(written, 0..64))
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(1), QueueFamilyId(0))
+            .depends_on(PassId(0))
+            .raw_buffer(
+                id,
+                declare(
+                    state(BufferAccess::VERTEX_BUFFER_READ, PipelineStage::VERTEX_INPUT),
+                    0..64,
+                ),
+            )
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(2), QueueFamilyId(0))
+            .depends_on(PassId(1))
+            .raw_buffer(id, declare(written, 64..128))
+            .build()
+            .unwrap(),
+    ];
+
+    let mut resources = Resources::new();
+    resources.add_buffer(
+        id,
+        ResourceInfo {
+            alignments: Some(BufferAlignments {
+                uniform: 256,
+                transfer: 4,
+                ..BufferAlignments::default()
+            }),
+            ..ResourceInfo::default()
+        },
+    );
+
+    let chains = collect(passes.clone(), &resources, |_| 1).unwrap();
+    assert!(!chains.buffers.contains_key(&id), "Buffer tracked in parts has whole chain.");
+    let parts: Vec<_> = chains.buffer_parts[&id]
+        .iter()
+        .map(|chain| chain.range().unwrap().clone())
+        .collect();
+    assert_eq!(parts, vec![0..64, 64..128]);
+
+    let mut semaphore_id = 0;
+    let schedule = sync_with_options(&chains, SyncOptions::default(), || {
+        let id = semaphore_id;
+        semaphore_id += 1;
+        (id, id)
+    }).unwrap();
+    let synched_chains = Chains {
+        schedule,
+        buffers: chains.buffers,
+        images: chains.images,
+        stencils: chains.stencils,
+        parts: chains.parts,
+        buffer_parts: chains.buffer_parts,
+    };
+    if let Err(errors) = validate(&synched_chains, &passes) {
+        panic!("Validation failed: {:#?}", errors);
+    }
+
+    let (_, submission) = synched_chains
+        .schedule
+        .iter()
+        .flat_map(|family| family.iter())
+        .flat_map(|queue| queue.iter())
+        .find(|&(_, submission)| submission.pass() == PassId(2))
+        .unwrap();
+    assert_eq!(submission.buffer_part(id, 1), Some(0), "Write joins link of the other range.");
+    assert!(
+        submission.sync().acquire.pipeline_barrier().is_none(),
+        "Write of disjoint range waits for the read."
+    );
+
+    let mut rng = Isaac64Rng::new_unseeded();
+    sanity_check(&mut rng, &synched_chains, &passes, semaphore_id, false);
+
+    let misaligned = |pass: PassBuilder| {
+        let mut passes = passes.clone();
+        passes.push(pass.build().unwrap());
+        match collect(passes, &resources, |_| 1) {
+            Err(CollectError::MisalignedBufferRange { alignment, .. }) => alignment,
+            other => panic!("Misaligned range is not rejected: {:?}", other),
+        }
+    };
+    let uniform = state(BufferAccess::CONSTANT_BUFFER_READ, PipelineStage::VERTEX_SHADER);
+    let pass = PassBuilder::new(PassId(3), QueueFamilyId(0));
+    assert_eq!(misaligned(pass.clone().raw_buffer(id, declare(uniform, 64..128))), 256);
+    assert_eq!(misaligned(pass.raw_buffer(id, declare(written, 64..126))), 4);
+}
+
+/// Independent passes are distributed among queues of the family by their costs.
+/// Passes on different queues don't access the same resources, so no semaphores are needed.
+fn test_queue_balancing() {
+    let compute = |pass: usize, cost: u32| {
+        PassBuilder::new(PassId(pass), QueueFamilyId(0))
+            .storage_buffer_write(Id::new(pass as u32), PipelineStage::COMPUTE_SHADER)
+            .cost(cost)
+            .build()
+            .unwrap()
+    };
+    for &(costs, expected) in &[([1, 1, 1, 1], [2, 2]), ([1, 3, 1, 1], [1, 3])] {
+        let passes: Vec<_> = costs
+            .iter()
+            .enumerate()
+            .map(|(pass, &cost)| compute(pass, cost))
+            .collect();
+        let chains = collect(passes.clone(), &declared(&passes), |_| 2).unwrap();
+        let queues: Vec<_> = chains.schedule.family(QueueFamilyId(0)).unwrap().iter().collect();
+        let mut lengths: Vec<_> = queues.iter().map(|queue| queue.len()).collect();
+        lengths.sort();
+        assert_eq!(lengths, expected, "Passes are not balanced by cost: {:?}", costs);
+        if costs[1] > 1 {
+            let queue = queues.iter().find(|queue| queue.len() == 1).unwrap();
+            let (_, submission) = queue.iter().next().unwrap();
+            assert_eq!(submission.pass(), PassId(1), "Costliest pass shares the queue.");
+        }
+
+        let mut semaphore_id = 0;
+        let schedule = sync_with_options(&chains, SyncOptions::default(), || {
+            let id = semaphore_id;
+            semaphore_id += 1;
+            (id, id)
+        }).unwrap();
+        assert_eq!(semaphore_id, 0, "Independent passes are synchronized with semaphores.");
+        let synched_chains = Chains {
+            schedule,
+            buffers: chains.buffers,
+            images: chains.images,
+            stencils: chains.stencils,
+            parts: chains.parts,
+            buffer_parts: chains.buffer_parts,
+        };
+        let mut rng = Isaac64Rng::new_unseeded();
+        sanity_check(&mut rng, &synched_chains, &passes, semaphore_id, false);
+    }
+}
+
+/// Debug overlay nobody reads is culled with passes it alone consumes.
+/// Passes with side effects and sparse binding of retained resources are kept.
+fn test_pass_culling() {
+    let (scene, overlay, swapchain) = (Id::new(0), Id::new(1), Id::new(2));
+    let (vertices, readback) = (Id::new(0), Id::new(1));
+    let family = QueueFamilyId(0);
+    let passes = vec![
+        PassBuilder::sparse_bind(PassId(0), family)
+            .bind_buffer(vertices, None)
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(1), family)
+            .transfer_buffer_dst(vertices)
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(2), family)
+            .vertex_buffer(vertices)
+            .color_attachment(scene)
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(3), family)
+            .sample_image(scene, PipelineStage::FRAGMENT_SHADER)
+            .color_attachment(overlay)
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(4), family)
+            .depends_on(PassId(3))
+            .sample_image(overlay, PipelineStage::COMPUTE_SHADER)
+            .storage_image_write(overlay, PipelineStage::COMPUTE_SHADER)
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(5), family)
+            .sample_image(scene, PipelineStage::FRAGMENT_SHADER)
+            .color_attachment(swapchain)
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(6), family)
+            .side_effects()
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(7), family)
+            .transfer_image_src(scene)
+            .transfer_buffer_dst(readback)
+            .build()
+            .unwrap(),
+    ];
+    assert!(passes[6].has_side_effects());
+
+    let culled = retain_roots(passes.clone(), &[swapchain], &[]);
+    assert_eq!(culled.culled, vec![PassId(3), PassId(4), PassId(7)]);
+    let retained: Vec<_> = culled.passes.iter().map(|pass| pass.id).collect();
+    assert_eq!(retained, vec![PassId(0), PassId(1), PassId(2), PassId(5), PassId(6)]);
+
+    let culled = retain_roots(passes.clone(), &[swapchain, overlay], &[readback]);
+    assert_eq!(culled.culled, vec![]);
+
+    let mut resources = Resources::new();
+    for &id in &[scene, overlay, swapchain] {
+        resources.add_image(id, ResourceInfo::default());
+    }
+    for &id in &[vertices, readback] {
+        resources.add_buffer(id, ResourceInfo::default());
+    }
+    let culled = retain_roots(passes, &[swapchain], &[]);
+    let chains = collect(culled.passes.clone(), &resources, |_| 1).unwrap();
+    assert!(!chains.images.contains_key(&overlay), "Culled pass is scheduled.");
+    let schedule = sync_with_options(&chains, SyncOptions::default(), || (0, 0)).unwrap();
+    let synched_chains = Chains {
+        schedule,
+        buffers: chains.buffers,
+        images: chains.images,
+        stencils: chains.stencils,
+        parts: chains.parts,
+        buffer_parts: chains.buffer_parts,
+    };
+    if let Err(errors) = validate(&synched_chains, &culled.passes) {
+        panic!("Validation failed: {:#?}", errors);
+    }
+}
+
+/// Skipping commands of conditional pass keeps resources in expected layouts
+/// as long as its guards are recorded.
+#[cfg(feature = "test-utils")]
+fn test_conditional_passes() {
+    let image = Id::new(0);
+    let passes = vec![
+        PassBuilder::new(PassId(0), QueueFamilyId(0))
+            .transfer_image_dst(image)
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(1), QueueFamilyId(0))
+            .depends_on(PassId(0))
+            .color_attachment(image)
+            .conditional()
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(2), QueueFamilyId(0))
+            .depends_on(PassId(1))
+            .sample_image(image, PipelineStage::FRAGMENT_SHADER)
+            .build()
+            .unwrap(),
+    ];
+    let resources = declared(&passes);
+    let chains = collect(passes, &resources, |_| 1).unwrap();
+    let chains = Chains {
+        schedule: sync_with_options(&chains, SyncOptions::default(), || (0, 0)).unwrap(),
+        buffers: chains.buffers,
+        images: chains.images,
+        stencils: chains.stencils,
+        parts: chains.parts,
+        buffer_parts: chains.buffer_parts,
+    };
+    let queue = chains.schedule.queue(QueueId::new(QueueFamilyId(0), 0)).unwrap();
+    for (_, submission) in queue.iter() {
+        let conditional = submission.pass() == PassId(1);
+        assert_eq!(submission.is_conditional(), conditional);
+        let sync = submission.sync();
+        assert_eq!(sync.acquire.must_record_even_if_skipped, conditional);
+        assert_eq!(sync.release.must_record_even_if_skipped, conditional);
+        if conditional {
+            // Transition into color attachment layout is performed even if commands are skipped.
+            let barrier = &sync.acquire.images[&image];
+            assert_eq!(barrier.states.end.layout, ImageLayout::ColorAttachmentOptimal);
+        }
+    }
+
+    for &skip in &[false, true] {
+        if let Err(hazard) = sim::verify_skipping(&chains, |_| skip) {
+            panic!("Hazard found with conditional pass skipped: {}, {}", skip, hazard);
+        }
+    }
+}
+
+/// Transitions from `Undefined` layout are hoisted to the beginning of the frame.
+/// Imported and present images keep their transitions. So do transitions from previous frame.
+fn test_hoist_initial_transitions() {
+    let (undefined, present, imported) = (Id::new(0), Id::new(1), Id::new(2));
+    let passes = vec![
+        PassBuilder::new(PassId(0), QueueFamilyId(0))
+            .transfer_buffer_dst(Id::new(0))
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(1), QueueFamilyId(0))
+            .depends_on(PassId(0))
+            .color_attachment(undefined)
+            .color_attachment(present)
+            .sample_image(imported, PipelineStage::FRAGMENT_SHADER)
+            .build()
+            .unwrap(),
+    ];
+    let mut resources = Resources::new();
+    resources.add_image(
+        undefined,
+        ResourceInfo {
+            initial_state: Some(State {
+                access: ImageAccess::empty(),
+                layout: ImageLayout::Undefined,
+                stages: PipelineStage::TOP_OF_PIPE,
+            }),
+            ..ResourceInfo::default()
+        },
+    );
+    resources.add_image(
+        present,
+        ResourceInfo {
+            present: true,
+            ..ResourceInfo::default()
+        },
+    );
+    resources.add_image(
+        imported,
+        ResourceInfo {
+            import: Some(External {
+                family: QueueFamilyId(1),
+                state: State {
+                    access: ImageAccess::empty(),
+                    layout: ImageLayout::General,
+                    stages: PipelineStage::BOTTOM_OF_PIPE,
+                },
+            }),
+            ..ResourceInfo::default()
+        },
+    );
+
+    register_declared(&mut resources, &passes);
+    let chains = collect(passes.clone(), &resources, |_| 1).unwrap();
+    let (first, second) = (
+        SubmissionId::new(QueueId::new(QueueFamilyId(0), 0), 0),
+        SubmissionId::new(QueueId::new(QueueFamilyId(0), 0), 1),
+    );
+    for &hoist in &[false, true] {
+        let options = SyncOptions {
+            hoist_initial_transitions: hoist,
+            ..SyncOptions::default()
+        };
+        let mut semaphore_id = 0;
+        let schedule = sync_with_options(&chains, options, || {
+            semaphore_id += 1;
+            (semaphore_id, semaphore_id)
+        }).unwrap();
+        let chains = Chains {
+            schedule,
+            buffers: chains.buffers.clone(),
+            images: chains.images.clone(),
+            stencils: chains.stencils.clone(),
+            parts: chains.parts.clone(),
+            buffer_parts: chains.buffer_parts.clone(),
+        };
+        if let Err(errors) = validate(&chains, &passes) {
+            panic!("Validation failed with hoisting {}: {:#?}", hoist, errors);
+        }
+
+        let frame_begin = &chains.schedule[first].sync().frame_begin;
+        let acquire = &chains.schedule[second].sync().acquire;
+        assert_eq!(frame_begin.images.contains_key(&undefined), hoist);
+        assert_eq!(acquire.images.contains_key(&undefined), !hoist);
+        assert_eq!(frame_begin.images.len(), hoist as usize, "Other images are hoisted.");
+        assert!(acquire.images.contains_key(&present) && acquire.images.contains_key(&imported));
+        assert!(chains.schedule[second].sync().frame_begin.pipeline_barrier().is_none());
+
+        let walker = ScheduleWalker::new(&chains.schedule);
+        let queue = walker.queues().next().unwrap();
+        match queue.frame_begin() {
+            Some(barrier) => {
+                assert!(hoist, "Frame begin barrier without hoisting.");
+                assert_eq!(barrier.images.len(), 1);
+                assert_eq!(barrier.images[0].0, undefined);
+            }
+            None => assert!(!hoist, "Hoisted barriers are not walked."),
+        }
+
+        // Later frames transition the image from its state in the previous frame instead.
+        let ring = FrameRing::new(&chains, 2);
+        let frame = ring.frame(1);
+        let sync = frame.sync(first, chains.schedule[first].sync());
+        assert!(sync.frame_begin.images.is_empty(), "Hoisted barrier in later frame.");
+        let sync = frame.sync(second, chains.schedule[second].sync());
+        let barrier = &sync.acquire.images[&undefined];
+        assert_eq!(barrier.states.start.layout, ImageLayout::ColorAttachmentOptimal);
+    }
+}
+
+/// Distinct uses of the same image merged into `General` layout are reported.
+/// Raw declarations never are.
+fn test_image_uses() {
+    let (attachment, sampled, raw) = (Id::new(0), Id::new(1), Id::new(2));
+    let clear = AttachmentOps::new(AttachmentLoadOp::Clear, AttachmentStoreOp::Store);
+    let passes = vec![
+        PassBuilder::new(PassId(0), QueueFamilyId(0))
+            .image_use(attachment, ImageUse::Attachment(clear))
+            .image_use(sampled, ImageUse::Sampled(PipelineStage::VERTEX_SHADER))
+            .image_use(sampled, ImageUse::Sampled(PipelineStage::FRAGMENT_SHADER))
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(1), QueueFamilyId(0))
+            .depends_on(PassId(0))
+            .image_use(attachment, ImageUse::Attachment(clear))
+            .image_use(attachment, ImageUse::Sampled(PipelineStage::FRAGMENT_SHADER))
+            .image_use(sampled, ImageUse::Sampled(PipelineStage::FRAGMENT_SHADER))
+            .color_attachment(raw)
+            .sample_image(raw, PipelineStage::FRAGMENT_SHADER)
+            .build()
+            .unwrap(),
+    ];
+
+    let declared = &passes[0].images[&attachment];
+    assert!(declared.discard, "Cleared attachment keeps its content.");
+    assert_eq!(declared.state.layout, ImageLayout::ColorAttachmentOptimal);
+    assert_eq!(declared.usage, ImageUsage::COLOR_ATTACHMENT);
+    let merged = &passes[1].images[&attachment];
+    assert_eq!(merged.state.layout, ImageLayout::General);
+    assert!(!merged.discard, "Sampled attachment is discarded.");
+
+    // Content is read back to keep chain warnings out.
+    let mut resources = Resources::new();
+    for &id in &[attachment, sampled, raw] {
+        let info = ResourceInfo {
+            host_read_after: true,
+            ..ResourceInfo::default()
+        };
+        resources.add_image(id, info);
+    }
+    let (_, warnings) = collect_with_warnings(passes, &resources, |_| 1).unwrap();
+    assert_eq!(
+        warnings,
+        vec![ChainWarning::GeneralLayout {
+            pass: PassId(1),
+            id: attachment,
+            uses: (
+                ImageUse::Attachment(clear),
+                ImageUse::Sampled(PipelineStage::FRAGMENT_SHADER),
+            ),
+        }]
+    );
+}
+
+/// Attachment info loads content that exists before the submission
+/// and stores content observed after it, including before and after the schedule.
+fn test_attachment_info() {
+    let (blended, cleared, read_back, per_frame) = (Id::new(0), Id::new(1), Id::new(2), Id::new(3));
+    let passes = vec![
+        PassBuilder::new(PassId(0), QueueFamilyId(0))
+            .render_pass()
+            .color_attachment(blended)
+            .clear_color_attachment(cleared)
+            .clear_color_attachment(read_back)
+            .clear_color_attachment(per_frame)
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(1), QueueFamilyId(0))
+            .depends_on(PassId(0))
+            .sample_image(cleared, PipelineStage::FRAGMENT_SHADER)
+            .build()
+            .unwrap(),
+    ];
+    let mut resources = Resources::new();
+    resources.add_image(
+        read_back,
+        ResourceInfo {
+            host_read_after: true,
+            ..ResourceInfo::default()
+        },
+    );
+    resources.add_image(
+        per_frame,
+        ResourceInfo {
+            per_frame: true,
+            ..ResourceInfo::default()
+        },
+    );
+    register_declared(&mut resources, &passes);
+    let chains = collect(passes, &resources, |_| 1).unwrap();
+    let sid = SubmissionId::new(QueueId::new(QueueFamilyId(0), 0), 0);
+    let info = |id| chains.attachment(sid, id).unwrap();
+
+    // Blending into the first use keeps content the image has before the schedule.
+    let blended = info(blended);
+    assert!(blended.load && !blended.store);
+    assert_eq!(blended.initial_layout, ImageLayout::ColorAttachmentOptimal);
+    let cleared = info(cleared);
+    assert!(!cleared.load && cleared.store);
+    assert_eq!(cleared.initial_layout, ImageLayout::Undefined);
+    for &id in &[read_back, per_frame] {
+        let info = info(id);
+        assert!(!info.load && info.store, "Content of {:?} is lost after the schedule.", id);
+        assert_eq!(info.final_layout, ImageLayout::ColorAttachmentOptimal);
+    }
+}
+
+/// Swapchain image drawn by its only pass is stored for presentation.
+/// Content it has when acquired is undefined, so blending into it loads nothing.
+fn test_present_attachment_info() {
+    let (drawn, blended) = (Id::new(0), Id::new(1));
+    let passes = vec![
+        PassBuilder::new(PassId(0), QueueFamilyId(0))
+            .render_pass()
+            .clear_color_attachment(drawn)
+            .color_attachment(blended)
+            .build()
+            .unwrap(),
+    ];
+    let mut resources = Resources::new();
+    for &id in &[drawn, blended] {
+        resources.add_image(
+            id,
+            ResourceInfo {
+                present: true,
+                ..ResourceInfo::default()
+            },
+        );
+    }
+    let chains = collect(passes, &resources, |_| 1).unwrap();
+    let sid = SubmissionId::new(QueueId::new(QueueFamilyId(0), 0), 0);
+    for &id in &[drawn, blended] {
+        let info = chains.attachment(sid, id).unwrap();
+        assert!(info.store, "Presented content of {:?} is not stored.", id);
+        assert!(!info.load, "Undefined content of {:?} is loaded.", id);
+        assert_eq!(info.initial_layout, ImageLayout::Undefined);
+        assert_eq!(info.final_layout, ImageLayout::ColorAttachmentOptimal);
+        assert_eq!(chains.images[&id].final_state().state.layout, ImageLayout::Present);
+    }
+}
+
+/// Image in known initial state keeps its content for the first write into it,
+/// unless the state has `Undefined` layout.
+fn test_initial_state_attachment_info() {
+    let (kept, undefined) = (Id::new(0), Id::new(1));
+    let drawn = State {
+        access: ImageAccess::COLOR_ATTACHMENT_WRITE,
+        layout: ImageLayout::ColorAttachmentOptimal,
+        stages: PipelineStage::COLOR_ATTACHMENT_OUTPUT,
+    };
+    let passes = vec![
+        PassBuilder::new(PassId(0), QueueFamilyId(0))
+            .render_pass()
+            .raw_image_state(kept, drawn)
+            .raw_image_state(undefined, drawn)
+            .build()
+            .unwrap(),
+    ];
+    let mut resources = Resources::new();
+    let initial = |layout| ResourceInfo {
+        initial_state: Some(State {
+            layout,
+            ..State::sampled(PipelineStage::FRAGMENT_SHADER)
+        }),
+        ..ResourceInfo::default()
+    };
+    resources.add_image(kept, initial(ImageLayout::ShaderReadOnlyOptimal));
+    resources.add_image(undefined, initial(ImageLayout::Undefined));
+    let chains = collect(passes, &resources, |_| 1).unwrap();
+    let sid = SubmissionId::new(QueueId::new(QueueFamilyId(0), 0), 0);
+
+    let info = chains.attachment(sid, kept).unwrap();
+    assert!(info.load, "Content in initial state is discarded.");
+    assert_eq!(info.initial_layout, ImageLayout::ColorAttachmentOptimal);
+    assert_eq!(
+        chains.images[&kept].initial_state().state.layout,
+        ImageLayout::ShaderReadOnlyOptimal
+    );
+    let info = chains.attachment(sid, undefined).unwrap();
+    assert!(!info.load);
+    assert_eq!(info.initial_layout, ImageLayout::Undefined);
+}
+
+/// Imported image is loaded as written by external family before the schedule
+/// and exported image is stored for external family after the schedule.
+fn test_external_attachment_info() {
+    let (imported, exported) = (Id::new(0), Id::new(1));
+    let drawn = State {
+        access: ImageAccess::COLOR_ATTACHMENT_WRITE,
+        layout: ImageLayout::ColorAttachmentOptimal,
+        stages: PipelineStage::COLOR_ATTACHMENT_OUTPUT,
+    };
+    let passes = vec![
+        PassBuilder::new(PassId(0), QueueFamilyId(0))
+            .render_pass()
+            .raw_image_state(imported, drawn)
+            .raw_image_state(exported, drawn)
+            .build()
+            .unwrap(),
+    ];
+    let external = |layout| External {
+        family: QueueFamilyId(1),
+        state: State {
+            access: ImageAccess::empty(),
+            layout,
+            stages: PipelineStage::BOTTOM_OF_PIPE,
+        },
+    };
+    let mut resources = Resources::new();
+    resources.add_image(
+        imported,
+        ResourceInfo {
+            import: Some(external(ImageLayout::General)),
+            ..ResourceInfo::default()
+        },
+    );
+    resources.add_image(
+        exported,
+        ResourceInfo {
+            export: Some(external(ImageLayout::ShaderReadOnlyOptimal)),
+            ..ResourceInfo::default()
+        },
+    );
+    let chains = collect(passes, &resources, |_| 1).unwrap();
+    let sid = SubmissionId::new(QueueId::new(QueueFamilyId(0), 0), 0);
+
+    let info = chains.attachment(sid, imported).unwrap();
+    assert!(info.load, "Imported content is discarded.");
+    assert!(!info.store);
+    assert_eq!(info.initial_layout, ImageLayout::ColorAttachmentOptimal);
+    let info = chains.attachment(sid, exported).unwrap();
+    assert!(!info.load);
+    assert!(info.store, "Exported content is discarded.");
+    assert_eq!(info.initial_layout, ImageLayout::Undefined);
+}
+
+/// Resolve target is reported as such by attachment info of the resolving pass.
+/// Multisampled image may be aliased right after the resolve.
+fn test_resolve() {
+    let (msaa, resolved, other) = (Id::new(0), Id::new(1), Id::new(2));
+    let passes = vec![
+        PassBuilder::new(PassId(0), QueueFamilyId(0))
+            .clear_color_attachment(msaa)
+            .resolve(msaa, resolved)
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(1), QueueFamilyId(0))
+            .depends_on(PassId(0))
+            .sample_image(resolved, PipelineStage::FRAGMENT_SHADER)
+            .color_attachment(other)
+            .build()
+            .unwrap(),
+    ];
+    assert!(passes[0].images[&resolved].discard);
+    assert_eq!(
+        passes[0].images[&msaa].state.layout,
+        ImageLayout::ColorAttachmentOptimal
+    );
+
+    let mut resources = Resources::new();
+    for &id in &[msaa, resolved, other] {
+        resources.add_image(id, ResourceInfo::default());
+    }
+    let chains = collect(passes.clone(), &resources, |_| 1).unwrap();
+    let schedule = sync_with_options(&chains, SyncOptions::default(), || (0, 0)).unwrap();
+    let synched_chains = Chains {
+        schedule,
+        buffers: chains.buffers,
+        images: chains.images,
+        stencils: chains.stencils,
+        parts: chains.parts,
+        buffer_parts: chains.buffer_parts,
+    };
+    if let Err(errors) = validate(&synched_chains, &passes) {
+        panic!("Validation failed: {:#?}", errors);
+    }
+
+    let sid = SubmissionId::new(QueueId::new(QueueFamilyId(0), 0), 0);
+    let infos = synched_chains.attachments(sid);
+    assert_eq!(infos.len(), 2);
+    for (id, info) in infos {
+        let expected = if id == resolved { Some(msaa) } else { None };
+        assert_eq!(info.resolve_source, expected);
+        assert_eq!(synched_chains.attachment(sid, id), Some(info));
+    }
+
+    let lifetimes = Lifetimes::new(&synched_chains);
+    assert!(lifetimes.aliasable(msaa, other), "Resolved image isn't free after resolve.");
+    assert!(!lifetimes.aliasable(resolved, other));
+}
+
+/// Images of ping-pong blur may alias when their lifetimes don't overlap.
+/// External image is never aliased.
+fn test_ping_pong_aliasing() {
+    let (first, second, third) = (Id::new(0), Id::new(1), Id::new(2));
+    let blur = |pass, src, dst| {
+        PassBuilder::new(PassId(pass), QueueFamilyId(0))
+            .depends_on(PassId(pass - 1))
+            .sample_image(src, PipelineStage::FRAGMENT_SHADER)
+            .color_attachment(dst)
+            .build()
+            .unwrap()
+    };
+    let passes = vec![
+        PassBuilder::new(PassId(0), QueueFamilyId(0))
+            .color_attachment(first)
+            .build()
+            .unwrap(),
+        blur(1, first, second),
+        blur(2, second, third),
+        PassBuilder::new(PassId(3), QueueFamilyId(0))
+            .depends_on(PassId(2))
+            .sample_image(third, PipelineStage::FRAGMENT_SHADER)
+            .build()
+            .unwrap(),
+    ];
+    let analyze = |external| {
+        let mut resources = Resources::new();
+        resources.add_image(first, ResourceInfo::default());
+        resources.add_image(second, ResourceInfo::default());
+        resources.add_image(
+            third,
+            ResourceInfo {
+                external,
+                ..ResourceInfo::default()
+            },
+        );
+        let (synched_chains, _) = sync_default(&passes, &resources);
+        Lifetimes::new(&synched_chains)
+    };
+
+    let qid = QueueId::new(QueueFamilyId(0), 0);
+    let lifetimes = analyze(false);
+    assert_eq!(
+        lifetimes.image(first),
+        Some(&Lifetime::Bounded {
+            order: 0..2,
+            queues: vec![(qid, 0..2)],
+        })
+    );
+    assert_eq!(
+        lifetimes.image(third),
+        Some(&Lifetime::Bounded {
+            order: 2..4,
+            queues: vec![(qid, 2..4)],
+        })
+    );
+    assert!(lifetimes.aliasable(first, third) && lifetimes.aliasable(third, first));
+    assert!(!lifetimes.aliasable(first, second), "Blur source aliases its target.");
+    assert!(!lifetimes.aliasable(second, third), "Blur source aliases its target.");
+
+    let lifetimes = analyze(true);
+    assert_eq!(lifetimes.image(third), Some(&Lifetime::Unbounded));
+    assert!(!lifetimes.aliasable(first, third), "External image is aliased.");
+}
+
+/// Barriers adjacent to render passes are expressed as external subpass dependencies.
+/// Dependency on transfer is not by region and must be allowed explicitly.
+fn test_external_dependency() {
+    let image = Id::new(0);
+    let passes = vec![
+        PassBuilder::new(PassId(0), QueueFamilyId(0))
+            .clear_image(image)
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(1), QueueFamilyId(0))
+            .render_pass()
+            .depends_on(PassId(0))
+            .color_attachment(image)
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(2), QueueFamilyId(0))
+            .render_pass()
+            .depends_on(PassId(1))
+            .input_attachment(image)
+            .build()
+            .unwrap(),
+    ];
+    let mut resources = Resources::new();
+    resources.add_image(image, ResourceInfo::default());
+    let chains = collect(passes.clone(), &resources, |_| 1).unwrap();
+    let schedule = sync_with_options(&chains, SyncOptions::default(), || (0, 0)).unwrap();
+    let walker = ScheduleWalker::new(&schedule);
+    let info = |pass| {
+        walker
+            .queues()
+            .flat_map(|queue| queue.iter())
+            .find(|info| info.pass() == pass)
+            .unwrap()
+    };
+
+    assert_eq!(info(PassId(0)).as_external_dependency(true), None);
+    assert_eq!(info(PassId(1)).as_external_dependency(false), None);
+    let after_transfer = info(PassId(1)).as_external_dependency(true).unwrap();
+    assert_eq!(after_transfer.end, None);
+    assert_eq!(
+        after_transfer.begin,
+        Some(SubpassDependency {
+            stages: PipelineStage::TRANSFER..PipelineStage::COLOR_ATTACHMENT_OUTPUT,
+            accesses: ImageAccess::TRANSFER_WRITE
+                ..ImageAccess::COLOR_ATTACHMENT_READ | ImageAccess::COLOR_ATTACHMENT_WRITE,
+            by_region: false,
+            layouts: vec![(
+                image,
+                ImageLayout::TransferDstOptimal..ImageLayout::ColorAttachmentOptimal,
+            )],
+        })
+    );
+    let by_region = info(PassId(2)).as_external_dependency(false).unwrap();
+    let begin = by_region.begin.unwrap();
+    assert!(begin.by_region);
+    assert_eq!(
+        begin.stages,
+        PipelineStage::COLOR_ATTACHMENT_OUTPUT..PipelineStage::FRAGMENT_SHADER
+    );
+    assert_eq!(
+        begin.layouts,
+        vec![(
+            image,
+            ImageLayout::ColorAttachmentOptimal..ImageLayout::ShaderReadOnlyOptimal,
+        )]
+    );
+}
+
+/// Devices of the group track states of the same image independently.
+/// Cross-device wait reports states on both devices instead of a barrier.
+fn test_device_group() {
+    let (frame, history) = (Id::new(0), Id::new(1));
+    let passes = vec![
+        DevicePass::new(
+            DeviceIndex(0),
+            PassBuilder::new(PassId(0), QueueFamilyId(0))
+                .clear_color_attachment(frame)
+                .build()
+                .unwrap(),
+        ),
+        DevicePass::new(
+            DeviceIndex(1),
+            PassBuilder::new(PassId(1), QueueFamilyId(0))
+                .sample_image(frame, PipelineStage::FRAGMENT_SHADER)
+                .color_attachment(history)
+                .build()
+                .unwrap(),
+        ).waits_on_device(DeviceIndex(0), frame),
+    ];
+    let mut resources = Resources::new();
+    for &id in &[frame, history] {
+        resources.add_image(id, ResourceInfo::default());
+    }
+    let mut semaphore_id = 0;
+    let mut new_semaphore = || {
+        let id = semaphore_id;
+        semaphore_id += 1;
+        (id, id)
+    };
+    let group = build_group(passes.clone(), &resources, |_, _| 1, &mut new_semaphore).unwrap();
+    assert_eq!(group.devices().len(), 2);
+    for &(device, ref chains) in group.devices() {
+        let pass = &passes[device.0].pass;
+        if let Err(errors) = validate(chains, ::std::slice::from_ref(pass)) {
+            panic!("Validation of device {:?} failed: {:#?}", device, errors);
+        }
+    }
+    let consumer = group.device(DeviceIndex(1)).unwrap();
+    assert_eq!(
+        consumer.images[&frame].links()[0].state().layout,
+        ImageLayout::ShaderReadOnlyOptimal,
+        "Image state is shared between devices."
+    );
+
+    assert!(group.buffer_waits().is_empty());
+    let waits = group.image_waits();
+    assert_eq!(waits.len(), 1);
+    let sid = SubmissionId::new(QueueId::new(QueueFamilyId(0), 0), 0);
+    assert_eq!(waits[0].id, frame);
+    assert_eq!(waits[0].signal, (DeviceIndex(0), sid));
+    assert_eq!(waits[0].wait, (DeviceIndex(1), sid));
+    assert_eq!(waits[0].states.start.layout, ImageLayout::ColorAttachmentOptimal);
+    assert_eq!(waits[0].states.end.layout, ImageLayout::ShaderReadOnlyOptimal);
+    assert_eq!(waits[0].states.end.stages, PipelineStage::FRAGMENT_SHADER);
+
+    let mut unproduced = passes.clone();
+    unproduced[1] = unproduced[1].clone().waits_on_device(DeviceIndex(0), history);
+    match build_group(unproduced, &resources, |_, _| 1, &mut new_semaphore) {
+        Err(DeviceError::Untracked {
+            pass,
+            device,
+            resource,
+        }) => {
+            assert_eq!((pass, device), (PassId(1), DeviceIndex(0)));
+            assert_eq!(resource, DeviceResource::Image(history));
+        }
+        other => panic!("Unproduced image is waited: {:?}", other.map(|_| ())),
+    }
+}
+
+/// Names and tags of passes reach the schedule, walker, DOT export and validation errors.
+fn test_pass_meta() {
+    let image = Id::new(0);
+    let passes = vec![
+        PassBuilder::new(PassId(0), QueueFamilyId(0))
+            .name("upload")
+            .transfer_image_dst(image)
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(1), QueueFamilyId(0))
+            .name("ssao-blur")
+            .tag(7)
+            .depends_on(PassId(0))
+            .sample_image(image, PipelineStage::FRAGMENT_SHADER)
+            .build()
+            .unwrap(),
+    ];
+    let mut resources = Resources::new();
+    resources.add_image(image, ResourceInfo::default());
+    let chains = build(passes.clone(), &resources, |_| 1, || (0, 0)).unwrap();
+
+    let blur = PassMeta {
+        name: Some("ssao-blur"),
+        tag: 7,
+    };
+    assert_eq!(chains.schedule.find_pass("ssao-blur"), Some(PassId(1)));
+    assert_eq!(chains.schedule.find_pass("ssao"), None);
+    assert_eq!(chains.schedule.pass_meta(PassId(1)), Some(blur));
+    assert_eq!(chains.schedule.pass_meta(PassId(2)), None);
+    let walker = ScheduleWalker::new(&chains.schedule);
+    let metas: Vec<_> = walker
+        .queues()
+        .flat_map(|queue| queue.iter())
+        .map(|info| info.meta())
+        .collect();
+    assert_eq!(metas, vec![passes[0].meta, blur]);
+
+    let mut dot = Vec::new();
+    write_dot(&chains, &mut dot).unwrap();
+    let dot = String::from_utf8(dot).unwrap();
+    assert!(dot.contains("Pass 1\\nssao-blur\\nTag 7\\nQueue 0:0"), "{}", dot);
+
+    let mut storage = passes.clone();
+    storage[1] = Pass {
+        images: PassBuilder::new(PassId(1), QueueFamilyId(0))
+            .storage_image_write(image, PipelineStage::FRAGMENT_SHADER)
+            .build()
+            .unwrap()
+            .images,
+        ..storage[1].clone()
+    };
+    let errors = validate(&chains, &storage).unwrap_err();
+    match errors[0] {
+        ValidationError::Image { pass, meta, .. } => assert_eq!((pass, meta), (PassId(1), blur)),
+        ref other => panic!("Unexpected error: {}", other),
+    }
+    assert!(
+        errors[0].to_string().contains("pass PassId(1) \"ssao-blur\" (tag 7)"),
+        "{}",
+        errors[0]
+    );
+}
+
+/// DOT export of two passes on different queues draws a cluster per queue
+/// and dashed semaphore edge labelled with the buffer and its states.
+/// States in labels are formatted with `Debug` like the export does.
+fn test_dot_output() {
+    let buffer = Id::new(0);
+    let passes = vec![
+        PassBuilder::new(PassId(0), QueueFamilyId(1))
+            .transfer_buffer_dst(buffer)
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(1), QueueFamilyId(0))
+            .vertex_buffer(buffer)
+            .build()
+            .unwrap(),
+    ];
+    let mut resources = Resources::new();
+    resources.add_buffer(buffer, ResourceInfo::default());
+    let chains = collect(passes, &resources, |_| 1).unwrap();
+
+    let mut dot = Vec::new();
+    write_dot(&chains, &mut dot).unwrap();
+    let state = |state: State<Buffer>| {
+        format!("{:?}\\n{:?}\\n{:?}", state.access, state.layout, state.stages)
+    };
+    let expected = format!(
+        r#"digraph chains {{
+    node [shape=box];
+    subgraph cluster_0_0 {{
+        label="Queue 0:0";
+        s_0_0_0 [label="Pass 1\nQueue 0:0"];
+    }}
+    subgraph cluster_1_0 {{
+        label="Queue 1:0";
+        s_1_0_0 [label="Pass 0\nQueue 1:0"];
+    }}
+    s_1_0_0 -> s_0_0_0 [label="Buffer 0\n{}\n-> {}\nsemaphore", style=dashed, color=blue];
+}}
+"#,
+        state(State::<Buffer>::transfer_dst()),
+        state(State::<Buffer>::vertex())
+    );
+    assert_eq!(String::from_utf8(dot).unwrap(), expected);
+}
+
+/// Ids far beyond the number of entries are stored sparsely
+/// and move into dense storage once it grows over them.
+fn test_id_map() {
+    let mut map: IdMap<Buffer, u32> = IdMap::with_capacity(4);
+    for &index in [3, 1_000_000, 0, 70].iter() {
+        assert_eq!(map.insert(Id::new(index), index), None);
+    }
+    assert_eq!(map.insert(Id::new(3), 4), Some(3));
+    assert_eq!(map.len(), 4);
+    assert_eq!(map[&Id::new(1_000_000)], 1_000_000);
+    assert_eq!(map.get(&Id::new(70)), Some(&70));
+    assert_eq!(map.get(&Id::new(2)), None);
+
+    for index in 4..64 {
+        map.insert(Id::new(index), index);
+    }
+    assert_eq!(map.remove(&Id::new(70)), Some(70));
+    assert_eq!(map.remove(&Id::new(70)), None);
+    *map.get_mut(&Id::new(0)).unwrap() = 5;
+    let keys: Vec<_> = map.keys().map(|id| id.index()).collect();
+    let expected: Vec<_> = [0, 3]
+        .iter()
+        .cloned()
+        .chain(4..64)
+        .chain(Some(1_000_000))
+        .collect();
+    assert_eq!(keys, expected);
+    assert_eq!(map.values().sum::<u32>(), 5 + 4 + (4..64).sum::<u32>() + 1_000_000);
+    assert_eq!(map.iter().len(), map.len());
+
+    let copy: IdMap<Buffer, u32> = map.clone().into_iter().collect();
+    assert_eq!(copy, map);
+}
+
+/// Fences are signaled at the end of the requested queues
+/// and after the latest access of the resource read by host.
+fn test_fence_strategy() {
+    let (readback, other, third) = (Id::new(0), Id::new(1), Id::new(2));
+    let passes = vec![
+        PassBuilder::new(PassId(0), QueueFamilyId(0))
+            .storage_buffer_write(readback, PipelineStage::COMPUTE_SHADER)
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(1), QueueFamilyId(0))
+            .depends_on(PassId(0))
+            .storage_buffer_write(other, PipelineStage::COMPUTE_SHADER)
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(2), QueueFamilyId(1))
+            .storage_buffer_write(third, PipelineStage::COMPUTE_SHADER)
+            .build()
+            .unwrap(),
+    ];
+    let mut resources = Resources::new();
+    resources.add_buffer(
+        readback,
+        ResourceInfo {
+            host_read_after: true,
+            ..ResourceInfo::default()
+        },
+    );
+    resources.add_buffer(other, ResourceInfo::default());
+    resources.add_buffer(third, ResourceInfo::default());
+
+    let graphics = QueueId::new(QueueFamilyId(0), 0);
+    let compute = QueueId::new(QueueFamilyId(1), 0);
+    let cases = vec![
+        (FenceStrategy::AllQueues, vec![Some(0), Some(1), Some(2)]),
+        (FenceStrategy::None, vec![Some(0), None, None]),
+        (FenceStrategy::Explicit(vec![compute]), vec![Some(0), None, Some(1)]),
+    ];
+    for (strategy, expected) in cases {
+        let chains = collect(passes.clone(), &resources, |_| 1).unwrap();
+        let options = SyncOptions::default().fences(strategy.clone());
+        let mut semaphore_id = 0;
+        let schedule = sync_with_options(&chains, options, || {
+            semaphore_id += 1;
+            (semaphore_id, semaphore_id)
+        }).unwrap();
+        let sids = [
+            SubmissionId::new(graphics, 0),
+            SubmissionId::new(graphics, 1),
+            SubmissionId::new(compute, 0),
+        ];
+        let fences: Vec<_> = sids.iter().map(|&sid| schedule[sid].sync().fence).collect();
+        assert_eq!(fences, expected, "{:?}", strategy);
+
+        let count = expected.iter().filter(|fence| fence.is_some()).count();
+        assert_eq!(fence_count(&schedule), count);
+        let chains = Chains {
+            schedule,
+            buffers: chains.buffers,
+            images: chains.images,
+            stencils: chains.stencils,
+            parts: chains.parts,
+            buffer_parts: chains.buffer_parts,
+        };
+        validate(&chains, &passes).unwrap();
+        let ring = FrameRing::new(&chains, 2);
+        assert_eq!(ring.fence_count(), count * 2);
+        let frame = ring.frame(1);
+        assert_eq!(frame.fences(), count..count * 2);
+        let sync = frame.sync(sids[0], chains.schedule[sids[0]].sync());
+        assert_eq!(sync.fence, Some(count));
+    }
+}
+
+/// Reads of the image in different layouts share a link.
+/// Layout of the link and the barrier into it depend on the policy.
+fn test_layout_policy() {
+    let image = Id::new(0);
+    let passes = vec![
+        PassBuilder::new(PassId(0), QueueFamilyId(0))
+            .transfer_image_dst(image)
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(1), QueueFamilyId(0))
+            .depends_on(PassId(0))
+            .sample_image(image, PipelineStage::FRAGMENT_SHADER)
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(2), QueueFamilyId(0))
+            .depends_on(PassId(0))
+            .transfer_image_src(image)
+            .build()
+            .unwrap(),
+    ];
+    let build = |policy| {
+        let mut resources = Resources::new();
+        resources.add_image(image, ResourceInfo::default());
+        resources.layout_policy = policy;
+        let chains = collect(passes.clone(), &resources, |_| 1)?;
+        let schedule = sync_with_options(&chains, SyncOptions::default(), || (0, 0)).unwrap();
+        Ok(Chains {
+            schedule,
+            buffers: chains.buffers,
+            images: chains.images,
+            stencils: chains.stencils,
+            parts: chains.parts,
+            buffer_parts: chains.buffer_parts,
+        })
+    };
+    let read_layout = |chains: &Chains<SyncData<usize, usize>>| {
+        let links = chains.images[&image].links();
+        assert_eq!(links.len(), 2, "Reads don't share a link.");
+        let barriers: Vec<_> = chains
+            .schedule
+            .iter()
+            .flat_map(|family| family.iter())
+            .flat_map(|queue| queue.iter())
+            .filter_map(|(_, submission)| submission.sync().acquire.images.get(&image))
+            .collect();
+        assert_eq!(barriers.len(), 1);
+        assert_eq!(barriers[0].states.end.layout, links[1].state().layout);
+        links[1].state().layout
+    };
+
+    let general: Result<_, CollectError> = build(LayoutPolicy::PreferGeneral);
+    assert_eq!(read_layout(&general.unwrap()), ImageLayout::General);
+    let read_only = build(LayoutPolicy::PreferReadOnly).unwrap();
+    assert_eq!(read_layout(&read_only), ImageLayout::ShaderReadOnlyOptimal);
+    if let Err(errors) = validate(&read_only, &passes) {
+        panic!("Validation failed: {:#?}", errors);
+    }
+    match build(LayoutPolicy::Strict) {
+        Err(CollectError::IncompatibleImageStates { id, error, .. }) => {
+            assert_eq!(id, image);
+            let layouts = [error.left.layout, error.right.layout];
+            assert!(
+                layouts.contains(&ImageLayout::ShaderReadOnlyOptimal)
+                    && layouts.contains(&ImageLayout::TransferSrcOptimal)
+            );
+        }
+        other => panic!("Strict policy merged layouts: {:?}", other.map(|_| ())),
+    }
+}
+
+/// Inserted pass doesn't change synchronization of passes after it.
+/// Semaphores are compared by passes they connect.
+fn test_sync_diff() {
+    let (buffer, other) = (Id::new(0), Id::new(1));
+    let passes = |insert: bool, stages| {
+        let mut passes = vec![
+            PassBuilder::new(PassId(0), QueueFamilyId(0))
+                .transfer_buffer_dst(buffer)
+                .build()
+                .unwrap(),
+        ];
+        if insert {
+            passes.push(
+                PassBuilder::new(PassId(2), QueueFamilyId(0))
+                    .transfer_buffer_dst(other)
+                    .build()
+                    .unwrap(),
+            );
+        }
+        passes.push(
+            PassBuilder::new(PassId(1), QueueFamilyId(0))
+                .depends_on(PassId(0))
+                .uniform_buffer(buffer, stages)
+                .build()
+                .unwrap(),
+        );
+        passes.push(
+            PassBuilder::new(PassId(3), QueueFamilyId(1))
+                .depends_on(PassId(1))
+                .uniform_buffer(buffer, stages)
+                .build()
+                .unwrap(),
+        );
+        passes
+    };
+    let mut resources = Resources::new();
+    resources.add_buffer(buffer, ResourceInfo::default());
+    resources.add_buffer(other, ResourceInfo::default());
+
+    let schedule = |insert, stages, first_semaphore| {
+        let chains = collect(passes(insert, stages), &resources, |_| 1).unwrap();
+        let mut semaphore_id = first_semaphore;
+        sync_with_options(&chains, SyncOptions::default(), || {
+            semaphore_id += 1;
+            (semaphore_id, semaphore_id)
+        }).unwrap()
+    };
+
+    let old = schedule(false, PipelineStage::FRAGMENT_SHADER, 0);
+    assert!(diff(&old, &old).is_empty());
+    let renamed = schedule(false, PipelineStage::FRAGMENT_SHADER, 100);
+    assert!(diff(&old, &renamed).is_empty(), "Semaphores are compared by value.");
+
+    let inserted = diff(&old, &schedule(true, PipelineStage::FRAGMENT_SHADER, 0));
+    let submissions: Vec<_> = inserted
+        .queues()
+        .iter()
+        .flat_map(|queue| &queue.submissions)
+        .collect();
+    assert_eq!(submissions.len(), 1, "Unchanged submissions are reported: {:#?}", inserted);
+    assert_eq!(submissions[0].pass, PassId(2));
+    assert_eq!(submissions[0].old, None);
+    assert!(submissions[0].new.is_some());
+
+    let changed = diff(&old, &schedule(false, PipelineStage::VERTEX_SHADER, 0));
+    assert!(!changed.is_empty());
+    let reader = changed
+        .queues()
+        .iter()
+        .flat_map(|queue| &queue.submissions)
+        .find(|submission| submission.pass == PassId(1))
+        .expect("Changed barrier is not reported");
+    // Both the barrier before the read and the ownership release after it change.
+    match reader.buffers[..] {
+        [Change::Modified { ref old, ref new }, Change::Modified { old: ref release, .. }] => {
+            assert_eq!((old.id, old.side), (buffer, GuardSide::Acquire));
+            assert_eq!(old.barrier.states.end.stages, PipelineStage::FRAGMENT_SHADER);
+            assert_eq!(new.barrier.states.end.stages, PipelineStage::VERTEX_SHADER);
+            assert_eq!(release.side, GuardSide::Release);
+        }
+        ref other => panic!("Unexpected barrier changes: {:#?}", other),
+    }
+    let waiter = changed
+        .queues()
+        .iter()
+        .flat_map(|queue| &queue.submissions)
+        .find(|submission| submission.pass == PassId(3))
+        .expect("Changed semaphore is not reported");
+    match waiter.semaphores[..] {
+        [Change::Modified { ref old, ref new }] => {
+            assert_eq!((old.signal, old.wait), (PassId(1), PassId(3)));
+            assert_eq!(new.stage, PipelineStage::VERTEX_SHADER);
+        }
+        ref other => panic!("Unexpected semaphore changes: {:#?}", other),
+    }
+}
+
+/// Compiled graph is instantiated with concrete images swapped between frames.
+/// Slots bound to the same image are rejected.
+fn test_compiled_graph() {
+    let (current, history, uniforms) = (Id::new(0), Id::new(1), Id::new(0));
+    let passes = vec![
+        PassBuilder::new(PassId(0), QueueFamilyId(0))
+            .color_attachment(current)
+            .sample_image(history, PipelineStage::FRAGMENT_SHADER)
+            .uniform_buffer(uniforms, PipelineStage::FRAGMENT_SHADER)
+            .build()
+            .unwrap(),
+    ];
+    let rendered = State {
+        access: ImageAccess::COLOR_ATTACHMENT_WRITE,
+        layout: ImageLayout::ColorAttachmentOptimal,
+        stages: PipelineStage::COLOR_ATTACHMENT_OUTPUT,
+    };
+    let mut resources = Resources::new();
+    for &id in &[current, history] {
+        resources.add_image(
+            id,
+            ResourceInfo {
+                initial_state: Some(rendered),
+                ..ResourceInfo::default()
+            },
+        );
+    }
+    register_declared(&mut resources, &passes);
+    let mut semaphores = 0..;
+    let graph = CompiledGraph::compile(passes, &resources, |_| 1, || {
+        let semaphore = semaphores.next().unwrap();
+        (semaphore, semaphore)
+    }).unwrap();
+    let (sid, submission) = graph
+        .chains()
+        .schedule
+        .iter()
+        .flat_map(|family| family.iter())
+        .flat_map(|queue| queue.iter())
+        .next()
+        .unwrap();
+    let (even, odd) = (Id::new(10), Id::new(11));
+
+    let first = graph
+        .bind(&Binding::new().bind_image(current, even).bind_image(history, odd))
+        .unwrap();
+    assert_eq!((first.image(current), first.buffer(uniforms)), (even, uniforms));
+    let sync = first.sync(sid, submission.sync());
+    let compiled = &submission.sync().acquire.images;
+    assert_eq!(sync.acquire.images.len(), 2);
+    assert_eq!(sync.acquire.images[&even], compiled[&current]);
+    assert_eq!(sync.acquire.images[&odd], compiled[&history]);
+    assert_eq!(first.schedule()[sid].sync().acquire.images[&odd], compiled[&history]);
+
+    // Images swap roles and are transitioned from their final states in the first frame.
+    let second = graph
+        .bind(
+            &Binding::new()
+                .bind_image(current, odd)
+                .bind_image(history, even)
+                .transition_image(current, graph.final_image_state(history).unwrap())
+                .transition_image(history, graph.final_image_state(current).unwrap()),
+        )
+        .unwrap();
+    let sync = second.sync(sid, submission.sync());
+    let history_barrier = &sync.acquire.images[&even];
+    assert_eq!(history_barrier.states.start.layout, ImageLayout::ColorAttachmentOptimal);
+    assert_eq!(history_barrier.states.end.layout, ImageLayout::ShaderReadOnlyOptimal);
+    let current_barrier = &sync.acquire.images[&odd];
+    assert_eq!(current_barrier.states.start.layout, ImageLayout::ShaderReadOnlyOptimal);
+    assert_eq!(current_barrier.states.end.layout, ImageLayout::ColorAttachmentOptimal);
+
+    match graph.bind(&Binding::new().bind_image(current, even).bind_image(history, even)) {
+        Err(BindError::AliasedImage { id, slots }) => {
+            assert_eq!((id, slots), (even, (current, history)))
+        }
+        other => panic!("Unexpected binding of aliased slots: {:?}", other.map(|_| ())),
+    }
+    match graph.bind(&Binding::new().bind_image(current, history)) {
+        Err(BindError::AliasedImage { id, .. }) => assert_eq!(id, history),
+        other => panic!("Unexpected binding of aliased slots: {:?}", other.map(|_| ())),
+    }
+    let previous = State {
+        access: BufferAccess::TRANSFER_WRITE,
+        layout: BufferLayout,
+        stages: PipelineStage::TRANSFER,
+    };
+    match graph.bind(&Binding::new().transition_buffer(uniforms, previous)) {
+        Err(BindError::UntransitionedBuffer { slot }) => assert_eq!(slot, uniforms),
+        other => panic!("Unexpected transition without initial state: {:?}", other.map(|_| ())),
+    }
+}
+
+/// States with access types none of their stages performs are rejected.
+/// Those are reported as warnings too.
+fn test_state_validation() {
+    let (buffer, image) = (Id::new(0), Id::new(0));
+    let stages = PipelineStage::VERTEX_INPUT;
+    let passes = vec![
+        PassBuilder::new(PassId(0), QueueFamilyId(0))
+            .sample_image(image, PipelineStage::FRAGMENT_SHADER)
+            .transfer_image_src(image)
+            .storage_buffer_read(buffer, PipelineStage::HULL_SHADER)
+            .raw_buffer_state(
+                Id::new(1),
+                State {
+                    access: BufferAccess::MEMORY_READ,
+                    layout: BufferLayout,
+                    stages: PipelineStage::TOP_OF_PIPE,
+                },
+            )
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(2), QueueFamilyId(0))
+            .depends_on(PassId(0))
+            .sample_image(image, PipelineStage::TRANSFER)
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(1), QueueFamilyId(0))
+            .depends_on(PassId(0))
+            .raw_buffer_state(
+                buffer,
+                State {
+                    access: BufferAccess::SHADER_WRITE | BufferAccess::VERTEX_BUFFER_READ,
+                    layout: BufferLayout,
+                    stages,
+                },
+            )
+            .build()
+            .unwrap(),
+    ];
+    assert_eq!(
+        declaration_warnings(&passes),
+        vec![
+            ChainWarning::InvalidBufferState {
+                pass: PassId(1),
+                id: buffer,
+                access: BufferAccess::SHADER_WRITE,
+                stages,
+            },
+            ChainWarning::InvalidImageState {
+                pass: PassId(2),
+                id: image,
+                access: ImageAccess::SHADER_READ,
+                stages: PipelineStage::TRANSFER,
+            },
+        ]
+    );
+    let resources = declared(&passes);
+    assert!(collect(passes[..1].to_vec(), &resources, |_| 1).is_ok());
+
+    #[cfg(not(feature = "lenient-states"))]
+    match collect(passes[..2].to_vec(), &resources, |_| 1) {
+        Err(ChainError::InvalidImageStateDeclaration {
+            id,
+            pass,
+            access,
+            stages,
+        }) => {
+            assert_eq!((id, pass), (image, PassId(2)));
+            assert_eq!((access, stages), (ImageAccess::SHADER_READ, PipelineStage::TRANSFER));
+        }
+        other => panic!("Unexpected collection of invalid states: {:?}", other.map(|_| ())),
+    }
+
+    #[cfg(not(feature = "lenient-states"))]
+    match collect(passes, &resources, |_| 1) {
+        Err(ChainError::InvalidBufferStateDeclaration {
+            id,
+            pass,
+            access,
+            stages: declared,
+        }) => {
+            assert_eq!((id, pass), (buffer, PassId(1)), "Pass with lowest id is reported.");
+            assert_eq!((access, declared), (BufferAccess::SHADER_WRITE, stages));
+        }
+        other => panic!("Unexpected collection of invalid states: {:?}", other.map(|_| ())),
+    }
+}
+
+/// Unread writes, layout bounces and queue family round trips are reported for collected chains.
+fn test_chain_warnings() {
+    let (buffer, image) = (Id::new(0), Id::new(0));
+    let passes = vec![
+        PassBuilder::new(PassId(0), QueueFamilyId(0))
+            .storage_buffer_write(buffer, PipelineStage::COMPUTE_SHADER)
+            .color_attachment(image)
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(1), QueueFamilyId(1))
+            .depends_on(PassId(0))
+            .storage_buffer_read(buffer, PipelineStage::COMPUTE_SHADER)
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(2), QueueFamilyId(0))
+            .depends_on(PassId(1))
+            .storage_buffer_read(buffer, PipelineStage::COMPUTE_SHADER)
+            .storage_image_read(image, PipelineStage::FRAGMENT_SHADER)
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(3), QueueFamilyId(0))
+            .depends_on(PassId(2))
+            .color_attachment(image)
+            .build()
+            .unwrap(),
+    ];
+    let round_trip = ChainWarning::BufferRoundTrip {
+        passes: (PassId(0), PassId(1), PassId(2)),
+        id: buffer,
+        families: (QueueFamilyId(0), QueueFamilyId(1)),
+    };
+    let bounce = ChainWarning::LayoutBounce {
+        passes: (PassId(0), PassId(2), PassId(3)),
+        id: image,
+        layouts: (ImageLayout::ColorAttachmentOptimal, ImageLayout::General),
+    };
+
+    let unread = ChainWarning::UnreadImageWrite {
+        pass: PassId(3),
+        id: image,
+        access: ImageAccess::COLOR_ATTACHMENT_READ | ImageAccess::COLOR_ATTACHMENT_WRITE,
+        layout: ImageLayout::ColorAttachmentOptimal,
+        stages: PipelineStage::COLOR_ATTACHMENT_OUTPUT,
+    };
+    let (_, warnings) = collect_with_warnings(passes.clone(), &declared(&passes), |_| 1).unwrap();
+    assert_eq!(warnings, vec![round_trip.clone(), bounce.clone(), unread]);
+
+    // Image read by the host after schedule execution isn't written in vain.
+    let mut resources = Resources::new();
+    let info = ResourceInfo {
+        host_read_after: true,
+        ..ResourceInfo::default()
+    };
+    resources.add_image(image, info);
+    register_declared(&mut resources, &passes);
+    let chains = collect(passes, &resources, |_| 1).unwrap();
+    assert_eq!(chain_warnings(&chains), vec![round_trip, bounce]);
+}
+
+/// Copy independent of rendering is suggested to move to the transfer family, and moved there
+/// when options allow.
+fn test_offload_suggestions() {
+    let (staging, vertices, target) = (Id::new(0), Id::new(1), Id::new(0));
+    let families = [
+        FamilyInfo::new(QueueFamilyId(0), Capability::Graphics, 1),
+        FamilyInfo::new(QueueFamilyId(1), Capability::Transfer, 1),
+    ];
+    let passes = vec![
+        PassBuilder::new(PassId(0), QueueFamilyId(0))
+            .color_attachment(target)
+            .cost(10)
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(1), QueueFamilyId(0))
+            .transfer_buffer_src(staging)
+            .transfer_buffer_dst(vertices)
+            .cost(2)
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(2), QueueFamilyId(0))
+            .depends_on(PassId(0))
+            .depends_on(PassId(1))
+            .vertex_buffer(vertices)
+            .color_attachment(target)
+            .cost(10)
+            .build()
+            .unwrap(),
+    ];
+    let resources = declared(&passes);
+    let chains = collect_with_families(passes.clone(), &resources, &families).unwrap();
+    assert_eq!(
+        offload_suggestions(&chains, &passes, &families),
+        vec![OffloadSuggestion {
+            pass: PassId(1),
+            target_family: QueueFamilyId(1),
+            added_semaphores: 1,
+            slack: 10,
+        }]
+    );
+
+    let family_of = |chains: &Chains, pass| {
+        chains
+            .schedule
+            .iter()
+            .flat_map(|family| family.iter())
+            .flat_map(|queue| queue.iter())
+            .find(|&(_, submission)| submission.pass() == pass)
+            .map(|(sid, _)| sid.family())
+            .unwrap()
+    };
+    let options = SchedulerOptions::default();
+    let chains = collect_with_options(passes.clone(), &resources, &families, &options).unwrap();
+    assert_eq!(family_of(&chains, PassId(1)), QueueFamilyId(0), "Passes are moved by default.");
+    let options = SchedulerOptions {
+        offload_transfers: true,
+    };
+    let chains = collect_with_options(passes.clone(), &resources, &families, &options).unwrap();
+    assert_eq!(family_of(&chains, PassId(1)), QueueFamilyId(1));
+    assert_eq!(offload_suggestions(&chains, &passes, &families), vec![]);
+}
+
+/// State constructors match declarations of the builder and are usable in constants.
+fn test_state_constructors() {
+    const VERTEX: State<Buffer> = State::<Buffer>::vertex();
+    const TRANSFER_SRC: State<Image> = State::<Image>::transfer_src();
+    let stages = PipelineStage::VERTEX_SHADER | PipelineStage::FRAGMENT_SHADER;
+    let buffer = |access, stages| State {
+        access,
+        layout: BufferLayout,
+        stages,
+    };
+    let image = |access, layout, stages| State {
+        access,
+        layout,
+        stages,
+    };
+
+    let buffers = [
+        (
+            State::<Buffer>::uniform(stages),
+            buffer(BufferAccess::CONSTANT_BUFFER_READ, stages),
+        ),
+        (
+            VERTEX,
+            buffer(BufferAccess::VERTEX_BUFFER_READ, PipelineStage::VERTEX_INPUT),
+        ),
+        (
+            State::<Buffer>::index(),
+            buffer(BufferAccess::INDEX_BUFFER_READ, PipelineStage::VERTEX_INPUT),
+        ),
+        (
+            State::<Buffer>::indirect_read(),
+            buffer(BufferAccess::INDIRECT_COMMAND_READ, PipelineStage::DRAW_INDIRECT),
+        ),
+        (
+            State::<Buffer>::transfer_src(),
+            buffer(BufferAccess::TRANSFER_READ, PipelineStage::TRANSFER),
+        ),
+        (
+            State::<Buffer>::transfer_dst(),
+            buffer(BufferAccess::TRANSFER_WRITE, PipelineStage::TRANSFER),
+        ),
+    ];
+    for &(constructed, expected) in &buffers {
+        assert_eq!(constructed, expected);
+    }
+
+    let depth_tests = PipelineStage::EARLY_FRAGMENT_TESTS | PipelineStage::LATE_FRAGMENT_TESTS;
+    let images = [
+        (
+            State::<Image>::sampled(stages),
+            image(ImageAccess::SHADER_READ, ImageLayout::ShaderReadOnlyOptimal, stages),
+        ),
+        (
+            State::<Image>::color_attachment(),
+            image(
+                ImageAccess::COLOR_ATTACHMENT_READ | ImageAccess::COLOR_ATTACHMENT_WRITE,
+                ImageLayout::ColorAttachmentOptimal,
+                PipelineStage::COLOR_ATTACHMENT_OUTPUT,
+            ),
+        ),
+        (
+            State::<Image>::depth_attachment_read(),
+            image(
+                ImageAccess::DEPTH_STENCIL_ATTACHMENT_READ,
+                ImageLayout::DepthStencilReadOnlyOptimal,
+                depth_tests,
+            ),
+        ),
+        (
+            TRANSFER_SRC,
+            image(
+                ImageAccess::TRANSFER_READ,
+                ImageLayout::TransferSrcOptimal,
+                PipelineStage::TRANSFER,
+            ),
+        ),
+        (
+            State::<Image>::transfer_dst(),
+            image(
+                ImageAccess::TRANSFER_WRITE,
+                ImageLayout::TransferDstOptimal,
+                PipelineStage::TRANSFER,
+            ),
+        ),
+        (
+            State::<Image>::storage_read_write(stages),
+            image(
+                ImageAccess::SHADER_READ | ImageAccess::SHADER_WRITE,
+                ImageLayout::General,
+                stages,
+            ),
+        ),
+    ];
+    for &(constructed, expected) in &images {
+        assert_eq!(constructed, expected);
+    }
+
+    let (buffer_id, image_id) = (Id::new(0), Id::new(0));
+    let declared = |builder: PassBuilder| builder.build().unwrap().images[&image_id].state;
+    let pass = || PassBuilder::new(PassId(0), QueueFamilyId(0));
+    assert_eq!(declared(pass().sample_image(image_id, stages)), State::<Image>::sampled(stages));
+    assert_eq!(declared(pass().color_attachment(image_id)), State::<Image>::color_attachment());
+    assert_eq!(
+        declared(pass().depth_stencil_read(image_id)),
+        State::<Image>::depth_attachment_read()
+    );
+    assert_eq!(declared(pass().transfer_image_src(image_id)), TRANSFER_SRC);
+    assert_eq!(declared(pass().transfer_image_dst(image_id)), State::<Image>::transfer_dst());
+    let declared = |builder: PassBuilder| builder.build().unwrap().buffers[&buffer_id].state;
+    assert_eq!(
+        declared(pass().uniform_buffer(buffer_id, stages)),
+        State::<Buffer>::uniform(stages)
+    );
+    assert_eq!(declared(pass().vertex_buffer(buffer_id)), VERTEX);
+    assert_eq!(declared(pass().index_buffer(buffer_id)), State::<Buffer>::index());
+    assert_eq!(declared(pass().indirect_buffer(buffer_id)), State::<Buffer>::indirect_read());
+    assert_eq!(declared(pass().transfer_buffer_src(buffer_id)), State::<Buffer>::transfer_src());
+    assert_eq!(declared(pass().transfer_buffer_dst(buffer_id)), State::<Buffer>::transfer_dst());
+
+    let sampled = State::<Image>::sampled(PipelineStage::FRAGMENT_SHADER);
+    let storage = State::<Image>::storage_read_write(PipelineStage::COMPUTE_SHADER);
+    assert!(sampled.is_read_only() && !storage.is_read_only());
+    assert!(TRANSFER_SRC.is_read_only() && !State::<Image>::transfer_dst().is_read_only());
+    assert!(storage.has_layout_transition_from(&sampled));
+    assert!(!sampled.has_layout_transition_from(&State::<Image>::sampled(stages)));
+    assert!(!VERTEX.has_layout_transition_from(&State::<Buffer>::transfer_dst()));
+    assert!(sampled.stages_intersect(&State::<Image>::sampled(stages)));
+    assert!(!sampled.stages_intersect(&storage));
+
+    let duplicate = State::<Image>::sampled(stages);
+    let unique: HashSet<_> = images
+        .iter()
+        .map(|&(state, _)| state)
+        .chain(Some(duplicate))
+        .collect();
+    assert_eq!(unique.len(), images.len(), "Equal states hash differently.");
+}
+
+fn test_truncate_sync() {
+    let (buffer, image) = (Id::new(0), Id::new(0));
+    let passes = vec![
+        PassBuilder::new(PassId(0), QueueFamilyId(0))
+            .storage_buffer_write(buffer, PipelineStage::COMPUTE_SHADER)
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(1), QueueFamilyId(1))
+            .depends_on(PassId(0))
+            .indirect_buffer(buffer)
+            .transfer_image_dst(image)
+            .build()
+            .unwrap(),
+    ];
+    let mut resources = Resources::new();
+    resources.add_buffer(
+        buffer,
+        ResourceInfo {
+            sharing: SharingMode::Exclusive,
+            ..ResourceInfo::default()
+        },
+    );
+    resources.add_image(image, ResourceInfo::default());
+
+    let chains = collect(passes, &resources, |_| 1).unwrap();
+    let mut semaphore_id = 0;
+    let mut new_semaphore = || {
+        let id = semaphore_id;
+        semaphore_id += 1;
+        id
+    };
+    let schedule = sync_with_options(&chains, SyncOptions::default(), || {
+        let id = new_semaphore();
+        (id, id)
+    }).unwrap();
+    let synched_chains = Chains {
+        schedule,
+        buffers: chains.buffers,
+        images: chains.images,
+        stencils: chains.stencils,
+        parts: chains.parts,
+        buffer_parts: chains.buffer_parts,
+    };
+    let parking = ParkingStates::default();
+    let first = SubmissionId::new(QueueId::new(QueueFamilyId(0), 0), 0);
+    let second = SubmissionId::new(QueueId::new(QueueFamilyId(1), 0), 0);
+
+    // Cut between release and acquire of the buffer.
+    let truncated = synched_chains.truncate_sync(first, parking, &mut new_semaphore);
+    assert_eq!(truncated[QueueId::new(QueueFamilyId(1), 0)].len(), 0);
+    let sync = truncated[first].sync();
+    let barrier = &sync.release.buffers[&buffer];
+    assert!(barrier.queues.is_none(), "Ownership is released to cut submission.");
+    assert_eq!(
+        barrier.states,
+        synched_chains.buffers[&buffer].link(0).state()..parking.buffers
+    );
+    assert!(sync.release.signal.is_empty(), "Semaphore waited by cut submission is signaled.");
+    assert!(sync.acquire.images.is_empty() && sync.release.images.is_empty());
+    assert_eq!(sync.fence, Some(0));
+    assert_eq!(fence_count(&truncated), 1);
+
+    // The whole schedule keeps the transfer and parks resources after the last links.
+    let truncated = synched_chains.truncate_sync(second, parking, &mut new_semaphore);
+    let (first_sync, second_sync) = (truncated[first].sync(), truncated[second].sync());
+    assert!(first_sync.release.buffers[&buffer].queues.is_some());
+    assert!(second_sync.acquire.buffers[&buffer].queues.is_some());
+    assert_eq!(first_sync.release.signal.len(), second_sync.acquire.wait.len());
+    assert_eq!(
+        second_sync.release.buffers[&buffer].states,
+        synched_chains.buffers[&buffer].link(1).state()..parking.buffers
+    );
+    let barrier = &second_sync.release.images[&image];
+    assert_eq!(barrier.states.start.layout, ImageLayout::TransferDstOptimal);
+    assert_eq!(barrier.states.end, parking.images);
+    assert_eq!(fence_count(&truncated), 2);
+
+    // Readers on other queues are waited before the parking barrier.
+    let passes = vec![
+        PassBuilder::new(PassId(0), QueueFamilyId(0))
+            .queue(0)
+            .storage_buffer_read(buffer, PipelineStage::COMPUTE_SHADER)
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(1), QueueFamilyId(0))
+            .queue(1)
+            .depends_on(PassId(0))
+            .storage_buffer_read(buffer, PipelineStage::COMPUTE_SHADER)
+            .build()
+            .unwrap(),
+    ];
+    let mut resources = Resources::new();
+    resources.add_buffer(buffer, ResourceInfo::default());
+    let chains = collect(passes, &resources, |_| 2).unwrap();
+    let schedule = sync_with_options(&chains, SyncOptions::default(), || {
+        let id = new_semaphore();
+        (id, id)
+    }).unwrap();
+    let synched_chains = Chains {
+        schedule,
+        buffers: chains.buffers,
+        images: chains.images,
+        stencils: chains.stencils,
+        parts: chains.parts,
+        buffer_parts: chains.buffer_parts,
+    };
+    assert!(!synched_chains.buffers[&buffer].link(0).single_queue());
+    let queues = [QueueId::new(QueueFamilyId(0), 0), QueueId::new(QueueFamilyId(0), 1)];
+    let sids: Vec<_> = queues.iter().map(|&qid| SubmissionId::new(qid, 0)).collect();
+    let last = *sids
+        .iter()
+        .max_by_key(|&&sid| synched_chains.schedule[sid].submit_order())
+        .unwrap();
+    let other = *sids.iter().find(|&&sid| sid != last).unwrap();
+    let truncated = synched_chains.truncate_sync(last, parking, &mut new_semaphore);
+    let signal = truncated[other].sync().release.signal[0];
+    let sync = truncated[last].sync();
+    assert!(sync.release.buffers.contains_key(&buffer));
+    assert!(
+        sync.release
+            .wait
+            .iter()
+            .any(|wait| wait.semaphore() == signal.semaphore()),
+        "Other reader isn't waited before the parking barrier."
+    );
+}
+
+fn test_critical_path() {
+    let id = Id::new(0);
+    let passes = vec![
+        PassBuilder::new(PassId(0), QueueFamilyId(0))
+            .storage_buffer_write(id, PipelineStage::COMPUTE_SHADER)
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(1), QueueFamilyId(1))
+            .depends_on(PassId(0))
+            .storage_buffer_read(id, PipelineStage::COMPUTE_SHADER)
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(2), QueueFamilyId(0))
+            .depends_on(PassId(0))
+            .build()
+            .unwrap(),
+    ];
+    let mut resources = Resources::new();
+    resources.add_buffer(id, ResourceInfo::default());
+
+    let chains = collect(passes, &resources, |_| 1).unwrap();
+    let mut semaphore_id = 0;
+    let schedule = sync_with_options(&chains, SyncOptions::default(), || {
+        let id = semaphore_id;
+        semaphore_id += 1;
+        (id, id)
+    }).unwrap();
+    let synched_chains = Chains {
+        schedule,
+        buffers: chains.buffers,
+        images: chains.images,
+        stencils: chains.stencils,
+        parts: chains.parts,
+        buffer_parts: chains.buffer_parts,
+    };
+    let sid = |pass| {
+        synched_chains
+            .schedule
+            .iter()
+            .flat_map(|family| family.iter())
+            .flat_map(|queue| queue.iter())
+            .find(|&(_, submission)| submission.pass() == PassId(pass))
+            .map(|(sid, _)| sid)
+            .unwrap()
+    };
+
+    let dependencies = synched_chains.semaphore_dependencies();
+    assert_eq!(dependencies.len(), 1);
+    assert_eq!(
+        (dependencies[0].signal, dependencies[0].wait),
+        (sid(0), sid(1)),
+        "Reader doesn't wait for the writer."
+    );
+
+    // Passes on different queues overlap after the first one.
+    let critical = synched_chains.critical_path();
+    assert_eq!(critical.length, 2);
+    assert_eq!(critical.total_work, 3);
+    assert_eq!(critical.parallelism_ratio, 1.5);
+
+    let costs = [1, 5, 2];
+    let critical = synched_chains.critical_path_with_costs(|sid| {
+        costs[synched_chains.schedule[sid].pass().0]
+    });
+    assert_eq!(critical.length, 6);
+    assert_eq!(critical.total_work, 8);
+    assert_eq!(critical.path, vec![sid(0), sid(1)]);
+}
+
+/// Collect and synchronize passes with default options and check resulting barriers.
+fn sync_default(passes: &[Pass], resources: &Resources) -> (Chains<SyncData<usize, usize>>, usize) {
+    let chains = collect(passes.to_vec(), resources, |_| 1).unwrap();
+    let mut semaphore_id = 0;
+    let schedule = sync_with_options(&chains, SyncOptions::default(), || {
+        let id = semaphore_id;
+        semaphore_id += 1;
+        (id, id)
+    }).unwrap();
+    let synched_chains = Chains {
+        schedule,
+        buffers: chains.buffers,
+        images: chains.images,
+        stencils: chains.stencils,
+        parts: chains.parts,
+        buffer_parts: chains.buffer_parts,
+    };
+    check_legal_barriers(&synched_chains.schedule);
+    if let Err(errors) = validate(&synched_chains, passes) {
+        panic!("Validation failed: {:#?}", errors);
+    }
+    (synched_chains, semaphore_id)
+}
+
+/// Resources exported without consuming pass after the writer keep accesses of initial states
+/// with no stages to perform them. Barriers move those to stages that do.
+fn test_export_without_consumer() {
+    let (buffer, image) = (Id::new(0), Id::new(1));
+    let family = QueueFamilyId(0);
+    let passes = vec![
+        PassBuilder::new(PassId(0), family)
+            .transfer_buffer_dst(buffer)
+            .color_attachment(image)
+            .build()
+            .unwrap(),
+    ];
+    let mut resources = Resources::new();
+    resources.add_buffer(
+        buffer,
+        ResourceInfo {
+            initial_state: Some(State {
+                access: BufferAccess::HOST_WRITE,
+                layout: BufferLayout,
+                stages: PipelineStage::TOP_OF_PIPE,
+            }),
+            export: Some(External {
+                family: EXTERNAL_FAMILY,
+                state: State::<Buffer>::transfer_src(),
+            }),
+            ..ResourceInfo::default()
+        },
+    );
+    resources.add_image(
+        image,
+        ResourceInfo {
+            initial_state: Some(State {
+                access: ImageAccess::COLOR_ATTACHMENT_WRITE,
+                layout: ImageLayout::ColorAttachmentOptimal,
+                stages: PipelineStage::BOTTOM_OF_PIPE,
+            }),
+            export: Some(External {
+                family: EXTERNAL_FAMILY,
+                state: State::sampled(PipelineStage::FRAGMENT_SHADER),
+            }),
+            ..ResourceInfo::default()
+        },
+    );
+
+    let (synched_chains, semaphore_id) = sync_default(&passes, &resources);
+    let sid = SubmissionId::new(QueueId::new(family, 0), 0);
+    let sync = synched_chains.schedule[sid].sync();
+    let initial = &sync.acquire.buffers[&buffer].states.start;
+    assert_eq!(initial.access, BufferAccess::HOST_WRITE);
+    assert_eq!(initial.stages, PipelineStage::TOP_OF_PIPE | PipelineStage::HOST);
+    let initial = &sync.acquire.images[&image].states.start;
+    assert_eq!(initial.access, ImageAccess::COLOR_ATTACHMENT_WRITE);
+    assert_eq!(
+        initial.stages,
+        PipelineStage::COLOR_ATTACHMENT_OUTPUT | PipelineStage::BOTTOM_OF_PIPE
+    );
+
+    let release = sync.release.pipeline_barrier().expect("Ownership isn't released.");
+    assert_eq!(release.stages.end, PipelineStage::BOTTOM_OF_PIPE);
+    assert!(release.buffers.iter().all(|&(_, b)| b.states.end.access == Access::none()));
+    assert!(release.images.iter().all(|&(_, b)| b.states.end.access == Access::none()));
+    assert_eq!(release.images[0].1.states.end.layout, ImageLayout::ShaderReadOnlyOptimal);
+
+    let mut rng = Isaac64Rng::new_unseeded();
+    sanity_check(&mut rng, &synched_chains, &passes, semaphore_id, false);
+}
+
+/// The only pass using the image transitions it to layout of the next use outside the frame.
+/// Accesses of the initial state are made available at stages that perform them.
+fn test_final_layout_only_image() {
+    let id = Id::new(0);
+    let rendered = State {
+        access: ImageAccess::COLOR_ATTACHMENT_WRITE,
+        layout: ImageLayout::ColorAttachmentOptimal,
+        stages: PipelineStage::BOTTOM_OF_PIPE,
+    };
+    let sampled_later = State {
+        access: ImageAccess::empty(),
+        layout: ImageLayout::ShaderReadOnlyOptimal,
+        stages: PipelineStage::BOTTOM_OF_PIPE,
+    };
+    let output = PipelineStage::COLOR_ATTACHMENT_OUTPUT | PipelineStage::BOTTOM_OF_PIPE;
+    assert!(!rendered.is_legal() && sampled_later.is_legal());
+    assert_eq!(rendered.legalized().stages, output);
+    assert_eq!(sampled_later.legalized(), sampled_later);
+
+    let passes = vec![
+        PassBuilder::new(PassId(0), QueueFamilyId(0))
+            .raw_image_state(id, sampled_later)
+            .build()
+            .unwrap(),
+    ];
+    let mut resources = Resources::new();
+    resources.add_image(
+        id,
+        ResourceInfo {
+            initial_state: Some(rendered),
+            ..ResourceInfo::default()
+        },
+    );
+
+    let (synched_chains, semaphore_id) = sync_default(&passes, &resources);
+    let sid = SubmissionId::new(QueueId::new(QueueFamilyId(0), 0), 0);
+    let sync = synched_chains.schedule[sid].sync();
+    let guard = if sync.frame_begin.images.contains_key(&id) {
+        &sync.frame_begin
+    } else {
+        &sync.acquire
+    };
+    let barrier = guard.pipeline_barrier().expect("Image isn't transitioned.");
+    assert_eq!(barrier.stages, output..PipelineStage::BOTTOM_OF_PIPE);
+    let states = &barrier.images[0].1.states;
+    assert_eq!(states.start.access, ImageAccess::COLOR_ATTACHMENT_WRITE);
+    assert_eq!(states.end, sampled_later);
+
+    let mut rng = Isaac64Rng::new_unseeded();
+    sanity_check(&mut rng, &synched_chains, &passes, semaphore_id, false);
+}
+
+/// Semaphore waits block only stages at which the waiting queue accesses the resources
+/// carried across the semaphore, including accesses of later submissions on the queue.
+fn test_minimal_wait_stages() {
+    let (buffer, image) = (Id::new(0), Id::new(0));
+    let (compute, graphics) = (QueueFamilyId(0), QueueFamilyId(1));
+    let written = State {
+        access: BufferAccess::SHADER_WRITE,
+        layout: BufferLayout,
+        stages: PipelineStage::COMPUTE_SHADER,
+    };
+    let stored = State {
+        access: ImageAccess::SHADER_WRITE,
+        layout: ImageLayout::General,
+        stages: PipelineStage::COMPUTE_SHADER,
+    };
+    let vertices = State {
+        access: BufferAccess::VERTEX_BUFFER_READ,
+        layout: BufferLayout,
+        stages: PipelineStage::VERTEX_INPUT,
+    };
+    let fetched = State {
+        access: BufferAccess::SHADER_READ,
+        stages: PipelineStage::FRAGMENT_SHADER,
+        ..vertices
+    };
+    let sampled = State {
+        access: ImageAccess::SHADER_READ,
+        stages: PipelineStage::FRAGMENT_SHADER,
+        ..stored
+    };
+    let passes = vec![
+        PassBuilder::new(PassId(0), compute)
+            .raw_buffer_state(buffer, written)
+            .raw_image_state(image, stored)
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(1), graphics)
+            .depends_on(PassId(0))
+            .raw_buffer_state(buffer, vertices)
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(2), graphics)
+            .depends_on(PassId(1))
+            .raw_buffer_state(buffer, fetched)
+            .raw_image_state(image, sampled)
+            .build()
+            .unwrap(),
+    ];
+    let (synched_chains, semaphore_id) = sync_default(&passes, &declared(&passes));
+
+    // Both resources are carried by the single wait of the first graphics submission.
+    let queue = QueueId::new(graphics, 0);
+    let first = synched_chains.schedule[SubmissionId::new(queue, 0)].sync();
+    let later = synched_chains.schedule[SubmissionId::new(queue, 1)].sync();
+    assert_eq!(first.acquire.wait.len(), 1);
+    assert!(later.acquire.wait.is_empty() && later.release.wait.is_empty());
+    assert_eq!(
+        first.acquire.wait[0].stage(),
+        PipelineStage::VERTEX_INPUT | PipelineStage::FRAGMENT_SHADER
+    );
+
+    let mut rng = Isaac64Rng::new_unseeded();
+    sanity_check(&mut rng, &synched_chains, &passes, semaphore_id, false);
+}
+
+/// Texture streaming through single staging buffer over two frames.
+/// Each frame refills the staging buffer, uploads it into the frame's texture on
+/// transfer queue and samples the texture. Refill must wait for the previous upload to
+/// finish reading the staging buffer. The last rendered frame is read back.
+fn test_staging_ring() {
+    let (staging, readback, target) = (Id::new(0), Id::new(1), Id::new(2));
+    let (transfer, graphics) = (QueueFamilyId(1), QueueFamilyId(0));
+    let slot = 0..256;
+    let mip = SubresourceRange {
+        aspects: Aspects::COLOR,
+        levels: 0..1,
+        layers: 0..1,
+    };
+    let refill = State {
+        access: BufferAccess::TRANSFER_WRITE,
+        layout: BufferLayout,
+        stages: PipelineStage::TRANSFER,
+    };
+
+    let mut passes = Vec::new();
+    for frame in 0..2 {
+        let texture = Id::new(frame);
+        let base = frame as usize * 3;
+        let mut fill = PassBuilder::new(PassId(base), transfer).raw_buffer(
+            staging,
+            StateUsage {
+                state: refill,
+                usage: BufferUsage::TRANSFER_DST,
+                range: Some(slot.clone()),
+                discard: true,
+                ordering: OrderingHint::None,
+            },
+        );
+        if frame > 0 {
+            fill = fill.depends_on(PassId(base - 2));
+        }
+        passes.push(fill.build().unwrap());
+        passes.push(
+            PassBuilder::new(PassId(base + 1), transfer)
+                .depends_on(PassId(base))
+                .copy_buffer_to_image(staging, slot.clone(), texture, mip.clone())
+                .discard_image(texture)
+                .build()
+                .unwrap(),
+        );
+        passes.push(
+            PassBuilder::new(PassId(base + 2), graphics)
+                .depends_on(PassId(base + 1))
+                .sample_image(texture, PipelineStage::FRAGMENT_SHADER)
+                .clear_color_attachment(target)
+                .build()
+                .unwrap(),
+        );
+    }
+    passes.push(
+        PassBuilder::new(PassId(6), graphics)
+            .depends_on(PassId(5))
+            .copy_image_to_buffer(target, mip.clone(), readback, slot.clone())
+            .build()
+            .unwrap(),
+    );
+    let upload = &passes[1];
+    assert_eq!(upload.buffers[&staging].state.access, BufferAccess::TRANSFER_READ);
+    assert_eq!(upload.buffers[&staging].range, Some(slot.clone()));
+    assert_eq!(upload.images[&Id::new(0)].state.layout, ImageLayout::TransferDstOptimal);
+    assert_eq!(upload.images[&Id::new(0)].range, Some(mip.clone()));
+
+    let (synched_chains, semaphore_id) = sync_default(&passes, &declared(&passes));
+    let sid = |pass| {
+        synched_chains
+            .schedule
+            .iter()
+            .flat_map(|family| family.iter())
+            .flat_map(|queue| queue.iter())
+            .find(|&(_, submission)| submission.pass() == PassId(pass))
+            .unwrap()
+            .0
+    };
+
+    // Textures are transitioned to be sampled after uploads.
+    for frame in 0..2 {
+        let texture = Id::new(frame as u32);
+        let sync = synched_chains.schedule[sid(frame * 3 + 2)].sync();
+        let barrier = &sync.acquire.images[&texture];
+        assert_eq!(barrier.states.start.layout, ImageLayout::TransferDstOptimal);
+        assert_eq!(barrier.states.end.layout, ImageLayout::ShaderReadOnlyOptimal);
+    }
+    let barrier = &synched_chains.schedule[sid(6)].sync().acquire.images[&target];
+    assert_eq!(barrier.states.start.layout, ImageLayout::ColorAttachmentOptimal);
+    assert_eq!(barrier.states.end.layout, ImageLayout::TransferSrcOptimal);
+
+    // Refill of the next frame waits for transfer stage of the upload reading the staging buffer.
+    // Its previous content is discarded, so only the execution dependency is required.
+    let (upload, refill) = (sid(1), sid(3));
+    assert_eq!(upload.queue(), refill.queue());
+    let guarded = |sid: SubmissionId, side: fn(&SyncData<usize, usize>) -> &Guard<usize, usize>| {
+        let guard = side(synched_chains.schedule[sid].sync());
+        guard
+            .buffers
+            .get(&staging)
+            .into_iter()
+            .chain(guard.buffer_parts.iter().map(|(_, barrier)| barrier))
+            .chain(guard.wait_events.iter().flat_map(|wait| wait.buffers.get(&staging)))
+            .any(|barrier| {
+                barrier.states.start.stages.contains(PipelineStage::TRANSFER)
+                    && barrier.states.end.access == BufferAccess::TRANSFER_WRITE
+            })
+    };
+    assert!(
+        (upload.index()..refill.index() + 1).any(|index| {
+            let sid = SubmissionId::new(upload.queue(), index);
+            guarded(sid, |sync| &sync.release) || guarded(sid, |sync| &sync.acquire)
+        }),
+        "Staging buffer isn't guarded against reuse."
+    );
+
+    let mut rng = Isaac64Rng::new_unseeded();
+    sanity_check(&mut rng, &synched_chains, &passes, semaphore_id, false);
+}
+
+/// Render pass samples texture written on another queue.
+/// With transition nodes the layout transition and the semaphore wait guarding it
+/// move to the node before the render pass, whose submission records no synchronization.
+fn test_transition_nodes() {
+    let (texture, target) = (Id::new(0), Id::new(1));
+    let (compute, graphics) = (QueueFamilyId(1), QueueFamilyId(0));
+    let stored = State {
+        access: ImageAccess::SHADER_WRITE,
+        layout: ImageLayout::General,
+        stages: PipelineStage::COMPUTE_SHADER,
+    };
+    let sampled = State {
+        access: ImageAccess::SHADER_READ,
+        layout: ImageLayout::ShaderReadOnlyOptimal,
+        stages: PipelineStage::FRAGMENT_SHADER,
+    };
+    let drawn = State {
+        access: ImageAccess::COLOR_ATTACHMENT_WRITE,
+        layout: ImageLayout::ColorAttachmentOptimal,
+        stages: PipelineStage::COLOR_ATTACHMENT_OUTPUT,
+    };
+    let passes = vec![
+        PassBuilder::new(PassId(0), compute)
+            .raw_image_state(texture, stored)
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(1), graphics)
+            .depends_on(PassId(0))
+            .render_pass()
+            .raw_image_state(texture, sampled)
+            .raw_image_state(target, drawn)
+            .build()
+            .unwrap(),
+    ];
+    let resources = declared(&passes);
+    let chains = collect(passes.clone(), &resources, |_| 1).unwrap();
+    let mut semaphore_id = 0;
+    let options = SyncOptions::default().standalone_transition_nodes(true);
+    match sync_with_options(&chains, options.clone(), || (0, 0)) {
+        Err(ChainError::UnsupportedTransitionNodes) => {}
+        other => panic!("Transition nodes are inserted by schedule: {:?}", other.map(|_| ())),
+    }
+    match IncrementalChains::new(passes.clone(), resources, |_| 1, options.clone(), || (0, 0)) {
+        Err(ChainError::UnsupportedTransitionNodes) => {}
+        other => panic!("Transition nodes are updated incrementally: {:?}", other.map(|_| ())),
+    }
+    let synched_chains = sync_chains(chains, options, || {
+        let id = semaphore_id;
+        semaphore_id += 1;
+        (id, id)
+    }).unwrap();
+    check_legal_barriers(&synched_chains.schedule);
+    if let Err(errors) = validate(&synched_chains, &passes) {
+        panic!("Validation failed: {:#?}", errors);
+    }
+
+    let queue = &synched_chains.schedule[QueueId::new(graphics, 0)];
+    assert_eq!(queue.len(), 2);
+    let node = &synched_chains.schedule[SubmissionId::new(queue.id(), 0)];
+    let pass = &synched_chains.schedule[SubmissionId::new(queue.id(), 1)];
+    assert!(node.is_transition() && !pass.is_transition());
+    assert_eq!((node.pass(), pass.pass()), (PassId(1), PassId(1)));
+    assert!(node.images().next().is_none());
+    let acquire = &node.sync().acquire;
+    assert_eq!(acquire.wait.len(), 1);
+    assert_eq!(acquire.images[&texture].states.end.layout, ImageLayout::ShaderReadOnlyOptimal);
+    let acquire = &pass.sync().acquire;
+    assert!(acquire.wait.is_empty() && acquire.images.is_empty() && acquire.signal.is_empty());
+
+    // Links of the render pass start at the node.
+    let span = SubmissionId::new(queue.id(), 0)..SubmissionId::new(queue.id(), 2);
+    for &id in &[texture, target] {
+        let link = synched_chains.images[&id].link_views().next_back().unwrap();
+        assert_eq!(link.spans(), vec![span.clone()]);
+    }
+    // Ids of submissions on the compute queue are unchanged.
+    let producer = SubmissionId::new(QueueId::new(compute, 0), 0);
+    assert!(!synched_chains.schedule[producer].is_transition());
+    assert_eq!(synched_chains.schedule.queue(producer.queue()).unwrap().len(), 1);
+
+    let mut rng = Isaac64Rng::new_unseeded();
+    sanity_check(&mut rng, &synched_chains, &passes, semaphore_id, false);
+}
+
+/// Attachment read as input attachment by the same pass is tracked by single link
+/// and gets by-region self-dependency.
+/// Another pass writing disjoint range of the image in the same link is rejected.
+fn test_feedback_loop() {
+    let (target, texture) = (Id::new(0), Id::new(1));
+    let load = AttachmentOps::new(AttachmentLoadOp::Load, AttachmentStoreOp::Store);
+    let passes = vec![
+        PassBuilder::new(PassId(0), QueueFamilyId(0))
+            .clear_image(target)
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(1), QueueFamilyId(0))
+            .depends_on(PassId(0))
+            .render_pass()
+            .image_use(target, ImageUse::FeedbackLoop(load))
+            .sample_image(texture, PipelineStage::FRAGMENT_SHADER)
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(2), QueueFamilyId(0))
+            .depends_on(PassId(1))
+            .sample_image(target, PipelineStage::FRAGMENT_SHADER)
+            .build()
+            .unwrap(),
+    ];
+    assert!(passes[1].images[&target].state.is_feedback_loop());
+    assert!(!passes[1].images[&texture].state.is_feedback_loop());
+    let resources = declared(&passes);
+    let (chains, warnings) = collect_with_warnings(passes.clone(), &resources, |_| 1).unwrap();
+    assert!(warnings.is_empty(), "Unexpected warnings: {:?}", warnings);
+    let layouts: Vec<_> = chains.images[&target]
+        .links()
+        .iter()
+        .map(|link| link.state().layout)
+        .collect();
+    assert_eq!(
+        layouts,
+        vec![
+            ImageLayout::TransferDstOptimal,
+            ImageLayout::General,
+            ImageLayout::ShaderReadOnlyOptimal,
+        ]
+    );
+
+    let mut semaphore_id = 0;
+    let synched_chains = sync_chains(chains, SyncOptions::default(), || {
+        let id = semaphore_id;
+        semaphore_id += 1;
+        (id, id)
+    }).unwrap();
+    check_legal_barriers(&synched_chains.schedule);
+    if let Err(errors) = validate(&synched_chains, &passes) {
+        panic!("Validation failed: {:#?}", errors);
+    }
+    let queue = QueueId::new(QueueFamilyId(0), 0);
+    let sync = |index| synched_chains.schedule[SubmissionId::new(queue, index)].sync();
+    let written = State {
+        access: ImageAccess::COLOR_ATTACHMENT_WRITE,
+        layout: ImageLayout::General,
+        stages: PipelineStage::COLOR_ATTACHMENT_OUTPUT,
+    };
+    let read = State {
+        access: ImageAccess::INPUT_ATTACHMENT_READ,
+        layout: ImageLayout::General,
+        stages: PipelineStage::FRAGMENT_SHADER,
+    };
+    assert_eq!(
+        sync(1).feedback_loops,
+        vec![FeedbackLoop {
+            id: target,
+            range: None,
+            states: written..read,
+            by_region: true,
+        }]
+    );
+    assert!(sync(0).feedback_loops.is_empty() && sync(2).feedback_loops.is_empty());
+    let walker = ScheduleWalker::new(&synched_chains.schedule);
+    let submissions = walker.queues().next().unwrap().submissions();
+    assert_eq!(submissions[1].feedback_loops(), &sync(1).feedback_loops[..]);
+    assert_eq!(
+        submissions[1].as_self_dependency(),
+        Some(SubpassDependency {
+            stages: PipelineStage::COLOR_ATTACHMENT_OUTPUT..PipelineStage::FRAGMENT_SHADER,
+            accesses: ImageAccess::COLOR_ATTACHMENT_WRITE..ImageAccess::INPUT_ATTACHMENT_READ,
+            by_region: true,
+            layouts: Vec::new(),
+        })
+    );
+    assert_eq!(submissions[2].as_self_dependency(), None);
+
+    let mut rng = Isaac64Rng::new_unseeded();
+    sanity_check(&mut rng, &synched_chains, &passes, semaphore_id, false);
+
+    // Mip levels are disjoint, so the storage write would join the link of the feedback loop.
+    let level = |level: u8| SubresourceRange {
+        aspects: Aspects::COLOR,
+        levels: level..level + 1,
+        layers: 0..1,
+    };
+    let feedback = StateUsage {
+        range: Some(level(0)),
+        ..ImageUse::FeedbackLoop(load).state_usage()
+    };
+    let storage = StateUsage {
+        range: Some(level(1)),
+        ..ImageUse::Storage(PipelineStage::FRAGMENT_SHADER).state_usage()
+    };
+    let passes = vec![
+        PassBuilder::new(PassId(0), QueueFamilyId(0))
+            .render_pass()
+            .raw_image(target, feedback)
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(1), QueueFamilyId(0))
+            .depends_on(PassId(0))
+            .raw_image(target, storage)
+            .build()
+            .unwrap(),
+    ];
+    match collect(passes, &resources, |_| 1) {
+        Err(CollectError::FeedbackLoopConflict { id, pass, state }) => {
+            assert_eq!((id, pass), (target, PassId(1)));
+            assert_eq!(state, ImageUse::Storage(PipelineStage::FRAGMENT_SHADER).state());
+        }
+        other => panic!("Feedback loop conflict is not rejected: {:?}", other.map(|_| ())),
+    }
+}
+
+
+/// Sub-graph inserted as single node transitions boundary resources to the states
+/// the parent provides them in.
+fn test_sub_chain() {
+    let (image, temp, output) = (Id::new(0), Id::new(0), Id::new(1));
+    let family = QueueFamilyId(0);
+    let imported = State {
+        access: ImageAccess::TRANSFER_READ,
+        layout: ImageLayout::TransferSrcOptimal,
+        stages: PipelineStage::TRANSFER,
+    };
+    let exported = State {
+        access: BufferAccess::TRANSFER_WRITE,
+        layout: BufferLayout,
+        stages: PipelineStage::TRANSFER,
+    };
+    let sub_passes = vec![
+        PassBuilder::new(PassId(0), family)
+            .transfer_image_src(image)
+            .transfer_buffer_dst(temp)
+            .cost(2)
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(1), family)
+            .depends_on(PassId(0))
+            .transfer_buffer_src(temp)
+            .transfer_buffer_dst(output)
+            .build()
+            .unwrap(),
+    ];
+    let mut semaphore_id = 0;
+    let mut sub_chain = SubChainBuilder::new(sub_passes.clone(), &declared(&sub_passes))
+        .import_image(image, imported)
+        .export_buffer(output, exported)
+        .build(PassId(1), || {
+            semaphore_id += 1;
+            (semaphore_id, semaphore_id)
+        })
+        .unwrap();
+    assert_eq!(sub_chain.node().cost, 3);
+    assert_eq!(sub_chain.node().required_capability(), Some(Capability::Transfer));
+
+    // Parent produces the image before the node and consumes the buffer after it.
+    let parent_passes = vec![
+        PassBuilder::new(PassId(0), family)
+            .transfer_image_dst(image)
+            .build()
+            .unwrap(),
+        sub_chain.node().clone(),
+        PassBuilder::new(PassId(2), family)
+            .depends_on(PassId(1))
+            .vertex_buffer(output)
+            .build()
+            .unwrap(),
+    ];
+    let (parent, _) = sync_default(&parent_passes, &declared(&parent_passes));
+    sub_chain.fit(&parent, LayoutPolicy::Strict).unwrap();
+    check_legal_barriers(&sub_chain.chains().schedule);
+    if let Err(errors) = validate(sub_chain.chains(), &sub_passes) {
+        panic!("Validation failed: {:#?}", errors);
+    }
+
+    // Parent that provides the image in another layout.
+    let sampled = PassBuilder::new(PassId(1), family)
+        .image_use(image, ImageUse::Sampled(PipelineStage::FRAGMENT_SHADER))
+        .raw_buffer_state(output, exported)
+        .build()
+        .unwrap();
+    let provided = sampled.images[&image].state;
+    let passes = [parent_passes[0].clone(), sampled];
+    let (parent, _) = sync_default(&passes, &declared(&passes));
+    match sub_chain.fit(&parent, LayoutPolicy::Strict) {
+        Err(SubChainError::LayoutMismatch { id, states }) => {
+            assert_eq!(id, image);
+            assert_eq!(states, provided..imported);
+        }
+        result => panic!("Expected layout mismatch, got {:?}", result),
+    }
+    sub_chain.fit(&parent, LayoutPolicy::PreferGeneral).unwrap();
+    check_legal_barriers(&sub_chain.chains().schedule);
+    let sid = SubmissionId::new(QueueId::new(family, 0), 0);
+    let sync = sub_chain.chains().schedule[sid].sync();
+    let guard = if sync.frame_begin.images.contains_key(&image) {
+        &sync.frame_begin
+    } else {
+        &sync.acquire
+    };
+    let barrier = guard.pipeline_barrier().expect("Image isn't transitioned.");
+    assert_eq!(barrier.images[0].1.states.start, provided);
+}
+
+/// Synchronized schedule with ownership transfer survives JSON round-trip.
+/// Values are compared, as order of entries of hash maps isn't preserved.
+#[cfg(feature = "serde-1")]
+fn test_json_round_trip() {
+    let texture = Id::new(0);
+    let passes = vec![
+        PassBuilder::new(PassId(0), QueueFamilyId(1))
+            .raw_image_state(texture, State::<Image>::transfer_dst())
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(1), QueueFamilyId(0))
+            .raw_image_state(texture, State::sampled(PipelineStage::FRAGMENT_SHADER))
+            .build()
+            .unwrap(),
+    ];
+    let mut resources = Resources::new();
+    resources.add_image(
+        texture,
+        ResourceInfo {
+            sharing: SharingMode::Exclusive,
+            ..ResourceInfo::default()
+        },
+    );
+    let chains = collect(passes, &resources, |_| 1).unwrap();
+    let mut semaphore_id = 0;
+    let schedule = sync_with_options(&chains, SyncOptions::default(), || {
+        let id = semaphore_id;
+        semaphore_id += 1;
+        (id, id)
+    }).unwrap();
+
+    let json = serde_json::to_string(&schedule).unwrap();
+    let restored: Schedule<SyncData<usize, usize>> = serde_json::from_str(&json).unwrap();
+    assert_eq!(restored.queue_count(), schedule.queue_count());
+    assert_eq!(
+        serde_json::to_value(&restored).unwrap(),
+        serde_json::to_value(&schedule).unwrap(),
+        "Schedule changed in round-trip: {}",
+        json
+    );
+    let sid = SubmissionId::new(QueueId::new(QueueFamilyId(0), 0), 0);
+    let acquire = &restored.submission(sid).unwrap().sync().acquire;
+    assert_eq!(acquire.wait.len(), 1);
+    assert!(acquire.images[&texture].queues.is_some(), "Ownership transfer is lost.");
+}
+
+/// Captured inputs replay into the same schedule and synchronization as the recorded build.
+#[cfg(feature = "capture")]
+fn test_capture() {
+    let (buffer, attachment, texture) = (Id::new(0), Id::new(0), Id::new(1));
+    let clear = AttachmentOps::new(AttachmentLoadOp::Clear, AttachmentStoreOp::Store);
+    let passes = vec![
+        PassBuilder::new(PassId(0), QueueFamilyId(1))
+            .allow_family(QueueFamilyId(0))
+            .transfer_buffer_dst(buffer)
+            .transfer_image_dst(texture)
+            .transfer_region(
+                texture,
+                TransferRegion::whole(Extent {
+                    width: 64,
+                    height: 64,
+                    depth: 1,
+                }),
+            )
+            .name("upload")
+            .tag(7)
+            .build()
+            .unwrap(),
+        PassBuilder::new(PassId(1), QueueFamilyId(0))
+            .depends_on(PassId(0))
+            .render_pass()
+            .vertex_buffer(buffer)
+            .image_use(attachment, ImageUse::Attachment(clear))
+            .image_use(texture, ImageUse::Sampled(PipelineStage::FRAGMENT_SHADER))
+            .build()
+            .unwrap(),
+    ];
+    let mut resources = Resources::new();
+    resources.add_buffer(buffer, ResourceInfo::default());
+    resources.add_image(texture, ResourceInfo::default());
+    let families = [
+        FamilyInfo::new(QueueFamilyId(0), Capability::Graphics, 1),
+        FamilyInfo::new(QueueFamilyId(1), Capability::Transfer, 1),
+    ];
+    let options = SyncOptions::default().fences(FenceStrategy::None);
+
+    let chains = collect_with_families(passes.clone(), &resources, &families).unwrap();
+    let mut semaphores = 0..;
+    let expected = sync_with_options(&chains, options.clone(), || {
+        let semaphore = semaphores.next().unwrap();
+        (semaphore, semaphore)
+    }).unwrap();
+
+    let bytes = Capture::record(&passes, &resources, &families, &options).to_bytes();
+    let replayed = Capture::replay(&bytes).unwrap();
+    assert!(diff(&expected, &replayed.schedule).is_empty());
+    assert_eq!(fence_count(&replayed.schedule), 0);
+    assert_eq!(replayed.buffers.len(), chains.buffers.len());
+    assert_eq!(replayed.images.len(), chains.images.len());
+    let capture = Capture::from_bytes(&bytes).unwrap();
+    assert_eq!(capture.passes[0].meta.tag, 7);
+    assert_eq!(capture.passes[0].meta.name, None, "Names are not captured.");
+
+    let mut newer = capture.clone();
+    newer.version = CAPTURE_VERSION + 1;
+    match Capture::replay(&newer.to_bytes()) {
+        Err(CaptureError::Version { found, supported }) => {
+            assert_eq!((found, supported), (CAPTURE_VERSION + 1, CAPTURE_VERSION))
+        }
+        other => panic!("Unexpected replay of newer capture: {:?}", other.map(|_| ())),
+    }
+    match Capture::replay(b"not a capture") {
+        Err(CaptureError::Format(_)) => {}
+        other => panic!("Unexpected replay of invalid capture: {:?}", other.map(|_| ())),
+    }
+}
+
+/// Generated graphs are free of hazards.
+/// Synchronization derived for other states than accessed ones exposes hazards.
+#[cfg(feature = "test-utils")]
+fn test_random_graphs() {
+    let mut rng = Isaac64Rng::new_unseeded();
+    for _ in 0..200 {
+        let graph = RandomGraph::new(&mut rng, GraphParams::default());
+        if let Err(hazard) = graph.run() {
+            panic!("Hazard found: {}\n{:#?}", hazard, graph);
+        }
+    }
+
+    let buffer = Id::new(0);
+    let image = Id::new(0);
+    let passes = |stages, sample| {
+        let reader = PassBuilder::new(PassId(1), QueueFamilyId(0))
+            .depends_on(PassId(0))
+            .uniform_buffer(buffer, stages);
+        let reader = if sample {
+            reader.sample_image(image, stages)
+        } else {
+            reader.storage_image_read(image, stages)
+        };
+        vec![
+            PassBuilder::new(PassId(0), QueueFamilyId(0))
+                .transfer_buffer_dst(buffer)
+                .transfer_image_dst(image)
+                .build()
+                .unwrap(),
+            reader.build().unwrap(),
+        ]
+    };
+    let mut resources = Resources::new();
+    resources.add_buffer(buffer, ResourceInfo::default());
+    resources.add_image(image, ResourceInfo::default());
+
+    let synced = collect(passes(PipelineStage::FRAGMENT_SHADER, true), &resources, |_| 1).unwrap();
+    let verify = |stages, sample| {
+        let accessed = collect(passes(stages, sample), &resources, |_| 1).unwrap();
+        sim::verify(&Chains {
+            schedule: sync_with_options(&synced, SyncOptions::default(), || (0, 0)).unwrap(),
+            buffers: accessed.buffers,
+            images: accessed.images,
+            stencils: accessed.stencils,
+            parts: accessed.parts,
+            buffer_parts: accessed.buffer_parts,
+        })
+    };
+    if let Err(hazard) = verify(PipelineStage::FRAGMENT_SHADER, true) {
+        panic!("Hazard found: {}", hazard);
+    }
+    match verify(PipelineStage::VERTEX_SHADER, true) {
+        Err(Hazard::Buffer {
+            kind: HazardKind::ReadAfterWrite,
+            ..
+        }) => {}
+        other => panic!("Read at stages out of barrier scope is not found: {:?}", other),
+    }
+    match verify(PipelineStage::FRAGMENT_SHADER, false) {
+        Err(Hazard::Image {
+            kind: HazardKind::LayoutMismatch,
+            ..
+        }) => {}
+        other => panic!("Access in wrong layout is not found: {:?}", other),
+    }
+}
+
+fn main() {
+    let mut app = App::new("gfx-chains random tester")
+        .subcommand(
+            SubCommand::with_name("test")
+                .about("Tests a random chain, printing all results.")
+                .arg(Arg::with_name("SEED").help("optional fixed seed").index(1)),
+        )
+        .subcommand(SubCommand::with_name("bench").about("Benchmarks large random chains."))
+        .subcommand(
+            SubCommand::with_name("regressions").about("Tests fixed chains of reported issues."),
+        )
+        .subcommand(
+            SubCommand::with_name("fuzz").about("Tests random chains, to find panicking cases."),
+        );
+    let matches = app.clone().get_matches();
+
+    let mut os_rng = OsRng::new().unwrap();
+
+    if let Some(matches) = matches.subcommand_matches("test") {
+        match matches.value_of("SEED") {
+            Some(seed) => {
+                let seed = seed.parse::<u64>().expect("seed is not a number!");
+                test_run(seed, true, None);
+            }
+            None => {
+                test_run(os_rng.next_u64(), true, None);
+            }
+        }
+        return;
+    }
+    if matches.subcommand_matches("regressions").is_some() {
+        test_read_only_readers();
+        test_separate_aspects();
+        test_semaphore_fan_out();
+        test_pass_builder();
+        test_discard_after_sample();
+        test_write_after_read_barriers();
+        test_combined_pipeline_barrier();
+        test_image_groups();
+        test_ordering_hints();
+        test_capabilities();
+        test_link_views();
+        test_disjoint_layouts();
+        test_generate_mipmaps();
+        test_collect_errors();
+        test_duplicate_registration();
+        test_incompatible_states();
+        test_hoist_initial_transitions();
+        test_transfer_granularity();
+        test_pinned_families();
+        test_sync_diff();
+        test_image_uses();
+        test_layout_policy();
+        test_attachment_info();
+        test_present_attachment_info();
+        test_initial_state_attachment_info();
+        test_external_attachment_info();
+        test_resolve();
+        test_ping_pong_aliasing();
+        test_external_dependency();
+        test_device_group();
+        test_pass_meta();
+        test_dot_output();
+        test_id_map();
+        test_fence_strategy();
+        test_sparse_bind_between_passes();
+        test_indirect_across_queues();
+        test_external_import_export();
+        test_disjoint_ranges();
+        test_disjoint_buffer_ranges();
+        test_pass_culling();
+        test_queue_balancing();
+        test_state_validation();
+        test_compiled_graph();
+        test_chain_warnings();
+        test_offload_suggestions();
+        test_state_constructors();
+        test_truncate_sync();
+        test_critical_path();
+        test_export_without_consumer();
+        test_final_layout_only_image();
+        test_sub_chain();
+        test_minimal_wait_stages();
+        test_staging_ring();
+        test_transition_nodes();
+        test_feedback_loop();
+        #[cfg(feature = "serde-1")]
+        test_json_round_trip();
+        #[cfg(feature = "capture")]
+        test_capture();
+        #[cfg(feature = "test-utils")]
+        test_conditional_passes();
+        #[cfg(feature = "test-utils")]
+        test_random_graphs();
+        println!("All regressions passed.");
         return;
     }
     if let Some(_) = matches.subcommand_matches("bench") {
@@ -0,0 +1,260 @@
+//!
+//! This module provides analysis of resource lifetimes in the schedule.
+//! Resources with non-overlapping lifetimes may share memory.
+//!
+
+use alloc::vec::Vec;
+use collections::FnvHashMap;
+use core::cmp::{max, min};
+use core::ops::Range;
+
+use chain::Chain;
+use collect::Chains;
+use resource::{Buffer, Id, IdMap, Image, Resource};
+use schedule::{QueueId, Schedule, SubmissionId};
+
+/// Lifetime of the resource in the schedule.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Lifetime {
+    /// Resource is used outside of the schedule.
+    /// It is alive during whole schedule execution and can't be aliased.
+    Unbounded,
+
+    /// Resource is used only by submissions from the schedule.
+    Bounded {
+        /// Range of submit orders from first to last submission that uses the resource.
+        order: Range<usize>,
+
+        /// Range of submission indices that use the resource for each queue.
+        /// Sorted by queue id.
+        queues: Vec<(QueueId, Range<usize>)>,
+    },
+}
+
+impl Lifetime {
+    fn new<R, S>(chains: &[&Chain<R>], schedule: &Schedule<S>) -> Self
+    where
+        R: Resource,
+    {
+        if chains.iter().any(|chain| chain.external()) {
+            return Lifetime::Unbounded;
+        }
+
+        let mut queues: FnvHashMap<QueueId, Range<usize>> = FnvHashMap::default();
+        for link in chains.iter().flat_map(|chain| chain.links()) {
+            for (qid, queue) in link.queues() {
+                let range = queues.entry(qid).or_insert(queue.first..queue.last + 1);
+                range.start = min(range.start, queue.first);
+                range.end = max(range.end, queue.last + 1);
+            }
+        }
+        // Memory must stay bound from sparse binding to the end of the lifetime.
+        for bind in chains.iter().flat_map(|chain| chain.binds()) {
+            let (qid, index) = (bind.sid.queue(), bind.sid.index());
+            let range = queues.entry(qid).or_insert(index..index + 1);
+            range.start = min(range.start, index);
+            range.end = max(range.end, index + 1);
+        }
+
+        let mut queues: Vec<_> = queues.into_iter().collect();
+        queues.sort_by_key(|&(qid, _)| qid);
+
+        let submit_order = |qid, index| schedule[SubmissionId::new(qid, index)].submit_order();
+        let order = queues
+            .iter()
+            .map(|&(qid, ref range)| {
+                submit_order(qid, range.start)..submit_order(qid, range.end - 1) + 1
+            })
+            .fold(None, |acc: Option<Range<usize>>, order| match acc {
+                None => Some(order),
+                Some(acc) => Some(min(acc.start, order.start)..max(acc.end, order.end)),
+            })
+            .unwrap_or(0..0);
+
+        Lifetime::Bounded { order, queues }
+    }
+}
+
+/// Lifetimes of all resources in the schedule.
+/// Allows to check if two resources may share memory.
+///
+/// Commands from different queues are ordered only by semaphores.
+/// So lifetimes on different queues are considered non-overlapping only if
+/// there is a chain of semaphore signals and waits that orders them.
+/// Note that memory can be reused only after pipeline barrier that makes last accesses
+/// to previous resource available.
+/// It is up to user to record it before first access to the next resource.
+#[derive(Clone, Debug)]
+pub struct Lifetimes {
+    buffers: FnvHashMap<Id<Buffer>, Lifetime>,
+    images: FnvHashMap<Id<Image>, Lifetime>,
+
+    /// Cross-queue dependencies. Sorted by submission index.
+    /// Each dependency will be satisfied by semaphore.
+    edges: FnvHashMap<QueueId, Vec<(usize, SubmissionId)>>,
+}
+
+impl Lifetimes {
+    /// Calculate lifetimes of resources in the `Chains`.
+    pub fn new<S>(chains: &Chains<S>) -> Self {
+        let mut edges = FnvHashMap::default();
+        collect_edges(chains.buffers.values(), &mut edges);
+        collect_edges(chains.images.values(), &mut edges);
+        collect_edges(chains.stencils.values(), &mut edges);
+        collect_edges(chains.parts.values().flatten(), &mut edges);
+        collect_edges(chains.buffer_parts.values().flatten(), &mut edges);
+        for edges in edges.values_mut() {
+            edges.sort();
+            edges.dedup();
+        }
+
+        let mut images = lifetimes(&chains.images, &chains.stencils, &chains.schedule);
+        images.extend(chains.parts.iter().map(|(&id, parts)| {
+            let all: Vec<_> = parts.iter().collect();
+            (id, Lifetime::new(&all, &chains.schedule))
+        }));
+
+        let mut buffers = lifetimes(&chains.buffers, &IdMap::new(), &chains.schedule);
+        buffers.extend(chains.buffer_parts.iter().map(|(&id, parts)| {
+            let all: Vec<_> = parts.iter().collect();
+            (id, Lifetime::new(&all, &chains.schedule))
+        }));
+
+        Lifetimes {
+            buffers,
+            images,
+            edges,
+        }
+    }
+
+    /// Get lifetime of the buffer.
+    /// Returns `None` if buffer isn't used in the schedule.
+    pub fn buffer(&self, id: Id<Buffer>) -> Option<&Lifetime> {
+        self.buffers.get(&id)
+    }
+
+    /// Get lifetime of the image.
+    /// Returns `None` if image isn't used in the schedule.
+    pub fn image(&self, id: Id<Image>) -> Option<&Lifetime> {
+        self.images.get(&id)
+    }
+
+    /// Check if two buffers may share memory.
+    /// Returns `false` if any of them isn't used in the schedule.
+    pub fn aliasable_buffers(&self, a: Id<Buffer>, b: Id<Buffer>) -> bool {
+        a != b
+            && match (self.buffers.get(&a), self.buffers.get(&b)) {
+                (Some(a), Some(b)) => self.disjoint(a, b),
+                _ => false,
+            }
+    }
+
+    /// Check if two images may share memory.
+    /// Returns `false` if any of them isn't used in the schedule.
+    pub fn aliasable(&self, a: Id<Image>, b: Id<Image>) -> bool {
+        a != b
+            && match (self.images.get(&a), self.images.get(&b)) {
+                (Some(a), Some(b)) => self.disjoint(a, b),
+                _ => false,
+            }
+    }
+
+    /// Check if submission `a` is guaranteed to complete before submission `b` starts.
+    pub fn happens_before(&self, a: SubmissionId, b: SubmissionId) -> bool {
+        if a.queue() == b.queue() {
+            return a.index() < b.index();
+        }
+
+        // Lowest submission index reached on each queue.
+        let mut reached = FnvHashMap::default();
+        let mut stack = vec![a];
+        reached.insert(a.queue(), a.index());
+
+        while let Some(sid) = stack.pop() {
+            if reached[&sid.queue()] < sid.index() {
+                // Visited from earlier submission already.
+                continue;
+            }
+            let edges = match self.edges.get(&sid.queue()) {
+                Some(edges) => edges,
+                None => continue,
+            };
+            for &(_, next) in edges.iter().filter(|&&(index, _)| index >= sid.index()) {
+                if next.queue() == b.queue() && next.index() <= b.index() {
+                    return true;
+                }
+                let index = reached.entry(next.queue()).or_insert(next.index() + 1);
+                if next.index() < *index {
+                    *index = next.index();
+                    stack.push(next);
+                }
+            }
+        }
+
+        false
+    }
+
+    fn disjoint(&self, a: &Lifetime, b: &Lifetime) -> bool {
+        match (a, b) {
+            (Lifetime::Bounded { queues: a, .. }, Lifetime::Bounded { queues: b, .. }) => {
+                self.precedes(a, b) || self.precedes(b, a)
+            }
+            _ => false,
+        }
+    }
+
+    /// Check that all submissions from `a` happen before all submissions from `b`.
+    fn precedes(&self, a: &[(QueueId, Range<usize>)], b: &[(QueueId, Range<usize>)]) -> bool {
+        a.iter().all(|&(a_qid, ref a_range)| {
+            b.iter().all(|&(b_qid, ref b_range)| {
+                self.happens_before(
+                    SubmissionId::new(a_qid, a_range.end - 1),
+                    SubmissionId::new(b_qid, b_range.start),
+                )
+            })
+        })
+    }
+}
+
+/// Calculate lifetimes of resources.
+/// `parts` contains chains that track other parts of the same resources.
+fn lifetimes<R, S>(
+    chains: &IdMap<R, Chain<R>>,
+    parts: &IdMap<R, Chain<R>>,
+    schedule: &Schedule<S>,
+) -> FnvHashMap<Id<R>, Lifetime>
+where
+    R: Resource,
+{
+    chains
+        .iter()
+        .map(|(&id, chain)| {
+            let mut all = vec![chain];
+            all.extend(parts.get(&id));
+            (id, Lifetime::new(&all, schedule))
+        })
+        .collect()
+}
+
+pub(crate) fn collect_edges<'a, R, I>(
+    chains: I,
+    edges: &mut FnvHashMap<QueueId, Vec<(usize, SubmissionId)>>,
+) where
+    R: Resource + 'a,
+    I: IntoIterator<Item = &'a Chain<R>>,
+{
+    for chain in chains {
+        for pair in chain.links().windows(2) {
+            for (next_qid, next_queue) in pair[1].queues() {
+                for (prev_qid, prev_queue) in pair[0].queues() {
+                    if prev_qid != next_qid {
+                        edges.entry(prev_qid).or_default().push((
+                            prev_queue.last,
+                            SubmissionId::new(next_qid, next_queue.first),
+                        ));
+                    }
+                }
+            }
+        }
+    }
+}
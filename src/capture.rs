@@ -0,0 +1,146 @@
+//!
+//! This module provides capturing of inputs of chain building into a self-contained RON document
+//! and replaying them later, e.g. to reproduce a bug report in a test independently of the
+//! engine that declared the passes.
+//! Available with `capture` feature.
+//!
+
+use ron;
+use std::error::Error;
+use std::fmt::{self, Display};
+
+use collect::{collect_with_families, Chains, CollectError, FamilyInfo};
+use pass::Pass;
+use resource::Resources;
+use sync::{sync_chains, SyncData, SyncOptions};
+
+/// Version of the capture format written by `Capture::to_bytes`.
+/// Captures of this and all previous versions can be replayed.
+pub const CAPTURE_VERSION: u32 = 1;
+
+/// Everything required to reproduce building of `Chains`.
+///
+/// Names of passes are not captured, see `PassMeta`.
+/// Fields added in later versions of the format are defaulted when older captures are loaded.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Capture {
+    /// Version of the format the capture was recorded with.
+    pub version: u32,
+
+    /// Declared passes.
+    pub passes: Vec<Pass>,
+
+    /// Registered resources.
+    pub resources: Resources,
+
+    /// Queue families available for the passes.
+    pub families: Vec<FamilyInfo>,
+
+    /// Options of synchronization.
+    pub options: SyncOptions,
+}
+
+/// Error that can occur when a capture is loaded or replayed.
+#[derive(Clone, Debug)]
+pub enum CaptureError {
+    /// Capture is not a valid document.
+    Format(String),
+
+    /// Capture is recorded with newer version of the format.
+    Version {
+        /// Version of the capture.
+        found: u32,
+
+        /// Latest supported version.
+        supported: u32,
+    },
+
+    /// Captured passes failed to collect.
+    Collect(CollectError),
+}
+
+impl From<CollectError> for CaptureError {
+    fn from(error: CollectError) -> Self {
+        CaptureError::Collect(error)
+    }
+}
+
+impl Display for CaptureError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CaptureError::Format(ref error) => write!(fmt, "Invalid capture: {}", error),
+            CaptureError::Version { found, supported } => write!(
+                fmt,
+                "Capture version {} is newer than supported version {}",
+                found, supported
+            ),
+            CaptureError::Collect(ref error) => write!(fmt, "Capture failed to collect: {}", error),
+        }
+    }
+}
+
+impl Error for CaptureError {
+    fn description(&self) -> &str {
+        match *self {
+            CaptureError::Format(_) => "Invalid capture",
+            CaptureError::Version { .. } => "Unsupported capture version",
+            CaptureError::Collect(_) => "Capture failed to collect",
+        }
+    }
+}
+
+impl Capture {
+    /// Record inputs of `collect_with_families` followed by `sync_chains`.
+    pub fn record(
+        passes: &[Pass],
+        resources: &Resources,
+        families: &[FamilyInfo],
+        options: &SyncOptions,
+    ) -> Self {
+        Capture {
+            version: CAPTURE_VERSION,
+            passes: passes.to_vec(),
+            resources: resources.clone(),
+            families: families.to_vec(),
+            options: options.clone(),
+        }
+    }
+
+    /// Serialize the capture.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        ron::ser::to_string(self)
+            .expect("Capture is always serializable")
+            .into_bytes()
+    }
+
+    /// Load capture serialized with `to_bytes`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, CaptureError> {
+        let capture: Capture =
+            ron::de::from_bytes(bytes).map_err(|error| CaptureError::Format(error.to_string()))?;
+        if capture.version > CAPTURE_VERSION {
+            return Err(CaptureError::Version {
+                found: capture.version,
+                supported: CAPTURE_VERSION,
+            });
+        }
+        Ok(capture)
+    }
+
+    /// Build synchronized `Chains` from captured inputs.
+    /// Semaphores are numbered from 0 in order of creation and used as both signal and wait.
+    pub fn build(&self) -> Result<Chains<SyncData<usize, usize>>, CollectError> {
+        let chains = collect_with_families(self.passes.clone(), &self.resources, &self.families)?;
+        let mut semaphores = 0..;
+        sync_chains(chains, self.options.clone(), || {
+            let semaphore = semaphores.next().unwrap();
+            (semaphore, semaphore)
+        })
+    }
+
+    /// Load capture serialized with `to_bytes` and build synchronized `Chains` from it.
+    /// Chains, schedule and synchronization of the result are the same as of the build
+    /// the capture was recorded from.
+    pub fn replay(bytes: &[u8]) -> Result<Chains<SyncData<usize, usize>>, CaptureError> {
+        Ok(Capture::from_bytes(bytes)?.build()?)
+    }
+}
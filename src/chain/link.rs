@@ -1,19 +1,24 @@
-use std::iter::Enumerate;
-use std::slice::Iter as SliceIter;
+use alloc::vec::Vec;
+use core::iter::Enumerate;
+use core::ops::Range as StdRange;
+use core::slice::Iter as SliceIter;
 
 use hal::pso::PipelineStage;
 use hal::queue::QueueFamilyId;
 
-use resource::{Resource, State};
+use resource::{Range, Resource, State};
 use schedule::{QueueId, SubmissionId};
 
 /// State of the link associated with queue.
 /// Contains submissions range, combined access and stages bits by submissions from the range.
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize), serde(bound = ""))]
 pub(crate) struct LinkQueueState<R: Resource> {
     pub first: usize,
     pub last: usize,
+    #[cfg_attr(feature = "serde-1", serde(with = "::ser::repr"))]
     pub access: R::Access,
+    #[cfg_attr(feature = "serde-1", serde(with = "::ser::repr"))]
     pub stages: PipelineStage,
 }
 
@@ -42,12 +47,18 @@ where
 /// Those commands doesn't required to perform actions with all access types declared by the link.
 /// But performing actions with access types not declared by the link is prohibited.
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize), serde(bound = ""))]
 pub struct Link<R: Resource> {
+    #[cfg_attr(feature = "serde-1", serde(with = "::ser::repr"))]
     usage: R::Usage,
     state: State<R>,
+    #[cfg_attr(feature = "serde-1", serde(with = "::ser::repr"))]
+    range: Option<R::Range>,
     queue_count: usize,
     queues: Vec<Option<LinkQueueState<R>>>,
+    #[cfg_attr(feature = "serde-1", serde(with = "::ser::repr"))]
     family: QueueFamilyId,
+    discard: bool,
 }
 
 impl<R> Link<R>
@@ -60,14 +71,23 @@ where
     ///
     /// `state`     - state of the first submission.
     /// `sid`       - id of the first submission.
+    /// `range`     - sub-resource range accessed by the first submission.
+    ///               `None` for whole resource.
     ///
-    pub fn new(sid: SubmissionId, state: State<R>, usage: R::Usage) -> Self {
+    pub fn new(
+        sid: SubmissionId,
+        state: State<R>,
+        usage: R::Usage,
+        range: Option<R::Range>,
+    ) -> Self {
         let mut link = Link {
             state,
+            range,
             queue_count: 1,
             queues: Vec::new(),
             family: sid.family(),
             usage,
+            discard: false,
         };
         link.ensure_queue(sid.queue().index());
         link.queues[sid.queue().index()] = Some(LinkQueueState::new(sid, state));
@@ -100,6 +120,28 @@ where
         self.state
     }
 
+    /// Get sub-resource range accessed by submissions of the link.
+    /// Returns `None` if whole resource is accessed.
+    pub fn range(&self) -> Option<&R::Range> {
+        self.range.as_ref()
+    }
+
+    /// Override layout of the link.
+    /// Accesses of all submissions of the link must be allowed in the layout.
+    pub(crate) fn set_layout(&mut self, layout: R::Layout) {
+        self.state.layout = layout;
+    }
+
+    /// Check if submissions of the link overwrite content without reading it.
+    pub fn discard(&self) -> bool {
+        self.discard
+    }
+
+    /// Set if submissions of the link overwrite content without reading it.
+    pub fn set_discard(&mut self, discard: bool) {
+        self.discard = discard;
+    }
+
     /// Check if the link is associated with only one queue.
     pub fn single_queue(&self) -> bool {
         self.queue_count == 1
@@ -107,9 +149,27 @@ where
 
     /// Check if the given state and submission are compatible with link.
     /// If compatible then the submission can be associated with the link.
-    pub fn compatible(&self, sid: SubmissionId, state: State<R>) -> bool {
+    /// Submissions that access sub-resources not accessed by the link
+    /// are compatible even if access is exclusive, as long as the layout is the same.
+    /// Otherwise merged layout would be used for accesses of both ranges.
+    pub fn compatible(
+        &self,
+        sid: SubmissionId,
+        state: State<R>,
+        range: Option<&R::Range>,
+    ) -> bool {
         // If queue the same and states are compatible.
-        self.family == sid.family() && self.state.compatible(state)
+        self.family == sid.family()
+            && (self.state.compatible(state)
+                || (self.disjoint(range) && self.state.layout == state.layout))
+    }
+
+    /// Check if range doesn't overlap with range of the link.
+    fn disjoint(&self, range: Option<&R::Range>) -> bool {
+        match (self.range.as_ref(), range) {
+            (Some(left), Some(right)) => !left.overlaps(right),
+            _ => false,
+        }
     }
 
     /// Insert submission with specified state to the link.
@@ -123,11 +183,17 @@ where
     /// This function will panic if `state` and `sid` are not compatible.
     /// E.g. `Link::compatible` didn't returned `true` for the arguments.
     ///
-    pub fn insert_submission(&mut self, sid: SubmissionId, state: State<R>, usage: R::Usage) {
+    pub fn insert_submission(
+        &mut self,
+        sid: SubmissionId,
+        state: State<R>,
+        usage: R::Usage,
+        range: Option<R::Range>,
+    ) {
         assert_eq!(self.family, sid.family());
+        let state = self.state.merge(state);
         self.ensure_queue(sid.queue().index());
 
-        let state = self.state.merge(state);
         match &mut self.queues[sid.queue().index()] {
             &mut Some(ref mut queue) => {
                 queue.push(sid, state);
@@ -139,6 +205,10 @@ where
         }
         self.state = state;
         self.usage |= usage;
+        self.range = match (self.range.take(), range) {
+            (Some(left), Some(right)) => Some(left.union(&right)),
+            _ => None,
+        };
     }
 
     /// Check if ownership transfer is required between those links.
@@ -168,6 +238,14 @@ where
         debug_assert_eq!(self.family, qid.family());
         self.queues[qid.index()].as_ref().unwrap()
     }
+
+    /// Set indices of the first and the last submissions of the queue within the link.
+    pub(crate) fn set_span(&mut self, qid: QueueId, first: usize, last: usize) {
+        debug_assert_eq!(self.family, qid.family());
+        let queue = self.queues[qid.index()].as_mut().unwrap();
+        queue.first = first;
+        queue.last = last;
+    }
 }
 
 pub(crate) struct QueuesIter<'a, R: Resource + 'a> {
@@ -191,3 +269,101 @@ impl<'a, R: Resource + 'a> Iterator for QueuesIter<'a, R> {
         (0, self.iter.size_hint().1)
     }
 }
+
+/// Read-only view of the link at some index of the chain.
+#[derive(Debug)]
+pub struct LinkView<'a, R: Resource + 'a> {
+    index: usize,
+    link: &'a Link<R>,
+}
+
+impl<'a, R: Resource + 'a> Clone for LinkView<'a, R> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, R: Resource + 'a> Copy for LinkView<'a, R> {}
+
+impl<'a, R> LinkView<'a, R>
+where
+    R: Resource + 'a,
+{
+    pub(crate) fn new(index: usize, link: &'a Link<R>) -> Self {
+        LinkView { index, link }
+    }
+
+    /// Get index of the link in the chain.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Get the link.
+    pub fn link(&self) -> &'a Link<R> {
+        self.link
+    }
+
+    /// Get state of the resource while submissions of the link are executed.
+    /// Barrier before the first submissions of the link transitions the resource into it.
+    pub fn state(&self) -> State<R> {
+        self.link.state()
+    }
+
+    /// Get queue family that owns the resource at the link.
+    pub fn family(&self) -> QueueFamilyId {
+        self.link.family()
+    }
+
+    /// Get ids of queues that execute submissions of the link. Sorted.
+    pub fn queues(&self) -> Vec<QueueId> {
+        self.link.queues().map(|(qid, _)| qid).collect()
+    }
+
+    /// Get range of submissions of the link on each of its queues. Sorted by queue.
+    /// Range spans from the first to the last submission of the queue that access the resource.
+    /// Submissions within the range that don't access the resource
+    /// are executed while it stays in state of the link.
+    pub fn spans(&self) -> Vec<StdRange<SubmissionId>> {
+        self.link
+            .queues()
+            .map(|(qid, queue)| {
+                SubmissionId::new(qid, queue.first)..SubmissionId::new(qid, queue.last + 1)
+            })
+            .collect()
+    }
+
+    /// Check if submission lies within span of the link on its queue.
+    pub fn contains(&self, sid: SubmissionId) -> bool {
+        self.link.family() == sid.family()
+            && self
+                .link
+                .queues
+                .get(sid.queue().index())
+                .and_then(Option::as_ref)
+                .is_some_and(|queue| queue.first <= sid.index() && sid.index() <= queue.last)
+    }
+}
+
+/// Iterator over views of links of the chain in chain order.
+#[derive(Clone, Debug)]
+pub struct LinkViews<'a, R: Resource + 'a> {
+    pub(crate) iter: Enumerate<SliceIter<'a, Link<R>>>,
+}
+
+impl<'a, R: Resource + 'a> Iterator for LinkViews<'a, R> {
+    type Item = LinkView<'a, R>;
+
+    fn next(&mut self) -> Option<LinkView<'a, R>> {
+        self.iter.next().map(|(index, link)| LinkView::new(index, link))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a, R: Resource + 'a> DoubleEndedIterator for LinkViews<'a, R> {
+    fn next_back(&mut self) -> Option<LinkView<'a, R>> {
+        self.iter.next_back().map(|(index, link)| LinkView::new(index, link))
+    }
+}
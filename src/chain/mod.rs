@@ -7,17 +7,28 @@
 
 mod link;
 
-use fnv::FnvHashMap;
-use resource::{Buffer, Id, Image, Resource, Usage};
-use std::ops::BitOr;
+use alloc::vec::Vec;
+use core::ops::BitOr;
+use hal::image::{Access as ImageAccess, Layout as ImageLayout};
+use hal::pso::PipelineStage;
+use hal::queue::QueueFamilyId;
+use resource::{
+    Access, Buffer, Id, IdMap, Image, Layout, Resource, ResourceInfo, SharingMode, State, Usage,
+};
+use schedule::{QueueId, SubmissionId};
 
-pub use self::link::Link;
+pub use self::link::{Link, LinkView, LinkViews};
 
 /// This type corresponds to resource category.
 /// All resources from the same category must be accessed as permitted by links of the chain.
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize), serde(bound = ""))]
 pub struct Chain<R: Resource> {
     links: Vec<Link<R>>,
+    binds: Vec<Bind>,
+    info: ResourceInfo<R>,
+    #[cfg_attr(feature = "serde-1", serde(with = "::ser::repr"))]
+    range: Option<R::Range>,
 }
 
 impl<R> Chain<R>
@@ -29,9 +40,76 @@ where
         &self.links
     }
 
+    /// Get iterator over read-only views of links in chain order.
+    pub fn link_views(&self) -> LinkViews<'_, R> {
+        LinkViews {
+            iter: self.links.iter().enumerate(),
+        }
+    }
+
+    /// Get link the submission lies within.
+    /// Resource is in state of this link while the submission is executed.
+    /// Returns `None` if no link spans the submission on its queue.
+    pub fn link_of(&self, sid: SubmissionId) -> Option<LinkView<'_, R>> {
+        self.link_views().find(|view| view.contains(sid))
+    }
+
+    /// Get link preceding the one the submission lies within.
+    /// Resource is transitioned from state of this link into the state of the submission's one.
+    /// Returns `None` if no link spans the submission or it is spanned by the first link.
+    pub fn link_before(&self, sid: SubmissionId) -> Option<LinkView<'_, R>> {
+        let index = self.link_of(sid)?.index();
+        if index == 0 {
+            None
+        } else {
+            Some(LinkView::new(index - 1, self.link(index - 1)))
+        }
+    }
+
     /// Create new empty `Chain`
     pub fn new() -> Self {
-        Chain { links: Vec::new() }
+        Chain {
+            links: Vec::new(),
+            binds: Vec::new(),
+            info: ResourceInfo::default(),
+            range: None,
+        }
+    }
+
+    /// Get sub-resource range tracked by the chain.
+    /// `None` if chain tracks whole resource.
+    /// Barriers that change layout are applied to this range.
+    pub fn range(&self) -> Option<&R::Range> {
+        self.range.as_ref()
+    }
+
+    /// Set sub-resource range tracked by the chain.
+    /// Chains that track separate parts of the same resource (like depth and stencil aspects)
+    /// must have disjoint ranges.
+    pub fn set_range(&mut self, range: Option<R::Range>) {
+        self.range = range;
+    }
+
+    /// Get properties of the resource.
+    pub fn info(&self) -> &ResourceInfo<R> {
+        &self.info
+    }
+
+    /// Set properties of the resource.
+    pub fn set_info(&mut self, info: ResourceInfo<R>) {
+        self.info = info;
+    }
+
+    /// Get sharing mode of the resource.
+    /// Resources with `SharingMode::Concurrent` don't require ownership transfers.
+    pub fn sharing(&self) -> SharingMode {
+        self.info.sharing
+    }
+
+    /// Check if resource is used outside of the schedule.
+    /// Resources imported from or exported to external families are.
+    pub fn external(&self) -> bool {
+        self.info.external || self.info.import.is_some() || self.info.export.is_some()
     }
 
     /// Get links slice
@@ -45,6 +123,30 @@ where
         self.links.last_mut().unwrap()
     }
 
+    /// Get sparse binding operations in execution order.
+    pub fn binds(&self) -> &[Bind] {
+        &self.binds
+    }
+
+    /// Get mutable sparse binding operations.
+    pub(crate) fn binds_mut(&mut self) -> &mut [Bind] {
+        &mut self.binds
+    }
+
+    /// Add sparse binding operation performed after all current links.
+    /// Next link added to the chain is ordered after it.
+    pub fn add_bind(&mut self, sid: SubmissionId) {
+        let link = self.links.len();
+        self.binds.push(Bind { sid, link });
+    }
+
+    /// Check if sparse binding operation was added after the last link.
+    pub fn bound(&self) -> bool {
+        self.binds
+            .last()
+            .is_some_and(|bind| bind.link == self.links.len())
+    }
+
     /// Get link by index.
     pub fn link(&self, index: usize) -> &Link<R> {
         &self.links[index]
@@ -79,8 +181,80 @@ where
         self.link_mut(index)
     }
 
-    /// Get total usage.
-    pub fn usage(&self) -> R::Usage {
+    /// Check if link at `index` discards previous content of the resource.
+    /// Link discards only if it overwrites the whole range tracked by the chain.
+    pub fn discards(&self, index: usize) -> bool {
+        let link = self.link(index);
+        link.discard() && link.range() == self.range()
+    }
+
+    /// Get state of the resource before schedule execution.
+    /// The first link is transitioned from this state.
+    /// Imported resource is owned by external family in this state.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if chain has no links.
+    pub fn initial_state(&self) -> Boundary<R> {
+        let link = &self.links[0];
+        if let Some(import) = self.info.import {
+            return Boundary::external(import.family, import.state);
+        }
+        let state = match (self.info.present, R::Layout::present()) {
+            (true, Some(_)) => State {
+                access: R::Access::none(),
+                layout: R::Layout::discard_content(),
+                stages: link.state().stages,
+            },
+            _ => match self.info.initial_state {
+                Some(state) => state,
+                None if self.info.host_write_before => State {
+                    access: R::Access::host_write(),
+                    stages: PipelineStage::HOST,
+                    ..link.state()
+                },
+                None => link.state(),
+            },
+        };
+        Boundary::new(link, self.sharing(), state)
+    }
+
+    /// Get state of the resource after schedule execution.
+    /// The last link is transitioned to this state.
+    /// Exported resource is owned by external family in this state.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if chain has no links.
+    pub fn final_state(&self) -> Boundary<R> {
+        let link = self.links.last().expect("Chain has no links");
+        if let Some(export) = self.info.export {
+            let state = State {
+                access: R::Access::none(),
+                layout: export.state.layout,
+                stages: PipelineStage::BOTTOM_OF_PIPE,
+            };
+            return Boundary::external(export.family, state);
+        }
+        let state = match (self.info.present, R::Layout::present()) {
+            (true, Some(layout)) => State {
+                access: R::Access::none(),
+                layout,
+                stages: PipelineStage::BOTTOM_OF_PIPE,
+            },
+            _ if self.info.host_read_after => State {
+                access: R::Access::host_read(),
+                stages: PipelineStage::HOST,
+                ..link.state()
+            },
+            _ => link.state(),
+        };
+        Boundary::new(link, self.sharing(), state)
+    }
+
+    /// Get union of usage of all links.
+    /// Resource must be created with this usage to be used by all passes.
+    pub fn total_usage(&self) -> R::Usage {
         self.links
             .iter()
             .map(Link::usage)
@@ -88,8 +262,163 @@ where
     }
 }
 
+impl Chain<Image> {
+    /// Get information required to use the image as render pass attachment
+    /// in submission associated with link at `index`.
+    pub fn attachment_info(&self, index: usize, sid: SubmissionId) -> AttachmentInfo {
+        let link = self.link(index);
+        let state = link.state();
+
+        // Later submissions of the same link observe the content too.
+        let shared = link
+            .queues()
+            .any(|(qid, queue)| qid != sid.queue() || queue.last > sid.index());
+
+        let load = !self.discards(index)
+            && (self.content_before()
+                || self.links[..index]
+                    .iter()
+                    .any(|link| link.state().access.is_write()));
+        let store = shared
+            || (index + 1 == self.links.len() && self.content_after())
+            || self.links[index + 1..]
+                .iter()
+                .any(|link| link.state().access.is_read());
+
+        let initial_layout = if load {
+            state.layout
+        } else {
+            ImageLayout::Undefined
+        };
+
+        AttachmentInfo {
+            initial_layout,
+            final_layout: state.layout,
+            load,
+            store,
+            resolve_source: None,
+        }
+    }
+
+    /// Check if the image has content before the first link.
+    /// That is unless the first link is transitioned from `Undefined` layout,
+    /// like presented images are. Content in known initial state or imported from
+    /// external family is kept. Otherwise first link reading the image,
+    /// like blending into color attachment, relies on it.
+    fn content_before(&self) -> bool {
+        self.initial_state().state.layout != ImageLayout::Undefined
+            && (self.info.import.is_some()
+                || self.info.initial_state.is_some()
+                || self.info.host_write_before
+                || self.links[0].state().access.is_read())
+    }
+
+    /// Check if content left by the last link is observed after schedule execution.
+    /// Exported image is observed by external family.
+    /// Instance of per-frame image keeps it until the frame that uses the instance again.
+    fn content_after(&self) -> bool {
+        self.info.present
+            || self.info.export.is_some()
+            || self.info.host_read_after
+            || self.info.per_frame
+    }
+}
+
+/// State of the resource at the start or the end of the schedule.
+#[derive(Clone, Debug)]
+pub struct Boundary<R: Resource> {
+    /// State of the resource.
+    pub state: State<R>,
+
+    /// Family that owns the resource.
+    /// `None` if resource is shared concurrently and ownership is not tracked.
+    pub owner: Option<QueueFamilyId>,
+
+    /// Queues that access the resource first (or last).
+    /// Empty if resource is owned by external family.
+    pub queues: Vec<QueueId>,
+}
+
+impl<R> Boundary<R>
+where
+    R: Resource,
+{
+    fn new(link: &Link<R>, sharing: SharingMode, state: State<R>) -> Self {
+        Boundary {
+            state,
+            owner: match sharing {
+                SharingMode::Exclusive => Some(link.family()),
+                SharingMode::Concurrent => None,
+            },
+            queues: link.queues().map(|(qid, _)| qid).collect(),
+        }
+    }
+
+    fn external(family: QueueFamilyId, state: State<R>) -> Self {
+        Boundary {
+            state,
+            owner: Some(family),
+            queues: Vec::new(),
+        }
+    }
+}
+
+/// Sparse binding operation performed on the resource.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize))]
+pub struct Bind {
+    /// Submission that performs binding.
+    pub sid: SubmissionId,
+
+    /// Index of the link that is ordered after binding.
+    /// Equals to number of links if binding is performed after all of them.
+    pub link: usize,
+}
+
+/// Information required to use image as render pass attachment.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AttachmentInfo {
+    /// Layout the image is guaranteed to be in when submission starts.
+    /// `Undefined` if the image has no content to load.
+    pub initial_layout: ImageLayout,
+
+    /// Layout the image must be in when submission ends.
+    /// Barriers after the submission transition it to the final state of the chain,
+    /// like `Present` layout of presented image.
+    /// See `walk::SubmissionInfo::as_external_dependency` to perform those by the render pass.
+    pub final_layout: ImageLayout,
+
+    /// Content was written by earlier submissions or before the schedule and must be loaded.
+    pub load: bool,
+
+    /// Content is observed by later submissions or after the schedule and must be stored.
+    pub store: bool,
+
+    /// Multisampled image resolved into this one by the render pass of the submission.
+    /// Then the image should be a resolve attachment. Only set by `Chains::attachment`
+    /// and `Chains::attachments`.
+    pub resolve_source: Option<Id<Image>>,
+}
+
+/// Accesses of images used as render pass attachments.
+pub(crate) fn attachment_access() -> ImageAccess {
+    ImageAccess::INPUT_ATTACHMENT_READ
+        | ImageAccess::COLOR_ATTACHMENT_READ
+        | ImageAccess::COLOR_ATTACHMENT_WRITE
+        | ImageAccess::DEPTH_STENCIL_ATTACHMENT_READ
+        | ImageAccess::DEPTH_STENCIL_ATTACHMENT_WRITE
+}
+
 /// Type alias for map of chains by id for buffers.
-pub type BufferChains = FnvHashMap<Id<Buffer>, Chain<Buffer>>;
+pub type BufferChains = IdMap<Buffer, Chain<Buffer>>;
 
 /// Type alias for map of chains by id for images.
-pub type ImageChains = FnvHashMap<Id<Image>, Chain<Image>>;
+pub type ImageChains = IdMap<Image, Chain<Image>>;
+
+/// Type alias for map of chains of buffer parts by id.
+/// Each chain tracks one of disjoint ranges of the buffer.
+pub type BufferPartChains = IdMap<Buffer, Vec<Chain<Buffer>>>;
+
+/// Type alias for map of chains of image parts by id.
+/// Each chain tracks one of disjoint ranges of the image.
+pub type ImagePartChains = IdMap<Image, Vec<Chain<Image>>>;
@@ -4,26 +4,48 @@
 //! `Chains` can be filled automatically by `schedule` function.
 //!
 
-use fnv::FnvHashMap;
+use alloc::vec::Vec;
+use collections::hash_map::Entry;
+use collections::{FnvHashMap, FnvHashSet};
+use core::cmp::{max, Reverse};
+use core::fmt::{self, Display};
+use core::hash::Hash;
+use core::ops::Range;
+use hal::buffer::Access as BufferAccess;
+use hal::format::Aspects;
+use hal::image::{Access as ImageAccess, Extent, SubresourceRange};
+use hal::pso::PipelineStage;
 use hal::queue::QueueFamilyId;
-use std::cmp::max;
-use std::hash::Hash;
-use std::ops::Range;
+#[cfg(feature = "std")]
+use std::error::Error;
 
-use chain::{BufferChains, Chain, ImageChains, Link};
-use pass::{Pass, PassId, StateUsage};
-use resource::{Buffer, Image, Resource, State};
+use chain::{
+    attachment_access, AttachmentInfo, Boundary, BufferChains, BufferPartChains, Chain,
+    ImageChains, ImagePartChains, Link,
+};
+use offload::offload_suggestions;
+use pass::{
+    merge_overlapping, Capability, OrderingHint, Pass, PassId, PassKind, PassMeta, RangeConflict,
+    StateUsage,
+};
+use resource::{
+    Buffer, BufferAlignments, Image, LayoutPolicy, MergeError, Range as ResourceRange, Resource,
+    ResourceGroup, ResourceInfo, Resources, State, Usage,
+};
 
-use resource::Id;
+use resource::{Id, IdMap};
 use schedule::{Queue, QueueId, Schedule, Submission, SubmissionId};
+use warning::{chain_warnings, declaration_warnings, ChainWarning};
 use Pick;
 
 /// Placeholder for synchronization type.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize))]
 pub struct Unsynchronized;
 
 /// Result of pass scheduler.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize))]
 pub struct Chains<S = Unsynchronized> {
     /// Contains submissions for passes spread among queue schedule.
     pub schedule: Schedule<S>,
@@ -32,34 +54,518 @@ pub struct Chains<S = Unsynchronized> {
     pub buffers: BufferChains,
 
     /// Contains all image chains.
+    /// For images which depth and stencil aspects are used separately
+    /// this chain tracks depth aspect only.
     pub images: ImageChains,
+
+    /// Contains chains of stencil aspect for images
+    /// which depth and stencil aspects are used separately.
+    pub stencils: ImageChains,
+
+    /// Contains chains of parts for images that some pass accesses in several disjoint ranges.
+    /// Each chain tracks one part and has its range set. Ranges of the parts are disjoint.
+    /// Those images have no chains in `images` and `stencils`.
+    pub parts: ImagePartChains,
+
+    /// Contains chains of parts for buffers that passes access only in disjoint ranges.
+    /// Each chain tracks one part and has its range set. Ranges of the parts are disjoint.
+    /// Those buffers have no chains in `buffers`.
+    pub buffer_parts: BufferPartChains,
+}
+
+/// Error that can occur during collection of `Chains`.
+#[derive(Clone, Debug)]
+pub enum CollectError {
+    /// Pass declared buffer state that can't be merged with state of the link.
+    IncompatibleBufferStates {
+        /// Id of the buffer.
+        id: Id<Buffer>,
+        /// Id of the pass.
+        pass: PassId,
+        /// States that can't be merged.
+        error: MergeError<Buffer>,
+    },
+
+    /// Pass declared image state that can't be merged with state of the link.
+    IncompatibleImageStates {
+        /// Id of the image.
+        id: Id<Image>,
+        /// Id of the pass.
+        pass: PassId,
+        /// States that can't be merged.
+        error: MergeError<Image>,
+    },
+
+    /// Pass declared buffer state with access types none of its stages performs.
+    /// See `State::unsupported_access`.
+    /// Reported as `ChainWarning` instead with `lenient-states` feature.
+    InvalidBufferStateDeclaration {
+        /// Id of the buffer.
+        id: Id<Buffer>,
+        /// Id of the pass.
+        pass: PassId,
+        /// Access types no declared stage performs.
+        access: BufferAccess,
+        /// Declared stages.
+        stages: PipelineStage,
+    },
+
+    /// Pass declared image state with access types none of its stages performs.
+    /// See `State::unsupported_access`.
+    /// Reported as `ChainWarning` instead with `lenient-states` feature.
+    InvalidImageStateDeclaration {
+        /// Id of the image.
+        id: Id<Image>,
+        /// Id of the pass.
+        pass: PassId,
+        /// Access types no declared stage performs.
+        access: ImageAccess,
+        /// Declared stages.
+        stages: PipelineStage,
+    },
+
+    /// Pass declared image in ranges that overlap or are tracked by the same part
+    /// with states that can't be merged.
+    IncompatibleImageRanges {
+        /// Id of the image.
+        id: Id<Image>,
+        /// Id of the pass.
+        pass: PassId,
+        /// Ranges that can't be tracked together.
+        ranges: (SubresourceRange, SubresourceRange),
+        /// States that can't be merged.
+        error: MergeError<Image>,
+    },
+
+    /// Pass declared image in state that would share the link with feedback loop
+    /// while one of them is exclusive. Like another pass writing disjoint range of the image
+    /// during the pass reading it as input attachment.
+    /// Self-dependency of the feedback loop doesn't order accesses of other passes.
+    /// See `ImageUse::FeedbackLoop`.
+    FeedbackLoopConflict {
+        /// Id of the image.
+        id: Id<Image>,
+        /// Id of the pass.
+        pass: PassId,
+        /// Declared state.
+        state: State<Image>,
+    },
+
+    /// Pass declared range of the image that isn't covered by its parts.
+    /// Image accessed in several disjoint ranges by some pass is tracked only
+    /// in ranges declared that way.
+    UncoveredImageRange {
+        /// Id of the image.
+        id: Id<Image>,
+        /// Id of the pass.
+        pass: PassId,
+        /// Declared range.
+        range: SubresourceRange,
+    },
+
+    /// Pass declared range of the buffer that violates alignment required for its usage.
+    MisalignedBufferRange {
+        /// Id of the buffer.
+        id: Id<Buffer>,
+        /// Id of the pass.
+        pass: PassId,
+        /// Declared range.
+        range: Range<u64>,
+        /// Violated alignment.
+        alignment: u64,
+    },
+
+    /// Pass declared member of the image group differently from other members.
+    /// Or declared it in several disjoint ranges.
+    SplitImageGroup {
+        /// Id of the member.
+        id: Id<Image>,
+        /// Id of the first member of the group.
+        group: Id<Image>,
+        /// Id of the pass.
+        pass: PassId,
+    },
+
+    /// Member of the image group is tracked by chain different from chains of other members.
+    /// Like when it has different initial state or is presented.
+    DivergedImageGroup {
+        /// Id of the member.
+        id: Id<Image>,
+        /// Id of the first member of the group.
+        group: Id<Image>,
+    },
+
+    /// Pass is pinned to the family that has no queues.
+    NoQueues {
+        /// Id of the pass.
+        pass: PassId,
+        /// Id of the family.
+        family: QueueFamilyId,
+    },
+
+    /// Pass is pinned to the queue that doesn't exist.
+    QueueOutOfRange {
+        /// Id of the pass.
+        pass: PassId,
+        /// Id of the family.
+        family: QueueFamilyId,
+        /// Index of the queue.
+        queue: usize,
+    },
+
+    /// Capability specified for the pass doesn't support stage of declared state.
+    InsufficientCapability {
+        /// Id of the pass.
+        pass: PassId,
+        /// Capability specified for the pass.
+        capability: Capability,
+        /// Declared stage the capability doesn't support.
+        stage: PipelineStage,
+    },
+
+    /// None of the families the pass may execute on has required capability.
+    IncapableFamilies {
+        /// Id of the pass.
+        pass: PassId,
+        /// Id of the family.
+        family: QueueFamilyId,
+        /// Capability required by the pass.
+        capability: Capability,
+    },
+
+    /// Regions of the image transferred by the pass don't fit transfer granularity
+    /// of any family the pass may execute on and which has required capability.
+    MisalignedImageTransfer {
+        /// Id of the image.
+        id: Id<Image>,
+        /// Id of the pass.
+        pass: PassId,
+        /// Id of the family.
+        family: QueueFamilyId,
+    },
+
+    /// Several passes have the same id.
+    DuplicatePass {
+        /// Id of the passes.
+        pass: PassId,
+    },
+
+    /// Pass depends on the pass that isn't collected.
+    UnknownDependency {
+        /// Id of the pass.
+        pass: PassId,
+        /// Id of the dependency.
+        dependency: PassId,
+    },
+
+    /// Passes depend on each other, so none of them can be scheduled first.
+    /// Each pass depends on the next one and the last depends on the first.
+    DependencyCycle {
+        /// Ids of passes of the cycle.
+        passes: Vec<PassId>,
+    },
+
+    /// Pass declared buffer that isn't registered in `Resources`.
+    UnknownBuffer {
+        /// Id of the buffer.
+        id: Id<Buffer>,
+        /// Id of the pass.
+        pass: PassId,
+    },
+
+    /// Pass declared image that isn't registered in `Resources`.
+    UnknownImage {
+        /// Id of the image.
+        id: Id<Image>,
+        /// Id of the pass.
+        pass: PassId,
+    },
+
+    /// Link of the chains being synchronized refers to submission that isn't in the schedule.
+    UnknownSubmission {
+        /// Id of the submission.
+        sid: SubmissionId,
+    },
+
+    /// Transition nodes are requested from synchronization that can't move links to their ids.
+    /// See `SyncOptions::standalone_transition_nodes`.
+    UnsupportedTransitionNodes,
+}
+
+impl From<(Id<Buffer>, PassId, MergeError<Buffer>)> for CollectError {
+    fn from((id, pass, error): (Id<Buffer>, PassId, MergeError<Buffer>)) -> Self {
+        CollectError::IncompatibleBufferStates { id, pass, error }
+    }
+}
+
+impl From<(Id<Image>, PassId, MergeError<Image>)> for CollectError {
+    fn from((id, pass, error): (Id<Image>, PassId, MergeError<Image>)) -> Self {
+        CollectError::IncompatibleImageStates { id, pass, error }
+    }
+}
+
+impl Display for CollectError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CollectError::IncompatibleBufferStates {
+                id,
+                pass,
+                ref error,
+            } => write!(fmt, "Buffer {:?} in pass {:?}: {}", id, pass, error),
+            CollectError::IncompatibleImageStates {
+                id,
+                pass,
+                ref error,
+            } => write!(fmt, "Image {:?} in pass {:?}: {}", id, pass, error),
+            CollectError::InvalidBufferStateDeclaration {
+                id,
+                pass,
+                access,
+                stages,
+            } => write!(
+                fmt,
+                "Buffer {:?} in pass {:?} has access {:?} not performed at stages {:?}",
+                id, pass, access, stages
+            ),
+            CollectError::InvalidImageStateDeclaration {
+                id,
+                pass,
+                access,
+                stages,
+            } => write!(
+                fmt,
+                "Image {:?} in pass {:?} has access {:?} not performed at stages {:?}",
+                id, pass, access, stages
+            ),
+            CollectError::IncompatibleImageRanges {
+                id,
+                pass,
+                ranges: (ref left, ref right),
+                ref error,
+            } => write!(
+                fmt,
+                "Image {:?} in pass {:?} has ranges {:?} and {:?} \
+                 that can't be tracked together: {}",
+                id, pass, left, right, error
+            ),
+            CollectError::FeedbackLoopConflict { id, pass, state } => write!(
+                fmt,
+                "Image {:?} in pass {:?} has state {:?} that conflicts with feedback loop \
+                 accessing the image simultaneously",
+                id, pass, state
+            ),
+            CollectError::UncoveredImageRange {
+                id,
+                pass,
+                ref range,
+            } => write!(
+                fmt,
+                "Image {:?} in pass {:?} has range {:?} not covered by ranges of its parts",
+                id, pass, range
+            ),
+            CollectError::MisalignedBufferRange {
+                id,
+                pass,
+                ref range,
+                alignment,
+            } => write!(
+                fmt,
+                "Buffer {:?} in pass {:?} has range {:?} not aligned to {}",
+                id, pass, range, alignment
+            ),
+            CollectError::SplitImageGroup { id, group, pass } => write!(
+                fmt,
+                "Image {:?} of group {:?} is declared by pass {:?} differently from other members",
+                id, group, pass
+            ),
+            CollectError::DivergedImageGroup { id, group } => write!(
+                fmt,
+                "Image {:?} of group {:?} is tracked differently from other members",
+                id, group
+            ),
+            CollectError::NoQueues { pass, family } => write!(
+                fmt,
+                "Pass {:?} is pinned to family {:?} which has no queues",
+                pass, family
+            ),
+            CollectError::QueueOutOfRange {
+                pass,
+                family,
+                queue,
+            } => write!(
+                fmt,
+                "Pass {:?} is pinned to queue {} of family {:?} which doesn't exist",
+                pass, queue, family
+            ),
+            CollectError::InsufficientCapability {
+                pass,
+                capability,
+                stage,
+            } => write!(
+                fmt,
+                "Capability {:?} of pass {:?} doesn't support declared stage {:?}",
+                capability, pass, stage
+            ),
+            CollectError::IncapableFamilies {
+                pass,
+                family,
+                capability,
+            } => write!(
+                fmt,
+                "Pass {:?} requires {:?} which family {:?} and its alternatives lack",
+                pass, capability, family
+            ),
+            CollectError::MisalignedImageTransfer { id, pass, family } => write!(
+                fmt,
+                "Image {:?} in pass {:?} has transfer regions not aligned to granularity \
+                 of family {:?} and its capable alternatives",
+                id, pass, family
+            ),
+            CollectError::DuplicatePass { pass } => {
+                write!(fmt, "Several passes have id {:?}", pass)
+            }
+            CollectError::UnknownDependency { pass, dependency } => write!(
+                fmt,
+                "Pass {:?} depends on pass {:?} which isn't collected",
+                pass, dependency
+            ),
+            CollectError::DependencyCycle { ref passes } => {
+                write!(fmt, "Passes {:?} depend on each other in a cycle", passes)
+            }
+            CollectError::UnknownBuffer { id, pass } => {
+                write!(fmt, "Buffer {:?} in pass {:?} isn't registered", id, pass)
+            }
+            CollectError::UnknownImage { id, pass } => {
+                write!(fmt, "Image {:?} in pass {:?} isn't registered", id, pass)
+            }
+            CollectError::UnknownSubmission { sid } => {
+                write!(fmt, "Submission {:?} referred by chains isn't scheduled", sid)
+            }
+            CollectError::UnsupportedTransitionNodes => {
+                write!(fmt, "Transition nodes are supported only by `sync_chains`")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for CollectError {
+    fn description(&self) -> &str {
+        match *self {
+            CollectError::IncompatibleBufferStates { .. } => "Incompatible buffer states",
+            CollectError::IncompatibleImageStates { .. } => "Incompatible image states",
+            CollectError::InvalidBufferStateDeclaration { .. } => "Invalid buffer state",
+            CollectError::InvalidImageStateDeclaration { .. } => "Invalid image state",
+            CollectError::IncompatibleImageRanges { .. } => "Incompatible image ranges",
+            CollectError::FeedbackLoopConflict { .. } => "Feedback loop conflict",
+            CollectError::UncoveredImageRange { .. } => "Image range not covered by parts",
+            CollectError::MisalignedBufferRange { .. } => "Misaligned buffer range",
+            CollectError::SplitImageGroup { .. } => "Image group split by pass",
+            CollectError::DivergedImageGroup { .. } => "Diverged image group",
+            CollectError::NoQueues { .. } => "Pinned family has no queues",
+            CollectError::QueueOutOfRange { .. } => "Pinned queue out of range",
+            CollectError::InsufficientCapability { .. } => "Insufficient pass capability",
+            CollectError::IncapableFamilies { .. } => "No family has required capability",
+            CollectError::MisalignedImageTransfer { .. } => "Misaligned image transfer",
+            CollectError::DuplicatePass { .. } => "Duplicate pass id",
+            CollectError::UnknownDependency { .. } => "Unknown dependency",
+            CollectError::DependencyCycle { .. } => "Dependency cycle",
+            CollectError::UnknownBuffer { .. } => "Unknown buffer",
+            CollectError::UnknownImage { .. } => "Unknown image",
+            CollectError::UnknownSubmission { .. } => "Unknown submission",
+            CollectError::UnsupportedTransitionNodes => "Unsupported transition nodes",
+        }
+    }
+}
+
+impl<S> Chains<S> {
+    /// Get attachment information for image used by submission.
+    /// Returns `None` if submission doesn't use the image.
+    /// Images tracked in parts are not supported.
+    pub fn attachment(&self, sid: SubmissionId, id: Id<Image>) -> Option<AttachmentInfo> {
+        let submission = self.schedule.submission(sid)?;
+        let mut info = match Pick::<Image>::pick(submission).get(&id) {
+            Some(&index) => self.images[&id].attachment_info(index, sid),
+            None => {
+                let index = *submission.stencils_map().get(&id)?;
+                self.stencils[&id].attachment_info(index, sid)
+            }
+        };
+        info.resolve_source = submission.resolve_source(id);
+        Some(info)
+    }
+
+    /// Get attachment information for all images used by submission as attachments.
+    /// Images tracked in parts are skipped.
+    pub fn attachments(&self, sid: SubmissionId) -> Vec<(Id<Image>, AttachmentInfo)> {
+        let attachment_access = attachment_access();
+        let mut attachments: Vec<_> = self
+            .schedule
+            .submission(sid)
+            .into_iter()
+            .flat_map(|submission| {
+                let images = submission
+                    .images()
+                    .map(move |(&id, &index)| (submission, id, index, false));
+                let stencils = submission
+                    .stencils()
+                    .map(move |(&id, &index)| (submission, id, index, true));
+                images.chain(stencils)
+            })
+            .filter_map(|(submission, id, index, stencil)| {
+                let chain = if stencil {
+                    &self.stencils[&id]
+                } else {
+                    &self.images[&id]
+                };
+                if chain.link(index).state().access.intersects(attachment_access) {
+                    let mut info = chain.attachment_info(index, sid);
+                    info.resolve_source = submission.resolve_source(id);
+                    Some((id, info))
+                } else {
+                    None
+                }
+            })
+            .collect();
+        attachments.sort_by_key(|&(id, _)| id);
+        attachments
+    }
 }
 
 #[derive(PartialEq, PartialOrd, Eq, Ord)]
 struct Fitness {
     transfers: usize,
     wait_factor: usize,
+    unpreferred: bool,
 }
 
+#[derive(Default)]
 struct ResolvedPass {
     id: usize,
-    family: QueueFamilyId,
-    queues: Range<usize>,
+    kind: PassKind,
+    conditional: bool,
+    cost: usize,
+    ordering: OrderingHint,
+    queues: Vec<usize>,
+    preferred_family: Option<QueueFamilyId>,
     rev_deps: Vec<usize>,
     buffers: Vec<(usize, StateUsage<Buffer>)>,
     images: Vec<(usize, StateUsage<Image>)>,
+    resolves: FnvHashMap<Id<Image>, Id<Image>>,
+    meta: PassMeta,
 }
-impl Default for ResolvedPass {
-    fn default() -> Self {
-        ResolvedPass {
-            id: 0,
-            family: QueueFamilyId(0),
-            queues: 0..0,
-            rev_deps: Vec::new(),
-            buffers: Vec::new(),
-            images: Vec::new(),
-        }
-    }
+
+/// Part of the image tracked by separate chain.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum ImagePart {
+    /// Whole image. Or depth aspect if stencil aspect is tracked separately.
+    Whole,
+
+    /// Stencil aspect tracked separately from depth aspect.
+    Stencil,
+
+    /// One of disjoint ranges of the image. Indexes ranges of image parts.
+    Range(usize),
 }
 
 struct ResolvedPassSet {
@@ -67,7 +573,12 @@ struct ResolvedPassSet {
     pass_ids: Vec<PassId>,
     queues: Vec<QueueId>,
     buffers: Vec<Id<Buffer>>,
+    buffer_parts: Vec<Option<usize>>,
+    buffer_ranges: FnvHashMap<Id<Buffer>, Vec<Range<u64>>>,
     images: Vec<Id<Image>>,
+    parts: Vec<ImagePart>,
+    ranges: FnvHashMap<Id<Image>, Vec<SubresourceRange>>,
+    layout_policy: LayoutPolicy,
 }
 
 struct ChainData<R: Resource> {
@@ -94,12 +605,205 @@ struct QueueData {
 
 /// Calculate automatic `Chains` for passes.
 /// This function tries to find most appropriate schedule for passes execution.
-pub fn collect<Q>(passes: Vec<Pass>, max_queues: Q) -> Chains
+/// `resources` provides properties of resources that can't be derived from passes.
+/// The result doesn't depend on order of `passes` or of resources declared by them.
+///
+/// # Complexity
+///
+/// With single queue each declaration of a resource by a pass is processed a constant
+/// number of times, so collection takes `O(D + P log P)` for `D` declarations and `P` passes.
+/// Resources the passes don't declare cost nothing. Images and buffers accessed in disjoint
+/// ranges add the number of their parts for each declaration.
+/// With several queues every ready pass is fitted to every queue it may be executed on
+/// whenever a pass is scheduled, which adds `O(P * ready passes * queues)`.
+/// Fitness of a ready pass is recomputed from its declarations only after another pass
+/// declaring the same resource is scheduled.
+///
+/// # Errors
+///
+/// This function returns `CollectError` if passes declared resource states that can't be merged.
+/// Or if ids of passes aren't unique or their dependencies are unknown or form a cycle.
+/// Or if passes declared resources that aren't registered in `resources`.
+pub fn collect<Q>(
+    passes: Vec<Pass>,
+    resources: &Resources,
+    max_queues: Q,
+) -> Result<Chains, CollectError>
+where
+    Q: Fn(QueueFamilyId) -> usize,
+{
+    collect_with_capabilities(passes, resources, max_queues, |_| Capability::Graphics)
+}
+
+/// Calculate automatic `Chains` for passes like `collect` does
+/// and find suspicious declarations and chains that don't prevent it.
+/// Warnings about declarations are followed by warnings about chains.
+/// See `warning::declaration_warnings` and `warning::chain_warnings`.
+///
+/// # Errors
+///
+/// This function returns the same errors as `collect`.
+pub fn collect_with_warnings<Q>(
+    passes: Vec<Pass>,
+    resources: &Resources,
+    max_queues: Q,
+) -> Result<(Chains, Vec<ChainWarning>), CollectError>
+where
+    Q: Fn(QueueFamilyId) -> usize,
+{
+    let mut warnings = declaration_warnings(&passes);
+    let chains = collect(passes, resources, max_queues)?;
+    warnings.extend(chain_warnings(&chains));
+    Ok((chains, warnings))
+}
+
+/// Calculate automatic `Chains` for passes executed by families with given capabilities.
+/// Passes are scheduled only to families supporting capability they require.
+/// See `Pass::required_capability`.
+///
+/// # Errors
+///
+/// In addition to errors of `collect` this function returns `CollectError`
+/// if none of the families pass may execute on has capability it requires.
+pub fn collect_with_capabilities<Q, C>(
+    passes: Vec<Pass>,
+    resources: &Resources,
+    max_queues: Q,
+    capability: C,
+) -> Result<Chains, CollectError>
 where
     Q: Fn(QueueFamilyId) -> usize,
+    C: Fn(QueueFamilyId) -> Capability,
+{
+    collect_families(passes, resources, |id| {
+        FamilyInfo::new(id, capability(id), max_queues(id))
+    })
+}
+
+/// Properties of the queue family that affect scheduling.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize))]
+pub struct FamilyInfo {
+    /// Id of the family.
+    #[cfg_attr(feature = "serde-1", serde(with = "::ser::repr"))]
+    pub id: QueueFamilyId,
+
+    /// Operations supported by queues of the family.
+    pub capability: Capability,
+
+    /// Maximum number of queues of the family to use.
+    pub queue_count: usize,
+
+    /// Granularity of image transfers performed on queues of the family,
+    /// like `min_image_transfer_granularity` of the queue family in Vulkan.
+    /// `(1, 1, 1)` for graphics and compute families. Transfer-only families may have
+    /// coarser one, or `(0, 0, 0)` if they transfer only whole levels.
+    /// See `Pass::transfer_regions`.
+    #[cfg_attr(feature = "serde-1", serde(with = "::ser::repr"))]
+    pub image_transfer_granularity: Extent,
+}
+
+impl FamilyInfo {
+    /// Create info for the family with fine-grained image transfers.
+    pub fn new(id: QueueFamilyId, capability: Capability, queue_count: usize) -> Self {
+        FamilyInfo {
+            id,
+            capability,
+            queue_count,
+            image_transfer_granularity: Extent {
+                width: 1,
+                height: 1,
+                depth: 1,
+            },
+        }
+    }
+}
+
+/// Calculate automatic `Chains` for passes executed by described families.
+/// Families that are not described have no queues.
+/// Passes are scheduled only to families supporting capability they require
+/// and which granularity fits regions of images they transfer.
+/// See `Pass::transfer_regions`.
+///
+/// # Errors
+///
+/// In addition to errors of `collect_with_capabilities` this function returns `CollectError`
+/// if granularity of none of the capable families fits regions transferred by some pass.
+pub fn collect_with_families(
+    passes: Vec<Pass>,
+    resources: &Resources,
+    families: &[FamilyInfo],
+) -> Result<Chains, CollectError> {
+    collect_families(passes, resources, |id| {
+        families
+            .iter()
+            .find(|family| family.id == id)
+            .cloned()
+            .unwrap_or_else(|| FamilyInfo::new(id, Capability::Transfer, 0))
+    })
+}
+
+/// Options of scheduling passes.
+/// Default options schedule passes like `collect_with_families`.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize))]
+pub struct SchedulerOptions {
+    /// Move passes to families suggested by `offload::offload_suggestions` with positive slack
+    /// and schedule passes again. Moved passes are pinned to suggested families.
+    /// All suggestions are applied at once, so those may cancel each other's slack.
+    pub offload_transfers: bool,
+}
+
+/// Calculate automatic `Chains` for passes executed by described families
+/// like `collect_with_families` does, adjusted by options.
+///
+/// # Errors
+///
+/// This function returns the same errors as `collect_with_families`.
+pub fn collect_with_options(
+    passes: Vec<Pass>,
+    resources: &Resources,
+    families: &[FamilyInfo],
+    options: &SchedulerOptions,
+) -> Result<Chains, CollectError> {
+    if !options.offload_transfers {
+        return collect_with_families(passes, resources, families);
+    }
+    let chains = collect_with_families(passes.clone(), resources, families)?;
+    let moved: FnvHashMap<_, _> = offload_suggestions(&chains, &passes, families)
+        .into_iter()
+        .filter(|suggestion| suggestion.slack > 0)
+        .map(|suggestion| (suggestion.pass, suggestion.target_family))
+        .collect();
+    if moved.is_empty() {
+        return Ok(chains);
+    }
+    let passes = passes
+        .into_iter()
+        .map(|pass| match moved.get(&pass.id) {
+            Some(&family) => pass.with_queue_family(family),
+            None => pass,
+        })
+        .collect();
+    collect_with_families(passes, resources, families)
+}
+
+fn collect_families<F>(
+    passes: Vec<Pass>,
+    resources: &Resources,
+    family_info: F,
+) -> Result<Chains, CollectError>
+where
+    F: Fn(QueueFamilyId) -> FamilyInfo,
 {
+    check_dependencies(&passes)?;
+    check_resources(&passes, resources)?;
+    #[cfg(not(feature = "lenient-states"))]
+    check_states(&passes)?;
+    check_group_declarations(&passes, &resources.image_groups)?;
+
     // Resolve passes into a form faster to work with.
-    let (passes, mut unscheduled_passes) = resolve_passes(passes, max_queues);
+    let (passes, mut unscheduled_passes) = resolve_passes(passes, resources, family_info)?;
     let mut ready_passes = Vec::new();
 
     // Chains.
@@ -123,37 +827,58 @@ where
 
     let mut scheduled = 0;
     if passes.queues.len() == 1 {
-        // With a single queue, wait_factor is always the number of scheduled passes, and
+        // With a single queue, wait_factor is always the total cost of scheduled passes, and
         // transfers is always zero. Thus, we only need dependency resolution.
-        while let Some(pass) = ready_passes.pop() {
+        while let Some(pass) = pop_ready(&mut ready_passes) {
+            let wait_factor = schedule[0].wait_factor;
             schedule_pass(
                 &mut ready_passes,
                 &mut unscheduled_passes,
                 &passes,
                 pass,
                 0,
-                scheduled,
+                wait_factor,
                 scheduled,
                 &mut schedule,
                 &mut images,
                 &mut buffers,
-            );
+            )?;
             scheduled += 1;
         }
     } else {
+        // Chains of the resources of the pass change only when another pass accessing them
+        // is scheduled, so their part of fitness is kept until then.
+        let (buffer_users, image_users) = resource_users(&passes);
+        let mut cached: Vec<Option<ChainFitness>> = fill(passes.passes.len());
         while !ready_passes.is_empty() {
             // Among ready passes find best fit.
-            let (fitness, qid, index) = ready_passes
+            // Passes hinted to go early are taken first and ones hinted to go late last.
+            // Costlier passes go first when fit equally well (longest processing time first).
+            let (_, fitness, _, qid, index) = ready_passes
                 .iter()
                 .enumerate()
                 .map(|(index, &pass)| {
-                    let (fitness, qid) = fitness(pass, &mut images, &mut buffers, &mut schedule);
-                    (fitness, qid, index)
+                    let chains = cached[pass.id].get_or_insert_with(|| {
+                        chain_fitness(pass, &passes.queues, &images, &buffers)
+                    });
+                    let (fitness, qid) = fitness(pass, chains, &schedule);
+                    let rank = ordering_rank(pass.ordering);
+                    (rank, fitness, Reverse(pass.cost), qid, index)
                 })
                 .min()
                 .unwrap();
 
             let pass = ready_passes.swap_remove(index);
+            for &(id, _) in &pass.buffers {
+                for &user in &buffer_users[id] {
+                    cached[user] = None;
+                }
+            }
+            for &(id, _) in &pass.images {
+                for &user in &image_users[id] {
+                    cached[user] = None;
+                }
+            }
             schedule_pass(
                 &mut ready_passes,
                 &mut unscheduled_passes,
@@ -165,16 +890,396 @@ where
                 &mut schedule,
                 &mut images,
                 &mut buffers,
-            );
+            )?;
             scheduled += 1;
         }
     }
+    // Cycles are rejected by `check_dependencies`.
     assert!(scheduled == passes.passes.len(), "Dependency loop found!");
+    mark_unhonored_hints(&passes, &mut schedule);
 
-    Chains {
+    let (images, stencils, parts) = reify_images(&passes, images, |id| resources.image(id));
+    check_group_chains(&resources.image_groups, &images, &stencils, &parts)?;
+    let (buffers, buffer_parts) = reify_buffers(&passes, buffers, |id| resources.buffer(id));
+    Ok(Chains {
         schedule: reify_schedule(&passes.queues, schedule),
-        buffers: reify_chain(&passes.buffers, buffers),
-        images: reify_chain(&passes.images, images),
+        buffers,
+        images,
+        stencils,
+        parts,
+        buffer_parts,
+    })
+}
+
+/// State of the pass in depth-first search for cycles.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Visit {
+    New,
+    Active,
+    Done,
+}
+
+/// Check that pass ids are unique, dependencies exist and have no cycles.
+/// Passes are visited in order of ids, so the same cycle is reported for the same passes.
+fn check_dependencies(passes: &[Pass]) -> Result<(), CollectError> {
+    let mut indices: FnvHashMap<PassId, usize> = FnvHashMap::default();
+    for (index, pass) in passes.iter().enumerate() {
+        if indices.insert(pass.id, index).is_some() {
+            return Err(CollectError::DuplicatePass { pass: pass.id });
+        }
+    }
+    for pass in passes {
+        for &dependency in &pass.dependencies {
+            if !indices.contains_key(&dependency) {
+                return Err(CollectError::UnknownDependency {
+                    pass: pass.id,
+                    dependency,
+                });
+            }
+        }
+    }
+
+    let mut order: Vec<usize> = (0..passes.len()).collect();
+    order.sort_by_key(|&index| passes[index].id);
+    let mut visits = vec![Visit::New; passes.len()];
+    for root in order {
+        if visits[root] != Visit::New {
+            continue;
+        }
+        // Each pass on the path depends on the next one.
+        // Second element is the number of dependencies visited.
+        let mut path = vec![(root, 0)];
+        visits[root] = Visit::Active;
+        while let Some(&(index, visited)) = path.last() {
+            let dependencies = &passes[index].dependencies;
+            if visited == dependencies.len() {
+                visits[index] = Visit::Done;
+                path.pop();
+                continue;
+            }
+            path.last_mut().unwrap().1 += 1;
+            let next = indices[&dependencies[visited]];
+            match visits[next] {
+                Visit::New => {
+                    visits[next] = Visit::Active;
+                    path.push((next, 0));
+                }
+                Visit::Active => {
+                    let start = path.iter().position(|&(index, _)| index == next).unwrap();
+                    return Err(CollectError::DependencyCycle {
+                        passes: path[start..]
+                            .iter()
+                            .map(|&(index, _)| passes[index].id)
+                            .collect(),
+                    });
+                }
+                Visit::Done => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Check that passes declare only registered resources.
+/// The unknown resource with the lowest id in the pass with the lowest id is reported.
+fn check_resources(passes: &[Pass], resources: &Resources) -> Result<(), CollectError> {
+    let mut passes: Vec<_> = passes.iter().collect();
+    passes.sort_by_key(|pass| pass.id);
+    for pass in passes {
+        let mut buffers: Vec<_> = pass
+            .buffers
+            .keys()
+            .filter(|id| !resources.buffers.contains_key(id))
+            .collect();
+        buffers.sort();
+        if let Some(&&id) = buffers.first() {
+            return Err(CollectError::UnknownBuffer { id, pass: pass.id });
+        }
+        let mut images: Vec<_> = pass
+            .images
+            .keys()
+            .chain(pass.image_ranges.keys())
+            .filter(|id| !resources.images.contains_key(id))
+            .collect();
+        images.sort();
+        if let Some(&&id) = images.first() {
+            return Err(CollectError::UnknownImage { id, pass: pass.id });
+        }
+    }
+    Ok(())
+}
+
+/// Check that each pass declares all members of each group in the same way or none of them.
+fn check_group_declarations(
+    passes: &[Pass],
+    groups: &[ResourceGroup<Image>],
+) -> Result<(), CollectError> {
+    // Only passes declaring some member of the group may declare it differently.
+    let mut declaring: Vec<Vec<&Pass>> = groups.iter().map(|_| Vec::new()).collect();
+    let mut members: FnvHashMap<Id<Image>, Vec<usize>> = FnvHashMap::default();
+    for (index, group) in groups.iter().enumerate() {
+        for &id in group.ids() {
+            members.entry(id).or_default().push(index);
+        }
+    }
+    for pass in passes {
+        for id in pass.images.keys().chain(pass.image_ranges.keys()) {
+            for &index in members.get(id).map_or(&[][..], |groups| &groups[..]) {
+                let declaring = &mut declaring[index];
+                if declaring.last().is_none_or(|last| last.id != pass.id) {
+                    declaring.push(pass);
+                }
+            }
+        }
+    }
+
+    for (group, declaring) in groups.iter().zip(declaring) {
+        let first = match group.ids().first() {
+            Some(&first) => first,
+            None => continue,
+        };
+        for pass in declaring {
+            let declared = pass.images.get(&first);
+            for &id in group.ids() {
+                let same = match (declared, pass.images.get(&id)) {
+                    (None, None) => true,
+                    (Some(left), Some(right)) => same_declaration(left, right),
+                    _ => false,
+                };
+                if !same || pass.image_ranges.contains_key(&id) {
+                    return Err(CollectError::SplitImageGroup {
+                        id,
+                        group: first,
+                        pass: pass.id,
+                    });
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn same_declaration<R: Resource>(left: &StateUsage<R>, right: &StateUsage<R>) -> bool {
+    left.state == right.state
+        && left.usage == right.usage
+        && left.range == right.range
+        && left.discard == right.discard
+}
+
+/// Check that members of each group are tracked by identical chains.
+fn check_group_chains(
+    groups: &[ResourceGroup<Image>],
+    images: &ImageChains,
+    stencils: &ImageChains,
+    parts: &ImagePartChains,
+) -> Result<(), CollectError> {
+    for group in groups {
+        let first = match group.ids().first() {
+            Some(&first) => first,
+            None => continue,
+        };
+        for &id in group.ids() {
+            let same = !parts.contains_key(&id)
+                && same_chains(images.get(&first), images.get(&id))
+                && same_chains(stencils.get(&first), stencils.get(&id));
+            if !same {
+                return Err(CollectError::DivergedImageGroup { id, group: first });
+            }
+        }
+    }
+    Ok(())
+}
+
+fn same_chains<R: Resource>(left: Option<&Chain<R>>, right: Option<&Chain<R>>) -> bool {
+    let (left, right) = match (left, right) {
+        (None, None) => return true,
+        (Some(left), Some(right)) => (left, right),
+        _ => return false,
+    };
+    let same_boundary = |left: Boundary<R>, right: Boundary<R>| {
+        left.state == right.state && left.owner == right.owner
+    };
+    left.range() == right.range()
+        && left.sharing() == right.sharing()
+        && left.info().present == right.info().present
+        && left.info().per_frame == right.info().per_frame
+        && left.binds() == right.binds()
+        && left.links().len() == right.links().len()
+        && left.links().iter().zip(right.links()).all(|(left, right)| {
+            left.family() == right.family()
+                && left.state() == right.state()
+                && left.discard() == right.discard()
+                && left.range() == right.range()
+                && left.queues().count() == right.queues().count()
+                && left.queues().zip(right.queues()).all(|((left_qid, left), (right_qid, right))| {
+                    left_qid == right_qid && left.first == right.first && left.last == right.last
+                })
+        })
+        && same_boundary(left.initial_state(), right.initial_state())
+        && same_boundary(left.final_state(), right.final_state())
+}
+
+/// Combine ordering hints of declarations of the pass.
+/// Conflicting hints cancel each other. Hints of sparse binding passes are ignored.
+fn pass_ordering(pass: &Pass) -> OrderingHint {
+    if pass.kind == PassKind::SparseBind {
+        return OrderingHint::None;
+    }
+    let hints: Vec<_> = pass
+        .buffers
+        .values()
+        .map(|state_usage| state_usage.ordering)
+        .chain(pass.images.values().map(|state_usage| state_usage.ordering))
+        .chain(
+            pass.image_ranges
+                .values()
+                .flat_map(|ranges| ranges.iter().map(|state_usage| state_usage.ordering)),
+        )
+        .collect();
+    let early = hints.contains(&OrderingHint::AsEarlyAsPossible);
+    let late = hints.contains(&OrderingHint::AsLateAsPossible);
+    match (early, late) {
+        (true, false) => OrderingHint::AsEarlyAsPossible,
+        (false, true) => OrderingHint::AsLateAsPossible,
+        _ => OrderingHint::None,
+    }
+}
+
+/// Check that passes declare only states which access types their stages perform.
+/// The first invalid state of the pass with the lowest id is reported.
+#[cfg(not(feature = "lenient-states"))]
+fn check_states(passes: &[Pass]) -> Result<(), CollectError> {
+    let mut passes: Vec<_> = passes.iter().collect();
+    passes.sort_by_key(|pass| pass.id);
+    for pass in passes {
+        if let Some(&(id, state)) = pass.unsupported_buffer_states().first() {
+            return Err(CollectError::InvalidBufferStateDeclaration {
+                id,
+                pass: pass.id,
+                access: state.unsupported_access(),
+                stages: state.stages,
+            });
+        }
+        if let Some(&(id, state)) = pass.unsupported_image_states().first() {
+            return Err(CollectError::InvalidImageStateDeclaration {
+                id,
+                pass: pass.id,
+                access: state.unsupported_access(),
+                stages: state.stages,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Get capability required by the pass.
+/// Validates that specified capability supports stages of declared states.
+fn required_capability(pass: &Pass) -> Result<Option<Capability>, CollectError> {
+    match (pass.capability, pass.declared_capability()) {
+        (Some(capability), Some((stage, declared))) if !capability.supports(declared) => {
+            Err(CollectError::InsufficientCapability {
+                pass: pass.id,
+                capability,
+                stage,
+            })
+        }
+        _ => Ok(pass.required_capability()),
+    }
+}
+
+/// Passes with lower rank are scheduled first among ready ones.
+fn ordering_rank(hint: OrderingHint) -> usize {
+    match hint {
+        OrderingHint::AsEarlyAsPossible => 0,
+        OrderingHint::None => 1,
+        OrderingHint::AsLateAsPossible => 2,
+    }
+}
+
+/// Take ready pass to schedule next on the only queue.
+/// The latest ready pass is taken unless ordering hints prefer another one.
+fn pop_ready<'a>(ready_passes: &mut Vec<&'a ResolvedPass>) -> Option<&'a ResolvedPass> {
+    let (index, _) = ready_passes
+        .iter()
+        .enumerate()
+        .min_by_key(|&(index, pass)| (ordering_rank(pass.ordering), Reverse(index)))?;
+    Some(ready_passes.remove(index))
+}
+
+/// Record in submissions which ordering hints of their passes couldn't be honored.
+fn mark_unhonored_hints(passes: &ResolvedPassSet, schedule: &mut Vec<QueueData>) {
+    let resolved: FnvHashMap<PassId, &ResolvedPass> = passes
+        .passes
+        .iter()
+        .map(|pass| (passes.pass_ids[pass.id], pass))
+        .collect();
+    for queue_data in schedule {
+        let queue = &mut queue_data.queue;
+        let mut unhonored = Vec::new();
+        for (sid, submission) in queue.iter() {
+            let pass = resolved[&submission.pass()];
+            if pass.kind == PassKind::SparseBind {
+                continue;
+            }
+            let buffers = pass
+                .buffers
+                .iter()
+                .map(|&(index, ref state_usage)| (passes.buffers[index], state_usage.ordering))
+                .filter(|&(id, hint)| !honored(queue, sid, hint, |other| other.accesses_buffer(id)))
+                .map(|(id, _)| id);
+            let images = pass
+                .images
+                .iter()
+                .map(|&(index, ref state_usage)| (passes.images[index], state_usage.ordering))
+                .filter(|&(id, hint)| !honored(queue, sid, hint, |other| other.accesses_image(id)))
+                .map(|(id, _)| id);
+            let mut buffers: Vec<_> = buffers.collect();
+            let mut images: Vec<_> = images.collect();
+            if !buffers.is_empty() || !images.is_empty() {
+                buffers.sort();
+                buffers.dedup();
+                images.sort();
+                images.dedup();
+                unhonored.push((sid, buffers, images));
+            }
+        }
+        for (sid, buffers, images) in unhonored {
+            queue[sid].set_unhonored_hints(buffers, images);
+        }
+    }
+}
+
+/// Check if submission is placed right after previous (or before next) submission
+/// of the queue that accesses the resource, as the hint asks.
+fn honored<F>(
+    queue: &Queue<Unsynchronized>,
+    sid: SubmissionId,
+    hint: OrderingHint,
+    accesses: F,
+) -> bool
+where
+    F: Fn(&Submission<Unsynchronized>) -> bool,
+{
+    let index = sid.index();
+    match hint {
+        OrderingHint::None => true,
+        OrderingHint::AsEarlyAsPossible => {
+            let previous = queue
+                .iter()
+                .take(index)
+                .filter(|&(_, other)| accesses(other))
+                .last();
+            match previous {
+                Some((previous, _)) => previous.index() + 1 == index,
+                None => index == 0,
+            }
+        }
+        OrderingHint::AsLateAsPossible => {
+            match queue.iter().skip(index + 1).find(|&(_, other)| accesses(other)) {
+                Some((next, _)) => next.index() == index + 1,
+                None => index + 1 == queue.len(),
+            }
+        }
     }
 }
 
@@ -212,11 +1317,35 @@ impl<I: Hash + Eq + Copy> LookupBuilder<I> {
     }
 }
 
-fn resolve_passes<Q>(passes: Vec<Pass>, max_queues: Q) -> (ResolvedPassSet, Vec<usize>)
+fn resolve_passes<F>(
+    mut passes: Vec<Pass>,
+    resources: &Resources,
+    family_info: F,
+) -> Result<(ResolvedPassSet, Vec<usize>), CollectError>
 where
-    Q: Fn(QueueFamilyId) -> usize,
+    F: Fn(QueueFamilyId) -> FamilyInfo,
 {
+    // Order of passes and resources must not affect the result.
+    passes.sort_by_key(|pass| pass.id);
+
+    let mut declared = Vec::with_capacity(passes.len());
+    for pass in &passes {
+        let mut images = declared_images(pass)?;
+        for &mut (id, ref mut declarations) in &mut images {
+            if declarations.len() > 1 && resources.image(id).present {
+                // Presentation engine accesses whole image, so it is never split.
+                let whole = union(declarations.iter().filter_map(|v| v.range.clone())).unwrap();
+                let merged = part_declaration(pass.id, id, declarations, &whole)?;
+                *declarations = merged.into_iter().collect();
+            }
+        }
+        declared.push(images);
+    }
+
     let pass_count = passes.len();
+    let ranges = split_ranges(&passes, &declared);
+    let split = split_aspects(&passes, &declared, &ranges);
+    let buffer_ranges = split_buffer_ranges(&passes);
 
     let mut unscheduled_passes = fill(passes.len());
     let mut reified_passes: Vec<ResolvedPass> = fill(passes.len());
@@ -225,24 +1354,44 @@ where
     let mut buffers = LookupBuilder::new();
     let mut images = LookupBuilder::new();
 
-    let mut family_full = FnvHashMap::default();
-    for pass in passes {
-        let family = pass.family;
-        if !family_full.contains_key(&family) {
-            let count = max_queues(family);
-            assert!(count > 0, "Cannot create a family with 0 max queues.");
-            for i in 0..count {
-                queues.forward(QueueId::new(family, i));
+    let mut family_full: FnvHashMap<QueueFamilyId, Range<usize>> = FnvHashMap::default();
+    for (pass, declared) in passes.into_iter().zip(declared) {
+        for &family in Some(&pass.family).into_iter().chain(&pass.families) {
+            if let Entry::Vacant(entry) = family_full.entry(family) {
+                let count = family_info(family).queue_count;
+                let full_range = if count > 0 {
+                    for i in 0..count {
+                        queues.forward(QueueId::new(family, i));
+                    }
+                    queues.forward(QueueId::new(family, 0))
+                        ..queues.forward(QueueId::new(family, count - 1)) + 1
+                } else {
+                    0..0
+                };
+                entry.insert(full_range);
             }
-
-            let full_range = queues.forward(QueueId::new(family, 0))
-                ..queues.forward(QueueId::new(family, count - 1)) + 1;
-            family_full.insert(family, full_range);
         }
 
         let id = pass_ids.forward(pass.id);
+        // Unknown dependencies are rejected by `check_dependencies`.
         assert!(id < pass_count, "Dependency not found."); // This implies a dep is not there.
         let unscheduled_count = pass.dependencies.len();
+        let ordering = pass_ordering(&pass);
+        let conditional = pass.is_conditional();
+        let required = required_capability(&pass)?;
+        let capable = |family: QueueFamilyId| {
+            required.is_none_or(|required| family_info(family).capability.supports(required))
+        };
+        // Families which granularity doesn't fit transfer regions of some image.
+        let misaligned: Vec<(QueueFamilyId, Id<Image>)> = Some(pass.family)
+            .into_iter()
+            .chain(pass.families.iter().cloned())
+            .filter_map(|family| {
+                let granularity = family_info(family).image_transfer_granularity;
+                pass.misaligned_transfer(granularity).map(|id| (family, id))
+            })
+            .collect();
+        let aligned = |family: QueueFamilyId| misaligned.iter().all(|&(other, _)| other != family);
 
         for dep in pass.dependencies {
             // Duplicated dependencies work fine, since they push two rev_deps entries and add two
@@ -253,94 +1402,682 @@ where
 
         // We set these manually, and notably, do *not* touch rev_deps.
         reified_passes[id].id = id;
-        reified_passes[id].family = pass.family;
+        reified_passes[id].kind = pass.kind;
+        reified_passes[id].conditional = conditional;
+        reified_passes[id].cost = max(pass.cost, 1) as usize;
+        reified_passes[id].ordering = ordering;
+        reified_passes[id].preferred_family = pass.preferred_family;
+        reified_passes[id].resolves = pass.resolves.into_iter().collect();
+        reified_passes[id].meta = pass.meta;
+        if !Some(pass.family).into_iter().chain(pass.families.iter().cloned()).any(&capable) {
+            return Err(CollectError::IncapableFamilies {
+                pass: pass.id,
+                family: pass.family,
+                capability: required.unwrap(),
+            });
+        }
+        let legal = |family: QueueFamilyId| capable(family) && aligned(family);
+        if !Some(pass.family).into_iter().chain(pass.families.iter().cloned()).any(&legal) {
+            let &(family, image) = misaligned.iter().find(|&&(family, _)| capable(family)).unwrap();
+            return Err(CollectError::MisalignedImageTransfer {
+                id: image,
+                pass: pass.id,
+                family,
+            });
+        }
         reified_passes[id].queues = if let Some(queue) = pass.queue {
-            let id = queues
-                .get(QueueId::new(family, queue))
-                .expect("Requested queue out of range!");
-            id..id + 1
+            if !capable(pass.family) {
+                return Err(CollectError::IncapableFamilies {
+                    pass: pass.id,
+                    family: pass.family,
+                    capability: required.unwrap(),
+                });
+            }
+            let family = pass.family;
+            if let Some(&(_, image)) = misaligned.iter().find(|&&(other, _)| other == family) {
+                return Err(CollectError::MisalignedImageTransfer {
+                    id: image,
+                    pass: pass.id,
+                    family,
+                });
+            }
+            let index = queues
+                .get(QueueId::new(pass.family, queue))
+                .ok_or(CollectError::QueueOutOfRange {
+                    pass: pass.id,
+                    family: pass.family,
+                    queue,
+                })?;
+            vec![index]
         } else {
-            family_full[&family].clone()
+            // Families without queues, capability or fine enough granularity
+            // can't execute the pass.
+            Some(&pass.family)
+                .into_iter()
+                .chain(&pass.families)
+                .filter(|&&family| legal(family))
+                .flat_map(|family| family_full[family].clone())
+                .collect()
         };
-        reified_passes[id].buffers = pass
-            .buffers
-            .into_iter()
-            .map(|(k, v)| (buffers.forward(k), v))
-            .collect();
-        reified_passes[id].images = pass
-            .images
-            .into_iter()
-            .map(|(k, v)| (images.forward(k), v))
-            .collect();
+        if reified_passes[id].queues.is_empty() {
+            return Err(CollectError::NoQueues {
+                pass: pass.id,
+                family: pass.family,
+            });
+        }
+        let mut declared_buffers: Vec<_> = pass.buffers.into_iter().collect();
+        declared_buffers.sort_by_key(|&(k, _)| k);
+        let mut resolved_buffers = Vec::with_capacity(declared_buffers.len());
+        for (k, v) in declared_buffers {
+            if pass.kind != PassKind::SparseBind {
+                check_alignment(pass.id, k, &v, resources.buffer(k).alignments)?;
+            }
+            let parts = match buffer_ranges.get(&k) {
+                Some(parts) => parts,
+                None => {
+                    resolved_buffers.push((buffers.forward((k, None)), v));
+                    continue;
+                }
+            };
+            if pass.kind == PassKind::SparseBind {
+                for (index, part) in parts.iter().enumerate() {
+                    let range = match v.range.as_ref() {
+                        Some(range) => range.intersection(part),
+                        None => Some(part.clone()),
+                    };
+                    if let Some(range) = range {
+                        let v = StateUsage {
+                            range: Some(range),
+                            ..v.clone()
+                        };
+                        resolved_buffers.push((buffers.forward((k, Some(index))), v));
+                    }
+                }
+            } else {
+                // Buffer is split only if each range declared by passes lies within single part.
+                let range = v.range.clone().unwrap();
+                let index = parts.iter().position(|part| part.overlaps(&range)).unwrap();
+                resolved_buffers.push((buffers.forward((k, Some(index))), v));
+            }
+        }
+        reified_passes[id].buffers = resolved_buffers;
+        let mut resolved_images = Vec::with_capacity(declared.len());
+        for (k, declarations) in declared {
+            if let Some(parts) = ranges.get(&k) {
+                for (index, part) in parts.iter().enumerate() {
+                    let v = if pass.kind == PassKind::SparseBind {
+                        bind_declaration(&declarations, part)
+                    } else {
+                        part_declaration(pass.id, k, &declarations, part)?
+                    };
+                    if let Some(v) = v {
+                        resolved_images.push((images.forward((k, ImagePart::Range(index))), v));
+                    }
+                }
+                for range in declarations.iter().filter_map(|v| v.range.as_ref()) {
+                    if !covered(range, parts) {
+                        return Err(CollectError::UncoveredImageRange {
+                            id: k,
+                            pass: pass.id,
+                            range: range.clone(),
+                        });
+                    }
+                }
+                continue;
+            }
+
+            // Only sparse binding may have several declarations of the image that isn't split.
+            let mut declarations = declarations.into_iter();
+            let mut v = declarations.next().unwrap();
+            for other in declarations {
+                v.range = match (v.range, other.range) {
+                    (Some(left), Some(right)) => Some(left.union(&right)),
+                    _ => None,
+                };
+            }
+
+            if !split.contains(&k) {
+                resolved_images.push((images.forward((k, ImagePart::Whole)), v));
+            } else if pass.kind == PassKind::SparseBind && !is_aspect(v.range.as_ref()) {
+                // Binding of both aspects is ordered against both chains.
+                resolved_images.push((images.forward((k, ImagePart::Whole)), v.clone()));
+                resolved_images.push((images.forward((k, ImagePart::Stencil)), v));
+            } else {
+                let part = if is_stencil(v.range.as_ref()) {
+                    ImagePart::Stencil
+                } else {
+                    ImagePart::Whole
+                };
+                resolved_images.push((images.forward((k, part)), v));
+            }
+        }
+        reified_passes[id].images = resolved_images;
     }
 
-    (
+    Ok((
         ResolvedPassSet {
             passes: reified_passes,
             pass_ids: pass_ids.backward,
             queues: queues.backward,
-            buffers: buffers.backward,
-            images: images.backward,
+            buffers: buffers.backward.iter().map(|&(id, _)| id).collect(),
+            buffer_parts: buffers.backward.iter().map(|&(_, part)| part).collect(),
+            buffer_ranges,
+            images: images.backward.iter().map(|&(id, _)| id).collect(),
+            parts: images.backward.iter().map(|&(_, part)| part).collect(),
+            ranges,
+            layout_policy: resources.layout_policy,
         },
         unscheduled_passes,
-    )
+    ))
+}
+
+/// Declarations of images by one pass grouped by image id.
+pub(crate) type DeclaredImages = Vec<(Id<Image>, Vec<StateUsage<Image>>)>;
+
+/// Get declarations of images by the pass sorted by id.
+/// Overlapping declarations of the same image are merged.
+pub(crate) fn declared_images(pass: &Pass) -> Result<DeclaredImages, CollectError> {
+    let mut ids: Vec<Id<Image>> = pass
+        .images
+        .keys()
+        .chain(pass.image_ranges.keys())
+        .cloned()
+        .collect();
+    ids.sort();
+    ids.dedup();
+    ids.into_iter()
+        .map(|id| {
+            let declared = pass
+                .images
+                .get(&id)
+                .into_iter()
+                .chain(pass.image_ranges.get(&id).into_iter().flatten())
+                .cloned();
+            let merged = merge_overlapping(declared)
+                .map_err(|conflict| range_conflict(id, pass.id, conflict))?;
+            Ok((id, merged))
+        })
+        .collect()
+}
+
+fn range_conflict(id: Id<Image>, pass: PassId, conflict: RangeConflict) -> CollectError {
+    match conflict.ranges {
+        Some(ranges) => CollectError::IncompatibleImageRanges {
+            id,
+            pass,
+            ranges,
+            error: conflict.error,
+        },
+        None => CollectError::IncompatibleImageStates {
+            id,
+            pass,
+            error: conflict.error,
+        },
+    }
+}
+
+/// Get declaration of the image part by the pass.
+/// Declarations are restricted to the range of the part and merged.
+/// Returns `None` if the pass doesn't access the part.
+fn part_declaration(
+    pass: PassId,
+    id: Id<Image>,
+    declarations: &[StateUsage<Image>],
+    part: &SubresourceRange,
+) -> Result<Option<StateUsage<Image>>, CollectError> {
+    let mut result: Option<StateUsage<Image>> = None;
+    for declared in declarations {
+        let range = match declared.range.as_ref() {
+            Some(range) => range.intersection(part),
+            None => Some(part.clone()),
+        };
+        let restricted = match range {
+            Some(range) => StateUsage {
+                range: Some(range),
+                ..declared.clone()
+            },
+            None => continue,
+        };
+        result = Some(match result {
+            None => restricted,
+            Some(other) => other.try_merge(&restricted).map_err(|error| {
+                CollectError::IncompatibleImageRanges {
+                    id,
+                    pass,
+                    ranges: (other.range.unwrap(), restricted.range.unwrap()),
+                    error,
+                }
+            })?,
+        });
+    }
+    Ok(result)
+}
+
+/// Get state in which the pass accesses the image within the range.
+/// Declarations in disjoint ranges are restricted to the range and merged.
+/// Returns `None` if the pass doesn't access the range or declarations can't be merged.
+pub(crate) fn image_state_usage(
+    pass: &Pass,
+    id: Id<Image>,
+    range: Option<&SubresourceRange>,
+) -> Option<StateUsage<Image>> {
+    if !pass.image_ranges.contains_key(&id) {
+        return pass.images.get(&id).cloned();
+    }
+    let declared = declared_images(pass).ok()?;
+    let (_, declarations) = declared.iter().find(|&&(image, _)| image == id)?;
+    if range.is_none() && declarations.len() == 1 {
+        return Some(declarations[0].clone());
+    }
+    let whole = union(declarations.iter().filter_map(|v| v.range.clone()))?;
+    part_declaration(pass.id, id, declarations, range.unwrap_or(&whole)).ok()?
+}
+
+/// Get declaration of sparse binding of the image part.
+/// States of sparse binding are ignored, so only ranges are merged.
+fn bind_declaration(
+    declarations: &[StateUsage<Image>],
+    part: &SubresourceRange,
+) -> Option<StateUsage<Image>> {
+    let range = union(
+        declarations
+            .iter()
+            .filter_map(|declared| match declared.range.as_ref() {
+                Some(range) => range.intersection(part),
+                None => Some(part.clone()),
+            }),
+    )?;
+    Some(StateUsage {
+        range: Some(range),
+        ..declarations[0].clone()
+    })
+}
+
+/// Get smallest range that contains all ranges.
+/// Returns `None` if there are no ranges.
+fn union<I>(ranges: I) -> Option<SubresourceRange>
+where
+    I: IntoIterator<Item = SubresourceRange>,
+{
+    ranges.into_iter().fold(None, |acc, range| match acc {
+        Some(acc) => Some(acc.union(&range)),
+        None => Some(range),
+    })
+}
+
+/// Check if disjoint parts cover the whole range.
+fn covered(range: &SubresourceRange, parts: &[SubresourceRange]) -> bool {
+    fn volume(range: &SubresourceRange) -> u64 {
+        range.aspects.bits().count_ones() as u64
+            * (range.levels.end - range.levels.start) as u64
+            * (range.layers.end - range.layers.start) as u64
+    }
+    let covered: u64 = parts
+        .iter()
+        .filter_map(|part| part.intersection(range))
+        .map(|part| volume(&part))
+        .sum();
+    covered == volume(range)
+}
+
+/// Find images that some pass accesses in several disjoint ranges and ranges of their parts.
+/// Overlapping ranges declared by different passes are tracked by the same part.
+fn split_ranges(
+    passes: &[Pass],
+    declared: &[DeclaredImages],
+) -> FnvHashMap<Id<Image>, Vec<SubresourceRange>> {
+    let mut ranges: FnvHashMap<Id<Image>, Vec<SubresourceRange>> = FnvHashMap::default();
+    for (pass, declared) in passes.iter().zip(declared) {
+        if pass.kind == PassKind::SparseBind {
+            continue;
+        }
+        for &(id, ref declarations) in declared.iter().filter(|(_, v)| v.len() > 1) {
+            let parts = ranges.entry(id).or_default();
+            // Disjoint declarations always have ranges.
+            for mut range in declarations.iter().filter_map(|v| v.range.clone()) {
+                while let Some(index) = parts.iter().position(|part| part.overlaps(&range)) {
+                    range = parts.swap_remove(index).union(&range);
+                }
+                parts.push(range);
+            }
+        }
+    }
+    for parts in ranges.values_mut() {
+        parts.sort_by_key(|part| (part.levels.start, part.layers.start, part.aspects.bits()));
+    }
+    ranges
+}
+
+/// Find buffers that passes access only in disjoint ranges and ranges of their parts.
+/// Buffer is split if every pass declares its range and some declared ranges are disjoint.
+/// Overlapping ranges are tracked by the same part. Sparse bindings don't affect the split.
+fn split_buffer_ranges(passes: &[Pass]) -> FnvHashMap<Id<Buffer>, Vec<Range<u64>>> {
+    let mut ranges: FnvHashMap<Id<Buffer>, Option<Vec<Range<u64>>>> = FnvHashMap::default();
+    for pass in passes.iter().filter(|pass| pass.kind != PassKind::SparseBind) {
+        for (&id, state_usage) in &pass.buffers {
+            let entry = ranges.entry(id).or_insert_with(|| Some(Vec::new()));
+            let range = match state_usage.range {
+                Some(ref range) if range.start < range.end => Some(range.clone()),
+                _ => None,
+            };
+            let split = match (entry.as_mut(), range) {
+                (Some(parts), Some(mut range)) => {
+                    while let Some(index) = parts.iter().position(|part| part.overlaps(&range)) {
+                        range = parts.swap_remove(index).union(&range);
+                    }
+                    parts.push(range);
+                    true
+                }
+                _ => false,
+            };
+            if !split {
+                *entry = None;
+            }
+        }
+    }
+    ranges
+        .into_iter()
+        .filter_map(|(id, parts)| match parts {
+            Some(mut parts) if parts.len() > 1 => {
+                parts.sort_by_key(|part| part.start);
+                Some((id, parts))
+            }
+            _ => None,
+        })
+        .collect()
 }
 
-fn reify_chain<R: Resource>(ids: &[Id<R>], vec: Vec<ChainData<R>>) -> FnvHashMap<Id<R>, Chain<R>> {
-    let mut map = FnvHashMap::with_capacity_and_hasher(vec.len(), Default::default());
+/// Check that the range of the buffer declared by the pass is aligned for declared usage.
+/// Usage derived from the state is checked if the pass doesn't declare it.
+fn check_alignment(
+    pass: PassId,
+    id: Id<Buffer>,
+    declared: &StateUsage<Buffer>,
+    alignments: Option<BufferAlignments>,
+) -> Result<(), CollectError> {
+    let (alignments, range) = match (alignments, declared.range.as_ref()) {
+        (Some(alignments), Some(range)) => (alignments, range),
+        _ => return Ok(()),
+    };
+    let usage = if declared.usage == Usage::none() {
+        declared.state.usage()
+    } else {
+        declared.usage
+    };
+    alignments
+        .check(range, usage)
+        .map_err(|alignment| CollectError::MisalignedBufferRange {
+            id,
+            pass,
+            range: range.clone(),
+            alignment,
+        })
+}
+
+/// Check if range covers stencil aspect only.
+fn is_stencil(range: Option<&SubresourceRange>) -> bool {
+    range.is_some_and(|range| range.aspects == Aspects::STENCIL)
+}
+
+/// Check if range covers either depth or stencil aspect only.
+fn is_aspect(range: Option<&SubresourceRange>) -> bool {
+    range.is_some_and(|range| {
+        range.aspects == Aspects::DEPTH || range.aspects == Aspects::STENCIL
+    })
+}
+
+/// Find images which depth and stencil aspects get separate chains.
+/// Those are images that are used by passes with both aspects
+/// but never with both in the same declaration.
+/// Sparse bindings and images tracked in parts don't affect the split.
+fn split_aspects(
+    passes: &[Pass],
+    declared: &[DeclaredImages],
+    ranges: &FnvHashMap<Id<Image>, Vec<SubresourceRange>>,
+) -> FnvHashSet<Id<Image>> {
+    let mut aspects: FnvHashMap<Id<Image>, Option<Aspects>> = FnvHashMap::default();
+    for &(id, ref declarations) in passes
+        .iter()
+        .zip(declared)
+        .filter(|&(pass, _)| pass.kind != PassKind::SparseBind)
+        .flat_map(|(_, declared)| declared)
+        .filter(|&&(id, _)| !ranges.contains_key(&id))
+    {
+        let state_usage = &declarations[0];
+        let entry = aspects.entry(id).or_insert(Some(Aspects::empty()));
+        *entry = match (*entry, state_usage.range.as_ref()) {
+            (Some(seen), Some(range))
+                if range.aspects == Aspects::DEPTH || range.aspects == Aspects::STENCIL =>
+            {
+                Some(seen | range.aspects)
+            }
+            _ => None,
+        };
+    }
+    aspects
+        .into_iter()
+        .filter(|&(_, aspects)| aspects == Some(Aspects::DEPTH | Aspects::STENCIL))
+        .map(|(id, _)| id)
+        .collect()
+}
+
+fn reify_chain<R, F>(ids: &[Id<R>], vec: Vec<ChainData<R>>, info: F) -> IdMap<R, Chain<R>>
+where
+    R: Resource,
+    F: Fn(Id<R>) -> ResourceInfo<R>,
+{
+    let slots = ids.iter().map(|id| id.index() + 1).max().unwrap_or(0);
+    let mut map = IdMap::with_capacity(slots);
     for (chain, &i) in vec.into_iter().zip(ids) {
-        map.insert(i, chain.chain);
+        let mut chain = chain.chain;
+        if chain.links().is_empty() {
+            // Resource is only bound by sparse binding passes.
+            continue;
+        }
+        chain.set_info(info(i));
+        let old = map.insert(i, chain);
+        debug_assert!(old.is_none(), "Duplicate chain for resource {:?}", i);
     }
     map
 }
 
+fn reify_images<F>(
+    passes: &ResolvedPassSet,
+    vec: Vec<ChainData<Image>>,
+    info: F,
+) -> (ImageChains, ImageChains, ImagePartChains)
+where
+    F: Fn(Id<Image>) -> ResourceInfo<Image>,
+{
+    let (mut image_ids, mut image_chains) = (Vec::new(), Vec::new());
+    let (mut stencil_ids, mut stencil_chains) = (Vec::new(), Vec::new());
+    let mut parts: FnvHashMap<Id<Image>, Vec<(usize, Chain<Image>)>> = FnvHashMap::default();
+    for ((chain, &id), &part) in vec.into_iter().zip(&passes.images).zip(&passes.parts) {
+        match part {
+            ImagePart::Whole => {
+                image_ids.push(id);
+                image_chains.push(chain);
+            }
+            ImagePart::Stencil => {
+                stencil_ids.push(id);
+                stencil_chains.push(chain);
+            }
+            ImagePart::Range(index) => {
+                let mut chain = chain.chain;
+                chain.set_info(info(id));
+                chain.set_range(Some(passes.ranges[&id][index].clone()));
+                parts.entry(id).or_default().push((index, chain));
+            }
+        }
+    }
+    let parts = parts
+        .into_iter()
+        .map(|(id, mut chains)| {
+            chains.sort_by_key(|&(index, _)| index);
+            debug_assert!(chains.iter().enumerate().all(|(i, &(index, _))| i == index));
+            (id, chains.into_iter().map(|(_, chain)| chain).collect())
+        })
+        .collect();
+
+    let mut images = reify_chain(&image_ids, image_chains, &info);
+    let mut stencils = reify_chain(&stencil_ids, stencil_chains, &info);
+
+    // Layout transitions of split images must not touch the other aspect.
+    for (id, stencil) in &mut stencils {
+        let depth = images
+            .get_mut(id)
+            .expect("Stencil aspect is tracked separately only along with depth aspect");
+        let range = links_range(depth);
+        depth.set_range(range);
+        let range = links_range(stencil);
+        stencil.set_range(range);
+    }
+    (images, stencils, parts)
+}
+
+fn reify_buffers<F>(
+    passes: &ResolvedPassSet,
+    vec: Vec<ChainData<Buffer>>,
+    info: F,
+) -> (BufferChains, BufferPartChains)
+where
+    F: Fn(Id<Buffer>) -> ResourceInfo<Buffer>,
+{
+    let (mut buffer_ids, mut buffer_chains) = (Vec::new(), Vec::new());
+    let mut parts: FnvHashMap<Id<Buffer>, Vec<(usize, Chain<Buffer>)>> = FnvHashMap::default();
+    for ((chain, &id), &part) in vec.into_iter().zip(&passes.buffers).zip(&passes.buffer_parts) {
+        match part {
+            None => {
+                buffer_ids.push(id);
+                buffer_chains.push(chain);
+            }
+            Some(index) => {
+                let mut chain = chain.chain;
+                chain.set_info(info(id));
+                chain.set_range(Some(passes.buffer_ranges[&id][index].clone()));
+                parts.entry(id).or_default().push((index, chain));
+            }
+        }
+    }
+    let parts = parts
+        .into_iter()
+        .map(|(id, mut chains)| {
+            chains.sort_by_key(|&(index, _)| index);
+            debug_assert!(chains.iter().enumerate().all(|(i, &(index, _))| i == index));
+            (id, chains.into_iter().map(|(_, chain)| chain).collect())
+        })
+        .collect();
+    (reify_chain(&buffer_ids, buffer_chains, &info), parts)
+}
+
+/// Get union of ranges of all links.
+fn links_range<R: Resource>(chain: &Chain<R>) -> Option<R::Range> {
+    chain.links().iter().fold(None, |acc, link| match (acc, link.range()) {
+        (Some(acc), Some(range)) => Some(::resource::Range::union(&acc, range)),
+        (None, range) => range.cloned(),
+        (acc, None) => acc,
+    })
+}
+
 fn reify_schedule(ids: &[QueueId], vec: Vec<QueueData>) -> Schedule<Unsynchronized> {
     let mut schedule = Schedule::new();
     for (queue_data, &i) in vec.into_iter().zip(ids) {
         *schedule.ensure_queue(i) = queue_data.queue;
     }
+    debug_assert!(schedule.is_dense());
     schedule
 }
 
-fn fitness(
+/// Part of fitness of the pass that depends only on chains of its resources.
+struct ChainFitness {
+    /// Wait factor after the last links of the resources.
+    wait_factor: usize,
+
+    /// Number of resource transfers required for each family the pass may be executed on.
+    transfers: Vec<(QueueFamilyId, usize)>,
+}
+
+/// Find passes accessing each buffer and image. Indexed like chains being collected.
+fn resource_users(passes: &ResolvedPassSet) -> (Vec<Vec<usize>>, Vec<Vec<usize>>) {
+    let mut buffers: Vec<Vec<usize>> = fill(passes.buffers.len());
+    let mut images: Vec<Vec<usize>> = fill(passes.images.len());
+    for pass in &passes.passes {
+        for &(id, _) in &pass.buffers {
+            buffers[id].push(pass.id);
+        }
+        for &(id, _) in &pass.images {
+            images[id].push(pass.id);
+        }
+    }
+    (buffers, images)
+}
+
+fn chain_fitness(
     pass: &ResolvedPass,
-    images: &mut Vec<ChainData<Image>>,
-    buffers: &mut Vec<ChainData<Buffer>>,
-    schedule: &mut Vec<QueueData>,
-) -> (Fitness, usize) {
-    let mut transfers = 0;
-    let mut wait_factor_from_chains = 0;
+    queues: &[QueueId],
+    images: &[ChainData<Image>],
+    buffers: &[ChainData<Buffer>],
+) -> ChainFitness {
+    let mut wait_factor = 0;
 
-    // Collect minimal waits required and resource transfers count.
+    // Collect minimal waits required.
     for &(id, _) in &pass.buffers {
-        let chain = &buffers[id];
-        if chain.current_family.unwrap_or(pass.family) != pass.family {
-            transfers += 1;
-        }
-        wait_factor_from_chains = max(wait_factor_from_chains, chain.last_link_wait_factor);
+        wait_factor = max(wait_factor, buffers[id].last_link_wait_factor);
     }
     for &(id, _) in &pass.images {
-        let chain = &images[id];
-        if chain.current_family.unwrap_or(pass.family) != pass.family {
-            transfers += 1;
+        wait_factor = max(wait_factor, images[id].last_link_wait_factor);
+    }
+
+    // Count resource transfers required if pass is executed on the family.
+    // Sparse binding doesn't require ownership of the resource.
+    let transfers = |family| {
+        if pass.kind == PassKind::SparseBind {
+            return 0;
         }
-        wait_factor_from_chains = max(wait_factor_from_chains, chain.last_link_wait_factor);
+        let buffers = pass
+            .buffers
+            .iter()
+            .filter(|&&(id, _)| buffers[id].current_family.unwrap_or(family) != family)
+            .count();
+        let images = pass
+            .images
+            .iter()
+            .filter(|&&(id, _)| images[id].current_family.unwrap_or(family) != family)
+            .count();
+        buffers + images
+    };
+    let mut families: Vec<QueueFamilyId> =
+        pass.queues.iter().map(|&index| queues[index].family()).collect();
+    families.dedup();
+    ChainFitness {
+        wait_factor,
+        transfers: families
+            .into_iter()
+            .map(|family| (family, transfers(family)))
+            .collect(),
     }
+}
 
+fn fitness(pass: &ResolvedPass, chains: &ChainFitness, schedule: &[QueueData]) -> (Fitness, usize) {
     // Find best queue for pass.
-    let (wait_factor_from_queue, queue) = pass
-        .queues
-        .clone()
-        .map(|index| (schedule[index].wait_factor, index))
+    pass.queues
+        .iter()
+        .map(|&index| {
+            let family = schedule[index].queue.id().family();
+            let transfers = chains
+                .transfers
+                .iter()
+                .find(|&&(other, _)| other == family)
+                .map_or(0, |&(_, transfers)| transfers);
+            let fitness = Fitness {
+                transfers,
+                wait_factor: max(chains.wait_factor, schedule[index].wait_factor),
+                unpreferred: pass.preferred_family.is_some_and(|preferred| preferred != family),
+            };
+            (fitness, index)
+        })
         .min()
-        .unwrap();
-    (
-        Fitness {
-            transfers,
-            wait_factor: max(wait_factor_from_chains, wait_factor_from_queue),
-        },
-        queue,
-    )
+        .unwrap()
 }
 
 fn schedule_pass<'a>(
@@ -354,35 +2091,68 @@ fn schedule_pass<'a>(
     schedule: &mut Vec<QueueData>,
     images: &mut Vec<ChainData<Image>>,
     buffers: &mut Vec<ChainData<Buffer>>,
-) {
+) -> Result<(), CollectError> {
     let pid = passes.pass_ids[pass.id];
     let ref mut queue_data = schedule[queue];
-    queue_data.wait_factor = max(queue_data.wait_factor, wait_factor + 1);
-    let submission = Submission::new(wait_factor, submitted, pid, Unsynchronized);
+    // Wait factor after the pass is executed.
+    let finish = wait_factor + pass.cost;
+    queue_data.wait_factor = max(queue_data.wait_factor, finish);
+    let mut submission = Submission::new(wait_factor, submitted, pid, pass.kind, Unsynchronized);
+    submission.set_conditional(pass.conditional);
+    submission.set_meta(pass.meta);
     let sid = queue_data.queue.add_submission(submission);
     let ref mut submission = queue_data.queue[sid];
 
-    for &(id, StateUsage { state, usage }) in &pass.buffers {
-        add_to_chain(
-            passes.buffers[id],
-            pass.family,
-            &mut buffers[id],
-            sid,
-            submission,
-            state,
-            usage,
-        );
-    }
-    for &(id, StateUsage { state, usage }) in &pass.images {
-        add_to_chain(
-            passes.images[id],
-            pass.family,
-            &mut images[id],
-            sid,
-            submission,
-            state,
-            usage,
-        );
+    if pass.kind == PassKind::SparseBind {
+        // Bindings aren't links. They are ordered between links instead.
+        for &(id, _) in &pass.buffers {
+            add_bind(&mut buffers[id], sid, finish);
+        }
+        for &(index, _) in &pass.images {
+            add_bind(&mut images[index], sid, finish);
+        }
+    } else {
+        submission.set_resolves(pass.resolves.clone());
+        for &(index, ref state_usage) in &pass.buffers {
+            let id = passes.buffers[index];
+            let link = add_to_chain(
+                id,
+                pid,
+                &mut buffers[index],
+                sid,
+                finish,
+                state_usage.clone(),
+                passes.layout_policy,
+            )?;
+            let old = match passes.buffer_parts[index] {
+                None => Pick::<Buffer>::pick_mut(submission).insert(id, link),
+                Some(part) => submission.buffer_parts_mut().insert(id, (part, link)).map(|_| link),
+            };
+            debug_assert!(old.is_none(), "Buffer {:?} registered twice in submission", id);
+        }
+        for &(index, ref state_usage) in &pass.images {
+            let id = passes.images[index];
+            check_feedback_loop(id, pid, &images[index].chain, sid, state_usage)?;
+            let link = add_to_chain(
+                id,
+                pid,
+                &mut images[index],
+                sid,
+                finish,
+                state_usage.clone(),
+                passes.layout_policy,
+            )?;
+            let old = match passes.parts[index] {
+                ImagePart::Whole => Pick::<Image>::pick_mut(submission).insert(id, link),
+                ImagePart::Stencil => submission.stencils_mut().insert(id, link),
+                ImagePart::Range(part) => {
+                    let parts = submission.parts_mut().entry(id).or_default();
+                    parts.push((part, link));
+                    None
+                }
+            };
+            debug_assert!(old.is_none(), "Image {:?} registered twice in submission", id);
+        }
     }
 
     for &rev_dep in &pass.rev_deps {
@@ -391,42 +2161,120 @@ fn schedule_pass<'a>(
             ready_passes.push(&passes.passes[rev_dep]);
         }
     }
+    Ok(())
+}
+
+/// `finish` is wait factor after the binding is executed.
+fn add_bind<R: Resource>(chain_data: &mut ChainData<R>, sid: SubmissionId, finish: usize) {
+    chain_data.chain.add_bind(sid);
+    chain_data.last_link_wait_factor = max(finish, chain_data.last_link_wait_factor);
 }
 
-fn add_to_chain<R, S>(
+/// Add submission to the chain.
+/// `finish` is wait factor after the pass is executed.
+/// Returns index of the link.
+fn add_to_chain<R>(
     id: Id<R>,
-    family: QueueFamilyId,
+    pass: PassId,
     chain_data: &mut ChainData<R>,
     sid: SubmissionId,
-    submission: &mut Submission<S>,
-    state: State<R>,
-    usage: R::Usage,
-) where
+    finish: usize,
+    state_usage: StateUsage<R>,
+    policy: LayoutPolicy,
+) -> Result<usize, CollectError>
+where
+    R: Resource,
+    CollectError: From<(Id<R>, PassId, MergeError<R>)>,
+{
+    chain_data.current_family = Some(sid.family());
+    chain_data.current_link_wait_factor = max(finish, chain_data.current_link_wait_factor);
+
+    let chain_len = chain_data.chain.links().len();
+    let index = link_submission(id, pass, &mut chain_data.chain, sid, state_usage, policy)?;
+    if index == chain_len {
+        chain_data.last_link_wait_factor = chain_data.current_link_wait_factor;
+    }
+    Ok(index)
+}
+
+/// Check that image declaration doesn't join the last link of the chain that accesses
+/// the image as feedback loop while one of them is exclusive. Or vice versa.
+/// See `CollectError::FeedbackLoopConflict`.
+pub(crate) fn check_feedback_loop(
+    id: Id<Image>,
+    pass: PassId,
+    chain: &Chain<Image>,
+    sid: SubmissionId,
+    state_usage: &StateUsage<Image>,
+) -> Result<(), CollectError> {
+    let state = state_usage.state;
+    match chain.links().last() {
+        Some(link)
+            if !chain.bound()
+                && link.compatible(sid, state, state_usage.range.as_ref())
+                && ((state.is_feedback_loop() && link.state().exclusive())
+                    || (link.state().is_feedback_loop() && state.exclusive())) =>
+        {
+            Err(CollectError::FeedbackLoopConflict { id, pass, state })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Associate submission with the last link if compatible or with new link.
+/// Layouts of the link and the submission are merged according to `policy`.
+/// Returns index of the link.
+pub(crate) fn link_submission<R>(
+    id: Id<R>,
+    pass: PassId,
+    chain: &mut Chain<R>,
+    sid: SubmissionId,
+    state_usage: StateUsage<R>,
+    policy: LayoutPolicy,
+) -> Result<usize, CollectError>
+where
     R: Resource,
-    Submission<S>: Pick<R, Target = FnvHashMap<Id<R>, usize>>,
+    CollectError: From<(Id<R>, PassId, MergeError<R>)>,
 {
-    chain_data.current_family = Some(family);
-    chain_data.current_link_wait_factor = max(
-        submission.wait_factor() + 1,
-        chain_data.current_link_wait_factor,
-    );
+    let StateUsage {
+        state,
+        usage,
+        range,
+        discard,
+        ..
+    } = state_usage;
+    let usage = if usage == R::Usage::none() {
+        state.usage()
+    } else {
+        usage
+    };
 
-    let ref mut chain = chain_data.chain;
     let chain_len = chain.links().len();
+    let bound = chain.bound();
     let append = match chain.last_link_mut() {
-        Some(ref mut link) if link.compatible(sid, state) => {
-            submission.pick_mut().insert(id, chain_len - 1);
-            link.insert_submission(sid, state, usage);
+        // Links are never extended over sparse binding.
+        Some(ref mut link) if !bound && link.compatible(sid, state, range.as_ref()) => {
+            let layout = policy
+                .merge(link.state(), state)
+                .map_err(|error| (id, pass, error))?;
+            link.insert_submission(sid, state, usage, range);
+            link.set_layout(layout);
+            let discard = link.discard() && discard;
+            link.set_discard(discard);
             None
         }
         Some(_) | None => {
-            submission.pick_mut().insert(id, chain_len);
-            chain_data.last_link_wait_factor = chain_data.current_link_wait_factor;
-            Some(Link::new(sid, state, usage))
+            let mut link = Link::new(sid, state, usage, range);
+            link.set_discard(discard);
+            Some(link)
         }
     };
 
-    if let Some(link) = append {
-        chain.add_link(link);
+    match append {
+        Some(link) => {
+            chain.add_link(link);
+            Ok(chain_len)
+        }
+        None => Ok(chain_len - 1),
     }
 }
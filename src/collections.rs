@@ -0,0 +1,30 @@
+//!
+//! This module provides hash maps and sets used by the crate.
+//! Those are maps of `std` with `std` feature and maps of `hashbrown` otherwise,
+//! as `alloc` has none.
+//!
+
+#[cfg(feature = "std")]
+pub use fnv::{FnvHashMap, FnvHashSet};
+#[cfg(feature = "std")]
+pub use std::collections::{hash_map, HashMap};
+
+#[cfg(not(feature = "std"))]
+use core::hash::BuildHasherDefault;
+#[cfg(not(feature = "std"))]
+use fnv::FnvHasher;
+#[cfg(not(feature = "std"))]
+pub use hashbrown::hash_map;
+
+/// Hash map with FNV hasher.
+/// There is no source of random keys for hasher of `std` maps without `std`.
+#[cfg(not(feature = "std"))]
+pub type HashMap<K, V> = ::hashbrown::HashMap<K, V, BuildHasherDefault<FnvHasher>>;
+
+/// Same as `HashMap` without `std`.
+#[cfg(not(feature = "std"))]
+pub type FnvHashMap<K, V> = HashMap<K, V>;
+
+/// Hash set with FNV hasher.
+#[cfg(not(feature = "std"))]
+pub type FnvHashSet<T> = ::hashbrown::HashSet<T, BuildHasherDefault<FnvHasher>>;
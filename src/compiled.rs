@@ -0,0 +1,489 @@
+//!
+//! This module provides two-phase building of synchronized schedules.
+//! Topology of the pass graph is compiled once over logical resource slots.
+//! Each frame binds slots to concrete resources, which instantiates synchronization
+//! of the compiled schedule with concrete ids without collecting passes again.
+//!
+
+use alloc::vec::Vec;
+use collections::FnvHashMap;
+use core::fmt::{self, Display};
+use hal::queue::QueueFamilyId;
+#[cfg(feature = "std")]
+use std::error::Error;
+
+use chain::Chain;
+use collect::{Chains, CollectError};
+use pass::Pass;
+use resource::{Access, Buffer, Id, IdMap, Image, Layout, Resource, Resources, State};
+use schedule::{Schedule, SubmissionId};
+use sync::{
+    earliest, Barrier, Barriers, FeedbackLoop, Guard, Signal, SyncData, Wait, WaitEvent,
+};
+use {build, Pick};
+
+/// Map of slots to resources bound to them.
+type Bound<R> = FnvHashMap<Id<R>, Id<R>>;
+
+/// Error that can occur when slots of `CompiledGraph` are bound.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BindError {
+    /// Two buffer slots used by the graph are bound to the same buffer.
+    /// Their chains can't be merged without collecting passes again.
+    AliasedBuffer {
+        /// Id of the buffer.
+        id: Id<Buffer>,
+        /// Slots bound to the buffer.
+        slots: (Id<Buffer>, Id<Buffer>),
+    },
+
+    /// Two image slots used by the graph are bound to the same image.
+    /// Their chains can't be merged without collecting passes again.
+    AliasedImage {
+        /// Id of the image.
+        id: Id<Image>,
+        /// Slots bound to the image.
+        slots: (Id<Image>, Id<Image>),
+    },
+
+    /// Buffer slot is bound with previous state but the graph has no initial transition for it.
+    /// See `Binding::transition_buffer`.
+    UntransitionedBuffer {
+        /// The slot.
+        slot: Id<Buffer>,
+    },
+
+    /// Image slot is bound with previous state but the graph has no initial transition for it.
+    /// See `Binding::transition_image`.
+    UntransitionedImage {
+        /// The slot.
+        slot: Id<Image>,
+    },
+}
+
+impl Display for BindError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BindError::AliasedBuffer {
+                id,
+                slots: (left, right),
+            } => write!(
+                fmt,
+                "Buffer slots {:?} and {:?} are bound to the same buffer {:?}",
+                left, right, id
+            ),
+            BindError::AliasedImage {
+                id,
+                slots: (left, right),
+            } => write!(
+                fmt,
+                "Image slots {:?} and {:?} are bound to the same image {:?}",
+                left, right, id
+            ),
+            BindError::UntransitionedBuffer { slot } => {
+                write!(fmt, "Buffer slot {:?} has no initial transition", slot)
+            }
+            BindError::UntransitionedImage { slot } => {
+                write!(fmt, "Image slot {:?} has no initial transition", slot)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for BindError {
+    fn description(&self) -> &str {
+        match *self {
+            BindError::AliasedBuffer { .. } => "Buffer slots bound to the same buffer",
+            BindError::AliasedImage { .. } => "Image slots bound to the same image",
+            BindError::UntransitionedBuffer { .. } => "Buffer slot has no initial transition",
+            BindError::UntransitionedImage { .. } => "Image slot has no initial transition",
+        }
+    }
+}
+
+/// Concrete resources bound to logical slots of `CompiledGraph` for one frame.
+/// Slots that aren't bound are bound to resources with the same ids.
+#[derive(Clone, Debug, Default)]
+pub struct Binding {
+    buffers: Bound<Buffer>,
+    images: Bound<Image>,
+    buffer_states: FnvHashMap<Id<Buffer>, State<Buffer>>,
+    image_states: FnvHashMap<Id<Image>, State<Image>>,
+}
+
+impl Binding {
+    /// Create binding of all slots to resources with the same ids.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bind buffer slot to the buffer.
+    pub fn bind_buffer(mut self, slot: Id<Buffer>, id: Id<Buffer>) -> Self {
+        self.buffers.insert(slot, id);
+        self
+    }
+
+    /// Bind image slot to the image.
+    pub fn bind_image(mut self, slot: Id<Image>, id: Id<Image>) -> Self {
+        self.images.insert(slot, id);
+        self
+    }
+
+    /// Transition the buffer bound to the slot from `previous` state,
+    /// like its final state in the previous frame, instead of initial state of the slot.
+    /// The slot must be registered with initial state when the graph is compiled.
+    /// Buffer must be owned by the family of the first link unless it's shared concurrently.
+    pub fn transition_buffer(mut self, slot: Id<Buffer>, previous: State<Buffer>) -> Self {
+        self.buffer_states.insert(slot, previous);
+        self
+    }
+
+    /// Transition the image bound to the slot from `previous` state,
+    /// like its final state in the previous frame, instead of initial state of the slot.
+    /// The slot must be registered with initial state when the graph is compiled.
+    /// Image must be owned by the family of the first link unless it's shared concurrently.
+    pub fn transition_image(mut self, slot: Id<Image>, previous: State<Image>) -> Self {
+        self.image_states.insert(slot, previous);
+        self
+    }
+}
+
+/// Synchronized schedule built once over logical resource slots.
+///
+/// Ids declared by passes and registered in `Resources` are slots.
+/// Chains and submissions of the compiled schedule are tracked by slots,
+/// while synchronization instantiated by `bind` refers to concrete resources.
+#[derive(Debug)]
+pub struct CompiledGraph<S, W> {
+    chains: Chains<SyncData<S, W>>,
+}
+
+impl<S, W> CompiledGraph<S, W>
+where
+    S: Clone,
+    W: Clone,
+{
+    /// Compile passes declared over slots like `build` does.
+    /// Slots that are transitioned from previous frames must be registered with initial state.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `build`.
+    pub fn compile<F, Q>(
+        passes: Vec<Pass>,
+        resources: &Resources,
+        max_queues: Q,
+        new_semaphore: F,
+    ) -> Result<Self, CollectError>
+    where
+        Q: Fn(QueueFamilyId) -> usize,
+        F: FnMut() -> (S, W),
+    {
+        build(passes, resources, max_queues, new_semaphore).map(CompiledGraph::from_chains)
+    }
+
+    /// Use already built `Chains` as compiled graph.
+    pub fn from_chains(chains: Chains<SyncData<S, W>>) -> Self {
+        CompiledGraph { chains }
+    }
+
+    /// Get compiled chains and schedule tracked by slots.
+    pub fn chains(&self) -> &Chains<SyncData<S, W>> {
+        &self.chains
+    }
+
+    /// Get state of the buffer bound to the slot after the frame.
+    /// Or `None` if the slot isn't tracked as a whole.
+    pub fn final_buffer_state(&self, slot: Id<Buffer>) -> Option<State<Buffer>> {
+        self.chains
+            .buffers
+            .get(&slot)
+            .map(|chain| chain.final_state().state)
+    }
+
+    /// Get state of the image bound to the slot after the frame.
+    /// Or `None` if the slot isn't tracked as a whole.
+    pub fn final_image_state(&self, slot: Id<Image>) -> Option<State<Image>> {
+        if self.chains.stencils.contains_key(&slot) {
+            return None;
+        }
+        self.chains
+            .images
+            .get(&slot)
+            .map(|chain| chain.final_state().state)
+    }
+
+    /// Bind slots to concrete resources for one frame.
+    ///
+    /// # Errors
+    ///
+    /// Returns `BindError` if two slots used by the graph are bound to the same resource,
+    /// or if the slot bound with previous state has no initial transition.
+    pub fn bind(&self, binding: &Binding) -> Result<BoundGraph<'_, S, W>, BindError> {
+        let chains = &self.chains;
+        let buffers = bound_ids(
+            chains.buffers.keys().chain(chains.buffer_parts.keys()),
+            &binding.buffers,
+            |id, slots| BindError::AliasedBuffer { id, slots },
+        )?;
+        let images = bound_ids(
+            chains
+                .images
+                .keys()
+                .chain(chains.stencils.keys())
+                .chain(chains.parts.keys()),
+            &binding.images,
+            |id, slots| BindError::AliasedImage { id, slots },
+        )?;
+
+        let mut buffer_transitions = Vec::new();
+        for (slot, previous) in sorted(&binding.buffer_states) {
+            let transition = initial_transition(slot, &chains.buffers, &chains.schedule, previous)
+                .ok_or(BindError::UntransitionedBuffer { slot })?;
+            buffer_transitions.push(transition);
+        }
+        let mut image_transitions = Vec::new();
+        for (slot, previous) in sorted(&binding.image_states) {
+            let transition = if chains.stencils.contains_key(&slot) {
+                None
+            } else {
+                initial_transition(slot, &chains.images, &chains.schedule, previous)
+            };
+            image_transitions.push(transition.ok_or(BindError::UntransitionedImage { slot })?);
+        }
+
+        Ok(BoundGraph {
+            graph: self,
+            buffers,
+            images,
+            buffer_transitions,
+            image_transitions,
+        })
+    }
+}
+
+/// Transition of the slot from the previous state replacing its initial barrier.
+#[derive(Clone, Debug)]
+struct SlotTransition<R: Resource> {
+    slot: Id<R>,
+    sid: SubmissionId,
+    barrier: Barrier<R>,
+}
+
+/// Synchronization of `CompiledGraph` instantiated with concrete resources.
+#[derive(Clone, Debug)]
+pub struct BoundGraph<'a, S: 'a, W: 'a> {
+    graph: &'a CompiledGraph<S, W>,
+    buffers: Bound<Buffer>,
+    images: Bound<Image>,
+    buffer_transitions: Vec<SlotTransition<Buffer>>,
+    image_transitions: Vec<SlotTransition<Image>>,
+}
+
+impl<'a, S, W> BoundGraph<'a, S, W>
+where
+    S: Clone,
+    W: Clone,
+{
+    /// Get buffer bound to the slot.
+    pub fn buffer(&self, slot: Id<Buffer>) -> Id<Buffer> {
+        self.buffers.get(&slot).cloned().unwrap_or(slot)
+    }
+
+    /// Get image bound to the slot.
+    pub fn image(&self, slot: Id<Image>) -> Id<Image> {
+        self.images.get(&slot).cloned().unwrap_or(slot)
+    }
+
+    /// Get synchronization of the submission with concrete resources.
+    pub fn sync(&self, sid: SubmissionId, sync: &SyncData<S, W>) -> SyncData<S, W> {
+        let mut sync = sync.clone();
+        for transition in &self.buffer_transitions {
+            replace_initial(transition, sid, &mut sync);
+        }
+        for transition in &self.image_transitions {
+            replace_initial(transition, sid, &mut sync);
+        }
+        SyncData {
+            acquire: self.guard(&sync.acquire),
+            release: self.guard(&sync.release),
+            frame_begin: self.guard(&sync.frame_begin),
+            feedback_loops: rebind_feedback_loops(&sync.feedback_loops, &self.images),
+            fence: sync.fence,
+        }
+    }
+
+    /// Get compiled schedule with synchronization of concrete resources.
+    /// Submissions still refer to chains by slots.
+    pub fn schedule(&self) -> Schedule<SyncData<S, W>> {
+        let mut result = Schedule::default();
+        for queue in self.graph.chains.schedule.iter().flat_map(|family| family.iter()) {
+            let new_queue = result.ensure_queue(queue.id());
+            for (sid, submission) in queue.iter() {
+                let new_sid = new_queue
+                    .add_submission(submission.set_sync(self.sync(sid, submission.sync())));
+                assert_eq!(sid, new_sid);
+            }
+        }
+        result
+    }
+
+    fn guard(&self, guard: &Guard<S, W>) -> Guard<S, W> {
+        Guard {
+            buffers: rebind_barriers(&guard.buffers, &self.buffers),
+            images: rebind_barriers(&guard.images, &self.images),
+            parts: rebind_parts(&guard.parts, &self.images),
+            buffer_parts: rebind_parts(&guard.buffer_parts, &self.buffers),
+            wait_events: guard
+                .wait_events
+                .iter()
+                .map(|wait| WaitEvent {
+                    buffers: rebind_barriers(&wait.buffers, &self.buffers),
+                    images: rebind_barriers(&wait.images, &self.images),
+                    parts: rebind_parts(&wait.parts, &self.images),
+                    buffer_parts: rebind_parts(&wait.buffer_parts, &self.buffers),
+                    ..wait.clone()
+                })
+                .collect(),
+            wait_acquired: guard
+                .wait_acquired
+                .iter()
+                .map(|wait| Wait::new(self.image(*wait.semaphore()), wait.stage()))
+                .collect(),
+            signal_present: guard
+                .signal_present
+                .iter()
+                .map(|signal| Signal::new(self.image(*signal.semaphore())))
+                .collect(),
+            ..guard.clone()
+        }
+    }
+}
+
+/// Get previous states of slots sorted by slots.
+fn sorted<R: Resource>(states: &FnvHashMap<Id<R>, State<R>>) -> Vec<(Id<R>, State<R>)> {
+    let mut states: Vec<_> = states.iter().map(|(&slot, &state)| (slot, state)).collect();
+    states.sort_by_key(|&(slot, _)| slot);
+    states
+}
+
+/// Map slots used by the graph to bound ids.
+/// Slots bound to resources with the same ids are omitted.
+/// Returns error made by `aliased` from the id and the pair of slots
+/// if two slots are bound to the same id.
+fn bound_ids<'a, R, I, E>(used: I, binding: &Bound<R>, aliased: E) -> Result<Bound<R>, BindError>
+where
+    R: Resource + 'a,
+    I: IntoIterator<Item = &'a Id<R>>,
+    E: Fn(Id<R>, (Id<R>, Id<R>)) -> BindError,
+{
+    let mut slots: Vec<_> = used.into_iter().cloned().collect();
+    slots.sort();
+    slots.dedup();
+    let mut bound = Bound::default();
+    let mut ids = FnvHashMap::default();
+    for slot in slots {
+        let id = binding.get(&slot).cloned().unwrap_or(slot);
+        if let Some(&other) = ids.get(&id) {
+            return Err(aliased(id, (other, slot)));
+        }
+        ids.insert(id, slot);
+        if id != slot {
+            bound.insert(slot, id);
+        }
+    }
+    Ok(bound)
+}
+
+/// Find transition of the slot from the previous state to the state of its first link.
+/// Returns `None` unless the compiled schedule transitions the slot from its initial state.
+fn initial_transition<R, S>(
+    slot: Id<R>,
+    chains: &IdMap<R, Chain<R>>,
+    schedule: &Schedule<S>,
+    previous: State<R>,
+) -> Option<SlotTransition<R>>
+where
+    R: Resource,
+{
+    let chain = chains.get(&slot)?;
+    let info = chain.info();
+    let present = info.present && R::Layout::present().is_some();
+    if info.initial_state.is_none() || info.import.is_some() || present {
+        return None;
+    }
+    let first = chain.link(0);
+    let mut start = previous;
+    if chain.discards(0) {
+        // Previous content is overwritten anyway.
+        start.access = R::Access::none();
+        start.layout = R::Layout::discard_content();
+    }
+    Some(SlotTransition {
+        slot,
+        sid: earliest(first, schedule),
+        barrier: Barrier::new(start..first.state(), chain.range().cloned()),
+    })
+}
+
+/// Replace initial barrier of the slot with the transition.
+/// Barrier is always recorded at acquire side, even if the initial one was hoisted.
+fn replace_initial<R, S, W>(
+    transition: &SlotTransition<R>,
+    sid: SubmissionId,
+    sync: &mut SyncData<S, W>,
+) where
+    R: Resource,
+    Guard<S, W>: Pick<R, Target = Barriers<R>>,
+{
+    if sid == SubmissionId::new(transition.sid.queue(), 0) {
+        sync.frame_begin.pick_mut().remove(&transition.slot);
+    }
+    if sid == transition.sid {
+        sync.acquire
+            .pick_mut()
+            .insert(transition.slot, transition.barrier.clone());
+    }
+}
+
+fn rebind<R: Resource>(id: Id<R>, bound: &Bound<R>) -> Id<R> {
+    bound.get(&id).cloned().unwrap_or(id)
+}
+
+fn rebind_barriers<R: Resource>(
+    barriers: &Barriers<R>,
+    bound: &Bound<R>,
+) -> Barriers<R> {
+    barriers
+        .iter()
+        .map(|(&id, barrier)| (rebind(id, bound), barrier.clone()))
+        .collect()
+}
+
+fn rebind_parts<R: Resource>(
+    parts: &[(Id<R>, Barrier<R>)],
+    bound: &Bound<R>,
+) -> Vec<(Id<R>, Barrier<R>)> {
+    let mut parts: Vec<_> = parts
+        .iter()
+        .map(|&(id, ref barrier)| (rebind(id, bound), barrier.clone()))
+        .collect();
+    parts.sort_by_key(|&(id, _)| id);
+    parts
+}
+
+fn rebind_feedback_loops(
+    feedback_loops: &[FeedbackLoop],
+    bound: &Bound<Image>,
+) -> Vec<FeedbackLoop> {
+    let mut feedback_loops: Vec<_> = feedback_loops
+        .iter()
+        .map(|feedback_loop| FeedbackLoop {
+            id: rebind(feedback_loop.id, bound),
+            ..feedback_loop.clone()
+        })
+        .collect();
+    feedback_loops.sort_by_key(|feedback_loop| feedback_loop.id);
+    feedback_loops
+}
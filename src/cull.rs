@@ -0,0 +1,156 @@
+//!
+//! This module provides culling of passes which results are never consumed.
+//! Culling is performed before `collect`, so culled passes are neither scheduled
+//! nor synchronized.
+//!
+
+use alloc::vec::Vec;
+use collections::FnvHashSet;
+use collections::HashMap;
+
+use pass::{Pass, PassId, PassKind, StateUsage};
+use resource::{Access, Buffer, Id, Image, Resource};
+
+/// Passes left after culling.
+#[derive(Clone, Debug)]
+pub struct Culled {
+    /// Passes that contribute to the roots. In original order.
+    pub passes: Vec<Pass>,
+
+    /// Ids of culled passes. Sorted.
+    /// Commands of those passes must not be recorded.
+    pub culled: Vec<PassId>,
+}
+
+/// Cull passes none of whose writes are transitively consumed by roots.
+///
+/// Pass is retained if it has side effects, writes a root resource,
+/// writes a resource read by a retained pass or a retained pass depends on it.
+/// Partial writes of a resource count as reads of its previous content.
+/// Sparse binding pass is retained if it binds a resource accessed by a retained pass.
+///
+/// Ordering of passes is not considered, so pass that writes resource after its last read
+/// is retained if the read is. Resources used after the schedule, like exported or presented
+/// ones, must be listed as roots explicitly.
+pub fn retain_roots(passes: Vec<Pass>, images: &[Id<Image>], buffers: &[Id<Buffer>]) -> Culled {
+    let mut consumed = Consumed {
+        passes: FnvHashSet::default(),
+        buffers: buffers.iter().cloned().collect(),
+        images: images.iter().cloned().collect(),
+        accessed_buffers: FnvHashSet::default(),
+        accessed_images: FnvHashSet::default(),
+    };
+
+    let mut retained = vec![false; passes.len()];
+    loop {
+        let mut changed = false;
+        for (index, pass) in passes.iter().enumerate() {
+            if retained[index] || !consumed.retains(pass) {
+                continue;
+            }
+            retained[index] = true;
+            changed = true;
+            consumed.add(pass);
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    let mut culled = Vec::new();
+    let passes = passes
+        .into_iter()
+        .zip(retained)
+        .filter_map(|(pass, retained)| {
+            if retained {
+                Some(pass)
+            } else {
+                culled.push(pass.id);
+                None
+            }
+        })
+        .collect();
+    culled.sort();
+    Culled { passes, culled }
+}
+
+/// Resources and passes consumed by roots and retained passes.
+struct Consumed {
+    passes: FnvHashSet<PassId>,
+    buffers: FnvHashSet<Id<Buffer>>,
+    images: FnvHashSet<Id<Image>>,
+    accessed_buffers: FnvHashSet<Id<Buffer>>,
+    accessed_images: FnvHashSet<Id<Image>>,
+}
+
+impl Consumed {
+    /// Check if pass must be retained.
+    fn retains(&self, pass: &Pass) -> bool {
+        if pass.side_effects || self.passes.contains(&pass.id) {
+            return true;
+        }
+        let images = pass
+            .images
+            .iter()
+            .chain(pass.image_ranges.iter().flat_map(|(id, ranges)| {
+                ranges.iter().map(move |state_usage| (id, state_usage))
+            }));
+        match pass.kind {
+            PassKind::SparseBind => {
+                pass.buffers.keys().any(|id| {
+                    self.buffers.contains(id) || self.accessed_buffers.contains(id)
+                }) || images.map(|(id, _)| id).any(|id| {
+                    self.images.contains(id) || self.accessed_images.contains(id)
+                })
+            }
+            PassKind::Commands | PassKind::RenderPass => {
+                pass.buffers
+                    .iter()
+                    .any(|(id, state_usage)| writes(state_usage) && self.buffers.contains(id))
+                    || images
+                        .filter(|&(_, state_usage)| writes(state_usage))
+                        .any(|(id, _)| self.images.contains(id))
+            }
+        }
+    }
+
+    /// Add resources and passes the retained pass consumes.
+    fn add(&mut self, pass: &Pass) {
+        self.passes.extend(pass.dependencies.iter().cloned());
+        if pass.kind == PassKind::SparseBind {
+            return;
+        }
+        add_resources(&pass.buffers, &mut self.buffers, &mut self.accessed_buffers);
+        add_resources(&pass.images, &mut self.images, &mut self.accessed_images);
+        for (&id, ranges) in &pass.image_ranges {
+            self.accessed_images.insert(id);
+            if ranges.iter().any(reads) {
+                self.images.insert(id);
+            }
+        }
+    }
+}
+
+fn add_resources<R>(
+    declared: &HashMap<Id<R>, StateUsage<R>>,
+    consumed: &mut FnvHashSet<Id<R>>,
+    accessed: &mut FnvHashSet<Id<R>>,
+) where
+    R: Resource,
+{
+    for (&id, state_usage) in declared {
+        accessed.insert(id);
+        if reads(state_usage) {
+            consumed.insert(id);
+        }
+    }
+}
+
+fn writes<R: Resource>(state_usage: &StateUsage<R>) -> bool {
+    state_usage.discard || state_usage.state.access.is_write()
+}
+
+/// Check if declaration observes previous content of the resource.
+fn reads<R: Resource>(state_usage: &StateUsage<R>) -> bool {
+    !state_usage.discard && (state_usage.state.access.is_read() || state_usage.range.is_some())
+}
@@ -0,0 +1,336 @@
+//!
+//! This module provides building of synchronized schedules for a group of devices.
+//! Each device gets its own `Chains` built from its passes like `build` does,
+//! so states of the same resource are tracked independently per device.
+//! Dependencies between devices are expressed with external semaphores instead of barriers.
+//!
+
+use alloc::vec::Vec;
+use collections::FnvHashMap;
+use core::ops::Range;
+use hal::queue::QueueFamilyId;
+
+use chain::Chain;
+use collect::{Chains, CollectError};
+use pass::{Pass, PassId};
+use resource::{Buffer, Id, IdMap, Image, Resource, Resources, State};
+use schedule::{Submission, SubmissionId};
+use sync::SyncData;
+use {build, Pick};
+
+/// Index of the device in the group.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DeviceIndex(pub usize);
+
+/// Resource which content is produced on another device.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DeviceResource {
+    /// Buffer resource.
+    Buffer(Id<Buffer>),
+
+    /// Image resource.
+    Image(Id<Image>),
+}
+
+impl From<Id<Buffer>> for DeviceResource {
+    fn from(id: Id<Buffer>) -> Self {
+        DeviceResource::Buffer(id)
+    }
+}
+
+impl From<Id<Image>> for DeviceResource {
+    fn from(id: Id<Image>) -> Self {
+        DeviceResource::Image(id)
+    }
+}
+
+/// Pass executed on one device of the group.
+/// Dependencies of the pass must be executed on the same device.
+#[derive(Clone, Debug)]
+pub struct DevicePass {
+    /// Device that executes the pass.
+    pub device: DeviceIndex,
+
+    /// Description of the pass.
+    pub pass: Pass,
+
+    /// Resources which content the pass consumes after it's produced on other devices.
+    pub waits: Vec<(DeviceIndex, DeviceResource)>,
+}
+
+impl DevicePass {
+    /// Create pass executed on the device.
+    pub fn new(device: DeviceIndex, pass: Pass) -> Self {
+        DevicePass {
+            device,
+            pass,
+            waits: Vec::new(),
+        }
+    }
+
+    /// Make the pass wait until the resource is produced on other device.
+    /// The pass must declare the resource and the other device must access it.
+    /// Content is not transferred between devices, see `DeviceWait`.
+    pub fn waits_on_device<T>(mut self, device: DeviceIndex, resource: T) -> Self
+    where
+        T: Into<DeviceResource>,
+    {
+        self.waits.push((device, resource.into()));
+        self
+    }
+}
+
+/// Dependency between submissions of two devices.
+///
+/// User must signal the semaphore after release side of the `signal` submission
+/// and wait for it before acquire side of the `wait` one. Content of the resource
+/// must be copied between devices and transitioned by the user as well.
+#[derive(Clone, Debug)]
+pub struct DeviceWait<R: Resource, S, W> {
+    /// Id of the resource.
+    pub id: Id<R>,
+
+    /// Device and the last submission of the device that accesses the resource.
+    pub signal: (DeviceIndex, SubmissionId),
+
+    /// Device and the submission of the waiting pass.
+    pub wait: (DeviceIndex, SubmissionId),
+
+    /// Final state of the resource on the producing device
+    /// and state required by the waiting pass.
+    pub states: Range<State<R>>,
+
+    /// External semaphore pair created for the dependency.
+    pub semaphore: (S, W),
+}
+
+/// Error that can occur during building of `DeviceChains`.
+#[derive(Clone, Debug)]
+pub enum DeviceError {
+    /// Collection of chains of the device failed.
+    Collect {
+        /// Index of the device.
+        device: DeviceIndex,
+
+        /// Collection error.
+        error: CollectError,
+    },
+
+    /// Resource waited by the pass isn't tracked as a whole on the device.
+    /// Either the device doesn't use it or tracks it in parts or by aspects.
+    Untracked {
+        /// Id of the waiting pass.
+        pass: PassId,
+
+        /// Index of the device.
+        device: DeviceIndex,
+
+        /// The resource.
+        resource: DeviceResource,
+    },
+}
+
+/// Synchronized schedules of a group of devices.
+#[derive(Debug)]
+pub struct DeviceChains<S, W> {
+    devices: Vec<(DeviceIndex, Chains<SyncData<S, W>>)>,
+    buffers: Vec<DeviceWait<Buffer, S, W>>,
+    images: Vec<DeviceWait<Image, S, W>>,
+}
+
+impl<S, W> DeviceChains<S, W> {
+    /// Get `Chains` of the device.
+    /// Returns `None` if the device executes no passes.
+    pub fn device(&self, device: DeviceIndex) -> Option<&Chains<SyncData<S, W>>> {
+        self.devices
+            .iter()
+            .find(|&&(index, _)| index == device)
+            .map(|(_, chains)| chains)
+    }
+
+    /// Get `Chains` of all devices that execute passes, sorted by device.
+    pub fn devices(&self) -> &[(DeviceIndex, Chains<SyncData<S, W>>)] {
+        &self.devices
+    }
+
+    /// Get dependencies between devices on buffers,
+    /// in order of waiting passes and then of their waits.
+    pub fn buffer_waits(&self) -> &[DeviceWait<Buffer, S, W>] {
+        &self.buffers
+    }
+
+    /// Get dependencies between devices on images,
+    /// in order of waiting passes and then of their waits.
+    pub fn image_waits(&self) -> &[DeviceWait<Image, S, W>] {
+        &self.images
+    }
+}
+
+/// Build synchronized schedules of the execution on a group of devices.
+///
+/// Passes of each device are built with `build` independently of other devices.
+/// Waits on other devices produce a `DeviceWait` for every queue that accesses
+/// the resource last on the producing device.
+///
+/// # Parameters
+///
+/// `passes`        - array of pass descriptions with devices executing them.
+/// `resources`     - properties of resources used by passes. The same for all devices.
+/// `max_queues`    - function that returns maximum number of queues for specified family
+///                   of the device.
+/// `new_semaphore` - function to create new semaphore pair - (signal, wait).
+///
+/// # Panics
+///
+/// This function will panic if the pass waits on its own device.
+pub fn build_group<F, Q, S, W>(
+    passes: Vec<DevicePass>,
+    resources: &Resources,
+    max_queues: Q,
+    mut new_semaphore: F,
+) -> Result<DeviceChains<S, W>, DeviceError>
+where
+    Q: Fn(DeviceIndex, QueueFamilyId) -> usize,
+    F: FnMut() -> (S, W),
+    S: Clone,
+    W: Clone,
+{
+    let mut grouped: Vec<(DeviceIndex, Vec<Pass>)> = Vec::new();
+    let mut waits = Vec::new();
+    for pass in passes {
+        for &(device, resource) in &pass.waits {
+            assert_ne!(device, pass.device, "Pass waits on its own device");
+            waits.push((pass.device, pass.pass.id, device, resource));
+        }
+        match grouped
+            .iter()
+            .position(|&(device, _)| device == pass.device)
+        {
+            Some(index) => grouped[index].1.push(pass.pass),
+            None => grouped.push((pass.device, vec![pass.pass])),
+        }
+    }
+    grouped.sort_by_key(|&(device, _)| device);
+
+    let mut devices = Vec::new();
+    for (device, passes) in grouped {
+        let chains = build(
+            passes,
+            resources,
+            |family| max_queues(device, family),
+            &mut new_semaphore,
+        )
+        .map_err(|error| DeviceError::Collect { device, error })?;
+        devices.push((device, chains));
+    }
+
+    let mut buffers = Vec::new();
+    let mut images = Vec::new();
+    for (device, pass, other, resource) in waits {
+        let untracked = |device| DeviceError::Untracked {
+            pass,
+            device,
+            resource,
+        };
+        let find = |device| {
+            devices
+                .iter()
+                .find(|&&(index, _)| index == device)
+                .map(|(_, chains)| chains)
+        };
+        let chains = find(device).unwrap();
+        let (sid, submission) = chains
+            .schedule
+            .iter()
+            .flat_map(|family| family.iter())
+            .flat_map(|queue| queue.iter())
+            .find(|&(_, submission)| submission.pass() == pass && !submission.is_transition())
+            .expect("Pass is not scheduled");
+        let producer = find(other).ok_or_else(|| untracked(other))?;
+        match resource {
+            DeviceResource::Buffer(id) => {
+                let state = consumer_state(id, &chains.buffers, submission)
+                    .ok_or_else(|| untracked(device))?;
+                let chain = producer.buffers.get(&id).ok_or_else(|| untracked(other))?;
+                buffers.extend(device_waits(
+                    id,
+                    other,
+                    chain,
+                    (device, sid),
+                    state,
+                    &mut new_semaphore,
+                ));
+            }
+            DeviceResource::Image(id) => {
+                if chains.stencils.contains_key(&id) {
+                    return Err(untracked(device));
+                }
+                if producer.stencils.contains_key(&id) {
+                    return Err(untracked(other));
+                }
+                let state = consumer_state(id, &chains.images, submission)
+                    .ok_or_else(|| untracked(device))?;
+                let chain = producer.images.get(&id).ok_or_else(|| untracked(other))?;
+                images.extend(device_waits(
+                    id,
+                    other,
+                    chain,
+                    (device, sid),
+                    state,
+                    &mut new_semaphore,
+                ));
+            }
+        }
+    }
+
+    Ok(DeviceChains {
+        devices,
+        buffers,
+        images,
+    })
+}
+
+/// Get state of the resource required by the submission.
+/// Returns `None` if the submission doesn't access the resource as a whole.
+fn consumer_state<R, S>(
+    id: Id<R>,
+    chains: &IdMap<R, Chain<R>>,
+    submission: &Submission<S>,
+) -> Option<State<R>>
+where
+    R: Resource,
+    Submission<S>: Pick<R, Target = FnvHashMap<Id<R>, usize>>,
+{
+    let index = *Pick::<R>::pick(submission).get(&id)?;
+    Some(chains.get(&id)?.link(index).state())
+}
+
+/// Create dependencies of the waiting submission on the last accesses of the resource
+/// on the producing device. One for each queue that accesses it last.
+fn device_waits<R, F, S, W>(
+    id: Id<R>,
+    signal: DeviceIndex,
+    producer: &Chain<R>,
+    wait: (DeviceIndex, SubmissionId),
+    state: State<R>,
+    new_semaphore: &mut F,
+) -> Vec<DeviceWait<R, S, W>>
+where
+    R: Resource,
+    F: FnMut() -> (S, W),
+{
+    let states = producer.final_state().state..state;
+    producer
+        .links()
+        .last()
+        .unwrap()
+        .queues()
+        .map(|(qid, queue)| DeviceWait {
+            id,
+            signal: (signal, SubmissionId::new(qid, queue.last)),
+            wait,
+            states: states.clone(),
+            semaphore: new_semaphore(),
+        })
+        .collect()
+}
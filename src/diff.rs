@@ -0,0 +1,423 @@
+//!
+//! This module provides comparison of synchronization of two schedules.
+//! Submissions are matched by pass, so inserting a pass doesn't report
+//! synchronization of every following submission as changed.
+//!
+
+use alloc::vec::Vec;
+use collections::FnvHashMap;
+use core::hash::Hash;
+use hal::pso::PipelineStage;
+
+use pass::PassId;
+use resource::{Buffer, Id, Image, Resource};
+use schedule::{QueueId, Schedule, Submission, SubmissionId};
+use sync::{Barrier, Barriers, Guard, SyncData};
+
+/// Place of synchronization command relative to main commands of the submission.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum GuardSide {
+    /// Barriers recorded at the beginning of the frame (only first submission of the queue).
+    FrameBegin,
+
+    /// Synchronization recorded before main commands.
+    Acquire,
+
+    /// Synchronization recorded after main commands.
+    Release,
+}
+
+/// Barrier attached to the submission of the pass.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PassBarrier<R: Resource> {
+    /// Pass of the submission.
+    pub pass: PassId,
+
+    /// Side of the submission.
+    pub side: GuardSide,
+
+    /// Barrier is performed by waiting for an event.
+    pub split: bool,
+
+    /// Id of the resource.
+    pub id: Id<R>,
+
+    /// The barrier.
+    pub barrier: Barrier<R>,
+}
+
+/// Semaphore dependency between submissions of two passes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SemaphoreEdge {
+    /// Pass which submission signals the semaphore.
+    pub signal: PassId,
+
+    /// Pass which submission waits for the semaphore.
+    pub wait: PassId,
+
+    /// Side of the waiting submission.
+    pub side: GuardSide,
+
+    /// Stage at which semaphore is waited.
+    pub stage: PipelineStage,
+}
+
+/// Change of synchronization record.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Change<T> {
+    /// Record exists only in new schedule.
+    Added(T),
+
+    /// Record exists only in old schedule.
+    Removed(T),
+
+    /// Record for the same resource or semaphore dependency differs.
+    Modified {
+        /// Record of old schedule.
+        old: T,
+
+        /// Record of new schedule.
+        new: T,
+    },
+}
+
+/// Changed synchronization of one pass.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SubmissionDiff {
+    /// Id of the pass.
+    pub pass: PassId,
+
+    /// Submission of the pass in old schedule. `None` if pass is added.
+    pub old: Option<SubmissionId>,
+
+    /// Submission of the pass in new schedule. `None` if pass is removed.
+    pub new: Option<SubmissionId>,
+
+    /// Changes of buffer barriers.
+    pub buffers: Vec<Change<PassBarrier<Buffer>>>,
+
+    /// Changes of image barriers.
+    pub images: Vec<Change<PassBarrier<Image>>>,
+
+    /// Changes of semaphores waited by the submission.
+    pub semaphores: Vec<Change<SemaphoreEdge>>,
+}
+
+/// Changed submissions of one queue.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QueueDiff {
+    /// Id of the queue.
+    pub id: QueueId,
+
+    /// Changed submissions in submission order.
+    /// Submissions of removed passes are placed by their index in old schedule.
+    pub submissions: Vec<SubmissionDiff>,
+}
+
+/// Difference between synchronization of two schedules.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SyncDiff {
+    queues: Vec<QueueDiff>,
+}
+
+impl SyncDiff {
+    /// Check if synchronization is unchanged.
+    pub fn is_empty(&self) -> bool {
+        self.queues.is_empty()
+    }
+
+    /// Get queues with changed submissions, sorted by id.
+    /// Pass moved to another queue is listed under queue of new schedule.
+    pub fn queues(&self) -> &[QueueDiff] {
+        &self.queues
+    }
+}
+
+/// Compare synchronization of two schedules.
+///
+/// Submissions are matched by pass, transition nodes with nodes of the same pass.
+/// Submission of the same pass with identical barriers and semaphore dependencies
+/// is unchanged even if its index or queue differ.
+/// Only the queue change is reported for the latter.
+/// Semaphores are compared by passes they connect, so schedules may use different semaphores.
+/// Events are compared only by barriers they perform.
+pub fn diff<T>(old: &Schedule<SyncData<T, T>>, new: &Schedule<SyncData<T, T>>) -> SyncDiff
+where
+    T: Eq + Hash,
+{
+    let old_signals = signals(old);
+    let new_signals = signals(new);
+
+    let mut old_passes = FnvHashMap::default();
+    for family in old.iter() {
+        for queue in family {
+            for (sid, submission) in queue.iter() {
+                let key = (submission.pass(), submission.is_transition());
+                old_passes.insert(key, (sid, submission));
+            }
+        }
+    }
+
+    let mut submissions = Vec::new();
+    for family in new.iter() {
+        for queue in family {
+            for (sid, submission) in queue.iter() {
+                let new_records = Records::new(sid, submission, &new_signals);
+                let key = (submission.pass(), submission.is_transition());
+                let diff = match old_passes.remove(&key) {
+                    Some((old_sid, old_submission)) => {
+                        let old_records = Records::new(old_sid, old_submission, &old_signals);
+                        if old_records == new_records && old_sid.queue() == sid.queue() {
+                            continue;
+                        }
+                        old_records.diff(new_records, Some(old_sid), Some(sid))
+                    }
+                    None => Records::default().diff(new_records, None, Some(sid)),
+                };
+                submissions.push(diff);
+            }
+        }
+    }
+    for (_, (old_sid, old_submission)) in old_passes {
+        let old_records = Records::new(old_sid, old_submission, &old_signals);
+        submissions.push(old_records.diff(Records::default(), Some(old_sid), None));
+    }
+
+    submissions.sort_by_key(|diff| {
+        let sid = diff.new.or(diff.old).unwrap();
+        (sid.queue(), sid.index())
+    });
+
+    let mut queues: Vec<QueueDiff> = Vec::new();
+    for diff in submissions {
+        let id = diff.new.or(diff.old).unwrap().queue();
+        match queues.last_mut() {
+            Some(ref mut queue) if queue.id == id => {
+                queue.submissions.push(diff);
+                continue;
+            }
+            _ => {}
+        }
+        queues.push(QueueDiff {
+            id,
+            submissions: vec![diff],
+        });
+    }
+    SyncDiff { queues }
+}
+
+/// Passes signaling semaphores with signaled values.
+type Signals<'a, T> = FnvHashMap<&'a T, Vec<(u64, PassId)>>;
+
+fn signals<T>(schedule: &Schedule<SyncData<T, T>>) -> Signals<'_, T>
+where
+    T: Eq + Hash,
+{
+    let mut signals = Signals::default();
+    for family in schedule.iter() {
+        for queue in family {
+            for (_, submission) in queue.iter() {
+                let sync = submission.sync();
+                for signal in sync.acquire.signal.iter().chain(&sync.release.signal) {
+                    signals
+                        .entry(signal.semaphore())
+                        .or_insert_with(Vec::new)
+                        .push((signal.value(), submission.pass()));
+                }
+            }
+        }
+    }
+    for signals in signals.values_mut() {
+        signals.sort();
+    }
+    signals
+}
+
+/// Synchronization records of one submission sorted by resource.
+#[derive(Default, PartialEq)]
+struct Records {
+    pass: Option<PassId>,
+    buffers: Vec<PassBarrier<Buffer>>,
+    images: Vec<PassBarrier<Image>>,
+    semaphores: Vec<SemaphoreEdge>,
+}
+
+impl Records {
+    fn new<T>(
+        sid: SubmissionId,
+        submission: &Submission<SyncData<T, T>>,
+        signals: &Signals<T>,
+    ) -> Self
+    where
+        T: Eq + Hash,
+    {
+        let sync = submission.sync();
+        let mut records = Records {
+            pass: Some(submission.pass()),
+            ..Records::default()
+        };
+        if sid.index() == 0 {
+            records.guard(GuardSide::FrameBegin, &sync.frame_begin, signals);
+        }
+        records.guard(GuardSide::Acquire, &sync.acquire, signals);
+        records.guard(GuardSide::Release, &sync.release, signals);
+
+        records.buffers.sort_by_key(barrier_key);
+        records.images.sort_by_key(barrier_key);
+        records
+            .semaphores
+            .sort_by_key(|edge| (edge.side, edge.signal, edge.stage.bits()));
+        records
+    }
+
+    fn guard<T>(&mut self, side: GuardSide, guard: &Guard<T, T>, signals: &Signals<T>)
+    where
+        T: Eq + Hash,
+    {
+        let pass = self.pass.unwrap();
+        for wait in &guard.wait {
+            let signal = signals.get(wait.semaphore()).and_then(|signals| {
+                signals
+                    .iter()
+                    .find(|&&(value, _)| value >= wait.value())
+                    .map(|&(_, pass)| pass)
+            });
+            if let Some(signal) = signal {
+                self.semaphores.push(SemaphoreEdge {
+                    signal,
+                    wait: pass,
+                    side,
+                    stage: wait.stage(),
+                });
+            }
+        }
+
+        let (buffers, images) = (&guard.buffers, &guard.images);
+        self.barriers(
+            side,
+            false,
+            buffers,
+            images,
+            &guard.parts,
+            &guard.buffer_parts,
+        );
+        for wait in &guard.wait_events {
+            self.barriers(
+                side,
+                true,
+                &wait.buffers,
+                &wait.images,
+                &wait.parts,
+                &wait.buffer_parts,
+            );
+        }
+    }
+
+    fn barriers(
+        &mut self,
+        side: GuardSide,
+        split: bool,
+        buffers: &Barriers<Buffer>,
+        images: &Barriers<Image>,
+        parts: &[(Id<Image>, Barrier<Image>)],
+        buffer_parts: &[(Id<Buffer>, Barrier<Buffer>)],
+    ) {
+        let pass = self.pass.unwrap();
+        let parts = parts.iter().map(|(id, barrier)| (id, barrier));
+        let buffer_parts = buffer_parts.iter().map(|(id, barrier)| (id, barrier));
+        push_barriers(
+            &mut self.buffers,
+            pass,
+            side,
+            split,
+            buffers.iter().chain(buffer_parts),
+        );
+        push_barriers(
+            &mut self.images,
+            pass,
+            side,
+            split,
+            images.iter().chain(parts),
+        );
+    }
+
+    fn diff(
+        self,
+        new: Records,
+        old_sid: Option<SubmissionId>,
+        sid: Option<SubmissionId>,
+    ) -> SubmissionDiff {
+        SubmissionDiff {
+            pass: new.pass.or(self.pass).unwrap(),
+            old: old_sid,
+            new: sid,
+            buffers: changes(self.buffers, new.buffers, barrier_key),
+            images: changes(self.images, new.images, barrier_key),
+            semaphores: changes(self.semaphores, new.semaphores, |edge| {
+                (edge.side, edge.signal)
+            }),
+        }
+    }
+}
+
+fn push_barriers<'a, R, I>(
+    records: &mut Vec<PassBarrier<R>>,
+    pass: PassId,
+    side: GuardSide,
+    split: bool,
+    barriers: I,
+) where
+    R: Resource + 'a,
+    I: Iterator<Item = (&'a Id<R>, &'a Barrier<R>)>,
+{
+    records.extend(barriers.map(|(&id, barrier)| PassBarrier {
+        pass,
+        side,
+        split,
+        id,
+        barrier: barrier.clone(),
+    }));
+}
+
+/// Barriers of the same resource at the same side are compared with each other.
+fn barrier_key<R: Resource>(record: &PassBarrier<R>) -> (GuardSide, bool, Id<R>) {
+    (record.side, record.split, record.id)
+}
+
+/// Find changes between records.
+/// Equal records are unchanged. Remaining records with the same key are paired in order
+/// as modified ones.
+fn changes<T, K, F>(old: Vec<T>, new: Vec<T>, key: F) -> Vec<Change<T>>
+where
+    T: PartialEq,
+    K: PartialEq,
+    F: Fn(&T) -> K,
+{
+    if old == new {
+        return Vec::new();
+    }
+
+    let mut new: Vec<_> = new.into_iter().map(Some).collect();
+    let mut removed = Vec::new();
+    for record in old {
+        match new.iter().position(|new| new.as_ref() == Some(&record)) {
+            Some(index) => new[index] = None,
+            None => removed.push(record),
+        }
+    }
+
+    let mut changes = Vec::new();
+    for old in removed {
+        let old_key = key(&old);
+        let modified = new
+            .iter_mut()
+            .find(|new| new.iter().any(|new| key(new) == old_key))
+            .and_then(Option::take);
+        changes.push(match modified {
+            Some(new) => Change::Modified { old, new },
+            None => Change::Removed(old),
+        });
+    }
+    changes.extend(new.into_iter().flatten().map(Change::Added));
+    changes
+}
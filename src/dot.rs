@@ -0,0 +1,176 @@
+//!
+//! This module provides export of the dependency graph in Graphviz DOT format.
+//! Each submission is a node. Each edge is a dependency between submissions induced by
+//! consecutive links of some resource chain.
+//!
+
+use std::io::{self, Write};
+
+use chain::{Chain, Link};
+use collect::Chains;
+use resource::{Buffer, Id, IdMap, Image, Resource, State};
+use schedule::{Family, SubmissionId};
+
+/// Write dependency graph of the `Chains` in DOT format.
+///
+/// Submissions of one queue are grouped into cluster.
+/// Dependencies between submissions of the same queue (resolved with pipeline barrier) are drawn
+/// as solid edges.
+/// Dependencies between different queues (resolved with semaphore) are drawn as dashed edges.
+pub fn write_dot<S, W: Write>(chains: &Chains<S>, w: &mut W) -> io::Result<()> {
+    writeln!(w, "digraph chains {{")?;
+    writeln!(w, "    node [shape=box];")?;
+
+    let mut families: Vec<&Family<S>> = chains.schedule.iter().collect();
+    families.sort_by_key(|family| family.id());
+
+    for family in families {
+        for queue in family {
+            let qid = queue.id();
+            writeln!(
+                w,
+                "    subgraph cluster_{}_{} {{",
+                qid.family().0,
+                qid.index()
+            )?;
+            writeln!(
+                w,
+                "        label=\"Queue {}:{}\";",
+                qid.family().0,
+                qid.index()
+            )?;
+            for (sid, submission) in queue.iter() {
+                let meta = submission.meta();
+                let mut label = String::new();
+                if let Some(name) = meta.name {
+                    label += &format!("\\n{}", name.replace('\\', "\\\\").replace('"', "\\\""));
+                }
+                if meta.tag != 0 {
+                    label += &format!("\\nTag {}", meta.tag);
+                }
+                // Transition node records barriers of the next submission of the same pass.
+                let kind = if submission.is_transition() {
+                    "Transition"
+                } else {
+                    "Pass"
+                };
+                writeln!(
+                    w,
+                    "        {} [label=\"{} {}{}\\nQueue {}:{}\"];",
+                    node(sid),
+                    kind,
+                    submission.pass().0,
+                    label,
+                    qid.family().0,
+                    qid.index()
+                )?;
+            }
+            // Keep submissions of the queue in order.
+            for index in 1..queue.len() {
+                writeln!(
+                    w,
+                    "        {} -> {} [style=invis];",
+                    node(SubmissionId::new(qid, index - 1)),
+                    node(SubmissionId::new(qid, index))
+                )?;
+            }
+            writeln!(w, "    }}")?;
+        }
+    }
+
+    write_edges::<Buffer, _>(&chains.buffers, "Buffer", w)?;
+    write_edges::<Image, _>(&chains.images, "Image", w)?;
+    write_edges::<Image, _>(&chains.stencils, "Stencil", w)?;
+
+    let mut parts: Vec<_> = chains.parts.iter().collect();
+    parts.sort_by_key(|&(&id, _)| id);
+    for (&id, parts) in parts {
+        for chain in parts {
+            for pair in chain.links().windows(2) {
+                write_link_edges(id, &pair[0], &pair[1], "Image part", w)?;
+            }
+        }
+    }
+
+    let mut buffer_parts: Vec<_> = chains.buffer_parts.iter().collect();
+    buffer_parts.sort_by_key(|&(&id, _)| id);
+    for (&id, parts) in buffer_parts {
+        for chain in parts {
+            for pair in chain.links().windows(2) {
+                write_link_edges(id, &pair[0], &pair[1], "Buffer part", w)?;
+            }
+        }
+    }
+
+    writeln!(w, "}}")
+}
+
+fn node(sid: SubmissionId) -> String {
+    format!(
+        "s_{}_{}_{}",
+        sid.family().0,
+        sid.queue().index(),
+        sid.index()
+    )
+}
+
+fn write_edges<R, W>(chains: &IdMap<R, Chain<R>>, kind: &str, w: &mut W) -> io::Result<()>
+where
+    R: Resource,
+    W: Write,
+{
+    let mut ids: Vec<Id<R>> = chains.keys().cloned().collect();
+    ids.sort();
+
+    for id in ids {
+        let links = chains[&id].links();
+        for pair in links.windows(2) {
+            write_link_edges(id, &pair[0], &pair[1], kind, w)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_link_edges<R, W>(
+    id: Id<R>,
+    prev: &Link<R>,
+    next: &Link<R>,
+    kind: &str,
+    w: &mut W,
+) -> io::Result<()>
+where
+    R: Resource,
+    W: Write,
+{
+    for (next_qid, next_queue) in next.queues() {
+        for (prev_qid, prev_queue) in prev.queues() {
+            let src = SubmissionId::new(prev_qid, prev_queue.last);
+            let dst = SubmissionId::new(next_qid, next_queue.first);
+            let (sync, style) = if prev_qid == next_qid {
+                ("barrier", "solid")
+            } else {
+                ("semaphore", "dashed, color=blue")
+            };
+            writeln!(
+                w,
+                "    {} -> {} [label=\"{} {}\\n{}\\n-> {}\\n{}\", style={}];",
+                node(src),
+                node(dst),
+                kind,
+                id.index(),
+                state(prev.queue_state(prev_qid)),
+                state(next.queue_state(next_qid)),
+                sync,
+                style,
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn state<R: Resource>(state: State<R>) -> String {
+    format!(
+        "{:?}\\n{:?}\\n{:?}",
+        state.access, state.layout, state.stages
+    )
+}
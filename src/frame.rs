@@ -0,0 +1,681 @@
+//!
+//! This module provides instancing of synchronized schedule for several frames in flight.
+//! Each frame slot gets its own semaphores, events and fences.
+//! Resources that persist between frames are transitioned from their final state
+//! in the previous frame instead of the initial state.
+//!
+
+use alloc::vec::Vec;
+use collections::FnvHashMap;
+use core::hash::Hash;
+use core::ops::Range;
+use hal::pso::PipelineStage;
+
+use chain::{Chain, Link};
+use collect::Chains;
+use resource::{
+    Access, Buffer, Id, IdMap, Image, Layout, Range as ResourceRange, Resource, SharingMode, State,
+};
+use schedule::{Schedule, SubmissionId};
+use sync::{
+    earliest, event_count, fence_count, latest, Barrier, Barriers, FenceId, Guard, PartBarriers,
+    SetEvent, Side, Signal, SyncData, Wait, WaitEvent,
+};
+use Pick;
+
+/// Transition of the resource from its final state in the previous frame
+/// to the state of its first link.
+#[derive(Clone, Debug)]
+pub struct FrameTransition<R: Resource> {
+    /// Id of the resource.
+    pub id: Id<R>,
+
+    /// Resource has separate instance for each frame slot.
+    /// It is transitioned from the final state of the frame that used the same slot.
+    pub per_frame: bool,
+
+    /// Index of the resource part the transition is performed for.
+    /// `None` if the resource isn't tracked in parts.
+    pub part: Option<usize>,
+
+    /// Submission at which acquire side the transition is performed.
+    pub sid: SubmissionId,
+
+    /// Barrier that performs the transition.
+    /// Replaces barrier of the resource from the schedule.
+    pub barrier: Barrier<R>,
+
+    /// Ownership release performed at the end of every frame.
+    /// Submission at which release side the barrier is recorded.
+    /// Or `None` if ownership transfer isn't required.
+    pub release: Option<(SubmissionId, Barrier<R>)>,
+}
+
+/// Frames in which extra semaphore is signaled and waited.
+#[derive(Clone, Copy, Debug)]
+enum Span {
+    /// Both in every frame.
+    Frame,
+
+    /// Both in frames that transition resource from the previous frame.
+    Transition { per_frame: bool },
+
+    /// Signaled in every frame and waited in the next one.
+    Next,
+}
+
+/// Semaphore required in addition to ones from the schedule.
+#[derive(Clone, Debug)]
+struct Dependency {
+    signal: (SubmissionId, Side),
+    wait: (SubmissionId, Side),
+    stages: PipelineStage,
+    span: Span,
+}
+
+/// Synchronized schedule instanced for several frames in flight.
+///
+/// Semaphores, events and fences are indexed separately for each frame slot.
+/// Semaphores are indexed from `0` to `semaphore_count`,
+/// events from `0` to `event_count`, fences from `0` to `fence_count`.
+/// Frame `n` uses slot `n % in_flight`. User must wait for fences of the slot
+/// before submitting a frame that reuses it.
+///
+/// Resources that are accessed by host or presentation engine
+/// are synchronized by the schedule in every frame and have no transitions.
+#[derive(Clone, Debug)]
+pub struct FrameRing<T> {
+    in_flight: usize,
+    semaphores: FnvHashMap<T, usize>,
+    semaphores_per_slot: usize,
+    events_per_slot: usize,
+    fences_per_slot: usize,
+    dependencies: Vec<Dependency>,
+    buffers: Vec<FrameTransition<Buffer>>,
+    images: Vec<FrameTransition<Image>>,
+}
+
+impl<T> FrameRing<T>
+where
+    T: Clone + Eq + Hash,
+{
+    /// Create ring of `in_flight` frame slots for synchronized `Chains`.
+    /// `T` is semaphore identifier used in the schedule.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `in_flight` is `0`
+    /// or schedule is synchronized with timeline semaphores.
+    pub fn new(chains: &Chains<SyncData<T, T>>, in_flight: usize) -> Self {
+        assert_ne!(in_flight, 0, "At least one frame must be in flight");
+
+        let mut families: Vec<_> = chains.schedule.iter().collect();
+        families.sort_by_key(|family| family.id());
+
+        let mut semaphores = FnvHashMap::default();
+        for family in families {
+            for queue in family {
+                for (_, submission) in queue.iter() {
+                    let sync = submission.sync();
+                    for signal in sync.acquire.signal.iter().chain(&sync.release.signal) {
+                        assert_eq!(signal.value(), 0, "Timeline semaphores are not supported");
+                        let index = semaphores.len();
+                        semaphores
+                            .entry(signal.semaphore().clone())
+                            .or_insert(index);
+                    }
+                }
+            }
+        }
+
+        let mut dependencies = Vec::new();
+        let mut buffers = transitions(&chains.buffers, &chains.schedule, &mut dependencies);
+        let mut buffer_parts: Vec<_> = chains.buffer_parts.iter().collect();
+        buffer_parts.sort_by_key(|&(&id, _)| id);
+        for (&id, parts) in buffer_parts {
+            for (index, chain) in parts.iter().enumerate() {
+                buffers.extend(
+                    transition(id, chain, &chains.schedule, &mut dependencies).map(
+                        |transition| FrameTransition {
+                            part: Some(index),
+                            ..transition
+                        },
+                    ),
+                );
+            }
+        }
+        let mut images = transitions(&chains.images, &chains.schedule, &mut dependencies);
+        images.extend(transitions(
+            &chains.stencils,
+            &chains.schedule,
+            &mut dependencies,
+        ));
+        let mut parts: Vec<_> = chains.parts.iter().collect();
+        parts.sort_by_key(|&(&id, _)| id);
+        for (&id, parts) in parts {
+            for (index, chain) in parts.iter().enumerate() {
+                images.extend(
+                    transition(id, chain, &chains.schedule, &mut dependencies).map(
+                        |transition| FrameTransition {
+                            part: Some(index),
+                            ..transition
+                        },
+                    ),
+                );
+            }
+        }
+        bind_dependencies(chains.buffers.iter(), &mut dependencies);
+        bind_dependencies(
+            chains
+                .buffer_parts
+                .iter()
+                .flat_map(|(id, parts)| parts.iter().map(move |chain| (id, chain))),
+            &mut dependencies,
+        );
+        bind_dependencies(chains.images.iter(), &mut dependencies);
+        bind_dependencies(chains.stencils.iter(), &mut dependencies);
+        bind_dependencies(
+            chains
+                .parts
+                .iter()
+                .flat_map(|(id, parts)| parts.iter().map(move |chain| (id, chain))),
+            &mut dependencies,
+        );
+
+        FrameRing {
+            in_flight,
+            semaphores_per_slot: semaphores.len() + dependencies.len(),
+            semaphores,
+            events_per_slot: event_count(&chains.schedule),
+            fences_per_slot: fence_count(&chains.schedule),
+            dependencies,
+            buffers,
+            images,
+        }
+    }
+
+    /// Get number of frame slots.
+    pub fn in_flight(&self) -> usize {
+        self.in_flight
+    }
+
+    /// Get number of semaphores used by all frame slots.
+    pub fn semaphore_count(&self) -> usize {
+        self.semaphores_per_slot * self.in_flight
+    }
+
+    /// Get number of events used by all frame slots.
+    pub fn event_count(&self) -> usize {
+        self.events_per_slot * self.in_flight
+    }
+
+    /// Get number of fences used by all frame slots.
+    /// Each slot has one fence for each fence signaled by the schedule.
+    pub fn fence_count(&self) -> usize {
+        self.fences_per_slot * self.in_flight
+    }
+
+    /// Get buffers which first links are transitioned from the previous frame.
+    /// Buffer tracked in parts may have transition for each part.
+    pub fn buffer_transitions(&self) -> &[FrameTransition<Buffer>] {
+        &self.buffers
+    }
+
+    /// Get images which first links are transitioned from the previous frame.
+    /// Image which stencil aspect is tracked separately may have two transitions,
+    /// distinguished by their barrier ranges.
+    /// Image tracked in parts may have transition for each part.
+    pub fn image_transitions(&self) -> &[FrameTransition<Image>] {
+        &self.images
+    }
+
+    /// Get synchronization for frame with index `n`.
+    pub fn frame(&self, n: usize) -> FrameSync<'_, T> {
+        FrameSync {
+            ring: self,
+            frame: n,
+        }
+    }
+}
+
+/// Synchronization of one frame.
+/// Maps semaphores, events and fences of the schedule to indices of the frame slot.
+#[derive(Clone, Copy, Debug)]
+pub struct FrameSync<'a, T: 'a> {
+    ring: &'a FrameRing<T>,
+    frame: usize,
+}
+
+impl<'a, T> FrameSync<'a, T>
+where
+    T: Clone + Eq + Hash,
+{
+    /// Get index of the frame.
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    /// Get slot used by the frame.
+    pub fn slot(&self) -> usize {
+        self.frame % self.ring.in_flight
+    }
+
+    /// Get index of the frame which final states resources are transitioned from.
+    /// Or `None` if resources are in their initial states.
+    pub fn previous(&self, per_frame: bool) -> Option<usize> {
+        let distance = if per_frame { self.ring.in_flight } else { 1 };
+        self.frame.checked_sub(distance)
+    }
+
+    /// Get semaphore indices used by the frame slot.
+    pub fn semaphores(&self) -> Range<usize> {
+        let start = self.slot() * self.ring.semaphores_per_slot;
+        start..start + self.ring.semaphores_per_slot
+    }
+
+    /// Get index of the semaphore from the schedule.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if semaphore is not signaled by the schedule.
+    pub fn semaphore(&self, semaphore: &T) -> usize {
+        self.semaphores().start + self.ring.semaphores[semaphore]
+    }
+
+    /// Get index of the event from the schedule.
+    pub fn event(&self, event: usize) -> usize {
+        self.slot() * self.ring.events_per_slot + event
+    }
+
+    /// Get fence indices used by the frame slot.
+    pub fn fences(&self) -> Range<usize> {
+        let start = self.slot() * self.ring.fences_per_slot;
+        start..start + self.ring.fences_per_slot
+    }
+
+    /// Get index of the fence from the schedule.
+    pub fn fence(&self, fence: FenceId) -> usize {
+        self.fences().start + fence
+    }
+
+    /// Get synchronization of the submission for this frame.
+    pub fn sync(&self, sid: SubmissionId, sync: &SyncData<T, T>) -> SyncData<usize, usize> {
+        let mut result = SyncData {
+            acquire: self.guard(&sync.acquire),
+            release: self.guard(&sync.release),
+            frame_begin: self.guard(&sync.frame_begin),
+            feedback_loops: sync.feedback_loops.clone(),
+            fence: sync.fence.map(|fence| self.fence(fence)),
+        };
+        for transition in &self.ring.buffers {
+            match transition.part {
+                None => self.transition(sid, transition, &mut result),
+                Some(_) => {
+                    self.part_transition(sid, transition, &mut result, |guard| {
+                        &mut guard.buffer_parts
+                    })
+                }
+            }
+        }
+        for transition in &self.ring.images {
+            match transition.part {
+                None => self.transition(sid, transition, &mut result),
+                Some(_) => {
+                    self.part_transition(sid, transition, &mut result, |guard| &mut guard.parts)
+                }
+            }
+        }
+
+        let slot = self.slot();
+        let previous = self
+            .previous(false)
+            .map(|frame| frame % self.ring.in_flight);
+        let first = self.ring.semaphores.len();
+        for (index, dependency) in self.ring.dependencies.iter().enumerate() {
+            let (signal, wait) = match dependency.span {
+                Span::Frame => (Some(slot), Some(slot)),
+                Span::Transition { per_frame } => match self.previous(per_frame) {
+                    Some(_) => (Some(slot), Some(slot)),
+                    None => (None, None),
+                },
+                Span::Next => (Some(slot), previous),
+            };
+            let semaphore = |slot| slot * self.ring.semaphores_per_slot + first + index;
+            if let (Some(slot), true) = (signal, dependency.signal.0 == sid) {
+                result
+                    .get_mut(dependency.signal.1)
+                    .signal
+                    .push(Signal::new(semaphore(slot)));
+            }
+            if let (Some(slot), true) = (wait, dependency.wait.0 == sid) {
+                result
+                    .get_mut(dependency.wait.1)
+                    .wait
+                    .push(Wait::new(semaphore(slot), dependency.stages));
+            }
+        }
+        result
+    }
+
+    /// Get synchronized schedule for this frame.
+    pub fn schedule(
+        &self,
+        schedule: &Schedule<SyncData<T, T>>,
+    ) -> Schedule<SyncData<usize, usize>> {
+        let mut result = Schedule::default();
+        for queue in schedule.iter().flat_map(|family| family.iter()) {
+            let new_queue = result.ensure_queue(queue.id());
+            for (sid, submission) in queue.iter() {
+                let new_sid = new_queue
+                    .add_submission(submission.set_sync(self.sync(sid, submission.sync())));
+                assert_eq!(sid, new_sid);
+            }
+        }
+        result
+    }
+
+    fn guard(&self, guard: &Guard<T, T>) -> Guard<usize, usize> {
+        Guard {
+            wait: guard
+                .wait
+                .iter()
+                .map(|wait| Wait::new(self.semaphore(wait.semaphore()), wait.stage()))
+                .collect(),
+            buffers: guard.buffers.clone(),
+            images: guard.images.clone(),
+            parts: guard.parts.clone(),
+            buffer_parts: guard.buffer_parts.clone(),
+            set_events: guard
+                .set_events
+                .iter()
+                .map(|set| SetEvent {
+                    event: self.event(set.event),
+                    stages: set.stages,
+                })
+                .collect(),
+            wait_events: guard
+                .wait_events
+                .iter()
+                .map(|wait| WaitEvent {
+                    event: self.event(wait.event),
+                    ..wait.clone()
+                })
+                .collect(),
+            wait_acquired: guard.wait_acquired.clone(),
+            signal: guard
+                .signal
+                .iter()
+                .map(|signal| Signal::new(self.semaphore(signal.semaphore())))
+                .collect(),
+            signal_present: guard.signal_present.clone(),
+            must_record_even_if_skipped: guard.must_record_even_if_skipped,
+        }
+    }
+
+    fn transition<R>(
+        &self,
+        sid: SubmissionId,
+        transition: &FrameTransition<R>,
+        sync: &mut SyncData<usize, usize>,
+    ) where
+        R: Resource,
+        Guard<usize, usize>: Pick<R, Target = Barriers<R>>,
+    {
+        if self.previous(transition.per_frame).is_some() {
+            // Transition from the previous frame replaces the hoisted one as well.
+            if hoisted_at(transition, sid) {
+                sync.frame_begin.pick_mut().remove(&transition.id);
+            }
+            if transition.sid == sid {
+                sync.acquire
+                    .pick_mut()
+                    .insert(transition.id, transition.barrier.clone());
+            }
+        }
+        if let Some((release_sid, ref barrier)) = transition.release {
+            if release_sid == sid {
+                sync.release
+                    .pick_mut()
+                    .insert(transition.id, barrier.clone());
+            }
+        }
+    }
+
+    /// Replace barrier of the resource part with the transition.
+    /// Barriers of other parts of the resource are kept.
+    /// `parts` picks barriers of resource parts from the guard.
+    fn part_transition<R>(
+        &self,
+        sid: SubmissionId,
+        transition: &FrameTransition<R>,
+        sync: &mut SyncData<usize, usize>,
+        parts: fn(&mut Guard<usize, usize>) -> &mut PartBarriers<R>,
+    ) where
+        R: Resource,
+    {
+        let id = transition.id;
+        let remove = |parts: &mut PartBarriers<R>, barrier: &Barrier<R>| {
+            parts.retain(|&(other, ref old)| {
+                other != id
+                    || match (old.range.as_ref(), barrier.range.as_ref()) {
+                        (Some(old), Some(range)) => !old.overlaps(range),
+                        _ => false,
+                    }
+            });
+        };
+        let replace = |parts: &mut PartBarriers<R>, barrier: &Barrier<R>| {
+            remove(parts, barrier);
+            parts.push((id, barrier.clone()));
+            parts.sort_by_key(|&(id, _)| id);
+        };
+        if self.previous(transition.per_frame).is_some() {
+            if hoisted_at(transition, sid) {
+                remove(parts(&mut sync.frame_begin), &transition.barrier);
+            }
+            if transition.sid == sid {
+                replace(parts(&mut sync.acquire), &transition.barrier);
+            }
+        }
+        if let Some((release_sid, ref barrier)) = transition.release {
+            if release_sid == sid {
+                replace(parts(&mut sync.release), barrier);
+            }
+        }
+    }
+}
+
+/// Check if initial barrier replaced by the transition could be hoisted to the submission.
+fn hoisted_at<R: Resource>(transition: &FrameTransition<R>, sid: SubmissionId) -> bool {
+    sid == SubmissionId::new(transition.sid.queue(), 0)
+}
+
+fn transitions<R, S>(
+    chains: &IdMap<R, Chain<R>>,
+    schedule: &Schedule<S>,
+    dependencies: &mut Vec<Dependency>,
+) -> Vec<FrameTransition<R>>
+where
+    R: Resource,
+{
+    let mut ids: Vec<_> = chains.keys().cloned().collect();
+    ids.sort();
+    ids.into_iter()
+        .filter_map(|id| transition(id, &chains[&id], schedule, dependencies))
+        .collect()
+}
+
+/// Order sparse bindings at the start of the frame after the last uses in the previous frame
+/// and the first uses after sparse bindings at the end of the previous frame.
+/// Resources with separate instance for each frame slot are not shared between frames.
+fn bind_dependencies<'a, R, I>(chains: I, dependencies: &mut Vec<Dependency>)
+where
+    R: Resource + 'a,
+    I: IntoIterator<Item = (&'a Id<R>, &'a Chain<R>)>,
+{
+    let mut chains: Vec<_> = chains.into_iter().collect();
+    chains.sort_by_key(|&(&id, _)| id);
+    for (_, chain) in chains {
+        let (first, last) = match (chain.binds().first(), chain.binds().last()) {
+            (Some(first), Some(last)) if !chain.info().per_frame => (first, last),
+            _ => continue,
+        };
+        let leading = first.link == 0;
+        let trailing = last.link == chain.links().len();
+
+        let mut signals = Vec::new();
+        if trailing {
+            signals.push(last.sid);
+        } else if leading {
+            let link = chain.links().last().unwrap();
+            for (queue_id, queue) in link.queues() {
+                signals.push(SubmissionId::new(queue_id, queue.last));
+            }
+        }
+
+        for signal in signals {
+            if leading {
+                dependencies.push(Dependency {
+                    signal: (signal, Side::Release),
+                    wait: (first.sid, Side::Acquire),
+                    stages: PipelineStage::empty(),
+                    span: Span::Next,
+                });
+            } else {
+                for (queue_id, queue) in chain.link(0).queues() {
+                    dependencies.push(Dependency {
+                        signal: (signal, Side::Release),
+                        wait: (SubmissionId::new(queue_id, queue.first), Side::Acquire),
+                        stages: queue.stages,
+                        span: Span::Next,
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// Find transition of the resource from the last link in the previous frame
+/// to the first link in the next one.
+fn transition<R, S>(
+    id: Id<R>,
+    chain: &Chain<R>,
+    schedule: &Schedule<S>,
+    dependencies: &mut Vec<Dependency>,
+) -> Option<FrameTransition<R>>
+where
+    R: Resource,
+{
+    let info = chain.info();
+    let present = info.present && R::Layout::present().is_some();
+    let host = info.host_write_before || info.host_read_after;
+    let external = info.import.is_some() || info.export.is_some();
+    if chain.links().is_empty() || present || host || external {
+        return None;
+    }
+
+    let per_frame = info.per_frame;
+    let first = chain.link(0);
+    let last = chain.links().last().unwrap();
+    let wait_sid = earliest(first, schedule);
+    let mut add =
+        |signal: (SubmissionId, Side), wait: (SubmissionId, Side), link: &Link<R>, span| {
+            if signal.0.queue() != wait.0.queue() {
+                dependencies.push(Dependency {
+                    signal,
+                    wait,
+                    stages: link.queue(wait.0.queue()).stages,
+                    span,
+                });
+            }
+        };
+
+    let (barrier, release) =
+        if last.family() == first.family() || chain.sharing() == SharingMode::Concurrent {
+            if !per_frame {
+                for (queue_id, queue) in last.queues() {
+                    let tail = SubmissionId::new(queue_id, queue.last);
+                    add(
+                        (tail, Side::Release),
+                        (wait_sid, Side::Acquire),
+                        first,
+                        Span::Next,
+                    );
+                }
+            }
+            let mut start = last.state();
+            let range = if chain.discards(0) {
+                // Previous frame's content is overwritten anyway.
+                start.access = R::Access::none();
+                start.layout = R::Layout::discard_content();
+                chain.range()
+            } else if last.state().layout == first.state().layout {
+                first.range()
+            } else {
+                chain.range()
+            };
+            (Barrier::new(start..first.state(), range.cloned()), None)
+        } else {
+            let signal_sid = latest(last, schedule);
+
+            // Delay the release until other queues finish
+            for (queue_id, queue) in last.queues() {
+                let tail = SubmissionId::new(queue_id, queue.last);
+                add(
+                    (tail, Side::Release),
+                    (signal_sid, Side::Release),
+                    last,
+                    Span::Frame,
+                );
+            }
+            if !per_frame {
+                add(
+                    (signal_sid, Side::Release),
+                    (wait_sid, Side::Acquire),
+                    first,
+                    Span::Next,
+                );
+            }
+            let queues = signal_sid.queue()..wait_sid.queue();
+            let release = Barrier::release(
+                queues.clone(),
+                State {
+                    access: last.state().access,
+                    ..last.queue_state(signal_sid.queue())
+                }..,
+                ..first.state().layout,
+                chain.range(),
+            );
+            let acquire = Barrier::acquire(
+                queues,
+                last.state().layout..,
+                ..State {
+                    access: first.state().access,
+                    ..first.queue_state(wait_sid.queue())
+                },
+                chain.range(),
+            );
+            (acquire, Some((signal_sid, release)))
+        };
+
+    if !first.single_queue() {
+        // Delay other queues in the link until the transition finishes
+        for (queue_id, queue) in first.queues() {
+            let head = SubmissionId::new(queue_id, queue.first);
+            add(
+                (wait_sid, Side::Acquire),
+                (head, Side::Acquire),
+                first,
+                Span::Transition { per_frame },
+            );
+        }
+    }
+
+    Some(FrameTransition {
+        id,
+        per_frame,
+        part: None,
+        sid: wait_sid,
+        barrier,
+        release,
+    })
+}
@@ -0,0 +1,472 @@
+//!
+//! This module provides incremental update of synchronized `Chains`.
+//! When only states declared by passes change, chains of affected resources
+//! and synchronization that references them are recomputed without rescheduling passes.
+//!
+
+use alloc::vec::Vec;
+use collections::FnvHashMap;
+use collections::HashMap;
+use core::cmp::max;
+use core::mem::take;
+use hal::queue::QueueFamilyId;
+
+use chain::Chain;
+use collect::{
+    check_feedback_loop, collect, link_submission, Chains, CollectError, Unsynchronized,
+};
+use pass::{OrderingHint, Pass, PassId, PassKind, StateUsage};
+use resource::{Buffer, Id, IdMap, Image, MergeError, Resource, Resources, State, Usage};
+use schedule::{Schedule, Submission, SubmissionId};
+use sync::{
+    legal_barriers, sync_schedule, update_buffer_barriers, update_image_barriers, SyncData,
+    SyncOptions,
+};
+use Pick;
+
+/// Result of incremental update.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum UpdateResult {
+    /// New states are the same as old ones. Nothing was changed.
+    Unchanged,
+
+    /// Only barriers of the affected resources were changed.
+    /// Semaphores and events are the same.
+    Barriers,
+
+    /// Links of affected resources were changed.
+    /// Synchronization was regenerated for the same schedule.
+    Synchronized,
+
+    /// Passes had to be collected again.
+    /// Schedule may be different.
+    Rebuilt,
+}
+
+impl UpdateResult {
+    /// Check if queue schedule may be changed by the update.
+    pub fn schedule_changed(&self) -> bool {
+        *self == UpdateResult::Rebuilt
+    }
+}
+
+/// Synchronized `Chains` together with everything required to update them.
+#[derive(Debug)]
+pub struct IncrementalChains<Q, F, S, W> {
+    passes: Vec<Pass>,
+    pass_indices: FnvHashMap<PassId, usize>,
+    resources: Resources,
+    max_queues: Q,
+    options: SyncOptions,
+    new_semaphore: F,
+    unsynchronized: Schedule<Unsynchronized>,
+    chains: Chains<SyncData<S, W>>,
+}
+
+impl<Q, F, S, W> IncrementalChains<Q, F, S, W>
+where
+    Q: Fn(QueueFamilyId) -> usize,
+    F: FnMut() -> (S, W),
+    S: Clone,
+    W: Clone,
+{
+    /// Collect and synchronize `Chains` keeping passes for later updates.
+    ///
+    /// # Errors
+    ///
+    /// Returns `CollectError` if passes declared resource states that can't be merged.
+    /// Returns `CollectError::UnsupportedTransitionNodes` if `options.standalone_transition_nodes`
+    /// is set. Updates patch links by ids of submissions, which transition nodes change.
+    pub fn new(
+        passes: Vec<Pass>,
+        resources: Resources,
+        max_queues: Q,
+        options: SyncOptions,
+        new_semaphore: F,
+    ) -> Result<Self, CollectError> {
+        if options.standalone_transition_nodes {
+            return Err(CollectError::UnsupportedTransitionNodes);
+        }
+        let pass_indices = passes
+            .iter()
+            .enumerate()
+            .map(|(index, pass)| (pass.id, index))
+            .collect();
+        let mut chains = collect(passes.clone(), &resources, &max_queues)?;
+        let mut new_semaphore = new_semaphore;
+        let schedule = sync_schedule(&chains.schedule, &chains, &options, &mut new_semaphore);
+        let unsynchronized = take(&mut chains.schedule);
+        Ok(IncrementalChains {
+            passes,
+            pass_indices,
+            resources,
+            max_queues,
+            options,
+            new_semaphore,
+            unsynchronized,
+            chains: Chains {
+                schedule,
+                buffers: chains.buffers,
+                images: chains.images,
+                stencils: chains.stencils,
+                parts: chains.parts,
+                buffer_parts: chains.buffer_parts,
+            },
+        })
+    }
+
+    /// Get synchronized `Chains`.
+    pub fn chains(&self) -> &Chains<SyncData<S, W>> {
+        &self.chains
+    }
+
+    /// Get passes `Chains` were built from.
+    pub fn passes(&self) -> &[Pass] {
+        &self.passes
+    }
+
+    /// Change states in which pass uses images.
+    ///
+    /// Chains of images which states changed are recomputed.
+    /// Barriers of those images are regenerated if links keep submissions and stages.
+    /// Otherwise synchronization is regenerated for the whole schedule.
+    /// If pass didn't use image before it is added with empty usage
+    /// and `Chains` are rebuilt from scratch.
+    /// Same happens if depth and stencil aspects of the image are tracked separately,
+    /// the image is tracked in parts, is a member of a group or bound by sparse binding passes.
+    /// Buffers tracked in parts are rebuilt from scratch as well.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no pass with specified id.
+    ///
+    /// # Errors
+    ///
+    /// Returns `CollectError` if new states can't be merged.
+    /// Passes and `Chains` are left unchanged in this case.
+    pub fn update_pass_states(
+        &mut self,
+        pass: PassId,
+        changes: &[(Id<Image>, State<Image>)],
+    ) -> Result<UpdateResult, CollectError> {
+        self.update::<Image>(pass, changes)
+    }
+
+    /// Change states in which pass uses buffers.
+    /// See `update_pass_states`.
+    pub fn update_pass_buffer_states(
+        &mut self,
+        pass: PassId,
+        changes: &[(Id<Buffer>, State<Buffer>)],
+    ) -> Result<UpdateResult, CollectError> {
+        self.update::<Buffer>(pass, changes)
+    }
+
+    fn update<R>(
+        &mut self,
+        pass: PassId,
+        changes: &[(Id<R>, State<R>)],
+    ) -> Result<UpdateResult, CollectError>
+    where
+        R: Updatable,
+        CollectError: From<(Id<R>, PassId, MergeError<R>)>,
+        Submission<Unsynchronized>: Pick<R, Target = FnvHashMap<Id<R>, usize>>,
+        Submission<SyncData<S, W>>: Pick<R, Target = FnvHashMap<Id<R>, usize>>,
+    {
+        let index = self.pass_indices[&pass];
+
+        // Apply changes remembering old states.
+        let mut old = Vec::new();
+        let mut added = Vec::new();
+        for &(id, state) in changes {
+            let declared = R::pass_resources_mut(&mut self.passes[index]);
+            match declared.get_mut(&id) {
+                Some(state_usage) => {
+                    if state_usage.state != state {
+                        old.push((id, state_usage.state));
+                        state_usage.state = state;
+                    }
+                }
+                None => {
+                    declared.insert(
+                        id,
+                        StateUsage {
+                            state,
+                            usage: Usage::none(),
+                            range: None,
+                            discard: false,
+                            ordering: OrderingHint::None,
+                        },
+                    );
+                    added.push(id);
+                }
+            }
+        }
+
+        // Declared states of sparse binding passes are ignored.
+        let bind = self.passes[index].kind == PassKind::SparseBind;
+
+        // Relinking keeps neither separate aspects nor sparse bindings.
+        // Members of groups are verified by `collect`.
+        let split = !bind && old.iter().any(|&(id, _)| {
+            R::split(&self.chains, id)
+                || R::grouped(&self.resources, id)
+                || !R::chains(&self.chains)[&id].binds().is_empty()
+        });
+        if !added.is_empty() || split {
+            let result = self.rebuild();
+            if result.is_err() {
+                self.revert::<R>(index, &old, &added);
+            }
+            return result;
+        }
+
+        if old.is_empty() || bind {
+            return Ok(UpdateResult::Unchanged);
+        }
+
+        let mut ids: Vec<Id<R>> = old.iter().map(|&(id, _)| id).collect();
+        ids.sort();
+        ids.dedup();
+
+        let mut new_chains = Vec::new();
+        for &id in &ids {
+            match self.relink(id) {
+                Ok(relinked) => new_chains.push((id, relinked)),
+                Err(error) => {
+                    self.revert::<R>(index, &old, &added);
+                    return Err(error);
+                }
+            }
+        }
+
+        let mut result = UpdateResult::Unchanged;
+        for (id, (chain, links)) in new_chains {
+            let old_chain = &R::chains(&self.chains)[&id];
+            let change = if same_links(old_chain, &chain, true) {
+                UpdateResult::Unchanged
+            } else if same_links(old_chain, &chain, false) {
+                UpdateResult::Barriers
+            } else {
+                UpdateResult::Synchronized
+            };
+            result = max(result, change);
+
+            for (sid, link) in links {
+                Pick::<R>::pick_mut(&mut self.unsynchronized[sid]).insert(id, link);
+                Pick::<R>::pick_mut(&mut self.chains.schedule[sid]).insert(id, link);
+            }
+            R::chains_mut(&mut self.chains).insert(id, chain);
+        }
+
+        match result {
+            UpdateResult::Unchanged => {}
+            UpdateResult::Barriers => {
+                for &id in &ids {
+                    R::update_barriers(id, &mut self.chains, &self.options);
+                }
+                debug_assert!(legal_barriers(&self.chains.schedule), "Illegal barrier emitted");
+            }
+            _ => {
+                self.chains.schedule = sync_schedule(
+                    &self.unsynchronized,
+                    &self.chains,
+                    &self.options,
+                    &mut self.new_semaphore,
+                );
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Restore states declared by the pass before update.
+    fn revert<R>(&mut self, index: usize, old: &[(Id<R>, State<R>)], added: &[Id<R>])
+    where
+        R: Updatable,
+    {
+        let declared = R::pass_resources_mut(&mut self.passes[index]);
+        for &(id, state) in old.iter().rev() {
+            declared.get_mut(&id).unwrap().state = state;
+        }
+        for id in added {
+            declared.remove(id);
+        }
+    }
+
+    /// Collect and synchronize `Chains` from scratch.
+    fn rebuild(&mut self) -> Result<UpdateResult, CollectError> {
+        let mut chains = collect(self.passes.clone(), &self.resources, &self.max_queues)?;
+        self.chains.schedule =
+            sync_schedule(&chains.schedule, &chains, &self.options, &mut self.new_semaphore);
+        self.unsynchronized = take(&mut chains.schedule);
+        self.chains.buffers = chains.buffers;
+        self.chains.images = chains.images;
+        self.chains.stencils = chains.stencils;
+        self.chains.parts = chains.parts;
+        self.chains.buffer_parts = chains.buffer_parts;
+        Ok(UpdateResult::Rebuilt)
+    }
+
+    /// Build new chain for the resource keeping the schedule.
+    /// Returns the chain and new link index for each submission.
+    fn relink<R>(&self, id: Id<R>) -> Result<Relinked<R>, CollectError>
+    where
+        R: Updatable,
+        CollectError: From<(Id<R>, PassId, MergeError<R>)>,
+        Submission<Unsynchronized>: Pick<R, Target = FnvHashMap<Id<R>, usize>>,
+        Submission<SyncData<S, W>>: Pick<R, Target = FnvHashMap<Id<R>, usize>>,
+    {
+        let old_chain = &R::chains(&self.chains)[&id];
+
+        // Submissions in links' ranges that use the resource.
+        let mut submissions = Vec::new();
+        for link in old_chain.links() {
+            for (qid, queue) in link.queues() {
+                for index in queue.first..queue.last + 1 {
+                    let sid = SubmissionId::new(qid, index);
+                    let submission = &self.unsynchronized[sid];
+                    if Pick::<R>::pick(submission).contains_key(&id) {
+                        submissions.push((submission.submit_order(), sid));
+                    }
+                }
+            }
+        }
+        submissions.sort();
+        submissions.dedup();
+
+        let mut chain = Chain::new();
+        chain.set_info(*old_chain.info());
+        let mut links = Vec::new();
+        for (_, sid) in submissions {
+            let pass = self.unsynchronized[sid].pass();
+            let state_usage =
+                R::pass_resources(&self.passes[self.pass_indices[&pass]])[&id].clone();
+            let policy = self.resources.layout_policy;
+            R::check_link(id, pass, &chain, sid, &state_usage)?;
+            let link = link_submission(id, pass, &mut chain, sid, state_usage, policy)?;
+            links.push((sid, link));
+        }
+        Ok((chain, links))
+    }
+}
+
+/// Rebuilt chain of the resource and new link index for each submission.
+type Relinked<R> = (Chain<R>, Vec<(SubmissionId, usize)>);
+
+/// Resource which chains can be updated.
+trait Updatable: Resource {
+    fn pass_resources(pass: &Pass) -> &HashMap<Id<Self>, StateUsage<Self>>;
+    fn pass_resources_mut(pass: &mut Pass) -> &mut HashMap<Id<Self>, StateUsage<Self>>;
+    fn chains<S>(chains: &Chains<S>) -> &IdMap<Self, Chain<Self>>;
+    fn chains_mut<S>(chains: &mut Chains<S>) -> &mut IdMap<Self, Chain<Self>>;
+    fn split<S>(chains: &Chains<S>, id: Id<Self>) -> bool;
+    fn grouped(resources: &Resources, id: Id<Self>) -> bool;
+    fn check_link(
+        id: Id<Self>,
+        pass: PassId,
+        chain: &Chain<Self>,
+        sid: SubmissionId,
+        state_usage: &StateUsage<Self>,
+    ) -> Result<(), CollectError>;
+    fn update_barriers<S, W>(
+        id: Id<Self>,
+        chains: &mut Chains<SyncData<S, W>>,
+        options: &SyncOptions,
+    );
+}
+
+impl Updatable for Buffer {
+    fn pass_resources(pass: &Pass) -> &HashMap<Id<Self>, StateUsage<Self>> {
+        &pass.buffers
+    }
+    fn pass_resources_mut(pass: &mut Pass) -> &mut HashMap<Id<Self>, StateUsage<Self>> {
+        &mut pass.buffers
+    }
+    fn chains<S>(chains: &Chains<S>) -> &IdMap<Self, Chain<Self>> {
+        &chains.buffers
+    }
+    fn chains_mut<S>(chains: &mut Chains<S>) -> &mut IdMap<Self, Chain<Self>> {
+        &mut chains.buffers
+    }
+    fn split<S>(chains: &Chains<S>, id: Id<Self>) -> bool {
+        chains.buffer_parts.contains_key(&id)
+    }
+    fn grouped(_resources: &Resources, _id: Id<Self>) -> bool {
+        false
+    }
+    fn check_link(
+        _id: Id<Self>,
+        _pass: PassId,
+        _chain: &Chain<Self>,
+        _sid: SubmissionId,
+        _state_usage: &StateUsage<Self>,
+    ) -> Result<(), CollectError> {
+        Ok(())
+    }
+    fn update_barriers<S, W>(
+        id: Id<Self>,
+        chains: &mut Chains<SyncData<S, W>>,
+        options: &SyncOptions,
+    ) {
+        update_buffer_barriers(id, &chains.buffers[&id], &mut chains.schedule, options);
+    }
+}
+
+impl Updatable for Image {
+    fn pass_resources(pass: &Pass) -> &HashMap<Id<Self>, StateUsage<Self>> {
+        &pass.images
+    }
+    fn pass_resources_mut(pass: &mut Pass) -> &mut HashMap<Id<Self>, StateUsage<Self>> {
+        &mut pass.images
+    }
+    fn chains<S>(chains: &Chains<S>) -> &IdMap<Self, Chain<Self>> {
+        &chains.images
+    }
+    fn chains_mut<S>(chains: &mut Chains<S>) -> &mut IdMap<Self, Chain<Self>> {
+        &mut chains.images
+    }
+    fn split<S>(chains: &Chains<S>, id: Id<Self>) -> bool {
+        chains.stencils.contains_key(&id) || chains.parts.contains_key(&id)
+    }
+    fn grouped(resources: &Resources, id: Id<Self>) -> bool {
+        resources.image_groups.iter().any(|group| group.contains(id))
+    }
+    fn check_link(
+        id: Id<Self>,
+        pass: PassId,
+        chain: &Chain<Self>,
+        sid: SubmissionId,
+        state_usage: &StateUsage<Self>,
+    ) -> Result<(), CollectError> {
+        check_feedback_loop(id, pass, chain, sid, state_usage)
+    }
+    fn update_barriers<S, W>(
+        id: Id<Self>,
+        chains: &mut Chains<SyncData<S, W>>,
+        options: &SyncOptions,
+    ) {
+        update_image_barriers(id, &chains.images[&id], &mut chains.schedule, options);
+    }
+}
+
+/// Check if chains have links with the same submissions and stages.
+/// Also compare states if `states` is `true`.
+fn same_links<R: Resource>(a: &Chain<R>, b: &Chain<R>, states: bool) -> bool {
+    a.links().len() == b.links().len()
+        && a.links().iter().zip(b.links()).all(|(a, b)| {
+            let a_state = a.state();
+            let b_state = b.state();
+            a.family() == b.family()
+                && a_state.stages == b_state.stages
+                && (!states || a_state == b_state)
+                && a.queues().count() == b.queues().count()
+                && a.queues().zip(b.queues()).all(|((a_qid, a), (b_qid, b))| {
+                    a_qid == b_qid
+                        && a.first == b.first
+                        && a.last == b.last
+                        && a.stages == b.stages
+                        && (!states || a.access == b.access)
+                })
+        })
+}
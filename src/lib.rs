@@ -5,21 +5,65 @@
 //! before and after each pass.
 //!
 
+#![cfg_attr(not(feature = "std"), no_std)]
 #![deny(missing_docs)]
 #![deny(unused_must_use)]
 #![deny(dead_code)]
 
+#[cfg_attr(not(feature = "std"), macro_use)]
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate core;
 extern crate fnv;
 extern crate gfx_hal as hal;
+#[cfg(not(feature = "std"))]
+extern crate hashbrown;
 
+#[cfg(feature = "serde-1")]
+extern crate serde;
+#[cfg(feature = "serde-1")]
+#[macro_use]
+extern crate serde_derive;
+
+#[cfg(feature = "test-utils")]
+extern crate rand;
+#[cfg(feature = "capture")]
+extern crate ron;
+
+use alloc::vec::Vec;
 use hal::queue::QueueFamilyId;
 
+pub mod alias;
+#[cfg(feature = "capture")]
+pub mod capture;
 pub mod chain;
 pub mod collect;
+mod collections;
+pub mod compiled;
+pub mod cull;
+pub mod device;
+pub mod diff;
+#[cfg(feature = "std")]
+pub mod dot;
+pub mod frame;
+pub mod incremental;
+pub mod offload;
 pub mod pass;
 pub mod resource;
 pub mod schedule;
+pub mod stats;
+pub mod subchain;
 pub mod sync;
+pub mod truncate;
+pub mod validate;
+pub mod walk;
+pub mod warning;
+
+#[cfg(feature = "serde-1")]
+pub mod ser;
+
+#[cfg(feature = "test-utils")]
+pub mod sim;
 
 /// Allows to insert links to submission generically.
 trait Pick<R> {
@@ -28,8 +72,15 @@ trait Pick<R> {
     fn pick_mut(&mut self) -> &mut Self::Target;
 }
 
-use collect::{collect, Chains};
+use collect::{collect, Chains, CollectError};
 use pass::Pass;
+
+/// Error of building `Chains`.
+/// Fallible entry points (`build`, `collect::collect` and `incremental::IncrementalChains`)
+/// fail only on collection, as synchronization of collected chains always succeeds.
+pub use collect::CollectError as ChainError;
+pub use warning::ChainWarning;
+use resource::Resources;
 use sync::{sync, SyncData};
 
 /// Build synchronized schedule of the execution from passes descriptions.
@@ -37,23 +88,38 @@ use sync::{sync, SyncData};
 /// # Parameters
 ///
 /// `passes`        - array of pass descriptions for passes to schedule and synchronize.
+/// `resources`     - properties of resources used by passes.
 /// `max_queues`    - function that returns maximum number of queues for specified family.
 /// `new_semaphore` - function to create new semaphore pair - (signal, wait).
 ///
+/// Takes time of `collect::collect` and `sync::sync` together. See those for complexity.
+///
+/// # Errors
+///
+/// Returns `ChainError` if passes declared resource states that can't be merged
+/// or resources that aren't registered, their dependencies are unknown or form a cycle,
+/// or no queue can execute some pass.
+///
 pub fn build<F, Q, S, W>(
     passes: Vec<Pass>,
+    resources: &Resources,
     max_queues: Q,
     new_semaphore: F,
-) -> Chains<SyncData<S, W>>
+) -> Result<Chains<SyncData<S, W>>, CollectError>
 where
     Q: Fn(QueueFamilyId) -> usize,
     F: FnMut() -> (S, W),
+    S: Clone,
+    W: Clone,
 {
-    let chains = collect(passes, max_queues);
-    let schedule = sync(&chains, new_semaphore);
-    Chains {
+    let chains = collect(passes, resources, max_queues)?;
+    let schedule = sync(&chains, new_semaphore)?;
+    Ok(Chains {
         schedule,
         images: chains.images,
+        stencils: chains.stencils,
+        parts: chains.parts,
         buffers: chains.buffers,
-    }
+        buffer_parts: chains.buffer_parts,
+    })
 }
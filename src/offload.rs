@@ -0,0 +1,256 @@
+//!
+//! This module provides suggestions to move transfer passes to dedicated transfer families,
+//! where they can overlap with passes of other families.
+//! See `collect::SchedulerOptions::offload_transfers` to move them automatically.
+//!
+
+use alloc::vec::Vec;
+use collections::{FnvHashMap, FnvHashSet};
+use core::cmp::max;
+use hal::queue::QueueFamilyId;
+
+use chain::Chain;
+use collect::{Chains, FamilyInfo};
+use pass::{Capability, Pass, PassId};
+use resource::Resource;
+use schedule::{QueueId, SubmissionId};
+
+/// Suggestion to execute the pass on a dedicated transfer family.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OffloadSuggestion {
+    /// Id of the pass.
+    pub pass: PassId,
+
+    /// Dedicated transfer family to execute the pass on. Its first queue is assumed.
+    pub target_family: QueueFamilyId,
+
+    /// Estimated number of semaphores the pass would additionally wait on or signal.
+    /// Migrations that save semaphores report zero.
+    pub added_semaphores: usize,
+
+    /// Slack the pass would have on the target family, in units of `Pass::cost`.
+    /// Pass may start that much later without lengthening the critical path.
+    pub slack: u64,
+}
+
+/// Submissions adjacent to the submission regardless of queue order.
+#[derive(Default)]
+struct Adjacent {
+    /// Submissions of the previous links of resources accessed by the submission.
+    link_preds: Vec<SubmissionId>,
+
+    /// Submissions of the next links of resources accessed by the submission.
+    link_succs: Vec<SubmissionId>,
+
+    /// Submissions of passes the pass depends on.
+    dependencies: Vec<SubmissionId>,
+
+    /// Submissions of passes that depend on the pass.
+    dependents: Vec<SubmissionId>,
+}
+
+impl Adjacent {
+    fn add_link<R: Resource>(&mut self, chain: &Chain<R>, index: usize) {
+        if index > 0 {
+            for (qid, queue) in chain.link(index - 1).queues() {
+                self.link_preds.push(SubmissionId::new(qid, queue.last));
+            }
+        }
+        if index + 1 < chain.links().len() {
+            for (qid, queue) in chain.link(index + 1).queues() {
+                self.link_succs.push(SubmissionId::new(qid, queue.first));
+            }
+        }
+    }
+
+    /// Count queues other than `queue` the submission would synchronize with by semaphores.
+    fn semaphores(&self, queue: QueueId) -> usize {
+        let count = |sids: &[SubmissionId]| {
+            sids.iter()
+                .map(SubmissionId::queue)
+                .filter(|&qid| qid != queue)
+                .collect::<FnvHashSet<_>>()
+                .len()
+        };
+        count(&self.link_preds) + count(&self.link_succs)
+    }
+}
+
+/// Find passes with transfer-only stages that may move to a dedicated transfer family
+/// without lengthening the critical path of the schedule.
+/// `passes` are the ones `chains` were collected from and `families` describe available families.
+///
+/// Execution of each submission takes `Pass::cost` and starts after its dependencies,
+/// previous links of its resources and previous submission on its queue are finished.
+/// Transition nodes take no time and are never suggested.
+/// Suggestion for each pass assumes all other passes stay where they are,
+/// and ignores other passes already executed on the target queue.
+/// Passes pinned to specific queues, or already executed on dedicated transfer families,
+/// are never suggested. The first fitting family from `families` is suggested.
+/// Suggestions are sorted by pass.
+pub fn offload_suggestions<S>(
+    chains: &Chains<S>,
+    passes: &[Pass],
+    families: &[FamilyInfo],
+) -> Vec<OffloadSuggestion> {
+    let passes: FnvHashMap<PassId, &Pass> = passes.iter().map(|pass| (pass.id, pass)).collect();
+
+    // Dependencies always point to submissions with greater submit order.
+    let mut order = Vec::new();
+    let mut sids = FnvHashMap::default();
+    for family in chains.schedule.iter() {
+        for queue in family {
+            for (sid, submission) in queue.iter() {
+                order.push((submission.submit_order(), sid));
+                if !submission.is_transition() {
+                    sids.insert(submission.pass(), sid);
+                }
+            }
+        }
+    }
+    order.sort();
+
+    let mut adjacent: FnvHashMap<SubmissionId, Adjacent> = FnvHashMap::default();
+    for &(_, sid) in &order {
+        let submission = &chains.schedule[sid];
+        let mut node = Adjacent::default();
+        for (id, &index) in submission.buffers() {
+            node.add_link(&chains.buffers[id], index);
+        }
+        for (id, &index) in submission.images() {
+            node.add_link(&chains.images[id], index);
+        }
+        for (id, &index) in submission.stencils() {
+            node.add_link(&chains.stencils[id], index);
+        }
+        for (id, parts) in submission.parts() {
+            for &(part, index) in parts {
+                node.add_link(&chains.parts[id][part], index);
+            }
+        }
+        for (id, &(part, index)) in submission.buffer_parts() {
+            node.add_link(&chains.buffer_parts[id][part], index);
+        }
+        // Transition node is ordered by its queue only.
+        if !submission.is_transition() {
+            node.dependencies = passes[&submission.pass()]
+                .dependencies
+                .iter()
+                .map(|dependency| sids[dependency])
+                .collect();
+        }
+        adjacent.insert(sid, node);
+    }
+    for &(_, sid) in &order {
+        for dependency in adjacent[&sid].dependencies.clone() {
+            adjacent.get_mut(&dependency).unwrap().dependents.push(sid);
+        }
+    }
+
+    let cost = |sid: SubmissionId| {
+        let submission = &chains.schedule[sid];
+        if submission.is_transition() {
+            0
+        } else {
+            u64::from(max(passes[&submission.pass()].cost, 1))
+        }
+    };
+    let queue_prev = |sid: SubmissionId| {
+        if sid.index() > 0 {
+            Some(SubmissionId::new(sid.queue(), sid.index() - 1))
+        } else {
+            None
+        }
+    };
+    let queue_next = |sid: SubmissionId| {
+        let next = SubmissionId::new(sid.queue(), sid.index() + 1);
+        chains.schedule.submission(next).map(|_| next)
+    };
+
+    // Earliest finish of each submission.
+    let mut finish: FnvHashMap<SubmissionId, u64> = FnvHashMap::default();
+    for &(_, sid) in &order {
+        let node = &adjacent[&sid];
+        let start = queue_prev(sid)
+            .iter()
+            .chain(&node.link_preds)
+            .chain(&node.dependencies)
+            .map(|pred| finish[pred])
+            .max()
+            .unwrap_or(0);
+        finish.insert(sid, start + cost(sid));
+    }
+    let length = finish.values().cloned().max().unwrap_or(0);
+
+    // Latest finish of each submission that keeps length of the schedule.
+    let mut latest: FnvHashMap<SubmissionId, u64> = FnvHashMap::default();
+    for &(_, sid) in order.iter().rev() {
+        let node = &adjacent[&sid];
+        let end = queue_next(sid)
+            .iter()
+            .chain(&node.link_succs)
+            .chain(&node.dependents)
+            .map(|&succ| latest[&succ] - cost(succ))
+            .min()
+            .unwrap_or(length);
+        latest.insert(sid, end);
+    }
+
+    let dedicated = |family: &FamilyInfo| family.capability == Capability::Transfer;
+    let mut suggestions = Vec::new();
+    for &(_, sid) in &order {
+        if chains.schedule[sid].is_transition() {
+            continue;
+        }
+        let pass = passes[&chains.schedule[sid].pass()];
+        let transfer_only = pass.declared_capability().is_some()
+            && pass.required_capability() == Some(Capability::Transfer);
+        let executed_on_dedicated = families
+            .iter()
+            .any(|family| family.id == sid.family() && dedicated(family));
+        if !transfer_only || pass.queue.is_some() || executed_on_dedicated {
+            continue;
+        }
+        let target = families.iter().find(|family| {
+            dedicated(family)
+                && family.queue_count > 0
+                && pass
+                    .misaligned_transfer(family.image_transfer_granularity)
+                    .is_none()
+        });
+        let target = match target {
+            Some(target) => target.id,
+            None => continue,
+        };
+
+        // Off its queue the pass is constrained only by dependencies and links.
+        let node = &adjacent[&sid];
+        let earliest = node
+            .link_preds
+            .iter()
+            .chain(&node.dependencies)
+            .map(|pred| finish[pred])
+            .max()
+            .unwrap_or(0);
+        let end = node
+            .link_succs
+            .iter()
+            .chain(&node.dependents)
+            .map(|&succ| latest[&succ] - cost(succ))
+            .min()
+            .unwrap_or(length);
+        if earliest + cost(sid) > end {
+            continue;
+        }
+
+        let target_semaphores = node.semaphores(QueueId::new(target, 0));
+        suggestions.push(OffloadSuggestion {
+            pass: pass.id,
+            target_family: target,
+            added_semaphores: target_semaphores.saturating_sub(node.semaphores(sid.queue())),
+            slack: end - earliest - cost(sid),
+        });
+    }
+    suggestions.sort_by_key(|suggestion| suggestion.pass);
+    suggestions
+}
@@ -0,0 +1,803 @@
+use alloc::vec::Vec;
+use collections::HashMap;
+use core::fmt::{self, Display};
+use core::hash::Hash;
+use core::ops::Range as StdRange;
+#[cfg(feature = "std")]
+use std::error::Error;
+
+use hal::buffer::{Access as BufferAccess, Usage as BufferUsage};
+use hal::image::{
+    Access as ImageAccess, Layout as ImageLayout, SubresourceRange, Usage as ImageUsage,
+};
+use hal::pso::PipelineStage;
+use hal::queue::QueueFamilyId;
+
+use resource::{Buffer, BufferLayout, Id, Image, MergeError, Resource, ResourceGroup, State};
+
+use super::{
+    merge_overlapping, Capability, ImageUse, OrderingHint, Pass, PassId, PassKind, PassMeta,
+    StateUsage, TransferRegion,
+};
+
+/// Error returned by `PassBuilder::build`
+/// when the same resource is declared twice with states that can't be merged.
+#[derive(Clone, Debug)]
+pub enum PassBuildError {
+    /// Buffer declared in states that can't be merged.
+    IncompatibleBufferStates {
+        /// Id of the buffer.
+        id: Id<Buffer>,
+        /// Id of the pass.
+        pass: PassId,
+        /// States that can't be merged.
+        error: MergeError<Buffer>,
+    },
+
+    /// Image declared in states that can't be merged.
+    IncompatibleImageStates {
+        /// Id of the image.
+        id: Id<Image>,
+        /// Id of the pass.
+        pass: PassId,
+        /// States that can't be merged.
+        error: MergeError<Image>,
+    },
+
+    /// Image declared in overlapping ranges with states that can't be merged.
+    IncompatibleImageRanges {
+        /// Id of the image.
+        id: Id<Image>,
+        /// Id of the pass.
+        pass: PassId,
+        /// Overlapping ranges.
+        ranges: (SubresourceRange, SubresourceRange),
+        /// States that can't be merged.
+        error: MergeError<Image>,
+    },
+}
+
+impl From<(Id<Buffer>, PassId, MergeError<Buffer>)> for PassBuildError {
+    fn from((id, pass, error): (Id<Buffer>, PassId, MergeError<Buffer>)) -> Self {
+        PassBuildError::IncompatibleBufferStates { id, pass, error }
+    }
+}
+
+impl From<(Id<Image>, PassId, MergeError<Image>)> for PassBuildError {
+    fn from((id, pass, error): (Id<Image>, PassId, MergeError<Image>)) -> Self {
+        PassBuildError::IncompatibleImageStates { id, pass, error }
+    }
+}
+
+impl Display for PassBuildError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PassBuildError::IncompatibleBufferStates {
+                id,
+                pass,
+                ref error,
+            } => write!(
+                fmt,
+                "Buffer {:?} declared twice in pass {:?}: {}",
+                id, pass, error
+            ),
+            PassBuildError::IncompatibleImageStates {
+                id,
+                pass,
+                ref error,
+            } => write!(
+                fmt,
+                "Image {:?} declared twice in pass {:?}: {}",
+                id, pass, error
+            ),
+            PassBuildError::IncompatibleImageRanges {
+                id,
+                pass,
+                ranges: (ref left, ref right),
+                ref error,
+            } => write!(
+                fmt,
+                "Image {:?} declared in overlapping ranges {:?} and {:?} in pass {:?}: {}",
+                id, left, right, pass, error
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for PassBuildError {
+    fn description(&self) -> &str {
+        match *self {
+            PassBuildError::IncompatibleBufferStates { .. } => "Incompatible buffer states",
+            PassBuildError::IncompatibleImageStates { .. } => "Incompatible image states",
+            PassBuildError::IncompatibleImageRanges { .. } => "Incompatible image ranges",
+        }
+    }
+}
+
+/// Builder for `Pass`.
+///
+/// Convenience methods declare resources with access, layout, usage and stages
+/// of the common use cases.
+/// Declarations of the same resource are merged by `build`,
+/// except for declarations of images in disjoint ranges.
+#[derive(Clone, Debug)]
+pub struct PassBuilder {
+    pass: Pass,
+    buffers: Vec<(Id<Buffer>, StateUsage<Buffer>)>,
+    images: Vec<(Id<Image>, StateUsage<Image>)>,
+}
+
+impl PassBuilder {
+    /// Create builder for pass executed on the family.
+    pub fn new(id: PassId, family: QueueFamilyId) -> Self {
+        PassBuilder {
+            pass: Pass {
+                id,
+                kind: PassKind::Commands,
+                family,
+                families: Vec::new(),
+                preferred_family: None,
+                queue: None,
+                dependencies: Vec::new(),
+                buffers: HashMap::default(),
+                images: HashMap::default(),
+                image_ranges: HashMap::default(),
+                side_effects: false,
+                conditional: false,
+                cost: 1,
+                capability: None,
+                transfer_regions: HashMap::default(),
+                image_uses: HashMap::default(),
+                resolves: HashMap::default(),
+                meta: PassMeta::default(),
+            },
+            buffers: Vec::new(),
+            images: Vec::new(),
+        }
+    }
+
+    /// Create builder for sparse binding pass executed on the family.
+    /// Resources are declared with `bind_buffer` and `bind_image`.
+    /// See `PassKind::SparseBind`.
+    pub fn sparse_bind(id: PassId, family: QueueFamilyId) -> Self {
+        let mut builder = Self::new(id, family);
+        builder.pass.kind = PassKind::SparseBind;
+        builder
+    }
+
+    /// Allow the pass to be executed on another family.
+    /// See `Pass::allow_family`.
+    pub fn allow_family(mut self, family: QueueFamilyId) -> Self {
+        self.pass = self.pass.allow_family(family);
+        self
+    }
+
+    /// Hint scheduler to place the pass on the family.
+    /// See `Pass::prefer_family`.
+    pub fn prefer_family(mut self, family: QueueFamilyId) -> Self {
+        self.pass = self.pass.prefer_family(family);
+        self
+    }
+
+    /// Hint scheduler with relative cost of executing the pass.
+    /// See `Pass::cost`.
+    pub fn cost(mut self, cost: u32) -> Self {
+        self.pass = self.pass.cost(cost);
+        self
+    }
+
+    /// Name the pass for diagnostics.
+    /// See `Pass::meta`.
+    pub fn name(mut self, name: &'static str) -> Self {
+        self.pass = self.pass.name(name);
+        self
+    }
+
+    /// Tag the pass with user value.
+    /// See `Pass::meta`.
+    pub fn tag(mut self, tag: u64) -> Self {
+        self.pass = self.pass.tag(tag);
+        self
+    }
+
+    /// Require capability from families executing the pass.
+    /// See `Pass::capability`.
+    pub fn capability(mut self, capability: Capability) -> Self {
+        self.pass = self.pass.capability(capability);
+        self
+    }
+
+    /// Declare region of the image accessed by transfer commands of the pass.
+    /// See `Pass::transfer_regions`.
+    pub fn transfer_region(mut self, id: Id<Image>, region: TransferRegion) -> Self {
+        self.pass = self.pass.transfer_region(id, region);
+        self
+    }
+
+    /// Pin the pass to specific queue of the family.
+    pub fn queue(mut self, index: usize) -> Self {
+        self.pass.queue = Some(index);
+        self
+    }
+
+    /// Make the pass depend on another one.
+    pub fn depends_on(mut self, pass: PassId) -> Self {
+        self.pass.dependencies.push(pass);
+        self
+    }
+
+    /// Mark the pass as having effects not visible through declared resources.
+    /// See `Pass::side_effects`.
+    pub fn side_effects(mut self) -> Self {
+        self.pass.side_effects = true;
+        self
+    }
+
+    /// Mark the pass as one which commands are recorded within single render pass.
+    /// See `PassKind::RenderPass`.
+    pub fn render_pass(mut self) -> Self {
+        self.pass.kind = PassKind::RenderPass;
+        self
+    }
+
+    /// Mark the pass as one which commands may be skipped at record time.
+    /// See `Pass::conditional`.
+    pub fn conditional(mut self) -> Self {
+        self.pass.conditional = true;
+        self
+    }
+
+    /// Declare buffer state.
+    /// Usage is derived from the state.
+    pub fn raw_buffer_state(self, id: Id<Buffer>, state: State<Buffer>) -> Self {
+        self.raw_buffer(
+            id,
+            StateUsage {
+                state,
+                usage: BufferUsage::empty(),
+                range: None,
+                discard: false,
+                ordering: OrderingHint::None,
+            },
+        )
+    }
+
+    /// Declare buffer state, usage and range.
+    pub fn raw_buffer(mut self, id: Id<Buffer>, state_usage: StateUsage<Buffer>) -> Self {
+        self.buffers.push((id, state_usage));
+        self
+    }
+
+    /// Declare image state.
+    /// Usage is derived from the state.
+    pub fn raw_image_state(self, id: Id<Image>, state: State<Image>) -> Self {
+        self.raw_image(
+            id,
+            StateUsage {
+                state,
+                usage: ImageUsage::empty(),
+                range: None,
+                discard: false,
+                ordering: OrderingHint::None,
+            },
+        )
+    }
+
+    /// Declare image state, usage and range.
+    pub fn raw_image(mut self, id: Id<Image>, state_usage: StateUsage<Image>) -> Self {
+        self.images.push((id, state_usage));
+        self
+    }
+
+    /// Declare image with canonical state and usage of the use.
+    /// Unlike other declarations, uses of the same image that degrade its layout
+    /// to `General` when merged are reported by `collect::collect_with_warnings`.
+    pub fn image_use(mut self, id: Id<Image>, image_use: ImageUse) -> Self {
+        self.pass.image_uses.entry(id).or_default().push(image_use);
+        self.raw_image(id, image_use.state_usage())
+    }
+
+    /// Declare every image of the group with the same declaration.
+    /// Like `.image_group(&gbuffer, PassBuilder::color_attachment)`.
+    pub fn image_group<F>(self, group: &ResourceGroup<Image>, declare: F) -> Self
+    where
+        F: Fn(Self, Id<Image>) -> Self,
+    {
+        group.ids().iter().fold(self, |builder, &id| declare(builder, id))
+    }
+
+    /// Read buffer as uniform buffer at specified shader stages.
+    pub fn uniform_buffer(self, id: Id<Buffer>, stages: PipelineStage) -> Self {
+        self.buffer(
+            id,
+            BufferAccess::CONSTANT_BUFFER_READ,
+            BufferUsage::UNIFORM,
+            stages,
+        )
+    }
+
+    /// Read buffer as storage buffer at specified shader stages.
+    pub fn storage_buffer_read(self, id: Id<Buffer>, stages: PipelineStage) -> Self {
+        self.buffer(id, BufferAccess::SHADER_READ, BufferUsage::STORAGE, stages)
+    }
+
+    /// Write buffer as storage buffer at specified shader stages.
+    pub fn storage_buffer_write(self, id: Id<Buffer>, stages: PipelineStage) -> Self {
+        self.buffer(id, BufferAccess::SHADER_WRITE, BufferUsage::STORAGE, stages)
+    }
+
+    /// Read vertices from buffer.
+    pub fn vertex_buffer(self, id: Id<Buffer>) -> Self {
+        self.buffer(
+            id,
+            BufferAccess::VERTEX_BUFFER_READ,
+            BufferUsage::VERTEX,
+            PipelineStage::VERTEX_INPUT,
+        )
+    }
+
+    /// Read indices from buffer.
+    pub fn index_buffer(self, id: Id<Buffer>) -> Self {
+        self.buffer(
+            id,
+            BufferAccess::INDEX_BUFFER_READ,
+            BufferUsage::INDEX,
+            PipelineStage::VERTEX_INPUT,
+        )
+    }
+
+    /// Read indirect draw or dispatch arguments from buffer.
+    pub fn indirect_buffer(self, id: Id<Buffer>) -> Self {
+        self.buffer(
+            id,
+            BufferAccess::INDIRECT_COMMAND_READ,
+            BufferUsage::INDIRECT,
+            PipelineStage::DRAW_INDIRECT,
+        )
+    }
+
+    /// Copy from buffer.
+    pub fn transfer_buffer_src(self, id: Id<Buffer>) -> Self {
+        self.buffer(
+            id,
+            BufferAccess::TRANSFER_READ,
+            BufferUsage::TRANSFER_SRC,
+            PipelineStage::TRANSFER,
+        )
+    }
+
+    /// Copy to buffer.
+    pub fn transfer_buffer_dst(self, id: Id<Buffer>) -> Self {
+        self.buffer(
+            id,
+            BufferAccess::TRANSFER_WRITE,
+            BufferUsage::TRANSFER_DST,
+            PipelineStage::TRANSFER,
+        )
+    }
+
+    /// Sample image at specified shader stages.
+    pub fn sample_image(self, id: Id<Image>, stages: PipelineStage) -> Self {
+        self.image(
+            id,
+            ImageAccess::SHADER_READ,
+            ImageLayout::ShaderReadOnlyOptimal,
+            ImageUsage::SAMPLED,
+            stages,
+        )
+    }
+
+    /// Read image as storage image at specified shader stages.
+    pub fn storage_image_read(self, id: Id<Image>, stages: PipelineStage) -> Self {
+        self.image(
+            id,
+            ImageAccess::SHADER_READ,
+            ImageLayout::General,
+            ImageUsage::STORAGE,
+            stages,
+        )
+    }
+
+    /// Write image as storage image at specified shader stages.
+    pub fn storage_image_write(self, id: Id<Image>, stages: PipelineStage) -> Self {
+        self.image(
+            id,
+            ImageAccess::SHADER_WRITE,
+            ImageLayout::General,
+            ImageUsage::STORAGE,
+            stages,
+        )
+    }
+
+    /// Use image as color attachment.
+    pub fn color_attachment(self, id: Id<Image>) -> Self {
+        self.image(
+            id,
+            ImageAccess::COLOR_ATTACHMENT_READ | ImageAccess::COLOR_ATTACHMENT_WRITE,
+            ImageLayout::ColorAttachmentOptimal,
+            ImageUsage::COLOR_ATTACHMENT,
+            PipelineStage::COLOR_ATTACHMENT_OUTPUT,
+        )
+    }
+
+    /// Use image as depth-stencil attachment with depth writes.
+    pub fn depth_stencil_attachment(self, id: Id<Image>) -> Self {
+        self.image(
+            id,
+            ImageAccess::DEPTH_STENCIL_ATTACHMENT_READ
+                | ImageAccess::DEPTH_STENCIL_ATTACHMENT_WRITE,
+            ImageLayout::DepthStencilAttachmentOptimal,
+            ImageUsage::DEPTH_STENCIL_ATTACHMENT,
+            PipelineStage::EARLY_FRAGMENT_TESTS | PipelineStage::LATE_FRAGMENT_TESTS,
+        )
+    }
+
+    /// Use image as read-only depth-stencil attachment.
+    pub fn depth_stencil_read(self, id: Id<Image>) -> Self {
+        self.image(
+            id,
+            ImageAccess::DEPTH_STENCIL_ATTACHMENT_READ,
+            ImageLayout::DepthStencilReadOnlyOptimal,
+            ImageUsage::DEPTH_STENCIL_ATTACHMENT,
+            PipelineStage::EARLY_FRAGMENT_TESTS | PipelineStage::LATE_FRAGMENT_TESTS,
+        )
+    }
+
+    /// Read image as input attachment.
+    pub fn input_attachment(self, id: Id<Image>) -> Self {
+        self.image(
+            id,
+            ImageAccess::INPUT_ATTACHMENT_READ,
+            ImageLayout::ShaderReadOnlyOptimal,
+            ImageUsage::INPUT_ATTACHMENT,
+            PipelineStage::FRAGMENT_SHADER,
+        )
+    }
+
+    /// Resolve multisampled color attachment into single-sample image
+    /// with resolve attachment of the render pass.
+    /// Previous content of `dst` is discarded.
+    /// Declare `src` as color attachment too if the pass renders into it.
+    pub fn resolve(mut self, src: Id<Image>, dst: Id<Image>) -> Self {
+        self.pass.resolves.insert(dst, src);
+        self.image(
+            src,
+            ImageAccess::COLOR_ATTACHMENT_READ,
+            ImageLayout::ColorAttachmentOptimal,
+            ImageUsage::COLOR_ATTACHMENT,
+            PipelineStage::COLOR_ATTACHMENT_OUTPUT,
+        ).image(
+            dst,
+            ImageAccess::COLOR_ATTACHMENT_WRITE,
+            ImageLayout::ColorAttachmentOptimal,
+            ImageUsage::COLOR_ATTACHMENT,
+            PipelineStage::COLOR_ATTACHMENT_OUTPUT,
+        ).discard_image(dst)
+    }
+
+    /// Resolve multisampled image into single-sample image with transfer commands.
+    /// Previous content of `dst` is discarded.
+    pub fn resolve_image(self, src: Id<Image>, dst: Id<Image>) -> Self {
+        self.transfer_image_src(src)
+            .transfer_image_dst(dst)
+            .discard_image(dst)
+    }
+
+    /// Copy from image.
+    pub fn transfer_image_src(self, id: Id<Image>) -> Self {
+        self.image(
+            id,
+            ImageAccess::TRANSFER_READ,
+            ImageLayout::TransferSrcOptimal,
+            ImageUsage::TRANSFER_SRC,
+            PipelineStage::TRANSFER,
+        )
+    }
+
+    /// Copy or clear to image.
+    pub fn transfer_image_dst(self, id: Id<Image>) -> Self {
+        self.image(
+            id,
+            ImageAccess::TRANSFER_WRITE,
+            ImageLayout::TransferDstOptimal,
+            ImageUsage::TRANSFER_DST,
+            PipelineStage::TRANSFER,
+        )
+    }
+
+    /// Copy range of buffer to subresources of image, like texture upload from staging buffer.
+    /// Previous content of `dst_range` is kept. Call `discard_image` if copy overwrites it.
+    pub fn copy_buffer_to_image(
+        self,
+        src: Id<Buffer>,
+        src_range: StdRange<u64>,
+        dst: Id<Image>,
+        dst_range: SubresourceRange,
+    ) -> Self {
+        self.buffer_range(
+            src,
+            BufferAccess::TRANSFER_READ,
+            BufferUsage::TRANSFER_SRC,
+            src_range,
+        ).image_range(
+            dst,
+            ImageAccess::TRANSFER_WRITE,
+            ImageLayout::TransferDstOptimal,
+            ImageUsage::TRANSFER_DST,
+            dst_range,
+        )
+    }
+
+    /// Copy subresources of image to range of buffer, like readback into staging buffer.
+    pub fn copy_image_to_buffer(
+        self,
+        src: Id<Image>,
+        src_range: SubresourceRange,
+        dst: Id<Buffer>,
+        dst_range: StdRange<u64>,
+    ) -> Self {
+        self.image_range(
+            src,
+            ImageAccess::TRANSFER_READ,
+            ImageLayout::TransferSrcOptimal,
+            ImageUsage::TRANSFER_SRC,
+            src_range,
+        ).buffer_range(
+            dst,
+            BufferAccess::TRANSFER_WRITE,
+            BufferUsage::TRANSFER_DST,
+            dst_range,
+        )
+    }
+
+    /// Clear or overwrite whole image with transfer commands.
+    /// Previous content of the image is discarded.
+    pub fn clear_image(self, id: Id<Image>) -> Self {
+        self.transfer_image_dst(id).discard_image(id)
+    }
+
+    /// Clear color attachment when render pass begins.
+    /// Previous content of the image is discarded.
+    pub fn clear_color_attachment(self, id: Id<Image>) -> Self {
+        self.color_attachment(id).discard_image(id)
+    }
+
+    /// Make all declarations of the image discard its previous content.
+    /// See `StateUsage::discard`.
+    pub fn discard_image(mut self, id: Id<Image>) -> Self {
+        for &mut (image, ref mut state_usage) in &mut self.images {
+            if image == id {
+                state_usage.discard = true;
+            }
+        }
+        self
+    }
+
+    /// Hint scheduler to place the pass relative to other accesses of the buffer.
+    /// Applies to all declarations of the buffer made so far.
+    /// See `OrderingHint`.
+    pub fn buffer_ordering(mut self, id: Id<Buffer>, hint: OrderingHint) -> Self {
+        for &mut (buffer, ref mut state_usage) in &mut self.buffers {
+            if buffer == id {
+                state_usage.ordering = hint;
+            }
+        }
+        self
+    }
+
+    /// Hint scheduler to place the pass relative to other accesses of the image.
+    /// Applies to all declarations of the image made so far.
+    /// See `OrderingHint`.
+    pub fn image_ordering(mut self, id: Id<Image>, hint: OrderingHint) -> Self {
+        for &mut (image, ref mut state_usage) in &mut self.images {
+            if image == id {
+                state_usage.ordering = hint;
+            }
+        }
+        self
+    }
+
+    /// Bind memory to the range of sparse buffer.
+    /// Or to whole buffer if `range` is `None`.
+    /// Pass must be created with `sparse_bind`.
+    pub fn bind_buffer(self, id: Id<Buffer>, range: Option<StdRange<u64>>) -> Self {
+        self.raw_buffer(
+            id,
+            StateUsage {
+                state: State {
+                    access: BufferAccess::empty(),
+                    layout: BufferLayout,
+                    stages: PipelineStage::empty(),
+                },
+                usage: BufferUsage::empty(),
+                range,
+                discard: false,
+                ordering: OrderingHint::None,
+            },
+        )
+    }
+
+    /// Bind memory to the range of sparse image.
+    /// Or to whole image if `range` is `None`.
+    /// Pass must be created with `sparse_bind`.
+    pub fn bind_image(self, id: Id<Image>, range: Option<SubresourceRange>) -> Self {
+        self.raw_image(
+            id,
+            StateUsage {
+                state: State {
+                    access: ImageAccess::empty(),
+                    layout: ImageLayout::Undefined,
+                    stages: PipelineStage::empty(),
+                },
+                usage: ImageUsage::empty(),
+                range,
+                discard: false,
+                ordering: OrderingHint::None,
+            },
+        )
+    }
+
+    /// Build the pass.
+    /// Declarations of the same buffer are merged.
+    /// Declarations of the same image are merged if their ranges overlap.
+    /// Image declared in several disjoint ranges is put into `Pass::image_ranges`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `PassBuildError` if the same resource is declared in states that can't be merged.
+    pub fn build(self) -> Result<Pass, PassBuildError> {
+        let PassBuilder {
+            mut pass,
+            buffers,
+            images,
+        } = self;
+        pass.buffers = merge_declarations(pass.id, buffers)?;
+
+        let mut ids: Vec<Id<Image>> = images.iter().map(|&(id, _)| id).collect();
+        ids.sort();
+        ids.dedup();
+        for id in ids {
+            let declared = images
+                .iter()
+                .filter(|&&(image, _)| image == id)
+                .map(|(_, state_usage)| state_usage.clone());
+            let mut merged = merge_overlapping(declared).map_err(|conflict| {
+                match conflict.ranges {
+                    Some(ranges) => PassBuildError::IncompatibleImageRanges {
+                        id,
+                        pass: pass.id,
+                        ranges,
+                        error: conflict.error,
+                    },
+                    None => (id, pass.id, conflict.error).into(),
+                }
+            })?;
+            if merged.len() == 1 {
+                pass.images.insert(id, merged.pop().unwrap());
+            } else {
+                pass.image_ranges.insert(id, merged);
+            }
+        }
+        Ok(pass)
+    }
+
+    fn buffer(
+        self,
+        id: Id<Buffer>,
+        access: BufferAccess,
+        usage: BufferUsage,
+        stages: PipelineStage,
+    ) -> Self {
+        self.raw_buffer(
+            id,
+            StateUsage {
+                state: State {
+                    access,
+                    layout: BufferLayout,
+                    stages,
+                },
+                usage,
+                range: None,
+                discard: false,
+                ordering: OrderingHint::None,
+            },
+        )
+    }
+
+    fn image(
+        self,
+        id: Id<Image>,
+        access: ImageAccess,
+        layout: ImageLayout,
+        usage: ImageUsage,
+        stages: PipelineStage,
+    ) -> Self {
+        self.raw_image(
+            id,
+            StateUsage {
+                state: State {
+                    access,
+                    layout,
+                    stages,
+                },
+                usage,
+                range: None,
+                discard: false,
+                ordering: OrderingHint::None,
+            },
+        )
+    }
+
+    /// Declare range of buffer accessed by transfer commands.
+    fn buffer_range(
+        self,
+        id: Id<Buffer>,
+        access: BufferAccess,
+        usage: BufferUsage,
+        range: StdRange<u64>,
+    ) -> Self {
+        self.raw_buffer(
+            id,
+            StateUsage {
+                state: State {
+                    access,
+                    layout: BufferLayout,
+                    stages: PipelineStage::TRANSFER,
+                },
+                usage,
+                range: Some(range),
+                discard: false,
+                ordering: OrderingHint::None,
+            },
+        )
+    }
+
+    /// Declare subresources of image accessed by transfer commands.
+    fn image_range(
+        self,
+        id: Id<Image>,
+        access: ImageAccess,
+        layout: ImageLayout,
+        usage: ImageUsage,
+        range: SubresourceRange,
+    ) -> Self {
+        self.raw_image(
+            id,
+            StateUsage {
+                state: State {
+                    access,
+                    layout,
+                    stages: PipelineStage::TRANSFER,
+                },
+                usage,
+                range: Some(range),
+                discard: false,
+                ordering: OrderingHint::None,
+            },
+        )
+    }
+}
+
+/// Merge declarations of the same resources.
+fn merge_declarations<R>(
+    pass: PassId,
+    declarations: Vec<(Id<R>, StateUsage<R>)>,
+) -> Result<HashMap<Id<R>, StateUsage<R>>, PassBuildError>
+where
+    R: Resource,
+    Id<R>: Hash,
+    PassBuildError: From<(Id<R>, PassId, MergeError<R>)>,
+{
+    let mut map: HashMap<Id<R>, StateUsage<R>> = HashMap::default();
+    for (id, declared) in declarations {
+        let merged = match map.remove(&id) {
+            None => declared,
+            Some(old) => old
+                .try_merge(&declared)
+                .map_err(|error| (id, pass, error))?,
+        };
+        map.insert(id, merged);
+    }
+    Ok(map)
+}
@@ -0,0 +1,62 @@
+use hal::pso::PipelineStage;
+
+/// Class of operations queue family supports.
+/// Following Vulkan, graphics families support compute operations
+/// and compute families support transfer operations,
+/// so capabilities are ordered by what they support.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize))]
+pub enum Capability {
+    /// Transfer commands only.
+    Transfer,
+
+    /// Compute dispatches and transfer commands.
+    Compute,
+
+    /// Draws, compute dispatches and transfer commands.
+    Graphics,
+}
+
+/// Capability required by each stage.
+/// Top and bottom of pipe and host stages require none.
+/// Indirect arguments are read for dispatches as well.
+const STAGES: [(PipelineStage, Capability); 12] = [
+    (PipelineStage::DRAW_INDIRECT, Capability::Compute),
+    (PipelineStage::VERTEX_INPUT, Capability::Graphics),
+    (PipelineStage::VERTEX_SHADER, Capability::Graphics),
+    (PipelineStage::HULL_SHADER, Capability::Graphics),
+    (PipelineStage::DOMAIN_SHADER, Capability::Graphics),
+    (PipelineStage::GEOMETRY_SHADER, Capability::Graphics),
+    (PipelineStage::FRAGMENT_SHADER, Capability::Graphics),
+    (PipelineStage::EARLY_FRAGMENT_TESTS, Capability::Graphics),
+    (PipelineStage::LATE_FRAGMENT_TESTS, Capability::Graphics),
+    (PipelineStage::COLOR_ATTACHMENT_OUTPUT, Capability::Graphics),
+    (PipelineStage::COMPUTE_SHADER, Capability::Compute),
+    (PipelineStage::TRANSFER, Capability::Transfer),
+];
+
+impl Capability {
+    /// Get capability required to execute commands at the stages.
+    /// Returns `None` if stages require no capability.
+    pub fn required(stages: PipelineStage) -> Option<Self> {
+        Self::required_by(stages).map(|(_, capability)| capability)
+    }
+
+    /// Get the first of the stages that require the most capable family
+    /// together with the capability.
+    /// Returns `None` if stages require no capability.
+    pub fn required_by(stages: PipelineStage) -> Option<(PipelineStage, Self)> {
+        STAGES
+            .iter()
+            .filter(|&&(stage, _)| stages.contains(stage))
+            .fold(None, |required, &(stage, capability)| match required {
+                Some((_, other)) if other >= capability => required,
+                _ => Some((stage, capability)),
+            })
+    }
+
+    /// Check if family with this capability can execute operations that require `other`.
+    pub fn supports(&self, other: Self) -> bool {
+        *self >= other
+    }
+}
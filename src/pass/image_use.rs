@@ -0,0 +1,151 @@
+use hal::image::{Access as ImageAccess, Layout as ImageLayout, Usage as ImageUsage};
+use hal::pass::{AttachmentLoadOp, AttachmentOps};
+use hal::pso::PipelineStage;
+
+use resource::{Image, Layout, State};
+
+use super::{OrderingHint, StateUsage};
+
+/// Kind of image declaration.
+/// Each kind maps to canonical state and usage of the image.
+/// Declared with `PassBuilder::image_use`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize))]
+pub enum ImageUse {
+    /// Color attachment.
+    /// Previous content is discarded unless attachment is loaded.
+    Attachment(
+        #[cfg_attr(feature = "serde-1", serde(with = "::ser::repr"))]
+        AttachmentOps,
+    ),
+
+    /// Depth-stencil attachment with depth writes.
+    /// Previous content is discarded unless attachment is loaded.
+    DepthStencilAttachment(
+        #[cfg_attr(feature = "serde-1", serde(with = "::ser::repr"))]
+        AttachmentOps,
+    ),
+
+    /// Color attachment read as input attachment by fragment shader of the same pass,
+    /// like for programmable blending. Kept in `General` layout.
+    /// Synchronization of the pass contains its self-dependency. See `sync::FeedbackLoop`.
+    /// Previous content is discarded unless attachment is loaded.
+    FeedbackLoop(
+        #[cfg_attr(feature = "serde-1", serde(with = "::ser::repr"))]
+        AttachmentOps,
+    ),
+
+    /// Image sampled at specified shader stages.
+    Sampled(
+        #[cfg_attr(feature = "serde-1", serde(with = "::ser::repr"))]
+        PipelineStage,
+    ),
+
+    /// Storage image read and written at specified shader stages.
+    Storage(
+        #[cfg_attr(feature = "serde-1", serde(with = "::ser::repr"))]
+        PipelineStage,
+    ),
+
+    /// Source of transfer commands.
+    TransferSrc,
+
+    /// Destination of transfer commands.
+    TransferDst,
+}
+
+impl ImageUse {
+    /// Get canonical state of the image for this use.
+    pub fn state(&self) -> State<Image> {
+        let (access, layout, stages) = match *self {
+            ImageUse::Attachment(_) => (
+                ImageAccess::COLOR_ATTACHMENT_READ | ImageAccess::COLOR_ATTACHMENT_WRITE,
+                ImageLayout::ColorAttachmentOptimal,
+                PipelineStage::COLOR_ATTACHMENT_OUTPUT,
+            ),
+            ImageUse::DepthStencilAttachment(_) => (
+                ImageAccess::DEPTH_STENCIL_ATTACHMENT_READ
+                    | ImageAccess::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                ImageLayout::DepthStencilAttachmentOptimal,
+                PipelineStage::EARLY_FRAGMENT_TESTS | PipelineStage::LATE_FRAGMENT_TESTS,
+            ),
+            ImageUse::FeedbackLoop(_) => (
+                ImageAccess::INPUT_ATTACHMENT_READ
+                    | ImageAccess::COLOR_ATTACHMENT_READ
+                    | ImageAccess::COLOR_ATTACHMENT_WRITE,
+                ImageLayout::General,
+                PipelineStage::FRAGMENT_SHADER | PipelineStage::COLOR_ATTACHMENT_OUTPUT,
+            ),
+            ImageUse::Sampled(stages) => (
+                ImageAccess::SHADER_READ,
+                ImageLayout::ShaderReadOnlyOptimal,
+                stages,
+            ),
+            ImageUse::Storage(stages) => (
+                ImageAccess::SHADER_READ | ImageAccess::SHADER_WRITE,
+                ImageLayout::General,
+                stages,
+            ),
+            ImageUse::TransferSrc => (
+                ImageAccess::TRANSFER_READ,
+                ImageLayout::TransferSrcOptimal,
+                PipelineStage::TRANSFER,
+            ),
+            ImageUse::TransferDst => (
+                ImageAccess::TRANSFER_WRITE,
+                ImageLayout::TransferDstOptimal,
+                PipelineStage::TRANSFER,
+            ),
+        };
+        State {
+            access,
+            layout,
+            stages,
+        }
+    }
+
+    /// Get usage flags required for this use.
+    pub fn usage(&self) -> ImageUsage {
+        match *self {
+            ImageUse::Attachment(_) => ImageUsage::COLOR_ATTACHMENT,
+            ImageUse::DepthStencilAttachment(_) => ImageUsage::DEPTH_STENCIL_ATTACHMENT,
+            ImageUse::FeedbackLoop(_) => {
+                ImageUsage::COLOR_ATTACHMENT | ImageUsage::INPUT_ATTACHMENT
+            }
+            ImageUse::Sampled(_) => ImageUsage::SAMPLED,
+            ImageUse::Storage(_) => ImageUsage::STORAGE,
+            ImageUse::TransferSrc => ImageUsage::TRANSFER_SRC,
+            ImageUse::TransferDst => ImageUsage::TRANSFER_DST,
+        }
+    }
+
+    /// Check if this use discards previous content of the image.
+    pub fn discards(&self) -> bool {
+        match *self {
+            ImageUse::Attachment(ops)
+            | ImageUse::DepthStencilAttachment(ops)
+            | ImageUse::FeedbackLoop(ops) => {
+                ops.load != AttachmentLoadOp::Load
+            }
+            _ => false,
+        }
+    }
+
+    /// Get declaration of the whole image for this use.
+    pub fn state_usage(&self) -> StateUsage<Image> {
+        StateUsage {
+            state: self.state(),
+            usage: self.usage(),
+            range: None,
+            discard: self.discards(),
+            ordering: OrderingHint::None,
+        }
+    }
+
+    /// Check if merging with another use puts the image into `General` layout,
+    /// which is slower for at least one of them.
+    pub(crate) fn degrades_layout(&self, other: &ImageUse) -> bool {
+        let (left, right) = (self.state().layout, other.state().layout);
+        left != right && left.merge(right) == Some(ImageLayout::General)
+    }
+}
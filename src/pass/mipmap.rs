@@ -0,0 +1,158 @@
+use alloc::vec::Vec;
+use core::fmt::{self, Display};
+#[cfg(feature = "std")]
+use std::error::Error;
+
+use hal::image::{
+    Access as ImageAccess, Layout as ImageLayout, SubresourceRange, Usage as ImageUsage,
+};
+use hal::pso::PipelineStage;
+use hal::queue::QueueFamilyId;
+
+use resource::{Id, Image, State};
+
+use super::{Capability, OrderingHint, Pass, PassBuildError, PassBuilder, PassId, StateUsage};
+
+/// Error returned by `generate_mipmaps`.
+#[derive(Clone, Debug)]
+pub enum MipmapError {
+    /// Pass that produces the first level is not found.
+    ProducerNotFound {
+        /// Id of the pass.
+        pass: PassId,
+    },
+
+    /// Pass that produces the first level doesn't declare the image.
+    NotProduced {
+        /// Id of the image.
+        id: Id<Image>,
+        /// Id of the pass.
+        pass: PassId,
+    },
+
+    /// Blit pass declares the image in states that can't be merged.
+    Build(PassBuildError),
+}
+
+impl From<PassBuildError> for MipmapError {
+    fn from(error: PassBuildError) -> Self {
+        MipmapError::Build(error)
+    }
+}
+
+impl Display for MipmapError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MipmapError::ProducerNotFound { pass } => {
+                write!(fmt, "Pass {:?} that produces the first level is not found", pass)
+            }
+            MipmapError::NotProduced { id, pass } => {
+                write!(fmt, "Pass {:?} doesn't declare image {:?}", pass, id)
+            }
+            MipmapError::Build(ref error) => write!(fmt, "{}", error),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for MipmapError {
+    fn description(&self) -> &str {
+        match *self {
+            MipmapError::ProducerNotFound { .. } => "Producer not found",
+            MipmapError::NotProduced { .. } => "Image not produced",
+            MipmapError::Build(_) => "Blit pass can't be built",
+        }
+    }
+}
+
+/// Add passes that generate levels of the image by blitting each level from the previous one.
+///
+/// `range` is the range of the image to generate levels in. Its first level is produced
+/// by the `producer` pass. Each blit pass reads the previous level in `TransferSrcOptimal`
+/// and writes the next one in `TransferDstOptimal` discarding its content,
+/// so barriers between blits transition single levels.
+/// Blit passes are ordinary passes with ids following the greatest id of `passes`.
+/// They are executed on `family` and require graphics capability, as blits do.
+///
+/// Other passes that declare the image and depend on `producer`
+/// are made dependent on the last blit pass, so they observe all levels.
+/// Returns ids of blit passes in order of levels they write.
+/// Commands of each pass must blit into level `range.levels.start + 1 + index`.
+///
+/// # Errors
+///
+/// Returns `MipmapError` if `producer` is not found or doesn't declare the image.
+pub fn generate_mipmaps(
+    passes: &mut Vec<Pass>,
+    producer: PassId,
+    id: Id<Image>,
+    range: SubresourceRange,
+    family: QueueFamilyId,
+) -> Result<Vec<PassId>, MipmapError> {
+    match passes.iter().find(|pass| pass.id == producer) {
+        None => return Err(MipmapError::ProducerNotFound { pass: producer }),
+        Some(pass) => {
+            if !pass.images.contains_key(&id) && !pass.image_ranges.contains_key(&id) {
+                return Err(MipmapError::NotProduced { id, pass: producer });
+            }
+        }
+    }
+
+    let next = passes.iter().map(|pass| pass.id.0 + 1).max().unwrap_or(0);
+    let level = |level| {
+        Some(SubresourceRange {
+            levels: level..level + 1,
+            ..range.clone()
+        })
+    };
+    let mut blits = Vec::new();
+    let mut last = producer;
+    for (index, dst) in (range.levels.start + 1..range.levels.end).enumerate() {
+        let pass = PassBuilder::new(PassId(next + index), family)
+            .capability(Capability::Graphics)
+            .depends_on(last)
+            .raw_image(
+                id,
+                StateUsage {
+                    state: State {
+                        access: ImageAccess::TRANSFER_READ,
+                        layout: ImageLayout::TransferSrcOptimal,
+                        stages: PipelineStage::TRANSFER,
+                    },
+                    usage: ImageUsage::TRANSFER_SRC,
+                    range: level(dst - 1),
+                    discard: false,
+                    ordering: OrderingHint::None,
+                },
+            )
+            .raw_image(
+                id,
+                StateUsage {
+                    state: State {
+                        access: ImageAccess::TRANSFER_WRITE,
+                        layout: ImageLayout::TransferDstOptimal,
+                        stages: PipelineStage::TRANSFER,
+                    },
+                    usage: ImageUsage::TRANSFER_DST,
+                    range: level(dst),
+                    discard: true,
+                    ordering: OrderingHint::None,
+                },
+            )
+            .build()?;
+        last = pass.id;
+        blits.push(pass);
+    }
+
+    if last != producer {
+        for pass in passes.iter_mut() {
+            let declares = pass.images.contains_key(&id) || pass.image_ranges.contains_key(&id);
+            if declares && pass.dependencies.contains(&producer) {
+                pass.dependencies.push(last);
+            }
+        }
+    }
+    let ids = blits.iter().map(|pass| pass.id).collect();
+    passes.extend(blits);
+    Ok(ids)
+}
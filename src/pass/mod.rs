@@ -0,0 +1,484 @@
+//!
+//! This module defines `Pass` type that contains information required to
+//! synchronize execution of passes.
+//!
+
+mod builder;
+mod capability;
+mod image_use;
+mod mipmap;
+mod region;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use collections::hash_map::Iter as HashMapIter;
+use collections::HashMap;
+use hal::image::{Extent, SubresourceRange};
+use hal::pso::PipelineStage;
+use hal::queue::QueueFamilyId;
+use resource::{Access, Buffer, Id, Image, MergeError, Range, Resource, State, Usage};
+
+pub use self::builder::{PassBuildError, PassBuilder};
+pub use self::capability::Capability;
+pub use self::image_use::ImageUse;
+pub use self::mipmap::{generate_mipmaps, MipmapError};
+pub use self::region::TransferRegion;
+
+/// Id of the pass.
+#[derive(Copy, Clone, Debug, PartialEq, PartialOrd, Eq, Ord, Hash)]
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize))]
+pub struct PassId(pub usize);
+
+/// User metadata of the pass carried through to the schedule.
+/// See `Submission::meta` and `Schedule::find_pass`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize))]
+pub struct PassMeta {
+    /// Name of the pass for diagnostics. Names are not serialized.
+    #[cfg_attr(feature = "serde-1", serde(skip))]
+    pub name: Option<&'static str>,
+
+    /// User tag of the pass.
+    pub tag: u64,
+}
+
+impl PassMeta {
+    /// Describe the pass for diagnostics, like `PassId(7) "ssao-blur" (tag 3)`.
+    /// Name and tag are omitted unless set.
+    pub fn label(&self, pass: PassId) -> String {
+        let mut label = format!("{:?}", pass);
+        if let Some(name) = self.name {
+            label += &format!(" {:?}", name);
+        }
+        if self.tag != 0 {
+            label += &format!(" (tag {})", self.tag);
+        }
+        label
+    }
+}
+
+/// Kind of operation performed by the pass.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize))]
+pub enum PassKind {
+    /// Pass records commands that access resources in declared states.
+    #[default]
+    Commands,
+
+    /// Pass binds memory to sparse resources with queue sparse binding operation.
+    /// Declared states are ignored and only ranges are used.
+    /// Binding is ordered against other uses of resources with semaphores only,
+    /// even on the same queue, and no barriers are generated for it.
+    /// Families of the pass must support sparse binding.
+    SparseBind,
+
+    /// Pass records commands that access resources in declared states within single render pass.
+    /// Scheduled like `Commands`, but pipeline barriers adjacent to the pass may be replaced
+    /// with subpass dependencies of the render pass.
+    /// See `walk::SubmissionInfo::as_external_dependency`.
+    RenderPass,
+}
+
+/// Hint for placement of the pass relative to other accesses of the declared resource.
+/// Scheduler prefers orders of passes within queues that honor hints,
+/// but never violates dependencies to do so.
+/// See `Submission::unhonored_buffer_hints` and `Submission::unhonored_image_hints`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize))]
+pub enum OrderingHint {
+    /// No preference.
+    #[default]
+    None,
+
+    /// Place the pass as early as possible.
+    /// Ideally right after the previous access of the resource on the queue.
+    AsEarlyAsPossible,
+
+    /// Place the pass as late as possible.
+    /// Ideally right before the next access of the resource on the queue.
+    /// Like tiny update of camera matrices latched late before the pass that reads them.
+    AsLateAsPossible,
+}
+
+/// State in which pass uses resource and usage flags.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize), serde(bound = ""))]
+pub struct StateUsage<R: Resource> {
+    /// State in which pass uses resource.
+    pub state: State<R>,
+
+    /// Usage flags.
+    /// Or `Usage::none()` to derive minimal usage from the state.
+    #[cfg_attr(feature = "serde-1", serde(with = "::ser::repr"))]
+    pub usage: R::Usage,
+
+    /// Sub-resource range accessed by the pass.
+    /// Or `None` if whole resource is accessed.
+    #[cfg_attr(feature = "serde-1", serde(with = "::ser::repr"))]
+    pub range: Option<R::Range>,
+
+    /// Pass overwrites the range without reading its previous content.
+    /// Barrier into the pass then discards the content instead of making it available.
+    /// Only execution dependency on earlier accesses is kept.
+    /// Has no effect unless the range covers whole resource (or the whole chain's range).
+    pub discard: bool,
+
+    /// Hint for placement of the pass relative to other accesses of the resource.
+    pub ordering: OrderingHint,
+}
+
+impl<R> StateUsage<R>
+where
+    R: Resource,
+{
+    /// Merge with another declaration of the same resource by the same pass.
+    /// Range of the result covers both ranges.
+    pub(crate) fn try_merge(&self, other: &Self) -> Result<Self, MergeError<R>> {
+        Ok(StateUsage {
+            state: self.state.try_merge(other.state)?,
+            usage: if self.usage == R::Usage::none() && other.usage == R::Usage::none() {
+                R::Usage::none()
+            } else {
+                // Keep usage derived from the state that had none declared.
+                explicit_usage(self) | explicit_usage(other)
+            },
+            range: match (self.range.as_ref(), other.range.as_ref()) {
+                (Some(left), Some(right)) => Some(left.union(right)),
+                _ => None,
+            },
+            discard: self.discard && other.discard,
+            ordering: match (self.ordering, other.ordering) {
+                (left, OrderingHint::None) => left,
+                (OrderingHint::None, right) => right,
+                (left, right) if left == right => left,
+                _ => OrderingHint::None,
+            },
+        })
+    }
+}
+
+fn explicit_usage<R: Resource>(state_usage: &StateUsage<R>) -> R::Usage {
+    if state_usage.usage == R::Usage::none() {
+        state_usage.state.usage()
+    } else {
+        state_usage.usage
+    }
+}
+
+/// Declarations of the image by the same pass that overlap but can't be merged.
+#[derive(Clone, Debug)]
+pub(crate) struct RangeConflict {
+    /// Ranges of both declarations.
+    /// Or `None` if either declaration covers whole image.
+    pub(crate) ranges: Option<(SubresourceRange, SubresourceRange)>,
+
+    /// States that can't be merged.
+    pub(crate) error: MergeError<Image>,
+}
+
+/// Merge overlapping declarations of the image.
+/// Declarations that don't overlap any other are kept separate.
+pub(crate) fn merge_overlapping<I>(declarations: I) -> Result<Vec<StateUsage<Image>>, RangeConflict>
+where
+    I: IntoIterator<Item = StateUsage<Image>>,
+{
+    let mut merged: Vec<StateUsage<Image>> = Vec::new();
+    for mut declared in declarations {
+        // Merged range may overlap declarations that the original didn't.
+        while let Some(index) = merged.iter().position(|other| {
+            match (other.range.as_ref(), declared.range.as_ref()) {
+                (Some(left), Some(right)) => left.overlaps(right),
+                _ => true,
+            }
+        }) {
+            let other = merged.remove(index);
+            declared = other.try_merge(&declared).map_err(|error| RangeConflict {
+                ranges: match (other.range, declared.range) {
+                    (Some(left), Some(right)) => Some((left, right)),
+                    _ => None,
+                },
+                error,
+            })?;
+        }
+        merged.push(declared);
+    }
+    Ok(merged)
+}
+
+/// Description of pass.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize))]
+pub struct Pass {
+    /// Id of the pass.
+    pub id: PassId,
+
+    /// Kind of operation performed by the pass.
+    pub kind: PassKind,
+
+    /// Family required to execute the pass.
+    /// Unless `families` has alternatives.
+    #[cfg_attr(feature = "serde-1", serde(with = "::ser::repr"))]
+    pub family: QueueFamilyId,
+
+    /// Other families that can execute the pass.
+    /// Scheduler places the pass on `family` or on one of those, whichever fits better.
+    /// Empty if pass is pinned to `family`.
+    #[cfg_attr(feature = "serde-1", serde(with = "::ser::repr"))]
+    pub families: Vec<QueueFamilyId>,
+
+    /// Family to prefer when `family` and `families` fit equally well.
+    #[cfg_attr(feature = "serde-1", serde(with = "::ser::repr"))]
+    pub preferred_family: Option<QueueFamilyId>,
+
+    /// Specific queue of `family` for the pass. Or `None` if any will do.
+    /// Pass with specific queue is never placed on `families`.
+    pub queue: Option<usize>,
+
+    /// Dependencies of the pass.
+    /// Those are indices of other passes in array.
+    pub dependencies: Vec<PassId>,
+
+    /// Buffer category ids and required state.
+    pub buffers: HashMap<Id<Buffer>, StateUsage<Buffer>>,
+
+    /// Image category ids and required state.
+    pub images: HashMap<Id<Image>, StateUsage<Image>>,
+
+    /// Images accessed in several disjoint ranges with different states.
+    /// Those are used along with declaration from `images` if there is one.
+    /// Image declared in several ranges by some pass gets a chain for each range,
+    /// so barriers into the pass transition each range to its own layout.
+    pub image_ranges: HashMap<Id<Image>, Vec<StateUsage<Image>>>,
+
+    /// Pass has effects not visible through declared resources.
+    /// Such pass is never culled by `cull::retain_roots`.
+    pub side_effects: bool,
+
+    /// Main commands of the pass may be skipped at record time.
+    /// Synchronization of conditional pass must still be recorded,
+    /// so resources are in expected states whether commands are skipped or not.
+    /// Sparse binding passes are never conditional.
+    /// See `sync::Guard::must_record_even_if_skipped`.
+    pub conditional: bool,
+
+    /// Relative cost of executing the pass. Zero is treated as one.
+    /// Scheduler balances costs of independent passes among queues.
+    pub cost: u32,
+
+    /// Capability the pass requires from families executing it.
+    /// Or `None` to derive it from stages of declared states.
+    /// Must support what declared stages require.
+    pub capability: Option<Capability>,
+
+    /// Regions of images accessed by transfer commands of the pass.
+    /// Pass is scheduled only to families which granularity fits all of them.
+    /// Images accessed only in whole levels need no regions.
+    pub transfer_regions: HashMap<Id<Image>, Vec<TransferRegion>>,
+
+    /// Kinds of image declarations made with `PassBuilder::image_use`, in declaration order.
+    /// Those are already merged into `images`, and kept only to warn
+    /// about uses that degrade layout of the image when merged.
+    /// See `collect::ChainWarning::GeneralLayout`.
+    pub image_uses: HashMap<Id<Image>, Vec<ImageUse>>,
+
+    /// Multisampled images resolved by render pass of the pass, by resolve targets.
+    /// Both images must be declared too, like `PassBuilder::resolve` does.
+    /// See `chain::AttachmentInfo::resolve_source`.
+    pub resolves: HashMap<Id<Image>, Id<Image>>,
+
+    /// Name and tag of the pass. Those don't affect scheduling.
+    pub meta: PassMeta,
+}
+
+impl Pass {
+    /// Pin the pass to the family.
+    /// Pass will be executed only on this family.
+    pub fn with_queue_family(mut self, family: QueueFamilyId) -> Self {
+        self.family = family;
+        self.families.clear();
+        self
+    }
+
+    /// Allow the pass to be executed on another family.
+    pub fn allow_family(mut self, family: QueueFamilyId) -> Self {
+        if family != self.family && !self.families.contains(&family) {
+            self.families.push(family);
+        }
+        self
+    }
+
+    /// Hint scheduler to place the pass on the family.
+    /// Hint affects only choice between families that fit equally well.
+    /// Family must be either `family` or one of `families` to take effect.
+    pub fn prefer_family(mut self, family: QueueFamilyId) -> Self {
+        self.preferred_family = Some(family);
+        self
+    }
+
+    /// Hint scheduler with relative cost of executing the pass.
+    /// Independent passes are distributed among queues so that the costliest queue is cheaper.
+    pub fn cost(mut self, cost: u32) -> Self {
+        self.cost = cost;
+        self
+    }
+
+    /// Require capability from families executing the pass.
+    /// Useful when commands use stages not visible in declared states.
+    pub fn capability(mut self, capability: Capability) -> Self {
+        self.capability = Some(capability);
+        self
+    }
+
+    /// Name the pass for diagnostics.
+    pub fn name(mut self, name: &'static str) -> Self {
+        self.meta.name = Some(name);
+        self
+    }
+
+    /// Tag the pass with user value.
+    pub fn tag(mut self, tag: u64) -> Self {
+        self.meta.tag = tag;
+        self
+    }
+
+    /// Get capability required by stages of states declared by the pass
+    /// together with the first stage that requires it.
+    /// Or `None` if declared stages require no capability.
+    /// States declared by sparse binding passes are ignored.
+    pub fn declared_capability(&self) -> Option<(PipelineStage, Capability)> {
+        if self.kind == PassKind::SparseBind {
+            return None;
+        }
+        let stages = self
+            .buffers
+            .values()
+            .map(|state_usage| state_usage.state.stages)
+            .chain(self.images.values().map(|state_usage| state_usage.state.stages))
+            .chain(
+                self.image_ranges
+                    .values()
+                    .flat_map(|ranges| ranges.iter().map(|state_usage| state_usage.state.stages)),
+            )
+            .fold(PipelineStage::empty(), |acc, stages| acc | stages);
+        Capability::required_by(stages)
+    }
+
+    /// Get capability required from families executing the pass.
+    /// Explicitly required capability, or the one required by declared stages.
+    /// `None` if neither requires any.
+    pub fn required_capability(&self) -> Option<Capability> {
+        self.capability
+            .or_else(|| self.declared_capability().map(|(_, capability)| capability))
+    }
+
+    /// Declare region of the image accessed by transfer commands of the pass.
+    pub fn transfer_region(mut self, id: Id<Image>, region: TransferRegion) -> Self {
+        self.transfer_regions
+            .entry(id)
+            .or_default()
+            .push(region);
+        self
+    }
+
+    /// Find image which transfer regions queues with the granularity can't transfer.
+    /// Or `None` if all regions fit. Images are checked in order of ids.
+    /// Regions declared by sparse binding passes are ignored.
+    pub fn misaligned_transfer(&self, granularity: Extent) -> Option<Id<Image>> {
+        if self.kind == PassKind::SparseBind {
+            return None;
+        }
+        let mut misaligned: Vec<_> = self
+            .transfer_regions
+            .iter()
+            .filter(|&(_, regions)| regions.iter().any(|region| !region.fits(granularity)))
+            .map(|(&id, _)| id)
+            .collect();
+        misaligned.sort();
+        misaligned.first().cloned()
+    }
+
+    /// Find buffers declared in states with access types none of the stages performs.
+    /// Buffers are sorted by ids. See `State::unsupported_access`.
+    pub fn unsupported_buffer_states(&self) -> Vec<(Id<Buffer>, State<Buffer>)> {
+        let mut unsupported: Vec<_> = self
+            .buffers
+            .iter()
+            .filter(|&(_, state_usage)| state_usage.state.unsupported_access() != Access::none())
+            .map(|(&id, state_usage)| (id, state_usage.state))
+            .collect();
+        unsupported.sort_by_key(|&(id, _)| id);
+        unsupported
+    }
+
+    /// Find images declared in states with access types none of the stages performs.
+    /// Images are sorted by ids. See `State::unsupported_access`.
+    pub fn unsupported_image_states(&self) -> Vec<(Id<Image>, State<Image>)> {
+        let mut unsupported: Vec<_> = self
+            .images
+            .iter()
+            .map(|(&id, state_usage)| (id, state_usage))
+            .chain(self.image_ranges.iter().flat_map(|(&id, ranges)| {
+                ranges.iter().map(move |state_usage| (id, state_usage))
+            }))
+            .filter(|&(_, state_usage)| state_usage.state.unsupported_access() != Access::none())
+            .map(|(id, state_usage)| (id, state_usage.state))
+            .collect();
+        unsupported.sort_by_key(|&(id, _)| id);
+        unsupported
+    }
+
+    /// Get kind of operation performed by this pass.
+    pub fn kind(&self) -> PassKind {
+        self.kind
+    }
+
+    /// Get family required to execute this pass.
+    pub fn family(&self) -> QueueFamilyId {
+        self.family
+    }
+
+    /// Get other families that can execute this pass.
+    pub fn families(&self) -> &[QueueFamilyId] {
+        &self.families
+    }
+
+    /// Get family this pass prefers. Or `None`.
+    pub fn preferred_family(&self) -> Option<QueueFamilyId> {
+        self.preferred_family
+    }
+
+    /// Get queue to which this pass assigned. Or `None`.
+    pub fn queue(&self) -> Option<usize> {
+        self.queue
+    }
+
+    /// Get indices of passes this pass depends on.
+    pub fn dependencies(&self) -> &[PassId] {
+        &self.dependencies
+    }
+
+    /// Get iterator to buffer states this pass accesses.
+    pub fn buffers(&self) -> HashMapIter<Id<Buffer>, StateUsage<Buffer>> {
+        self.buffers.iter()
+    }
+
+    /// Get iterator to image states this pass accesses.
+    pub fn images(&self) -> HashMapIter<Id<Image>, StateUsage<Image>> {
+        self.images.iter()
+    }
+
+    /// Get iterator to images this pass accesses in several disjoint ranges.
+    pub fn image_ranges(&self) -> HashMapIter<'_, Id<Image>, Vec<StateUsage<Image>>> {
+        self.image_ranges.iter()
+    }
+
+    /// Check if pass has effects not visible through declared resources.
+    pub fn has_side_effects(&self) -> bool {
+        self.side_effects
+    }
+
+    /// Check if main commands of the pass may be skipped at record time.
+    pub fn is_conditional(&self) -> bool {
+        self.conditional && self.kind != PassKind::SparseBind
+    }
+}
@@ -0,0 +1,54 @@
+use hal::image::{Extent, Offset};
+
+/// Region of the image level accessed by transfer commands of the pass.
+/// Queues of some families can copy only regions aligned to their granularity.
+/// See `collect::FamilyInfo::image_transfer_granularity`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize))]
+pub struct TransferRegion {
+    /// Offset of the region in texels.
+    #[cfg_attr(feature = "serde-1", serde(with = "::ser::repr"))]
+    pub offset: Offset,
+
+    /// Size of the region in texels.
+    #[cfg_attr(feature = "serde-1", serde(with = "::ser::repr"))]
+    pub extent: Extent,
+
+    /// Size of the whole level of the image.
+    /// Region that ends at the edge of the level may have unaligned size.
+    #[cfg_attr(feature = "serde-1", serde(with = "::ser::repr"))]
+    pub level_extent: Extent,
+}
+
+impl TransferRegion {
+    /// Create region covering whole level of the given size.
+    pub fn whole(level_extent: Extent) -> Self {
+        TransferRegion {
+            offset: Offset { x: 0, y: 0, z: 0 },
+            extent: level_extent,
+            level_extent,
+        }
+    }
+
+    /// Check if queues with the granularity can transfer the region.
+    /// Offset must be a multiple of the granularity and so must be the size,
+    /// unless the region ends at the edge of the level.
+    /// Zero granularity allows to transfer only whole levels.
+    pub fn fits(&self, granularity: Extent) -> bool {
+        fits(self.offset.x, self.extent.width, self.level_extent.width, granularity.width)
+            && fits(self.offset.y, self.extent.height, self.level_extent.height, granularity.height)
+            && fits(self.offset.z, self.extent.depth, self.level_extent.depth, granularity.depth)
+    }
+}
+
+fn fits(offset: i32, size: u32, level: u32, granularity: u32) -> bool {
+    if offset < 0 {
+        return false;
+    }
+    let offset = offset as u32;
+    if granularity == 0 {
+        return offset == 0 && size == level;
+    }
+    offset.is_multiple_of(granularity)
+        && (size.is_multiple_of(granularity) || offset + size == level)
+}
@@ -1,10 +1,21 @@
+use core::fmt::Debug;
+use core::hash::Hash;
+use core::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign};
 use hal::pso::PipelineStage;
-use std::fmt::Debug;
-use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign};
+
+use resource::MaybeRepr;
 
 /// Access type combination
 pub trait Access:
-    Debug + Copy + Eq + BitAnd<Output = Self> + BitAndAssign + BitOr<Output = Self> + BitOrAssign
+    Debug
+    + Copy
+    + Eq
+    + Hash
+    + BitAnd<Output = Self>
+    + BitAndAssign
+    + BitOr<Output = Self>
+    + BitOrAssign
+    + MaybeRepr
 {
     /// Create empty combinations of access types.
     fn none() -> Self;
@@ -12,6 +23,12 @@ pub trait Access:
     /// Create access instance that combines all possible access types.
     fn all() -> Self;
 
+    /// Create access instance with host read access type.
+    fn host_read() -> Self;
+
+    /// Create access instance with host write access type.
+    fn host_write() -> Self;
+
     /// Check if the access combination contains at least one read access type.
     fn is_read(&self) -> bool;
 
@@ -25,4 +42,18 @@ pub trait Access:
     ///
     /// If this access combination has more than one access types this function will panic.
     fn supported_pipeline_stages(&self) -> PipelineStage;
+
+    /// Get access types of the combination that none of the stages performs.
+    /// Memory accesses are performed at any stage.
+    fn unsupported_by(&self, stages: PipelineStage) -> Self;
+}
+
+/// Check if some of the stages perform the single access type.
+pub(super) fn supported_by<A: Access>(access: A, stages: PipelineStage) -> bool {
+    let supported = access.supported_pipeline_stages();
+    if supported.is_empty() {
+        !stages.is_empty()
+    } else {
+        stages.intersects(supported)
+    }
 }
@@ -1,7 +1,10 @@
+use core::cmp::{max, min};
+use core::ops;
 use hal::buffer::{Access as BufferAccess, Usage as BufferUsage};
 use hal::pso::PipelineStage;
 
-use resource::{Access, Layout, Usage};
+use resource::access::supported_by;
+use resource::{Access, Layout, Range, Usage};
 
 impl Access for BufferAccess {
     fn none() -> Self {
@@ -12,6 +15,14 @@ impl Access for BufferAccess {
         Self::all()
     }
 
+    fn host_read() -> Self {
+        Self::HOST_READ
+    }
+
+    fn host_write() -> Self {
+        Self::HOST_WRITE
+    }
+
     fn is_write(&self) -> bool {
         self.contains(Self::TRANSFER_WRITE)
             || self.contains(Self::SHADER_WRITE)
@@ -38,17 +49,31 @@ impl Access for BufferAccess {
             Self::INDEX_BUFFER_READ | Self::VERTEX_BUFFER_READ => PS::VERTEX_INPUT,
             Self::INDIRECT_COMMAND_READ => PS::DRAW_INDIRECT,
             Self::CONSTANT_BUFFER_READ | Self::SHADER_READ | Self::SHADER_WRITE => {
-                PS::VERTEX_SHADER | PS::GEOMETRY_SHADER | PS::FRAGMENT_SHADER | PS::COMPUTE_SHADER
+                PS::VERTEX_SHADER
+                    | PS::HULL_SHADER
+                    | PS::DOMAIN_SHADER
+                    | PS::GEOMETRY_SHADER
+                    | PS::FRAGMENT_SHADER
+                    | PS::COMPUTE_SHADER
             }
             Self::HOST_READ | Self::HOST_WRITE => PS::HOST,
             Self::MEMORY_READ | Self::MEMORY_WRITE => PS::empty(),
             _ => panic!("Only one bit must be set"),
         }
     }
+
+    fn unsupported_by(&self, stages: PipelineStage) -> Self {
+        (0..32)
+            .map(|bit| Self::from_bits_truncate(1 << bit))
+            .filter(|&access| {
+                !access.is_empty() && self.contains(access) && !supported_by(access, stages)
+            })
+            .fold(Self::empty(), |acc, access| acc | access)
+    }
 }
 
 /// Buffers can be placed in memory only linearly
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct BufferLayout;
 
 impl Layout for BufferLayout {
@@ -59,6 +84,111 @@ impl Layout for BufferLayout {
     fn discard_content() -> Self {
         BufferLayout
     }
+
+    fn present() -> Option<Self> {
+        None
+    }
+}
+
+/// Derive minimal usage from buffer access.
+/// Shader accesses are assumed to be storage buffer accesses.
+pub(super) fn derive_usage(access: BufferAccess) -> BufferUsage {
+    let mut usage = BufferUsage::empty();
+    if access.contains(BufferAccess::TRANSFER_READ) {
+        usage |= BufferUsage::TRANSFER_SRC;
+    }
+    if access.contains(BufferAccess::TRANSFER_WRITE) {
+        usage |= BufferUsage::TRANSFER_DST;
+    }
+    if access.contains(BufferAccess::CONSTANT_BUFFER_READ) {
+        usage |= BufferUsage::UNIFORM;
+    }
+    if access.intersects(BufferAccess::SHADER_READ | BufferAccess::SHADER_WRITE) {
+        usage |= BufferUsage::STORAGE;
+    }
+    if access.contains(BufferAccess::INDEX_BUFFER_READ) {
+        usage |= BufferUsage::INDEX;
+    }
+    if access.contains(BufferAccess::VERTEX_BUFFER_READ) {
+        usage |= BufferUsage::VERTEX;
+    }
+    if access.contains(BufferAccess::INDIRECT_COMMAND_READ) {
+        usage |= BufferUsage::INDIRECT;
+    }
+    usage
+}
+
+/// Alignments of buffer ranges required by the device for each usage.
+/// Usually taken from device limits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize))]
+pub struct BufferAlignments {
+    /// Alignment of offset of range bound as uniform buffer.
+    pub uniform: u64,
+
+    /// Alignment of offset of range bound as storage buffer.
+    pub storage: u64,
+
+    /// Alignment of offset of range viewed as texel buffer.
+    pub texel: u64,
+
+    /// Alignment of offset and size of range accessed by transfer commands.
+    pub transfer: u64,
+}
+
+impl Default for BufferAlignments {
+    fn default() -> Self {
+        BufferAlignments {
+            uniform: 1,
+            storage: 1,
+            texel: 1,
+            transfer: 1,
+        }
+    }
+}
+
+impl BufferAlignments {
+    /// Get alignment of offset required for the usage.
+    /// Range used in several ways must satisfy alignment of each usage.
+    pub fn offset(&self, usage: BufferUsage) -> u64 {
+        let mut alignment = 1;
+        if usage.contains(BufferUsage::UNIFORM) {
+            alignment = max(alignment, self.uniform);
+        }
+        if usage.contains(BufferUsage::STORAGE) {
+            alignment = max(alignment, self.storage);
+        }
+        if usage.intersects(BufferUsage::UNIFORM_TEXEL | BufferUsage::STORAGE_TEXEL) {
+            alignment = max(alignment, self.texel);
+        }
+        if usage.intersects(BufferUsage::TRANSFER_SRC | BufferUsage::TRANSFER_DST) {
+            alignment = max(alignment, self.transfer);
+        }
+        alignment
+    }
+
+    /// Get alignment of size required for the usage.
+    pub fn size(&self, usage: BufferUsage) -> u64 {
+        if usage.intersects(BufferUsage::TRANSFER_SRC | BufferUsage::TRANSFER_DST) {
+            self.transfer
+        } else {
+            1
+        }
+    }
+
+    /// Check that the range is aligned for the usage.
+    /// Returns violated alignment if it isn't.
+    pub fn check(&self, range: &ops::Range<u64>, usage: BufferUsage) -> Result<(), u64> {
+        let offset = self.offset(usage);
+        if !range.start.is_multiple_of(offset) {
+            return Err(offset);
+        }
+        let size = self.size(usage);
+        if !(range.end - range.start).is_multiple_of(size) {
+            return Err(size);
+        }
+        Ok(())
+    }
 }
 
 impl Usage for BufferUsage {
@@ -69,3 +199,21 @@ impl Usage for BufferUsage {
         BufferUsage::all()
     }
 }
+
+impl Range for ops::Range<u64> {
+    fn overlaps(&self, other: &Self) -> bool {
+        self.start < other.end && other.start < self.end
+    }
+
+    fn union(&self, other: &Self) -> Self {
+        min(self.start, other.start)..max(self.end, other.end)
+    }
+
+    fn intersection(&self, other: &Self) -> Option<Self> {
+        if self.overlaps(other) {
+            Some(max(self.start, other.start)..min(self.end, other.end))
+        } else {
+            None
+        }
+    }
+}
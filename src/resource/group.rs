@@ -0,0 +1,41 @@
+use alloc::vec::Vec;
+use resource::{Id, Resource};
+
+/// Set of resources that are always accessed together in identical states.
+/// Like images of g-buffer that are written and read by the same passes.
+///
+/// Each pass must declare either all members of the group in the same state or none of them.
+/// `collect` verifies that chains of members are identical,
+/// so barriers of members are always recorded together.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize), serde(bound = ""))]
+pub struct ResourceGroup<R: Resource> {
+    ids: Vec<Id<R>>,
+}
+
+impl<R> ResourceGroup<R>
+where
+    R: Resource,
+{
+    /// Create group of resources.
+    /// Duplicate ids are ignored.
+    pub fn new<I>(ids: I) -> Self
+    where
+        I: IntoIterator<Item = Id<R>>,
+    {
+        let mut ids: Vec<_> = ids.into_iter().collect();
+        ids.sort();
+        ids.dedup();
+        ResourceGroup { ids }
+    }
+
+    /// Get ids of members. Sorted.
+    pub fn ids(&self) -> &[Id<R>] {
+        &self.ids
+    }
+
+    /// Check if resource is a member of the group.
+    pub fn contains(&self, id: Id<R>) -> bool {
+        self.ids.binary_search(&id).is_ok()
+    }
+}
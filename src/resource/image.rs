@@ -1,7 +1,11 @@
-use hal::image::{Access as ImageAccess, Layout as ImageLayout, Usage as ImageUsage};
+use core::cmp::{max, min};
+use hal::image::{
+    Access as ImageAccess, Layout as ImageLayout, SubresourceRange, Usage as ImageUsage,
+};
 use hal::pso::PipelineStage;
 
-use resource::{Access, Layout, Usage};
+use resource::access::supported_by;
+use resource::{Access, Layout, Range, Usage};
 
 impl Access for ImageAccess {
     fn none() -> Self {
@@ -10,6 +14,12 @@ impl Access for ImageAccess {
     fn all() -> Self {
         Self::all()
     }
+    fn host_read() -> Self {
+        Self::HOST_READ
+    }
+    fn host_write() -> Self {
+        Self::HOST_WRITE
+    }
     fn is_write(&self) -> bool {
         self.contains(Self::COLOR_ATTACHMENT_WRITE)
             || self.contains(Self::DEPTH_STENCIL_ATTACHMENT_WRITE)
@@ -38,7 +48,12 @@ impl Access for ImageAccess {
             }
             Self::TRANSFER_READ | Self::TRANSFER_WRITE => PS::TRANSFER,
             Self::SHADER_READ | Self::SHADER_WRITE => {
-                PS::VERTEX_SHADER | PS::GEOMETRY_SHADER | PS::FRAGMENT_SHADER | PS::COMPUTE_SHADER
+                PS::VERTEX_SHADER
+                    | PS::HULL_SHADER
+                    | PS::DOMAIN_SHADER
+                    | PS::GEOMETRY_SHADER
+                    | PS::FRAGMENT_SHADER
+                    | PS::COMPUTE_SHADER
             }
             Self::DEPTH_STENCIL_ATTACHMENT_READ | Self::DEPTH_STENCIL_ATTACHMENT_WRITE => {
                 PS::EARLY_FRAGMENT_TESTS | PS::LATE_FRAGMENT_TESTS
@@ -49,6 +64,15 @@ impl Access for ImageAccess {
             _ => panic!("Only one bit must be set"),
         }
     }
+
+    fn unsupported_by(&self, stages: PipelineStage) -> Self {
+        (0..32)
+            .map(|bit| Self::from_bits_truncate(1 << bit))
+            .filter(|&access| {
+                !access.is_empty() && self.contains(access) && !supported_by(access, stages)
+            })
+            .fold(Self::empty(), |acc, access| acc | access)
+    }
 }
 
 impl Layout for ImageLayout {
@@ -64,9 +88,62 @@ impl Layout for ImageLayout {
         }
     }
 
+    fn merge_read_only(self, other: ImageLayout) -> Option<ImageLayout> {
+        match (self, other) {
+            (x, y) if x == y => Some(x),
+            (ImageLayout::Present, _) | (_, ImageLayout::Present) => None,
+            (ImageLayout::DepthStencilReadOnlyOptimal, _)
+            | (_, ImageLayout::DepthStencilReadOnlyOptimal)
+            | (ImageLayout::DepthStencilAttachmentOptimal, _)
+            | (_, ImageLayout::DepthStencilAttachmentOptimal) => {
+                Some(ImageLayout::DepthStencilReadOnlyOptimal)
+            }
+            (_, _) => Some(ImageLayout::ShaderReadOnlyOptimal),
+        }
+    }
+
     fn discard_content() -> Self {
         ImageLayout::Undefined
     }
+
+    fn present() -> Option<Self> {
+        Some(ImageLayout::Present)
+    }
+}
+
+/// Derive minimal usage from image access and layout.
+/// Shader reads in `General` layout are assumed to be storage image accesses.
+/// Other shader reads are assumed to be sampled image accesses.
+pub(super) fn derive_usage(access: ImageAccess, layout: ImageLayout) -> ImageUsage {
+    let mut usage = ImageUsage::empty();
+    if access.contains(ImageAccess::TRANSFER_READ) {
+        usage |= ImageUsage::TRANSFER_SRC;
+    }
+    if access.contains(ImageAccess::TRANSFER_WRITE) {
+        usage |= ImageUsage::TRANSFER_DST;
+    }
+    if access.contains(ImageAccess::SHADER_WRITE) {
+        usage |= ImageUsage::STORAGE;
+    }
+    if access.contains(ImageAccess::SHADER_READ) {
+        usage |= if layout == ImageLayout::General {
+            ImageUsage::STORAGE
+        } else {
+            ImageUsage::SAMPLED
+        };
+    }
+    if access.intersects(ImageAccess::COLOR_ATTACHMENT_READ | ImageAccess::COLOR_ATTACHMENT_WRITE) {
+        usage |= ImageUsage::COLOR_ATTACHMENT;
+    }
+    if access.intersects(
+        ImageAccess::DEPTH_STENCIL_ATTACHMENT_READ | ImageAccess::DEPTH_STENCIL_ATTACHMENT_WRITE,
+    ) {
+        usage |= ImageUsage::DEPTH_STENCIL_ATTACHMENT;
+    }
+    if access.contains(ImageAccess::INPUT_ATTACHMENT_READ) {
+        usage |= ImageUsage::INPUT_ATTACHMENT;
+    }
+    usage
 }
 
 impl Usage for ImageUsage {
@@ -77,3 +154,37 @@ impl Usage for ImageUsage {
         ImageUsage::all()
     }
 }
+
+impl Range for SubresourceRange {
+    fn overlaps(&self, other: &Self) -> bool {
+        self.aspects.intersects(other.aspects)
+            && self.levels.start < other.levels.end
+            && other.levels.start < self.levels.end
+            && self.layers.start < other.layers.end
+            && other.layers.start < self.layers.end
+    }
+
+    fn union(&self, other: &Self) -> Self {
+        SubresourceRange {
+            aspects: self.aspects | other.aspects,
+            levels: min(self.levels.start, other.levels.start)
+                ..max(self.levels.end, other.levels.end),
+            layers: min(self.layers.start, other.layers.start)
+                ..max(self.layers.end, other.layers.end),
+        }
+    }
+
+    fn intersection(&self, other: &Self) -> Option<Self> {
+        if self.overlaps(other) {
+            Some(SubresourceRange {
+                aspects: self.aspects & other.aspects,
+                levels: max(self.levels.start, other.levels.start)
+                    ..min(self.levels.end, other.levels.end),
+                layers: max(self.layers.start, other.layers.start)
+                    ..min(self.layers.end, other.layers.end),
+            })
+        } else {
+            None
+        }
+    }
+}
@@ -1,13 +1,70 @@
-use std::fmt::Debug;
+use core::fmt::Debug;
+use core::hash::Hash;
+
+use resource::{Access, MaybeRepr, MergeError, Resource, State};
 
 /// Layout specify how resource's content is placed in gpu memory
-pub trait Layout: Debug + Copy + Eq + Sized {
+pub trait Layout: Debug + Copy + Eq + Hash + Sized + MaybeRepr {
     /// Merge this layout and another.
     /// Returns `None` if layouts can't be merged.
     fn merge(self, other: Self) -> Option<Self>;
 
+    /// Merge layouts of read-only accesses into read-only layout if there is one.
+    /// Returns `None` if layouts can't be merged.
+    fn merge_read_only(self, other: Self) -> Option<Self> {
+        self.merge(other)
+    }
+
     /// Get relaxed source layout for transition.
     /// Content may be discarded during transition from the relaxed layout.
     /// Not all layout types have dedicated value for this.
     fn discard_content() -> Self;
+
+    /// Get layout for presentation.
+    /// Or `None` if resource of this layout type can't be presented.
+    fn present() -> Option<Self>;
+}
+
+/// Policy of reconciling layouts of different states of the resource accessed by the same link.
+/// See `Resources::layout_policy`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize))]
+pub enum LayoutPolicy {
+    /// Merge layouts with `Layout::merge`.
+    /// Different optimal layouts are merged into `General` one.
+    #[default]
+    PreferGeneral,
+
+    /// Merge layouts of read-only states with `Layout::merge_read_only`.
+    /// Like different layouts of image reads are merged into
+    /// `ShaderReadOnlyOptimal` or `DepthStencilReadOnlyOptimal` one.
+    /// The device must support all accesses of the link in that layout.
+    PreferReadOnly,
+
+    /// Never merge different layouts.
+    /// Collection fails instead, so passes must be split or declarations changed.
+    Strict,
+}
+
+impl LayoutPolicy {
+    /// Get layout in which both states can be accessed.
+    ///
+    /// # Errors
+    ///
+    /// Returns error containing both states if the policy doesn't allow to merge their layouts.
+    pub fn merge<R>(&self, left: State<R>, right: State<R>) -> Result<R::Layout, MergeError<R>>
+    where
+        R: Resource,
+    {
+        let layout = match *self {
+            LayoutPolicy::PreferGeneral => left.layout.merge(right.layout),
+            LayoutPolicy::PreferReadOnly if !left.access.is_write() && !right.access.is_write() => {
+                left.layout.merge_read_only(right.layout)
+            }
+            LayoutPolicy::PreferReadOnly => left.layout.merge(right.layout),
+            LayoutPolicy::Strict if left.layout == right.layout => Some(left.layout),
+            LayoutPolicy::Strict => None,
+        };
+        layout.ok_or(MergeError { left, right })
+    }
 }
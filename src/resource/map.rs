@@ -0,0 +1,434 @@
+//!
+//! This module provides `IdMap` and its iterators.
+//!
+
+use alloc::vec::{self, Vec};
+use collections::FnvHashMap;
+use collections::hash_map;
+use core::fmt::{self, Debug};
+use core::iter::FromIterator;
+use core::ops::Index;
+use core::slice;
+
+use super::Id;
+
+/// Minimal number of slots dense storage may grow to regardless of the number of entries.
+const MIN_DENSE_SLOTS: usize = 64;
+
+/// Map keyed by resource ids.
+///
+/// Ids allocated by `IdAllocator` are small consecutive integers, so entries are stored
+/// in a vector indexed by id instead of being hashed. Ids that are too far beyond
+/// the number of entries fall back to a hash map to keep memory bounded when ids are sparse.
+/// Ids stored densely are iterated in increasing order, followed by the sparse ones
+/// in unspecified order.
+pub struct IdMap<R, V> {
+    dense: Vec<Option<(Id<R>, V)>>,
+    sparse: FnvHashMap<usize, (Id<R>, V)>,
+    len: usize,
+}
+
+impl<R, V> IdMap<R, V> {
+    /// Create empty map.
+    pub fn new() -> Self {
+        IdMap::with_capacity(0)
+    }
+
+    /// Create empty map that can hold ids below `capacity` without reallocation.
+    pub fn with_capacity(capacity: usize) -> Self {
+        IdMap {
+            dense: Vec::with_capacity(capacity),
+            sparse: FnvHashMap::default(),
+            len: 0,
+        }
+    }
+
+    /// Get number of entries.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Check if the map has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Get value of the id.
+    pub fn get(&self, id: &Id<R>) -> Option<&V> {
+        let index = id.index();
+        let slot = if index < self.dense.len() {
+            self.dense[index].as_ref()
+        } else {
+            self.sparse.get(&index)
+        };
+        slot.map(|entry| &entry.1)
+    }
+
+    /// Get mutable value of the id.
+    pub fn get_mut(&mut self, id: &Id<R>) -> Option<&mut V> {
+        let index = id.index();
+        let slot = if index < self.dense.len() {
+            self.dense[index].as_mut()
+        } else {
+            self.sparse.get_mut(&index)
+        };
+        slot.map(|entry| &mut entry.1)
+    }
+
+    /// Check if the map has value for the id.
+    pub fn contains_key(&self, id: &Id<R>) -> bool {
+        self.get(id).is_some()
+    }
+
+    /// Insert value for the id.
+    /// Returns previous value if there was one.
+    pub fn insert(&mut self, id: Id<R>, value: V) -> Option<V> {
+        let index = id.index();
+        if index >= self.dense.len() && index < self.dense_limit() {
+            self.grow(index + 1);
+        }
+        let old = if index < self.dense.len() {
+            self.dense[index].replace((id, value))
+        } else {
+            self.sparse.insert(index, (id, value))
+        };
+        if old.is_none() {
+            self.len += 1;
+        }
+        old.map(|(_, value)| value)
+    }
+
+    /// Remove value of the id.
+    /// Returns removed value if there was one.
+    pub fn remove(&mut self, id: &Id<R>) -> Option<V> {
+        let index = id.index();
+        let old = if index < self.dense.len() {
+            self.dense[index].take()
+        } else {
+            self.sparse.remove(&index)
+        };
+        if old.is_some() {
+            self.len -= 1;
+        }
+        old.map(|(_, value)| value)
+    }
+
+    /// Remove all entries. Keeps allocated storage.
+    pub fn clear(&mut self) {
+        self.dense.clear();
+        self.sparse.clear();
+        self.len = 0;
+    }
+
+    /// Iterate over ids and values.
+    pub fn iter(&self) -> Iter<'_, R, V> {
+        Iter {
+            dense: self.dense.iter(),
+            sparse: self.sparse.values(),
+            len: self.len,
+        }
+    }
+
+    /// Iterate over ids and mutable values.
+    pub fn iter_mut(&mut self) -> IterMut<'_, R, V> {
+        IterMut {
+            dense: self.dense.iter_mut(),
+            sparse: self.sparse.values_mut(),
+            len: self.len,
+        }
+    }
+
+    /// Iterate over ids.
+    pub fn keys(&self) -> Keys<'_, R, V> {
+        Keys { iter: self.iter() }
+    }
+
+    /// Iterate over values.
+    pub fn values(&self) -> Values<'_, R, V> {
+        Values { iter: self.iter() }
+    }
+
+    /// Iterate over mutable values.
+    pub fn values_mut(&mut self) -> ValuesMut<'_, R, V> {
+        ValuesMut {
+            iter: self.iter_mut(),
+        }
+    }
+
+    /// Get number of slots dense storage may grow to.
+    fn dense_limit(&self) -> usize {
+        let limit = (self.len + 1).saturating_mul(4);
+        if limit < MIN_DENSE_SLOTS {
+            MIN_DENSE_SLOTS
+        } else {
+            limit
+        }
+    }
+
+    /// Grow dense storage and move sparse entries that fit into it.
+    fn grow(&mut self, slots: usize) {
+        while self.dense.len() < slots {
+            self.dense.push(None);
+        }
+        if self.sparse.is_empty() {
+            return;
+        }
+        let moved: Vec<_> = self
+            .sparse
+            .keys()
+            .cloned()
+            .filter(|&index| index < slots)
+            .collect();
+        for index in moved {
+            let entry = self.sparse.remove(&index);
+            self.dense[index] = entry;
+        }
+    }
+}
+
+impl<R, V> Default for IdMap<R, V> {
+    fn default() -> Self {
+        IdMap::new()
+    }
+}
+
+impl<R, V> Clone for IdMap<R, V>
+where
+    R: Clone,
+    V: Clone,
+{
+    fn clone(&self) -> Self {
+        IdMap {
+            dense: self.dense.clone(),
+            sparse: self.sparse.clone(),
+            len: self.len,
+        }
+    }
+}
+
+impl<R, V> Debug for IdMap<R, V>
+where
+    R: Debug,
+    V: Debug,
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<R, V> PartialEq for IdMap<R, V>
+where
+    V: PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.iter().all(|(id, value)| other.get(id) == Some(value))
+    }
+}
+
+impl<R, V> Eq for IdMap<R, V> where V: Eq {}
+
+impl<R, V> Index<&Id<R>> for IdMap<R, V> {
+    type Output = V;
+
+    fn index(&self, id: &Id<R>) -> &V {
+        self.get(id).expect("No entry found for id")
+    }
+}
+
+impl<R, V> FromIterator<(Id<R>, V)> for IdMap<R, V> {
+    fn from_iter<I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = (Id<R>, V)>,
+    {
+        let mut map = IdMap::new();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<R, V> Extend<(Id<R>, V)> for IdMap<R, V> {
+    fn extend<I>(&mut self, iter: I)
+    where
+        I: IntoIterator<Item = (Id<R>, V)>,
+    {
+        for (id, value) in iter {
+            self.insert(id, value);
+        }
+    }
+}
+
+impl<R, V> IntoIterator for IdMap<R, V> {
+    type Item = (Id<R>, V);
+    type IntoIter = IntoIter<R, V>;
+
+    fn into_iter(self) -> IntoIter<R, V> {
+        IntoIter {
+            dense: self.dense.into_iter(),
+            sparse: self.sparse.into_iter(),
+            len: self.len,
+        }
+    }
+}
+
+impl<'a, R, V> IntoIterator for &'a IdMap<R, V> {
+    type Item = (&'a Id<R>, &'a V);
+    type IntoIter = Iter<'a, R, V>;
+
+    fn into_iter(self) -> Iter<'a, R, V> {
+        self.iter()
+    }
+}
+
+impl<'a, R, V> IntoIterator for &'a mut IdMap<R, V> {
+    type Item = (&'a Id<R>, &'a mut V);
+    type IntoIter = IterMut<'a, R, V>;
+
+    fn into_iter(self) -> IterMut<'a, R, V> {
+        self.iter_mut()
+    }
+}
+
+/// Iterator over ids and values of `IdMap`.
+pub struct Iter<'a, R: 'a, V: 'a> {
+    dense: slice::Iter<'a, Option<(Id<R>, V)>>,
+    sparse: hash_map::Values<'a, usize, (Id<R>, V)>,
+    len: usize,
+}
+
+impl<'a, R, V> Iterator for Iter<'a, R, V> {
+    type Item = (&'a Id<R>, &'a V);
+
+    fn next(&mut self) -> Option<(&'a Id<R>, &'a V)> {
+        let entry = match self.dense.by_ref().filter_map(Option::as_ref).next() {
+            Some(entry) => Some(entry),
+            None => self.sparse.next(),
+        };
+        entry.map(|entry| {
+            self.len -= 1;
+            (&entry.0, &entry.1)
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, R, V> ExactSizeIterator for Iter<'a, R, V> {}
+
+/// Iterator over ids and mutable values of `IdMap`.
+pub struct IterMut<'a, R: 'a, V: 'a> {
+    dense: slice::IterMut<'a, Option<(Id<R>, V)>>,
+    sparse: hash_map::ValuesMut<'a, usize, (Id<R>, V)>,
+    len: usize,
+}
+
+impl<'a, R, V> Iterator for IterMut<'a, R, V> {
+    type Item = (&'a Id<R>, &'a mut V);
+
+    fn next(&mut self) -> Option<(&'a Id<R>, &'a mut V)> {
+        let entry = match self.dense.by_ref().filter_map(Option::as_mut).next() {
+            Some(entry) => Some(entry),
+            None => self.sparse.next(),
+        };
+        entry.map(|entry| {
+            self.len -= 1;
+            (&entry.0, &mut entry.1)
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, R, V> ExactSizeIterator for IterMut<'a, R, V> {}
+
+/// Owning iterator over ids and values of `IdMap`.
+pub struct IntoIter<R, V> {
+    dense: vec::IntoIter<Option<(Id<R>, V)>>,
+    sparse: hash_map::IntoIter<usize, (Id<R>, V)>,
+    len: usize,
+}
+
+impl<R, V> Iterator for IntoIter<R, V> {
+    type Item = (Id<R>, V);
+
+    fn next(&mut self) -> Option<(Id<R>, V)> {
+        let mut entry = None;
+        while entry.is_none() {
+            match self.dense.next() {
+                Some(slot) => entry = slot,
+                None => break,
+            }
+        }
+        let entry = entry.or_else(|| self.sparse.next().map(|(_, entry)| entry));
+        if entry.is_some() {
+            self.len -= 1;
+        }
+        entry
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<R, V> ExactSizeIterator for IntoIter<R, V> {}
+
+/// Iterator over ids of `IdMap`.
+pub struct Keys<'a, R: 'a, V: 'a> {
+    iter: Iter<'a, R, V>,
+}
+
+impl<'a, R, V> Iterator for Keys<'a, R, V> {
+    type Item = &'a Id<R>;
+
+    fn next(&mut self) -> Option<&'a Id<R>> {
+        self.iter.next().map(|(id, _)| id)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a, R, V> ExactSizeIterator for Keys<'a, R, V> {}
+
+/// Iterator over values of `IdMap`.
+pub struct Values<'a, R: 'a, V: 'a> {
+    iter: Iter<'a, R, V>,
+}
+
+impl<'a, R, V> Iterator for Values<'a, R, V> {
+    type Item = &'a V;
+
+    fn next(&mut self) -> Option<&'a V> {
+        self.iter.next().map(|(_, value)| value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a, R, V> ExactSizeIterator for Values<'a, R, V> {}
+
+/// Iterator over mutable values of `IdMap`.
+pub struct ValuesMut<'a, R: 'a, V: 'a> {
+    iter: IterMut<'a, R, V>,
+}
+
+impl<'a, R, V> Iterator for ValuesMut<'a, R, V> {
+    type Item = &'a mut V;
+
+    fn next(&mut self) -> Option<&'a mut V> {
+        self.iter.next().map(|(_, value)| value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a, R, V> ExactSizeIterator for ValuesMut<'a, R, V> {}
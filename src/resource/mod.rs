@@ -5,25 +5,50 @@
 
 mod access;
 mod buffer;
+mod group;
 mod image;
 mod layout;
+pub mod map;
+mod range;
 mod usage;
 
+use alloc::vec::Vec;
+use collections::FnvHashMap;
+use core::fmt::{self, Debug, Display};
+use core::hash::Hash;
+use core::marker::PhantomData;
+use core::ops;
 use hal::buffer::{Access as BufferAccess, Usage as BufferUsage};
 use hal::image::{
     Access as ImageAccess, Layout as ImageLayout, SubresourceRange, Usage as ImageUsage,
 };
 use hal::pso::PipelineStage;
-use std::fmt::Debug;
-use std::hash::Hash;
-use std::marker::PhantomData;
-use std::ops::Range;
+use hal::queue::QueueFamilyId;
+#[cfg(feature = "std")]
+use std::error::Error;
 
 pub use self::access::Access;
-pub use self::buffer::BufferLayout;
-pub use self::layout::Layout;
+pub use self::buffer::{BufferAlignments, BufferLayout};
+pub use self::group::ResourceGroup;
+pub use self::layout::{Layout, LayoutPolicy};
+pub use self::map::IdMap;
+pub use self::range::Range;
 pub use self::usage::Usage;
 
+/// Additional bound for associated types of `Resource`.
+/// Requires serializable representation when `serde-1` feature is enabled.
+#[cfg(feature = "serde-1")]
+pub trait MaybeRepr: ::ser::Repr {}
+#[cfg(feature = "serde-1")]
+impl<T: ::ser::Repr> MaybeRepr for T {}
+
+/// Additional bound for associated types of `Resource`.
+/// Requires serializable representation when `serde-1` feature is enabled.
+#[cfg(not(feature = "serde-1"))]
+pub trait MaybeRepr {}
+#[cfg(not(feature = "serde-1"))]
+impl<T> MaybeRepr for T {}
+
 /// Defines resource type.
 /// Should be implemented for buffers and images.
 pub trait Resource: Copy + Debug + Eq + Ord + Hash {
@@ -37,7 +62,10 @@ pub trait Resource: Copy + Debug + Eq + Ord + Hash {
     type Usage: Usage;
 
     /// Sub-resource range.
-    type Range: Clone;
+    type Range: Range;
+
+    /// Derive minimal usage required to access the resource in specified state.
+    fn derive_usage(state: State<Self>) -> Self::Usage;
 }
 
 /// Buffer resource.
@@ -48,7 +76,11 @@ impl Resource for Buffer {
     type Access = BufferAccess;
     type Layout = buffer::BufferLayout;
     type Usage = BufferUsage;
-    type Range = Range<u64>;
+    type Range = ops::Range<u64>;
+
+    fn derive_usage(state: State<Self>) -> BufferUsage {
+        buffer::derive_usage(state.access)
+    }
 }
 
 /// Image resource.
@@ -60,6 +92,10 @@ impl Resource for Image {
     type Layout = ImageLayout;
     type Usage = ImageUsage;
     type Range = SubresourceRange;
+
+    fn derive_usage(state: State<Self>) -> ImageUsage {
+        image::derive_usage(state.access, state.layout)
+    }
 }
 
 /// Resource typed id
@@ -73,21 +109,101 @@ impl<R> Id<R> {
     }
 
     /// Get index
-    pub fn index(&self) -> u32 {
-        self.0
+    pub fn index(&self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// Allocator of resource ids.
+/// Each allocated id is unique for allocator instance.
+/// Buffer and image ids are allocated from separate allocators.
+#[derive(Clone, Debug)]
+pub struct IdAllocator<R: Resource> {
+    next: u32,
+    marker: PhantomData<R>,
+}
+
+impl<R> IdAllocator<R>
+where
+    R: Resource,
+{
+    /// Create new allocator.
+    pub fn new() -> Self {
+        IdAllocator {
+            next: 0,
+            marker: PhantomData,
+        }
+    }
+
+    /// Allocate new unique id.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if id space is exhausted.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Id<R> {
+        Id::new(self.alloc_range(1).start as u32)
+    }
+
+    /// Reserve contiguous block of `count` unique ids.
+    /// Returns range of indices of the ids. Those fit into `u32` to be passed to `Id::new`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if id space is exhausted.
+    pub fn alloc_range(&mut self, count: usize) -> ops::Range<usize> {
+        let start = self.next;
+        assert!(count <= (u32::MAX - start) as usize, "Resource ids exhausted");
+        self.next += count as u32;
+        start as usize..self.next as usize
+    }
+
+    /// Get the number of ids allocated so far.
+    pub fn allocated(&self) -> usize {
+        self.next as usize
     }
 }
 
+impl<R> Default for IdAllocator<R>
+where
+    R: Resource,
+{
+    fn default() -> Self {
+        IdAllocator::new()
+    }
+}
+
+/// Hazard between two consecutive accesses of the resource.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize))]
+pub enum Hazard {
+    /// No write is involved. Accesses need no dependency unless layout changes.
+    None,
+
+    /// Write must be made available and visible to the read.
+    ReadAfterWrite,
+
+    /// Read must be finished before the write. Execution dependency suffices.
+    WriteAfterRead,
+
+    /// Earlier write must be made available before the later one.
+    WriteAfterWrite,
+}
+
 /// State of the resource.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize), serde(bound = ""))]
 pub struct State<R: Resource> {
     /// Access types for the resource.
+    #[cfg_attr(feature = "serde-1", serde(with = "::ser::repr"))]
     pub access: R::Access,
 
     /// Current layout of the resource.
+    #[cfg_attr(feature = "serde-1", serde(with = "::ser::repr"))]
     pub layout: R::Layout,
 
     /// Stages at which resource is accessed.
+    #[cfg_attr(feature = "serde-1", serde(with = "::ser::repr"))]
     pub stages: PipelineStage,
 }
 
@@ -98,11 +214,59 @@ where
     /// Merge states.
     /// Panic if layouts are incompatible.
     pub fn merge(&self, rhs: Self) -> Self {
-        State {
+        self.try_merge(rhs).unwrap()
+    }
+
+    /// Merge states.
+    /// Returns error containing both states if layouts are incompatible.
+    pub fn try_merge(&self, rhs: Self) -> Result<Self, MergeError<R>> {
+        let layout = self.layout.merge(rhs.layout).ok_or(MergeError {
+            left: *self,
+            right: rhs,
+        })?;
+        Ok(State {
             access: self.access | rhs.access,
-            layout: self.layout.merge(rhs.layout).unwrap(),
+            layout,
             stages: self.stages | rhs.stages,
+        })
+    }
+
+    /// Get minimal usage required to access resource in this state.
+    /// Shader accesses are ambiguous. Declare usage explicitly if derived one doesn't fit.
+    pub fn usage(&self) -> R::Usage {
+        R::derive_usage(*self)
+    }
+
+    /// Get access types of the state that none of its stages performs.
+    /// Those can't be synchronized by barriers, which require each access type
+    /// to be supported by some of their stages. Including write access types.
+    pub fn unsupported_access(&self) -> R::Access {
+        self.access.unsupported_by(self.stages)
+    }
+
+    /// Get the state with stages extended to perform all its access types.
+    /// `TOP_OF_PIPE` and `BOTTOM_OF_PIPE` perform no accesses, so access types left
+    /// without other stages get all stages that perform them, like `HOST` for host access types.
+    /// Declared stages are kept. Legal states are returned as is.
+    pub fn legalized(&self) -> Self {
+        let no_access = PipelineStage::TOP_OF_PIPE | PipelineStage::BOTTOM_OF_PIPE;
+        let missing = self.access.unsupported_by(self.stages - no_access);
+        if missing == R::Access::none() {
+            return *self;
         }
+        let stages = (0..32)
+            .map(|bit| PipelineStage::from_bits_truncate(1 << bit) - no_access)
+            .filter(|&stage| !stage.is_empty() && missing.unsupported_by(stage) != missing)
+            .fold(self.stages, |acc, stage| acc | stage);
+        State { stages, ..*self }
+    }
+
+    /// Check if barriers may synchronize the state as is.
+    /// This requires each access type to be performed by some of its stages
+    /// other than `TOP_OF_PIPE` and `BOTTOM_OF_PIPE`. See `legalized`.
+    pub fn is_legal(&self) -> bool {
+        let no_access = PipelineStage::TOP_OF_PIPE | PipelineStage::BOTTOM_OF_PIPE;
+        self.access.unsupported_by(self.stages - no_access) == R::Access::none()
     }
 
     /// Check if access is exclusive.
@@ -110,9 +274,365 @@ where
         self.access.is_write()
     }
 
+    /// Check if the state has no write access types.
+    pub fn is_read_only(&self) -> bool {
+        !self.access.is_write()
+    }
+
+    /// Check if transition from `previous` state to this one changes layout.
+    pub fn has_layout_transition_from(&self, previous: &Self) -> bool {
+        self.layout != previous.layout
+    }
+
+    /// Check if states share some stages.
+    pub fn stages_intersect(&self, other: &Self) -> bool {
+        self.stages.intersects(other.stages)
+    }
+
     /// Check if states are compatible.
     /// This requires layouts to be compatible and non-exclusive access.
     pub fn compatible(&self, rhs: Self) -> bool {
         !self.exclusive() && !rhs.exclusive() && self.layout.merge(rhs.layout).is_some()
     }
+
+    /// Classify hazard between access in this state and following access in `next` state.
+    /// Layouts aren't considered. Layout transition is a write on its own.
+    pub fn hazard(&self, next: &Self) -> Hazard {
+        if self.access.is_write() {
+            if next.access.is_read() {
+                Hazard::ReadAfterWrite
+            } else if next.access.is_write() {
+                Hazard::WriteAfterWrite
+            } else {
+                Hazard::None
+            }
+        } else if next.access.is_write() {
+            Hazard::WriteAfterRead
+        } else {
+            Hazard::None
+        }
+    }
+}
+
+impl State<Buffer> {
+    /// State of the buffer read as uniform buffer at specified shader stages.
+    pub const fn uniform(stages: PipelineStage) -> Self {
+        State {
+            access: BufferAccess::CONSTANT_BUFFER_READ,
+            layout: BufferLayout,
+            stages,
+        }
+    }
+
+    /// State of the buffer read as vertex buffer.
+    pub const fn vertex() -> Self {
+        State {
+            access: BufferAccess::VERTEX_BUFFER_READ,
+            layout: BufferLayout,
+            stages: PipelineStage::VERTEX_INPUT,
+        }
+    }
+
+    /// State of the buffer read as index buffer.
+    pub const fn index() -> Self {
+        State {
+            access: BufferAccess::INDEX_BUFFER_READ,
+            layout: BufferLayout,
+            stages: PipelineStage::VERTEX_INPUT,
+        }
+    }
+
+    /// State of the buffer read as indirect draw or dispatch arguments.
+    pub const fn indirect_read() -> Self {
+        State {
+            access: BufferAccess::INDIRECT_COMMAND_READ,
+            layout: BufferLayout,
+            stages: PipelineStage::DRAW_INDIRECT,
+        }
+    }
+
+    /// State of the buffer copied from.
+    pub const fn transfer_src() -> Self {
+        State {
+            access: BufferAccess::TRANSFER_READ,
+            layout: BufferLayout,
+            stages: PipelineStage::TRANSFER,
+        }
+    }
+
+    /// State of the buffer copied to.
+    pub const fn transfer_dst() -> Self {
+        State {
+            access: BufferAccess::TRANSFER_WRITE,
+            layout: BufferLayout,
+            stages: PipelineStage::TRANSFER,
+        }
+    }
+}
+
+// Constructors of states that combine several access types or stages aren't `const`,
+// as combining flags isn't.
+impl State<Image> {
+    /// State of the image sampled at specified shader stages.
+    pub const fn sampled(stages: PipelineStage) -> Self {
+        State {
+            access: ImageAccess::SHADER_READ,
+            layout: ImageLayout::ShaderReadOnlyOptimal,
+            stages,
+        }
+    }
+
+    /// State of the image used as color attachment.
+    pub fn color_attachment() -> Self {
+        State {
+            access: ImageAccess::COLOR_ATTACHMENT_READ | ImageAccess::COLOR_ATTACHMENT_WRITE,
+            layout: ImageLayout::ColorAttachmentOptimal,
+            stages: PipelineStage::COLOR_ATTACHMENT_OUTPUT,
+        }
+    }
+
+    /// State of the image used as read-only depth-stencil attachment.
+    pub fn depth_attachment_read() -> Self {
+        State {
+            access: ImageAccess::DEPTH_STENCIL_ATTACHMENT_READ,
+            layout: ImageLayout::DepthStencilReadOnlyOptimal,
+            stages: PipelineStage::EARLY_FRAGMENT_TESTS | PipelineStage::LATE_FRAGMENT_TESTS,
+        }
+    }
+
+    /// State of the image copied from.
+    pub const fn transfer_src() -> Self {
+        State {
+            access: ImageAccess::TRANSFER_READ,
+            layout: ImageLayout::TransferSrcOptimal,
+            stages: PipelineStage::TRANSFER,
+        }
+    }
+
+    /// State of the image copied or cleared to.
+    pub const fn transfer_dst() -> Self {
+        State {
+            access: ImageAccess::TRANSFER_WRITE,
+            layout: ImageLayout::TransferDstOptimal,
+            stages: PipelineStage::TRANSFER,
+        }
+    }
+
+    /// State of the storage image read and written at specified shader stages.
+    pub fn storage_read_write(stages: PipelineStage) -> Self {
+        State {
+            access: ImageAccess::SHADER_READ | ImageAccess::SHADER_WRITE,
+            layout: ImageLayout::General,
+            stages,
+        }
+    }
+
+    /// State of the color attachment also read as input attachment by fragment shader.
+    /// See `ImageUse::FeedbackLoop`.
+    pub fn feedback_loop() -> Self {
+        State {
+            access: ImageAccess::INPUT_ATTACHMENT_READ
+                | ImageAccess::COLOR_ATTACHMENT_READ
+                | ImageAccess::COLOR_ATTACHMENT_WRITE,
+            layout: ImageLayout::General,
+            stages: PipelineStage::FRAGMENT_SHADER | PipelineStage::COLOR_ATTACHMENT_OUTPUT,
+        }
+    }
+
+    /// Check if the image is read as input attachment while written as attachment.
+    /// Commands in this state must be ordered by self-dependency of the pass.
+    /// See `sync::FeedbackLoop`.
+    pub fn is_feedback_loop(&self) -> bool {
+        self.access.contains(ImageAccess::INPUT_ATTACHMENT_READ)
+            && self.access.intersects(
+                ImageAccess::COLOR_ATTACHMENT_WRITE | ImageAccess::DEPTH_STENCIL_ATTACHMENT_WRITE,
+            )
+    }
+}
+
+/// Error returned by `State::try_merge` when layouts of the states can't be merged.
+#[derive(Clone, Copy, Debug)]
+pub struct MergeError<R: Resource> {
+    /// Left-hand side state of the merge.
+    pub left: State<R>,
+
+    /// Right-hand side state of the merge.
+    pub right: State<R>,
+}
+
+impl<R> Display for MergeError<R>
+where
+    R: Resource,
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmt,
+            "Layouts {:?} (stages: {:?}) and {:?} (stages: {:?}) can't be merged",
+            self.left.layout, self.left.stages, self.right.layout, self.right.stages
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R> Error for MergeError<R>
+where
+    R: Resource,
+{
+    fn description(&self) -> &str {
+        "Incompatible layouts can't be merged"
+    }
+}
+
+/// Sharing mode of the resource.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize))]
+pub enum SharingMode {
+    /// Resource is owned by one queue family at a time.
+    /// Ownership transfer is required to access it from another queue family.
+    #[default]
+    Exclusive,
+
+    /// Resource can be accessed from any queue family without ownership transfer.
+    Concurrent,
+}
+
+/// Ownership of the resource by queue family outside of the schedule.
+/// Like `QUEUE_FAMILY_EXTERNAL` or `QUEUE_FAMILY_FOREIGN` in Vulkan.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize), serde(bound = ""))]
+pub struct External<R: Resource> {
+    /// Family that owns the resource outside of the schedule.
+    /// Must not be used by passes.
+    #[cfg_attr(feature = "serde-1", serde(with = "::ser::repr"))]
+    pub family: QueueFamilyId,
+
+    /// State of the resource while it is owned by external family.
+    /// Only layout is used in ownership transfer barriers.
+    pub state: State<R>,
+}
+
+/// Properties of the resource that are not derived from passes.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize), serde(bound = ""))]
+pub struct ResourceInfo<R: Resource> {
+    /// Sharing mode of the resource.
+    pub sharing: SharingMode,
+
+    /// Resource is imported or exported.
+    /// i.e. it is used outside of the schedule and its memory can't be aliased.
+    pub external: bool,
+
+    /// Host writes resource content before schedule execution.
+    /// Host writes will be made visible to the first link.
+    pub host_write_before: bool,
+
+    /// Host reads resource content after schedule execution.
+    /// Device accesses from the last link will be made visible to the host.
+    pub host_read_after: bool,
+
+    /// Image is acquired from swapchain before schedule execution and presented after.
+    /// Its content is undefined before first link and it is transitioned to `Present` layout
+    /// after last link.
+    /// Must not be combined with host accesses. Ignored for buffers.
+    pub present: bool,
+
+    /// Known state of the resource before schedule execution.
+    /// First link will be transitioned from this state.
+    /// Or `None` if resource is already in the state of the first link.
+    /// Overrides `host_write_before`. Ignored for present images.
+    pub initial_state: Option<State<R>>,
+
+    /// Resource has separate instance for each frame in flight.
+    /// See `frame::FrameRing`.
+    pub per_frame: bool,
+
+    /// Resource is owned by external family before schedule execution.
+    /// First link acquires ownership from it regardless of sharing mode.
+    /// Overrides `initial_state` and `host_write_before`. Must not be combined with `present`.
+    pub import: Option<External<R>>,
+
+    /// Resource is returned to external family after schedule execution.
+    /// Last link releases ownership to it and transitions the resource to its layout.
+    /// Overrides `host_read_after`. Must not be combined with `present`.
+    pub export: Option<External<R>>,
+
+    /// Alignments required for ranges of the buffer declared by passes.
+    /// `None` if ranges are not validated. Ignored for images.
+    pub alignments: Option<BufferAlignments>,
+}
+
+impl<R> Default for ResourceInfo<R>
+where
+    R: Resource,
+{
+    fn default() -> Self {
+        ResourceInfo {
+            sharing: SharingMode::default(),
+            external: false,
+            host_write_before: false,
+            host_read_after: false,
+            present: false,
+            initial_state: None,
+            per_frame: false,
+            import: None,
+            export: None,
+            alignments: None,
+        }
+    }
+}
+
+/// Registered resources.
+/// Passes may declare only registered resources.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize))]
+pub struct Resources {
+    /// Registered buffers.
+    pub buffers: FnvHashMap<Id<Buffer>, ResourceInfo<Buffer>>,
+
+    /// Registered images.
+    pub images: FnvHashMap<Id<Image>, ResourceInfo<Image>>,
+
+    /// Groups of images that share state transitions.
+    pub image_groups: Vec<ResourceGroup<Image>>,
+
+    /// Policy of merging layouts of states in which passes sharing a link access the resource.
+    #[cfg_attr(feature = "serde-1", serde(default))]
+    pub layout_policy: LayoutPolicy,
+}
+
+impl Resources {
+    /// Create empty registry.
+    pub fn new() -> Self {
+        Resources::default()
+    }
+
+    /// Register buffer.
+    /// Each buffer must be registered once.
+    pub fn add_buffer(&mut self, id: Id<Buffer>, info: ResourceInfo<Buffer>) {
+        let old = self.buffers.insert(id, info);
+        debug_assert!(old.is_none(), "Buffer {:?} registered twice", id);
+    }
+
+    /// Register image.
+    /// Each image must be registered once.
+    pub fn add_image(&mut self, id: Id<Image>, info: ResourceInfo<Image>) {
+        let old = self.images.insert(id, info);
+        debug_assert!(old.is_none(), "Image {:?} registered twice", id);
+    }
+
+    /// Register group of images.
+    /// See `ResourceGroup`.
+    pub fn add_image_group(&mut self, group: ResourceGroup<Image>) {
+        self.image_groups.push(group);
+    }
+
+    /// Get info of the buffer.
+    pub fn buffer(&self, id: Id<Buffer>) -> ResourceInfo<Buffer> {
+        self.buffers.get(&id).cloned().unwrap_or_default()
+    }
+
+    /// Get info of the image.
+    pub fn image(&self, id: Id<Image>) -> ResourceInfo<Image> {
+        self.images.get(&id).cloned().unwrap_or_default()
+    }
 }
@@ -0,0 +1,16 @@
+use core::fmt::Debug;
+
+use resource::MaybeRepr;
+
+/// Range of sub-resources
+pub trait Range: Debug + Clone + Eq + MaybeRepr {
+    /// Check if this range and another have common sub-resources.
+    fn overlaps(&self, other: &Self) -> bool;
+
+    /// Get smallest range that contains both ranges.
+    fn union(&self, other: &Self) -> Self;
+
+    /// Get common sub-resources of both ranges.
+    /// Or `None` if ranges don't overlap.
+    fn intersection(&self, other: &Self) -> Option<Self>;
+}
@@ -1,8 +1,10 @@
-use std::fmt::Debug;
-use std::ops::{BitOr, BitOrAssign};
+use core::fmt::Debug;
+use core::ops::{BitOr, BitOrAssign};
+
+use resource::MaybeRepr;
 
 /// Usage type combination
-pub trait Usage: Debug + Copy + BitOr<Output = Self> + BitOrAssign + Eq {
+pub trait Usage: Debug + Copy + BitOr<Output = Self> + BitOrAssign + Eq + MaybeRepr {
     /// Create empty combinations of usage types.
     fn none() -> Self;
 
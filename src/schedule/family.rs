@@ -1,6 +1,7 @@
-use std::ops::{Index, IndexMut};
-use std::slice::{Iter as SliceIter, IterMut as SliceIterMut};
-use std::vec::IntoIter as VecIntoIter;
+use alloc::vec::IntoIter as VecIntoIter;
+use alloc::vec::Vec;
+use core::ops::{Index, IndexMut};
+use core::slice::{Iter as SliceIter, IterMut as SliceIterMut};
 
 use hal::queue::QueueFamilyId;
 
@@ -10,7 +11,9 @@ use super::submission::{Submission, SubmissionId};
 /// Instances of this type contains array of `Queue`s.
 /// All contained queues has identical capabilities.
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize))]
 pub struct Family<S> {
+    #[cfg_attr(feature = "serde-1", serde(with = "::ser::repr"))]
     id: QueueFamilyId,
     queues: Vec<Queue<S>>,
 }
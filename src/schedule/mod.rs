@@ -7,26 +7,38 @@
 //! `Queue`'s are grouped into `Family`. All queues from one `Family` has identical capabilities.
 //! `Schedule` is a set or `Family` instances.
 //!
+//! Queues of each family and submissions of each queue are indexed contiguously from zero.
+//! `QueueId` and `SubmissionId` are stable references to them and may be used with
+//! `Schedule::queue` and `Schedule::submission` or indexing.
+//!
 
 mod family;
 mod queue;
 mod submission;
 
-use fnv::FnvHashMap;
-use std::collections::hash_map::{
+use collections::FnvHashMap;
+use collections::hash_map::{
     IntoIter as HashMapIntoIter, Values as HashMapValues, ValuesMut as HashMapValuesMut,
 };
-use std::ops::{Index, IndexMut};
+use core::ops::{Index, IndexMut};
 
 use hal::queue::QueueFamilyId;
 
+use pass::{PassId, PassMeta};
+
 pub use self::family::Family;
 pub use self::queue::{Queue, QueueId, QueueIter, QueueIterMut};
 pub use self::submission::{Submission, SubmissionId};
 
 /// All schedule on which passes were scheduled.
 #[derive(Debug)]
+#[cfg_attr(
+    feature = "serde-1",
+    derive(Serialize, Deserialize),
+    serde(bound(serialize = "S: ::serde::Serialize", deserialize = "S: ::serde::Deserialize<'de>"))
+)]
 pub struct Schedule<S> {
+    #[cfg_attr(feature = "serde-1", serde(with = "::ser::families"))]
     map: FnvHashMap<QueueFamilyId, Family<S>>,
 }
 
@@ -103,11 +115,46 @@ impl<S> Schedule<S> {
             .and_then(|queue| queue.submission(sid))
     }
 
-    /// Get reference to `Submission` instance by id.
+    /// Get mutable reference to `Submission` instance by id.
     pub fn submission_mut(&mut self, sid: SubmissionId) -> Option<&mut Submission<S>> {
         self.queue_mut(sid.queue())
             .and_then(|queue| queue.submission_mut(sid))
     }
+
+    /// Get name and tag of the pass.
+    /// Returns `None` if the pass has no submission in the schedule.
+    pub fn pass_meta(&self, pass: PassId) -> Option<PassMeta> {
+        self.iter()
+            .flat_map(|family| family.iter())
+            .flat_map(|queue| queue.iter())
+            .find(|&(_, submission)| submission.pass() == pass)
+            .map(|(_, submission)| submission.meta())
+    }
+
+    /// Find scheduled pass by name.
+    /// Returns the pass with the lowest id if several passes share the name.
+    pub fn find_pass(&self, name: &str) -> Option<PassId> {
+        self.iter()
+            .flat_map(|family| family.iter())
+            .flat_map(|queue| queue.iter())
+            .filter(|&(_, submission)| submission.meta().name == Some(name))
+            .map(|(_, submission)| submission.pass())
+            .min()
+    }
+
+    /// Check that queues and submissions are indexed contiguously from zero
+    /// and submissions of each queue are submitted in order of indices.
+    pub(crate) fn is_dense(&self) -> bool {
+        self.map.iter().all(|(&fid, family)| {
+            family.id() == fid && family.iter().enumerate().all(|(index, queue)| {
+                queue.id() == QueueId::new(fid, index)
+                    && queue
+                        .iter()
+                        .zip(queue.iter().skip(1))
+                        .all(|((_, prev), (_, next))| prev.submit_order() < next.submit_order())
+            })
+        })
+    }
 }
 
 /// Iterator over owned families in this schedule
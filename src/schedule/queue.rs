@@ -1,14 +1,18 @@
-use std::iter::{DoubleEndedIterator, Enumerate, ExactSizeIterator};
-use std::ops::{Index, IndexMut};
-use std::slice::{Iter as SliceIter, IterMut as SliceIterMut};
-use std::vec::IntoIter as VecIntoIter;
+use alloc::vec::IntoIter as VecIntoIter;
+use alloc::vec::Vec;
+use core::iter::{DoubleEndedIterator, Enumerate, ExactSizeIterator};
+use core::ops::{Index, IndexMut};
+use core::slice::{Iter as SliceIter, IterMut as SliceIterMut};
 
 use hal::queue::QueueFamilyId;
 
 use super::submission::{Submission, SubmissionId};
 
 /// Queue id.
+/// Queues of a family are indexed contiguously from zero.
+/// Ids are ordered by family first and then by index.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize))]
 pub struct QueueId {
     family: usize,
     index: usize,
@@ -130,6 +134,7 @@ impl<S> ExactSizeIterator for QueueIntoIter<S> {}
 /// Instances of this type contains array of `Submission`s.
 /// Those submissions are expected to be submitted in order.
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize))]
 pub struct Queue<S> {
     id: QueueId,
     submissions: Vec<Submission<S>>,
@@ -1,16 +1,22 @@
-use fnv::FnvHashMap;
-use std::collections::hash_map::Iter as HashMapIter;
+use alloc::vec::Vec;
+use collections::FnvHashMap;
+use collections::hash_map::Iter as HashMapIter;
 
 use hal::queue::QueueFamilyId;
 
-use pass::PassId;
+use pass::{PassId, PassKind, PassMeta};
 use resource::{Buffer, Id, Image};
 use Pick;
 
 use super::QueueId;
 
 /// Submission id.
+/// Submissions of a queue are indexed contiguously from zero in the order they must be submitted.
+/// Thus, ids of submissions of the same queue are ordered as the submissions are.
+/// Ids of submissions of different queues are ordered by queue ids,
+/// which implies nothing about order of execution.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize))]
 pub struct SubmissionId {
     queue: QueueId,
     index: usize,
@@ -32,7 +38,7 @@ impl SubmissionId {
         self.queue
     }
 
-    /// Get index.
+    /// Get index within the queue.
     pub fn index(&self) -> usize {
         self.index
     }
@@ -40,10 +46,21 @@ impl SubmissionId {
 
 /// This type corresponds to commands that should be recorded into single primary command buffer.
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize))]
 pub struct Submission<S> {
     buffers: FnvHashMap<Id<Buffer>, usize>,
     images: FnvHashMap<Id<Image>, usize>,
+    stencils: FnvHashMap<Id<Image>, usize>,
+    parts: FnvHashMap<Id<Image>, Vec<(usize, usize)>>,
+    buffer_parts: FnvHashMap<Id<Buffer>, (usize, usize)>,
+    unhonored_buffers: Vec<Id<Buffer>>,
+    unhonored_images: Vec<Id<Image>>,
+    resolves: FnvHashMap<Id<Image>, Id<Image>>,
     pass: PassId,
+    meta: PassMeta,
+    kind: PassKind,
+    conditional: bool,
+    transition: bool,
     wait_factor: usize,
     submit_order: usize,
     sync: S,
@@ -55,6 +72,31 @@ impl<S> Submission<S> {
         self.pass
     }
 
+    /// Get name and tag of the pass.
+    pub fn meta(&self) -> PassMeta {
+        self.meta
+    }
+
+    /// Get kind of operation performed by `Submission`.
+    /// Sparse binding submissions aren't ordered with other submissions of the queue.
+    pub fn kind(&self) -> PassKind {
+        self.kind
+    }
+
+    /// Check if main commands of `Submission` may be skipped at record time.
+    /// Its synchronization must be recorded regardless.
+    pub fn is_conditional(&self) -> bool {
+        self.conditional
+    }
+
+    /// Check if `Submission` is a transition node inserted by synchronization.
+    /// It has no main commands and accesses no resources. Its acquire side holds barriers,
+    /// event waits and semaphores of the next submission of the queue, which is the submission
+    /// of the same pass. See `SyncOptions::standalone_transition_nodes`.
+    pub fn is_transition(&self) -> bool {
+        self.transition
+    }
+
     /// Get synchronization for `Submission`.
     pub fn sync(&self) -> &S {
         &self.sync
@@ -70,6 +112,11 @@ impl<S> Submission<S> {
         self.submit_order
     }
 
+    /// Get mutable synchronization for `Submission`.
+    pub(crate) fn sync_mut(&mut self) -> &mut S {
+        &mut self.sync
+    }
+
     /// Iterator over buffers
     pub fn buffers(&self) -> HashMapIter<Id<Buffer>, usize> {
         self.buffers.iter()
@@ -80,6 +127,23 @@ impl<S> Submission<S> {
         self.images.iter()
     }
 
+    /// Iterator over images which stencil aspect is tracked by separate chain.
+    pub fn stencils(&self) -> HashMapIter<'_, Id<Image>, usize> {
+        self.stencils.iter()
+    }
+
+    /// Iterator over images tracked by separate chain for each range.
+    /// Each image has pairs of part index and link index for parts accessed by the submission.
+    pub fn parts(&self) -> HashMapIter<'_, Id<Image>, Vec<(usize, usize)>> {
+        self.parts.iter()
+    }
+
+    /// Iterator over buffers tracked by separate chain for each range.
+    /// Each buffer has part index and link index of the part accessed by the submission.
+    pub fn buffer_parts(&self) -> HashMapIter<'_, Id<Buffer>, (usize, usize)> {
+        self.buffer_parts.iter()
+    }
+
     /// Get link index for buffer by id.
     pub fn buffer(&self, id: Id<Buffer>) -> usize {
         self.buffers[&id]
@@ -90,24 +154,167 @@ impl<S> Submission<S> {
         self.images[&id]
     }
 
+    /// Get link index for stencil aspect chain of image by id.
+    pub fn stencil(&self, id: Id<Image>) -> usize {
+        self.stencils[&id]
+    }
+
+    /// Get link index for image part by id and index of the part.
+    /// Or `None` if submission doesn't access the part.
+    pub fn part(&self, id: Id<Image>, part: usize) -> Option<usize> {
+        self.parts
+            .get(&id)?
+            .iter()
+            .find(|&&(index, _)| index == part)
+            .map(|&(_, link)| link)
+    }
+
+    /// Get link index for buffer part by id and index of the part.
+    /// Or `None` if submission doesn't access the part.
+    pub fn buffer_part(&self, id: Id<Buffer>, part: usize) -> Option<usize> {
+        match self.buffer_parts.get(&id) {
+            Some(&(index, link)) if index == part => Some(link),
+            _ => None,
+        }
+    }
+
+    /// Get buffers which ordering hints declared by the pass couldn't be honored.
+    /// Sorted.
+    /// Pass with `AsLateAsPossible` hint isn't immediately followed by the next submission
+    /// of the queue that accesses the buffer. Or isn't the last one if there is none.
+    /// Similarly for `AsEarlyAsPossible` and the previous submission.
+    pub fn unhonored_buffer_hints(&self) -> &[Id<Buffer>] {
+        &self.unhonored_buffers
+    }
+
+    /// Get images which ordering hints declared by the pass couldn't be honored.
+    /// Sorted. See `unhonored_buffer_hints`.
+    pub fn unhonored_image_hints(&self) -> &[Id<Image>] {
+        &self.unhonored_images
+    }
+
+    /// Get multisampled image resolved into the image by render pass of the submission.
+    /// Or `None` if the image isn't a resolve target. See `Pass::resolves`.
+    pub fn resolve_source(&self, id: Id<Image>) -> Option<Id<Image>> {
+        self.resolves.get(&id).cloned()
+    }
+
+    /// Check if submission accesses the buffer or any of its parts.
+    pub(crate) fn accesses_buffer(&self, id: Id<Buffer>) -> bool {
+        self.buffers.contains_key(&id) || self.buffer_parts.contains_key(&id)
+    }
+
+    /// Check if submission accesses the image, its stencil aspect or any of its parts.
+    pub(crate) fn accesses_image(&self, id: Id<Image>) -> bool {
+        self.images.contains_key(&id)
+            || self.stencils.contains_key(&id)
+            || self.parts.contains_key(&id)
+    }
+
+    /// Set resources which ordering hints couldn't be honored.
+    pub(crate) fn set_unhonored_hints(&mut self, buffers: Vec<Id<Buffer>>, images: Vec<Id<Image>>) {
+        self.unhonored_buffers = buffers;
+        self.unhonored_images = images;
+    }
+
+    /// Get map of link indices for stencil aspect chains.
+    pub(crate) fn stencils_map(&self) -> &FnvHashMap<Id<Image>, usize> {
+        &self.stencils
+    }
+
+    /// Get mutable map of link indices for stencil aspect chains.
+    pub(crate) fn stencils_mut(&mut self) -> &mut FnvHashMap<Id<Image>, usize> {
+        &mut self.stencils
+    }
+
+    /// Get mutable map of link indices for image part chains.
+    pub(crate) fn parts_mut(&mut self) -> &mut FnvHashMap<Id<Image>, Vec<(usize, usize)>> {
+        &mut self.parts
+    }
+
+    /// Get mutable map of link indices for buffer part chains.
+    pub(crate) fn buffer_parts_mut(&mut self) -> &mut FnvHashMap<Id<Buffer>, (usize, usize)> {
+        &mut self.buffer_parts
+    }
+
+    /// Set images resolved by render pass of the submission, by resolve targets.
+    pub(crate) fn set_resolves(&mut self, resolves: FnvHashMap<Id<Image>, Id<Image>>) {
+        self.resolves = resolves;
+    }
+
+    /// Set name and tag of the pass.
+    pub(crate) fn set_meta(&mut self, meta: PassMeta) {
+        self.meta = meta;
+    }
+
+    /// Set submit order of the submission.
+    pub(crate) fn set_submit_order(&mut self, submit_order: usize) {
+        self.submit_order = submit_order;
+    }
+
+    /// Mark submission as one which main commands may be skipped.
+    pub(crate) fn set_conditional(&mut self, conditional: bool) {
+        self.conditional = conditional;
+    }
+
     /// Create new submission with specified pass.
-    pub(crate) fn new(wait_factor: usize, submit_order: usize, pass: PassId, sync: S) -> Self {
+    pub(crate) fn new(
+        wait_factor: usize,
+        submit_order: usize,
+        pass: PassId,
+        kind: PassKind,
+        sync: S,
+    ) -> Self {
         Submission {
             buffers: FnvHashMap::default(),
             images: FnvHashMap::default(),
+            stencils: FnvHashMap::default(),
+            parts: FnvHashMap::default(),
+            buffer_parts: FnvHashMap::default(),
+            unhonored_buffers: Vec::new(),
+            unhonored_images: Vec::new(),
+            resolves: FnvHashMap::default(),
             pass,
+            meta: PassMeta::default(),
+            kind,
+            conditional: false,
+            transition: false,
             wait_factor,
             submit_order,
             sync,
         }
     }
 
+    /// Create transition node to be submitted right before the submission.
+    pub(crate) fn transition_node<T>(&self, submit_order: usize, sync: T) -> Submission<T> {
+        let mut node = Submission::new(
+            self.wait_factor,
+            submit_order,
+            self.pass,
+            PassKind::Commands,
+            sync,
+        );
+        node.meta = self.meta;
+        node.transition = true;
+        node
+    }
+
     /// Set new synchronization to the `Submission`.
     pub(crate) fn set_sync<T>(&self, sync: T) -> Submission<T> {
         Submission {
             buffers: self.buffers.clone(),
             images: self.images.clone(),
+            stencils: self.stencils.clone(),
+            parts: self.parts.clone(),
+            buffer_parts: self.buffer_parts.clone(),
+            unhonored_buffers: self.unhonored_buffers.clone(),
+            unhonored_images: self.unhonored_images.clone(),
+            resolves: self.resolves.clone(),
             pass: self.pass,
+            meta: self.meta,
+            kind: self.kind,
+            conditional: self.conditional,
+            transition: self.transition,
             wait_factor: self.wait_factor,
             submit_order: self.submit_order,
             sync,
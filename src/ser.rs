@@ -0,0 +1,362 @@
+//!
+//! This module provides `serde` support for types that use `gfx-hal` types
+//! which are not serializable by themselves.
+//!
+
+use alloc::vec::Vec;
+use collections::FnvHashMap;
+use core::ops::Range;
+use hal::buffer::{Access as BufferAccess, Usage as BufferUsage};
+use hal::format::Aspects;
+use hal::image::{
+    Access as ImageAccess, Extent, Layout as ImageLayout, Offset, SubresourceRange,
+    Usage as ImageUsage,
+};
+use hal::pass::{AttachmentLoadOp, AttachmentOps, AttachmentStoreOp};
+use hal::pso::PipelineStage;
+use hal::queue::QueueFamilyId;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use resource::{BufferLayout, Id, IdMap};
+
+/// Serializable representation of a type.
+pub trait Repr: Sized {
+    /// Serializable type.
+    type Repr: Serialize + DeserializeOwned;
+
+    /// Convert value to representation.
+    fn to_repr(&self) -> Self::Repr;
+
+    /// Convert representation back to value.
+    /// Returns `None` if representation is invalid.
+    fn from_repr(repr: Self::Repr) -> Option<Self>;
+}
+
+/// Use with `#[serde(with = "::ser::repr")]` for fields of type implementing `Repr`.
+pub mod repr {
+    use super::Repr;
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    /// Serialize value by representation.
+    pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Repr,
+        S: Serializer,
+    {
+        value.to_repr().serialize(serializer)
+    }
+
+    /// Deserialize value from representation.
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: Repr,
+        D: Deserializer<'de>,
+    {
+        let repr = T::Repr::deserialize(deserializer)?;
+        T::from_repr(repr).ok_or_else(|| D::Error::custom("invalid value"))
+    }
+}
+
+/// Use with `#[serde(with = "::ser::families")]` for map of families.
+/// Families are serialized as sequence since they know their ids.
+pub mod families {
+    use collections::FnvHashMap;
+    use hal::queue::QueueFamilyId;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use schedule::Family;
+
+    /// Serialize families as sequence.
+    pub fn serialize<T, S>(
+        map: &FnvHashMap<QueueFamilyId, Family<T>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        T: Serialize,
+        S: Serializer,
+    {
+        let mut families: Vec<_> = map.values().collect();
+        families.sort_by_key(|family| family.id());
+        serializer.collect_seq(families)
+    }
+
+    /// Deserialize families from sequence.
+    pub fn deserialize<'de, T, D>(
+        deserializer: D,
+    ) -> Result<FnvHashMap<QueueFamilyId, Family<T>>, D::Error>
+    where
+        T: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        let families = Vec::<Family<T>>::deserialize(deserializer)?;
+        Ok(families
+            .into_iter()
+            .map(|family| (family.id(), family))
+            .collect())
+    }
+}
+
+impl<R> Serialize for Id<R> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        (self.index() as u32).serialize(serializer)
+    }
+}
+
+impl<'de, R> Deserialize<'de> for Id<R> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        u32::deserialize(deserializer).map(Id::new)
+    }
+}
+
+impl<R, V> Serialize for IdMap<R, V>
+where
+    V: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_map(self.iter())
+    }
+}
+
+impl<'de, R, V> Deserialize<'de> for IdMap<R, V>
+where
+    V: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let map = FnvHashMap::<u32, V>::deserialize(deserializer)?;
+        Ok(map.into_iter().map(|(index, value)| (Id::new(index), value)).collect())
+    }
+}
+
+macro_rules! bits_repr {
+    ($($type:ty : $bits:ty),*) => {
+        $(
+            impl Repr for $type {
+                type Repr = $bits;
+
+                fn to_repr(&self) -> $bits {
+                    self.bits()
+                }
+
+                fn from_repr(bits: $bits) -> Option<Self> {
+                    Self::from_bits(bits)
+                }
+            }
+        )*
+    };
+}
+
+bits_repr!(
+    BufferAccess: u32,
+    BufferUsage: u32,
+    ImageAccess: u32,
+    ImageUsage: u32,
+    PipelineStage: u32
+);
+
+impl Repr for QueueFamilyId {
+    type Repr = usize;
+
+    fn to_repr(&self) -> usize {
+        self.0
+    }
+
+    fn from_repr(repr: usize) -> Option<Self> {
+        Some(QueueFamilyId(repr))
+    }
+}
+
+impl Repr for BufferLayout {
+    type Repr = ();
+
+    fn to_repr(&self) {}
+
+    fn from_repr(_: ()) -> Option<Self> {
+        Some(BufferLayout)
+    }
+}
+
+impl Repr for ImageLayout {
+    type Repr = u8;
+
+    fn to_repr(&self) -> u8 {
+        match *self {
+            ImageLayout::General => 0,
+            ImageLayout::ColorAttachmentOptimal => 1,
+            ImageLayout::DepthStencilAttachmentOptimal => 2,
+            ImageLayout::DepthStencilReadOnlyOptimal => 3,
+            ImageLayout::ShaderReadOnlyOptimal => 4,
+            ImageLayout::TransferSrcOptimal => 5,
+            ImageLayout::TransferDstOptimal => 6,
+            ImageLayout::Undefined => 7,
+            ImageLayout::Preinitialized => 8,
+            ImageLayout::Present => 9,
+        }
+    }
+
+    fn from_repr(repr: u8) -> Option<Self> {
+        Some(match repr {
+            0 => ImageLayout::General,
+            1 => ImageLayout::ColorAttachmentOptimal,
+            2 => ImageLayout::DepthStencilAttachmentOptimal,
+            3 => ImageLayout::DepthStencilReadOnlyOptimal,
+            4 => ImageLayout::ShaderReadOnlyOptimal,
+            5 => ImageLayout::TransferSrcOptimal,
+            6 => ImageLayout::TransferDstOptimal,
+            7 => ImageLayout::Undefined,
+            8 => ImageLayout::Preinitialized,
+            9 => ImageLayout::Present,
+            _ => return None,
+        })
+    }
+}
+
+impl Repr for Range<u64> {
+    type Repr = Range<u64>;
+
+    fn to_repr(&self) -> Range<u64> {
+        self.clone()
+    }
+
+    fn from_repr(repr: Range<u64>) -> Option<Self> {
+        Some(repr)
+    }
+}
+
+impl Repr for Range<PipelineStage> {
+    type Repr = Range<u32>;
+
+    fn to_repr(&self) -> Range<u32> {
+        self.start.bits()..self.end.bits()
+    }
+
+    fn from_repr(repr: Range<u32>) -> Option<Self> {
+        Some(PipelineStage::from_bits(repr.start)?..PipelineStage::from_bits(repr.end)?)
+    }
+}
+
+impl Repr for SubresourceRange {
+    type Repr = (u8, Range<u8>, Range<u16>);
+
+    fn to_repr(&self) -> (u8, Range<u8>, Range<u16>) {
+        (
+            self.aspects.bits(),
+            self.levels.clone(),
+            self.layers.clone(),
+        )
+    }
+
+    fn from_repr((aspects, levels, layers): (u8, Range<u8>, Range<u16>)) -> Option<Self> {
+        Some(SubresourceRange {
+            aspects: Aspects::from_bits(aspects)?,
+            levels,
+            layers,
+        })
+    }
+}
+
+impl Repr for Extent {
+    type Repr = (u32, u32, u32);
+
+    fn to_repr(&self) -> (u32, u32, u32) {
+        (self.width, self.height, self.depth)
+    }
+
+    fn from_repr((width, height, depth): (u32, u32, u32)) -> Option<Self> {
+        Some(Extent {
+            width,
+            height,
+            depth,
+        })
+    }
+}
+
+impl Repr for Offset {
+    type Repr = (i32, i32, i32);
+
+    fn to_repr(&self) -> (i32, i32, i32) {
+        (self.x, self.y, self.z)
+    }
+
+    fn from_repr((x, y, z): (i32, i32, i32)) -> Option<Self> {
+        Some(Offset { x, y, z })
+    }
+}
+
+impl Repr for AttachmentOps {
+    type Repr = (u8, u8);
+
+    fn to_repr(&self) -> (u8, u8) {
+        let load = match self.load {
+            AttachmentLoadOp::Load => 0,
+            AttachmentLoadOp::Clear => 1,
+            AttachmentLoadOp::DontCare => 2,
+        };
+        let store = match self.store {
+            AttachmentStoreOp::Store => 0,
+            AttachmentStoreOp::DontCare => 1,
+        };
+        (load, store)
+    }
+
+    fn from_repr((load, store): (u8, u8)) -> Option<Self> {
+        let load = match load {
+            0 => AttachmentLoadOp::Load,
+            1 => AttachmentLoadOp::Clear,
+            2 => AttachmentLoadOp::DontCare,
+            _ => return None,
+        };
+        let store = match store {
+            0 => AttachmentStoreOp::Store,
+            1 => AttachmentStoreOp::DontCare,
+            _ => return None,
+        };
+        Some(AttachmentOps::new(load, store))
+    }
+}
+
+impl<T> Repr for Vec<T>
+where
+    T: Repr,
+{
+    type Repr = Vec<T::Repr>;
+
+    fn to_repr(&self) -> Vec<T::Repr> {
+        self.iter().map(Repr::to_repr).collect()
+    }
+
+    fn from_repr(repr: Vec<T::Repr>) -> Option<Self> {
+        repr.into_iter().map(T::from_repr).collect()
+    }
+}
+
+impl<T> Repr for Option<T>
+where
+    T: Repr,
+{
+    type Repr = Option<T::Repr>;
+
+    fn to_repr(&self) -> Option<T::Repr> {
+        self.as_ref().map(Repr::to_repr)
+    }
+
+    fn from_repr(repr: Option<T::Repr>) -> Option<Self> {
+        match repr {
+            Some(repr) => T::from_repr(repr).map(Some),
+            None => Some(None),
+        }
+    }
+}
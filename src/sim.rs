@@ -0,0 +1,1440 @@
+//!
+//! This module provides hazard simulator for synchronized `Chains`
+//! and generator of random pass graphs to throw at it.
+//! Available with `test-utils` feature.
+//!
+
+use fnv::FnvHashMap;
+use rand::Rng;
+use std::cmp::{max, min};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::hash::Hash;
+use std::ops::Range;
+
+use hal::format::Aspects;
+use hal::image::{Layout as ImageLayout, SubresourceRange};
+use hal::pso::PipelineStage;
+use hal::queue::QueueFamilyId;
+
+use chain::Chain;
+use collect::{collect_with_capabilities, Chains};
+use pass::{Capability, Pass, PassBuilder, PassId, PassKind};
+use resource::{
+    Access, Buffer, External, Id, Image, Layout, Range as ResourceRange, Resource, ResourceInfo,
+    Resources, SharingMode, State,
+};
+use schedule::{QueueId, SubmissionId};
+use sync::{
+    sync_chains, Barrier, FenceStrategy, Guard, GuardBarriers, SyncData, SyncMode, SyncOptions,
+};
+
+/// Kind of data hazard found by `verify`.
+/// Layout transitions, ownership transfers and sparse binding write the resource.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HazardKind {
+    /// Read isn't ordered after write or the write isn't made visible to it.
+    ReadAfterWrite,
+
+    /// Write isn't ordered after write or the earlier write isn't made available.
+    WriteAfterWrite,
+
+    /// Write isn't ordered after read.
+    WriteAfterRead,
+
+    /// Resource is accessed in layout other than the one it is in.
+    /// Or barrier transitions it from another layout.
+    LayoutMismatch,
+
+    /// Resource is accessed by family that doesn't own it.
+    /// Or ownership is acquired without being released.
+    Ownership,
+}
+
+/// Hazard found by `verify`.
+#[derive(Clone, Debug)]
+pub enum Hazard {
+    /// Submission waits for semaphore or event that isn't signaled by any earlier submission.
+    Unsignaled {
+        /// Id of the waiting submission.
+        submission: SubmissionId,
+    },
+
+    /// Access to the buffer exposes hazard.
+    Buffer {
+        /// Id of the buffer.
+        id: Id<Buffer>,
+        /// Submission that performed the access.
+        /// Or the last submission that accessed the buffer for hazards after the schedule.
+        submission: SubmissionId,
+        /// Submission that performed conflicting access.
+        /// `None` if the access was performed outside of the schedule.
+        other: Option<SubmissionId>,
+        /// Kind of the hazard.
+        kind: HazardKind,
+    },
+
+    /// Access to the image exposes hazard.
+    Image {
+        /// Id of the image.
+        id: Id<Image>,
+        /// Submission that performed the access.
+        /// Or the last submission that accessed the image for hazards after the schedule.
+        submission: SubmissionId,
+        /// Submission that performed conflicting access.
+        /// `None` if the access was performed outside of the schedule.
+        other: Option<SubmissionId>,
+        /// Kind of the hazard.
+        kind: HazardKind,
+    },
+}
+
+impl From<(Id<Buffer>, SubmissionId, Conflict)> for Hazard {
+    fn from((id, submission, conflict): (Id<Buffer>, SubmissionId, Conflict)) -> Self {
+        Hazard::Buffer {
+            id,
+            submission,
+            other: conflict.other,
+            kind: conflict.kind,
+        }
+    }
+}
+
+impl From<(Id<Image>, SubmissionId, Conflict)> for Hazard {
+    fn from((id, submission, conflict): (Id<Image>, SubmissionId, Conflict)) -> Self {
+        Hazard::Image {
+            id,
+            submission,
+            other: conflict.other,
+            kind: conflict.kind,
+        }
+    }
+}
+
+impl Display for Hazard {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Hazard::Unsignaled { submission } => write!(
+                fmt,
+                "Submission {:?} waits for unsignaled semaphore or event",
+                submission
+            ),
+            Hazard::Buffer {
+                id,
+                submission,
+                other,
+                kind,
+            } => write!(
+                fmt,
+                "{:?} hazard on buffer {:?} in submission {:?} with submission {:?}",
+                kind, id, submission, other
+            ),
+            Hazard::Image {
+                id,
+                submission,
+                other,
+                kind,
+            } => write!(
+                fmt,
+                "{:?} hazard on image {:?} in submission {:?} with submission {:?}",
+                kind, id, submission, other
+            ),
+        }
+    }
+}
+
+impl Error for Hazard {
+    fn description(&self) -> &str {
+        match *self {
+            Hazard::Unsignaled { .. } => "Unsignaled semaphore or event",
+            Hazard::Buffer { .. } => "Buffer access hazard",
+            Hazard::Image { .. } => "Image access hazard",
+        }
+    }
+}
+
+/// Check that no order of execution permitted by synchronization of the `Chains`
+/// exposes data hazard.
+///
+/// Submissions are simulated in submit order, but conflicting operations are checked against
+/// dependencies rather than against the simulated order.
+/// Dependencies are built from queue order, pipeline barriers, events and semaphores
+/// with their stage and access masks. Dependency must cover stages the later operation
+/// is performed at and writes must be made available and visible, as Vulkan requires.
+/// So any hazard that some permitted order exposes is found.
+///
+/// Only states of links are used, so passes are not required.
+/// Semaphores are matched by equality of signal and wait parts.
+/// Build `Chains` with the same value for both parts (e.g. index of the semaphore)
+/// to verify them.
+///
+/// Barriers recorded after semaphore wait are treated as chained with it
+/// whatever stages they wait for. Synchronization names stages of the signaling submission
+/// as source stages of such barriers.
+///
+/// Returns the first hazard found.
+pub fn verify<T>(chains: &Chains<SyncData<T, T>>) -> Result<(), Hazard>
+where
+    T: Eq + Hash,
+{
+    verify_skipping(chains, |_| false)
+}
+
+/// Check `Chains` like `verify` does with main commands of some conditional submissions skipped.
+/// Main commands of conditional submissions for which `skip` returns `true` are not simulated.
+/// Their guards are simulated only if those must be recorded even if commands are skipped.
+/// Other submissions are never skipped.
+pub fn verify_skipping<T, F>(chains: &Chains<SyncData<T, T>>, skip: F) -> Result<(), Hazard>
+where
+    T: Eq + Hash,
+    F: Fn(SubmissionId) -> bool,
+{
+    let mut queues: Vec<QueueId> = chains
+        .schedule
+        .iter()
+        .flat_map(|family| family.iter())
+        .map(|queue| queue.id())
+        .collect();
+    queues.sort();
+    let queue_indices: FnvHashMap<QueueId, usize> = queues
+        .iter()
+        .enumerate()
+        .map(|(index, &qid)| (qid, index))
+        .collect();
+
+    let mut submissions: Vec<(usize, SubmissionId)> = chains
+        .schedule
+        .iter()
+        .flat_map(|family| family.iter())
+        .flat_map(|queue| queue.iter())
+        .map(|(sid, submission)| (submission.submit_order(), sid))
+        .collect();
+    submissions.sort();
+
+    // Sparse binding isn't ordered with its queue. Each one gets own clock.
+    let mut bind_queues: FnvHashMap<SubmissionId, usize> = FnvHashMap::default();
+    for &(_, sid) in &submissions {
+        if chains.schedule[sid].kind() == PassKind::SparseBind {
+            let index = queues.len() + bind_queues.len();
+            bind_queues.insert(sid, index);
+        }
+    }
+    let clock_count = queues.len() + bind_queues.len();
+
+    let mut simulator = Simulator {
+        clocks: vec![vec![0; clock_count]; clock_count],
+        signals: HashMap::new(),
+        events: FnvHashMap::default(),
+        buffers: Vec::new(),
+        buffer_indices: FnvHashMap::default(),
+        images: Vec::new(),
+        image_indices: FnvHashMap::default(),
+    };
+    simulator.add_chains(chains);
+
+    // Barriers hoisted to the beginning of the frame are recorded before any submission.
+    for &(_, sid) in submissions.iter().filter(|&&(_, sid)| sid.index() == 0) {
+        let frame_begin = &chains.schedule[sid].sync().frame_begin;
+        simulator.guard(sid, queue_indices[&sid.queue()], frame_begin)?;
+    }
+
+    for (_, sid) in submissions {
+        let submission = &chains.schedule[sid];
+        let queue = match bind_queues.get(&sid) {
+            Some(&queue) => queue,
+            None => queue_indices[&sid.queue()],
+        };
+        let sync = submission.sync();
+        let skipped = submission.is_conditional() && skip(sid);
+
+        if !skipped || sync.acquire.must_record_even_if_skipped {
+            simulator.guard(sid, queue, &sync.acquire)?;
+        }
+        if skipped {
+            if sync.release.must_record_even_if_skipped {
+                simulator.guard(sid, queue, &sync.release)?;
+            }
+            continue;
+        }
+
+        let point = simulator.step(queue);
+        let clock = simulator.clocks[queue].clone();
+        if submission.kind() == PassKind::SparseBind {
+            simulator.bind(sid, point, &clock)?;
+        }
+        let buffers = submission
+            .buffers()
+            .map(|(&id, &link)| (id, None, link))
+            .chain(
+                submission
+                    .buffer_parts()
+                    .map(|(&id, &(part, link))| (id, Some(part), link)),
+            );
+        for (id, part, link) in buffers {
+            let index = simulator.buffer_indices[&(id, part)];
+            simulator.buffers[index]
+                .2
+                .access(sid, point, &clock, link)
+                .map_err(|conflict| (id, sid, conflict))?;
+        }
+        let images = submission
+            .images()
+            .map(|(&id, &link)| (id, Track::Whole, link))
+            .chain(
+                submission
+                    .stencils()
+                    .map(|(&id, &link)| (id, Track::Stencil, link)),
+            )
+            .chain(submission.parts().flat_map(|(&id, links)| {
+                links
+                    .iter()
+                    .map(move |&(part, link)| (id, Track::Part(part), link))
+            }));
+        for (id, track, link) in images {
+            let index = simulator.image_index(id, track);
+            simulator.images[index]
+                .2
+                .access(sid, point, &clock, link)
+                .map_err(|conflict| (id, sid, conflict))?;
+        }
+
+        simulator.guard(sid, queue, &sync.release)?;
+    }
+
+    simulator.finish()
+}
+
+/// Point of the execution.
+#[derive(Clone, Copy, Debug)]
+struct Point {
+    queue: usize,
+    time: usize,
+}
+
+impl Point {
+    /// Check if point happens before execution with specified clock.
+    fn before(&self, clock: &[usize]) -> bool {
+        clock[self.queue] >= self.time
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Owner {
+    Family(QueueFamilyId),
+    Transferring(QueueFamilyId),
+}
+
+/// Hazard found for the resource.
+#[derive(Clone, Copy, Debug)]
+struct Conflict {
+    kind: HazardKind,
+    other: Option<SubmissionId>,
+}
+
+impl Conflict {
+    fn new(kind: HazardKind, other: Option<SubmissionId>) -> Self {
+        Conflict { kind, other }
+    }
+}
+
+/// Operations at `stages` after `point` are ordered after the operation.
+#[derive(Clone, Debug)]
+struct Dependency<R: Resource> {
+    point: Point,
+
+    /// Second scope of the dependency. Includes logically later stages.
+    stages: PipelineStage,
+
+    /// Write performed by the operation is made available.
+    available: bool,
+
+    /// Accesses the write is made visible to.
+    visible: R::Access,
+
+    /// Dependency is created by semaphore wait.
+    semaphore: bool,
+}
+
+/// Access, layout transition, ownership transfer or sparse binding.
+#[derive(Clone, Debug)]
+struct Operation<R: Resource> {
+    /// Submission that performed the operation.
+    /// `None` if the operation is performed outside of the schedule.
+    sid: Option<SubmissionId>,
+
+    /// Point and stages of commands that performed the operation.
+    /// Barriers are not performed at stages and are ordered only by their dependencies.
+    commands: Option<(Point, PipelineStage)>,
+
+    /// Link of the access. Accesses of the same link don't conflict.
+    link: Option<usize>,
+
+    access: R::Access,
+    write: bool,
+    range: Option<R::Range>,
+    dependencies: Vec<Dependency<R>>,
+}
+
+/// Simulated memory of the resource tracked by the chain.
+#[derive(Clone, Debug)]
+struct Simulated<'a, R: Resource + 'a> {
+    chain: &'a Chain<R>,
+    layout: R::Layout,
+    owner: Owner,
+
+    /// Operations later operations may conflict with.
+    /// Operations overwritten by ordered writes are dropped.
+    operations: Vec<Operation<R>>,
+
+    /// Last submission that accessed the resource.
+    last: Option<SubmissionId>,
+}
+
+impl<'a, R> Simulated<'a, R>
+where
+    R: Resource,
+{
+    fn new(chain: &'a Chain<R>) -> Self {
+        let link = chain.link(0);
+        let mut operations = Vec::new();
+        let (layout, owner) = if chain.info().import.is_some() {
+            // External family has released the resource and transitioned it to the first layout.
+            (link.state().layout, Owner::Transferring(link.family()))
+        } else {
+            (
+                chain.initial_state().state.layout,
+                Owner::Family(link.family()),
+            )
+        };
+        if chain.info().present {
+            // Presentation engine reads the image until acquisition is waited.
+            operations.push(Operation {
+                sid: None,
+                commands: None,
+                link: None,
+                access: R::Access::none(),
+                write: false,
+                range: None,
+                dependencies: Vec::new(),
+            });
+        }
+        Simulated {
+            chain,
+            layout,
+            owner,
+            operations,
+            last: None,
+        }
+    }
+
+    /// Check that conflicting operations are ordered before access at `stages`
+    /// performed on the queue after `clock`. `None` queue stands for the host.
+    fn check(
+        &self,
+        queue: Option<usize>,
+        link: Option<usize>,
+        stages: PipelineStage,
+        access: R::Access,
+        range: Option<&R::Range>,
+        clock: &[usize],
+    ) -> Result<(), Conflict> {
+        let write = access.is_write();
+        let read = access.is_read();
+        for operation in &self.operations {
+            if (link.is_some() && operation.link == link)
+                || !(operation.write || write)
+                || !overlaps(operation.range.as_ref(), range)
+            {
+                continue;
+            }
+            let ordered = operation.dependencies.iter().any(|dependency| {
+                queue.is_none_or(|queue| dependency.point.queue == queue)
+                    && dependency.point.before(clock)
+                    && dependency.stages.contains(stages)
+                    && (!operation.write
+                        || (dependency.available
+                            && (!read || dependency.visible & access == access)))
+            });
+            // Host waits for fences. Binding doesn't access memory and needs nothing else.
+            let fenced = queue.is_none()
+                && operation.access == R::Access::none()
+                && operation.commands.is_some_and(|(at, _)| at.before(clock));
+            if !ordered && !fenced {
+                let kind = match (operation.write, read) {
+                    (true, true) => HazardKind::ReadAfterWrite,
+                    (true, false) => HazardKind::WriteAfterWrite,
+                    (false, _) => HazardKind::WriteAfterRead,
+                };
+                return Err(Conflict::new(kind, operation.sid));
+            }
+        }
+        Ok(())
+    }
+
+    /// Drop operations overwritten by the write of `range`.
+    fn overwrite(&mut self, range: Option<&R::Range>, link: Option<usize>) {
+        self.operations.retain(|operation| {
+            (link.is_some() && operation.link == link)
+                || !covers(range, operation.range.as_ref())
+        });
+    }
+
+    /// Access the resource by commands of the submission associated with the link.
+    fn access(
+        &mut self,
+        sid: SubmissionId,
+        point: Point,
+        clock: &[usize],
+        link: usize,
+    ) -> Result<(), Conflict> {
+        let chain = self.chain;
+        let state = chain.link(link).queue_state(sid.queue());
+        let range = chain.link(link).range();
+
+        // Concurrent resource may be accessed by any family unless external family owns it.
+        let owned = match (chain.sharing(), self.owner) {
+            (SharingMode::Exclusive, owner) => owner == Owner::Family(sid.family()),
+            (SharingMode::Concurrent, Owner::Family(_)) => true,
+            (SharingMode::Concurrent, Owner::Transferring(_)) => false,
+        };
+        if !owned {
+            return Err(Conflict::new(HazardKind::Ownership, self.last));
+        }
+        self.check(
+            Some(point.queue),
+            Some(link),
+            state.stages,
+            state.access,
+            range,
+            clock,
+        )?;
+        if state.layout != self.layout {
+            return Err(Conflict::new(HazardKind::LayoutMismatch, self.last));
+        }
+
+        let write = state.access.is_write();
+        if write {
+            self.overwrite(range, Some(link));
+        }
+        self.operations.push(Operation {
+            sid: Some(sid),
+            commands: Some((point, state.stages)),
+            link: Some(link),
+            access: state.access,
+            write,
+            range: range.cloned(),
+            dependencies: Vec::new(),
+        });
+        self.last = Some(sid);
+        if chain.sharing() == SharingMode::Concurrent {
+            // The family that accessed concurrent resource last releases it to external family.
+            self.owner = Owner::Family(sid.family());
+        }
+        Ok(())
+    }
+
+    /// Perform barrier at `point` of the submission with execution scopes of `stages`.
+    /// Operations ordered before `source` clock at stages of the first scope
+    /// get dependency on the second scope.
+    /// `barrier` is the memory barrier of the resource if any.
+    fn barrier(
+        &mut self,
+        sid: SubmissionId,
+        point: Point,
+        source: &[usize],
+        stages: Range<PipelineStage>,
+        barrier: Option<&Barrier<R>>,
+    ) -> Result<(), Conflict> {
+        let first = earlier_stages(stages.start);
+        let second = later_stages(stages.end);
+        let (transfer, acquire) = match barrier.and_then(|barrier| barrier.queues.as_ref()) {
+            Some(queues) => (true, queues.start.family() != sid.family()),
+            None => (false, false),
+        };
+        let discard = barrier.is_some_and(|barrier| {
+            !transfer
+                && barrier.states.start.access == R::Access::none()
+                && barrier.states.start.layout == R::Layout::discard_content()
+        });
+        let write = barrier.is_some_and(|barrier| {
+            transfer || barrier.states.start.layout != barrier.states.end.layout
+        });
+
+        for operation in &mut self.operations {
+            let memory = barrier.and_then(|barrier| {
+                if overlaps(barrier.range.as_ref(), operation.range.as_ref()) {
+                    Some(barrier)
+                } else {
+                    None
+                }
+            });
+
+            let direct = operation.commands.is_some_and(|(at, stages)| {
+                at.queue == point.queue && at.before(source) && first.contains(stages)
+            });
+            let (chained, chained_available) = operation
+                .dependencies
+                .iter()
+                .filter(|dependency| {
+                    dependency.point.queue == point.queue
+                        && dependency.point.before(source)
+                        && (dependency.semaphore || dependency.stages.intersects(first))
+                })
+                .fold((false, false), |(_, available), dependency| {
+                    (true, available || dependency.available)
+                });
+
+            if !direct && !chained {
+                if write && memory.is_some() {
+                    let kind = if operation.write {
+                        HazardKind::WriteAfterWrite
+                    } else {
+                        HazardKind::WriteAfterRead
+                    };
+                    return Err(Conflict::new(kind, operation.sid));
+                }
+                continue;
+            }
+
+            // Discarded content doesn't need to be made available.
+            let available = !operation.write
+                || chained_available
+                || memory.is_some_and(|barrier| {
+                    discard || barrier.states.start.access & operation.access == operation.access
+                });
+            if write && memory.is_some() && !available {
+                return Err(Conflict::new(HazardKind::WriteAfterWrite, operation.sid));
+            }
+            let visible = match memory {
+                Some(barrier) if available => barrier.states.end.access,
+                _ => R::Access::none(),
+            };
+            operation.dependencies.push(Dependency {
+                point,
+                stages: second,
+                available,
+                visible,
+                semaphore: false,
+            });
+        }
+
+        let barrier = match barrier {
+            Some(barrier) => barrier,
+            None => return Ok(()),
+        };
+        let layouts = &barrier.states;
+        if acquire {
+            // Layout was transitioned by release barrier.
+            if self.owner != Owner::Transferring(sid.family()) {
+                return Err(Conflict::new(HazardKind::Ownership, self.last));
+            }
+            if layouts.end.layout != self.layout {
+                return Err(Conflict::new(HazardKind::LayoutMismatch, self.last));
+            }
+            self.owner = Owner::Family(sid.family());
+        } else {
+            if !discard && layouts.start.layout != self.layout {
+                return Err(Conflict::new(HazardKind::LayoutMismatch, self.last));
+            }
+            if let Some(ref queues) = barrier.queues {
+                // Any family may release concurrent resource, even one which commands
+                // were skipped and didn't access it.
+                let owned = match (self.chain.sharing(), self.owner) {
+                    (SharingMode::Concurrent, Owner::Family(_)) => true,
+                    (_, owner) => owner == Owner::Family(sid.family()),
+                };
+                if !owned {
+                    return Err(Conflict::new(HazardKind::Ownership, self.last));
+                }
+                self.owner = Owner::Transferring(queues.end.family());
+            }
+        }
+
+        if write {
+            self.layout = layouts.end.layout;
+            self.overwrite(barrier.range.as_ref(), None);
+            // Released resource is accessed only after semaphore.
+            self.operations.push(Operation {
+                sid: Some(sid),
+                commands: None,
+                link: None,
+                access: R::Access::none(),
+                write: true,
+                range: barrier.range.clone(),
+                dependencies: vec![Dependency {
+                    point,
+                    stages: if transfer && !acquire {
+                        PipelineStage::empty()
+                    } else {
+                        second
+                    },
+                    available: true,
+                    visible: layouts.end.access,
+                    semaphore: false,
+                }],
+            });
+            self.last = Some(sid);
+        }
+        Ok(())
+    }
+
+    /// Order operations that happen before semaphore signal at `signal` clock
+    /// before operations at `stages` after `point`.
+    fn wait(&mut self, point: Point, signal: &[usize], stages: PipelineStage) {
+        let stages = later_stages(stages);
+        for operation in &mut self.operations {
+            let signaled = operation
+                .commands
+                .is_some_and(|(at, _)| at.before(signal))
+                || operation
+                    .dependencies
+                    .iter()
+                    .any(|dependency| dependency.point.before(signal));
+            if signaled {
+                operation.dependencies.push(Dependency {
+                    point,
+                    stages,
+                    available: true,
+                    visible: R::Access::all(),
+                    semaphore: true,
+                });
+            }
+        }
+    }
+
+    /// Order reads by presentation engine before operations at `stages` after `point`.
+    fn acquired(&mut self, point: Point, stages: PipelineStage) {
+        let stages = later_stages(stages);
+        for operation in &mut self.operations {
+            if operation.sid.is_none() {
+                operation.dependencies.push(Dependency {
+                    point,
+                    stages,
+                    available: true,
+                    visible: R::Access::all(),
+                    semaphore: true,
+                });
+            }
+        }
+    }
+
+    /// Bind memory to the resource by sparse binding submission.
+    /// Binding is ordered only by semaphores.
+    /// Presentable images are never bound sparsely, so reads by presentation engine are ignored.
+    fn bind(&mut self, sid: SubmissionId, point: Point, clock: &[usize]) -> Result<(), Conflict> {
+        for operation in &self.operations {
+            let ordered = operation.sid.is_none()
+                || operation.dependencies.iter().any(|dependency| {
+                    dependency.point.queue == point.queue && dependency.point.before(clock)
+                });
+            if !ordered {
+                let kind = if operation.write {
+                    HazardKind::WriteAfterWrite
+                } else {
+                    HazardKind::WriteAfterRead
+                };
+                return Err(Conflict::new(kind, operation.sid));
+            }
+        }
+        self.operations.clear();
+        self.operations.push(Operation {
+            sid: Some(sid),
+            commands: Some((point, PipelineStage::empty())),
+            link: None,
+            access: R::Access::none(),
+            write: true,
+            range: None,
+            dependencies: Vec::new(),
+        });
+        Ok(())
+    }
+
+    /// Check state of the resource after the schedule.
+    /// Host waits for all queues with `end` clock.
+    fn finish(&self, end: &[usize]) -> Result<(), Conflict> {
+        let info = self.chain.info();
+        if let Some(export) = info.export {
+            if self.owner != Owner::Transferring(export.family) {
+                return Err(Conflict::new(HazardKind::Ownership, self.last));
+            }
+        } else if info.host_read_after {
+            self.check(
+                None,
+                None,
+                PipelineStage::HOST,
+                R::Access::host_read(),
+                None,
+                end,
+            )?;
+        }
+        if self.layout != self.chain.final_state().state.layout {
+            return Err(Conflict::new(HazardKind::LayoutMismatch, self.last));
+        }
+        Ok(())
+    }
+}
+
+/// Chain of the image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Track {
+    /// Whole image or depth aspect if stencil is tracked separately.
+    Whole,
+    Stencil,
+    Part(usize),
+}
+
+struct Simulator<'a, T: 'a> {
+    /// Vector clock of each queue.
+    clocks: Vec<Vec<usize>>,
+
+    /// Clocks of semaphore signals with signaled values.
+    signals: HashMap<&'a T, Vec<(u64, Vec<usize>)>>,
+
+    /// Clocks of event sets.
+    events: FnvHashMap<usize, Vec<usize>>,
+
+    /// Simulated chains of buffers sorted by id and then by part.
+    buffers: Vec<(Id<Buffer>, Option<usize>, Simulated<'a, Buffer>)>,
+    buffer_indices: FnvHashMap<(Id<Buffer>, Option<usize>), usize>,
+
+    /// Simulated chains of images sorted by id.
+    images: Vec<(Id<Image>, Track, Simulated<'a, Image>)>,
+    image_indices: FnvHashMap<(Id<Image>, Track), usize>,
+}
+
+impl<'a, T> Simulator<'a, T>
+where
+    T: Eq + Hash,
+{
+    fn add_chains<S>(&mut self, chains: &'a Chains<S>) {
+        self.buffers = chains
+            .buffers
+            .iter()
+            .map(|(&id, chain)| (id, None, chain))
+            .chain(chains.buffer_parts.iter().flat_map(|(&id, parts)| {
+                parts
+                    .iter()
+                    .enumerate()
+                    .map(move |(part, chain)| (id, Some(part), chain))
+            }))
+            .map(|(id, part, chain)| (id, part, Simulated::new(chain)))
+            .collect();
+        self.buffers.sort_by_key(|&(id, part, _)| (id, part));
+        self.buffer_indices = self
+            .buffers
+            .iter()
+            .enumerate()
+            .map(|(index, &(id, part, _))| ((id, part), index))
+            .collect();
+
+        self.images = chains
+            .images
+            .iter()
+            .map(|(&id, chain)| (id, Track::Whole, chain))
+            .chain(
+                chains
+                    .stencils
+                    .iter()
+                    .map(|(&id, chain)| (id, Track::Stencil, chain)),
+            )
+            .chain(chains.parts.iter().flat_map(|(&id, parts)| {
+                parts
+                    .iter()
+                    .enumerate()
+                    .map(move |(part, chain)| (id, Track::Part(part), chain))
+            }))
+            .map(|(id, track, chain)| (id, track, Simulated::new(chain)))
+            .collect();
+        self.images.sort_by_key(|&(id, track, _)| {
+            let track = match track {
+                Track::Whole => 0,
+                Track::Stencil => 1,
+                Track::Part(part) => 2 + part,
+            };
+            (id, track)
+        });
+        self.image_indices = self
+            .images
+            .iter()
+            .enumerate()
+            .map(|(index, &(id, track, _))| ((id, track), index))
+            .collect();
+    }
+
+    fn image_index(&self, id: Id<Image>, track: Track) -> usize {
+        self.image_indices[&(id, track)]
+    }
+
+    /// Advance clock of the queue.
+    fn step(&mut self, queue: usize) -> Point {
+        self.clocks[queue][queue] += 1;
+        Point {
+            queue,
+            time: self.clocks[queue][queue],
+        }
+    }
+
+    /// Merge clock into clock of the queue.
+    fn join(&mut self, queue: usize, clock: &[usize]) {
+        for (own, &other) in self.clocks[queue].iter_mut().zip(clock) {
+            *own = max(*own, other);
+        }
+    }
+
+    /// Execute guard of the submission.
+    /// Commands are executed in the order `walk::SubmissionInfo` records them.
+    fn guard(
+        &mut self,
+        sid: SubmissionId,
+        queue: usize,
+        guard: &'a Guard<T, T>,
+    ) -> Result<(), Hazard> {
+        let mut waits = Vec::new();
+        for wait in &guard.wait {
+            let clock = self.signals.get(wait.semaphore()).and_then(|signals| {
+                signals
+                    .iter()
+                    .find(|&&(value, _)| value >= wait.value())
+                    .map(|(_, clock)| clock.clone())
+            });
+            match clock {
+                Some(clock) => {
+                    self.join(queue, &clock);
+                    waits.push((clock, wait.stage()));
+                }
+                None => return Err(Hazard::Unsignaled { submission: sid }),
+            }
+        }
+
+        let point = self.step(queue);
+        let clock = self.clocks[queue].clone();
+        for (signal, stages) in waits {
+            for &mut (_, _, ref mut simulated) in &mut self.buffers {
+                simulated.wait(point, &signal, stages);
+            }
+            for &mut (_, _, ref mut simulated) in &mut self.images {
+                simulated.wait(point, &signal, stages);
+            }
+        }
+        for wait in &guard.wait_acquired {
+            let id = *wait.semaphore();
+            if let Some(&index) = self.image_indices.get(&(id, Track::Whole)) {
+                self.images[index].2.acquired(point, wait.stage());
+            }
+        }
+
+        for wait in &guard.wait_events {
+            let source = match self.events.get(&wait.event) {
+                Some(source) => source.clone(),
+                None => return Err(Hazard::Unsignaled { submission: sid }),
+            };
+            let stages = wait.stages.clone();
+            let barriers = (&wait.buffers, &wait.images, &wait.parts[..], &wait.buffer_parts[..]);
+            self.barriers(sid, point, &source, stages, barriers)?;
+        }
+        if let Some(pipeline) = guard.pipeline_barrier() {
+            let stages = pipeline.stages;
+            let barriers = (
+                &guard.buffers,
+                &guard.images,
+                &guard.parts[..],
+                &guard.buffer_parts[..],
+            );
+            self.barriers(sid, point, &clock, stages, barriers)?;
+        }
+
+        for set in &guard.set_events {
+            self.events.insert(set.event, clock.clone());
+        }
+        for signal in &guard.signal {
+            self.signals
+                .entry(signal.semaphore())
+                .or_default()
+                .push((signal.value(), clock.clone()));
+        }
+        Ok(())
+    }
+
+    /// Perform barriers of one command.
+    /// Execution dependency applies to all resources, memory dependency only to those with barrier.
+    fn barriers(
+        &mut self,
+        sid: SubmissionId,
+        point: Point,
+        source: &[usize],
+        stages: Range<PipelineStage>,
+        (buffers, images, parts, buffer_parts): GuardBarriers,
+    ) -> Result<(), Hazard> {
+        for &mut (id, part, ref mut simulated) in &mut self.buffers {
+            let chain = simulated.chain;
+            let barrier = match part {
+                None => buffers.get(&id),
+                Some(_) => buffer_parts
+                    .iter()
+                    .find(|&&(other, ref barrier)| {
+                        other == id && overlaps(barrier.range.as_ref(), chain.range())
+                    })
+                    .map(|(_, barrier)| barrier),
+            };
+            simulated
+                .barrier(sid, point, source, stages.clone(), barrier)
+                .map_err(|conflict| (id, sid, conflict))?;
+        }
+        let separate: Vec<Id<Image>> = self
+            .images
+            .iter()
+            .filter(|&&(_, track, _)| track == Track::Stencil)
+            .map(|&(id, _, _)| id)
+            .collect();
+        for &mut (id, track, ref mut simulated) in &mut self.images {
+            let chain = simulated.chain;
+            let covers = |barrier: &&Barrier<Image>, aspect| {
+                barrier
+                    .range
+                    .as_ref()
+                    .is_none_or(|range: &SubresourceRange| range.aspects.contains(aspect))
+            };
+            let barrier = match track {
+                Track::Whole if separate.contains(&id) => images
+                    .get(&id)
+                    .and_then(|barrier| some_if(barrier, covers(&barrier, Aspects::DEPTH))),
+                Track::Whole => images.get(&id),
+                Track::Stencil => images
+                    .get(&id)
+                    .and_then(|barrier| some_if(barrier, covers(&barrier, Aspects::STENCIL))),
+                Track::Part(_) => parts
+                    .iter()
+                    .find(|&&(other, ref barrier)| {
+                        other == id && overlaps(barrier.range.as_ref(), chain.range())
+                    })
+                    .map(|(_, barrier)| barrier),
+            };
+            simulated
+                .barrier(sid, point, source, stages.clone(), barrier)
+                .map_err(|conflict| (id, sid, conflict))?;
+        }
+        Ok(())
+    }
+
+    /// Bind memory to resources bound by the sparse binding submission.
+    fn bind(&mut self, sid: SubmissionId, point: Point, clock: &[usize]) -> Result<(), Hazard> {
+        let bound = |chain: &Chain<_>| chain.binds().iter().any(|bind| bind.sid == sid);
+        for &mut (id, _, ref mut simulated) in &mut self.buffers {
+            if bound(simulated.chain) {
+                simulated
+                    .bind(sid, point, clock)
+                    .map_err(|conflict| (id, sid, conflict))?;
+            }
+        }
+        for &mut (id, _, ref mut simulated) in &mut self.images {
+            if simulated.chain.binds().iter().any(|bind| bind.sid == sid) {
+                simulated
+                    .bind(sid, point, clock)
+                    .map_err(|conflict| (id, sid, conflict))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Check states of resources after the schedule.
+    fn finish(&self) -> Result<(), Hazard> {
+        let end: Vec<usize> = self
+            .clocks
+            .iter()
+            .enumerate()
+            .map(|(queue, clock)| clock[queue])
+            .collect();
+        for &(id, _, ref simulated) in &self.buffers {
+            if let Some(sid) = simulated.last {
+                simulated
+                    .finish(&end)
+                    .map_err(|conflict| (id, sid, conflict))?;
+            }
+        }
+        for &(id, _, ref simulated) in &self.images {
+            if let Some(sid) = simulated.last {
+                simulated
+                    .finish(&end)
+                    .map_err(|conflict| (id, sid, conflict))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn some_if<T>(value: T, condition: bool) -> Option<T> {
+    if condition {
+        Some(value)
+    } else {
+        None
+    }
+}
+
+fn overlaps<R: ResourceRange>(left: Option<&R>, right: Option<&R>) -> bool {
+    match (left, right) {
+        (Some(left), Some(right)) => left.overlaps(right),
+        _ => true,
+    }
+}
+
+/// Check if `outer` range contains `inner` one.
+fn covers<R: ResourceRange>(outer: Option<&R>, inner: Option<&R>) -> bool {
+    match (outer, inner) {
+        (None, _) => true,
+        (Some(_), None) => false,
+        (Some(outer), Some(inner)) => outer.union(inner) == *outer,
+    }
+}
+
+/// Stages of graphics pipeline in logical order.
+const GRAPHICS_STAGES: &[PipelineStage] = &[
+    PipelineStage::TOP_OF_PIPE,
+    PipelineStage::DRAW_INDIRECT,
+    PipelineStage::VERTEX_INPUT,
+    PipelineStage::VERTEX_SHADER,
+    PipelineStage::HULL_SHADER,
+    PipelineStage::DOMAIN_SHADER,
+    PipelineStage::GEOMETRY_SHADER,
+    PipelineStage::EARLY_FRAGMENT_TESTS,
+    PipelineStage::FRAGMENT_SHADER,
+    PipelineStage::LATE_FRAGMENT_TESTS,
+    PipelineStage::COLOR_ATTACHMENT_OUTPUT,
+    PipelineStage::BOTTOM_OF_PIPE,
+];
+
+/// Stages of compute pipeline in logical order.
+const COMPUTE_STAGES: &[PipelineStage] = &[
+    PipelineStage::TOP_OF_PIPE,
+    PipelineStage::DRAW_INDIRECT,
+    PipelineStage::COMPUTE_SHADER,
+    PipelineStage::BOTTOM_OF_PIPE,
+];
+
+/// Stages of transfer commands in logical order.
+const TRANSFER_STAGES: &[PipelineStage] = &[
+    PipelineStage::TOP_OF_PIPE,
+    PipelineStage::TRANSFER,
+    PipelineStage::BOTTOM_OF_PIPE,
+];
+
+/// Include stages logically earlier than any of `stages`.
+/// First scope of dependency is expanded this way.
+fn earlier_stages(stages: PipelineStage) -> PipelineStage {
+    let mut expanded = stages;
+    for pipeline in &[GRAPHICS_STAGES, COMPUTE_STAGES, TRANSFER_STAGES] {
+        if let Some(last) = pipeline.iter().rposition(|&stage| stages.contains(stage)) {
+            for &stage in &pipeline[..last + 1] {
+                expanded |= stage;
+            }
+        }
+    }
+    expanded
+}
+
+/// Include stages logically later than any of `stages`.
+/// Second scope of dependency is expanded this way.
+fn later_stages(stages: PipelineStage) -> PipelineStage {
+    let mut expanded = stages;
+    for pipeline in &[GRAPHICS_STAGES, COMPUTE_STAGES, TRANSFER_STAGES] {
+        if let Some(first) = pipeline.iter().position(|&stage| stages.contains(stage)) {
+            for &stage in &pipeline[first..] {
+                expanded |= stage;
+            }
+        }
+    }
+    expanded
+}
+
+/// Parameters of generated pass graphs.
+#[derive(Clone, Copy, Debug)]
+pub struct GraphParams {
+    /// Maximum number of passes.
+    pub passes: usize,
+
+    /// Maximum number of buffers.
+    pub buffers: u32,
+
+    /// Maximum number of images.
+    pub images: u32,
+
+    /// Maximum number of queue families.
+    pub families: usize,
+
+    /// Maximum number of queues of each family.
+    pub queues: usize,
+}
+
+impl Default for GraphParams {
+    fn default() -> Self {
+        GraphParams {
+            passes: 20,
+            buffers: 8,
+            images: 8,
+            families: 3,
+            queues: 3,
+        }
+    }
+}
+
+/// Randomly generated pass graph with resources and queue capabilities.
+#[derive(Clone, Debug)]
+pub struct RandomGraph {
+    /// Descriptions of passes.
+    pub passes: Vec<Pass>,
+
+    /// Properties of resources used by passes.
+    pub resources: Resources,
+
+    /// Number of queues of each family. Index is id of the family.
+    pub queues: Vec<usize>,
+
+    /// Capability of each family. Index is id of the family.
+    pub capabilities: Vec<Capability>,
+
+    /// Options to synchronize the graph with.
+    pub options: SyncOptions,
+}
+
+impl RandomGraph {
+    /// Generate random graph.
+    ///
+    /// Passes access random resources in random read and write states supported by their
+    /// families. Some passes may be executed by one of two families, some bind memory sparsely,
+    /// some are conditional.
+    /// Resources are shared concurrently, accessed by host, presented, imported from
+    /// or exported to a family outside of the graph at random.
+    pub fn new<G: Rng>(rng: &mut G, params: GraphParams) -> Self {
+        let family_count = rng.gen_range(1, params.families + 1);
+        let queues: Vec<usize> = (0..family_count)
+            .map(|_| rng.gen_range(1, params.queues + 1))
+            .collect();
+        let capabilities: Vec<Capability> = (0..family_count)
+            .map(|index| match index {
+                // At least one family can execute any pass.
+                0 => Capability::Graphics,
+                _ => *rng
+                    .choose(&[
+                        Capability::Transfer,
+                        Capability::Compute,
+                        Capability::Graphics,
+                    ])
+                    .unwrap(),
+            })
+            .collect();
+        let external = QueueFamilyId(family_count);
+
+        let mut resources = Resources::new();
+        let buffer_count = rng.gen_range(0, params.buffers + 1);
+        let image_count = rng.gen_range(0, params.images + 1);
+        for index in 0..buffer_count {
+            let info = random_info(rng, external, false, State::indirect_read());
+            resources.add_buffer(Id::new(index), info);
+        }
+        for index in 0..image_count {
+            let state = State {
+                access: Access::none(),
+                layout: ImageLayout::General,
+                stages: PipelineStage::BOTTOM_OF_PIPE,
+            };
+            let info = random_info(rng, external, true, state);
+            resources.add_image(Id::new(index), info);
+        }
+
+        let pass_count = rng.gen_range(1, params.passes + 1);
+        let mut passes = Vec::new();
+        for index in 0..pass_count {
+            let id = PassId(index);
+            let family = rng.gen_range(0, family_count);
+            let mut capability = capabilities[family];
+            let bind = buffer_count > 0 && rng.gen_range(0, 16) == 0;
+            let mut builder = if bind {
+                let buffer = Id::new(rng.gen_range(0, buffer_count));
+                PassBuilder::sparse_bind(id, QueueFamilyId(family)).bind_buffer(buffer, None)
+            } else {
+                PassBuilder::new(id, QueueFamilyId(family))
+            };
+            if family_count > 1 && rng.gen_range(0, 4) == 0 {
+                let other = rng.gen_range(0, family_count);
+                if other != family {
+                    builder = builder.allow_family(QueueFamilyId(other));
+                    capability = min(capability, capabilities[other]);
+                }
+            }
+            for dependency in 0..index {
+                if rng.gen_range(0, 4) == 0 {
+                    builder = builder.depends_on(PassId(dependency));
+                }
+            }
+            if !bind {
+                for buffer in 0..buffer_count {
+                    if rng.gen_range(0, 3) == 0 {
+                        builder = random_buffer_access(rng, builder, Id::new(buffer), capability);
+                    }
+                }
+                for image in 0..image_count {
+                    if rng.gen_range(0, 3) == 0 {
+                        builder = random_image_access(rng, builder, Id::new(image), capability);
+                    }
+                }
+            }
+            if rng.gen_range(0, 4) == 0 {
+                builder = builder.conditional();
+            }
+            passes.push(builder.build().expect("Each resource is declared once"));
+        }
+
+        let options = SyncOptions {
+            optimize_semaphores: rng.gen_range(0, 4) != 0,
+            mode: if rng.gen_range(0, 2) == 0 {
+                SyncMode::BinarySemaphores
+            } else {
+                SyncMode::Timelines
+            },
+            split_barriers: if rng.gen_range(0, 2) == 0 {
+                Some(rng.gen_range(0, 3))
+            } else {
+                None
+            },
+            hoist_initial_transitions: rng.gen_range(0, 2) == 0,
+            fences: FenceStrategy::AllQueues,
+            standalone_transition_nodes: rng.gen_range(0, 2) == 0,
+        };
+
+        RandomGraph {
+            passes,
+            resources,
+            queues,
+            capabilities,
+            options,
+        }
+    }
+
+    /// Get maximum number of queues of the family.
+    pub fn max_queues(&self, family: QueueFamilyId) -> usize {
+        self.queues.get(family.0).cloned().unwrap_or(0)
+    }
+
+    /// Get capability of the family.
+    pub fn capability(&self, family: QueueFamilyId) -> Capability {
+        self.capabilities.get(family.0).cloned().unwrap_or(Capability::Transfer)
+    }
+
+    /// Collect and synchronize passes of the graph and `verify` the result.
+    /// It is verified again with commands of all conditional submissions skipped.
+    /// Semaphores are indices, so the same value is used for signal and wait.
+    ///
+    /// # Panics
+    ///
+    /// Panics if passes can't be collected. Generated passes always can.
+    pub fn run(&self) -> Result<Chains<SyncData<usize, usize>>, Hazard> {
+        let chains = collect_with_capabilities(
+            self.passes.clone(),
+            &self.resources,
+            |family| self.max_queues(family),
+            |family| self.capability(family),
+        ).expect("Generated passes can be collected");
+        let mut semaphores = 0;
+        let chains = sync_chains(chains, self.options.clone(), || {
+            let semaphore = semaphores;
+            semaphores += 1;
+            (semaphore, semaphore)
+        }).expect("Collected chains can be synchronized");
+        verify(&chains)?;
+        verify_skipping(&chains, |_| true)?;
+        Ok(chains)
+    }
+}
+
+/// Generate properties of the resource.
+/// `present` is ignored for buffers.
+fn random_info<G, R>(
+    rng: &mut G,
+    external: QueueFamilyId,
+    image: bool,
+    external_state: State<R>,
+) -> ResourceInfo<R>
+where
+    G: Rng,
+    R: Resource,
+{
+    let mut info = ResourceInfo::default();
+    if rng.gen_range(0, 4) == 0 {
+        info.sharing = SharingMode::Concurrent;
+    }
+    match rng.gen_range(0, 8) {
+        0 => info.host_write_before = true,
+        1 => info.host_read_after = true,
+        2 if image => info.present = true,
+        3 => {
+            info.import = Some(External {
+                family: external,
+                state: external_state,
+            })
+        }
+        4 => {
+            info.export = Some(External {
+                family: external,
+                state: external_state,
+            })
+        }
+        _ => {}
+    }
+    info
+}
+
+/// Declare random access to the buffer supported by the family.
+fn random_buffer_access<G: Rng>(
+    rng: &mut G,
+    builder: PassBuilder,
+    id: Id<Buffer>,
+    capability: Capability,
+) -> PassBuilder {
+    let shader = match capability {
+        Capability::Graphics => PipelineStage::FRAGMENT_SHADER,
+        _ => PipelineStage::COMPUTE_SHADER,
+    };
+    let accesses = match capability {
+        Capability::Transfer => 2,
+        Capability::Compute => 6,
+        Capability::Graphics => 8,
+    };
+    match rng.gen_range(0, accesses) {
+        0 => builder.transfer_buffer_src(id),
+        1 => builder.transfer_buffer_dst(id),
+        2 => builder.storage_buffer_read(id, shader),
+        3 => builder.storage_buffer_write(id, shader),
+        4 => builder.uniform_buffer(id, shader),
+        5 => builder.indirect_buffer(id),
+        6 => builder.vertex_buffer(id),
+        _ => builder.index_buffer(id),
+    }
+}
+
+/// Declare random access to the image supported by the family.
+fn random_image_access<G: Rng>(
+    rng: &mut G,
+    builder: PassBuilder,
+    id: Id<Image>,
+    capability: Capability,
+) -> PassBuilder {
+    let shader = match capability {
+        Capability::Graphics => PipelineStage::FRAGMENT_SHADER,
+        _ => PipelineStage::COMPUTE_SHADER,
+    };
+    let accesses = match capability {
+        Capability::Transfer => 2,
+        Capability::Compute => 6,
+        Capability::Graphics => 8,
+    };
+    match rng.gen_range(0, accesses) {
+        0 => builder.transfer_image_src(id),
+        1 => builder.transfer_image_dst(id),
+        2 => builder.sample_image(id, shader),
+        3 => builder.storage_image_read(id, shader),
+        4 => builder.storage_image_write(id, shader),
+        5 => builder.clear_image(id),
+        6 => builder.color_attachment(id),
+        _ => builder.input_attachment(id),
+    }
+}
@@ -0,0 +1,485 @@
+//!
+//! This module provides statistics of generated synchronization.
+//! Those allow to track synchronization overhead when passes change.
+//!
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use collections::FnvHashMap;
+use core::cmp::max;
+use core::fmt::{self, Display};
+use core::hash::Hash;
+use core::ops::AddAssign;
+use hal::pso::PipelineStage;
+
+use alias::collect_edges;
+use collect::Chains;
+use diff::GuardSide;
+use pass::PassId;
+use resource::{Access, Resource};
+use schedule::{QueueId, Schedule, SubmissionId};
+use sync::{Barrier, Guard, SyncData};
+
+/// Counts of synchronization commands.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SyncCounts {
+    /// Number of barriers including split ones.
+    pub barriers: usize,
+
+    /// Number of image barriers that change layout.
+    /// Ownership transfer performs transition once, so only release barrier is counted.
+    /// Or acquire barrier if resource is released by external family.
+    pub layout_transitions: usize,
+
+    /// Number of barriers between read-only states without layout transition
+    /// or ownership transfer. Those are only needed to make earlier writes visible
+    /// to more stages, so high count may indicate poorly declared stages.
+    pub read_read_barriers: usize,
+
+    /// Number of semaphore signals.
+    pub semaphore_signals: usize,
+
+    /// Number of semaphore waits.
+    /// Each wait is paired with a signal. Timeline signal may be waited more than once.
+    pub semaphore_waits: usize,
+
+    /// Number of queue family ownership transfers.
+    /// Counted by release barriers. Or acquire barriers for transfers from external families.
+    pub ownership_transfers: usize,
+}
+
+impl AddAssign for SyncCounts {
+    fn add_assign(&mut self, rhs: SyncCounts) {
+        self.barriers += rhs.barriers;
+        self.layout_transitions += rhs.layout_transitions;
+        self.read_read_barriers += rhs.read_read_barriers;
+        self.semaphore_signals += rhs.semaphore_signals;
+        self.semaphore_waits += rhs.semaphore_waits;
+        self.ownership_transfers += rhs.ownership_transfers;
+    }
+}
+
+/// Statistics of synchronization of one pass.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PassStats {
+    /// Id of the pass.
+    pub pass: PassId,
+
+    /// Submission of the pass.
+    pub sid: SubmissionId,
+
+    /// Counts of synchronization commands recorded with the submission.
+    pub counts: SyncCounts,
+
+    /// Union of stages at which the submission waits for semaphores.
+    /// `TOP_OF_PIPE` here blocks whole pipeline.
+    pub wait_stages: PipelineStage,
+}
+
+/// Statistics of synchronized schedule.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SyncStats {
+    /// Counts for whole schedule.
+    pub total: SyncCounts,
+
+    /// Counts for each queue. Sorted by queue id.
+    pub queues: Vec<(QueueId, SyncCounts)>,
+
+    /// Statistics for each pass. Sorted by pass id.
+    pub passes: Vec<PassStats>,
+
+    /// Number of submissions in the longest chain of dependent submissions.
+    /// Submissions depend on earlier submissions of the same queue
+    /// and on submissions of other queues that previously accessed the same resources.
+    pub longest_chain: usize,
+}
+
+impl SyncStats {
+    /// Gather statistics of synchronized `Chains`.
+    pub fn new<S, W>(chains: &Chains<SyncData<S, W>>) -> Self {
+        let mut passes = Vec::new();
+        let mut queues = Vec::new();
+        let mut total = SyncCounts::default();
+        for family in chains.schedule.iter() {
+            for queue in family {
+                let mut queue_counts = SyncCounts::default();
+                for (sid, submission) in queue.iter() {
+                    let sync = submission.sync();
+                    let mut counts = SyncCounts::default();
+                    let schedule = &chains.schedule;
+                    guard_counts(sid, &sync.acquire, schedule, &mut counts);
+                    guard_counts(sid, &sync.release, schedule, &mut counts);
+                    guard_counts(sid, &sync.frame_begin, schedule, &mut counts);
+                    let wait_stages = sync
+                        .acquire
+                        .wait
+                        .iter()
+                        .chain(&sync.release.wait)
+                        .fold(PipelineStage::empty(), |acc, wait| acc | wait.stage());
+                    passes.push(PassStats {
+                        pass: submission.pass(),
+                        sid,
+                        counts,
+                        wait_stages,
+                    });
+                    queue_counts += counts;
+                }
+                queues.push((queue.id(), queue_counts));
+                total += queue_counts;
+            }
+        }
+        queues.sort_by_key(|&(qid, _)| qid);
+        passes.sort_by_key(|stats| stats.pass);
+
+        SyncStats {
+            total,
+            queues,
+            passes,
+            longest_chain: longest_chain(chains),
+        }
+    }
+}
+
+impl<S, W> Chains<SyncData<S, W>> {
+    /// Gather statistics of generated synchronization.
+    pub fn stats(&self) -> SyncStats {
+        SyncStats::new(self)
+    }
+}
+
+/// Dependency between submissions expressed by semaphore.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SemaphoreDependency {
+    /// Submission that signals the semaphore.
+    pub signal: SubmissionId,
+
+    /// Side at which semaphore is signaled.
+    /// Signal at acquire side doesn't wait for main commands of the submission.
+    pub signal_side: GuardSide,
+
+    /// Submission that waits for the semaphore.
+    pub wait: SubmissionId,
+
+    /// Side at which semaphore is waited.
+    /// Wait at release side doesn't delay main commands of the submission.
+    pub wait_side: GuardSide,
+
+    /// Stage at which semaphore is waited.
+    pub stage: PipelineStage,
+}
+
+/// The longest path through submissions of the schedule.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CriticalPath {
+    /// Total cost of submissions on the path.
+    /// Execution of the schedule can't take less even with unlimited queues.
+    pub length: u64,
+
+    /// Total cost of all submissions, which is duration of fully serialized execution.
+    pub total_work: u64,
+
+    /// Ratio of `total_work` to `length`. `1.0` for schedule with no overlap between queues.
+    /// Also `1.0` if `length` is zero.
+    pub parallelism_ratio: f64,
+
+    /// Submissions which costs form the path, in order of execution.
+    /// Each one follows the previous one on its queue or waits for it by semaphores.
+    pub path: Vec<SubmissionId>,
+}
+
+impl<T> Chains<SyncData<T, T>>
+where
+    T: Eq + Hash,
+{
+    /// Find dependencies expressed by semaphores of the schedule.
+    /// Timeline wait depends on the submission that signals the smallest value it waits for.
+    /// Sorted by waiting submission and side, then by signaling submission and side.
+    pub fn semaphore_dependencies(&self) -> Vec<SemaphoreDependency> {
+        let mut signals: FnvHashMap<&T, Vec<(u64, SubmissionId, GuardSide)>> =
+            FnvHashMap::default();
+        for queue in self.schedule.iter().flat_map(|family| family.iter()) {
+            for (sid, submission) in queue.iter() {
+                let sync = submission.sync();
+                let sides = vec![
+                    (&sync.acquire, GuardSide::Acquire),
+                    (&sync.release, GuardSide::Release),
+                ];
+                for (guard, side) in sides {
+                    for signal in &guard.signal {
+                        signals
+                            .entry(signal.semaphore())
+                            .or_default()
+                            .push((signal.value(), sid, side));
+                    }
+                }
+            }
+        }
+        for signals in signals.values_mut() {
+            signals.sort();
+        }
+
+        let mut dependencies = Vec::new();
+        for queue in self.schedule.iter().flat_map(|family| family.iter()) {
+            for (sid, submission) in queue.iter() {
+                let sync = submission.sync();
+                let sides = vec![
+                    (&sync.acquire, GuardSide::Acquire),
+                    (&sync.release, GuardSide::Release),
+                ];
+                for (guard, side) in sides {
+                    for wait in &guard.wait {
+                        let signal = signals.get(wait.semaphore()).and_then(|signals| {
+                            signals.iter().find(|&&(value, _, _)| value >= wait.value())
+                        });
+                        if let Some(&(_, signal, signal_side)) = signal {
+                            dependencies.push(SemaphoreDependency {
+                                signal,
+                                signal_side,
+                                wait: sid,
+                                wait_side: side,
+                                stage: wait.stage(),
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        dependencies.sort_by_key(|dependency| {
+            (
+                dependency.wait,
+                dependency.wait_side,
+                dependency.signal,
+                dependency.signal_side,
+            )
+        });
+        dependencies
+    }
+
+    /// Find the critical path of the schedule with unit cost of each submission.
+    /// See `critical_path_with_costs`.
+    pub fn critical_path(&self) -> CriticalPath {
+        self.critical_path_with_costs(|_| 1)
+    }
+
+    /// Find the critical path of the schedule with cost of each submission given by `cost`.
+    ///
+    /// Main commands of the submission start after it is submitted by the queue,
+    /// which happens after the previous submission finishes, and after semaphores waited
+    /// at its acquire side are signaled. Submission finishes after its main commands
+    /// and semaphores waited at its release side. Stages are not considered.
+    pub fn critical_path_with_costs<F>(&self, cost: F) -> CriticalPath
+    where
+        F: Fn(SubmissionId) -> u64,
+    {
+        let mut waits: FnvHashMap<(SubmissionId, GuardSide), Vec<(SubmissionId, GuardSide)>> =
+            FnvHashMap::default();
+        for dependency in self.semaphore_dependencies() {
+            waits
+                .entry((dependency.wait, dependency.wait_side))
+                .or_default()
+                .push((dependency.signal, dependency.signal_side));
+        }
+
+        // Semaphores always point to submissions with greater submit order.
+        let mut submissions: Vec<_> = self
+            .schedule
+            .iter()
+            .flat_map(|family| family.iter())
+            .flat_map(|queue| queue.iter())
+            .map(|(sid, submission)| (submission.submit_order(), sid))
+            .collect();
+        submissions.sort();
+
+        // Time of each point along with the point it was reached from.
+        // Acquire point is the start of main commands and release point is the finish.
+        type Point = (SubmissionId, GuardSide);
+        let mut times: FnvHashMap<Point, (u64, Option<Point>)> = FnvHashMap::default();
+        let latest = |times: &FnvHashMap<Point, (u64, Option<Point>)>, time, points: &[Point]| {
+            points.iter().fold(time, |(time, from), &point| {
+                let other = times[&point].0;
+                if other > time {
+                    (other, Some(point))
+                } else {
+                    (time, from)
+                }
+            })
+        };
+        let mut total_work = 0;
+        let mut end: (u64, Option<Point>) = (0, None);
+        for &(_, sid) in &submissions {
+            let mut points = Vec::new();
+            if sid.index() > 0 {
+                points.push((SubmissionId::new(sid.queue(), sid.index() - 1), GuardSide::Release));
+            }
+            if let Some(signals) = waits.get(&(sid, GuardSide::Acquire)) {
+                points.extend(signals);
+            }
+            let start = latest(&times, (0, None), &points);
+            times.insert((sid, GuardSide::Acquire), start);
+
+            let cost = cost(sid);
+            total_work += cost;
+            let signals = waits.get(&(sid, GuardSide::Release)).map_or(&[][..], |s| &s[..]);
+            let finish = latest(&times, (start.0 + cost, Some((sid, GuardSide::Acquire))), signals);
+            times.insert((sid, GuardSide::Release), finish);
+            if end.1.is_none() || finish.0 > end.0 {
+                end = (finish.0, Some((sid, GuardSide::Release)));
+            }
+        }
+
+        // Submissions which main commands are passed through.
+        let mut path = Vec::new();
+        let mut point = end.1;
+        while let Some((sid, side)) = point {
+            let from = times[&(sid, side)].1;
+            if side == GuardSide::Release && from == Some((sid, GuardSide::Acquire)) {
+                path.push(sid);
+            }
+            point = from;
+        }
+        path.reverse();
+
+        let length = end.0;
+        CriticalPath {
+            length,
+            total_work,
+            parallelism_ratio: if length == 0 {
+                1.0
+            } else {
+                total_work as f64 / length as f64
+            },
+            path,
+        }
+    }
+}
+
+impl Display for SyncStats {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            fmt,
+            "{:<24} {:>8} {:>8} {:>8} {:>8} {:>8} {:>8}  wait stages",
+            "", "barriers", "layouts", "read", "signals", "waits", "owners"
+        )?;
+        let row = |fmt: &mut fmt::Formatter, name: String, counts: &SyncCounts| {
+            write!(
+                fmt,
+                "{:<24} {:>8} {:>8} {:>8} {:>8} {:>8} {:>8}",
+                name,
+                counts.barriers,
+                counts.layout_transitions,
+                counts.read_read_barriers,
+                counts.semaphore_signals,
+                counts.semaphore_waits,
+                counts.ownership_transfers
+            )
+        };
+        for stats in &self.passes {
+            row(fmt, format!("Pass {}", stats.pass.0), &stats.counts)?;
+            writeln!(fmt, "  {:?}", stats.wait_stages)?;
+        }
+        for &(qid, ref counts) in &self.queues {
+            let name = format!("Queue {}:{}", qid.family().0, qid.index());
+            row(fmt, name, counts)?;
+            writeln!(fmt)?;
+        }
+        row(fmt, "Total".to_string(), &self.total)?;
+        writeln!(fmt)?;
+        writeln!(fmt, "Longest chain: {} submissions", self.longest_chain)
+    }
+}
+
+fn guard_counts<S, W, T>(
+    sid: SubmissionId,
+    guard: &Guard<S, W>,
+    schedule: &Schedule<T>,
+    counts: &mut SyncCounts,
+) {
+    barrier_counts(sid, guard.buffers.values(), schedule, counts);
+    barrier_counts(sid, guard.images.values(), schedule, counts);
+    barrier_counts(sid, guard.parts.iter().map(|(_, b)| b), schedule, counts);
+    barrier_counts(sid, guard.buffer_parts.iter().map(|(_, b)| b), schedule, counts);
+    for wait in &guard.wait_events {
+        barrier_counts(sid, wait.buffers.values(), schedule, counts);
+        barrier_counts(sid, wait.images.values(), schedule, counts);
+        barrier_counts(sid, wait.parts.iter().map(|(_, b)| b), schedule, counts);
+        barrier_counts(sid, wait.buffer_parts.iter().map(|(_, b)| b), schedule, counts);
+    }
+    counts.semaphore_signals += guard.signal.len();
+    counts.semaphore_waits += guard.wait.len();
+}
+
+fn barrier_counts<'a, R, T, I>(
+    sid: SubmissionId,
+    barriers: I,
+    schedule: &Schedule<T>,
+    counts: &mut SyncCounts,
+) where
+    R: Resource + 'a,
+    I: IntoIterator<Item = &'a Barrier<R>>,
+{
+    for barrier in barriers {
+        let states = &barrier.states;
+        let release = barrier.queues.as_ref().is_none_or(|queues| {
+            let source = queues.start.family();
+            source == sid.family() || schedule.family(source).is_none()
+        });
+        counts.barriers += 1;
+        if release && states.start.layout != states.end.layout {
+            counts.layout_transitions += 1;
+        }
+        if barrier.queues.is_none()
+            && states.start.layout == states.end.layout
+            && !states.start.access.is_write()
+            && !states.end.access.is_write()
+        {
+            counts.read_read_barriers += 1;
+        }
+        if barrier.queues.is_some() && release {
+            counts.ownership_transfers += 1;
+        }
+    }
+}
+
+/// Find the number of submissions in the longest chain of dependent submissions.
+fn longest_chain<S>(chains: &Chains<S>) -> usize {
+    let mut edges = FnvHashMap::default();
+    collect_edges(chains.buffers.values(), &mut edges);
+    collect_edges(chains.images.values(), &mut edges);
+    collect_edges(chains.stencils.values(), &mut edges);
+    collect_edges(chains.parts.values().flatten(), &mut edges);
+    collect_edges(chains.buffer_parts.values().flatten(), &mut edges);
+
+    // Dependencies always point to submissions with greater submit order.
+    let mut submissions: Vec<_> = chains
+        .schedule
+        .iter()
+        .flat_map(|family| family.iter())
+        .flat_map(|queue| queue.iter())
+        .map(|(sid, submission)| (submission.submit_order(), sid))
+        .collect();
+    submissions.sort();
+
+    let mut lengths: FnvHashMap<SubmissionId, usize> = FnvHashMap::default();
+    let mut longest = 0;
+    for (_, sid) in submissions {
+        let length = lengths.get(&sid).cloned().unwrap_or(0) + 1;
+        longest = max(longest, length);
+
+        let mut next: Vec<_> = edges
+            .get(&sid.queue())
+            .into_iter()
+            .flat_map(|edges| edges.iter())
+            .filter(|&&(index, _)| index == sid.index())
+            .map(|&(_, next)| next)
+            .collect();
+        let following = SubmissionId::new(sid.queue(), sid.index() + 1);
+        if chains.schedule.submission(following).is_some() {
+            next.push(following);
+        }
+        for next in next {
+            let entry = lengths.entry(next).or_insert(0);
+            *entry = max(*entry, length);
+        }
+    }
+    longest
+}
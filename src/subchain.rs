@@ -0,0 +1,464 @@
+//!
+//! This module provides composition of pre-built sub-graphs into a parent graph.
+//! Sub-graph is synchronized on its own and inserted into the parent as a single pass node,
+//! which declares only boundary states of the resources the sub-graph imports and exports.
+//!
+
+use alloc::vec::Vec;
+use collections::FnvHashMap;
+use core::fmt::{self, Display};
+use core::ops::Range;
+#[cfg(feature = "std")]
+use std::error::Error;
+
+use chain::Chain;
+use collect::{collect, Chains, CollectError};
+use pass::{OrderingHint, Pass, PassBuilder, PassId, StateUsage};
+use resource::{
+    Access, Buffer, Id, IdMap, Image, Layout, LayoutPolicy, Resource, ResourceInfo, Resources,
+    State,
+};
+use schedule::{Schedule, Submission, SubmissionId};
+use sync::{
+    earliest, latest, sync_with_options, Barrier, Barriers, FenceStrategy, Guard, SyncData,
+    SyncOptions,
+};
+use Pick;
+
+/// Error that can occur when building or fitting `SubChain`.
+#[derive(Clone, Debug)]
+pub enum SubChainError {
+    /// Collection of chains of the sub-graph failed.
+    Collect(CollectError),
+
+    /// Sub-graph isn't scheduled on exactly one queue.
+    /// Its internal ordering is kept opaque to the parent by executing it
+    /// within single submission of the node.
+    QueueCount {
+        /// Number of queues the sub-graph is scheduled on.
+        queues: usize,
+    },
+
+    /// Boundary buffer isn't tracked as a whole by the sub-graph or by the node in the parent.
+    /// Either it's not used or tracked in parts.
+    UntrackedBuffer {
+        /// Id of the buffer.
+        id: Id<Buffer>,
+    },
+
+    /// Boundary image isn't tracked as a whole by the sub-graph or by the node in the parent.
+    /// Either it's not used, tracked in parts or by aspects.
+    UntrackedImage {
+        /// Id of the image.
+        id: Id<Image>,
+    },
+
+    /// Parent provides the image in layout other than the boundary one
+    /// under `LayoutPolicy::Strict`.
+    LayoutMismatch {
+        /// Id of the image.
+        id: Id<Image>,
+
+        /// State of the link of the node in the parent and the boundary state.
+        states: Range<State<Image>>,
+    },
+}
+
+impl Display for SubChainError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SubChainError::Collect(ref error) => write!(fmt, "Sub-graph: {}", error),
+            SubChainError::QueueCount { queues } => write!(
+                fmt,
+                "Sub-graph is scheduled on {} queues instead of one",
+                queues
+            ),
+            SubChainError::UntrackedBuffer { id } => {
+                write!(fmt, "Boundary buffer {:?} isn't tracked as a whole", id)
+            }
+            SubChainError::UntrackedImage { id } => {
+                write!(fmt, "Boundary image {:?} isn't tracked as a whole", id)
+            }
+            SubChainError::LayoutMismatch { id, ref states } => write!(
+                fmt,
+                "Boundary image {:?} is provided in layout {:?} instead of {:?}",
+                id, states.start.layout, states.end.layout
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for SubChainError {}
+
+/// Boundary of the sub-graph for one resource.
+#[derive(Clone, Copy, Debug)]
+struct Interface<R: Resource> {
+    state: State<R>,
+    import: bool,
+}
+
+/// Builder of `SubChain` from passes of the sub-graph and its boundary.
+///
+/// Boundary state is the state the parent provides the resource in before the node
+/// and expects it in after the node, whether the resource is imported, exported or both.
+/// Sub-graph transitions resources from and to boundary states itself.
+#[derive(Clone, Debug)]
+pub struct SubChainBuilder {
+    passes: Vec<Pass>,
+    resources: Resources,
+    buffers: FnvHashMap<Id<Buffer>, Interface<Buffer>>,
+    images: FnvHashMap<Id<Image>, Interface<Image>>,
+}
+
+impl SubChainBuilder {
+    /// Create builder for the sub-graph of passes.
+    /// Dependencies of the passes must be passes of the sub-graph.
+    /// Properties of boundary resources from `resources` are replaced by the boundary.
+    pub fn new(passes: Vec<Pass>, resources: &Resources) -> Self {
+        SubChainBuilder {
+            passes,
+            resources: resources.clone(),
+            buffers: FnvHashMap::default(),
+            images: FnvHashMap::default(),
+        }
+    }
+
+    /// Declare buffer which content the sub-graph consumes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffer is exported in another boundary state.
+    pub fn import_buffer(mut self, id: Id<Buffer>, state: State<Buffer>) -> Self {
+        declare(&mut self.buffers, id, state, true);
+        self
+    }
+
+    /// Declare buffer which content the sub-graph produces for the parent.
+    /// Buffer that is not imported is discarded by the parent before the node.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffer is imported in another boundary state.
+    pub fn export_buffer(mut self, id: Id<Buffer>, state: State<Buffer>) -> Self {
+        declare(&mut self.buffers, id, state, false);
+        self
+    }
+
+    /// Declare image which content the sub-graph consumes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the image is exported in another boundary state.
+    pub fn import_image(mut self, id: Id<Image>, state: State<Image>) -> Self {
+        declare(&mut self.images, id, state, true);
+        self
+    }
+
+    /// Declare image which content the sub-graph produces for the parent.
+    /// Image that is not imported is discarded by the parent before the node.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the image is imported in another boundary state.
+    pub fn export_image(mut self, id: Id<Image>, state: State<Image>) -> Self {
+        declare(&mut self.images, id, state, false);
+        self
+    }
+
+    /// Collect and synchronize the sub-graph on single queue,
+    /// and create the node with id `id` to insert into the parent.
+    ///
+    /// Node is executed on the family of the queue. Its cost is the sum of costs of the passes
+    /// and it requires the most capable family any of them requires.
+    /// Sub-graph signals no fences, except ones required for host reads.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SubChainError` if the sub-graph can't be collected,
+    /// isn't scheduled on single queue or doesn't track boundary resources as a whole.
+    pub fn build<F, S, W>(
+        self,
+        id: PassId,
+        new_semaphore: F,
+    ) -> Result<SubChain<S, W>, SubChainError>
+    where
+        F: FnMut() -> (S, W),
+        S: Clone,
+        W: Clone,
+    {
+        let SubChainBuilder {
+            passes,
+            mut resources,
+            buffers,
+            images,
+        } = self;
+        for (&id, interface) in &buffers {
+            bound(resources.buffers.entry(id).or_insert_with(ResourceInfo::default), interface);
+        }
+        for (&id, interface) in &images {
+            bound(resources.images.entry(id).or_insert_with(ResourceInfo::default), interface);
+        }
+
+        let cost = passes
+            .iter()
+            .fold(0u32, |acc, pass| acc.saturating_add(pass.cost.max(1)));
+        let capability = passes.iter().filter_map(Pass::required_capability).max();
+        let side_effects = passes.iter().any(|pass| pass.side_effects);
+
+        let chains = collect(passes, &resources, |_| 1).map_err(SubChainError::Collect)?;
+        let queues = chains.schedule.queue_count();
+        if queues != 1 {
+            return Err(SubChainError::QueueCount { queues });
+        }
+        let options = SyncOptions::default().fences(FenceStrategy::None);
+        let schedule =
+            sync_with_options(&chains, options, new_semaphore).map_err(SubChainError::Collect)?;
+        let family = schedule.iter().next().unwrap().id();
+        let mut chains = Chains {
+            schedule,
+            buffers: chains.buffers,
+            images: chains.images,
+            stencils: chains.stencils,
+            parts: chains.parts,
+            buffer_parts: chains.buffer_parts,
+        };
+
+        let mut node = PassBuilder::new(id, family).cost(cost);
+        if let Some(capability) = capability {
+            node = node.capability(capability);
+        }
+        if side_effects {
+            node = node.side_effects();
+        }
+        for (id, interface) in sorted(&buffers) {
+            let chain = chains
+                .buffers
+                .get(&id)
+                .ok_or(SubChainError::UntrackedBuffer { id })?;
+            exit(id, chain, interface.state, &mut chains.schedule);
+            node = node.raw_buffer(id, declaration(chain, interface));
+        }
+        for (id, interface) in sorted(&images) {
+            let chain = match chains.images.get(&id) {
+                Some(chain) if !chains.stencils.contains_key(&id) => chain,
+                _ => return Err(SubChainError::UntrackedImage { id }),
+            };
+            exit(id, chain, interface.state, &mut chains.schedule);
+            node = node.raw_image(id, declaration(chain, interface));
+        }
+
+        Ok(SubChain {
+            node: node.build().expect("Boundary resources are declared once"),
+            chains,
+        })
+    }
+}
+
+/// Sub-graph synchronized on single queue together with the node that represents it
+/// in the parent graph.
+///
+/// Submission of the node in the parent records the only queue of the sub-graph in place
+/// of the main commands of the node, including barriers hoisted to its frame beginning.
+/// Events of the sub-graph are indexed separately from events of the parent.
+#[derive(Debug)]
+pub struct SubChain<S, W> {
+    node: Pass,
+    chains: Chains<SyncData<S, W>>,
+}
+
+impl<S, W> SubChain<S, W> {
+    /// Get the node to insert into the parent.
+    /// Node declares boundary states of imported and exported resources with usage of the
+    /// sub-graph. Dependencies on other passes of the parent may be added to it.
+    pub fn node(&self) -> &Pass {
+        &self.node
+    }
+
+    /// Get synchronized chains of the sub-graph.
+    pub fn chains(&self) -> &Chains<SyncData<S, W>> {
+        &self.chains
+    }
+
+    /// Transition boundary resources from and to the states of the links of the node
+    /// in the parent, where those differ from boundary states.
+    /// States of links differ when the parent merges states of other passes with the node's.
+    /// Barriers of the sub-graph from and to boundary states are adjusted for that,
+    /// so fitting the chain again to another parent replaces earlier adjustments.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the node is not scheduled in the parent.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SubChainError` if the parent doesn't track boundary resources of the node
+    /// as a whole, or provides an image in another layout under `LayoutPolicy::Strict`.
+    pub fn fit<T>(
+        &mut self,
+        parent: &Chains<T>,
+        policy: LayoutPolicy,
+    ) -> Result<(), SubChainError> {
+        let submission = parent
+            .schedule
+            .iter()
+            .flat_map(|family| family.iter())
+            .flat_map(|queue| queue.iter())
+            .find(|&(_, submission)| submission.pass() == self.node.id)
+            .map(|(_, submission)| submission)
+            .expect("Node is not scheduled in the parent");
+
+        let mut buffers: Vec<_> = self.node.buffers.keys().cloned().collect();
+        buffers.sort();
+        for id in buffers {
+            let provided = provided(id, &parent.buffers, submission)
+                .ok_or(SubChainError::UntrackedBuffer { id })?;
+            let chain = &self.chains.buffers[&id];
+            reenter(id, chain, provided, &mut self.chains.schedule);
+        }
+
+        let mut images: Vec<_> = self.node.images.keys().cloned().collect();
+        images.sort();
+        for id in images {
+            let provided = match provided(id, &parent.images, submission) {
+                Some(state) if !parent.stencils.contains_key(&id) => state,
+                _ => return Err(SubChainError::UntrackedImage { id }),
+            };
+            let boundary = self.node.images[&id].state;
+            if policy == LayoutPolicy::Strict && provided.layout != boundary.layout {
+                return Err(SubChainError::LayoutMismatch {
+                    id,
+                    states: provided..boundary,
+                });
+            }
+            let chain = &self.chains.images[&id];
+            reenter(id, chain, provided, &mut self.chains.schedule);
+        }
+        Ok(())
+    }
+}
+
+fn declare<R: Resource>(
+    map: &mut FnvHashMap<Id<R>, Interface<R>>,
+    id: Id<R>,
+    state: State<R>,
+    import: bool,
+) {
+    let interface = map.entry(id).or_insert(Interface {
+        state,
+        import: false,
+    });
+    assert!(
+        interface.state == state,
+        "Imported and exported resource has different boundary states"
+    );
+    interface.import |= import;
+}
+
+/// Make the sub-graph find the resource in the boundary state.
+fn bound<R: Resource>(info: &mut ResourceInfo<R>, interface: &Interface<R>) {
+    info.initial_state = Some(interface.state);
+    info.host_write_before = false;
+    info.host_read_after = false;
+    info.present = false;
+    info.import = None;
+    info.export = None;
+}
+
+fn sorted<R: Resource>(map: &FnvHashMap<Id<R>, Interface<R>>) -> Vec<(Id<R>, Interface<R>)> {
+    let mut sorted: Vec<_> = map.iter().map(|(&id, &interface)| (id, interface)).collect();
+    sorted.sort_by_key(|&(id, _)| id);
+    sorted
+}
+
+/// Declaration of the boundary resource by the node.
+fn declaration<R: Resource>(chain: &Chain<R>, interface: Interface<R>) -> StateUsage<R> {
+    StateUsage {
+        state: interface.state,
+        usage: chain.total_usage(),
+        range: None,
+        discard: !interface.import,
+        ordering: OrderingHint::None,
+    }
+}
+
+/// Transition the resource from the last link of the sub-graph to `state`.
+/// Replaces earlier transition after the last link.
+fn exit<R, S, W>(
+    id: Id<R>,
+    chain: &Chain<R>,
+    state: State<R>,
+    schedule: &mut Schedule<SyncData<S, W>>,
+) where
+    R: Resource,
+    Guard<S, W>: Pick<R, Target = Barriers<R>>,
+{
+    let last = chain.links().last().unwrap();
+    let sid = latest(last, schedule);
+    let barriers = Pick::<R>::pick_mut(&mut schedule[sid].sync_mut().release);
+    barriers.remove(&id);
+    if last.state() != state {
+        barriers.insert(id, Barrier::new(last.state()..state, chain.range().cloned()));
+    }
+}
+
+/// Transition the resource from `state` into the first link of the sub-graph
+/// and back after the last link.
+fn reenter<R, S, W>(
+    id: Id<R>,
+    chain: &Chain<R>,
+    state: State<R>,
+    schedule: &mut Schedule<SyncData<S, W>>,
+) where
+    R: Resource,
+    Guard<S, W>: Pick<R, Target = Barriers<R>>,
+{
+    let wait_sid = earliest(chain.link(0), schedule);
+    let hoisted = SubmissionId::new(wait_sid.queue(), 0);
+    let (hoist, barrier) = match Pick::<R>::pick_mut(&mut schedule[hoisted].sync_mut().frame_begin)
+        .remove(&id)
+    {
+        Some(barrier) => (true, barrier),
+        None => {
+            let barrier = Pick::<R>::pick_mut(&mut schedule[wait_sid].sync_mut().acquire)
+                .remove(&id)
+                .expect("Sub-graph transitions boundary resources into the first link");
+            (false, barrier)
+        }
+    };
+
+    // Discarded content needs only the execution dependency on the parent.
+    let start = if chain.discards(0) {
+        State {
+            access: R::Access::none(),
+            layout: R::Layout::discard_content(),
+            stages: state.stages,
+        }
+    } else {
+        state
+    };
+    let barrier = Barrier::new(start..barrier.states.end, barrier.range);
+    let sync = if hoist {
+        &mut schedule[hoisted].sync_mut().frame_begin
+    } else {
+        &mut schedule[wait_sid].sync_mut().acquire
+    };
+    Pick::<R>::pick_mut(sync).insert(id, barrier);
+
+    exit(id, chain, state, schedule);
+}
+
+/// Get state of the link of the node in the parent.
+/// Returns `None` if the node doesn't access the resource as a whole.
+fn provided<R, T>(
+    id: Id<R>,
+    chains: &IdMap<R, Chain<R>>,
+    submission: &Submission<T>,
+) -> Option<State<R>>
+where
+    R: Resource,
+    Submission<T>: Pick<R, Target = FnvHashMap<Id<R>, usize>>,
+{
+    let index = *Pick::<R>::pick(submission).get(&id)?;
+    Some(chains.get(&id)?.link(index).state())
+}
@@ -2,16 +2,22 @@
 //! This crates provide functions for find all required synchronizations (barriers and semaphores).
 //!
 
-use fnv::FnvHashMap;
-use std::cmp::Ordering;
-use std::ops::{Range, RangeFrom, RangeTo};
-
+use alloc::vec::Vec;
+use collections::{FnvHashMap, FnvHashSet};
+use collections::hash_map::Entry;
+use core::cmp::{max, Ordering, Reverse};
+use core::mem::{replace, take};
+use core::ops::{Range, RangeFrom, RangeTo};
+
+use hal::image::{Access as ImageAccess, Layout as ImageLayout, SubresourceRange};
 use hal::pso::PipelineStage;
+use hal::queue::QueueFamilyId;
 
-use chain::{Chain, Link};
-use collect::{Chains, Unsynchronized};
-use resource::{Access, Buffer, Id, Image, Resource, State};
-use schedule::{QueueId, Schedule, SubmissionId};
+use chain::{BufferChains, BufferPartChains, Chain, ImageChains, ImagePartChains, Link};
+use collect::{Chains, CollectError, Unsynchronized};
+use pass::PassKind;
+use resource::{Access, Buffer, Hazard, Id, IdMap, Image, Layout, Resource, SharingMode, State};
+use schedule::{QueueId, Schedule, Submission, SubmissionId};
 use Pick;
 
 // fn earlier_stage(stages: PipelineStage) -> PipelineStage {
@@ -20,8 +26,10 @@ use Pick;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 enum Uid {
-    Buffer(u32),
-    Image(u32),
+    Buffer(usize),
+    Image(usize),
+    Part(usize, usize),
+    BufferPart(usize, usize),
 }
 
 impl From<Id<Buffer>> for Uid {
@@ -38,7 +46,7 @@ impl From<Id<Image>> for Uid {
 
 /// Side of the submission. `Acquire` or `Release`.
 #[derive(Clone, Copy, Debug, PartialOrd, Ord, PartialEq, Eq, Hash)]
-enum Side {
+pub(crate) enum Side {
     /// Acquire side of the submission.
     /// Synchronization commands from this side must be recorded before main commands of submission.
     Acquire,
@@ -95,33 +103,55 @@ impl Semaphore {
 /// Semaphore signal info.
 /// There must be paired wait.
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
-pub struct Signal<S>(S);
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize))]
+pub struct Signal<S>(S, u64);
 
 impl<S> Signal<S> {
     /// Create signaling for specified point.
     /// At this point `Wait` must be created as well.
     /// `id` and `point` combination must be unique.
-    fn new(semaphore: S) -> Self {
-        Signal(semaphore)
+    pub(crate) fn new(semaphore: S) -> Self {
+        Signal(semaphore, 0)
+    }
+
+    /// Create signaling of `value` of timeline semaphore.
+    pub(crate) fn timeline(semaphore: S, value: u64) -> Self {
+        Signal(semaphore, value)
     }
 
     /// Get semaphore of the `Signal`.
     pub fn semaphore(&self) -> &S {
         &self.0
     }
+
+    /// Value to signal.
+    /// Always `0` for binary semaphores.
+    pub fn value(&self) -> u64 {
+        self.1
+    }
 }
 
 /// Semaphore wait info.
 /// There must be paired signal.
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
-pub struct Wait<S>(S, PipelineStage);
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize))]
+pub struct Wait<S>(
+    S,
+    #[cfg_attr(feature = "serde-1", serde(with = "::ser::repr"))] PipelineStage,
+    u64,
+);
 
 impl<S> Wait<S> {
     /// Create waiting for specified point.
     /// At this point `Signal` must be created as well.
     /// `id` and `point` combination must be unique.
-    fn new(semaphore: S, stages: PipelineStage) -> Self {
-        Wait(semaphore, stages)
+    pub(crate) fn new(semaphore: S, stages: PipelineStage) -> Self {
+        Wait(semaphore, stages, 0)
+    }
+
+    /// Create waiting for `value` of timeline semaphore.
+    pub(crate) fn timeline(semaphore: S, stages: PipelineStage, value: u64) -> Self {
+        Wait(semaphore, stages, value)
     }
 
     /// Get semaphore of the `Wait`.
@@ -130,43 +160,95 @@ impl<S> Wait<S> {
     }
 
     /// Stage at which to wait.
+    /// Union of stages at which the waiting queue accesses resources ordered by the semaphore,
+    /// including accesses by later submissions of the queue that rely on this wait.
+    /// Stages before those are not blocked.
     pub fn stage(&self) -> PipelineStage {
         self.1
     }
+
+    /// Value to wait for.
+    /// Always `0` for binary semaphores.
+    pub fn value(&self) -> u64 {
+        self.2
+    }
 }
 
 /// Pipeline barrier info.
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize), serde(bound = ""))]
 pub struct Barrier<R: Resource> {
     /// `Some` queue for ownership transfer. Or `None`
     pub queues: Option<Range<QueueId>>,
 
     /// Stage transition.
     pub states: Range<State<R>>,
+
+    /// Sub-resource range to which barrier applies.
+    /// Or `None` if barrier applies to whole resource.
+    #[cfg_attr(feature = "serde-1", serde(with = "::ser::repr"))]
+    pub range: Option<R::Range>,
 }
 
 impl<R> Barrier<R>
 where
     R: Resource,
 {
-    fn new(states: Range<State<R>>) -> Self {
+    /// Create barrier for state transition on the same queue.
+    /// Write-after-read without layout transition requires only an execution dependency,
+    /// so source access is dropped to avoid needless cache flushes.
+    pub(crate) fn new(mut states: Range<State<R>>, range: Option<R::Range>) -> Self {
+        if states.start.hazard(&states.end) == Hazard::WriteAfterRead
+            && states.start.layout == states.end.layout
+        {
+            states.start.access = R::Access::none();
+        }
         Barrier {
             queues: None,
-            states,
+            states: legal_states(states),
+            range,
+        }
+    }
+
+    /// Create barrier between links.
+    /// Barrier without layout transition is narrowed to the range accessed by next link.
+    /// Layout transition is performed for the whole range tracked by the chain.
+    /// If next link discards the content only the execution dependency is kept.
+    fn between(prev: &Link<R>, next: &Link<R>, whole: Option<&R::Range>, discard: bool) -> Self {
+        if discard {
+            let start = State {
+                access: R::Access::none(),
+                layout: R::Layout::discard_content(),
+                stages: prev.state().stages,
+            };
+            return Self::new(start..next.state(), whole.cloned());
         }
+
+        let range = if prev.state().layout == next.state().layout {
+            next.range()
+        } else {
+            whole
+        };
+        Self::new(prev.state()..next.state(), range.cloned())
     }
 
-    fn transfer(queues: Range<QueueId>, states: Range<State<R>>) -> Self {
+    fn transfer(
+        queues: Range<QueueId>,
+        states: Range<State<R>>,
+        range: Option<&R::Range>,
+    ) -> Self {
         Barrier {
             queues: Some(queues),
-            states,
+            states: legal_states(states),
+            range: range.cloned(),
         }
     }
 
-    fn acquire(
+    pub(crate) fn acquire(
         queues: Range<QueueId>,
         left: RangeFrom<R::Layout>,
         right: RangeTo<State<R>>,
+        range: Option<&R::Range>,
     ) -> Self {
         Self::transfer(
             queues,
@@ -175,13 +257,15 @@ where
                 layout: left.start,
                 stages: PipelineStage::empty(),
             }..right.end,
+            range,
         )
     }
 
-    fn release(
+    pub(crate) fn release(
         queues: Range<QueueId>,
         left: RangeFrom<State<R>>,
         right: RangeTo<R::Layout>,
+        range: Option<&R::Range>,
     ) -> Self {
         Self::transfer(
             queues,
@@ -190,10 +274,37 @@ where
                 layout: right.end,
                 stages: PipelineStage::empty(),
             },
+            range,
         )
     }
+
+    /// Check if both states of the barrier are legal.
+    /// Access types must be performed by stages of their side,
+    /// which `TOP_OF_PIPE` and `BOTTOM_OF_PIPE` never do. See `State::legalized`.
+    pub fn is_legal(&self) -> bool {
+        self.states.start.is_legal() && self.states.end.is_legal()
+    }
+}
+
+/// Barriers can't synchronize access types at stages that don't perform them.
+fn legal_states<R: Resource>(states: Range<State<R>>) -> Range<State<R>> {
+    states.start.legalized()..states.end.legalized()
+}
+
+impl<R> PartialEq for Barrier<R>
+where
+    R: Resource,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.queues == other.queues
+            && self.states.start == other.states.start
+            && self.states.end == other.states.end
+            && self.range == other.range
+    }
 }
 
+impl<R> Eq for Barrier<R> where R: Resource {}
+
 /// Map of barriers by resource id.
 pub type Barriers<R> = FnvHashMap<Id<R>, Barrier<R>>;
 
@@ -203,8 +314,20 @@ pub type BufferBarriers = Barriers<Buffer>;
 /// Map of barriers by image id.
 pub type ImageBarriers = Barriers<Image>;
 
+/// Barriers of resource parts paired with resource ids.
+pub(crate) type PartBarriers<R> = Vec<(Id<R>, Barrier<R>)>;
+
+/// Barriers of buffers, images, image parts and buffer parts performed together.
+pub(crate) type GuardBarriers<'a> = (
+    &'a Barriers<Buffer>,
+    &'a Barriers<Image>,
+    &'a [(Id<Image>, Barrier<Image>)],
+    &'a [(Id<Buffer>, Barrier<Buffer>)],
+);
+
 /// Synchronization for submission at one side.
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize))]
 pub struct Guard<S, W> {
     /// Points at other queues that must be waited before commands from the submission can be executed.
     pub wait: Vec<Wait<W>>,
@@ -215,8 +338,37 @@ pub struct Guard<S, W> {
     /// Image pipeline barriers to be inserted before or after (depends on the side) main commands of the submission.
     pub images: ImageBarriers,
 
+    /// Barriers for parts of images accessed in several disjoint ranges.
+    /// Image may have barrier for each part.
+    pub parts: Vec<(Id<Image>, Barrier<Image>)>,
+
+    /// Barriers for parts of buffers accessed only in disjoint ranges.
+    /// Buffer may have barrier for each part.
+    pub buffer_parts: Vec<(Id<Buffer>, Barrier<Buffer>)>,
+
+    /// Events to set after (only on release side) main commands of the submission.
+    pub set_events: Vec<SetEvent>,
+
+    /// Events to wait before (only on acquire side) main commands of the submission.
+    /// Those are split barriers. Barriers from them are not contained in `buffers` and `images`.
+    pub wait_events: Vec<WaitEvent>,
+
+    /// Present images which acquisition must be waited (only on acquire side).
+    /// User should wait for semaphore signaled by image acquisition.
+    pub wait_acquired: Vec<Wait<Id<Image>>>,
+
     /// Points at other queues that can run after barriers above.
     pub signal: Vec<Signal<S>>,
+
+    /// Present images that can be presented after barriers above (only on release side).
+    /// User should signal semaphore to be waited by image presentation.
+    pub signal_present: Vec<Signal<Id<Image>>>,
+
+    /// Guard belongs to conditional submission and must be recorded
+    /// even if main commands of the submission are skipped.
+    /// Barriers of the guard transition resources into and out of states of the pass
+    /// either way, so later submissions find them in expected states.
+    pub must_record_even_if_skipped: bool,
 }
 
 impl<S, W> Guard<S, W> {
@@ -225,11 +377,220 @@ impl<S, W> Guard<S, W> {
             wait: Vec::new(),
             buffers: FnvHashMap::default(),
             images: FnvHashMap::default(),
+            parts: Vec::new(),
+            buffer_parts: Vec::new(),
+            set_events: Vec::new(),
+            wait_events: Vec::new(),
+            wait_acquired: Vec::new(),
+            signal: Vec::new(),
+            signal_present: Vec::new(),
+            must_record_even_if_skipped: false,
+        }
+    }
+
+    /// Move barriers into guard with other semaphore types.
+    /// Guard must have no semaphores, like `SyncData::frame_begin`.
+    fn into_barriers<T, U>(self) -> Guard<T, U> {
+        debug_assert!(self.wait.is_empty() && self.signal.is_empty());
+        Guard {
+            wait: Vec::new(),
+            buffers: self.buffers,
+            images: self.images,
+            parts: self.parts,
+            buffer_parts: self.buffer_parts,
+            set_events: self.set_events,
+            wait_events: self.wait_events,
+            wait_acquired: self.wait_acquired,
             signal: Vec::new(),
+            signal_present: self.signal_present,
+            must_record_even_if_skipped: self.must_record_even_if_skipped,
+        }
+    }
+
+    /// Check if all barriers of the guard, including ones performed with event waits, are legal.
+    /// See `Barrier::is_legal`.
+    fn is_legal(&self) -> bool {
+        self.buffers.values().all(Barrier::is_legal)
+            && self.images.values().all(Barrier::is_legal)
+            && self.parts.iter().all(|(_, barrier)| barrier.is_legal())
+            && self.buffer_parts.iter().all(|(_, barrier)| barrier.is_legal())
+            && self.wait_events.iter().all(WaitEvent::is_legal)
+    }
+
+    /// Combine all barriers of the guard into single pipeline barrier command.
+    /// Returns `None` if there are no barriers.
+    pub fn pipeline_barrier(&self) -> Option<PipelineBarrier<'_>> {
+        if self.buffers.is_empty()
+            && self.images.is_empty()
+            && self.parts.is_empty()
+            && self.buffer_parts.is_empty()
+        {
+            return None;
+        }
+
+        let mut buffers: Vec<_> = self
+            .buffers
+            .iter()
+            .map(|(&id, b)| (id, b))
+            .chain(self.buffer_parts.iter().map(|&(id, ref b)| (id, b)))
+            .collect();
+        let mut images: Vec<_> = self
+            .images
+            .iter()
+            .map(|(&id, b)| (id, b))
+            .chain(self.parts.iter().map(|&(id, ref b)| (id, b)))
+            .collect();
+        buffers.sort_by_key(|&(id, _)| id);
+        images.sort_by_key(|&(id, _)| id);
+
+        let mut src = PipelineStage::empty();
+        let mut dst = PipelineStage::empty();
+        for &(_, barrier) in &buffers {
+            src |= barrier.states.start.stages;
+            dst |= barrier.states.end.stages;
+        }
+        for &(_, barrier) in &images {
+            src |= barrier.states.start.stages;
+            dst |= barrier.states.end.stages;
+        }
+
+        Some(PipelineBarrier {
+            stages: legal_stages(src..dst),
+            buffers,
+            images,
+        })
+    }
+}
+
+/// Stage masks can't be empty.
+/// Substituted `TOP_OF_PIPE` and `BOTTOM_OF_PIPE` never perform accesses of legal barriers,
+/// as barriers with access types have stages that perform them.
+fn legal_stages(stages: Range<PipelineStage>) -> Range<PipelineStage> {
+    let mut src = stages.start;
+    let mut dst = stages.end;
+    if src.is_empty() {
+        src = PipelineStage::TOP_OF_PIPE;
+    }
+    if dst.is_empty() {
+        dst = PipelineStage::BOTTOM_OF_PIPE;
+    }
+    src..dst
+}
+
+/// Event set command.
+/// First half of split barrier.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize))]
+pub struct SetEvent {
+    /// Index of the event.
+    /// Events are indexed from `0` to `event_count` of the schedule.
+    pub event: usize,
+
+    /// Stages to wait before setting the event.
+    #[cfg_attr(feature = "serde-1", serde(with = "::ser::repr"))]
+    pub stages: PipelineStage,
+}
+
+/// Event wait command.
+/// Second half of split barrier.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize))]
+pub struct WaitEvent {
+    /// Index of the event.
+    /// Events are indexed from `0` to `event_count` of the schedule.
+    pub event: usize,
+
+    /// Source and destination stages.
+    /// Source stages are the same as stages of paired `SetEvent`.
+    #[cfg_attr(feature = "serde-1", serde(with = "::ser::repr"))]
+    pub stages: Range<PipelineStage>,
+
+    /// Buffer barriers to perform with event wait.
+    pub buffers: BufferBarriers,
+
+    /// Image barriers to perform with event wait.
+    pub images: ImageBarriers,
+
+    /// Barriers for parts of images accessed in several disjoint ranges.
+    /// Image may have barrier for each part.
+    pub parts: Vec<(Id<Image>, Barrier<Image>)>,
+
+    /// Barriers for parts of buffers accessed only in disjoint ranges.
+    /// Buffer may have barrier for each part.
+    pub buffer_parts: Vec<(Id<Buffer>, Barrier<Buffer>)>,
+}
+
+impl WaitEvent {
+    fn new() -> Self {
+        WaitEvent {
+            event: 0,
+            stages: PipelineStage::empty()..PipelineStage::empty(),
+            buffers: FnvHashMap::default(),
+            images: FnvHashMap::default(),
+            parts: Vec::new(),
+            buffer_parts: Vec::new(),
         }
     }
 }
 
+impl WaitEvent {
+    fn is_legal(&self) -> bool {
+        self.buffers.values().all(Barrier::is_legal)
+            && self.images.values().all(Barrier::is_legal)
+            && self.parts.iter().all(|(_, barrier)| barrier.is_legal())
+            && self.buffer_parts.iter().all(|(_, barrier)| barrier.is_legal())
+    }
+}
+
+impl Pick<Image> for WaitEvent {
+    type Target = ImageBarriers;
+
+    fn pick(&self) -> &ImageBarriers {
+        &self.images
+    }
+    fn pick_mut(&mut self) -> &mut ImageBarriers {
+        &mut self.images
+    }
+}
+
+impl Pick<Buffer> for WaitEvent {
+    type Target = BufferBarriers;
+
+    fn pick(&self) -> &BufferBarriers {
+        &self.buffers
+    }
+    fn pick_mut(&mut self) -> &mut BufferBarriers {
+        &mut self.buffers
+    }
+}
+
+/// Get number of events used by synchronized schedule.
+/// Events are indexed from `0` to returned value.
+pub fn event_count<S, W>(schedule: &Schedule<SyncData<S, W>>) -> usize {
+    schedule
+        .iter()
+        .flat_map(|family| family.iter())
+        .flat_map(|queue| queue.iter())
+        .flat_map(|(_, submission)| &submission.sync().release.set_events)
+        .count()
+}
+
+/// Barriers from one `Guard` combined to be recorded with single pipeline barrier command.
+#[derive(Clone, Debug)]
+pub struct PipelineBarrier<'a> {
+    /// Source and destination stages.
+    /// Union of stages of all barriers.
+    pub stages: Range<PipelineStage>,
+
+    /// Buffer barriers sorted by id.
+    /// Buffer tracked in parts may have barrier for each part.
+    pub buffers: Vec<(Id<Buffer>, &'a Barrier<Buffer>)>,
+
+    /// Image barriers sorted by id.
+    /// Image tracked in parts may have barrier for each part.
+    pub images: Vec<(Id<Image>, &'a Barrier<Image>)>,
+}
+
 impl<S, W> Pick<Image> for Guard<S, W> {
     type Target = ImageBarriers;
 
@@ -252,8 +613,66 @@ impl<S, W> Pick<Buffer> for Guard<S, W> {
     }
 }
 
+/// Self-dependency of the submission that accesses image as feedback loop.
+/// Attachment writes by earlier fragments must be made visible to input attachment reads
+/// by later ones within the submission. Record it as subpass self-dependency
+/// or pipeline barrier between its draws. See `ImageUse::FeedbackLoop`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize))]
+pub struct FeedbackLoop {
+    /// Id of the image.
+    pub id: Id<Image>,
+
+    /// Sub-resource range accessed as feedback loop.
+    /// Or `None` if the whole image is accessed that way.
+    #[cfg_attr(feature = "serde-1", serde(with = "::ser::repr"))]
+    pub range: Option<SubresourceRange>,
+
+    /// Attachment writes and following input attachment reads, both in `General` layout.
+    pub states: Range<State<Image>>,
+
+    /// Source and destination stages are framebuffer-space stages,
+    /// so the dependency can be made by region.
+    pub by_region: bool,
+}
+
+impl FeedbackLoop {
+    /// Get self-dependency required by the link state. Or `None` if it isn't a feedback loop.
+    fn new(id: Id<Image>, link: &Link<Image>) -> Option<Self> {
+        let state = link.state();
+        if !state.is_feedback_loop() {
+            return None;
+        }
+        let writes =
+            ImageAccess::COLOR_ATTACHMENT_WRITE | ImageAccess::DEPTH_STENCIL_ATTACHMENT_WRITE;
+        let attachment_stages = PipelineStage::EARLY_FRAGMENT_TESTS
+            | PipelineStage::LATE_FRAGMENT_TESTS
+            | PipelineStage::COLOR_ATTACHMENT_OUTPUT;
+        let framebuffer = attachment_stages | PipelineStage::FRAGMENT_SHADER;
+        // Stages not performing the accesses are dropped. Legalizing restores required ones.
+        let src = State {
+            access: state.access & writes,
+            layout: state.layout,
+            stages: state.stages & attachment_stages,
+        };
+        let dst = State {
+            access: ImageAccess::INPUT_ATTACHMENT_READ,
+            layout: state.layout,
+            stages: state.stages & PipelineStage::FRAGMENT_SHADER,
+        };
+        let (src, dst) = (src.legalized(), dst.legalized());
+        Some(FeedbackLoop {
+            id,
+            range: link.range().cloned(),
+            by_region: framebuffer.contains(src.stages) && framebuffer.contains(dst.stages),
+            states: src..dst,
+        })
+    }
+}
+
 /// Both sides of synchronization for submission.
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize))]
 pub struct SyncData<S, W> {
     /// Acquire side of submission synchronization.
     /// Synchronization commands from this side must be recorded before main commands of submission.
@@ -261,6 +680,16 @@ pub struct SyncData<S, W> {
     /// Release side of submission synchronization.
     /// Synchronization commands from this side must be recorded after main commands of submission.
     pub release: Guard<S, W>,
+    /// Barriers hoisted to the beginning of the frame. Contains no semaphores and events.
+    /// Only the first submission of a queue may have them. They must be recorded on the queue
+    /// before all other commands of the schedule, including acquire side of the submission.
+    /// See `SyncOptions::hoist_initial_transitions`.
+    pub frame_begin: Guard<S, W>,
+    /// Self-dependencies of images the submission accesses as feedback loops, sorted by id.
+    /// Those order commands within the submission. See `FeedbackLoop`.
+    pub feedback_loops: Vec<FeedbackLoop>,
+    /// Fence to signal with the submission. See `SyncOptions::fences`.
+    pub fence: Option<FenceId>,
 }
 
 impl<S, W> SyncData<S, W> {
@@ -268,11 +697,14 @@ impl<S, W> SyncData<S, W> {
         SyncData {
             acquire: Guard::new(),
             release: Guard::new(),
+            frame_begin: Guard::new(),
+            feedback_loops: Vec::new(),
+            fence: None,
         }
     }
 
     /// Get mutable reference to `Guard` by `Side`.
-    fn get_mut(&mut self, side: Side) -> &mut Guard<S, W> {
+    pub(crate) fn get_mut(&mut self, side: Side) -> &mut Guard<S, W> {
         match side {
             Side::Acquire => &mut self.acquire,
             Side::Release => &mut self.release,
@@ -290,10 +722,17 @@ impl<S, W> SyncData<S, W> {
                     .acquire
                     .signal
                     .into_iter()
-                    .map(|Signal(semaphore)| Signal(f(semaphore)))
+                    .map(|Signal(semaphore, value)| Signal(f(semaphore), value))
                     .collect(),
                 buffers: self.acquire.buffers,
                 images: self.acquire.images,
+                parts: self.acquire.parts,
+                buffer_parts: self.acquire.buffer_parts,
+                set_events: self.acquire.set_events,
+                wait_events: self.acquire.wait_events,
+                wait_acquired: self.acquire.wait_acquired,
+                signal_present: self.acquire.signal_present,
+                must_record_even_if_skipped: self.acquire.must_record_even_if_skipped,
             },
             release: Guard {
                 wait: self.release.wait,
@@ -301,11 +740,21 @@ impl<S, W> SyncData<S, W> {
                     .release
                     .signal
                     .into_iter()
-                    .map(|Signal(semaphore)| Signal(f(semaphore)))
+                    .map(|Signal(semaphore, value)| Signal(f(semaphore), value))
                     .collect(),
                 buffers: self.release.buffers,
                 images: self.release.images,
+                parts: self.release.parts,
+                buffer_parts: self.release.buffer_parts,
+                set_events: self.release.set_events,
+                wait_events: self.release.wait_events,
+                wait_acquired: self.release.wait_acquired,
+                signal_present: self.release.signal_present,
+                must_record_even_if_skipped: self.release.must_record_even_if_skipped,
             },
+            frame_begin: self.frame_begin.into_barriers(),
+            feedback_loops: self.feedback_loops,
+            fence: self.fence,
         }
     }
 
@@ -319,63 +768,822 @@ impl<S, W> SyncData<S, W> {
                     .acquire
                     .wait
                     .into_iter()
-                    .map(|Wait(semaphore, stage)| Wait(f(semaphore), stage))
+                    .map(|Wait(semaphore, stage, value)| Wait(f(semaphore), stage, value))
                     .collect(),
                 signal: self.acquire.signal,
                 buffers: self.acquire.buffers,
                 images: self.acquire.images,
+                parts: self.acquire.parts,
+                buffer_parts: self.acquire.buffer_parts,
+                set_events: self.acquire.set_events,
+                wait_events: self.acquire.wait_events,
+                wait_acquired: self.acquire.wait_acquired,
+                signal_present: self.acquire.signal_present,
+                must_record_even_if_skipped: self.acquire.must_record_even_if_skipped,
             },
             release: Guard {
                 wait: self
                     .release
                     .wait
                     .into_iter()
-                    .map(|Wait(semaphore, stage)| Wait(f(semaphore), stage))
+                    .map(|Wait(semaphore, stage, value)| Wait(f(semaphore), stage, value))
                     .collect(),
                 signal: self.release.signal,
                 buffers: self.release.buffers,
                 images: self.release.images,
+                parts: self.release.parts,
+                buffer_parts: self.release.buffer_parts,
+                set_events: self.release.set_events,
+                wait_events: self.release.wait_events,
+                wait_acquired: self.release.wait_acquired,
+                signal_present: self.release.signal_present,
+                must_record_even_if_skipped: self.release.must_record_even_if_skipped,
             },
+            frame_begin: self.frame_begin.into_barriers(),
+            feedback_loops: self.feedback_loops,
+            fence: self.fence,
         }
     }
 }
 
-struct SyncTemp(FnvHashMap<SubmissionId, SyncData<Semaphore, Semaphore>>);
+/// Ownership transfer between the schedule and external family.
+/// The barrier is stored in the acquire (or release) side of the submission
+/// and must be paired with external synchronization that orders it
+/// after the release (or before the acquire) performed by external family.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ExternalTransfer<R: Resource> {
+    /// Id of the resource.
+    pub id: Id<R>,
+
+    /// External family that releases or acquires ownership.
+    pub family: QueueFamilyId,
+
+    /// Submission that performs the barrier.
+    pub sid: SubmissionId,
+}
+
+/// All ownership transfers between the schedule and external families.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ExternalTransfers {
+    /// Buffers acquired from external families. Sorted by id.
+    /// Barriers are stored in `SyncData::acquire`.
+    pub buffer_imports: Vec<ExternalTransfer<Buffer>>,
+
+    /// Buffers released to external families. Sorted by id.
+    /// Barriers are stored in `SyncData::release`.
+    pub buffer_exports: Vec<ExternalTransfer<Buffer>>,
+
+    /// Images acquired from external families. Sorted by id.
+    /// Barriers are stored in `SyncData::acquire`.
+    pub image_imports: Vec<ExternalTransfer<Image>>,
+
+    /// Images released to external families. Sorted by id.
+    /// Barriers are stored in `SyncData::release`.
+    pub image_exports: Vec<ExternalTransfer<Image>>,
+}
+
+impl<S, W> Chains<SyncData<S, W>> {
+    /// Find ownership transfers between the schedule and external families.
+    /// Resources imported but not exported have only the import transfer.
+    pub fn external_transfers(&self) -> ExternalTransfers {
+        let mut transfers = ExternalTransfers::default();
+        external_transfers(
+            sorted(&self.buffers),
+            &self.schedule,
+            &mut transfers.buffer_imports,
+            &mut transfers.buffer_exports,
+        );
+        external_transfers(
+            sorted(&self.images),
+            &self.schedule,
+            &mut transfers.image_imports,
+            &mut transfers.image_exports,
+        );
+        external_transfers(
+            sorted(&self.stencils),
+            &self.schedule,
+            &mut transfers.image_imports,
+            &mut transfers.image_exports,
+        );
+        external_transfers(
+            sorted_parts(&self.buffer_parts),
+            &self.schedule,
+            &mut transfers.buffer_imports,
+            &mut transfers.buffer_exports,
+        );
+        external_transfers(
+            sorted_parts(&self.parts),
+            &self.schedule,
+            &mut transfers.image_imports,
+            &mut transfers.image_exports,
+        );
+        for list in &mut [&mut transfers.buffer_imports, &mut transfers.buffer_exports] {
+            list.sort_by_key(|transfer| (transfer.id, transfer.sid));
+            list.dedup();
+        }
+        for list in &mut [&mut transfers.image_imports, &mut transfers.image_exports] {
+            list.sort_by_key(|transfer| (transfer.id, transfer.sid));
+            list.dedup();
+        }
+        transfers
+    }
+}
+
+fn external_transfers<R, S>(
+    chains: Vec<(Id<R>, &Chain<R>)>,
+    schedule: &Schedule<S>,
+    imports: &mut Vec<ExternalTransfer<R>>,
+    exports: &mut Vec<ExternalTransfer<R>>,
+) where
+    R: Resource,
+{
+    for (id, chain) in chains {
+        if chain.links().is_empty() {
+            continue;
+        }
+        if let Some(import) = chain.info().import {
+            imports.push(ExternalTransfer {
+                id,
+                family: import.family,
+                sid: earliest(&chain.links()[0], schedule),
+            });
+        }
+        if let Some(export) = chain.info().export {
+            exports.push(ExternalTransfer {
+                id,
+                family: export.family,
+                sid: latest(chain.links().last().unwrap(), schedule),
+            });
+        }
+    }
+}
+
+struct SyncTemp(
+    FnvHashMap<SubmissionId, SyncData<Semaphore, Semaphore>>,
+    FnvHashMap<(SubmissionId, SubmissionId), WaitEvent>,
+);
 impl SyncTemp {
     fn get_sync(&mut self, sid: SubmissionId) -> &mut SyncData<Semaphore, Semaphore> {
         self.0.entry(sid).or_insert_with(|| SyncData::new())
     }
 }
 
+/// Kind of semaphores used to express dependencies between queues.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize))]
+pub enum SyncMode {
+    /// Binary semaphore pair is created for each dependency.
+    /// `Signal::value` and `Wait::value` are always `0`.
+    BinarySemaphores,
+
+    /// Timeline semaphore is created for each queue that signals.
+    /// Submission with index `i` signals value `i + 1` after all its commands.
+    /// Waits are recorded at the acquire side and contain the value of the latest submission
+    /// waited for each queue.
+    /// Signals and waits are performed per submission instead of per side,
+    /// so submission may wait and be waited longer than with binary semaphores.
+    Timelines,
+}
+
+/// Index of the fence signaled by the schedule.
+/// Fences are indexed from `0` to `fence_count` in order of queues
+/// sorted by family id and then by index, and then in submission order.
+pub type FenceId = usize;
+
+/// Submissions that signal fences.
+/// Regardless of the strategy the latest submission that accesses a resource
+/// read by host after the schedule signals a fence, so host can wait for it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize))]
+pub enum FenceStrategy {
+    /// Last submission of every queue signals a fence.
+    AllQueues,
+
+    /// No fences except ones required for host reads.
+    None,
+
+    /// Last submission of each of the queues signals a fence.
+    /// Queues that are not in the schedule are ignored.
+    Explicit(Vec<QueueId>),
+}
+
+/// Options for synchronization generation.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize))]
+pub struct SyncOptions {
+    /// Remove semaphores for dependencies that are already satisfied by other semaphores.
+    /// Disable to get one semaphore per dependency, which may be useful for debugging.
+    pub optimize_semaphores: bool,
+
+    /// Kind of semaphores to generate.
+    pub mode: SyncMode,
+
+    /// Generate split barriers for dependencies between submissions on the same queue
+    /// if there are at least specified number (and at least one) of submissions between them.
+    /// Or `None` to always generate pipeline barriers.
+    pub split_barriers: Option<usize>,
+
+    /// Move transitions of first links from `Undefined` layout with no access to wait
+    /// to `SyncData::frame_begin` of the first submission of the queue that performs them.
+    /// Those don't depend on other commands of the schedule, so recording them
+    /// in one barrier before the first pass avoids pipeline bubbles in the middle of the frame.
+    /// Imported, present and sparse bound images are not hoisted.
+    /// Hoisted images are transitioned from the beginning of the schedule,
+    /// so they must not alias memory of other resources.
+    pub hoist_initial_transitions: bool,
+
+    /// Submissions that signal fences. See `SyncData::fence`.
+    pub fences: FenceStrategy,
+
+    /// Insert transition node before each submission that has barriers on its acquire side.
+    /// Barriers, event waits and semaphores of the acquire side move to the node, which has
+    /// no main commands, so they can be recorded into a separate small command buffer
+    /// and command buffers of passes start with their main commands.
+    /// Nodes are submissions of the schedule with own ids. See `Submission::is_transition`.
+    /// Links start at the node inserted before their first submission on each queue.
+    /// Only `sync_chains` supports it, because ids of submissions change.
+    #[cfg_attr(feature = "serde-1", serde(default))]
+    pub standalone_transition_nodes: bool,
+}
+
+impl SyncOptions {
+    /// Set submissions that signal fences.
+    pub fn fences(self, fences: FenceStrategy) -> Self {
+        SyncOptions { fences, ..self }
+    }
+
+    /// Enable or disable transition nodes. See `SyncOptions::standalone_transition_nodes`.
+    pub fn standalone_transition_nodes(self, standalone_transition_nodes: bool) -> Self {
+        SyncOptions {
+            standalone_transition_nodes,
+            ..self
+        }
+    }
+}
+
+impl Default for SyncOptions {
+    fn default() -> Self {
+        SyncOptions {
+            optimize_semaphores: true,
+            mode: SyncMode::BinarySemaphores,
+            split_barriers: None,
+            hoist_initial_transitions: false,
+            fences: FenceStrategy::AllQueues,
+            standalone_transition_nodes: false,
+        }
+    }
+}
+
 /// Find required synchronization for all submissions in `Chains`.
+///
+/// Each link of each chain is synchronized with the previous one only,
+/// so synchronization takes time linear in the number of links and submissions,
+/// plus sorting of chains by id and of semaphores of each submission.
+///
+/// # Errors
+///
+/// Returns `CollectError::UnknownSubmission` if some link of the chains
+/// refers to a submission that isn't in the schedule.
 pub fn sync<F, S, W>(
     chains: &Chains<Unsynchronized>,
-    mut new_semaphore: F,
-) -> Schedule<SyncData<S, W>>
+    new_semaphore: F,
+) -> Result<Schedule<SyncData<S, W>>, CollectError>
 where
     F: FnMut() -> (S, W),
+    S: Clone,
+    W: Clone,
 {
-    let ref schedule = chains.schedule;
-    let ref buffers = chains.buffers;
-    let ref images = chains.images;
+    sync_with_options(chains, SyncOptions::default(), new_semaphore)
+}
+
+/// Find required synchronization for all submissions in `Chains` using specified options.
+///
+/// With `SyncMode::Timelines` `new_semaphore` is called once per queue
+/// and returned semaphore is cloned into all signals and waits of the queue's timeline.
+///
+/// # Errors
+///
+/// Returns `CollectError::UnknownSubmission` if some link of the chains
+/// refers to a submission that isn't in the schedule.
+/// Returns `CollectError::UnsupportedTransitionNodes` if `options.standalone_transition_nodes`
+/// is set. Submissions of the schedule wouldn't match links of `chains`, use `sync_chains`.
+///
+pub fn sync_with_options<F, S, W>(
+    chains: &Chains<Unsynchronized>,
+    options: SyncOptions,
+    new_semaphore: F,
+) -> Result<Schedule<SyncData<S, W>>, CollectError>
+where
+    F: FnMut() -> (S, W),
+    S: Clone,
+    W: Clone,
+{
+    if options.standalone_transition_nodes {
+        return Err(CollectError::UnsupportedTransitionNodes);
+    }
+    check_links(chains)?;
+    Ok(sync_schedule(&chains.schedule, chains, &options, new_semaphore))
+}
 
-    let mut sync = SyncTemp(FnvHashMap::default());
-    for (&id, chain) in buffers {
-        sync_chain(id, chain, schedule, &mut sync);
+/// Find required synchronization for all submissions in `Chains` using specified options
+/// and return synchronized `Chains`.
+///
+/// Unlike `sync_with_options` supports `SyncOptions::standalone_transition_nodes`.
+/// Links of the chains are moved to ids of submissions in the synchronized schedule.
+///
+/// # Errors
+///
+/// Returns `CollectError::UnknownSubmission` if some link of the chains
+/// refers to a submission that isn't in the schedule.
+pub fn sync_chains<F, S, W>(
+    chains: Chains<Unsynchronized>,
+    options: SyncOptions,
+    new_semaphore: F,
+) -> Result<Chains<SyncData<S, W>>, CollectError>
+where
+    F: FnMut() -> (S, W),
+    S: Clone,
+    W: Clone,
+{
+    check_links(&chains)?;
+    let schedule = sync_schedule(&chains.schedule, &chains, &options, new_semaphore);
+    let mut chains = Chains {
+        schedule,
+        buffers: chains.buffers,
+        images: chains.images,
+        stencils: chains.stencils,
+        parts: chains.parts,
+        buffer_parts: chains.buffer_parts,
+    };
+    if options.standalone_transition_nodes {
+        reindex_chains(&mut chains);
     }
-    for (&id, chain) in images {
-        sync_chain(id, chain, schedule, &mut sync);
+    Ok(chains)
+}
+
+/// Check that links of all chains refer only to submissions of the schedule.
+/// The lowest id of unknown submission is reported.
+fn check_links(chains: &Chains<Unsynchronized>) -> Result<(), CollectError> {
+    let buffers = chains
+        .buffers
+        .values()
+        .chain(chains.buffer_parts.values().flatten());
+    let images = chains
+        .images
+        .values()
+        .chain(chains.stencils.values())
+        .chain(chains.parts.values().flatten());
+    let unknown = unknown_submission(&chains.schedule, buffers);
+    match unknown.into_iter().chain(unknown_submission(&chains.schedule, images)).min() {
+        Some(sid) => Err(CollectError::UnknownSubmission { sid }),
+        None => Ok(()),
     }
+}
 
-    if schedule.queue_count() > 1 {
+/// Find the lowest id of submission referred by the links of the chains that isn't in the schedule.
+fn unknown_submission<'a, R, I>(
+    schedule: &Schedule<Unsynchronized>,
+    chains: I,
+) -> Option<SubmissionId>
+where
+    R: Resource + 'a,
+    I: Iterator<Item = &'a Chain<R>>,
+{
+    chains
+        .flat_map(|chain| chain.links())
+        .flat_map(|link| link.queues())
+        .filter_map(|(qid, queue)| {
+            let len = schedule.queue(qid).map_or(0, |known| known.len());
+            if queue.last < len {
+                None
+            } else {
+                Some(SubmissionId::new(qid, max(queue.first, len)))
+            }
+        })
+        .min()
+}
+
+/// Find required synchronization for the schedule and chains stored separately.
+/// Schedule of `chains` is ignored.
+pub(crate) fn sync_schedule<F, T, S, W>(
+    schedule: &Schedule<Unsynchronized>,
+    chains: &Chains<T>,
+    options: &SyncOptions,
+    new_semaphore: F,
+) -> Schedule<SyncData<S, W>>
+where
+    F: FnMut() -> (S, W),
+    S: Clone,
+    W: Clone,
+{
+    debug_assert!(schedule.is_dense());
+    let Chains {
+        buffers,
+        images,
+        stencils,
+        parts,
+        buffer_parts,
+        ..
+    } = chains;
+    let mut host_reads = host_reads(schedule, buffers, images, stencils, parts, buffer_parts);
+
+    // Chains are processed in order of ids so that result doesn't depend on map iteration order.
+    let mut sync = SyncTemp(FnvHashMap::default(), FnvHashMap::default());
+    for (id, chain) in sorted(buffers) {
+        sync_chain(id, id.into(), chain, schedule, options, &mut sync);
+    }
+    for (id, chain) in sorted(images) {
+        sync_chain(id, id.into(), chain, schedule, options, &mut sync);
+        if chain.info().present {
+            sync_present(id, chain, schedule, &mut sync);
+        }
+    }
+    for (id, chain) in sorted(stencils) {
+        sync_chain(id, id.into(), chain, schedule, options, &mut sync);
+    }
+    let mut image_parts: Vec<_> = parts.iter().map(|(&id, parts)| (id, parts)).collect();
+    image_parts.sort_by_key(|&(id, _)| id);
+    for (id, parts) in image_parts {
+        sync_parts(id, parts, schedule, options, &mut sync);
+    }
+    let mut buffer_parts: Vec<_> = buffer_parts.iter().map(|(&id, parts)| (id, parts)).collect();
+    buffer_parts.sort_by_key(|&(id, _)| id);
+    for (id, parts) in buffer_parts {
+        sync_buffer_parts(id, parts, schedule, options, &mut sync);
+    }
+    assign_events(&mut sync);
+
+    if options.optimize_semaphores && schedule.queue_count() > 1 {
         optimize(schedule, &mut sync);
     }
+    let nodes;
+    let schedule = if options.standalone_transition_nodes {
+        nodes = insert_transition_nodes(schedule, &mut sync, &mut host_reads);
+        &nodes
+    } else {
+        schedule
+    };
+    sort_semaphores(&mut sync);
+
+    let mut result = match options.mode {
+        SyncMode::BinarySemaphores => binary_semaphores(schedule, sync, new_semaphore),
+        SyncMode::Timelines => timelines(schedule, sync, new_semaphore),
+    };
+    mark_conditional(&mut result);
+    assign_feedback_loops(&mut result, images, stencils, parts);
+    assign_fences(&mut result, &options.fences, &host_reads);
+    debug_assert!(legal_barriers(&result), "Illegal barrier emitted");
+    result
+}
+
+/// Get latest submissions that access resources read by host after the schedule.
+fn host_reads<S>(
+    schedule: &Schedule<S>,
+    buffers: &BufferChains,
+    images: &ImageChains,
+    stencils: &ImageChains,
+    parts: &ImagePartChains,
+    buffer_parts: &BufferPartChains,
+) -> FnvHashSet<SubmissionId> {
+    fn read<R: Resource, S>(chain: &Chain<R>, schedule: &Schedule<S>) -> Option<SubmissionId> {
+        let info = chain.info();
+        match chain.links().last() {
+            Some(link) if info.host_read_after && info.export.is_none() => {
+                Some(latest(link, schedule))
+            }
+            _ => None,
+        }
+    }
+
+    let buffers = buffers
+        .values()
+        .chain(buffer_parts.values().flat_map(|parts| parts.iter()))
+        .filter_map(|chain| read(chain, schedule));
+    let images = images
+        .values()
+        .chain(stencils.values())
+        .chain(parts.values().flat_map(|parts| parts.iter()))
+        .filter_map(|chain| read(chain, schedule));
+    buffers.chain(images).collect()
+}
 
+/// Assign dense fence ids to submissions that signal fences.
+fn assign_fences<S, W>(
+    schedule: &mut Schedule<SyncData<S, W>>,
+    fences: &FenceStrategy,
+    host_reads: &FnvHashSet<SubmissionId>,
+) {
+    let mut families: Vec<_> = schedule.iter_mut().collect();
+    families.sort_by_key(|family| family.id());
+
+    let mut next = 0;
+    for queue in families.into_iter().flat_map(|family| family.iter_mut()) {
+        let fenced = match *fences {
+            FenceStrategy::AllQueues => true,
+            FenceStrategy::None => false,
+            FenceStrategy::Explicit(ref queues) => queues.contains(&queue.id()),
+        };
+        let count = queue.len();
+        for (sid, submission) in queue.iter_mut() {
+            let last = sid.index() + 1 == count;
+            if (fenced && last) || host_reads.contains(&sid) {
+                submission.sync_mut().fence = Some(next);
+                next += 1;
+            }
+        }
+    }
+}
+
+/// Get number of fences signaled by synchronized schedule.
+/// Fences are indexed from `0` to returned value.
+pub fn fence_count<S, W>(schedule: &Schedule<SyncData<S, W>>) -> usize {
+    schedule
+        .iter()
+        .flat_map(|family| family.iter())
+        .flat_map(|queue| queue.iter())
+        .filter(|&(_, submission)| submission.sync().fence.is_some())
+        .count()
+}
+
+/// Check if all barriers of synchronized schedule are legal. See `Barrier::is_legal`.
+/// Barriers are legalized when created, so this is checked by debug assertions only.
+pub(crate) fn legal_barriers<S, W>(schedule: &Schedule<SyncData<S, W>>) -> bool {
+    schedule
+        .iter()
+        .flat_map(|family| family.iter())
+        .flat_map(|queue| queue.iter())
+        .all(|(_, submission)| {
+            let sync = submission.sync();
+            sync.frame_begin.is_legal() && sync.acquire.is_legal() && sync.release.is_legal()
+        })
+}
+
+/// Mark guards of conditional submissions as ones that must be recorded anyway.
+///
+/// Skipping main commands removes only accesses of the pass. Dependencies are formed by
+/// barriers, events and semaphores which scopes chain through stages of the pass
+/// whether its commands are executed or not. So recording both guards is enough for
+/// later submissions to find resources in expected states.
+fn mark_conditional<S, W>(schedule: &mut Schedule<SyncData<S, W>>) {
+    for queue in schedule.iter_mut().flat_map(|family| family.iter_mut()) {
+        for (_, submission) in queue.iter_mut() {
+            if submission.is_conditional() {
+                let sync = submission.sync_mut();
+                sync.acquire.must_record_even_if_skipped = true;
+                sync.release.must_record_even_if_skipped = true;
+            }
+        }
+    }
+}
+
+/// Attach self-dependencies to submissions that access images as feedback loops.
+fn assign_feedback_loops<S, W>(
+    schedule: &mut Schedule<SyncData<S, W>>,
+    images: &ImageChains,
+    stencils: &ImageChains,
+    parts: &ImagePartChains,
+) {
+    for queue in schedule.iter_mut().flat_map(|family| family.iter_mut()) {
+        for (_, submission) in queue.iter_mut() {
+            let feedback_loops = feedback_loops(submission, images, stencils, parts);
+            submission.sync_mut().feedback_loops = feedback_loops;
+        }
+    }
+}
+
+/// Get self-dependencies of images the submission accesses as feedback loops, sorted by id.
+fn feedback_loops<T>(
+    submission: &Submission<T>,
+    images: &ImageChains,
+    stencils: &ImageChains,
+    parts: &ImagePartChains,
+) -> Vec<FeedbackLoop> {
+    let whole = submission
+        .images()
+        .map(|(&id, &index)| FeedbackLoop::new(id, images[&id].link(index)));
+    let stencils = submission
+        .stencils()
+        .map(|(&id, &index)| FeedbackLoop::new(id, stencils[&id].link(index)));
+    let parts = submission.parts().flat_map(|(&id, links)| {
+        links
+            .iter()
+            .map(move |&(part, index)| FeedbackLoop::new(id, parts[&id][part].link(index)))
+    });
+    let mut feedback_loops: Vec<_> = whole.chain(stencils).chain(parts).flatten().collect();
+    // Stable sort keeps aspects and parts of the same image in deterministic order.
+    feedback_loops.sort_by_key(|feedback_loop| feedback_loop.id);
+    feedback_loops
+}
+
+/// Insert transition node before each submission which acquire side has barriers or event waits.
+/// Whole acquire side moves to the node, so semaphores waited there still guard the barriers
+/// and the submission, which is the next one on the queue.
+/// The first node of the queue also takes barriers hoisted to the beginning of the frame.
+/// Returns the schedule with nodes. Synchronization and `host_reads` are moved to its ids.
+fn insert_transition_nodes(
+    schedule: &Schedule<Unsynchronized>,
+    sync: &mut SyncTemp,
+    host_reads: &mut FnvHashSet<SubmissionId>,
+) -> Schedule<Unsynchronized> {
+    fn barriers(guard: &Guard<Semaphore, Semaphore>) -> bool {
+        !guard.buffers.is_empty()
+            || !guard.images.is_empty()
+            || !guard.parts.is_empty()
+            || !guard.buffer_parts.is_empty()
+            || !guard.wait_events.is_empty()
+    }
+
+    // New index of each submission and whether node is inserted right before it.
+    let mut indices: FnvHashMap<SubmissionId, (usize, bool)> = FnvHashMap::default();
+    let mut orders = Vec::new();
+    for queue in schedule.iter().flat_map(|family| family.iter()) {
+        let mut index = 0;
+        for (sid, submission) in queue.iter() {
+            let node = submission.kind() != PassKind::SparseBind
+                && sync.0.get(&sid).is_some_and(|sync_data| barriers(&sync_data.acquire));
+            if node {
+                orders.push((submission.submit_order(), false, sid));
+                index += 1;
+            }
+            orders.push((submission.submit_order(), true, sid));
+            indices.insert(sid, (index, node));
+            index += 1;
+        }
+    }
+    // Node is submitted right before its submission.
+    orders.sort();
+    let orders: FnvHashMap<(SubmissionId, bool), usize> = orders
+        .into_iter()
+        .enumerate()
+        .map(|(order, (_, submission, sid))| ((sid, submission), order))
+        .collect();
+
+    let new_sid = |sid: SubmissionId| SubmissionId::new(sid.queue(), indices[&sid].0);
+    let new_point = |point: Point| match (indices[&point.sid], point.side) {
+        ((index, true), Side::Acquire) => {
+            Point::new(SubmissionId::new(point.sid.queue(), index - 1), Side::Acquire)
+        }
+        _ => Point::new(new_sid(point.sid), point.side),
+    };
+    let move_semaphores = |guard: &mut Guard<Semaphore, Semaphore>| {
+        for semaphore in guard
+            .signal
+            .iter_mut()
+            .map(|signal| &mut signal.0)
+            .chain(guard.wait.iter_mut().map(|wait| &mut wait.0))
+        {
+            semaphore.points = new_point(semaphore.points.start)..new_point(semaphore.points.end);
+        }
+    };
+
+    let old = take(&mut sync.0);
+    for (sid, mut sync_data) in old {
+        move_semaphores(&mut sync_data.acquire);
+        move_semaphores(&mut sync_data.release);
+        let (index, node) = indices[&sid];
+        if node {
+            let mut node_data = SyncData::new();
+            node_data.acquire = replace(&mut sync_data.acquire, Guard::new());
+            node_data.frame_begin = replace(&mut sync_data.frame_begin, Guard::new());
+            sync.0.insert(SubmissionId::new(sid.queue(), index - 1), node_data);
+        }
+        sync.0.insert(SubmissionId::new(sid.queue(), index), sync_data);
+    }
+    *host_reads = host_reads.iter().map(|&sid| new_sid(sid)).collect();
+
+    let mut result = Schedule::new();
+    for queue in schedule.iter().flat_map(|family| family.iter()) {
+        let new_queue = result.ensure_queue(queue.id());
+        for (sid, submission) in queue.iter() {
+            if indices[&sid].1 {
+                let node = submission.transition_node(orders[&(sid, false)], Unsynchronized);
+                new_queue.add_submission(node);
+            }
+            let mut submission = submission.set_sync(Unsynchronized);
+            submission.set_submit_order(orders[&(sid, true)]);
+            new_queue.add_submission(submission);
+        }
+    }
+    result
+}
+
+/// Move links of chains to ids of submissions in schedule synchronized with transition nodes.
+/// Link starts at the node inserted before its first submission on the queue,
+/// so it's the node that performs barriers into state of the link.
+fn reindex_chains<S, W>(chains: &mut Chains<SyncData<S, W>>) {
+    // New index of each submission and whether node is inserted right before it.
+    let mut indices: FnvHashMap<QueueId, Vec<(usize, bool)>> = FnvHashMap::default();
+    for queue in chains.schedule.iter().flat_map(|family| family.iter()) {
+        let mut node = false;
+        let queue_indices = indices.entry(queue.id()).or_default();
+        for (sid, submission) in queue.iter() {
+            if submission.is_transition() {
+                node = true;
+            } else {
+                queue_indices.push((sid.index(), node));
+                node = false;
+            }
+        }
+    }
+
+    let buffers = chains
+        .buffers
+        .values_mut()
+        .chain(chains.buffer_parts.values_mut().flatten());
+    for chain in buffers {
+        reindex_chain(chain, &indices);
+    }
+    let images = chains
+        .images
+        .values_mut()
+        .chain(chains.stencils.values_mut())
+        .chain(chains.parts.values_mut().flatten());
+    for chain in images {
+        reindex_chain(chain, &indices);
+    }
+}
+
+fn reindex_chain<R>(chain: &mut Chain<R>, indices: &FnvHashMap<QueueId, Vec<(usize, bool)>>)
+where
+    R: Resource,
+{
+    for index in 0..chain.links().len() {
+        let spans: Vec<_> = chain
+            .link(index)
+            .queues()
+            .map(|(qid, queue)| {
+                let (first, node) = indices[&qid][queue.first];
+                let first = if node { first - 1 } else { first };
+                (qid, first, indices[&qid][queue.last].0)
+            })
+            .collect();
+        for (qid, first, last) in spans {
+            chain.link_mut(index).set_span(qid, first, last);
+        }
+    }
+    for bind in chain.binds_mut() {
+        let index = indices[&bind.sid.queue()][bind.sid.index()].0;
+        bind.sid = SubmissionId::new(bind.sid.queue(), index);
+    }
+}
+
+/// Get chains sorted by id.
+fn sorted<R>(chains: &IdMap<R, Chain<R>>) -> Vec<(Id<R>, &Chain<R>)>
+where
+    R: Resource,
+{
+    let mut sorted: Vec<_> = chains.iter().map(|(&id, chain)| (id, chain)).collect();
+    sorted.sort_by_key(|&(id, _)| id);
+    sorted
+}
+
+/// Get chains of resource parts sorted by id and then by part.
+fn sorted_parts<R>(parts: &IdMap<R, Vec<Chain<R>>>) -> Vec<(Id<R>, &Chain<R>)>
+where
+    R: Resource,
+{
+    let mut sorted: Vec<_> = parts
+        .iter()
+        .flat_map(|(&id, parts)| parts.iter().map(move |chain| (id, chain)))
+        .collect();
+    sorted.sort_by_key(|&(id, _)| id);
+    sorted
+}
+
+/// Sort signals and waits of each guard by points of semaphores and then by resource.
+/// Semaphores are created in this order.
+fn sort_semaphores(sync: &mut SyncTemp) {
+    fn sort_guard(guard: &mut Guard<Semaphore, Semaphore>) {
+        let key = |semaphore: &Semaphore| {
+            let (start, end) = (semaphore.points.start, semaphore.points.end);
+            (start.sid, start.side, end.sid, end.side, semaphore.id)
+        };
+        guard.signal.sort_by_key(|signal| key(&signal.0));
+        guard.wait.sort_by_key(|wait| key(&wait.0));
+    }
+
+    for sync_data in sync.0.values_mut() {
+        sort_guard(&mut sync_data.acquire);
+        sort_guard(&mut sync_data.release);
+    }
+}
+
+fn binary_semaphores<F, S, W>(
+    schedule: &Schedule<Unsynchronized>,
+    mut sync: SyncTemp,
+    mut new_semaphore: F,
+) -> Schedule<SyncData<S, W>>
+where
+    F: FnMut() -> (S, W),
+{
     let mut result = Schedule::default();
     let mut signals: FnvHashMap<Semaphore, Option<S>> = FnvHashMap::default();
     let mut waits: FnvHashMap<Semaphore, Option<W>> = FnvHashMap::default();
 
-    for queue in schedule.iter().flat_map(|family| family.iter()) {
+    // Semaphores are created in order of families, queues and submissions.
+    let mut families: Vec<_> = schedule.iter().collect();
+    families.sort_by_key(|family| family.id());
+    for queue in families.into_iter().flat_map(|family| family.iter()) {
         let new_queue = result.ensure_queue(queue.id());
         for (sid, submission) in queue.iter() {
             let sync = if let Some(sync) = sync.0.remove(&sid) {
@@ -413,9 +1621,123 @@ where
     result
 }
 
+fn timelines<F, S, W>(
+    schedule: &Schedule<Unsynchronized>,
+    mut sync: SyncTemp,
+    mut new_semaphore: F,
+) -> Schedule<SyncData<S, W>>
+where
+    F: FnMut() -> (S, W),
+    S: Clone,
+    W: Clone,
+{
+    // Each semaphore is replaced by wait for the value signaled by submission at its start point.
+    // Semaphores always point to submissions with greater submit order,
+    // so waiting for whole submission can't introduce deadlock.
+    let mut waits: FnvHashMap<SubmissionId, FnvHashMap<QueueId, (u64, PipelineStage)>> =
+        FnvHashMap::default();
+    let mut signaled = FnvHashMap::default();
+    for (&sid, sync_data) in &sync.0 {
+        for wait in sync_data.acquire.wait.iter().chain(&sync_data.release.wait) {
+            let signal_sid = wait.semaphore().points.start.sid;
+            let value = signal_sid.index() as u64 + 1;
+            let entry = waits
+                .entry(sid)
+                .or_default()
+                .entry(signal_sid.queue())
+                .or_insert((value, PipelineStage::empty()));
+            entry.0 = max(entry.0, value);
+            entry.1 |= wait.stage();
+            signaled.insert(signal_sid, value);
+        }
+    }
+
+    // Sparse binding doesn't respect queue order.
+    // Order it with adjacent submissions so that values are signaled in increasing order.
+    for sid in bind_submissions(schedule) {
+        let mut order = |wait_sid: SubmissionId, signal_sid: SubmissionId, stages| {
+            let value = signal_sid.index() as u64 + 1;
+            let entry = waits
+                .entry(wait_sid)
+                .or_default()
+                .entry(signal_sid.queue())
+                .or_insert((value, PipelineStage::empty()));
+            entry.0 = max(entry.0, value);
+            entry.1 |= stages;
+            signaled.insert(signal_sid, value);
+        };
+        if sid.index() > 0 {
+            let prev = SubmissionId::new(sid.queue(), sid.index() - 1);
+            order(sid, prev, PipelineStage::empty());
+        }
+        if sid.index() + 1 < schedule.queue(sid.queue()).unwrap().len() {
+            let next = SubmissionId::new(sid.queue(), sid.index() + 1);
+            order(next, sid, PipelineStage::BOTTOM_OF_PIPE);
+        }
+    }
+
+    let mut queues: Vec<QueueId> = signaled.keys().map(|sid| sid.queue()).collect();
+    queues.sort();
+    queues.dedup();
+    let semaphores: FnvHashMap<QueueId, (S, W)> = queues
+        .into_iter()
+        .map(|qid| (qid, new_semaphore()))
+        .collect();
+
+    let mut families: Vec<_> = schedule.iter().collect();
+    families.sort_by_key(|family| family.id());
+    let mut result = Schedule::default();
+    for queue in families.into_iter().flat_map(|family| family.iter()) {
+        let new_queue = result.ensure_queue(queue.id());
+        for (sid, submission) in queue.iter() {
+            // Semaphores are replaced by timeline waits and signals below.
+            let mut sync_data = match sync.0.remove(&sid) {
+                Some(mut old) => {
+                    old.acquire.wait.clear();
+                    old.acquire.signal.clear();
+                    old.release.wait.clear();
+                    old.release.signal.clear();
+                    old.convert_signal(|_| unreachable!())
+                        .convert_wait(|_| unreachable!())
+                }
+                None => SyncData::new(),
+            };
+            if let Some(waits) = waits.remove(&sid) {
+                let mut waits: Vec<_> = waits.into_iter().collect();
+                waits.sort_by_key(|&(qid, _)| qid);
+                sync_data.acquire.wait = waits
+                    .into_iter()
+                    .map(|(qid, (value, stages))| Wait(semaphores[&qid].1.clone(), stages, value))
+                    .collect();
+            }
+            if let Some(&value) = signaled.get(&sid) {
+                sync_data
+                    .release
+                    .signal
+                    .push(Signal(semaphores[&sid.queue()].0.clone(), value));
+            }
+            let new_sid = new_queue.add_submission(submission.set_sync(sync_data));
+            assert_eq!(sid, new_sid);
+        }
+    }
+
+    result
+}
+
+/// Find submissions that perform sparse binding.
+fn bind_submissions<S>(schedule: &Schedule<S>) -> FnvHashSet<SubmissionId> {
+    schedule
+        .iter()
+        .flat_map(|family| family.iter())
+        .flat_map(|queue| queue.iter())
+        .filter(|&(_, submission)| submission.kind() == PassKind::SparseBind)
+        .map(|(sid, _)| sid)
+        .collect()
+}
+
 // submit_order creates a consistant direction in which semaphores are generated, avoiding issues
 // with deadlocks.
-fn latest<R, S>(link: &Link<R>, schedule: &Schedule<S>) -> SubmissionId
+pub(crate) fn latest<R, S>(link: &Link<R>, schedule: &Schedule<S>) -> SubmissionId
 where
     R: Resource,
 {
@@ -429,7 +1751,7 @@ where
         .unwrap();
     sid
 }
-fn earliest<R, S>(link: &Link<R>, schedule: &Schedule<S>) -> SubmissionId
+pub(crate) fn earliest<R, S>(link: &Link<R>, schedule: &Schedule<S>) -> SubmissionId
 where
     R: Resource,
 {
@@ -468,24 +1790,178 @@ fn generate_semaphore_pair<R: Resource>(
     }
 }
 
-fn sync_chain<R, S>(id: Id<R>, chain: &Chain<R>, schedule: &Schedule<S>, sync: &mut SyncTemp)
+/// Generate semaphore pair even if both points are on the same queue.
+fn generate_bind_semaphore_pair(
+    sync: &mut SyncTemp,
+    id: Uid,
+    range: Range<SubmissionId>,
+    stages: PipelineStage,
+) {
+    let points = Point::new(range.start, Side::Release)..Point::new(range.end, Side::Acquire);
+    let semaphore = Semaphore::new(id, points.clone());
+    sync.get_sync(points.start.sid)
+        .release
+        .signal
+        .push(Signal::new(semaphore.clone()));
+    sync.get_sync(points.end.sid)
+        .acquire
+        .wait
+        .push(Wait::new(semaphore, stages));
+}
+
+/// Order sparse bindings after accesses of the previous link and before the next link.
+/// Sparse binding doesn't respect queue order so semaphores are generated even
+/// between submissions of the same queue. Bindings don't need barriers.
+fn sync_binds<R>(uid: Uid, chain: &Chain<R>, sync: &mut SyncTemp)
 where
     R: Resource,
-    Id<R>: Into<Uid>,
+{
+    let binds = chain.binds();
+    for (index, bind) in binds.iter().enumerate() {
+        let prev = if index > 0 { binds.get(index - 1) } else { None };
+        match prev {
+            Some(prev) if prev.link == bind.link => {
+                generate_bind_semaphore_pair(sync, uid, prev.sid..bind.sid, PipelineStage::empty());
+            }
+            _ if bind.link > 0 => {
+                for (queue_id, queue) in chain.link(bind.link - 1).queues() {
+                    let tail = SubmissionId::new(queue_id, queue.last);
+                    generate_bind_semaphore_pair(sync, uid, tail..bind.sid, PipelineStage::empty());
+                }
+            }
+            _ => {}
+        }
+
+        let next_bound = binds
+            .get(index + 1)
+            .is_some_and(|next| next.link == bind.link);
+        if !next_bound && bind.link < chain.links().len() {
+            let link = chain.link(bind.link);
+            for (queue_id, queue) in link.queues() {
+                let head = SubmissionId::new(queue_id, queue.first);
+                generate_bind_semaphore_pair(sync, uid, bind.sid..head, queue.stages);
+            }
+        }
+    }
+}
+
+/// Check if barrier between links is pure overhead.
+/// Read-only links with the same layout don't need memory dependency
+/// unless the next link reads with access types the previous one didn't.
+/// Execution dependency is still provided by semaphores between queues.
+fn redundant_barrier<R>(prev_link: &Link<R>, link: &Link<R>) -> bool
+where
+    R: Resource,
+{
+    let (prev, next) = (prev_link.state(), link.state());
+    !prev.access.is_write()
+        && !next.access.is_write()
+        && prev.layout == next.layout
+        && next.access & prev.access == next.access
+}
+
+/// Get submissions range between links if split barrier should be used for them.
+fn split_barrier<R>(
+    prev_link: &Link<R>,
+    link: &Link<R>,
+    options: &SyncOptions,
+) -> Option<Range<SubmissionId>>
+where
+    R: Resource,
+{
+    let min_slack = options.split_barriers?;
+    if !prev_link.single_queue() || !link.single_queue() {
+        return None;
+    }
+    let (prev_qid, prev_queue) = prev_link.queues().next().unwrap();
+    let (qid, queue) = link.queues().next().unwrap();
+    // Dependencies without submissions between always use pipeline barriers.
+    if prev_qid != qid || queue.first - prev_queue.last <= max(min_slack, 1) {
+        return None;
+    }
+    Some(SubmissionId::new(prev_qid, prev_queue.last)..SubmissionId::new(qid, queue.first))
+}
+
+/// Assign indices to events and add them to submissions.
+fn assign_events(sync: &mut SyncTemp) {
+    let mut events: Vec<_> = sync.1.drain().collect();
+    events.sort_by_key(|&(pair, _)| pair);
+    for (index, ((signal_sid, wait_sid), mut event)) in events.into_iter().enumerate() {
+        let mut stages = PipelineStage::empty()..PipelineStage::empty();
+        for barrier in event.buffers.values() {
+            stages.start |= barrier.states.start.stages;
+            stages.end |= barrier.states.end.stages;
+        }
+        for barrier in event.images.values() {
+            stages.start |= barrier.states.start.stages;
+            stages.end |= barrier.states.end.stages;
+        }
+        for (_, barrier) in &event.parts {
+            stages.start |= barrier.states.start.stages;
+            stages.end |= barrier.states.end.stages;
+        }
+        for (_, barrier) in &event.buffer_parts {
+            stages.start |= barrier.states.start.stages;
+            stages.end |= barrier.states.end.stages;
+        }
+        event.event = index;
+        event.stages = legal_stages(stages);
+        sync.get_sync(signal_sid).release.set_events.push(SetEvent {
+            event: index,
+            stages: event.stages.start,
+        });
+        sync.get_sync(wait_sid).acquire.wait_events.push(event);
+    }
+}
+
+fn sync_chain<R, S>(
+    id: Id<R>,
+    uid: Uid,
+    chain: &Chain<R>,
+    schedule: &Schedule<S>,
+    options: &SyncOptions,
+    sync: &mut SyncTemp,
+) where
+    R: Resource,
     Guard<Semaphore, Semaphore>: Pick<R, Target = Barriers<R>>,
+    WaitEvent: Pick<R, Target = Barriers<R>>,
 {
-    let uid = id.into();
-    for (prev_link, link) in chain.links().windows(2).map(|pair| (&pair[0], &pair[1])) {
-        if prev_link.family() == link.family() {
+    for (index, pair) in chain.links().windows(2).enumerate() {
+        let (prev_link, link) = (&pair[0], &pair[1]);
+        // Ownership transfers keep the content regardless.
+        let discard = chain.discards(index + 1);
+        // Event wait isn't ordered after sparse binding performed between links.
+        // And binding doesn't make memory visible, so barrier can't be dropped.
+        let bound = chain.binds().iter().any(|bind| bind.link == index + 1);
+        let split = if bound {
+            None
+        } else {
+            split_barrier(prev_link, link, options)
+        };
+        if let Some(range) = split {
+            // Dependency on the same queue with enough submissions between.
+            sync.1
+                .entry((range.start, range.end))
+                .or_insert_with(WaitEvent::new)
+                .pick_mut()
+                .insert(id, Barrier::between(prev_link, link, chain.range(), discard));
+            continue;
+        }
+
+        // Resources shared concurrently don't require ownership transfer.
+        // Semaphores make writes visible to other queue families.
+        if prev_link.family() == link.family() || chain.sharing() == SharingMode::Concurrent {
             // Prefer to generate barriers on the acquire side, if possible.
             if prev_link.single_queue() && !link.single_queue() {
                 let signal_sid = latest(prev_link, schedule);
 
                 // Generate barrier in prev link's last submission.
-                sync.get_sync(signal_sid)
-                    .release
-                    .pick_mut()
-                    .insert(id, Barrier::new(prev_link.state()..link.state()));
+                if bound || !redundant_barrier(prev_link, link) {
+                    sync.get_sync(signal_sid)
+                        .release
+                        .pick_mut()
+                        .insert(id, Barrier::between(prev_link, link, chain.range(), discard));
+                }
 
                 // Generate semaphores between queues in the previous link and the current one.
                 for (queue_id, queue) in link.queues() {
@@ -514,10 +1990,12 @@ where
                 }
 
                 // Generate barrier in next link's first submission.
-                sync.get_sync(wait_sid)
-                    .acquire
-                    .pick_mut()
-                    .insert(id, Barrier::new(prev_link.state()..link.state()));
+                if bound || !redundant_barrier(prev_link, link) {
+                    sync.get_sync(wait_sid)
+                        .acquire
+                        .pick_mut()
+                        .insert(id, Barrier::between(prev_link, link, chain.range(), discard));
+                }
 
                 if !link.single_queue() {
                     // Delay other queues in the link until the barrier finishes
@@ -574,6 +2052,7 @@ where
                         ..prev_link.queue_state(signal_sid.queue())
                     }..,
                     ..link.state().layout,
+                    chain.range(),
                 ),
             );
             sync.get_sync(wait_sid).acquire.pick_mut().insert(
@@ -585,6 +2064,7 @@ where
                         access: link.state().access,
                         ..link.queue_state(wait_sid.queue())
                     },
+                    chain.range(),
                 ),
             );
 
@@ -605,45 +2085,502 @@ where
             }
         }
     }
+
+    sync_binds(uid, chain, sync);
+
+    let info = chain.info();
+    let present = info.present && R::Layout::present().is_some();
+    let initial = info.initial_state.is_some() || info.host_write_before;
+    if info.import.is_some() || (!present && initial) {
+        sync_initial(id, uid, chain, schedule, options, sync);
+    }
+    if info.export.is_some() || info.host_read_after {
+        sync_final(id, uid, chain, schedule, sync);
+    }
+}
+
+/// Synchronize chains of the image parts.
+/// Barriers are stored in `parts` of guards and events instead of `images`.
+fn sync_parts<S>(
+    id: Id<Image>,
+    parts: &[Chain<Image>],
+    schedule: &Schedule<S>,
+    options: &SyncOptions,
+    sync: &mut SyncTemp,
+) {
+    for (index, chain) in parts.iter().enumerate() {
+        let mut part = SyncTemp(FnvHashMap::default(), FnvHashMap::default());
+        let uid = Uid::Part(id.index(), index);
+        sync_chain(id, uid, chain, schedule, options, &mut part);
+
+        let SyncTemp(syncs, events) = part;
+        for (sid, new) in syncs {
+            let sync_data = sync.get_sync(sid);
+            for (guard, new) in [
+                (&mut sync_data.acquire, new.acquire),
+                (&mut sync_data.release, new.release),
+            ] {
+                guard.parts.extend(new.images);
+                guard.wait.extend(new.wait);
+                guard.signal.extend(new.signal);
+            }
+            sync_data.frame_begin.parts.extend(new.frame_begin.images);
+        }
+        for (pair, new) in events {
+            sync.1
+                .entry(pair)
+                .or_insert_with(WaitEvent::new)
+                .parts
+                .extend(new.images);
+        }
+    }
+}
+
+/// Synchronize chains of the buffer parts.
+/// Barriers are stored in `buffer_parts` of guards and events instead of `buffers`.
+fn sync_buffer_parts<S>(
+    id: Id<Buffer>,
+    parts: &[Chain<Buffer>],
+    schedule: &Schedule<S>,
+    options: &SyncOptions,
+    sync: &mut SyncTemp,
+) {
+    for (index, chain) in parts.iter().enumerate() {
+        let mut part = SyncTemp(FnvHashMap::default(), FnvHashMap::default());
+        let uid = Uid::BufferPart(id.index(), index);
+        sync_chain(id, uid, chain, schedule, options, &mut part);
+
+        let SyncTemp(syncs, events) = part;
+        for (sid, new) in syncs {
+            let sync_data = sync.get_sync(sid);
+            for (guard, new) in [
+                (&mut sync_data.acquire, new.acquire),
+                (&mut sync_data.release, new.release),
+            ] {
+                guard.buffer_parts.extend(new.buffers);
+                guard.wait.extend(new.wait);
+                guard.signal.extend(new.signal);
+            }
+            sync_data.frame_begin.buffer_parts.extend(new.frame_begin.buffers);
+        }
+        for (pair, new) in events {
+            sync.1
+                .entry(pair)
+                .or_insert_with(WaitEvent::new)
+                .buffer_parts
+                .extend(new.buffers);
+        }
+    }
+}
+
+/// Regenerate barriers of the buffer in synchronized schedule.
+/// See `update_barriers`.
+pub(crate) fn update_buffer_barriers<S, W>(
+    id: Id<Buffer>,
+    chain: &Chain<Buffer>,
+    schedule: &mut Schedule<SyncData<S, W>>,
+    options: &SyncOptions,
+) {
+    let mut sync = SyncTemp(FnvHashMap::default(), FnvHashMap::default());
+    sync_chain(id, id.into(), chain, schedule, options, &mut sync);
+    update_barriers(id, chain, schedule, sync);
+}
+
+/// Regenerate barriers of the image in synchronized schedule.
+/// See `update_barriers`.
+pub(crate) fn update_image_barriers<S, W>(
+    id: Id<Image>,
+    chain: &Chain<Image>,
+    schedule: &mut Schedule<SyncData<S, W>>,
+    options: &SyncOptions,
+) {
+    let mut sync = SyncTemp(FnvHashMap::default(), FnvHashMap::default());
+    sync_chain(id, id.into(), chain, schedule, options, &mut sync);
+    if chain.info().present {
+        sync_present(id, chain, schedule, &mut sync);
+    }
+    update_barriers(id, chain, schedule, sync);
+    update_feedback_loops(id, chain, schedule);
+}
+
+/// Replace self-dependencies of the image in submissions of the chain.
+/// The chain must track the whole image.
+fn update_feedback_loops<S, W>(
+    id: Id<Image>,
+    chain: &Chain<Image>,
+    schedule: &mut Schedule<SyncData<S, W>>,
+) {
+    for (index, link) in chain.links().iter().enumerate() {
+        for (qid, queue) in link.queues() {
+            for sid in (queue.first..queue.last + 1).map(|index| SubmissionId::new(qid, index)) {
+                let submission = &mut schedule[sid];
+                if Pick::<Image>::pick(submission).get(&id) != Some(&index) {
+                    continue;
+                }
+                let feedback_loops = &mut submission.sync_mut().feedback_loops;
+                feedback_loops.retain(|feedback_loop| feedback_loop.id != id);
+                feedback_loops.extend(FeedbackLoop::new(id, link));
+                feedback_loops.sort_by_key(|feedback_loop| feedback_loop.id);
+            }
+        }
+    }
+}
+
+/// Replace barriers of the resource with ones from `sync`.
+/// Semaphores and events are kept as is.
+/// Links of the chain must have the same submissions and stages
+/// as ones the schedule was synchronized with.
+fn update_barriers<R, S, W>(
+    id: Id<R>,
+    chain: &Chain<R>,
+    schedule: &mut Schedule<SyncData<S, W>>,
+    sync: SyncTemp,
+) where
+    R: Resource,
+    Guard<Semaphore, Semaphore>: Pick<R, Target = Barriers<R>>,
+    Guard<S, W>: Pick<R, Target = Barriers<R>>,
+    WaitEvent: Pick<R, Target = Barriers<R>>,
+{
+    // Barriers are recorded only at the first and the last submissions of the link's queues.
+    // Or hoisted to the first submission of the queue.
+    for link in chain.links() {
+        for (qid, queue) in link.queues() {
+            schedule[SubmissionId::new(qid, 0)]
+                .sync_mut()
+                .frame_begin
+                .pick_mut()
+                .remove(&id);
+            for &index in &[queue.first, queue.last] {
+                let sync_data = schedule[SubmissionId::new(qid, index)].sync_mut();
+                for guard in [&mut sync_data.acquire, &mut sync_data.release] {
+                    guard.pick_mut().remove(&id);
+                    for event in &mut guard.wait_events {
+                        event.pick_mut().remove(&id);
+                    }
+                }
+            }
+        }
+    }
+
+    let SyncTemp(syncs, events) = sync;
+    for (sid, mut new) in syncs {
+        let sync_data = schedule[sid].sync_mut();
+        sync_data.acquire.pick_mut().extend(new.acquire.pick_mut().drain());
+        sync_data.release.pick_mut().extend(new.release.pick_mut().drain());
+        sync_data.frame_begin.pick_mut().extend(new.frame_begin.pick_mut().drain());
+    }
+    for ((signal_sid, wait_sid), mut new) in events {
+        let event = schedule[signal_sid]
+            .sync()
+            .release
+            .set_events
+            .iter()
+            .map(|set| set.event)
+            .find(|&event| {
+                schedule[wait_sid]
+                    .sync()
+                    .acquire
+                    .wait_events
+                    .iter()
+                    .any(|wait| wait.event == event)
+            })
+            .expect("Event must be generated for the same links");
+        let wait = schedule[wait_sid]
+            .sync_mut()
+            .acquire
+            .wait_events
+            .iter_mut()
+            .find(|wait| wait.event == event)
+            .unwrap();
+        wait.pick_mut().extend(new.pick_mut().drain());
+    }
+}
+
+/// Transition the first link from the known initial state of the resource.
+/// Host writes performed before schedule execution are made visible this way too.
+fn sync_initial<R, S>(
+    id: Id<R>,
+    uid: Uid,
+    chain: &Chain<R>,
+    schedule: &Schedule<S>,
+    options: &SyncOptions,
+    sync: &mut SyncTemp,
+) where
+    R: Resource,
+    Guard<Semaphore, Semaphore>: Pick<R, Target = Barriers<R>>,
+{
+    let link = &chain.links()[0];
+    let wait_sid = earliest(link, schedule);
+
+    // Generate barrier in link's first submission.
+    let mut initial = chain.initial_state().state;
+    if chain.discards(0) {
+        initial.access = R::Access::none();
+        initial.layout = R::Layout::discard_content();
+    }
+    let barrier = match chain.info().import {
+        Some(import) => {
+            // Acquire ownership from external family.
+            let queues = QueueId::new(import.family, 0)..wait_sid.queue();
+            Barrier::acquire(queues, initial.layout.., ..link.state(), chain.range())
+        }
+        None => Barrier::new(initial..link.state(), chain.range().cloned()),
+    };
+    let barrier = if options.hoist_initial_transitions && hoistable(chain, &barrier) {
+        // Depth and stencil chains of the image can't share the barrier.
+        let first = SubmissionId::new(wait_sid.queue(), 0);
+        match sync.get_sync(first).frame_begin.pick_mut().entry(id) {
+            Entry::Vacant(entry) => {
+                entry.insert(barrier);
+                None
+            }
+            Entry::Occupied(_) => Some(barrier),
+        }
+    } else {
+        Some(barrier)
+    };
+    if let Some(barrier) = barrier {
+        sync.get_sync(wait_sid).acquire.pick_mut().insert(id, barrier);
+    }
+
+    if !link.single_queue() {
+        // Delay other queues in the link until the barrier finishes
+        for (queue_id, queue) in link.queues() {
+            if queue_id != wait_sid.queue() {
+                let head = SubmissionId::new(queue_id, queue.first);
+                generate_semaphore_pair(
+                    sync,
+                    uid,
+                    link,
+                    wait_sid..head,
+                    Side::Acquire..Side::Acquire,
+                );
+            }
+        }
+    }
+}
+
+/// Check if initial barrier can be performed at the beginning of the frame.
+/// Layout transition from `Undefined` with no access doesn't wait for anything
+/// but memory binding, which is performed by sparse binding passes after the frame begins.
+fn hoistable<R>(chain: &Chain<R>, barrier: &Barrier<R>) -> bool
+where
+    R: Resource,
+{
+    let states = &barrier.states;
+    barrier.queues.is_none()
+        && chain.binds().is_empty()
+        && states.start.access == R::Access::none()
+        && states.start.layout == R::Layout::discard_content()
+        && states.start.layout != states.end.layout
+}
+
+/// Make device accesses from the last link visible to host reads after schedule execution.
+/// Or release ownership to external family if resource is exported.
+fn sync_final<R, S>(
+    id: Id<R>,
+    uid: Uid,
+    chain: &Chain<R>,
+    schedule: &Schedule<S>,
+    sync: &mut SyncTemp,
+) where
+    R: Resource,
+    Guard<Semaphore, Semaphore>: Pick<R, Target = Barriers<R>>,
+{
+    let link = chain.links().last().unwrap();
+    let signal_sid = latest(link, schedule);
+
+    if !link.single_queue() {
+        // Delay the last submission in the queue until other queues finish
+        for (queue_id, queue) in link.queues() {
+            if queue_id != signal_sid.queue() {
+                let tail = SubmissionId::new(queue_id, queue.last);
+                generate_semaphore_pair(
+                    sync,
+                    uid,
+                    link,
+                    tail..signal_sid,
+                    Side::Release..Side::Release,
+                );
+            }
+        }
+    }
+
+    // Generate barrier in link's last submission.
+    let barrier = match chain.info().export {
+        Some(export) => {
+            let queues = signal_sid.queue()..QueueId::new(export.family, 0);
+            Barrier::release(queues, link.state().., ..export.state.layout, chain.range())
+        }
+        None => {
+            let host = State {
+                access: R::Access::host_read(),
+                stages: PipelineStage::HOST,
+                ..link.state()
+            };
+            Barrier::new(link.state()..host, chain.range().cloned())
+        }
+    };
+    sync.get_sync(signal_sid).release.pick_mut().insert(id, barrier);
+}
+
+/// Synchronize present image with presentation engine.
+/// Image is transitioned from `Undefined` layout after acquisition
+/// and to `Present` layout before presentation.
+fn sync_present<S>(
+    id: Id<Image>,
+    chain: &Chain<Image>,
+    schedule: &Schedule<S>,
+    sync: &mut SyncTemp,
+) {
+    let uid = id.into();
+    let first = &chain.links()[0];
+    let last = chain.links().last().unwrap();
+
+    // Wait for acquisition at stages of the first link and transition image from `Undefined`.
+    // Other queues of the link wait for the transition at their own stages.
+    let wait_sid = earliest(first, schedule);
+    let acquired = State {
+        access: ImageAccess::empty(),
+        layout: ImageLayout::Undefined,
+        stages: first.state().stages,
+    };
+    {
+        let acquire = &mut sync.get_sync(wait_sid).acquire;
+        acquire
+            .wait_acquired
+            .push(Wait::new(id, first.queue(wait_sid.queue()).stages));
+        acquire
+            .images
+            .insert(id, Barrier::new(acquired..first.state(), None));
+    }
+    if !first.single_queue() {
+        // Delay other queues in the link until the barrier finishes
+        for (queue_id, queue) in first.queues() {
+            if queue_id != wait_sid.queue() {
+                let head = SubmissionId::new(queue_id, queue.first);
+                generate_semaphore_pair(
+                    sync,
+                    uid,
+                    first,
+                    wait_sid..head,
+                    Side::Acquire..Side::Acquire,
+                );
+            }
+        }
+    }
+
+    // Transition image to `Present` layout after last link.
+    let signal_sid = latest(last, schedule);
+    if !last.single_queue() {
+        // Delay the last submission in the queue until other queues finish
+        for (queue_id, queue) in last.queues() {
+            if queue_id != signal_sid.queue() {
+                let tail = SubmissionId::new(queue_id, queue.last);
+                generate_semaphore_pair(
+                    sync,
+                    uid,
+                    last,
+                    tail..signal_sid,
+                    Side::Release..Side::Release,
+                );
+            }
+        }
+    }
+    let present = State {
+        access: ImageAccess::empty(),
+        layout: ImageLayout::Present,
+        stages: PipelineStage::BOTTOM_OF_PIPE,
+    };
+    let release = &mut sync.get_sync(signal_sid).release;
+    release
+        .images
+        .insert(id, Barrier::new(last.state()..present, None));
+    release.signal_present.push(Signal::new(id));
+}
+
+/// Wait that makes other waits from the same queue redundant.
+/// Stages of removed waits are folded into it.
+struct Covering {
+    pos: (usize, Side),
+    point: Point,
+    semaphore: Semaphore,
 }
 
 fn optimize_side(
+    point: Point,
+    binds: &FnvHashSet<SubmissionId>,
     guard: &mut Guard<Semaphore, Semaphore>,
     to_remove: &mut Vec<Semaphore>,
-    found: &mut FnvHashMap<QueueId, (usize, Side)>,
+    to_widen: &mut Vec<(Point, Semaphore, PipelineStage)>,
+    found: &mut FnvHashMap<QueueId, Covering>,
 ) {
-    guard
-        .wait
-        .sort_unstable_by_key(|wait| (wait.stage(), wait.semaphore().points.end.sid.index()));
+    // Latest signals first. They make earlier signals from the same queue redundant.
+    guard.wait.sort_by_key(|wait| {
+        let start = wait.semaphore().points.start;
+        (Reverse((start.sid.index(), start.side)), wait.semaphore().id)
+    });
     guard.wait.retain(|wait| {
         let start = wait.semaphore().points.start;
+        if binds.contains(&start.sid) {
+            // Sparse binding isn't covered by later signals from its queue.
+            return true;
+        }
         let pos = (start.sid.index(), start.side);
-        if let Some(synched_to) = found.get_mut(&start.sid.queue()) {
-            if *synched_to >= pos {
+        if let Some(covering) = found.get_mut(&start.sid.queue()) {
+            if covering.pos >= pos {
                 to_remove.push(wait.semaphore().clone());
+                to_widen.push((covering.point, covering.semaphore.clone(), wait.stage()));
                 return false;
             } else {
-                *synched_to = pos;
+                *covering = Covering {
+                    pos,
+                    point,
+                    semaphore: wait.semaphore().clone(),
+                };
                 return true;
             }
         }
 
-        found.insert(start.sid.queue(), pos);
+        found.insert(
+            start.sid.queue(),
+            Covering {
+                pos,
+                point,
+                semaphore: wait.semaphore().clone(),
+            },
+        );
         true
     });
 }
 
 fn optimize_submission(
     sid: SubmissionId,
+    binds: &FnvHashSet<SubmissionId>,
     to_remove: &mut Vec<Semaphore>,
-    found: &mut FnvHashMap<QueueId, (usize, Side)>,
+    to_widen: &mut Vec<(Point, Semaphore, PipelineStage)>,
+    found: &mut FnvHashMap<QueueId, Covering>,
     sync: &mut SyncTemp,
 ) {
     {
         let sync_data = sync.0.get_mut(&sid);
         if let Some(sync_data) = sync_data {
-            optimize_side(&mut sync_data.acquire, to_remove, found);
-            optimize_side(&mut sync_data.release, to_remove, found);
+            optimize_side(
+                Point::new(sid, Side::Acquire),
+                binds,
+                &mut sync_data.acquire,
+                to_remove,
+                to_widen,
+                found,
+            );
+            optimize_side(
+                Point::new(sid, Side::Release),
+                binds,
+                &mut sync_data.release,
+                to_remove,
+                to_widen,
+                found,
+            );
         } else {
             return;
         }
@@ -665,12 +2602,29 @@ fn optimize_submission(
     }
 }
 
+/// Remove waits (and paired signals) for points already waited by earlier waits on the same queue.
+/// Stages of removed waits are added to the wait that covers them.
+/// Sparse binding submissions aren't ordered by their queues,
+/// so semaphores to and from them are kept.
 fn optimize<S>(schedule: &Schedule<S>, sync: &mut SyncTemp) {
+    let binds = bind_submissions(schedule);
     let mut to_remove = Vec::new();
+    let mut to_widen = Vec::new();
     for queue in schedule.iter().flat_map(|family| family.iter()) {
         let mut found = FnvHashMap::default();
-        for (sid, _) in queue.iter() {
-            optimize_submission(sid, &mut to_remove, &mut found, sync);
+        for (sid, _) in queue.iter().filter(|&(sid, _)| !binds.contains(&sid)) {
+            optimize_submission(sid, &binds, &mut to_remove, &mut to_widen, &mut found, sync);
         }
     }
+
+    for (point, semaphore, stage) in to_widen {
+        let wait = sync
+            .get_sync(point.sid)
+            .get_mut(point.side)
+            .wait
+            .iter_mut()
+            .find(|wait| wait.0 == semaphore)
+            .unwrap();
+        wait.1 |= stage;
+    }
 }
@@ -0,0 +1,471 @@
+//!
+//! This module provides synchronization for executing only a prefix of synchronized schedule.
+//! Tools that step through the frame may submit the prefix and inspect resources it accessed,
+//! as all of them are transitioned into known states at the end.
+//!
+
+use alloc::vec::Vec;
+use collections::{FnvHashMap, FnvHashSet};
+use core::cmp::{max, min};
+use core::hash::Hash;
+use hal::buffer::Access as BufferAccess;
+use hal::image::{Access as ImageAccess, Layout as ImageLayout};
+use hal::pso::PipelineStage;
+
+use chain::{Chain, Link};
+use collect::Chains;
+use resource::{Buffer, BufferLayout, Id, IdMap, Image, Range as ResourceRange, Resource, State};
+use schedule::{QueueId, Schedule, SubmissionId};
+use sync::{legal_barriers, Barrier, Barriers, Guard, PartBarriers, Signal, SyncData, Wait};
+use Pick;
+
+/// States resources accessed by the executed prefix of the schedule are left in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize))]
+pub struct ParkingStates {
+    /// State of buffers and their parts.
+    pub buffers: State<Buffer>,
+
+    /// State of images, their stencil aspects and parts.
+    pub images: State<Image>,
+}
+
+impl Default for ParkingStates {
+    /// Buffers are readable by host and images are in `General` layout readable by transfers.
+    fn default() -> Self {
+        ParkingStates {
+            buffers: State {
+                access: BufferAccess::HOST_READ,
+                layout: BufferLayout,
+                stages: PipelineStage::HOST,
+            },
+            images: State {
+                access: ImageAccess::TRANSFER_READ,
+                layout: ImageLayout::General,
+                stages: PipelineStage::TRANSFER,
+            },
+        }
+    }
+}
+
+/// Number of submissions of each queue in the prefix.
+struct Prefix(FnvHashMap<QueueId, usize>);
+
+impl Prefix {
+    fn contains(&self, sid: SubmissionId) -> bool {
+        self.0.get(&sid.queue()).is_some_and(|&len| sid.index() < len)
+    }
+}
+
+/// Semaphore that orders tail of the link on another queue before the parking barrier.
+#[derive(Clone, Copy, Debug)]
+struct Dependency {
+    signal: SubmissionId,
+    wait: SubmissionId,
+    stages: PipelineStage,
+}
+
+impl<T> Chains<SyncData<T, T>>
+where
+    T: Clone + Eq + Hash,
+{
+    /// Get synchronization for executing only submissions up to `upto` in submit order,
+    /// `upto` included. After the prefix is executed every resource it accessed
+    /// is in the state from `parking`. `T` is semaphore identifier used in the schedule.
+    ///
+    /// Submissions after the cut are dropped, along with semaphores and events they wait for.
+    /// Barrier that transitions the resource out of the last link accessed by the prefix
+    /// is replaced by barrier into the parking state on the queue that accesses the link latest.
+    /// If the cut lands between release and acquire of queue family ownership,
+    /// the release is replaced too, so the resource stays owned by the releasing family.
+    /// Other queues of the link are waited by semaphores created by `new_semaphore`,
+    /// which are timelines if the schedule signals timeline values.
+    ///
+    /// Resources not accessed by the prefix have no barriers and present images aren't presented.
+    /// The last submission of each queue signals a fence, so host can wait for the prefix.
+    /// Fences and events are indexed densely again. Queues keep their ids.
+    pub fn truncate_sync<F>(
+        &self,
+        upto: SubmissionId,
+        parking: ParkingStates,
+        mut new_semaphore: F,
+    ) -> Schedule<SyncData<T, T>>
+    where
+        F: FnMut() -> T,
+    {
+        let cut = self.schedule[upto].submit_order();
+        let mut families: Vec<_> = self.schedule.iter().collect();
+        families.sort_by_key(|family| family.id());
+
+        // Submissions of each queue are submitted in order, so prefix of the queue is contiguous.
+        let mut prefix = Prefix(FnvHashMap::default());
+        let mut timelines = false;
+        let mut result = Schedule::default();
+        for queue in families.into_iter().flat_map(|family| family.iter()) {
+            let new_queue = result.ensure_queue(queue.id());
+            for (sid, submission) in queue.iter() {
+                let sync = submission.sync();
+                timelines |= sync
+                    .acquire
+                    .signal
+                    .iter()
+                    .chain(&sync.release.signal)
+                    .any(|signal| signal.value() != 0);
+                if submission.submit_order() <= cut {
+                    let new_sid = new_queue.add_submission(submission.set_sync(sync.clone()));
+                    assert_eq!(sid, new_sid);
+                    *prefix.0.entry(sid.queue()).or_insert(0) += 1;
+                }
+            }
+        }
+        drop_cut_waiters(&mut result);
+
+        let mut dependencies = Vec::new();
+        park_chains(
+            &self.buffers,
+            parking.buffers,
+            &prefix,
+            &mut result,
+            &mut dependencies,
+        );
+        park_parts(
+            &self.buffer_parts,
+            parking.buffers,
+            &prefix,
+            &mut result,
+            &mut dependencies,
+            |guard| &mut guard.buffer_parts,
+        );
+        park_chains(
+            &self.images,
+            parking.images,
+            &prefix,
+            &mut result,
+            &mut dependencies,
+        );
+        park_chains(
+            &self.stencils,
+            parking.images,
+            &prefix,
+            &mut result,
+            &mut dependencies,
+        );
+        park_parts(
+            &self.parts,
+            parking.images,
+            &prefix,
+            &mut result,
+            &mut dependencies,
+            |guard| &mut guard.parts,
+        );
+        for queue in result.iter_mut().flat_map(|family| family.iter_mut()) {
+            for (_, submission) in queue.iter_mut() {
+                submission.sync_mut().release.signal_present.clear();
+            }
+        }
+
+        // Semaphores are created in order of dependencies.
+        dependencies.sort_by_key(|dependency| (dependency.signal, dependency.wait));
+        let mut merged: Vec<Dependency> = Vec::new();
+        for dependency in dependencies {
+            match merged.last_mut() {
+                Some(last) if (last.signal, last.wait) == (dependency.signal, dependency.wait) => {
+                    last.stages |= dependency.stages;
+                    continue;
+                }
+                _ => {}
+            }
+            merged.push(dependency);
+        }
+        if timelines {
+            // Timeline is created for each queue that signals, like `SyncMode::Timelines` does.
+            // Only the latest tail of each queue is waited, as signaled values increase.
+            merged.sort_by_key(|dependency| (dependency.wait, dependency.signal));
+            let mut latest: Vec<Dependency> = Vec::new();
+            for dependency in merged {
+                match latest.last_mut() {
+                    Some(last)
+                        if last.wait == dependency.wait
+                            && last.signal.queue() == dependency.signal.queue() =>
+                    {
+                        last.signal = dependency.signal;
+                        last.stages |= dependency.stages;
+                        continue;
+                    }
+                    _ => {}
+                }
+                latest.push(dependency);
+            }
+            let mut semaphores: FnvHashMap<QueueId, T> = FnvHashMap::default();
+            for dependency in latest {
+                let semaphore = semaphores
+                    .entry(dependency.signal.queue())
+                    .or_insert_with(&mut new_semaphore)
+                    .clone();
+                let value = dependency.signal.index() as u64 + 1;
+                let signals = &mut result[dependency.signal].sync_mut().release.signal;
+                if !signals
+                    .iter()
+                    .any(|signal| *signal.semaphore() == semaphore && signal.value() == value)
+                {
+                    signals.push(Signal::timeline(semaphore.clone(), value));
+                }
+                let waits = &mut result[dependency.wait].sync_mut().acquire.wait;
+                match waits.iter().position(|wait| *wait.semaphore() == semaphore) {
+                    Some(index) => {
+                        let (stages, old) = (waits[index].stage(), waits[index].value());
+                        waits[index] = Wait::timeline(
+                            semaphore,
+                            stages | dependency.stages,
+                            max(old, value),
+                        );
+                    }
+                    None => waits.push(Wait::timeline(semaphore, dependency.stages, value)),
+                }
+            }
+        } else {
+            for dependency in merged {
+                let semaphore = new_semaphore();
+                result[dependency.signal]
+                    .sync_mut()
+                    .release
+                    .signal
+                    .push(Signal::new(semaphore.clone()));
+                result[dependency.wait]
+                    .sync_mut()
+                    .release
+                    .wait
+                    .push(Wait::new(semaphore, dependency.stages));
+            }
+        }
+
+        assign_fences(&mut result);
+        debug_assert!(legal_barriers(&result), "Illegal barrier emitted");
+        result
+    }
+}
+
+/// Remove signals which waits were cut, and set events which waits were cut.
+/// Events are indexed densely again.
+fn drop_cut_waiters<T>(schedule: &mut Schedule<SyncData<T, T>>)
+where
+    T: Clone + Eq + Hash,
+{
+    // Signals and waits of the schedule are paired by semaphore and value.
+    let mut waits = FnvHashSet::default();
+    let mut events = Vec::new();
+    for queue in schedule.iter().flat_map(|family| family.iter()) {
+        for (_, submission) in queue.iter() {
+            let sync = submission.sync();
+            for wait in sync.acquire.wait.iter().chain(&sync.release.wait) {
+                waits.insert((wait.semaphore().clone(), wait.value()));
+            }
+            events.extend(sync.acquire.wait_events.iter().map(|wait| wait.event));
+        }
+    }
+    events.sort();
+    let events: FnvHashMap<usize, usize> = events
+        .into_iter()
+        .enumerate()
+        .map(|(index, event)| (event, index))
+        .collect();
+
+    for queue in schedule.iter_mut().flat_map(|family| family.iter_mut()) {
+        for (_, submission) in queue.iter_mut() {
+            let sync = submission.sync_mut();
+            for guard in [&mut sync.acquire, &mut sync.release] {
+                guard
+                    .signal
+                    .retain(|signal| waits.contains(&(signal.semaphore().clone(), signal.value())));
+                guard.set_events.retain(|set| events.contains_key(&set.event));
+                for set in &mut guard.set_events {
+                    set.event = events[&set.event];
+                }
+                for wait in &mut guard.wait_events {
+                    wait.event = events[&wait.event];
+                }
+            }
+        }
+    }
+}
+
+/// Find the last link of the chain accessed by the prefix.
+/// Returns the link with the submission of the prefix that accesses it latest
+/// and the last submissions of the prefix that access it on each queue.
+fn last_link<'a, R, S>(
+    chain: &'a Chain<R>,
+    schedule: &Schedule<S>,
+    prefix: &Prefix,
+) -> Option<(&'a Link<R>, SubmissionId, Vec<SubmissionId>)>
+where
+    R: Resource,
+{
+    chain.links().iter().rev().filter_map(|link| {
+        let tails: Vec<_> = link
+            .queues()
+            .filter(|&(qid, queue)| prefix.contains(SubmissionId::new(qid, queue.first)))
+            .map(|(qid, queue)| SubmissionId::new(qid, min(queue.last, prefix.0[&qid] - 1)))
+            .collect();
+        let latest = *tails
+            .iter()
+            .max_by_key(|&&sid| (schedule[sid].submit_order(), sid.queue().index()))?;
+        Some((link, latest, tails))
+    }).next()
+}
+
+/// Get barrier that transitions the resource into the parking state after the prefix
+/// and submission at which release side it is recorded.
+/// Or `None` if the prefix doesn't access the resource.
+fn park<R, T>(
+    chain: &Chain<R>,
+    parking: State<R>,
+    prefix: &Prefix,
+    schedule: &Schedule<SyncData<T, T>>,
+    dependencies: &mut Vec<Dependency>,
+) -> Option<(SubmissionId, Barrier<R>)>
+where
+    R: Resource,
+    T: Eq,
+{
+    let (link, sid, tails) = last_link(chain, schedule, prefix)?;
+    for tail in tails {
+        if tail.queue() != sid.queue() && !ordered(schedule, tail, sid) {
+            dependencies.push(Dependency {
+                signal: tail,
+                wait: sid,
+                stages: link.queue(sid.queue()).stages,
+            });
+        }
+    }
+    Some((sid, Barrier::new(link.state()..parking, chain.range().cloned())))
+}
+
+/// Check if the submission waits for semaphore signaled after commands of another one.
+fn ordered<T>(schedule: &Schedule<SyncData<T, T>>, signal: SubmissionId, wait: SubmissionId) -> bool
+where
+    T: Eq,
+{
+    let wait = schedule[wait].sync();
+    schedule[signal].sync().release.signal.iter().any(|signal| {
+        wait.acquire
+            .wait
+            .iter()
+            .chain(&wait.release.wait)
+            .any(|wait| wait.semaphore() == signal.semaphore() && wait.value() >= signal.value())
+    })
+}
+
+/// Get first submissions of the link's queues, which initial barriers could be hoisted to.
+fn hoisted<R, S>(chain: &Chain<R>, schedule: &Schedule<S>) -> Vec<SubmissionId>
+where
+    R: Resource,
+{
+    chain.links().first().map_or(Vec::new(), |link| {
+        link.queues()
+            .map(|(qid, _)| SubmissionId::new(qid, 0))
+            .filter(|&sid| schedule.submission(sid).is_some())
+            .collect()
+    })
+}
+
+/// Transition resources accessed by the prefix into the parking state.
+/// Barriers of other resources are removed.
+fn park_chains<R, T>(
+    chains: &IdMap<R, Chain<R>>,
+    parking: State<R>,
+    prefix: &Prefix,
+    schedule: &mut Schedule<SyncData<T, T>>,
+    dependencies: &mut Vec<Dependency>,
+) where
+    R: Resource,
+    T: Eq,
+    Guard<T, T>: Pick<R, Target = Barriers<R>>,
+{
+    let mut ids: Vec<_> = chains.keys().cloned().collect();
+    ids.sort();
+    for id in ids {
+        let chain = &chains[&id];
+        match park(chain, parking, prefix, schedule, dependencies) {
+            Some((sid, barrier)) => {
+                schedule[sid].sync_mut().release.pick_mut().insert(id, barrier);
+            }
+            None => {
+                // Initial barrier of the first link may be hoisted into submission of the prefix.
+                for sid in hoisted(chain, schedule) {
+                    let barriers = schedule[sid].sync_mut().frame_begin.pick_mut();
+                    if barriers
+                        .get(&id)
+                        .is_some_and(|barrier| barrier.range.as_ref() == chain.range())
+                    {
+                        barriers.remove(&id);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Transition parts of resources accessed by the prefix into the parking state.
+/// Barriers of other parts are removed.
+/// `parts` picks barriers of resource parts from the guard.
+fn park_parts<R, T>(
+    chains: &IdMap<R, Vec<Chain<R>>>,
+    parking: State<R>,
+    prefix: &Prefix,
+    schedule: &mut Schedule<SyncData<T, T>>,
+    dependencies: &mut Vec<Dependency>,
+    parts: fn(&mut Guard<T, T>) -> &mut PartBarriers<R>,
+) where
+    R: Resource,
+    T: Eq,
+{
+    let remove = |parts: &mut PartBarriers<R>, id: Id<R>, range: Option<&R::Range>| {
+        parts.retain(|&(other, ref old)| {
+            other != id || match (old.range.as_ref(), range) {
+                (Some(old), Some(range)) => !old.overlaps(range),
+                _ => false,
+            }
+        });
+    };
+
+    let mut ids: Vec<_> = chains.keys().cloned().collect();
+    ids.sort();
+    for id in ids {
+        for chain in &chains[&id] {
+            match park(chain, parking, prefix, schedule, dependencies) {
+                Some((sid, barrier)) => {
+                    let parts = parts(&mut schedule[sid].sync_mut().release);
+                    remove(parts, id, chain.range());
+                    parts.push((id, barrier));
+                    parts.sort_by_key(|&(id, _)| id);
+                }
+                None => {
+                    for sid in hoisted(chain, schedule) {
+                        let parts = parts(&mut schedule[sid].sync_mut().frame_begin);
+                        remove(parts, id, chain.range());
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Make the last submission of each queue signal a fence and index fences densely.
+/// Fences are indexed in order of queues sorted by family id and then by index,
+/// and then in submission order.
+fn assign_fences<T>(schedule: &mut Schedule<SyncData<T, T>>) {
+    let mut families: Vec<_> = schedule.iter_mut().collect();
+    families.sort_by_key(|family| family.id());
+
+    let mut next = 0;
+    for queue in families.into_iter().flat_map(|family| family.iter_mut()) {
+        let count = queue.len();
+        for (sid, submission) in queue.iter_mut() {
+            let sync = submission.sync_mut();
+            if sync.fence.is_some() || sid.index() + 1 == count {
+                sync.fence = Some(next);
+                next += 1;
+            }
+        }
+    }
+}
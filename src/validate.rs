@@ -0,0 +1,1068 @@
+//!
+//! This module provides validation of synchronized `Chains`.
+//! Execution of the schedule is simulated to check that every state declared by pass
+//! is reached with generated barriers, semaphores and events.
+//!
+
+use alloc::vec::Vec;
+use collections::FnvHashMap;
+use collections::HashMap;
+use core::cmp::max;
+use core::fmt::{self, Display};
+use core::hash::Hash;
+use core::ops::Range;
+#[cfg(feature = "std")]
+use std::error::Error;
+
+use hal::format::Aspects;
+use hal::image::{Layout as ImageLayout, SubresourceRange};
+use hal::pso::PipelineStage;
+use hal::queue::QueueFamilyId;
+
+use chain::{Bind, Chain};
+use collect::{image_state_usage, Chains};
+use pass::{Pass, PassId, PassKind, PassMeta, StateUsage};
+use resource::{
+    Access, Buffer, Id, IdMap, Image, Layout, Range as ResourceRange, Resource, SharingMode, State,
+};
+use schedule::{QueueId, SubmissionId};
+use sync::{Barrier, Guard, GuardBarriers, SyncData};
+
+/// Violation of resource state found by `validate`.
+#[derive(Clone, Debug)]
+pub enum Violation<R: Resource> {
+    /// Declared state is not reached.
+    /// Layout can't be merged with actual one or declared accesses aren't made visible.
+    /// Barrier from `actual` to `expected` state is missing.
+    MissingBarrier {
+        /// State declared by the pass.
+        expected: State<R>,
+        /// State of the resource before the pass.
+        actual: State<R>,
+    },
+
+    /// Access isn't ordered after conflicting access or barrier from another queue.
+    /// Semaphore from `other` submission is missing.
+    MissingSemaphore {
+        /// Submission that performed conflicting access or barrier.
+        other: SubmissionId,
+    },
+
+    /// Resource with exclusive sharing mode is accessed by family that doesn't own it.
+    /// Or exported resource isn't released to external family.
+    /// Queue family ownership transfer is missing.
+    MissingTransfer {
+        /// Family that owns the resource.
+        /// Or `None` if resource is being transferred.
+        owner: Option<QueueFamilyId>,
+    },
+
+    /// Source state of the barrier doesn't match actual state of the resource.
+    InvalidBarrier {
+        /// States of the barrier.
+        barrier: Range<State<R>>,
+        /// State of the resource before the barrier.
+        actual: State<R>,
+    },
+
+    /// Barrier discarded content of the resource but the pass doesn't overwrite it.
+    DiscardedContent {
+        /// States of the discarding barrier.
+        barrier: Range<State<R>>,
+    },
+}
+
+/// Error found by `validate`.
+#[derive(Clone, Debug)]
+pub enum ValidationError {
+    /// Submission waits for semaphore or event that isn't signaled by any earlier submission.
+    Unsignaled {
+        /// Id of the pass.
+        pass: PassId,
+        /// Name and tag of the pass.
+        meta: PassMeta,
+        /// Id of the waiting submission.
+        submission: SubmissionId,
+    },
+
+    /// Synchronization of the buffer is invalid.
+    Buffer {
+        /// Id of the buffer.
+        id: Id<Buffer>,
+        /// Id of the pass.
+        pass: PassId,
+        /// Name and tag of the pass.
+        meta: PassMeta,
+        /// Id of the submission.
+        submission: SubmissionId,
+        /// Found violation.
+        violation: Violation<Buffer>,
+    },
+
+    /// Synchronization of the image is invalid.
+    Image {
+        /// Id of the image.
+        id: Id<Image>,
+        /// Id of the pass.
+        pass: PassId,
+        /// Name and tag of the pass.
+        meta: PassMeta,
+        /// Id of the submission.
+        submission: SubmissionId,
+        /// Found violation.
+        violation: Violation<Image>,
+    },
+}
+
+impl From<(Id<Buffer>, PassId, SubmissionId, Violation<Buffer>)> for ValidationError {
+    fn from(
+        (id, pass, submission, violation): (Id<Buffer>, PassId, SubmissionId, Violation<Buffer>),
+    ) -> Self {
+        ValidationError::Buffer {
+            id,
+            pass,
+            meta: PassMeta::default(),
+            submission,
+            violation,
+        }
+    }
+}
+
+impl From<(Id<Image>, PassId, SubmissionId, Violation<Image>)> for ValidationError {
+    fn from(
+        (id, pass, submission, violation): (Id<Image>, PassId, SubmissionId, Violation<Image>),
+    ) -> Self {
+        ValidationError::Image {
+            id,
+            pass,
+            meta: PassMeta::default(),
+            submission,
+            violation,
+        }
+    }
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ValidationError::Unsignaled {
+                pass,
+                meta,
+                submission,
+            } => write!(
+                fmt,
+                "Submission {:?} of pass {} waits for unsignaled semaphore or event",
+                submission,
+                meta.label(pass)
+            ),
+            ValidationError::Buffer {
+                id,
+                pass,
+                meta,
+                submission,
+                ref violation,
+            } => write!(
+                fmt,
+                "Buffer {:?} in submission {:?} of pass {}: {:?}",
+                id,
+                submission,
+                meta.label(pass),
+                violation
+            ),
+            ValidationError::Image {
+                id,
+                pass,
+                meta,
+                submission,
+                ref violation,
+            } => write!(
+                fmt,
+                "Image {:?} in submission {:?} of pass {}: {:?}",
+                id,
+                submission,
+                meta.label(pass),
+                violation
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for ValidationError {
+    fn description(&self) -> &str {
+        match *self {
+            ValidationError::Unsignaled { .. } => "Unsignaled semaphore or event",
+            ValidationError::Buffer { .. } => "Invalid buffer synchronization",
+            ValidationError::Image { .. } => "Invalid image synchronization",
+        }
+    }
+}
+
+/// Validate synchronization of the `Chains` against states declared by `passes`.
+///
+/// Submissions are executed in submit order.
+/// Vector clocks built from queue order, semaphores and events are used to check
+/// that conflicting accesses from different queues are ordered.
+///
+/// Semaphores are matched by equality of signal and wait parts.
+/// Build `Chains` with the same value for both parts (e.g. index of the semaphore)
+/// to validate them.
+///
+/// # Panics
+///
+/// Panics if `passes` don't contain pass of some submission.
+///
+pub fn validate<T>(
+    chains: &Chains<SyncData<T, T>>,
+    passes: &[Pass],
+) -> Result<(), Vec<ValidationError>>
+where
+    T: Eq + Hash,
+{
+    let passes: FnvHashMap<PassId, &Pass> = passes.iter().map(|pass| (pass.id, pass)).collect();
+
+    let mut queues: Vec<QueueId> = chains
+        .schedule
+        .iter()
+        .flat_map(|family| family.iter())
+        .map(|queue| queue.id())
+        .collect();
+    queues.sort();
+    let queue_indices: FnvHashMap<QueueId, usize> = queues
+        .iter()
+        .enumerate()
+        .map(|(index, &qid)| (qid, index))
+        .collect();
+
+    let mut submissions: Vec<(usize, SubmissionId)> = chains
+        .schedule
+        .iter()
+        .flat_map(|family| family.iter())
+        .flat_map(|queue| queue.iter())
+        .map(|(sid, submission)| (submission.submit_order(), sid))
+        .collect();
+    submissions.sort();
+
+    // Sparse binding isn't ordered with its queue. Each one gets own clock.
+    let mut bind_queues: FnvHashMap<SubmissionId, usize> = FnvHashMap::default();
+    for &(_, sid) in &submissions {
+        if chains.schedule[sid].kind() == PassKind::SparseBind {
+            let index = queues.len() + bind_queues.len();
+            bind_queues.insert(sid, index);
+        }
+    }
+    let clock_count = queues.len() + bind_queues.len();
+
+    let mut state = ValidationState {
+        clocks: vec![vec![0; clock_count]; clock_count],
+        signals: HashMap::default(),
+        events: FnvHashMap::default(),
+        buffers: tracked(&chains.buffers),
+        images: tracked(&chains.images),
+        stencils: tracked(&chains.stencils),
+        parts: chains
+            .parts
+            .iter()
+            .map(|(&id, parts)| {
+                let parts = parts
+                    .iter()
+                    .map(|chain| (chain.range().unwrap().clone(), Tracked::new(chain)))
+                    .collect();
+                (id, parts)
+            })
+            .collect(),
+        buffer_parts: chains
+            .buffer_parts
+            .iter()
+            .map(|(&id, parts)| {
+                let parts = parts
+                    .iter()
+                    .map(|chain| (chain.range().unwrap().clone(), Tracked::new(chain)))
+                    .collect();
+                (id, parts)
+            })
+            .collect(),
+        errors: Vec::new(),
+    };
+
+    // Barriers hoisted to the beginning of the frame are recorded before any submission.
+    for &(_, sid) in submissions.iter().filter(|&&(_, sid)| sid.index() == 0) {
+        let submission = &chains.schedule[sid];
+        let queue = queue_indices[&sid.queue()];
+        state.guard(sid, submission.pass(), queue, &submission.sync().frame_begin);
+    }
+
+    for (_, sid) in submissions {
+        let submission = &chains.schedule[sid];
+        let pass = passes[&submission.pass()];
+        let queue = match bind_queues.get(&sid) {
+            Some(&queue) => queue,
+            None => queue_indices[&sid.queue()],
+        };
+        let sync = submission.sync();
+
+        state.guard(sid, pass.id, queue, &sync.acquire);
+
+        let point = state.step(sid, pass.id, queue);
+        if pass.kind == PassKind::SparseBind {
+            let bound = |binds: &[Bind]| binds.iter().any(|bind| bind.sid == sid);
+            for (&id, state_usage) in pass.buffers() {
+                for (chain, &mut (_, ref mut tracked)) in chains
+                    .buffer_parts
+                    .get(&id)
+                    .into_iter()
+                    .flatten()
+                    .zip(state.buffer_parts.get_mut(&id).into_iter().flatten())
+                {
+                    let overlaps = match (chain.range(), state_usage.range.as_ref()) {
+                        (Some(part), Some(range)) => part.overlaps(range),
+                        _ => true,
+                    };
+                    if overlaps && bound(chain.binds()) {
+                        tracked.bind(
+                            id,
+                            state_usage.range.clone(),
+                            point,
+                            &state.clocks[queue],
+                            &mut state.errors,
+                        );
+                    }
+                }
+                if chains.buffers.get(&id).is_some_and(|chain| bound(chain.binds())) {
+                    state.buffers.get_mut(&id).unwrap().bind(
+                        id,
+                        state_usage.range.clone(),
+                        point,
+                        &state.clocks[queue],
+                        &mut state.errors,
+                    );
+                }
+            }
+            let images = pass.images().chain(
+                pass.image_ranges()
+                    .flat_map(|(id, ranges)| ranges.iter().map(move |v| (id, v))),
+            );
+            for (&id, state_usage) in images {
+                for (chain, &mut (_, ref mut tracked)) in chains
+                    .parts
+                    .get(&id)
+                    .into_iter()
+                    .flatten()
+                    .zip(state.parts.get_mut(&id).into_iter().flatten())
+                {
+                    let overlaps = match (chain.range(), state_usage.range.as_ref()) {
+                        (Some(part), Some(range)) => part.overlaps(range),
+                        _ => true,
+                    };
+                    if overlaps && bound(chain.binds()) {
+                        tracked.bind(
+                            id,
+                            state_usage.range.clone(),
+                            point,
+                            &state.clocks[queue],
+                            &mut state.errors,
+                        );
+                    }
+                }
+                if chains.images.get(&id).is_some_and(|chain| bound(chain.binds())) {
+                    state.images.get_mut(&id).unwrap().bind(
+                        id,
+                        state_usage.range.clone(),
+                        point,
+                        &state.clocks[queue],
+                        &mut state.errors,
+                    );
+                }
+                if chains.stencils.get(&id).is_some_and(|chain| bound(chain.binds())) {
+                    state.stencils.get_mut(&id).unwrap().bind(
+                        id,
+                        state_usage.range.clone(),
+                        point,
+                        &state.clocks[queue],
+                        &mut state.errors,
+                    );
+                }
+            }
+        }
+        for (&id, _) in submission.buffers() {
+            let chain = &chains.buffers[&id];
+            state.buffers.get_mut(&id).unwrap().access(
+                id,
+                chain,
+                &pass.buffers[&id],
+                point,
+                &state.clocks[queue],
+                &mut state.errors,
+            );
+        }
+        for (&id, &(part, _)) in submission.buffer_parts() {
+            let chain = &chains.buffer_parts[&id][part];
+            state.buffer_parts.get_mut(&id).unwrap()[part].1.access(
+                id,
+                chain,
+                &pass.buffers[&id],
+                point,
+                &state.clocks[queue],
+                &mut state.errors,
+            );
+        }
+        for (&id, _) in submission.images() {
+            let chain = &chains.images[&id];
+            state.images.get_mut(&id).unwrap().access(
+                id,
+                chain,
+                &image_state_usage(pass, id, None).unwrap(),
+                point,
+                &state.clocks[queue],
+                &mut state.errors,
+            );
+        }
+        for (&id, _) in submission.stencils() {
+            let chain = &chains.stencils[&id];
+            state.stencils.get_mut(&id).unwrap().access(
+                id,
+                chain,
+                &image_state_usage(pass, id, None).unwrap(),
+                point,
+                &state.clocks[queue],
+                &mut state.errors,
+            );
+        }
+        for (&id, links) in submission.parts() {
+            for &(part, _) in links {
+                let chain = &chains.parts[&id][part];
+                let state_usage = match image_state_usage(pass, id, chain.range()) {
+                    Some(state_usage) => state_usage,
+                    None => continue,
+                };
+                state.parts.get_mut(&id).unwrap()[part].1.access(
+                    id,
+                    chain,
+                    &state_usage,
+                    point,
+                    &state.clocks[queue],
+                    &mut state.errors,
+                );
+            }
+        }
+
+        state.guard(sid, pass.id, queue, &sync.release);
+    }
+
+    let mut errors = state.errors;
+    let parts = {
+        let tracked = &state.parts;
+        let mut parts: Vec<_> = chains
+            .parts
+            .iter()
+            .flat_map(|(&id, parts)| {
+                parts
+                    .iter()
+                    .zip(&tracked[&id])
+                    .map(move |(chain, (_, tracked))| (id, chain, tracked))
+            })
+            .collect();
+        parts.sort_by_key(|&(id, _, _)| id);
+        parts
+    };
+    let buffer_parts = {
+        let tracked = &state.buffer_parts;
+        let mut parts: Vec<_> = chains
+            .buffer_parts
+            .iter()
+            .flat_map(|(&id, parts)| {
+                parts
+                    .iter()
+                    .zip(&tracked[&id])
+                    .map(move |(chain, (_, tracked))| (id, chain, tracked))
+            })
+            .collect();
+        parts.sort_by_key(|&(id, _, _)| id);
+        parts
+    };
+    final_states(sorted(&chains.buffers, &state.buffers), &mut errors, |_| None);
+    final_states(buffer_parts, &mut errors, |_| None);
+    final_states(sorted(&chains.images, &state.images), &mut errors, |chain| {
+        if chain.info().present {
+            Some(ImageLayout::Present)
+        } else {
+            None
+        }
+    });
+    final_states(sorted(&chains.stencils, &state.stencils), &mut errors, |_| None);
+    final_states(parts, &mut errors, |_| None);
+
+    for error in &mut errors {
+        match *error {
+            ValidationError::Unsignaled { pass, ref mut meta, .. }
+            | ValidationError::Buffer { pass, ref mut meta, .. }
+            | ValidationError::Image { pass, ref mut meta, .. } => {
+                *meta = passes[&pass].meta;
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Point of the execution.
+#[derive(Clone, Copy, Debug)]
+struct Point {
+    sid: SubmissionId,
+    pass: PassId,
+    queue: usize,
+    time: usize,
+}
+
+impl Point {
+    /// Check if point happens before execution with specified clock.
+    fn before(&self, clock: &[usize]) -> bool {
+        clock[self.queue] >= self.time
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Owner {
+    Family(QueueFamilyId),
+    Transferring(QueueFamilyId),
+}
+
+/// Access performed by pass.
+#[derive(Clone, Debug)]
+struct Use<R: Resource> {
+    point: Point,
+    write: bool,
+    range: Option<R::Range>,
+
+    /// Sparse binding. Conflicts with all accesses but doesn't require barriers.
+    bind: bool,
+}
+
+/// Simulated state of the resource.
+#[derive(Clone, Debug)]
+struct Tracked<R: Resource> {
+    state: State<R>,
+    owner: Owner,
+
+    /// Last barrier. Or `None` if there was no barriers yet.
+    barrier: Option<Point>,
+
+    /// Accesses since last barrier.
+    uses: Vec<Use<R>>,
+
+    /// Last barrier if it discarded content and no pass accessed the resource since.
+    discarded: Option<Barrier<R>>,
+
+    /// Last submission that accessed the resource.
+    last: Option<(SubmissionId, PassId)>,
+}
+
+impl<R> Tracked<R>
+where
+    R: Resource,
+{
+    fn new(chain: &Chain<R>) -> Self {
+        let link = chain.link(0);
+        let mut state = chain.initial_state().state;
+        let mut owner = Owner::Family(link.family());
+        if chain.info().present {
+            state = State {
+                access: R::Access::none(),
+                layout: R::Layout::discard_content(),
+                stages: PipelineStage::empty(),
+            };
+        }
+        if chain.info().import.is_some() {
+            // External family has released the resource already.
+            state.access = R::Access::none();
+            state.stages = PipelineStage::empty();
+            owner = Owner::Transferring(link.family());
+        }
+        Tracked {
+            state,
+            owner,
+            barrier: None,
+            uses: Vec::new(),
+            discarded: None,
+            last: None,
+        }
+    }
+
+    /// Check that there were no accesses not ordered before `clock`.
+    /// Returns submission that performed unordered access.
+    fn unordered<F>(&self, clock: &[usize], conflicts: F) -> Option<SubmissionId>
+    where
+        F: Fn(&Use<R>) -> bool,
+    {
+        self.barrier
+            .iter()
+            .filter(|point| !point.before(clock))
+            .map(|point| point.sid)
+            .chain(
+                self.uses
+                    .iter()
+                    .filter(|access| conflicts(access) && !access.point.before(clock))
+                    .map(|access| access.point.sid),
+            )
+            .next()
+    }
+
+    /// Check if accesses from another queue since last barrier are ordered before `clock`.
+    /// Semaphore wait makes memory visible to all stages it blocks.
+    fn waited(&self, point: Point, clock: &[usize]) -> bool {
+        self.uses.iter().any(|access| {
+            !access.bind && access.point.queue != point.queue && access.point.before(clock)
+        })
+    }
+
+    /// Access resource by the pass.
+    fn access(
+        &mut self,
+        id: Id<R>,
+        chain: &Chain<R>,
+        state_usage: &StateUsage<R>,
+        point: Point,
+        clock: &[usize],
+        errors: &mut Vec<ValidationError>,
+    ) where
+        ValidationError: From<(Id<R>, PassId, SubmissionId, Violation<R>)>,
+    {
+        let Point { sid, pass, .. } = point;
+        let expected = state_usage.state;
+        let write = expected.access.is_write();
+        let conflicts = |access: &Use<R>| {
+            (write || access.write)
+                && match (access.range.as_ref(), state_usage.range.as_ref()) {
+                    (Some(left), Some(right)) => left.overlaps(right),
+                    _ => true,
+                }
+        };
+
+        // Concurrent resource may be accessed by any family unless external family owns it.
+        let owned = match (chain.sharing(), self.owner) {
+            (SharingMode::Exclusive, owner) => owner == Owner::Family(sid.family()),
+            (SharingMode::Concurrent, Owner::Family(_)) => true,
+            (SharingMode::Concurrent, Owner::Transferring(_)) => false,
+        };
+
+        let mut violation = None;
+        if !owned {
+            violation = Some(Violation::MissingTransfer {
+                owner: match self.owner {
+                    Owner::Family(family) => Some(family),
+                    Owner::Transferring(_) => None,
+                },
+            });
+        } else if let Some(other) = self.unordered(clock, conflicts) {
+            violation = Some(Violation::MissingSemaphore { other });
+        } else if self.uses.iter().any(|access| !access.bind && conflicts(access))
+            || self.state.layout.merge(expected.layout).is_none()
+            || self.state.access & expected.access != expected.access
+            || self.barrier.is_some_and(|barrier| {
+                barrier.queue == point.queue
+                    && !self.state.stages.contains(expected.stages)
+                    && !self.waited(point, clock)
+            })
+        {
+            // Conflicting accesses without barrier between
+            // or barrier made resource available in wrong state.
+            violation = Some(Violation::MissingBarrier {
+                expected,
+                actual: self.state,
+            });
+        }
+
+        // Pass must overwrite all the content discarded by the barrier.
+        if let Some(barrier) = self.discarded.take() {
+            let covers = match (state_usage.range.as_ref(), barrier.range) {
+                (None, _) => true,
+                (Some(_), None) => false,
+                (Some(left), Some(right)) => left.union(&right) == *left,
+            };
+            if violation.is_none() && !(state_usage.discard && covers) {
+                violation = Some(Violation::DiscardedContent {
+                    barrier: barrier.states,
+                });
+            }
+        }
+
+        if let Some(violation) = violation {
+            errors.push((id, pass, sid, violation).into());
+        }
+
+        self.uses.push(Use {
+            point,
+            write,
+            range: state_usage.range.clone(),
+            bind: false,
+        });
+        self.last = Some((sid, pass));
+        if owned && chain.sharing() == SharingMode::Concurrent {
+            // The family that accessed concurrent resource last releases it to external family.
+            self.owner = Owner::Family(sid.family());
+        }
+    }
+
+    /// Bind memory to the resource by sparse binding pass.
+    /// Binding must be ordered after all accesses and barriers since the last barrier.
+    fn bind(
+        &mut self,
+        id: Id<R>,
+        range: Option<R::Range>,
+        point: Point,
+        clock: &[usize],
+        errors: &mut Vec<ValidationError>,
+    ) where
+        ValidationError: From<(Id<R>, PassId, SubmissionId, Violation<R>)>,
+    {
+        if let Some(other) = self.unordered(clock, |_| true) {
+            errors.push((id, point.pass, point.sid, Violation::MissingSemaphore { other }).into());
+        }
+        self.uses.push(Use {
+            point,
+            write: true,
+            range,
+            bind: true,
+        });
+    }
+
+    /// Perform barrier.
+    /// Barrier must be ordered after accesses at `source` clock.
+    fn barrier(
+        &mut self,
+        id: Id<R>,
+        barrier: &Barrier<R>,
+        point: Point,
+        source: &[usize],
+        errors: &mut Vec<ValidationError>,
+    ) where
+        ValidationError: From<(Id<R>, PassId, SubmissionId, Violation<R>)>,
+    {
+        let Point { sid, pass, .. } = point;
+        let states = &barrier.states;
+        let queues = barrier.queues.as_ref();
+        let mut violation = None;
+
+        if let Some(other) = self.unordered(source, |_| true) {
+            violation = Some(Violation::MissingSemaphore { other });
+        }
+
+        let mut transferred = false;
+        if let Some(queues) = queues {
+            if queues.start.family() == sid.family() {
+                if self.owner != Owner::Family(sid.family()) {
+                    violation = Some(Violation::MissingTransfer { owner: None });
+                }
+                self.owner = Owner::Transferring(queues.end.family());
+            } else {
+                if self.owner != Owner::Transferring(sid.family()) {
+                    violation = Some(Violation::MissingTransfer { owner: None });
+                }
+                self.owner = Owner::Family(sid.family());
+                transferred = true;
+            }
+        }
+
+        // Layout of the resource being transferred is defined by release barrier.
+        // Reads don't need to be made available, so any read-only source matches.
+        // Barrier from undefined state discards the content whatever state it was in.
+        // Unless the content was only read since it was made available and layout is kept,
+        // like in execution-only barrier for write-after-read.
+        let read_only = !states.start.access.is_write() && !self.state.access.is_write();
+        let discarding = queues.is_none()
+            && states.start.access == R::Access::none()
+            && states.start.layout == R::Layout::discard_content();
+        let matches = states.start.layout == self.state.layout
+            && (states.start.access == self.state.access || read_only);
+        self.discarded = if discarding && !matches {
+            Some(barrier.clone())
+        } else {
+            None
+        };
+        if !discarding
+            && ((!transferred && states.start.layout != self.state.layout)
+                || (states.start.access != self.state.access && !read_only))
+        {
+            violation = Some(Violation::InvalidBarrier {
+                barrier: states.clone(),
+                actual: self.state,
+            });
+        }
+
+        if let Some(violation) = violation {
+            errors.push((id, pass, sid, violation).into());
+        }
+
+        self.state = states.end;
+        self.barrier = Some(point);
+        self.uses.clear();
+        self.last = Some((sid, pass));
+    }
+}
+
+/// Simulated states of resource parts with ranges of the parts.
+type TrackedParts<R> = FnvHashMap<Id<R>, Vec<(<R as Resource>::Range, Tracked<R>)>>;
+
+fn tracked<R>(chains: &IdMap<R, Chain<R>>) -> FnvHashMap<Id<R>, Tracked<R>>
+where
+    R: Resource,
+{
+    chains
+        .iter()
+        .map(|(&id, chain)| (id, Tracked::new(chain)))
+        .collect()
+}
+
+/// Get chains with simulated states sorted by id.
+fn sorted<'a, R>(
+    chains: &'a IdMap<R, Chain<R>>,
+    tracked: &'a FnvHashMap<Id<R>, Tracked<R>>,
+) -> Vec<(Id<R>, &'a Chain<R>, &'a Tracked<R>)>
+where
+    R: Resource,
+{
+    let mut sorted: Vec<_> = chains
+        .iter()
+        .map(|(&id, chain)| (id, chain, &tracked[&id]))
+        .collect();
+    sorted.sort_by_key(|&(id, _, _)| id);
+    sorted
+}
+
+/// Check states of resources after schedule execution.
+fn final_states<R, F>(
+    chains: Vec<(Id<R>, &Chain<R>, &Tracked<R>)>,
+    errors: &mut Vec<ValidationError>,
+    final_layout: F,
+) where
+    R: Resource,
+    F: Fn(&Chain<R>) -> Option<R::Layout>,
+    ValidationError: From<(Id<R>, PassId, SubmissionId, Violation<R>)>,
+{
+    for (id, chain, tracked) in chains {
+        let (sid, pass) = match tracked.last {
+            Some(last) => last,
+            None => continue,
+        };
+        let mut expected = tracked.state;
+        if let Some(export) = chain.info().export {
+            if tracked.owner != Owner::Transferring(export.family) {
+                let owner = match tracked.owner {
+                    Owner::Family(family) => Some(family),
+                    Owner::Transferring(_) => None,
+                };
+                errors.push((id, pass, sid, Violation::MissingTransfer { owner }).into());
+            }
+            expected.access = R::Access::none();
+            expected.layout = export.state.layout;
+        } else if chain.info().host_read_after {
+            expected.access = R::Access::host_read();
+            expected.stages = PipelineStage::HOST;
+        }
+        if let Some(layout) = final_layout(chain) {
+            expected.layout = layout;
+        }
+        if expected.access != tracked.state.access || expected.layout != tracked.state.layout {
+            errors.push(
+                (
+                    id,
+                    pass,
+                    sid,
+                    Violation::MissingBarrier {
+                        expected,
+                        actual: tracked.state,
+                    },
+                )
+                    .into(),
+            );
+        }
+    }
+}
+
+struct ValidationState<'a, T: 'a> {
+    /// Vector clock of each queue.
+    clocks: Vec<Vec<usize>>,
+
+    /// Clocks of semaphore signals with signaled values.
+    signals: HashMap<&'a T, Vec<(u64, Vec<usize>)>>,
+
+    /// Clocks of event sets.
+    events: FnvHashMap<usize, Vec<usize>>,
+
+    buffers: FnvHashMap<Id<Buffer>, Tracked<Buffer>>,
+    images: FnvHashMap<Id<Image>, Tracked<Image>>,
+
+    /// Stencil aspects of images tracked separately from depth aspects.
+    stencils: FnvHashMap<Id<Image>, Tracked<Image>>,
+
+    /// Parts of images accessed in several disjoint ranges with ranges of the parts.
+    parts: TrackedParts<Image>,
+
+    /// Parts of buffers accessed only in disjoint ranges with ranges of the parts.
+    buffer_parts: TrackedParts<Buffer>,
+    errors: Vec<ValidationError>,
+}
+
+fn guard_barriers<T>(guard: &Guard<T, T>) -> GuardBarriers<'_> {
+    (&guard.buffers, &guard.images, &guard.parts, &guard.buffer_parts)
+}
+
+impl<'a, T> ValidationState<'a, T>
+where
+    T: Eq + Hash,
+{
+    /// Advance clock of the queue.
+    fn step(&mut self, sid: SubmissionId, pass: PassId, queue: usize) -> Point {
+        self.clocks[queue][queue] += 1;
+        Point {
+            sid,
+            pass,
+            queue,
+            time: self.clocks[queue][queue],
+        }
+    }
+
+    /// Merge clock into clock of the queue.
+    fn join(&mut self, queue: usize, clock: &[usize]) {
+        for (own, &other) in self.clocks[queue].iter_mut().zip(clock) {
+            *own = max(*own, other);
+        }
+    }
+
+    /// Execute guard.
+    fn guard(&mut self, sid: SubmissionId, pass: PassId, queue: usize, guard: &'a Guard<T, T>) {
+        for wait in &guard.wait {
+            let clock = self.signals.get(wait.semaphore()).and_then(|signals| {
+                signals
+                    .iter()
+                    .find(|&&(value, _)| value >= wait.value())
+                    .map(|(_, clock)| clock.clone())
+            });
+            match clock {
+                Some(clock) => self.join(queue, &clock),
+                None => self.errors.push(ValidationError::Unsignaled {
+                    pass,
+                    meta: PassMeta::default(),
+                    submission: sid,
+                }),
+            }
+        }
+
+        let point = self.step(sid, pass, queue);
+        let clock = self.clocks[queue].clone();
+        self.barriers(point, guard_barriers(guard), &clock);
+
+        for wait in &guard.wait_events {
+            let source = match self.events.get(&wait.event) {
+                Some(source) => source.clone(),
+                None => {
+                    self.errors.push(ValidationError::Unsignaled {
+                        pass,
+                        meta: PassMeta::default(),
+                        submission: sid,
+                    });
+                    clock.clone()
+                }
+            };
+            let barriers = (&wait.buffers, &wait.images, &wait.parts[..], &wait.buffer_parts[..]);
+            self.barriers(point, barriers, &source);
+        }
+
+        for set in &guard.set_events {
+            self.events.insert(set.event, clock.clone());
+        }
+        for signal in &guard.signal {
+            self.signals
+                .entry(signal.semaphore())
+                .or_default()
+                .push((signal.value(), clock.clone()));
+        }
+    }
+
+    /// Perform barriers at `point` ordered after accesses at `source` clock.
+    fn barriers(
+        &mut self,
+        point: Point,
+        (buffers, images, parts, buffer_parts): GuardBarriers,
+        source: &[usize],
+    ) {
+        let mut ids: Vec<_> = buffers.keys().cloned().collect();
+        ids.sort();
+        for id in ids {
+            let barrier = &buffers[&id];
+            self.buffers.get_mut(&id).unwrap().barrier(
+                id,
+                barrier,
+                point,
+                source,
+                &mut self.errors,
+            );
+        }
+        // Barrier of the buffer part affects parts its range overlaps.
+        for &(id, ref barrier) in buffer_parts {
+            for &mut (ref range, ref mut tracked) in self.buffer_parts.get_mut(&id).unwrap() {
+                if barrier.range.as_ref().is_none_or(|other| other.overlaps(range)) {
+                    tracked.barrier(
+                        id,
+                        barrier,
+                        point,
+                        source,
+                        &mut self.errors,
+                    );
+                }
+            }
+        }
+        let mut ids: Vec<_> = images.keys().cloned().collect();
+        ids.sort();
+        for id in ids {
+            let barrier = &images[&id];
+            let covers = |aspect| {
+                barrier
+                    .range
+                    .as_ref()
+                    .is_none_or(|range: &SubresourceRange| range.aspects.contains(aspect))
+            };
+
+            // Barrier that covers both aspects of split image affects both chains.
+            let mut tracked = Vec::new();
+            match self.stencils.get_mut(&id) {
+                Some(stencil) => {
+                    if covers(Aspects::DEPTH) {
+                        tracked.push(self.images.get_mut(&id).unwrap());
+                    }
+                    if covers(Aspects::STENCIL) {
+                        tracked.push(stencil);
+                    }
+                }
+                None => tracked.push(self.images.get_mut(&id).unwrap()),
+            }
+            for tracked in tracked {
+                tracked.barrier(
+                    id,
+                    barrier,
+                    point,
+                    source,
+                    &mut self.errors,
+                );
+            }
+        }
+        // Barrier of the image part affects parts its range overlaps.
+        for &(id, ref barrier) in parts {
+            for &mut (ref range, ref mut tracked) in self.parts.get_mut(&id).unwrap() {
+                if barrier.range.as_ref().is_none_or(|other| other.overlaps(range)) {
+                    tracked.barrier(
+                        id,
+                        barrier,
+                        point,
+                        source,
+                        &mut self.errors,
+                    );
+                }
+            }
+        }
+    }
+}
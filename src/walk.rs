@@ -0,0 +1,410 @@
+//!
+//! This module provides view of synchronized schedule for command recording.
+//! Synchronization of each submission is gathered in the order commands must be recorded.
+//!
+
+use alloc::vec::Vec;
+use core::ops::Range;
+use core::slice::Iter as SliceIter;
+use hal::image::{Access as ImageAccess, Layout as ImageLayout};
+use hal::pso::PipelineStage;
+
+use chain::attachment_access;
+use pass::{PassId, PassKind, PassMeta};
+use resource::{Id, Image};
+use schedule::{QueueId, Schedule, SubmissionId};
+use sync::{
+    fence_count, FeedbackLoop, FenceId, Guard, PipelineBarrier, SetEvent, Signal, SyncData, Wait,
+    WaitEvent,
+};
+
+/// Synchronization command to be recorded before or after main commands of the submission.
+#[derive(Clone, Debug)]
+pub enum BarrierRecord<'a> {
+    /// Wait for the event and perform its barriers (only before main commands).
+    WaitEvent(&'a WaitEvent),
+
+    /// Perform combined pipeline barrier.
+    Pipeline(PipelineBarrier<'a>),
+
+    /// Set the event (only after main commands).
+    SetEvent(&'a SetEvent),
+}
+
+/// Dependency between render pass and commands outside of it.
+/// Replaces pipeline barrier recorded next to the render pass.
+/// Or self-dependency of the subpass. See `SubmissionInfo::as_self_dependency`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SubpassDependency {
+    /// Source and destination stages.
+    pub stages: Range<PipelineStage>,
+
+    /// Source and destination accesses.
+    pub accesses: Range<ImageAccess>,
+
+    /// Source and destination stages are framebuffer-space stages,
+    /// so the dependency can be made by region.
+    pub by_region: bool,
+
+    /// Layout transitions of attachments to be performed by the render pass instead of
+    /// the barrier, sorted by id. For the beginning of the render pass start of the range
+    /// is initial layout of the attachment. For the end, end of the range is its final layout.
+    pub layouts: Vec<(Id<Image>, Range<ImageLayout>)>,
+}
+
+/// Pipeline barriers adjacent to the render pass expressed as subpass dependencies
+/// with external subpass. See `SubmissionInfo::as_external_dependency`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExternalDependency {
+    /// Dependency from external subpass replacing `BarrierRecord::Pipeline` from `before`.
+    /// Or `None` if the barrier must be recorded.
+    pub begin: Option<SubpassDependency>,
+
+    /// Dependency to external subpass replacing `BarrierRecord::Pipeline` from `after`.
+    /// Or `None` if the barrier must be recorded.
+    pub end: Option<SubpassDependency>,
+}
+
+/// Synchronization of one submission.
+#[derive(Clone, Debug)]
+pub struct SubmissionInfo<'a, S: 'a, W: 'a> {
+    sid: SubmissionId,
+    pass: PassId,
+    meta: PassMeta,
+    kind: PassKind,
+    before: Vec<BarrierRecord<'a>>,
+    after: Vec<BarrierRecord<'a>>,
+    sync: &'a SyncData<S, W>,
+}
+
+impl<'a, S, W> SubmissionInfo<'a, S, W> {
+    fn new(
+        sid: SubmissionId,
+        pass: PassId,
+        meta: PassMeta,
+        kind: PassKind,
+        sync: &'a SyncData<S, W>,
+    ) -> Self {
+        let mut before: Vec<_> = sync
+            .acquire
+            .wait_events
+            .iter()
+            .map(BarrierRecord::WaitEvent)
+            .collect();
+        before.extend(sync.acquire.pipeline_barrier().map(BarrierRecord::Pipeline));
+
+        let mut after: Vec<_> = sync
+            .release
+            .pipeline_barrier()
+            .map(BarrierRecord::Pipeline)
+            .into_iter()
+            .collect();
+        after.extend(sync.release.set_events.iter().map(BarrierRecord::SetEvent));
+
+        SubmissionInfo {
+            sid,
+            pass,
+            meta,
+            kind,
+            before,
+            after,
+            sync,
+        }
+    }
+
+    /// Get id of the submission.
+    pub fn sid(&self) -> SubmissionId {
+        self.sid
+    }
+
+    /// Get id of the pass.
+    pub fn pass(&self) -> PassId {
+        self.pass
+    }
+
+    /// Get name and tag of the pass.
+    pub fn meta(&self) -> PassMeta {
+        self.meta
+    }
+
+    /// Get kind of the pass.
+    /// Sparse binding submissions have no commands to record
+    /// and must be submitted as sparse binding operations with the same semaphores.
+    pub fn kind(&self) -> PassKind {
+        self.kind
+    }
+
+    /// Get semaphores to wait before the submission.
+    pub fn waits(&self) -> &[Wait<W>] {
+        &self.sync.acquire.wait
+    }
+
+    /// Get present images which acquisition must be waited before the submission.
+    pub fn wait_acquired(&self) -> &[Wait<Id<Image>>] {
+        &self.sync.acquire.wait_acquired
+    }
+
+    /// Get commands to record before main commands of the submission.
+    pub fn before(&self) -> &[BarrierRecord<'a>] {
+        &self.before
+    }
+
+    /// Get semaphores to signal after commands from `before`.
+    /// Non-empty list requires to submit commands from `before` in separate batch.
+    pub fn early_signals(&self) -> &[Signal<S>] {
+        &self.sync.acquire.signal
+    }
+
+    /// Get semaphores to wait before commands from `after`.
+    /// Non-empty list requires to submit commands from `after` in separate batch.
+    pub fn late_waits(&self) -> &[Wait<W>] {
+        &self.sync.release.wait
+    }
+
+    /// Get commands to record after main commands of the submission.
+    /// Those include ownership releases and transitions to final states.
+    pub fn after(&self) -> &[BarrierRecord<'a>] {
+        &self.after
+    }
+
+    /// Get semaphores to signal after the submission.
+    pub fn signals(&self) -> &[Signal<S>] {
+        &self.sync.release.signal
+    }
+
+    /// Get present images that can be presented after the submission.
+    pub fn signal_present(&self) -> &[Signal<Id<Image>>] {
+        &self.sync.release.signal_present
+    }
+
+    /// Get index of the fence to signal with the submission.
+    /// See `SyncOptions::fences`.
+    pub fn fence(&self) -> Option<FenceId> {
+        self.sync.fence
+    }
+
+    /// Get self-dependencies of images the submission accesses as feedback loops.
+    /// See `ImageUse::FeedbackLoop`.
+    pub fn feedback_loops(&self) -> &'a [FeedbackLoop] {
+        &self.sync.feedback_loops
+    }
+
+    /// Get synchronization of the submission as stored in the schedule.
+    pub fn sync(&self) -> &'a SyncData<S, W> {
+        self.sync
+    }
+
+    /// Express self-dependencies of feedback loops as single subpass self-dependency.
+    /// Returns `None` if the submission accesses no image as feedback loop.
+    /// The dependency is by region if all of them are. It has no layout transitions.
+    pub fn as_self_dependency(&self) -> Option<SubpassDependency> {
+        let (first, rest) = self.sync.feedback_loops.split_first()?;
+        let start = SubpassDependency {
+            stages: first.states.start.stages..first.states.end.stages,
+            accesses: first.states.start.access..first.states.end.access,
+            by_region: first.by_region,
+            layouts: Vec::new(),
+        };
+        Some(rest.iter().fold(start, |mut dependency, feedback_loop| {
+            let states = &feedback_loop.states;
+            dependency.stages.start |= states.start.stages;
+            dependency.stages.end |= states.end.stages;
+            dependency.accesses.start |= states.start.access;
+            dependency.accesses.end |= states.end.access;
+            dependency.by_region &= feedback_loop.by_region;
+            dependency
+        }))
+    }
+
+    /// Express pipeline barriers adjacent to the render pass as subpass dependencies.
+    /// Returns `None` if the pass isn't `PassKind::RenderPass` or neither barrier can be
+    /// expressed. Barrier can be expressed if it
+    ///
+    /// * has no buffer barriers, as subpass dependencies have only image accesses,
+    /// * transfers no ownership,
+    /// * transitions layouts only of whole images used by the pass as attachments,
+    /// * is not separated from the render pass by semaphore operations,
+    /// * needs not be recorded when main commands of the submission are skipped.
+    ///
+    /// Unless `allow_global` is set, the dependency must be by region as well.
+    pub fn as_external_dependency(&self, allow_global: bool) -> Option<ExternalDependency> {
+        if self.kind != PassKind::RenderPass {
+            return None;
+        }
+        let begin = if self.sync.acquire.signal.is_empty() {
+            subpass_dependency(&self.sync.acquire, true, allow_global)
+        } else {
+            None
+        };
+        let end = if self.sync.release.wait.is_empty() {
+            subpass_dependency(&self.sync.release, false, allow_global)
+        } else {
+            None
+        };
+        if begin.is_none() && end.is_none() {
+            return None;
+        }
+        Some(ExternalDependency { begin, end })
+    }
+}
+
+/// Express pipeline barrier of the guard as subpass dependency.
+/// `begin` is set for the acquire side of the render pass.
+fn subpass_dependency<S, W>(
+    guard: &Guard<S, W>,
+    begin: bool,
+    allow_global: bool,
+) -> Option<SubpassDependency> {
+    if guard.must_record_even_if_skipped
+        || !guard.buffers.is_empty()
+        || !guard.buffer_parts.is_empty()
+    {
+        return None;
+    }
+    let barrier = guard.pipeline_barrier()?;
+
+    let mut src = PipelineStage::empty();
+    let mut dst = PipelineStage::empty();
+    let mut accesses = ImageAccess::empty()..ImageAccess::empty();
+    let mut layouts = Vec::new();
+    for &(id, barrier) in &barrier.images {
+        if barrier.queues.is_some() {
+            return None;
+        }
+        let states = &barrier.states;
+        if states.start.layout != states.end.layout {
+            let own = if begin { &states.end } else { &states.start };
+            if barrier.range.is_some() || !own.access.intersects(attachment_access()) {
+                return None;
+            }
+            layouts.push((id, states.start.layout..states.end.layout));
+        }
+        src |= states.start.stages;
+        dst |= states.end.stages;
+        accesses.start |= states.start.access;
+        accesses.end |= states.end.access;
+    }
+
+    let framebuffer = PipelineStage::FRAGMENT_SHADER
+        | PipelineStage::EARLY_FRAGMENT_TESTS
+        | PipelineStage::LATE_FRAGMENT_TESTS
+        | PipelineStage::COLOR_ATTACHMENT_OUTPUT;
+    let by_region = framebuffer.contains(src) && framebuffer.contains(dst);
+    if !by_region && !allow_global {
+        return None;
+    }
+    Some(SubpassDependency {
+        stages: barrier.stages,
+        accesses,
+        by_region,
+        layouts,
+    })
+}
+
+/// Synchronization of submissions of one queue in submission order.
+#[derive(Clone, Debug)]
+pub struct QueueInfo<'a, S: 'a, W: 'a> {
+    id: QueueId,
+    frame_begin: Option<PipelineBarrier<'a>>,
+    submissions: Vec<SubmissionInfo<'a, S, W>>,
+}
+
+impl<'a, S, W> QueueInfo<'a, S, W> {
+    /// Get id of the queue.
+    pub fn id(&self) -> QueueId {
+        self.id
+    }
+
+    /// Get barriers hoisted to the beginning of the frame.
+    /// Those must be recorded before the first submission of the queue, including its `before`.
+    /// Or `None` if no barriers were hoisted.
+    /// See `SyncOptions::hoist_initial_transitions`.
+    pub fn frame_begin(&self) -> Option<&PipelineBarrier<'a>> {
+        self.frame_begin.as_ref()
+    }
+
+    /// Get synchronization of submissions in submission order.
+    pub fn submissions(&self) -> &[SubmissionInfo<'a, S, W>] {
+        &self.submissions
+    }
+
+    /// Iterate over synchronization of submissions in submission order.
+    pub fn iter(&self) -> SliceIter<'_, SubmissionInfo<'a, S, W>> {
+        self.submissions.iter()
+    }
+}
+
+impl<'a, 'b, S, W> IntoIterator for &'b QueueInfo<'a, S, W> {
+    type Item = &'b SubmissionInfo<'a, S, W>;
+    type IntoIter = SliceIter<'b, SubmissionInfo<'a, S, W>>;
+
+    fn into_iter(self) -> SliceIter<'b, SubmissionInfo<'a, S, W>> {
+        self.iter()
+    }
+}
+
+/// View of synchronized schedule for command recording.
+///
+/// Queues are sorted by family id and then by index.
+/// Fences are indexed from `0` to `fence_count` in the same order as
+/// `FrameSync` indexes fences of the frame slot.
+#[derive(Clone, Debug)]
+pub struct ScheduleWalker<'a, S: 'a, W: 'a> {
+    queues: Vec<QueueInfo<'a, S, W>>,
+    fence_count: usize,
+}
+
+impl<'a, S, W> ScheduleWalker<'a, S, W> {
+    /// Gather synchronization of all submissions of the schedule.
+    pub fn new(schedule: &'a Schedule<SyncData<S, W>>) -> Self {
+        let mut families: Vec<_> = schedule.iter().collect();
+        families.sort_by_key(|family| family.id());
+
+        let mut queues = Vec::new();
+        for family in families {
+            for queue in family {
+                let submissions: Vec<_> = queue
+                    .iter()
+                    .map(|(sid, submission)| {
+                        SubmissionInfo::new(
+                            sid,
+                            submission.pass(),
+                            submission.meta(),
+                            submission.kind(),
+                            submission.sync(),
+                        )
+                    })
+                    .collect();
+                let frame_begin = queue
+                    .iter()
+                    .next()
+                    .and_then(|(_, submission)| submission.sync().frame_begin.pipeline_barrier());
+                queues.push(QueueInfo {
+                    id: queue.id(),
+                    frame_begin,
+                    submissions,
+                });
+            }
+        }
+
+        ScheduleWalker {
+            queues,
+            fence_count: fence_count(schedule),
+        }
+    }
+
+    /// Get number of fences signaled by the schedule.
+    pub fn fence_count(&self) -> usize {
+        self.fence_count
+    }
+
+    /// Get synchronization of submissions of the queue.
+    pub fn queue(&self, qid: QueueId) -> Option<&QueueInfo<'a, S, W>> {
+        self.queues.iter().find(|queue| queue.id == qid)
+    }
+
+    /// Iterate over queues of the schedule.
+    pub fn queues(&self) -> SliceIter<'_, QueueInfo<'a, S, W>> {
+        self.queues.iter()
+    }
+}
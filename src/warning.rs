@@ -0,0 +1,410 @@
+//!
+//! This module provides warnings about passes and chains that are legal but likely not intended.
+//! Those never prevent building `Chains`.
+//!
+
+use alloc::vec::Vec;
+use collections::FnvHashMap;
+use core::fmt::{self, Display};
+use hal::buffer::Access as BufferAccess;
+use hal::image::{Access as ImageAccess, Layout as ImageLayout};
+use hal::pso::PipelineStage;
+use hal::queue::QueueFamilyId;
+
+use chain::Chain;
+use collect::Chains;
+use pass::{ImageUse, Pass, PassId, PassKind};
+use resource::{Access, Buffer, Id, Image, Resource};
+use schedule::{Schedule, Submission};
+use Pick;
+
+/// Suspicious but legal declaration or chain found during collection of `Chains`.
+/// See `collect::collect_with_warnings`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ChainWarning {
+    /// Distinct uses of the image by the pass were merged into `General` layout,
+    /// which is slower than optimal layout of at least one of them.
+    /// Only uses declared with `PassBuilder::image_use` are checked.
+    GeneralLayout {
+        /// Id of the pass.
+        pass: PassId,
+        /// Id of the image.
+        id: Id<Image>,
+        /// First pair of uses that degrade the layout, in declaration order.
+        uses: (ImageUse, ImageUse),
+    },
+
+    /// Pass declared buffer state with access types none of its stages performs.
+    /// Collection fails with `CollectError::InvalidBufferStateDeclaration`
+    /// unless `lenient-states` feature is enabled.
+    InvalidBufferState {
+        /// Id of the pass.
+        pass: PassId,
+        /// Id of the buffer.
+        id: Id<Buffer>,
+        /// Access types no declared stage performs.
+        access: BufferAccess,
+        /// Declared stages.
+        stages: PipelineStage,
+    },
+
+    /// Pass declared image state with access types none of its stages performs.
+    /// Collection fails with `CollectError::InvalidImageStateDeclaration`
+    /// unless `lenient-states` feature is enabled.
+    InvalidImageState {
+        /// Id of the pass.
+        pass: PassId,
+        /// Id of the image.
+        id: Id<Image>,
+        /// Access types no declared stage performs.
+        access: ImageAccess,
+        /// Declared stages.
+        stages: PipelineStage,
+    },
+
+    /// Content the pass writes to the buffer is never read by later passes.
+    /// Buffers read by the host after schedule execution or used outside of it aren't reported.
+    UnreadBufferWrite {
+        /// Id of the pass with the lowest id in the link of the last write.
+        pass: PassId,
+        /// Id of the buffer.
+        id: Id<Buffer>,
+        /// Access types of the link.
+        access: BufferAccess,
+        /// Stages of the link.
+        stages: PipelineStage,
+    },
+
+    /// Content the pass writes to the image is never read by later passes.
+    /// Images presented, read by the host after schedule execution or used outside of it
+    /// aren't reported.
+    UnreadImageWrite {
+        /// Id of the pass with the lowest id in the link of the last write.
+        pass: PassId,
+        /// Id of the image.
+        id: Id<Image>,
+        /// Access types of the link.
+        access: ImageAccess,
+        /// Layout of the link.
+        layout: ImageLayout,
+        /// Stages of the link.
+        stages: PipelineStage,
+    },
+
+    /// Layout of the image goes from `General` to another one and back, or vice versa,
+    /// in three consecutive links. Each of the changes is a layout transition.
+    LayoutBounce {
+        /// Ids of the passes with the lowest id in each of the links.
+        passes: (PassId, PassId, PassId),
+        /// Id of the image.
+        id: Id<Image>,
+        /// Layout of the first and the last link followed by layout of the middle one.
+        layouts: (ImageLayout, ImageLayout),
+    },
+
+    /// Buffer is transferred to another queue family and back in three consecutive links.
+    /// Each of the transfers requires waiting on a semaphore.
+    BufferRoundTrip {
+        /// Ids of the passes with the lowest id in each of the links.
+        passes: (PassId, PassId, PassId),
+        /// Id of the buffer.
+        id: Id<Buffer>,
+        /// Family of the first and the last link followed by family of the middle one.
+        families: (QueueFamilyId, QueueFamilyId),
+    },
+
+    /// Image is transferred to another queue family and back in three consecutive links.
+    /// Each of the transfers requires waiting on a semaphore.
+    ImageRoundTrip {
+        /// Ids of the passes with the lowest id in each of the links.
+        passes: (PassId, PassId, PassId),
+        /// Id of the image.
+        id: Id<Image>,
+        /// Family of the first and the last link followed by family of the middle one.
+        families: (QueueFamilyId, QueueFamilyId),
+    },
+}
+
+impl ChainWarning {
+    /// Key warnings are sorted by: pass, then resource with buffers first.
+    /// Warnings spanning several passes are sorted by the middle one.
+    fn sort_key(&self) -> (PassId, u8, usize) {
+        match *self {
+            ChainWarning::InvalidBufferState { pass, id, .. }
+            | ChainWarning::UnreadBufferWrite { pass, id, .. }
+            | ChainWarning::BufferRoundTrip {
+                passes: (_, pass, _),
+                id,
+                ..
+            } => (pass, 0, id.index()),
+            ChainWarning::InvalidImageState { pass, id, .. }
+            | ChainWarning::GeneralLayout { pass, id, .. }
+            | ChainWarning::UnreadImageWrite { pass, id, .. }
+            | ChainWarning::LayoutBounce {
+                passes: (_, pass, _),
+                id,
+                ..
+            }
+            | ChainWarning::ImageRoundTrip {
+                passes: (_, pass, _),
+                id,
+                ..
+            } => (pass, 1, id.index()),
+        }
+    }
+}
+
+impl Display for ChainWarning {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ChainWarning::GeneralLayout {
+                pass,
+                id,
+                uses: (ref left, ref right),
+            } => write!(
+                fmt,
+                "Uses {:?} and {:?} of image {:?} in pass {:?} degrade its layout to General",
+                left, right, id, pass
+            ),
+            ChainWarning::InvalidBufferState {
+                pass,
+                id,
+                access,
+                stages,
+            } => write!(
+                fmt,
+                "Buffer {:?} in pass {:?} has access {:?} not performed at stages {:?}",
+                id, pass, access, stages
+            ),
+            ChainWarning::InvalidImageState {
+                pass,
+                id,
+                access,
+                stages,
+            } => write!(
+                fmt,
+                "Image {:?} in pass {:?} has access {:?} not performed at stages {:?}",
+                id, pass, access, stages
+            ),
+            ChainWarning::UnreadBufferWrite {
+                pass,
+                id,
+                access,
+                stages,
+            } => write!(
+                fmt,
+                "Buffer {:?} written by pass {:?} with access {:?} at stages {:?} is never read",
+                id, pass, access, stages
+            ),
+            ChainWarning::UnreadImageWrite {
+                pass,
+                id,
+                access,
+                layout,
+                stages,
+            } => write!(
+                fmt,
+                "Image {:?} written by pass {:?} with access {:?} in layout {:?} at stages {:?} \
+                 is never read",
+                id, pass, access, layout, stages
+            ),
+            ChainWarning::LayoutBounce {
+                passes: (first, middle, last),
+                id,
+                layouts: (outer, inner),
+            } => write!(
+                fmt,
+                "Image {:?} goes from {:?} to {:?} and back in passes {:?}, {:?} and {:?}",
+                id, outer, inner, first, middle, last
+            ),
+            ChainWarning::BufferRoundTrip {
+                passes: (first, middle, last),
+                id,
+                families: (home, away),
+            } => write!(
+                fmt,
+                "Buffer {:?} goes from family {:?} to {:?} and back in passes {:?}, {:?} and {:?}",
+                id, home, away, first, middle, last
+            ),
+            ChainWarning::ImageRoundTrip {
+                passes: (first, middle, last),
+                id,
+                families: (home, away),
+            } => write!(
+                fmt,
+                "Image {:?} goes from family {:?} to {:?} and back in passes {:?}, {:?} and {:?}",
+                id, home, away, first, middle, last
+            ),
+        }
+    }
+}
+
+/// Find suspicious declarations of passes.
+/// Warnings are sorted by pass and then by resource, buffers first.
+pub fn declaration_warnings(passes: &[Pass]) -> Vec<ChainWarning> {
+    let mut warnings = Vec::new();
+    for pass in passes {
+        for (id, state) in pass.unsupported_buffer_states() {
+            warnings.push(ChainWarning::InvalidBufferState {
+                pass: pass.id,
+                id,
+                access: state.unsupported_access(),
+                stages: state.stages,
+            });
+        }
+        for (id, state) in pass.unsupported_image_states() {
+            warnings.push(ChainWarning::InvalidImageState {
+                pass: pass.id,
+                id,
+                access: state.unsupported_access(),
+                stages: state.stages,
+            });
+        }
+    }
+    for pass in passes.iter().filter(|pass| pass.kind != PassKind::SparseBind) {
+        let mut images: Vec<_> = pass.image_uses.iter().collect();
+        images.sort_by_key(|&(id, _)| *id);
+        for (&id, uses) in images {
+            let mut degraded = uses.iter().enumerate().filter_map(|(index, left)| {
+                uses[index + 1..]
+                    .iter()
+                    .find(|right| left.degrades_layout(right))
+                    .map(|right| (*left, *right))
+            });
+            if let Some(uses) = degraded.next() {
+                warnings.push(ChainWarning::GeneralLayout {
+                    pass: pass.id,
+                    id,
+                    uses,
+                });
+            }
+        }
+    }
+    warnings.sort_by_key(ChainWarning::sort_key);
+    warnings
+}
+
+/// Find suspicious chains of buffers and images.
+/// Only chains of whole resources in `buffers` and `images` are checked.
+/// Each kind of warning is reported once per resource, for the earliest links.
+/// Warnings are sorted by pass and then by resource, buffers first.
+pub fn chain_warnings<S>(chains: &Chains<S>) -> Vec<ChainWarning> {
+    let mut warnings = Vec::new();
+
+    let buffer_passes = link_passes::<Buffer, S>(&chains.schedule);
+    for (&id, chain) in &chains.buffers {
+        let pass = |index| buffer_passes[&(id, index)];
+        if let Some(index) = unread_write(chain) {
+            let state = chain.link(index).state();
+            warnings.push(ChainWarning::UnreadBufferWrite {
+                pass: pass(index),
+                id,
+                access: state.access,
+                stages: state.stages,
+            });
+        }
+        if let Some(index) = round_trip(chain) {
+            warnings.push(ChainWarning::BufferRoundTrip {
+                passes: (pass(index), pass(index + 1), pass(index + 2)),
+                id,
+                families: (chain.link(index).family(), chain.link(index + 1).family()),
+            });
+        }
+    }
+
+    let image_passes = link_passes::<Image, S>(&chains.schedule);
+    for (&id, chain) in &chains.images {
+        let pass = |index| image_passes[&(id, index)];
+        if let Some(index) = unread_write(chain) {
+            let state = chain.link(index).state();
+            warnings.push(ChainWarning::UnreadImageWrite {
+                pass: pass(index),
+                id,
+                access: state.access,
+                layout: state.layout,
+                stages: state.stages,
+            });
+        }
+        if let Some(index) = layout_bounce(chain) {
+            warnings.push(ChainWarning::LayoutBounce {
+                passes: (pass(index), pass(index + 1), pass(index + 2)),
+                id,
+                layouts: (
+                    chain.link(index).state().layout,
+                    chain.link(index + 1).state().layout,
+                ),
+            });
+        }
+        if let Some(index) = round_trip(chain) {
+            warnings.push(ChainWarning::ImageRoundTrip {
+                passes: (pass(index), pass(index + 1), pass(index + 2)),
+                id,
+                families: (chain.link(index).family(), chain.link(index + 1).family()),
+            });
+        }
+    }
+
+    warnings.sort_by_key(ChainWarning::sort_key);
+    warnings
+}
+
+/// Find pass with the lowest id among submissions of each link of the resources.
+fn link_passes<R, S>(schedule: &Schedule<S>) -> FnvHashMap<(Id<R>, usize), PassId>
+where
+    R: Resource,
+    Submission<S>: Pick<R, Target = FnvHashMap<Id<R>, usize>>,
+{
+    let mut passes = FnvHashMap::default();
+    for family in schedule.iter() {
+        for queue in family {
+            for (_, submission) in queue.iter() {
+                for (&id, &index) in submission.pick() {
+                    let pass = passes.entry((id, index)).or_insert_with(|| submission.pass());
+                    if submission.pass() < *pass {
+                        *pass = submission.pass();
+                    }
+                }
+            }
+        }
+    }
+    passes
+}
+
+/// Find index of the link of the last write if no later link reads the resource.
+fn unread_write<R: Resource>(chain: &Chain<R>) -> Option<usize> {
+    let info = chain.info();
+    if info.external || info.present || info.host_read_after || info.export.is_some() {
+        return None;
+    }
+    let links = chain.links();
+    let last = links
+        .iter()
+        .rposition(|link| link.state().access.is_write())?;
+    if links[last + 1..]
+        .iter()
+        .any(|link| link.state().access.is_read())
+    {
+        None
+    } else {
+        Some(last)
+    }
+}
+
+/// Find index of the first of three consecutive links
+/// where the middle one is owned by another family.
+fn round_trip<R: Resource>(chain: &Chain<R>) -> Option<usize> {
+    chain.links().windows(3).position(|links| {
+        links[0].family() == links[2].family() && links[0].family() != links[1].family()
+    })
+}
+
+/// Find index of the first of three consecutive links
+/// where layout goes from `General` to another one and back, or vice versa.
+fn layout_bounce(chain: &Chain<Image>) -> Option<usize> {
+    chain.links().windows(3).position(|links| {
+        let (outer, inner) = (links[0].state().layout, links[1].state().layout);
+        outer == links[2].state().layout
+            && outer != inner
+            && (outer == ImageLayout::General || inner == ImageLayout::General)
+    })
+}